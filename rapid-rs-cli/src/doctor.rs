@@ -0,0 +1,240 @@
+//! `rapid doctor`: checks the local environment a rapid-rs app needs
+
+use rapid_rs::config::AppConfig;
+use rapid_rs::database::{pending_migrations, MigrationConfig, PgPool};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// What to do about a warning or failure
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: Status::Ok, detail: detail.into(), fix: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Warn, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn print(&self) {
+        let icon = match self.status {
+            Status::Ok => "✅",
+            Status::Warn => "⚠️ ",
+            Status::Fail => "❌",
+        };
+        println!("{} {}: {}", icon, self.name, self.detail);
+        if let Some(fix) = &self.fix {
+            println!("   → {}", fix);
+        }
+    }
+}
+
+/// Run every check, failing if any of them did
+pub async fn run(migrations: &str, required: &[String]) -> anyhow::Result<()> {
+    println!("🩺 Checking your rapid-rs environment...\n");
+
+    let dotenv = read_env_file(Path::new(".env"));
+    let (config, config_check) = match AppConfig::load() {
+        Ok(config) => (config, Check::ok("Config", "config/default.toml, config/local.toml and APP__* variables load")),
+        Err(error) => (
+            AppConfig::default(),
+            Check::fail("Config", error.to_string(), "Fix the key named above in config/*.toml or its APP__* variable"),
+        ),
+    };
+
+    let database_url = lookup("DATABASE_URL", &dotenv).unwrap_or_else(|| config.database.url.clone());
+    let mut checks = vec![config_check];
+    checks.extend(check_database(&database_url, migrations).await);
+    checks.push(check_redis(lookup("REDIS_URL", &dotenv)).await);
+    checks.push(check_env_vars(required, &dotenv));
+    checks.push(check_port(&config.server.host, config.server.port).await);
+
+    for check in &checks {
+        check.print();
+    }
+
+    let failed = checks.iter().filter(|check| matches!(check.status, Status::Fail)).count();
+    let warned = checks.iter().filter(|check| matches!(check.status, Status::Warn)).count();
+    println!("\n{} passed, {} warning(s), {} failed", checks.len() - failed - warned, warned, failed);
+    if failed > 0 {
+        anyhow::bail!("{} check(s) failed", failed);
+    }
+
+    Ok(())
+}
+
+async fn check_database(database_url: &str, migrations: &str) -> Vec<Check> {
+    let pool = match tokio::time::timeout(TIMEOUT, PgPool::connect(database_url)).await {
+        Ok(Ok(pool)) => pool,
+        Ok(Err(error)) => {
+            return vec![Check::fail(
+                "Database",
+                format!("can't connect to {}: {}", redact(database_url), error),
+                "Start Postgres (e.g. `docker run -p 5432:5432 -e POSTGRES_PASSWORD=postgres postgres`) \
+                 or point DATABASE_URL / APP__DATABASE__URL at a running one",
+            )]
+        }
+        Err(_) => {
+            return vec![Check::fail(
+                "Database",
+                format!("no answer from {} within {}s", redact(database_url), TIMEOUT.as_secs()),
+                "Check the host and port in DATABASE_URL and that no firewall is in the way",
+            )]
+        }
+    };
+    let database = Check::ok("Database", format!("connected to {}", redact(database_url)));
+
+    let config = MigrationConfig::new().migrations_path(migrations);
+    let migrations = match pending_migrations(&pool, &config).await {
+        Ok(pending) if pending.is_empty() => Check::ok("Migrations", "up to date"),
+        Ok(pending) => Check::warn(
+            "Migrations",
+            format!("{} pending: {}", pending.len(), pending.join(", ")),
+            "Run the app (migrations run on startup) or `sqlx migrate run`",
+        ),
+        Err(error) => Check::fail("Migrations", error.to_string(), format!("Check the files in {}", migrations)),
+    };
+    pool.close().await;
+
+    vec![database, migrations]
+}
+
+async fn check_redis(url: Option<String>) -> Check {
+    let Some(url) = url else {
+        return Check::ok("Redis", "not configured (REDIS_URL unset), skipped");
+    };
+    let address = redis_address(&url);
+
+    let ping = async {
+        let mut stream = TcpStream::connect(&address).await?;
+        stream.write_all(b"*1\r\n$4\r\nPING\r\n").await?;
+        let mut reply = [0u8; 64];
+        let read = stream.read(&mut reply).await?;
+        Ok::<_, std::io::Error>(String::from_utf8_lossy(&reply[..read]).trim().to_string())
+    };
+    match tokio::time::timeout(TIMEOUT, ping).await {
+        Ok(Ok(reply)) if reply == "+PONG" => Check::ok("Redis", format!("{} answered PING", address)),
+        Ok(Ok(reply)) => Check::warn(
+            "Redis",
+            format!("{} replied {:?}", address, reply),
+            "Check the password in REDIS_URL",
+        ),
+        Ok(Err(error)) => Check::fail(
+            "Redis",
+            format!("can't connect to {}: {}", address, error),
+            "Start Redis (e.g. `docker run -p 6379:6379 redis`) or fix REDIS_URL",
+        ),
+        Err(_) => Check::fail(
+            "Redis",
+            format!("no answer from {} within {}s", address, TIMEOUT.as_secs()),
+            "Check the host and port in REDIS_URL",
+        ),
+    }
+}
+
+/// `host:port` from `redis://[user:pass@]host[:port][/db]`
+fn redis_address(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    let host = rest.split('/').next().unwrap_or(rest);
+    if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:6379", host)
+    }
+}
+
+/// Variables named with `--require` or in `.env.example` must be set
+fn check_env_vars(required: &[String], dotenv: &HashMap<String, String>) -> Check {
+    let mut names: Vec<String> = required.to_vec();
+    names.extend(read_env_file(Path::new(".env.example")).into_keys());
+    names.sort();
+    names.dedup();
+
+    let missing: Vec<&String> = names.iter().filter(|name| lookup(name, dotenv).is_none()).collect();
+    if names.is_empty() {
+        Check::ok("Env vars", "none required (no .env.example or --require)")
+    } else if missing.is_empty() {
+        Check::ok("Env vars", format!("all {} required variable(s) set", names.len()))
+    } else {
+        let missing: Vec<&str> = missing.iter().map(|name| name.as_str()).collect();
+        Check::fail(
+            "Env vars",
+            format!("missing {}", missing.join(", ")),
+            "Export them or add them to .env (see .env.example)",
+        )
+    }
+}
+
+async fn check_port(host: &str, port: u16) -> Check {
+    match tokio::net::TcpListener::bind((host, port)).await {
+        Ok(_) => Check::ok("Port", format!("{}:{} is free", host, port)),
+        Err(error) => Check::fail(
+            "Port",
+            format!("can't listen on {}:{}: {}", host, port, error),
+            format!(
+                "Stop whatever holds the port (`lsof -i :{}`) or set APP__SERVER__PORT to another one",
+                port
+            ),
+        ),
+    }
+}
+
+/// `KEY=value` lines, skipping blanks and comments
+fn read_env_file(path: &Path) -> HashMap<String, String> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.trim_start_matches("export ").split_once('=')?;
+            Some((key.trim().to_string(), value.trim().trim_matches('"').to_string()))
+        })
+        .collect()
+}
+
+fn lookup(name: &str, dotenv: &HashMap<String, String>) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .or_else(|| dotenv.get(name).cloned())
+        .filter(|value| !value.is_empty())
+}
+
+/// The URL without its password, for printing
+fn redact(url: &str) -> String {
+    match (url.find("://"), url.rfind('@')) {
+        (Some(scheme), Some(at)) if at > scheme => {
+            let credentials = &url[scheme + 3..at];
+            match credentials.split_once(':') {
+                Some((user, _)) => format!("{}{}:***{}", &url[..scheme + 3], user, &url[at..]),
+                None => url.to_string(),
+            }
+        }
+        _ => url.to_string(),
+    }
+}
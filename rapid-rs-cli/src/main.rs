@@ -1,8 +1,10 @@
 use clap::{Parser, Subcommand};
-use std::fs;
-use std::path::Path;
 use std::process::Command;
 
+mod doctor;
+mod replay;
+mod scaffold;
+
 #[derive(Parser)]
 #[command(name = "rapid")]
 #[command(about = "CLI tool for rapid-rs framework", long_about = None)]
@@ -21,220 +23,264 @@ enum Commands {
         /// Template to use (rest-api, graphql, grpc)
         #[arg(short, long, default_value = "rest-api")]
         template: String,
+
+        /// Where the data lives (default: postgres)
+        #[arg(long, value_enum)]
+        database: Option<scaffold::Database>,
+
+        /// Include JWT authentication (default: true)
+        #[arg(long)]
+        auth: Option<bool>,
+
+        /// Add a Dockerfile and docker-compose.yml (default: false)
+        #[arg(long)]
+        docker: Option<bool>,
+
+        /// CI workflow to generate (default: none)
+        #[arg(long, value_enum)]
+        ci: Option<scaffold::Ci>,
+
+        /// Don't ask; use defaults for options not given
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Run the project in development mode with hot reload
     Dev,
+
+    /// Check the database, migrations, Redis, env vars, port and config
+    Doctor {
+        /// Path to the migrations directory
+        #[arg(long, default_value = "./migrations")]
+        migrations: String,
+
+        /// Environment variable that must be set (repeatable), on top of
+        /// those listed in .env.example
+        #[arg(short, long = "require")]
+        required: Vec<String>,
+    },
+
+    /// Multi-tenant operations
+    Tenants {
+        #[command(subcommand)]
+        command: TenantCommands,
+    },
+
+    /// Read-model projection operations
+    Projections {
+        #[command(subcommand)]
+        command: ProjectionCommands,
+    },
+
+    /// Send captured requests to a dev instance to reproduce failures
+    Replay {
+        /// Captures endpoint of the app (e.g. https://api.example.com/admin/captures)
+        /// or a JSON file of captures
+        source: String,
+
+        /// Only replay this capture (default: all)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Base URL of the instance to replay against
+        #[arg(short, long, default_value = "http://localhost:3000")]
+        target: String,
+
+        /// Extra header as 'Name: value' (repeatable), e.g. credentials for the target
+        #[arg(short = 'H', long = "header")]
+        headers: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectionCommands {
+    /// Show each projection's checkpoint
+    Status {
+        /// Database holding the projection checkpoints (defaults to DATABASE_URL)
+        #[arg(long)]
+        database_url: Option<String>,
+    },
+
+    /// Ask running projectors to reset a projection and replay the change log
+    Rebuild {
+        /// Projection name
+        name: String,
+
+        /// Database holding the projection checkpoints (defaults to DATABASE_URL)
+        #[arg(long)]
+        database_url: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TenantCommands {
+    /// Run migrations for every active tenant (or only --tenant ones)
+    Migrate {
+        /// Database holding the tenants table (defaults to DATABASE_URL)
+        #[arg(long)]
+        database_url: Option<String>,
+
+        /// Only migrate these tenants (repeatable)
+        #[arg(short, long = "tenant")]
+        tenants: Vec<String>,
+
+        /// Path to the migrations directory
+        #[arg(long, default_value = "./migrations")]
+        migrations: String,
+
+        /// Tenants migrated at the same time
+        #[arg(short, long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Migrate one schema per tenant in the shared database instead of
+        /// each tenant's own database_url
+        #[arg(long)]
+        schema_per_tenant: bool,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::New { name, template } => {
-            create_project(&name, &template)?;
+        Commands::New { name, template, database, auth, docker, ci, yes } => {
+            let options = scaffold::ProjectOptions::resolve(database, auth, docker, ci, !yes)?;
+            scaffold::create_project(&name, &template, options)?;
         }
         Commands::Dev => {
             run_dev_mode()?;
         }
+        Commands::Doctor { migrations, required } => {
+            tokio::runtime::Runtime::new()?.block_on(doctor::run(&migrations, &required))?;
+        }
+        Commands::Tenants { command } => match command {
+            TenantCommands::Migrate {
+                database_url,
+                tenants,
+                migrations,
+                concurrency,
+                schema_per_tenant,
+            } => {
+                let database_url = resolve_database_url(database_url)?;
+                tokio::runtime::Runtime::new()?.block_on(migrate_tenants(
+                    &database_url,
+                    tenants,
+                    migrations,
+                    concurrency,
+                    schema_per_tenant,
+                ))?;
+            }
+        },
+        Commands::Projections { command } => {
+            let runtime = tokio::runtime::Runtime::new()?;
+            match command {
+                ProjectionCommands::Status { database_url } => {
+                    runtime.block_on(projection_status(&resolve_database_url(database_url)?))?;
+                }
+                ProjectionCommands::Rebuild { name, database_url } => {
+                    runtime.block_on(rebuild_projection(&resolve_database_url(database_url)?, &name))?;
+                }
+            }
+        }
+        Commands::Replay { source, id, target, headers } => {
+            tokio::runtime::Runtime::new()?.block_on(replay::run(&source, id.as_deref(), &target, &headers))?;
+        }
     }
 
     Ok(())
 }
 
-fn create_project(name: &str, template: &str) -> anyhow::Result<()> {
-    println!("🚀 Creating new rapid-rs project: {}", name);
-
-    if template != "rest-api" {
-        anyhow::bail!("Only 'rest-api' template is currently supported");
+fn resolve_database_url(database_url: Option<String>) -> anyhow::Result<String> {
+    match database_url {
+        Some(url) => Ok(url),
+        None => std::env::var("DATABASE_URL")
+            .map_err(|_| anyhow::anyhow!("Pass --database-url or set DATABASE_URL")),
     }
-
-    let project_path = Path::new(name);
-    if project_path.exists() {
-        anyhow::bail!("Directory '{}' already exists", name);
-    }
-
-    // Create project structure
-    fs::create_dir_all(project_path.join("src"))?;
-    fs::create_dir_all(project_path.join("config"))?;
-
-    // Create Cargo.toml
-    let cargo_toml = format!(
-        r#"[package]
-name = "{}"
-version = "0.1.0"
-edition = "2021"
-
-[dependencies]
-rapid-rs = "0.5"
-tokio = {{ version = "1", features = ["full"] }}
-serde = {{ version = "1.0", features = ["derive"] }}
-uuid = {{ version = "1.0", features = ["v4", "serde"] }}
-chrono = {{ version = "0.4", features = ["serde"] }}
-validator = {{ version = "0.18", features = ["derive"] }}
-"#,
-        name
-    );
-    fs::write(project_path.join("Cargo.toml"), cargo_toml)?;
-
-    // Create main.rs with full example
-    let main_rs = r#"use rapid_rs::prelude::*;
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
-
-#[derive(Serialize, Deserialize, Clone)]
-struct User {
-    id: Uuid,
-    email: String,
-    name: String,
-    created_at: DateTime<Utc>,
 }
 
-#[derive(Deserialize, Validate)]
-struct CreateUserRequest {
-    #[validate(email(message = "Invalid email format"))]
-    email: String,
+async fn migrate_tenants(
+    database_url: &str,
+    only: Vec<String>,
+    migrations: String,
+    concurrency: usize,
+    schema_per_tenant: bool,
+) -> anyhow::Result<()> {
+    use rapid_rs::database::MigrationConfig;
+    use rapid_rs::multi_tenancy::{
+        PostgresTenantResolver, TenantId, TenantMigrationStatus, TenantMigrator,
+    };
 
-    #[validate(length(min = 2, max = 100))]
-    name: String,
-}
+    let pool = rapid_rs::database::PgPool::connect(database_url).await?;
+    let tenants = PostgresTenantResolver::new(pool.clone()).list_tenants().await?;
+
+    let mut migrator = TenantMigrator::new(MigrationConfig::new().migrations_path(migrations))
+        .with_concurrency(concurrency)
+        .on_progress(|progress| match &progress.status {
+            TenantMigrationStatus::Started => {}
+            TenantMigrationStatus::Succeeded => {
+                println!("[{}/{}] ✅ {}", progress.completed, progress.total, progress.tenant_id)
+            }
+            TenantMigrationStatus::Skipped(reason) => println!(
+                "[{}/{}] ⏭️  {} ({})",
+                progress.completed, progress.total, progress.tenant_id, reason
+            ),
+            TenantMigrationStatus::Failed(error) => println!(
+                "[{}/{}] ❌ {}: {}",
+                progress.completed, progress.total, progress.tenant_id, error
+            ),
+        });
+    if schema_per_tenant {
+        migrator = migrator.schema_per_tenant(pool);
+    }
+
+    println!("🏢 Migrating {} tenant(s)...", if only.is_empty() { tenants.len() } else { only.len() });
 
-type Database = Arc<Mutex<HashMap<Uuid, User>>>;
-
-async fn create_user(
-    State(db): State<Database>,
-    ValidatedJson(payload): ValidatedJson<CreateUserRequest>,
-) -> ApiResult<User> {
-    let user = User {
-        id: Uuid::new_v4(),
-        email: payload.email,
-        name: payload.name,
-        created_at: Utc::now(),
+    let report = if only.is_empty() {
+        migrator.run(&tenants).await
+    } else {
+        let ids: Vec<TenantId> = only.into_iter().map(TenantId::new).collect();
+        migrator.run_subset(&tenants, &ids).await
     };
 
-    db.lock().unwrap().insert(user.id, user.clone());
-    Ok(Json(user))
-}
+    println!(
+        "\n{} succeeded, {} skipped, {} failed",
+        report.succeeded.len(),
+        report.skipped.len(),
+        report.failed.len()
+    );
 
-async fn list_users(State(db): State<Database>) -> ApiResult<Vec<User>> {
-    let users: Vec<User> = db.lock().unwrap().values().cloned().collect();
-    Ok(Json(users))
-}
+    if !report.is_success() {
+        anyhow::bail!("Migrations failed for {} tenant(s)", report.failed.len());
+    }
 
-async fn get_user(
-    State(db): State<Database>,
-    Path(id): Path<Uuid>,
-) -> Result<Json<User>, ApiError> {
-    let user = db
-        .lock()
-        .unwrap()
-        .get(&id)
-        .ok_or_else(|| ApiError::NotFound(format!("User {} not found", id)))?
-        .clone();
-    Ok(Json(user))
+    Ok(())
 }
 
-fn routes() -> Router<Database> {
-    Router::new()
-        .route("/users", post(create_user))
-        .route("/users", get(list_users))
-        .route("/users/:id", get(get_user))
-}
+async fn projection_status(database_url: &str) -> anyhow::Result<()> {
+    let pool = rapid_rs::database::PgPool::connect(database_url).await?;
+    let projections = rapid_rs::events::projections::projection_status(&pool).await?;
 
-#[tokio::main]
-async fn main() {
-    let db: Database = Arc::new(Mutex::new(HashMap::new()));
+    if projections.is_empty() {
+        println!("No projections have run against this database");
+    }
+    for projection in projections {
+        println!(
+            "{}  tx {} seq {}  updated {}{}",
+            projection.name,
+            projection.position.tx_id,
+            projection.position.seq,
+            projection.updated_at.format("%Y-%m-%d %H:%M:%S"),
+            if projection.rebuild_requested { "  (rebuild pending)" } else { "" }
+        );
+    }
 
-    App::new()
-        .auto_configure()
-        .mount(routes().with_state(db))
-        .run()
-        .await
-        .unwrap();
+    Ok(())
 }
-"#;
-    fs::write(project_path.join("src/main.rs"), main_rs)?;
-
-    // Create config files
-    let default_config = r#"[server]
-host = "0.0.0.0"
-port = 3000
-
-[database]
-url = "postgres://localhost/rapid_rs"
-max_connections = 10
-"#;
-    fs::write(project_path.join("config/default.toml"), default_config)?;
-
-    let local_config = r#"# Override settings for local development
-# This file is gitignored by default
-
-[server]
-port = 3000
-"#;
-    fs::write(project_path.join("config/local.toml"), local_config)?;
-
-    // Create .gitignore
-    let gitignore = r#"/target
-/config/local.toml
-.env
-"#;
-    fs::write(project_path.join(".gitignore"), gitignore)?;
-
-    // Create README
-    let readme = format!(
-        r#"# {}
-
-A rapid-rs API project.
-
-## Getting Started
-
-```bash
-# Run the server
-cargo run
-
-# The server will start at http://localhost:3000
-# Swagger UI: http://localhost:3000/docs
-# Health check: http://localhost:3000/health
-```
-
-## API Endpoints
-
-- `POST /users` - Create a new user
-- `GET /users` - List all users
-- `GET /users/:id` - Get a user by ID
-
-## Configuration
-
-Configuration is loaded from:
-1. `config/default.toml` - Default settings
-2. `config/local.toml` - Local overrides (gitignored)
-3. Environment variables (prefixed with `APP__`)
-
-Example:
-```bash
-APP__SERVER__PORT=8080 cargo run
-```
-
-## Development
-
-```bash
-# Run with hot reload (requires cargo-watch)
-cargo install cargo-watch
-cargo watch -x run
-```
-"#,
-        name
-    );
-    fs::write(project_path.join("README.md"), readme)?;
-
-    println!("✅ Project created successfully!");
-    println!("\n📦 Next steps:");
-    println!("   cd {}", name);
-    println!("   cargo run");
-    println!("\n🌐 Your API will be available at:");
-    println!("   http://localhost:3000");
-    println!("   http://localhost:3000/docs (Swagger UI)");
+
+async fn rebuild_projection(database_url: &str, name: &str) -> anyhow::Result<()> {
+    let pool = rapid_rs::database::PgPool::connect(database_url).await?;
+    rapid_rs::events::projections::request_rebuild(&pool, name).await?;
+    println!("🔁 Requested rebuild of {}; running projectors will replay the change log", name);
 
     Ok(())
 }
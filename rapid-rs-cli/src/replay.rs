@@ -0,0 +1,74 @@
+//! `rapid replay`: sends captured requests to a dev instance
+
+use rapid_rs::http_client::HttpClient;
+use rapid_rs::replay::{replay, CapturedRequest};
+
+/// Replay the capture `id` (or every capture) from `source` against `target`
+///
+/// `source` is the app's captures endpoint, e.g.
+/// `https://api.example.com/admin/captures`, or a JSON file holding one
+/// capture or a list of them.
+pub async fn run(source: &str, id: Option<&str>, target: &str, headers: &[String]) -> anyhow::Result<()> {
+    let headers = headers
+        .iter()
+        .map(|header| match header.split_once(':') {
+            Some((name, value)) => Ok((name.trim().to_string(), value.trim().to_string())),
+            None => Err(anyhow::anyhow!("Invalid header '{}', expected 'Name: value'", header)),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let client = HttpClient::new();
+
+    let captures = load(&client, source, id, &headers).await?;
+    if captures.is_empty() {
+        anyhow::bail!("No captured requests found in {}", source);
+    }
+
+    for capture in &captures {
+        let result = replay(&client, capture, target, &headers)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let marker = if result.status == result.original_status { "✓" } else { "≠" };
+        println!(
+            "{} {} {} {}: {} originally, {} now",
+            marker, capture.id, capture.method, capture.uri, result.original_status, result.status
+        );
+        if result.status >= 500 && !result.body.is_empty() {
+            println!("    {}", result.body);
+        }
+    }
+    Ok(())
+}
+
+async fn load(
+    client: &HttpClient,
+    source: &str,
+    id: Option<&str>,
+    headers: &[(String, String)],
+) -> anyhow::Result<Vec<CapturedRequest>> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let url = match id {
+            Some(id) => format!("{}/{}", source.trim_end_matches('/'), id),
+            None => source.to_string(),
+        };
+        let mut request = client.get(&url);
+        // The captures endpoint usually needs the same credentials
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?.error_for_status()?;
+        return Ok(match id {
+            Some(_) => vec![response.json().await?],
+            None => response.json().await?,
+        });
+    }
+
+    let contents = std::fs::read_to_string(source)?;
+    let captures: Vec<CapturedRequest> = match serde_json::from_str(&contents) {
+        Ok(captures) => captures,
+        Err(_) => vec![serde_json::from_str(&contents)?],
+    };
+    Ok(captures
+        .into_iter()
+        .filter(|capture| id.is_none_or(|id| capture.id == id))
+        .collect())
+}
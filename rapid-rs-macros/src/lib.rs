@@ -1,10 +1,717 @@
 //! Procedural macros for rapid-rs
 //!
-//! This crate will contain derive macros and attribute macros for:
-//! - Route generation with OpenAPI annotations
-//! - Repository pattern implementations
-//! - Automatic CRUD generation
+//! - `#[derive(FromEnv)]`: bind environment variables into a typed config struct
+//! - `#[derive(AsyncValidate)]`: reference registered async validators from fields
+//! - `#[derive(SchemaConstraints)]`: publish `#[validate]` rules in the OpenAPI spec
+//! - `#[derive(ApiExample)]`: declare example payloads for the OpenAPI spec and tests
+//! - `#[api_handler(...)]`: document a handler's route in the OpenAPI spec
 //!
-//! Coming in Phase 2!
+//! Use them through the `rapid-rs` crate rather than depending on this one.
 
-// Placeholder for future proc macros
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, ItemFn, LitInt, LitStr, Type};
+
+/// Derive `rapid_rs::env::FromEnv` and a `Debug` impl that redacts secrets
+///
+/// Struct attributes:
+/// - `#[env(prefix = "APP_")]`: prepended to every derived variable name
+/// - `#[env(validate)]`: run `validator::Validate` after loading
+///
+/// Field attributes:
+/// - `#[env(name = "DATABASE_URL")]`: variable name (default: the field name upper-cased)
+/// - `#[env(default = "8080")]`: value used when the variable is unset
+/// - `#[env(secret)]`: never printed by `Debug` or in error reports
+///
+/// `Option<T>` fields are optional; every other field is required unless it
+/// has a default. Field types are parsed with `FromStr`.
+#[proc_macro_derive(FromEnv, attributes(env))]
+pub fn derive_from_env(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_env(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct StructOptions {
+    prefix: String,
+    validate: bool,
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<String>,
+    default: Option<String>,
+    secret: bool,
+}
+
+fn expand_from_env(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut options = StructOptions::default();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("env")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                options.prefix = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("validate") {
+                options.validate = true;
+            } else {
+                return Err(meta.error("expected `prefix` or `validate`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "FromEnv can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "FromEnv can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut reads = Vec::new();
+    let mut inits = Vec::new();
+    let mut debug_fields = Vec::new();
+    let mut var_names = Vec::new();
+
+    for field in fields {
+        let field_ident = field.ident.as_ref().expect("named field");
+        let field_name = field_ident.to_string().trim_start_matches("r#").to_string();
+
+        let mut field_options = FieldOptions::default();
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("env")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    field_options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    field_options.default = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("secret") {
+                    field_options.secret = true;
+                } else {
+                    return Err(meta.error("expected `name`, `default` or `secret`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let var = field_options
+            .name
+            .unwrap_or_else(|| format!("{}{}", options.prefix, field_name.to_uppercase()));
+        let secret = field_options.secret;
+
+        match option_inner(&field.ty) {
+            Some(inner) => {
+                if field_options.default.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        &field.ty,
+                        "`default` has no effect on an Option field",
+                    ));
+                }
+                reads.push(quote! {
+                    let #field_ident = reader.optional::<#inner>(#var, #secret);
+                });
+                inits.push(quote! { #field_ident });
+            }
+            None => {
+                let ty = &field.ty;
+                let default = match &field_options.default {
+                    Some(default) => quote! { ::core::option::Option::Some(#default) },
+                    None => quote! { ::core::option::Option::None },
+                };
+                reads.push(quote! {
+                    let #field_ident = reader.required::<#ty>(#var, #default, #secret);
+                });
+                inits.push(quote! {
+                    #field_ident: #field_ident.expect("checked by EnvReader::finish")
+                });
+            }
+        }
+
+        debug_fields.push(if secret {
+            quote! { .field(#field_name, &::rapid_rs::env::Redacted) }
+        } else {
+            quote! { .field(#field_name, &self.#field_ident) }
+        });
+        var_names.push(quote! { (#field_name, #var) });
+    }
+
+    let validate = if options.validate {
+        quote! { ::rapid_rs::env::validate(&config, &[#(#var_names),*])?; }
+    } else {
+        quote! {}
+    };
+    let struct_name = ident.to_string();
+
+    Ok(quote! {
+        impl #impl_generics ::rapid_rs::env::FromEnv for #ident #ty_generics #where_clause {
+            fn from_env_with(
+                lookup: &dyn ::core::ops::Fn(&str) -> ::core::option::Option<::std::string::String>,
+            ) -> ::core::result::Result<Self, ::rapid_rs::env::EnvError> {
+                let mut reader = ::rapid_rs::env::EnvReader::new(lookup);
+                #(#reads)*
+                reader.finish()?;
+                let config = Self { #(#inits),* };
+                #validate
+                ::core::result::Result::Ok(config)
+            }
+        }
+
+        impl #impl_generics ::core::fmt::Debug for #ident #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(#struct_name)
+                    #(#debug_fields)*
+                    .finish()
+            }
+        }
+    })
+}
+
+/// Derive `rapid_rs::validation::AsyncValidate`
+///
+/// Field attributes:
+/// - `#[validate_async(unique_email, country_code)]`: validators to run, by
+///   the name they are registered under in a `ValidatorRegistry`
+/// - `#[validate_async(..., field = "address.country")]`: JSON path of the
+///   value (default: the field name)
+///
+/// The rules only run for types added with `ValidatorRegistry::with_rules_for`.
+#[proc_macro_derive(AsyncValidate, attributes(validate_async))]
+pub fn derive_async_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_async_validate(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_async_validate(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "AsyncValidate can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "AsyncValidate can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut rules = Vec::new();
+    for field in fields {
+        let field_ident = field.ident.as_ref().expect("named field");
+        let mut path = field_ident.to_string().trim_start_matches("r#").to_string();
+        let mut validators = Vec::new();
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("validate_async")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("field") {
+                    path = meta.value()?.parse::<LitStr>()?.value();
+                } else if let Some(name) = meta.path.get_ident() {
+                    validators.push(name.to_string());
+                } else {
+                    return Err(meta.error("expected a validator name or `field = \"...\"`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        rules.extend(validators.iter().map(|validator| {
+            quote! { ::rapid_rs::validation::AsyncRule::new(#path, #validator) }
+        }));
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::rapid_rs::validation::AsyncValidate for #ident #ty_generics #where_clause {
+            fn async_rules() -> ::std::vec::Vec<::rapid_rs::validation::AsyncRule> {
+                ::std::vec![#(#rules),*]
+            }
+        }
+    })
+}
+
+/// Derive `rapid_rs::openapi::SchemaConstraints` from `#[validate]` attributes
+///
+/// Translates `length`, `range`, `email`, `url` and `regex` into JSON Schema
+/// keywords; other rules are ignored. Fields renamed with
+/// `#[serde(rename = "...")]` use the JSON name.
+#[proc_macro_derive(SchemaConstraints)]
+pub fn derive_schema_constraints(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_schema_constraints(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_schema_constraints(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "SchemaConstraints can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "SchemaConstraints can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut constraints = Vec::new();
+    for field in fields {
+        let field_ident = field.ident.as_ref().expect("named field");
+        let mut name = field_ident.to_string().trim_start_matches("r#").to_string();
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    skip_meta(&meta)
+                }
+            })?;
+        }
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("validate")) {
+            attr.parse_nested_meta(|meta| {
+                let constraint = if meta.path.is_ident("length") {
+                    let args = rule_args(&meta, &["min", "max", "equal"])?;
+                    let [min, max, equal] = args.map(|arg| optional(arg, quote!(u64)));
+                    quote! {
+                        ::rapid_rs::openapi::Constraint::Length {
+                            min: #equal.or(#min),
+                            max: #equal.or(#max),
+                        }
+                    }
+                } else if meta.path.is_ident("range") {
+                    let args = rule_args(&meta, &["min", "max", "exclusive_min", "exclusive_max"])?;
+                    let [min, max, exclusive_min, exclusive_max] = args.map(|arg| optional(arg, quote!(f64)));
+                    quote! {
+                        ::rapid_rs::openapi::Constraint::Range {
+                            min: #min,
+                            max: #max,
+                            exclusive_min: #exclusive_min,
+                            exclusive_max: #exclusive_max,
+                        }
+                    }
+                } else if meta.path.is_ident("email") {
+                    skip_meta(&meta)?;
+                    quote! { ::rapid_rs::openapi::Constraint::Format("email") }
+                } else if meta.path.is_ident("url") {
+                    skip_meta(&meta)?;
+                    quote! { ::rapid_rs::openapi::Constraint::Format("uri") }
+                } else if meta.path.is_ident("regex") {
+                    let [path] = rule_args(&meta, &["path"])?;
+                    let path = path.ok_or_else(|| meta.error("regex needs `path`"))?;
+                    quote! {
+                        ::rapid_rs::openapi::Constraint::Pattern(
+                            ::std::string::ToString::to_string((#path).as_str())
+                        )
+                    }
+                } else {
+                    return skip_meta(&meta);
+                };
+
+                constraints.push(quote! {
+                    ::rapid_rs::openapi::FieldConstraint::new(#name, #constraint)
+                });
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::rapid_rs::openapi::SchemaConstraints for #ident #ty_generics #where_clause {
+            fn schema_constraints() -> ::std::vec::Vec<::rapid_rs::openapi::FieldConstraint> {
+                ::std::vec![#(#constraints),*]
+            }
+        }
+    })
+}
+
+/// Derive `rapid_rs::openapi::ApiExample` from `#[api_example]` attributes
+///
+/// Each `#[api_example(name = "minimal", summary = "...", json = r#"{...}"#)]`
+/// adds one example; `name` defaults to `default` and `summary` is optional.
+///
+/// ```rust,ignore
+/// #[derive(Deserialize, ToSchema, ApiExample)]
+/// #[api_example(name = "minimal", json = r#"{"email": "ada@example.com"}"#)]
+/// #[api_example(name = "full", json = r#"{"email": "ada@example.com", "name": "Ada"}"#)]
+/// struct CreateUser { email: String, name: Option<String> }
+/// ```
+#[proc_macro_derive(ApiExample, attributes(api_example))]
+pub fn derive_api_example(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_api_example(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_api_example(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut examples = Vec::new();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("api_example")) {
+        let (mut name, mut summary, mut json) = (None, None, None::<LitStr>);
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("summary") {
+                summary = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("json") {
+                json = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `name`, `summary` or `json`"));
+            }
+            Ok(())
+        })?;
+
+        let json = json.ok_or_else(|| syn::Error::new_spanned(attr, "api_example needs `json`"))?;
+        let name = name.map(|name| name.value()).unwrap_or_else(|| "default".to_string());
+        let summary = summary.map(|summary| quote! { .with_summary(#summary) });
+        examples.push(quote! {
+            ::rapid_rs::openapi::Example::from_json(#name, #json) #summary
+        });
+    }
+
+    if examples.is_empty() {
+        return Err(syn::Error::new_spanned(ident, "ApiExample needs at least one #[api_example(...)]"));
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::rapid_rs::openapi::ApiExample for #ident #ty_generics #where_clause {
+            fn examples() -> ::std::vec::Vec<::rapid_rs::openapi::Example> {
+                ::std::vec![#(#examples),*]
+            }
+        }
+    })
+}
+
+/// Values of the `names` arguments of a rule like `length(min = 1, max = 5)`
+fn rule_args<const N: usize>(
+    meta: &syn::meta::ParseNestedMeta,
+    names: &[&str; N],
+) -> syn::Result<[Option<syn::Expr>; N]> {
+    let mut values: [Option<syn::Expr>; N] = std::array::from_fn(|_| None);
+    if !meta.input.peek(syn::token::Paren) {
+        return Ok(values);
+    }
+    meta.parse_nested_meta(|arg| {
+        match names.iter().position(|name| arg.path.is_ident(name)) {
+            Some(index) => values[index] = Some(arg.value()?.parse()?),
+            None => skip_meta(&arg)?,
+        }
+        Ok(())
+    })?;
+    Ok(values)
+}
+
+fn optional(value: Option<syn::Expr>, ty: TokenStream2) -> TokenStream2 {
+    match value {
+        Some(value) => quote! { ::core::option::Option::Some((#value) as #ty) },
+        None => quote! { ::core::option::Option::<#ty>::None },
+    }
+}
+
+/// Consume an attribute argument we don't interpret
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<TokenStream2>()?;
+    }
+    Ok(())
+}
+
+/// Document a handler for the OpenAPI spec
+///
+/// Adds `<handler>_doc()` returning a `rapid_rs::openapi::RouteDoc`, to pass
+/// to `App::route_with_doc`. The summary and description default to the
+/// handler's doc comment and the operation id to its name.
+///
+/// ```rust,ignore
+/// /// Fetch a user
+/// #[api_handler(get, tag = "users", response(200, User, "The user"), errors(NotFound))]
+/// async fn get_user(Path(id): Path<String>) -> ApiResult<User> { /* ... */ }
+///
+/// App::new().route_with_doc("/users/:id", get(get_user), get_user_doc())
+/// ```
+///
+/// Arguments:
+/// - `get`, `post`, `put`, `patch` or `delete`: the HTTP method (required)
+/// - `summary = "..."`, `description = "..."`, `tag = "..."`, `operation_id = "..."`
+/// - `request = Type` / `validated_request = Type`: JSON request body
+/// - `response(200, Type, "description")` or `response(204, "description")`
+/// - `errors(NotFound, Unauthorized)`: `ApiError` variants the handler returns
+/// - `deprecated` or `deprecated(since = "2025-01-01", sunset = "2025-07-01",
+///   link = "...", successor = "/v2/...")`: see `rapid_rs::deprecation`
+#[proc_macro_attribute]
+pub fn api_handler(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let handler = parse_macro_input!(item as ItemFn);
+    let mut options = HandlerOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(attr with parser);
+
+    expand_api_handler(options, handler)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct HandlerOptions {
+    method: Option<syn::Ident>,
+    summary: Option<LitStr>,
+    description: Option<LitStr>,
+    tags: Vec<LitStr>,
+    operation_id: Option<LitStr>,
+    request: Option<(Type, bool)>,
+    responses: Vec<(LitInt, Option<Type>, LitStr)>,
+    errors: Vec<syn::Ident>,
+    /// Builder calls on `Deprecation::new()`
+    deprecated: Option<Vec<TokenStream2>>,
+}
+
+const METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+impl HandlerOptions {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        let path = &meta.path;
+        if let Some(method) = path.get_ident().filter(|ident| METHODS.contains(&ident.to_string().as_str())) {
+            if self.method.is_some() {
+                return Err(meta.error("the HTTP method is already set"));
+            }
+            self.method = Some(method.clone());
+        } else if path.is_ident("summary") {
+            self.summary = Some(meta.value()?.parse()?);
+        } else if path.is_ident("description") {
+            self.description = Some(meta.value()?.parse()?);
+        } else if path.is_ident("tag") {
+            self.tags.push(meta.value()?.parse()?);
+        } else if path.is_ident("operation_id") {
+            self.operation_id = Some(meta.value()?.parse()?);
+        } else if path.is_ident("request") {
+            self.request = Some((meta.value()?.parse()?, false));
+        } else if path.is_ident("validated_request") {
+            self.request = Some((meta.value()?.parse()?, true));
+        } else if path.is_ident("response") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            let status: LitInt = content.parse()?;
+            content.parse::<syn::Token![,]>()?;
+            let body = if content.peek(LitStr) {
+                None
+            } else {
+                let body: Type = content.parse()?;
+                content.parse::<syn::Token![,]>()?;
+                Some(body)
+            };
+            let description: LitStr = content.parse()?;
+            self.responses.push((status, body, description));
+        } else if path.is_ident("errors") {
+            meta.parse_nested_meta(|error| {
+                let variant = error
+                    .path
+                    .get_ident()
+                    .ok_or_else(|| error.error("expected an `ApiError` variant name"))?;
+                self.errors.push(variant.clone());
+                Ok(())
+            })?;
+        } else if path.is_ident("deprecated") {
+            let mut calls = Vec::new();
+            if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|option| {
+                    let value: LitStr = option.value()?.parse()?;
+                    if option.path.is_ident("since") || option.path.is_ident("sunset") {
+                        let (year, month, day) = parse_date(&value)?;
+                        let setter = option.path.get_ident();
+                        calls.push(quote! { .#setter(::rapid_rs::deprecation::ymd(#year, #month, #day)) });
+                    } else if option.path.is_ident("link") {
+                        calls.push(quote! { .with_link(#value) });
+                    } else if option.path.is_ident("successor") {
+                        calls.push(quote! { .with_successor(#value) });
+                    } else {
+                        return Err(option.error("expected `since`, `sunset`, `link` or `successor`"));
+                    }
+                    Ok(())
+                })?;
+            }
+            self.deprecated = Some(calls);
+        } else {
+            return Err(meta.error(
+                "expected an HTTP method, `summary`, `description`, `tag`, `operation_id`, \
+                 `request`, `validated_request`, `response(...)`, `errors(...)` or `deprecated(...)`",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn expand_api_handler(options: HandlerOptions, handler: ItemFn) -> syn::Result<TokenStream2> {
+    let name = &handler.sig.ident;
+    let vis = &handler.vis;
+    let method = options.method.as_ref().ok_or_else(|| {
+        syn::Error::new_spanned(name, "api_handler needs an HTTP method, e.g. #[api_handler(get)]")
+    })?;
+    let doc_fn = syn::Ident::new(&format!("{}_doc", name), name.span());
+
+    // Doc comment: first paragraph is the summary, the rest the description
+    let doc = handler
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (doc_summary, doc_description) = match doc.trim().split_once("\n\n") {
+        Some((summary, description)) => (summary.replace('\n', " "), description.trim().to_string()),
+        None => (doc.trim().replace('\n', " "), String::new()),
+    };
+
+    let summary = match &options.summary {
+        Some(summary) => Some(summary.value()),
+        None => Some(doc_summary).filter(|s| !s.is_empty()),
+    };
+    let description = match &options.description {
+        Some(description) => Some(description.value()),
+        None => Some(doc_description).filter(|s| !s.is_empty()),
+    };
+    let operation_id = options
+        .operation_id
+        .as_ref()
+        .map(LitStr::value)
+        .unwrap_or_else(|| name.to_string());
+
+    let mut calls = vec![quote! { .operation_id(#operation_id) }];
+    if let Some(summary) = summary {
+        calls.push(quote! { .summary(#summary) });
+    }
+    if let Some(description) = description {
+        calls.push(quote! { .description(#description) });
+    }
+    calls.extend(options.tags.iter().map(|tag| quote! { .tag(#tag) }));
+    match &options.request {
+        Some((ty, false)) => calls.push(quote! { .request::<#ty>() }),
+        Some((ty, true)) => calls.push(quote! { .validated_request::<#ty>() }),
+        None => {}
+    }
+    for (status, body, description) in &options.responses {
+        calls.push(match body {
+            Some(body) => quote! { .response::<#body>(#status, #description) },
+            None => quote! { .response_empty(#status, #description) },
+        });
+    }
+    if let Some(deprecation) = &options.deprecated {
+        calls.push(quote! { .deprecated(::rapid_rs::deprecation::Deprecation::new() #(#deprecation)*) });
+    }
+    let errors = &options.errors;
+    calls.extend(errors.iter().map(|variant| {
+        let variant = variant.to_string();
+        quote! { .api_error(#variant) }
+    }));
+
+    // Checks the error names are real `ApiError` variants
+    let error_check = if errors.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            let _ = |error: &::rapid_rs::ApiError| {
+                ::core::matches!(error, #(::rapid_rs::ApiError::#errors { .. })|*)
+            };
+        }
+    };
+
+    let doc_comment = format!("OpenAPI documentation for [`{}`]", name);
+    Ok(quote! {
+        #handler
+
+        #[doc = #doc_comment]
+        #vis fn #doc_fn() -> ::rapid_rs::openapi::RouteDoc {
+            #error_check
+            ::rapid_rs::openapi::RouteDoc::#method() #(#calls)*
+        }
+    })
+}
+
+/// `YYYY-MM-DD` as (year, month, day), checked to be a real date
+fn parse_date(value: &LitStr) -> syn::Result<(i32, u32, u32)> {
+    let text = value.value();
+    let parts: Vec<&str> = text.split('-').collect();
+    let invalid = || syn::Error::new_spanned(value, "expected a date like \"2025-01-31\"");
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let (year, month, day): (i32, u32, u32) = (
+        year.parse().map_err(|_| invalid())?,
+        month.parse().map_err(|_| invalid())?,
+        day.parse().map_err(|_| invalid())?,
+    );
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days {
+        return Err(invalid());
+    }
+    Ok((year, month, day))
+}
+
+/// `T` if `ty` is `Option<T>`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        syn::GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
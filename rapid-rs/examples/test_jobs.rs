@@ -1,38 +1,32 @@
-use rapid_rs::jobs::{JobQueue, JobMetadata, JobPriority, JobStatus};
+use rapid_rs::jobs::{InMemoryJobStorage, JobConfig, JobPriority, JobQueue};
 use serde_json::json;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create job queue
-    let queue = JobQueue::new();
-    
+    let queue = JobQueue::new(InMemoryJobStorage::new(), JobConfig::default());
+
     // Submit a job
-    let job_id = queue.submit(
-        "send_email",
-        json!({
-            "to": "user@example.com",
-            "subject": "Welcome!"
-        }),
-        JobPriority::High,
-    ).await?;
-    
+    let job_id = queue
+        .enqueue_with_priority(
+            json!({
+                "to": "user@example.com",
+                "subject": "Welcome!"
+            }),
+            "send_email",
+            JobPriority::High,
+        )
+        .await?;
+
     println!("✅ Job submitted: {}", job_id);
-    
-    // Fetch job
-    if let Some((metadata, payload)) = queue.fetch_next().await? {
-        println!("✅ Job fetched: {}", metadata.job_id);
-        println!("   Type: {}", metadata.job_type);
-        println!("   Priority: {:?}", metadata.priority);
-        println!("   Payload: {}", payload);
-        
-        // Mark as completed
-        queue.complete(metadata.job_id, json!({"status": "sent"})).await?;
-        println!("✅ Job completed");
-    }
-    
-    // List jobs
-    let jobs = queue.list_jobs(Some(JobStatus::Completed), None, 10, 0).await?;
-    println!("✅ Found {} completed jobs", jobs.len());
-    
+
+    // Check job status
+    let status = queue.get_status(job_id).await?;
+    println!("✅ Job status: {:?}", status);
+
+    // Queue statistics
+    let stats = queue.stats().await?;
+    println!("✅ Pending jobs: {}", stats.pending);
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -67,6 +67,9 @@ fn enabled_features() -> Vec<String> {
     #[cfg(feature = "cache-redis")]
     features.push("cache-redis".to_string());
 
+    #[cfg(feature = "cache-memcached")]
+    features.push("cache-memcached".to_string());
+
     #[cfg(feature = "rate-limit")]
     features.push("rate-limit".to_string());
 
@@ -0,0 +1,451 @@
+//! API usage accounting per consumer
+//!
+//! [`App::with_usage_analytics`](crate::App::with_usage_analytics) counts calls,
+//! errors and time spent per route and consumer (the authenticated user or
+//! API key, and the tenant) into hourly and daily rollups, so product teams
+//! can see who uses what without an external analytics stack. Counts are
+//! aggregated in memory and flushed to a [`UsageStore`] every minute and on
+//! shutdown:
+//!
+//! ```rust,ignore
+//! let usage = PostgresUsageStore::new(pool.clone());
+//! usage.init().await?;
+//! let usage: Arc<dyn UsageStore> = Arc::new(usage);
+//!
+//! App::new()
+//!     .auto_configure()
+//!     .with_usage_analytics(UsageAnalytics::new(usage.clone()).with_excluded_route("/health"))
+//!     .mount(Router::new().nest("/admin", analytics::routes(usage)).route_layer(admin_only))
+//! ```
+//!
+//! `GET /usage?granularity=day&from=2024-05-01T00:00:00Z&consumer=billing`
+//! returns rollup rows as JSON, and `GET /usage.csv` the same as CSV.
+
+#[cfg(feature = "database")]
+pub mod postgres;
+
+use axum::{
+    async_trait,
+    extract::{MatchedPath, Query, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+use crate::reporting::ReportScope;
+
+#[cfg(feature = "database")]
+pub use postgres::PostgresUsageStore;
+
+/// Size of a rollup bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl Granularity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+        }
+    }
+
+    /// Start of the bucket holding `at`
+    pub fn bucket(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let size = match self {
+            Granularity::Hour => TimeDelta::hours(1),
+            Granularity::Day => TimeDelta::days(1),
+        };
+        at.duration_trunc(size).unwrap_or(at)
+    }
+}
+
+/// Who made a call to which route
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct UsageKey {
+    /// Route pattern, e.g. `/orders/:id`
+    pub route: String,
+    pub method: String,
+    /// Authenticated user or API key
+    pub consumer: Option<String>,
+    pub tenant: Option<String>,
+}
+
+/// Counters of a key over some period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageCounts {
+    pub calls: u64,
+    /// Responses with a 4xx or 5xx status
+    pub errors: u64,
+    pub duration_ms: u64,
+}
+
+impl UsageCounts {
+    fn add(&mut self, other: &UsageCounts) {
+        self.calls += other.calls;
+        self.errors += other.errors;
+        self.duration_ms += other.duration_ms;
+    }
+}
+
+/// Calls counted since the last flush, in the hour starting at `hour`
+#[derive(Debug, Clone)]
+pub struct UsageDelta {
+    pub hour: DateTime<Utc>,
+    pub key: UsageKey,
+    pub counts: UsageCounts,
+}
+
+/// One row of a rollup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRow {
+    pub granularity: Granularity,
+    pub bucket: DateTime<Utc>,
+    #[serde(flatten)]
+    pub key: UsageKey,
+    #[serde(flatten)]
+    pub counts: UsageCounts,
+}
+
+/// Filter for [`UsageStore::query`]; unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UsageQuery {
+    #[serde(default)]
+    pub granularity: Granularity,
+    /// Buckets starting at or after this
+    pub from: Option<DateTime<Utc>>,
+    /// Buckets starting before this
+    pub to: Option<DateTime<Utc>>,
+    pub route: Option<String>,
+    pub consumer: Option<String>,
+    pub tenant: Option<String>,
+}
+
+impl UsageQuery {
+    fn matches(&self, bucket: DateTime<Utc>, key: &UsageKey) -> bool {
+        self.from.is_none_or(|from| bucket >= from)
+            && self.to.is_none_or(|to| bucket < to)
+            && self.route.as_ref().is_none_or(|route| *route == key.route)
+            && self.consumer.as_ref().is_none_or(|consumer| key.consumer.as_ref() == Some(consumer))
+            && self.tenant.as_ref().is_none_or(|tenant| key.tenant.as_ref() == Some(tenant))
+    }
+}
+
+/// Where rollups are kept
+#[async_trait]
+pub trait UsageStore: Send + Sync + 'static {
+    /// Add `deltas` to their hourly and daily rollups
+    async fn add(&self, deltas: &[UsageDelta]) -> Result<(), ApiError>;
+
+    /// Rows matching `query`, oldest bucket first
+    async fn query(&self, query: &UsageQuery) -> Result<Vec<UsageRow>, ApiError>;
+}
+
+/// Counts by granularity, bucket start and key
+type Rollups = BTreeMap<(Granularity, DateTime<Utc>, UsageKey), UsageCounts>;
+
+/// Counts by hour and key, not yet flushed
+type Pending = HashMap<(DateTime<Utc>, UsageKey), UsageCounts>;
+
+/// Rollups in memory, for development and tests
+#[derive(Default)]
+pub struct MemoryUsageStore {
+    rollups: Mutex<Rollups>,
+}
+
+impl MemoryUsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UsageStore for MemoryUsageStore {
+    async fn add(&self, deltas: &[UsageDelta]) -> Result<(), ApiError> {
+        let mut rollups = self.rollups.lock().unwrap();
+        for delta in deltas {
+            for granularity in [Granularity::Hour, Granularity::Day] {
+                let bucket = granularity.bucket(delta.hour);
+                rollups
+                    .entry((granularity, bucket, delta.key.clone()))
+                    .or_default()
+                    .add(&delta.counts);
+            }
+        }
+        Ok(())
+    }
+
+    async fn query(&self, query: &UsageQuery) -> Result<Vec<UsageRow>, ApiError> {
+        let rollups = self.rollups.lock().unwrap();
+        Ok(rollups
+            .iter()
+            .filter(|((granularity, bucket, key), _)| *granularity == query.granularity && query.matches(*bucket, key))
+            .map(|((granularity, bucket, key), counts)| UsageRow {
+                granularity: *granularity,
+                bucket: *bucket,
+                key: key.clone(),
+                counts: *counts,
+            })
+            .collect())
+    }
+}
+
+/// Counts calls and flushes them to a [`UsageStore`], see the [module docs](self)
+#[derive(Clone)]
+pub struct UsageAnalytics {
+    store: Arc<dyn UsageStore>,
+    pending: Arc<Mutex<Pending>>,
+    excluded: Vec<String>,
+    flush_interval: Duration,
+}
+
+impl UsageAnalytics {
+    pub fn new(store: Arc<dyn UsageStore>) -> Self {
+        Self {
+            store,
+            pending: Arc::default(),
+            excluded: Vec::new(),
+            flush_interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Don't count routes starting with `prefix` (repeatable)
+    pub fn with_excluded_route(mut self, prefix: impl Into<String>) -> Self {
+        self.excluded.push(prefix.into());
+        self
+    }
+
+    /// How often counts are written to the store (every minute by default)
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        self.flush_interval
+    }
+
+    pub fn store(&self) -> &Arc<dyn UsageStore> {
+        &self.store
+    }
+
+    fn record(&self, key: UsageKey, counts: UsageCounts) {
+        let hour = Granularity::Hour.bucket(Utc::now());
+        self.pending.lock().unwrap().entry((hour, key)).or_default().add(&counts);
+    }
+
+    /// Write pending counts to the store; they are kept for the next flush if it fails
+    pub async fn flush(&self) -> Result<(), ApiError> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let deltas: Vec<UsageDelta> = pending
+            .iter()
+            .map(|((hour, key), counts)| UsageDelta {
+                hour: *hour,
+                key: key.clone(),
+                counts: *counts,
+            })
+            .collect();
+
+        if let Err(error) = self.store.add(&deltas).await {
+            let mut current = self.pending.lock().unwrap();
+            for (key, counts) in pending {
+                current.entry(key).or_default().add(&counts);
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Flush every [`flush_interval`](Self::with_flush_interval) in the background
+    pub fn spawn_flusher(&self) -> tokio::task::JoinHandle<()> {
+        let analytics = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(analytics.flush_interval);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                if let Err(error) = analytics.flush().await {
+                    tracing::warn!(error = %error, "Failed to flush usage analytics");
+                }
+            }
+        })
+    }
+}
+
+/// Count the request towards its route and consumer
+pub(crate) async fn track(analytics: Arc<UsageAnalytics>, mut request: Request, next: Next) -> Response {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(route) if !analytics.excluded.iter().any(|prefix| route.as_str().starts_with(prefix.as_str())) => {
+            route.as_str().to_string()
+        }
+        _ => return next.run(request).await,
+    };
+    let method = request.method().to_string();
+    let scope = ReportScope::attach(&mut request);
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let (tenant, consumer) = scope.get();
+    let status = response.status();
+    analytics.record(
+        UsageKey { route, method, consumer, tenant },
+        UsageCounts {
+            calls: 1,
+            errors: u64::from(status.is_client_error() || status.is_server_error()),
+            duration_ms: started.elapsed().as_millis() as u64,
+        },
+    );
+    response
+}
+
+/// `rows` as CSV with a header line
+pub fn to_csv(rows: &[UsageRow]) -> String {
+    fn field(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    let mut csv = String::from("granularity,bucket,route,method,consumer,tenant,calls,errors,duration_ms\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            row.granularity.as_str(),
+            row.bucket.to_rfc3339(),
+            field(&row.key.route),
+            row.key.method,
+            field(row.key.consumer.as_deref().unwrap_or_default()),
+            field(row.key.tenant.as_deref().unwrap_or_default()),
+            row.counts.calls,
+            row.counts.errors,
+            row.counts.duration_ms,
+        ));
+    }
+    csv
+}
+
+async fn usage(
+    State(store): State<Arc<dyn UsageStore>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<Vec<UsageRow>>, ApiError> {
+    Ok(Json(store.query(&query).await?))
+}
+
+async fn usage_csv(
+    State(store): State<Arc<dyn UsageStore>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Response, ApiError> {
+    let rows = store.query(&query).await?;
+    let filename = format!("attachment; filename=\"usage-{}.csv\"", query.granularity.as_str());
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, filename)],
+        to_csv(&rows),
+    )
+        .into_response())
+}
+
+/// `GET /usage` (JSON) and `GET /usage.csv`; protect them like any admin endpoint
+pub fn routes(store: Arc<dyn UsageStore>) -> Router {
+    Router::new()
+        .route("/usage", get(usage))
+        .route("/usage.csv", get(usage_csv))
+        .with_state(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::{body::Body, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_counts_calls_per_consumer() {
+        let store: Arc<dyn UsageStore> = Arc::new(MemoryUsageStore::new());
+        let analytics = UsageAnalytics::new(store.clone()).with_excluded_route("/health");
+        let router = App::new()
+            .route(
+                "/orders/:id",
+                get(|request: Request| async move {
+                    if let Some(key) = request.headers().get("x-api-key") {
+                        crate::reporting::record_user(request.extensions(), key.to_str().unwrap());
+                    }
+                    "order"
+                }),
+            )
+            .route("/fail", get(|| async { StatusCode::BAD_REQUEST }))
+            .route("/health", get(|| async { "ok" }))
+            .with_usage_analytics(analytics.clone())
+            .mount(routes(store.clone()))
+            .into_router();
+        let call = |uri: &str, key: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(key) = key {
+                request = request.header("x-api-key", key);
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        for (uri, key) in [("/orders/1", Some("billing")), ("/orders/2", Some("billing")), ("/orders/3", None), ("/fail", None), ("/health", None)] {
+            call(uri, key).await.unwrap();
+        }
+        analytics.flush().await.unwrap();
+
+        let rows = store.query(&UsageQuery::default()).await.unwrap();
+        let counts: Vec<_> = rows
+            .iter()
+            .map(|row| (row.key.route.as_str(), row.key.consumer.as_deref(), row.counts.calls, row.counts.errors))
+            .collect();
+        assert_eq!(
+            counts,
+            [("/fail", None, 1, 1), ("/orders/:id", None, 1, 0), ("/orders/:id", Some("billing"), 2, 0)]
+        );
+
+        let hourly = UsageQuery {
+            granularity: Granularity::Hour,
+            consumer: Some("billing".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.query(&hourly).await.unwrap().len(), 1);
+
+        let response = call("/usage.csv?consumer=billing", None).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.lines().nth(1).unwrap().contains(",/orders/:id,GET,billing,,2,0,"));
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        let row = UsageRow {
+            granularity: Granularity::Day,
+            bucket: Granularity::Day.bucket(Utc::now()),
+            key: UsageKey {
+                route: "/a".to_string(),
+                method: "GET".to_string(),
+                consumer: Some("Acme, \"Inc\"".to_string()),
+                tenant: None,
+            },
+            counts: UsageCounts { calls: 1, errors: 0, duration_ms: 3 },
+        };
+        assert!(to_csv(&[row]).contains(",\"Acme, \"\"Inc\"\"\","));
+    }
+}
@@ -1,18 +1,56 @@
 use axum::{http::Method, Router};
+use std::future::Future;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use utoipa::OpenApi;
-
-#[cfg(feature = "swagger-ui")]
-use utoipa_swagger_ui::SwaggerUi;
 
 use crate::config::AppConfig;
+use crate::dependencies::Dependencies;
+use crate::openapi::{ApiDocs, RouteDoc};
+use crate::shutdown::ShutdownHook;
 
 /// Main application builder
 pub struct App {
     router: Router,
     config: Option<AppConfig>,
+    dependencies: Dependencies,
+    shutdown_hooks: Vec<ShutdownHook>,
+    shutdown_timeout: Option<Duration>,
+    pre_stop_delay: Option<Duration>,
+    limits: crate::limits::RequestLimits,
+    validation: Option<crate::extractors::ValidationConfig>,
+    validators: Option<crate::validation::ValidatorRegistry>,
+    error_mappers: Vec<crate::error::ErrorMapper>,
+    error_observers: Vec<crate::reporting::ErrorObserver>,
+    health: crate::health::HealthChecks,
+    dev: Option<crate::dev::DevIntegrations>,
+    docs: ApiDocs,
+    docs_ui: Option<crate::docs_ui::DocsUi>,
+    serve_docs: bool,
+    sitemap: Option<crate::sitemap::Sitemap>,
+    robots: Option<crate::sitemap::Robots>,
+    request_ids: bool,
+    trusted_proxies: Option<crate::client_ip::TrustedProxies>,
+    conditional: Option<crate::conditional::ConditionalRequests>,
+    capture: Option<crate::replay::RequestCapture>,
+    analytics: Option<crate::analytics::UsageAnalytics>,
+    route_policies: std::collections::HashMap<String, std::sync::Arc<crate::policy::RoutePolicy>>,
+    startup: Vec<crate::startup::Init>,
+    plugins: Vec<String>,
+    plugin_migrations: Vec<(String, crate::plugin::PluginMigration)>,
+    versions: std::collections::BTreeMap<u32, Router>,
+    versioning: crate::versioning::Versioning,
+    #[cfg(feature = "auth")]
+    auth_config: Option<crate::auth::AuthConfig>,
+    #[cfg(feature = "sessions")]
+    sessions: Option<crate::auth::Sessions>,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsSource>,
+    #[cfg(feature = "acme")]
+    certificates: Option<crate::multi_tenancy::TenantCertificates>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<std::sync::Arc<crate::chaos::Chaos>>,
 }
 
 impl App {
@@ -21,6 +59,43 @@ impl App {
         Self {
             router: Router::new(),
             config: None,
+            dependencies: Dependencies::new(),
+            shutdown_hooks: Vec::new(),
+            shutdown_timeout: None,
+            pre_stop_delay: None,
+            limits: crate::limits::RequestLimits::default(),
+            validation: None,
+            validators: None,
+            error_mappers: Vec::new(),
+            error_observers: Vec::new(),
+            health: crate::health::HealthChecks::default(),
+            dev: None,
+            docs: ApiDocs::default(),
+            docs_ui: None,
+            serve_docs: false,
+            sitemap: None,
+            robots: None,
+            request_ids: false,
+            trusted_proxies: None,
+            conditional: None,
+            capture: None,
+            analytics: None,
+            route_policies: std::collections::HashMap::new(),
+            startup: Vec::new(),
+            plugins: Vec::new(),
+            plugin_migrations: Vec::new(),
+            versions: std::collections::BTreeMap::new(),
+            versioning: crate::versioning::Versioning::default(),
+            #[cfg(feature = "auth")]
+            auth_config: None,
+            #[cfg(feature = "sessions")]
+            sessions: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "acme")]
+            certificates: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
         }
     }
 
@@ -28,8 +103,9 @@ impl App {
     /// - Loads configuration from files and environment
     /// - Sets up structured logging with tracing
     /// - Configures CORS with permissive defaults
-    /// - Adds health check endpoint
-    /// - Enables Swagger UI at /docs
+    /// - Tags requests, logs and error bodies with a request id
+    /// - Adds health check endpoints: `/health`, `/health/live` and `/health/ready`
+    /// - Serves the OpenAPI spec at /openapi.json and Swagger UI at /docs
     pub fn auto_configure(mut self) -> Self {
         // Initialize logging
         tracing_subscriber::registry()
@@ -58,81 +134,897 @@ impl App {
             .allow_origin(tower_http::cors::Any)
             .allow_headers(tower_http::cors::Any);
 
-        // Add health endpoint
-        let health_router = Router::new().route(
-            "/health",
-            axum::routing::get(|| async {
-                axum::Json(serde_json::json!({
-                    "status": "healthy",
-                    "timestamp": chrono::Utc::now()
-                }))
-            }),
-        );
-
-        // Setup Swagger UI with a basic OpenAPI spec
-        #[derive(OpenApi)]
-        #[openapi(
-            info(
-                title = "rapid-rs API",
-                version = "0.1.0",
-                description = "API built with rapid-rs"
-            ),
-            paths(),
-            components(schemas())
-        )]
-        struct ApiDoc;
-
-        // Add Swagger UI if feature is enabled
-        #[cfg(feature = "swagger-ui")]
-        let swagger = SwaggerUi::new("/docs").url("/api-docs/openapi.json", ApiDoc::openapi());
-
-        // Build the router with middleware
-        #[cfg(feature = "swagger-ui")]
-        let router_with_docs = Router::new().merge(swagger).merge(health_router);
-
-        #[cfg(not(feature = "swagger-ui"))]
-        let router_with_docs = health_router;
+        // Add health endpoints; readiness runs checks registered later too
+        let health_router = Router::new()
+            .route(
+                "/health",
+                axum::routing::get(|| async {
+                    axum::Json(serde_json::json!({
+                        "status": "healthy",
+                        "timestamp": chrono::Utc::now()
+                    }))
+                }),
+            )
+            .merge(self.health.router());
 
-        self.router = router_with_docs
+        self.router = health_router
             .merge(self.router)
             .layer(TraceLayer::new_for_http())
             .layer(cors);
 
+        if config.dev_mode {
+            self = self.with_dev_integrations();
+        }
+
+        self.docs_ui = self.docs_ui.or_else(|| Some(config.docs.clone()));
         self.config = Some(config);
+        self.serve_docs = true;
+        self.request_ids = true;
 
         tracing::info!("✅ Auto-configuration complete");
         self
     }
 
+    /// Make `/health/ready` depend on `check`
+    ///
+    /// See [`health`](crate::health) for the built-in checks.
+    pub fn with_health_check(self, check: impl crate::health::HealthCheck) -> Self {
+        self.health.add(check);
+        self
+    }
+
+    /// Replace the mailer, SMS and upload storage with local stubs and
+    /// serve the dev mailbox at `/dev/mailbox`
+    ///
+    /// See [`dev`](crate::dev); also enabled by `dev_mode = true` in config.
+    pub fn with_dev_integrations(mut self) -> Self {
+        if self.dev.is_none() {
+            tracing::warn!("🧪 Dev mode: external integrations are stubbed, see /dev/mailbox");
+            self.dev = Some(crate::dev::DevIntegrations::new());
+        }
+        self
+    }
+
+    /// The stubs installed by [`App::with_dev_integrations`]
+    pub fn dev_integrations(&self) -> Option<&crate::dev::DevIntegrations> {
+        self.dev.as_ref()
+    }
+
+    /// Use `config` instead of the one [`App::auto_configure`] loads
+    pub fn with_config(mut self, config: AppConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Install a [`Plugin`](crate::plugin::Plugin): its configuration,
+    /// routes, migrations, health checks and shutdown hook
+    ///
+    /// Add plugins after [`App::auto_configure`] so they can read their
+    /// settings. Installing the same plugin twice does nothing.
+    pub fn plugin(self, plugin: impl crate::plugin::Plugin) -> Self {
+        let name = plugin.name().to_string();
+        if self.plugins.contains(&name) {
+            tracing::warn!(plugin = %name, "Plugin already installed, skipping");
+            return self;
+        }
+
+        let plugin = std::sync::Arc::new(plugin);
+        let mut app = plugin.configure(self);
+        if let Some(routes) = plugin.routes() {
+            app = app.mount(routes);
+        }
+        for check in plugin.health_checks() {
+            app.health.add_shared(check.into());
+        }
+        app.plugin_migrations
+            .extend(plugin.migrations().into_iter().map(|migration| (name.clone(), migration)));
+        tracing::info!(plugin = %name, "🔌 Plugin installed");
+        app.plugins.push(name);
+        app.on_shutdown(move || async move { plugin.shutdown().await })
+    }
+
+    /// Names of the installed plugins, in installation order
+    pub fn plugins(&self) -> &[String] {
+        &self.plugins
+    }
+
+    /// Migrations the installed plugins will apply at startup, by plugin name
+    pub fn plugin_migrations(&self) -> &[(String, crate::plugin::PluginMigration)] {
+        &self.plugin_migrations
+    }
+
+    /// The `[plugins.<name>]` config section, if the config has one
+    pub fn plugin_config<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>, serde_json::Error> {
+        self.config
+            .as_ref()
+            .and_then(|config| config.plugins.get(name))
+            .map(|value| serde_json::from_value(value.clone()))
+            .transpose()
+    }
+
     /// Mount additional routes
     pub fn mount(mut self, router: Router) -> Self {
         self.router = self.router.merge(router);
         self
     }
 
+    /// Mount the routes of one API version, see [`versioning`](crate::versioning)
+    ///
+    /// Mounting the same version again adds to its routes.
+    pub fn mount_versioned(mut self, version: u32, router: Router) -> Self {
+        let existing = self.versions.remove(&version).unwrap_or_default();
+        self.versions.insert(version, existing.merge(router));
+        self
+    }
+
+    /// How clients pick a version (URL prefix by default) and which are deprecated
+    pub fn with_versioning(mut self, versioning: crate::versioning::Versioning) -> Self {
+        self.versioning = versioning;
+        self
+    }
+
     /// Add a route manually
     pub fn route(mut self, path: &str, method_router: axum::routing::MethodRouter) -> Self {
         self.router = self.router.route(path, method_router);
         self
     }
 
+    /// Add a route and document it in the OpenAPI spec
+    ///
+    /// With [`RouteDoc::auth`](crate::openapi::RouteDoc::auth), the documented
+    /// requirement is also enforced on the route, and with
+    /// [`RouteDoc::deprecated`](crate::openapi::RouteDoc::deprecated) responses
+    /// carry the deprecation headers.
+    pub fn route_with_doc(mut self, path: &str, method_router: axum::routing::MethodRouter, doc: RouteDoc) -> Self {
+        // Inside the auth layer, so usage can be counted per authenticated client
+        let method_router = match doc.route_deprecation() {
+            Some(deprecation) => {
+                let deprecation = std::sync::Arc::new(deprecation.clone());
+                let route: std::sync::Arc<str> = path.into();
+                method_router.route_layer(axum::middleware::from_fn(move |request, next| {
+                    crate::deprecation::mark_deprecated(deprecation.clone(), route.clone(), request, next)
+                }))
+            }
+            None => method_router,
+        };
+        #[cfg(feature = "auth")]
+        let method_router = match doc.route_auth().filter(|auth| !auth.is_public()) {
+            Some(auth) => {
+                let auth = std::sync::Arc::new(auth.clone());
+                method_router.route_layer(axum::middleware::from_fn(move |request, next| {
+                    crate::auth::route_auth::enforce(auth.clone(), request, next)
+                }))
+            }
+            None => method_router,
+        };
+        self.docs.add_route(path, doc);
+        self.route(path, method_router)
+    }
+
+    /// Document a `#[utoipa::path]` handler; mount its route separately
+    pub fn document<P: utoipa::Path>(mut self) -> Self {
+        self.docs.add_path::<P>();
+        self
+    }
+
+    /// Add a schema and its `#[validate]` rules to the OpenAPI spec
+    pub fn document_schema<T>(mut self) -> Self
+    where
+        T: utoipa::ToSchema<'static> + crate::openapi::SchemaConstraints,
+    {
+        self.docs.add_validated_schema::<T>();
+        self
+    }
+
+    /// Merge an OpenAPI document, e.g. from `#[derive(utoipa::OpenApi)]`
+    pub fn with_openapi(mut self, openapi: utoipa::openapi::OpenApi) -> Self {
+        self.docs.merge(openapi);
+        self
+    }
+
+    /// Title and version shown in the OpenAPI spec
+    pub fn with_api_info(mut self, title: impl Into<String>, version: impl Into<String>) -> Self {
+        self.docs.set_info(title, version);
+        self
+    }
+
+    /// List a domain error code in the [error catalog](crate::error_catalog) served with the docs
+    pub fn with_error_code(
+        mut self,
+        status: axum::http::StatusCode,
+        code: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.docs.add_error_code(status, code, description);
+        self
+    }
+
+    pub fn error_catalog(&self) -> &crate::error_catalog::ErrorCatalog {
+        self.docs.error_catalog()
+    }
+
+    /// Serve `/openapi.json` and, with `swagger-ui`, `/docs`
+    ///
+    /// Called by [`App::auto_configure`]. The spec is built when the app
+    /// is finished, so routes added afterwards are included.
+    pub fn with_api_docs(mut self) -> Self {
+        self.serve_docs = true;
+        self
+    }
+
+    /// Renderer, branding and hidden routes for the served docs
+    ///
+    /// See [`DocsUi`](crate::docs_ui::DocsUi); overrides the `[docs]` config section.
+    pub fn with_docs_ui(mut self, ui: crate::docs_ui::DocsUi) -> Self {
+        self.docs_ui = Some(ui);
+        self
+    }
+
+    /// Serve `/sitemap.xml`, see [`sitemap`](crate::sitemap)
+    ///
+    /// Documented `GET` routes are listed, including ones added afterwards.
+    pub fn with_sitemap(mut self, sitemap: crate::sitemap::Sitemap) -> Self {
+        self.sitemap = Some(sitemap);
+        self
+    }
+
+    /// Serve `/robots.txt`, linking the sitemap when there is one
+    pub fn with_robots(mut self, robots: crate::sitemap::Robots) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    /// Register a dependency for the [`Dep`](crate::dependencies::Dep) extractor
+    pub fn provide<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.dependencies.insert(value);
+        self
+    }
+
+    /// Register a shared dependency, e.g. `Arc<dyn EmailProvider>`
+    pub fn provide_arc<T: ?Sized + Send + Sync + 'static>(mut self, value: std::sync::Arc<T>) -> Self {
+        self.dependencies.insert_arc(value);
+        self
+    }
+
+    /// Generate user, job, workflow, request and tenant ids with `generator`
+    ///
+    /// Replaces the process-wide generator (UUIDv4 by default) and registers
+    /// it as a `Dep<dyn IdGenerator>`.
+    pub fn with_id_generator(mut self, generator: crate::ids::SharedIdGenerator) -> Self {
+        crate::ids::set_generator(generator.clone());
+        self.dependencies.insert_arc(generator);
+        self
+    }
+
+    /// Run `hook` during graceful shutdown, after in-flight requests drain
+    ///
+    /// Hooks run in registration order; use them to close pools, stop job
+    /// workers or flush telemetry.
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_hooks.push(crate::shutdown::hook(hook));
+        self
+    }
+
+    /// Run a startup step before serving, see [`startup`](crate::startup)
+    pub fn with_init(mut self, step: crate::startup::Init) -> Self {
+        self.startup.push(step);
+        self
+    }
+
+    /// Provide `services` to handlers and probe failed-over primaries every 10 seconds,
+    /// see [`ServiceRegistry`](crate::http_client::ServiceRegistry)
+    #[cfg(feature = "http-client")]
+    pub fn with_services(self, services: crate::http_client::ServiceRegistry) -> Self {
+        let health_checks = services.clone();
+        self.provide(services).with_init(crate::startup::Init::new("services", move |deps| {
+            health_checks.spawn_health_checks(Duration::from_secs(10));
+            async { Ok(deps) }
+        }))
+    }
+
+    /// Give each request an id, see [`RequestId`](crate::middleware::RequestId)
+    ///
+    /// Covers every route, including ones added after this call.
+    pub fn with_request_ids(mut self) -> Self {
+        self.request_ids = true;
+        self
+    }
+
+    /// Believe `X-Forwarded-For` from these proxies when resolving the
+    /// client address
+    ///
+    /// Overrides `trusted_proxies` in the server config. See
+    /// [`client_ip`](crate::client_ip).
+    pub fn with_trusted_proxies(mut self, proxies: crate::client_ip::TrustedProxies) -> Self {
+        self.trusted_proxies = Some(proxies);
+        self
+    }
+
+    /// Inject the faults of `chaos` into every route and [`DbConn`](crate::database::DbConn)
+    ///
+    /// Overrides the `[chaos]` profile in config. For tests and local
+    /// resilience checks only, see [`chaos`](crate::chaos).
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, chaos: std::sync::Arc<crate::chaos::Chaos>) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// Tag JSON responses with ETags and answer fresh `GET`s with 304,
+    /// see [`conditional`](crate::conditional)
+    pub fn with_conditional_requests(mut self, config: crate::conditional::ConditionalRequests) -> Self {
+        self.conditional = Some(config);
+        self
+    }
+
+    /// Count calls per route and consumer into usage rollups, see [`analytics`](crate::analytics)
+    ///
+    /// Counts are flushed periodically once the app runs, and on shutdown.
+    pub fn with_usage_analytics(mut self, analytics: crate::analytics::UsageAnalytics) -> Self {
+        self.analytics = Some(analytics.clone());
+        let (flusher, on_shutdown) = (analytics.clone(), analytics.clone());
+        self.provide(analytics)
+            .with_init(crate::startup::Init::new("usage-analytics", move |deps| {
+                flusher.spawn_flusher();
+                async { Ok(deps) }
+            }))
+            .on_shutdown(move || async move {
+                if let Err(error) = on_shutdown.flush().await {
+                    tracing::warn!(error = %error, "Failed to flush usage analytics");
+                }
+            })
+    }
+
+    /// Run built-in and custom maintenance tasks on their schedules once the
+    /// app starts, see [`Maintenance`](crate::jobs::Maintenance)
+    #[cfg(feature = "jobs")]
+    pub fn with_maintenance(self, maintenance: crate::jobs::Maintenance) -> Self {
+        let maintenance = std::sync::Mutex::new(Some(maintenance));
+        self.with_init(crate::startup::Init::new("maintenance", move |deps| {
+            if let Some(maintenance) = maintenance.lock().unwrap().take() {
+                maintenance.spawn();
+            }
+            async { Ok(deps) }
+        }))
+    }
+
+    /// Record requests matching `capture` for later replay, see [`replay`](crate::replay)
+    pub fn with_request_capture(mut self, capture: crate::replay::RequestCapture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// How long shutdown waits for in-flight requests before giving up
+    ///
+    /// Overrides `server.shutdown_timeout_seconds` (30 seconds by default).
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep serving for `delay` after SIGTERM while `/health/ready` reports
+    /// not ready, before the listener closes
+    ///
+    /// Set it a little longer than your load balancer's readiness probe
+    /// interval so it stops routing here first. Overrides
+    /// `server.pre_stop_delay_seconds` (0 by default).
+    pub fn with_pre_stop_delay(mut self, delay: Duration) -> Self {
+        self.pre_stop_delay = Some(delay);
+        self
+    }
+
+    /// Handle to take this instance out of rotation ("lame duck") by hand
+    ///
+    /// [`App::run`] starts draining on SIGTERM by itself.
+    pub fn readiness(&self) -> crate::health::Readiness {
+        self.health.readiness.clone()
+    }
+
+    /// Fail requests that run longer than `timeout` with 408
+    ///
+    /// Queries on a [`DbConn`](crate::database::DbConn) get a matching
+    /// `statement_timeout` and are cancelled when the request times out or
+    /// the client disconnects. Overrides `server.request_timeout_seconds`.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for one route, by its path pattern (`"/reports/:id"`)
+    pub fn with_route_timeout(mut self, path: impl Into<String>, timeout: Duration) -> Self {
+        self.limits.route_timeouts.insert(path.into(), timeout);
+        self
+    }
+
+    /// Timeout, downstream retries and fallback for one route, by its path
+    /// pattern, see [`policy`](crate::policy)
+    pub fn with_route_policy(mut self, path: impl Into<String>, policy: crate::policy::RoutePolicy) -> Self {
+        let path = path.into();
+        if let Some(timeout) = policy.timeout() {
+            self.limits.route_timeouts.insert(path.clone(), timeout);
+        }
+        self.route_policies.insert(path, std::sync::Arc::new(policy));
+        self
+    }
+
+    /// Reject request bodies larger than `bytes` with 413
+    ///
+    /// Overrides `server.max_body_size_bytes`; without either, axum's 2 MB
+    /// default applies to extractors that buffer the body.
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.limits.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Body size limit for one route, by its path pattern
+    pub fn with_route_max_body_size(mut self, path: impl Into<String>, bytes: usize) -> Self {
+        self.limits.route_body_sizes.insert(path.into(), bytes);
+        self
+    }
+
+    /// Customize how [`ValidatedJson`](crate::ValidatedJson) reports errors
+    ///
+    /// Translated messages, default locale and which rejected values are
+    /// redacted; see [`ValidationConfig`](crate::ValidationConfig).
+    pub fn with_validation(mut self, config: crate::extractors::ValidationConfig) -> Self {
+        self.validation = Some(config);
+        self
+    }
+
+    /// Async validators run by [`ValidatedJson`](crate::ValidatedJson)
+    ///
+    /// See [`ValidatorRegistry`](crate::validation::ValidatorRegistry);
+    /// validators get this app's dependencies.
+    ///
+    /// # Panics
+    ///
+    /// If a validator is used by no registered payload type, or a payload
+    /// type uses a validator that isn't registered.
+    pub fn with_validators(mut self, validators: crate::validation::ValidatorRegistry) -> Self {
+        if let Err(message) = validators.check() {
+            panic!("Invalid validator registry: {}", message);
+        }
+        self.validators = Some(validators);
+        self
+    }
+
+    /// Rewrite [`ApiError`](crate::ApiError)s from any route before they are sent
+    ///
+    /// `mapper` returns a replacement, or `None` to keep the error. Mappers
+    /// run in the order they were added; the first replacement wins.
+    ///
+    /// ```rust,ignore
+    /// App::new().map_errors(|error| match error {
+    ///     ApiError::DatabaseError(sqlx::Error::RowNotFound) => Some(ApiError::NotFound("Not found".into())),
+    ///     _ => None,
+    /// })
+    /// ```
+    pub fn map_errors<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(&crate::ApiError) -> Option<crate::ApiError> + Send + Sync + 'static,
+    {
+        self.error_mappers.push(std::sync::Arc::new(mapper));
+        self
+    }
+
+    /// Observe every [`ApiError`](crate::ApiError) and handler panic
+    ///
+    /// `observer` gets the error after [`map_errors`](App::map_errors) and an
+    /// [`ErrorContext`](crate::reporting::ErrorContext) with the request id,
+    /// route, tenant and user. Panics become 500 responses.
+    ///
+    /// ```rust,ignore
+    /// App::new().on_error(|error, context| {
+    ///     tracing::warn!(request_id = ?context.request_id, route = ?context.route, "{}", error);
+    /// })
+    /// ```
+    pub fn on_error<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&crate::ApiError, &crate::reporting::ErrorContext) + Send + Sync + 'static,
+    {
+        self.error_observers.push(std::sync::Arc::new(observer));
+        self
+    }
+
+    /// Share an [`AuthConfig`](crate::auth::AuthConfig) with every route
+    ///
+    /// The config is added to request extensions when the app runs, so
+    /// `AuthUser` and websocket upgrades verify tokens against it instead of
+    /// falling back to environment variables.
+    #[cfg(feature = "auth")]
+    pub fn with_auth(mut self, config: crate::auth::AuthConfig) -> Self {
+        self.auth_config = Some(config);
+        self
+    }
+
+    /// Verify `X-API-Key` on routes whose [`RouteAuth`](crate::auth::RouteAuth)
+    /// accepts API keys
+    #[cfg(feature = "auth")]
+    pub fn with_api_keys(self, store: impl crate::auth::ApiKeyStore) -> Self {
+        let store: std::sync::Arc<dyn crate::auth::ApiKeyStore> = std::sync::Arc::new(store);
+        self.provide_arc(store)
+    }
+
+    /// Share [`Sessions`](crate::auth::Sessions) with every route, so handlers
+    /// can take a `SessionUser`
+    #[cfg(feature = "sessions")]
+    pub fn with_sessions(mut self, sessions: crate::auth::Sessions) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Mount a websocket endpoint at `/ws` served by `handler`
+    ///
+    /// Connections authenticate with a bearer token (header or `?token=`)
+    /// against the app's auth config, record the client address, and report
+    /// connection gauges when `observability` is enabled.
+    #[cfg(feature = "websocket")]
+    pub fn with_websocket(self, handler: impl crate::websocket::WebSocketHandler + 'static) -> Self {
+        self.with_websocket_server(crate::websocket::WebSocketServer::new().with_handler(handler))
+    }
+
+    /// Mount a pre-configured websocket server
+    ///
+    /// Use this instead of [`App::with_websocket`] when you need to keep a
+    /// handle on the server (e.g. its room manager) for broadcasting.
+    #[cfg(feature = "websocket")]
+    pub fn with_websocket_server(self, server: crate::websocket::WebSocketServer) -> Self {
+        self.mount(server.routes())
+    }
+
+    /// Serve HTTPS on the app port with a PEM certificate chain and key
+    ///
+    /// The files are loaded when the app runs and re-read on `SIGHUP`.
+    /// HTTP/2 is offered over ALPN alongside HTTP/1.1.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, cert_path: impl Into<std::path::PathBuf>, key_path: impl Into<std::path::PathBuf>) -> Self {
+        self.tls = Some(crate::tls::TlsSource::PemFiles {
+            cert: cert_path.into(),
+            key: key_path.into(),
+        });
+        self
+    }
+
+    /// Serve HTTPS on the app port with a prepared [`TlsConfig`](crate::tls::TlsConfig)
+    #[cfg(feature = "tls")]
+    pub fn with_tls_config(mut self, config: crate::tls::TlsConfig) -> Self {
+        self.tls = Some(crate::tls::TlsSource::Config(config));
+        self
+    }
+
+    /// Serve HTTPS on the app port with a custom rustls config
+    #[cfg(feature = "tls")]
+    pub fn with_rustls_config(self, config: crate::tls::rustls::ServerConfig) -> Self {
+        self.with_tls_config(crate::tls::TlsConfig::from_rustls(config))
+    }
+
+    /// Serve HTTPS for tenant custom domains with ACME-issued certificates
+    ///
+    /// `run()` keeps the plain HTTP listener and adds a TLS listener on
+    /// [`AcmeSettings::https_port`](crate::multi_tenancy::AcmeSettings), which
+    /// also answers the `tls-alpn-01` challenges.
+    #[cfg(feature = "acme")]
+    pub fn with_tenant_certificates(mut self, certificates: crate::multi_tenancy::TenantCertificates) -> Self {
+        self.certificates = Some(certificates);
+        self
+    }
+
+    /// Finish the app into a router with app-wide layers applied
+    ///
+    /// Useful for serving the app yourself or driving it in tests.
+    pub fn into_router(self) -> Router {
+        let (router, dependencies) = self.into_parts();
+        router.layer(axum::Extension(dependencies))
+    }
+
+    /// Layered router without the dependencies, so tests can override them
+    pub(crate) fn into_parts(self) -> (Router, Dependencies) {
+        let router = crate::versioning::install(self.router, self.versioning, self.versions);
+        let router = if self.sitemap.is_some() || self.robots.is_some() {
+            router.merge(crate::sitemap::router(self.sitemap, self.robots, &self.docs))
+        } else {
+            router
+        };
+        let router = if self.serve_docs {
+            router.merge(crate::docs_ui::router(&self.docs, &self.docs_ui.unwrap_or_default()))
+        } else {
+            router
+        };
+
+        let mut dependencies = self.dependencies;
+        let router = match &self.dev {
+            Some(dev) => {
+                dev.install(&mut dependencies);
+                router.merge(dev.router())
+            }
+            None => router,
+        };
+
+        let router = match self.validation {
+            Some(validation) => router.layer(axum::Extension(validation)),
+            None => router,
+        };
+
+        let router = match self.validators {
+            Some(validators) => router.layer(axum::Extension(std::sync::Arc::new(validators))),
+            None => router,
+        };
+
+        let health = self.health.clone();
+        let router = router.layer(axum::middleware::map_response(move |response| {
+            let health = health.clone();
+            async move { health.close_while_draining(response) }
+        }));
+
+        let limits = std::sync::Arc::new(self.limits);
+        let router = if limits.has_timeouts() {
+            let limits = limits.clone();
+            router.layer(axum::middleware::from_fn(move |request, next| {
+                crate::limits::enforce_timeout(limits.clone(), request, next)
+            }))
+        } else {
+            router
+        };
+
+        let router = if limits.has_body_sizes() {
+            router
+                .layer(axum::extract::DefaultBodyLimit::disable())
+                .layer(axum::middleware::from_fn(move |request, next| {
+                    crate::limits::enforce_body_size(limits.clone(), request, next)
+                }))
+        } else {
+            router
+        };
+
+        // Outside the timeout, so fallbacks can replace the 408
+        let router = if self.route_policies.is_empty() {
+            router
+        } else {
+            let policies = std::sync::Arc::new(self.route_policies);
+            router.layer(axum::middleware::from_fn(move |request, next| {
+                crate::policy::apply(policies.clone(), request, next)
+            }))
+        };
+
+        let router = if self.error_mappers.is_empty() {
+            router
+        } else {
+            let mappers = std::sync::Arc::new(self.error_mappers);
+            router.layer(axum::middleware::map_response(move |response| {
+                let mappers = mappers.clone();
+                async move { crate::error::map_error_response(&mappers, response) }
+            }))
+        };
+
+        let router = if self.error_observers.is_empty() {
+            router
+        } else {
+            let observers: std::sync::Arc<[_]> = self.error_observers.into();
+            router.layer(axum::middleware::from_fn(move |request, next| {
+                crate::reporting::observe_errors(observers.clone(), request, next)
+            }))
+        };
+        
+        let router = match self.analytics {
+            Some(analytics) => {
+                let analytics = std::sync::Arc::new(analytics);
+                router.layer(axum::middleware::from_fn(move |request, next| {
+                    crate::analytics::track(analytics.clone(), request, next)
+                }))
+            }
+            None => router,
+        };
+
+        let router = match self.capture {
+            Some(capture) => {
+                let capture = std::sync::Arc::new(capture);
+                router.layer(axum::middleware::from_fn(move |request, next| {
+                    crate::replay::capture(capture.clone(), request, next)
+                }))
+            }
+            None => router,
+        };
+
+        let router = match self.conditional {
+            Some(conditional) => {
+                let conditional = std::sync::Arc::new(conditional);
+                router.layer(axum::middleware::from_fn(move |request, next| {
+                    crate::conditional::conditional(conditional.clone(), request, next)
+                }))
+            }
+            None => router,
+        };
+        
+        #[cfg(feature = "auth")]
+        let router = match self.auth_config {
+            Some(auth_config) => router.layer(axum::Extension(auth_config)),
+            None => router,
+        };
+
+        #[cfg(feature = "sessions")]
+        let router = match self.sessions {
+            Some(sessions) => router.layer(axum::Extension(sessions)),
+            None => router,
+        };
+
+        #[cfg(feature = "chaos")]
+        let router = match self.chaos {
+            Some(chaos) => {
+                tracing::warn!(profile = ?chaos.profile(), "Chaos testing is injecting faults");
+                router.layer(axum::middleware::from_fn(move |request, next| {
+                    crate::chaos::inject_faults(chaos.clone(), request, next)
+                }))
+            }
+            None => router,
+        };
+
+        let router = match self.trusted_proxies {
+            Some(proxies) if !proxies.is_empty() => {
+                let proxies = std::sync::Arc::new(proxies);
+                router.layer(axum::middleware::from_fn(move |request, next| {
+                    crate::client_ip::resolve(proxies.clone(), request, next)
+                }))
+            }
+            _ => router,
+        };
+
+        // Outermost, so everything above runs in the request's span
+        let router = if self.request_ids {
+            router.layer(crate::middleware::RequestIdLayer::new())
+        } else {
+            router
+        };
+
+        (router, dependencies)
+    }
+
+    /// Apply the server limits, run the startup steps and plugin migrations
+    pub(crate) async fn prepare(&mut self, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+        self.limits.timeout = self
+            .limits
+            .timeout
+            .or(config.server.request_timeout_seconds.map(Duration::from_secs));
+        self.limits.max_body_size = self.limits.max_body_size.or(config.server.max_body_size_bytes);
+        #[cfg(feature = "chaos")]
+        if self.chaos.is_none() {
+            self.chaos = crate::chaos::Chaos::from_config(config)?.map(std::sync::Arc::new);
+        }
+        if self.trusted_proxies.is_none() {
+            self.trusted_proxies = Some(crate::client_ip::TrustedProxies::new(&config.server.trusted_proxies)?);
+        }
+        crate::startup::boot(std::mem::take(&mut self.startup), &mut self.dependencies).await?;
+        if !self.plugin_migrations.is_empty() {
+            let pool = self
+                .dependencies
+                .get::<sqlx::PgPool>()
+                .ok_or("Plugins have migrations to run; register a PgPool with App::provide")?;
+            crate::plugin::run_migrations(&pool, &self.plugin_migrations).await?;
+        }
+        Ok(())
+    }
+
+    /// Prepare the app as [`run`](App::run) does, without serving it
+    #[cfg(feature = "lambda")]
+    pub(crate) async fn into_prepared_router(mut self) -> Result<Router, Box<dyn std::error::Error>> {
+        let config = self.config.take().unwrap_or_default();
+        self.prepare(&config).await?;
+        Ok(self.into_router())
+    }
+
     /// Run the application
-    pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        let config = self.config.unwrap_or_default();
+    ///
+    /// On SIGINT or SIGTERM the server stops accepting connections, waits up
+    /// to the shutdown timeout for in-flight requests, then runs the
+    /// [`on_shutdown`](App::on_shutdown) hooks.
+    pub async fn run(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let config = self.config.take().unwrap_or_default();
+        let shutdown_timeout = self
+            .shutdown_timeout
+            .unwrap_or(Duration::from_secs(config.server.shutdown_timeout_seconds));
+        let shutdown_hooks = std::mem::take(&mut self.shutdown_hooks);
+        let pre_stop_delay = self
+            .pre_stop_delay
+            .unwrap_or(Duration::from_secs(config.server.pre_stop_delay_seconds));
+        let readiness = self.readiness();
+        self.prepare(&config).await?;
+        #[cfg(feature = "tls")]
+        let tls = self.tls.take().map(|tls| tls.into_config()).transpose()?;
+        #[cfg(feature = "acme")]
+        let certificates = self.certificates.take();
+        let router = self.into_router();
         let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
+        let limits = crate::listener::ConnectionLimits::new(&config.server);
 
-        tracing::info!("🎯 Server starting on http://{}", addr);
+        #[cfg(feature = "tls")]
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        #[cfg(not(feature = "tls"))]
+        let scheme = "http";
+
+        tracing::info!("🎯 Server starting on {}://{}", scheme, addr);
 
         #[cfg(feature = "swagger-ui")]
-        tracing::info!("📚 Swagger UI available at http://{}/docs", addr);
+        tracing::info!("📚 Swagger UI available at {}://{}/docs", scheme, addr);
 
         #[cfg(not(feature = "swagger-ui"))]
         tracing::info!("💡 Tip: Enable 'swagger-ui' feature for API docs at /docs");
 
-        tracing::info!("💚 Health check available at http://{}/health", addr);
+        tracing::info!("💚 Health check available at {}://{}/health", scheme, addr);
+
+        let (trigger, triggered) = tokio::sync::watch::channel(false);
+        tokio::spawn(async move {
+            crate::shutdown::signal().await;
+            readiness.start_draining();
+            if !pre_stop_delay.is_zero() {
+                tracing::info!(delay = ?pre_stop_delay, "🛑 Shutdown signal received, reporting not ready");
+                tokio::time::sleep(pre_stop_delay).await;
+            }
+            tracing::info!("🛑 Draining requests");
+            let _ = trigger.send(true);
+        });
+
+        #[cfg(feature = "acme")]
+        let mut tenant_server = None;
+        #[cfg(feature = "acme")]
+        if let Some(certificates) = certificates {
+            let tls_addr = SocketAddr::from(([0, 0, 0, 0], certificates.settings().https_port));
+            let tls_listener = crate::listener::bind(tls_addr, config.server.accept_backlog)?;
+            certificates.start();
+            tracing::info!("🔒 HTTPS for tenant domains on https://{}", tls_addr);
 
-        let listener = tokio::net::TcpListener::bind(addr).await?;
-        axum::serve(listener, self.router).await?;
+            tenant_server = Some(tokio::spawn(crate::tls::serve_tls(
+                tls_listener,
+                router.clone(),
+                certificates.rustls_config(),
+                limits.clone(),
+                triggered.clone(),
+            )));
+        }
+
+        let listener = crate::listener::bind(addr, config.server.accept_backlog)?;
+        let stop_accepting = triggered.clone();
+        let server = async move {
+            #[cfg(feature = "tls")]
+            if let Some(tls) = tls {
+                tls.reload_on_sighup();
+                return crate::tls::serve_tls(listener, router, tls.server_config(), limits, stop_accepting).await;
+            }
+
+            crate::listener::serve(listener, router, limits, stop_accepting).await
+        };
+
+        let drained = async move {
+            let result = server.await;
+            #[cfg(feature = "acme")]
+            if let Some(tenant_server) = tenant_server {
+                match tenant_server.await {
+                    Ok(Err(e)) => tracing::error!(error = %e, "HTTPS listener stopped"),
+                    Err(e) => tracing::error!(error = %e, "HTTPS listener panicked"),
+                    Ok(Ok(())) => {}
+                }
+            }
+            result
+        };
+
+        let mut drain_deadline = triggered;
+        tokio::select! {
+            result = drained => result?,
+            _ = async {
+                if drain_deadline.wait_for(|stopping| *stopping).await.is_ok() {
+                    tokio::time::sleep(shutdown_timeout).await;
+                } else {
+                    std::future::pending::<()>().await;
+                }
+            } => {
+                tracing::warn!(timeout = ?shutdown_timeout, "In-flight requests did not finish before the shutdown timeout");
+            }
+        }
+        
+        crate::shutdown::run_hooks(shutdown_hooks).await;
+        tracing::info!("👋 Shutdown complete");
 
         Ok(())
     }
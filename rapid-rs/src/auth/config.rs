@@ -1,8 +1,13 @@
 //! Authentication configuration
 
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::time::Duration;
 
+use super::audit::AuditSink;
+use super::denylist::TokenDenylist;
+use crate::clock::SharedClock;
+
 /// Configuration for authentication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
@@ -29,6 +34,18 @@ pub struct AuthConfig {
     
     /// Argon2 parallelism (default: 4 threads)
     pub argon2_parallelism: u32,
+    
+    /// Time source for token issue and expiry checks
+    #[serde(skip, default = "crate::clock::system")]
+    pub clock: SharedClock,
+
+    /// Revoked tokens, checked on every verification
+    #[serde(skip)]
+    pub denylist: Option<Arc<dyn TokenDenylist>>,
+
+    /// Where security events are recorded
+    #[serde(skip)]
+    pub audit: Option<Arc<dyn AuditSink>>,
 }
 
 impl AuthConfig {
@@ -64,6 +81,24 @@ impl AuthConfig {
         self
     }
     
+    /// Use a custom clock, e.g. a `ManualClock` in tests
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Reject tokens revoked through the given denylist
+    pub fn with_denylist(mut self, denylist: impl TokenDenylist) -> Self {
+        self.denylist = Some(Arc::new(denylist));
+        self
+    }
+    
+    /// Record sign-ins and other security events to `sink`
+    pub fn with_audit_sink(mut self, sink: impl AuditSink) -> Self {
+        self.audit = Some(Arc::new(sink));
+        self
+    }
+    
     /// Load auth config from environment variables
     /// 
     /// Environment variables:
@@ -115,6 +150,9 @@ impl Default for AuthConfig {
             argon2_memory_cost: 65536, // 64 MB
             argon2_time_cost: 3,
             argon2_parallelism: 4,
+            clock: crate::clock::system(),
+            denylist: None,
+            audit: None,
         }
     }
 }
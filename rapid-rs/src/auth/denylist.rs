@@ -0,0 +1,550 @@
+//! Revoking tokens before they expire
+//!
+//! JWTs stay valid until they expire. A [`TokenDenylist`] on the
+//! [`AuthConfig`] lets [`verify_token`](super::verify_token) reject single
+//! tokens (by `jti`) or every token issued to a user up to a point in time,
+//! e.g. to log out everywhere after a password change or a leaked token.
+//!
+//! ```rust,ignore
+//! let config = AuthConfig::new(secret).with_denylist(InMemoryTokenDenylist::new());
+//!
+//! App::new()
+//!     .with_auth(config.clone())
+//!     .mount(auth_routes(config.clone()))
+//!     .mount(revocation_routes(config))
+//! ```
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    audit::{emit, AuditEvent, AuditEventKind},
+    config::AuthConfig,
+    extractors::AuthUser,
+    jwt::Claims,
+    models::MessageResponse,
+};
+use crate::clock::SharedClock;
+use crate::error::ApiError;
+use crate::extractors::ValidatedJson;
+
+/// Revoked tokens and users, checked on every token verification
+///
+/// [`is_revoked`](Self::is_revoked) runs on every request and is not async,
+/// so implementations answer it from memory.
+#[async_trait::async_trait]
+pub trait TokenDenylist: Send + Sync + std::fmt::Debug + 'static {
+    fn is_revoked(&self, claims: &Claims) -> bool;
+
+    /// Revoke one token; the entry can be forgotten after `until`
+    async fn revoke_token(&self, jti: &str, until: DateTime<Utc>) -> Result<(), ApiError>;
+
+    /// Revoke every token of `user_id` issued at or before `at`
+    async fn revoke_user(&self, user_id: &str, at: DateTime<Utc>, until: DateTime<Utc>) -> Result<(), ApiError>;
+}
+
+#[derive(Debug, Default)]
+struct Revocations {
+    /// jti -> when the entry can be dropped
+    tokens: HashMap<String, DateTime<Utc>>,
+    /// user ID -> (revoked at, when the entry can be dropped)
+    users: HashMap<String, (DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Revocations {
+    fn is_revoked(&self, claims: &Claims) -> bool {
+        // `iat` has whole seconds, so tokens from the revoking second are revoked too
+        self.tokens.contains_key(&claims.jti)
+            || self.users.get(&claims.sub).is_some_and(|(at, _)| claims.iat <= at.timestamp())
+    }
+
+    fn prune(&mut self, now: DateTime<Utc>) {
+        self.tokens.retain(|_, until| *until > now);
+        self.users.retain(|_, (_, until)| *until > now);
+    }
+}
+
+/// In-process denylist
+///
+/// Revocations only reach this instance and are lost on restart; use
+/// `RedisTokenDenylist` when running more than one.
+#[derive(Debug, Clone)]
+pub struct InMemoryTokenDenylist {
+    revocations: Arc<RwLock<Revocations>>,
+    clock: SharedClock,
+}
+
+impl Default for InMemoryTokenDenylist {
+    fn default() -> Self {
+        Self {
+            revocations: Arc::default(),
+            clock: crate::clock::system(),
+        }
+    }
+}
+
+impl InMemoryTokenDenylist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `clock` to decide when entries can be dropped
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenDenylist for InMemoryTokenDenylist {
+    fn is_revoked(&self, claims: &Claims) -> bool {
+        self.revocations.read().unwrap().is_revoked(claims)
+    }
+
+    async fn revoke_token(&self, jti: &str, until: DateTime<Utc>) -> Result<(), ApiError> {
+        let mut revocations = self.revocations.write().unwrap();
+        revocations.prune(self.clock.now());
+        revocations.tokens.insert(jti.to_string(), until);
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: &str, at: DateTime<Utc>, until: DateTime<Utc>) -> Result<(), ApiError> {
+        let mut revocations = self.revocations.write().unwrap();
+        revocations.prune(self.clock.now());
+        let entry = revocations.users.entry(user_id.to_string()).or_insert((at, until));
+        *entry = (entry.0.max(at), entry.1.max(until));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cache-redis")]
+pub use self::redis::RedisTokenDenylist;
+
+#[cfg(feature = "cache-redis")]
+mod redis {
+    use super::*;
+    use ::redis::AsyncCommands;
+    use futures::stream::{BoxStream, StreamExt};
+    use serde::Serialize;
+    use std::sync::Weak;
+    use std::time::Duration;
+
+    const TOKENS_KEY: &str = "denylist:tokens";
+    const USERS_KEY: &str = "denylist:users";
+    const CHANNEL: &str = "denylist:revocations";
+
+    /// A revocation as published to the other instances
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "kind", rename_all = "snake_case")]
+    enum Published {
+        Token { jti: String, until: i64 },
+        User { user_id: String, at: i64, until: i64 },
+    }
+
+    /// Denylist shared through Redis
+    ///
+    /// Revocations are published to every instance as they are made, so they
+    /// apply everywhere within a round trip. Each instance also reloads its
+    /// in-memory copy when it (re)subscribes and every `refresh`, in case it
+    /// missed a message.
+    #[derive(Clone)]
+    pub struct RedisTokenDenylist {
+        connection: ::redis::aio::ConnectionManager,
+        local: InMemoryTokenDenylist,
+    }
+
+    impl std::fmt::Debug for RedisTokenDenylist {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("RedisTokenDenylist").field("local", &self.local).finish_non_exhaustive()
+        }
+    }
+
+    impl RedisTokenDenylist {
+        pub async fn new(redis_url: &str, refresh: Duration) -> Result<Self, ApiError> {
+            Self::with_clock(redis_url, refresh, crate::clock::system()).await
+        }
+
+        /// Like [`new`](Self::new), using `clock` to decide when entries can be dropped
+        pub async fn with_clock(redis_url: &str, refresh: Duration, clock: SharedClock) -> Result<Self, ApiError> {
+            let client = ::redis::Client::open(redis_url)
+                .map_err(|e| ApiError::InternalServerError(format!("Failed to create Redis client: {}", e)))?;
+            let connection = ::redis::aio::ConnectionManager::new(client.clone())
+                .await
+                .map_err(|e| ApiError::InternalServerError(format!("Failed to connect to Redis: {}", e)))?;
+
+            let denylist = Self {
+                connection,
+                local: InMemoryTokenDenylist::new().with_clock(clock.clone()),
+            };
+            *denylist.local.revocations.write().unwrap() = load(&denylist.connection, clock.now()).await?;
+
+            tokio::spawn(listen(
+                client,
+                denylist.connection.clone(),
+                Arc::downgrade(&denylist.local.revocations),
+                refresh,
+                clock,
+            ));
+
+            Ok(denylist)
+        }
+
+        async fn publish(&self, revocation: &Published) -> Result<(), ApiError> {
+            let message = serde_json::to_string(revocation)
+                .map_err(|e| ApiError::InternalServerError(format!("Failed to serialize revocation: {}", e)))?;
+            let mut connection = self.connection.clone();
+            connection.publish::<_, _, ()>(CHANNEL, message).await.map_err(redis_error)
+        }
+    }
+
+    /// Apply published revocations until every clone is dropped
+    async fn listen(
+        client: ::redis::Client,
+        connection: ::redis::aio::ConnectionManager,
+        revocations: Weak<RwLock<Revocations>>,
+        refresh: Duration,
+        clock: SharedClock,
+    ) {
+        loop {
+            let mut messages = match subscribe(&client).await {
+                Ok(messages) => Some(messages),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to subscribe to token revocations");
+                    None
+                }
+            };
+
+            loop {
+                // Anything published while unsubscribed is picked up here
+                let Some(local) = revocations.upgrade() else { return };
+                match load(&connection, clock.now()).await {
+                    Ok(loaded) => *local.write().unwrap() = loaded,
+                    Err(e) => tracing::warn!(error = %e, "Failed to reload token denylist"),
+                }
+                drop(local);
+
+                let Some(stream) = messages.as_mut() else {
+                    tokio::time::sleep(refresh).await;
+                    break;
+                };
+                let deadline = tokio::time::Instant::now() + refresh;
+                let ended = loop {
+                    match tokio::time::timeout_at(deadline, stream.next()).await {
+                        Ok(Some(message)) => {
+                            let Some(local) = revocations.upgrade() else { return };
+                            apply(&mut local.write().unwrap(), &message);
+                        }
+                        Ok(None) => break true,
+                        Err(_) => break false,
+                    }
+                };
+                if ended {
+                    tracing::warn!("Token revocation subscription ended, resubscribing");
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn subscribe(client: &::redis::Client) -> ::redis::RedisResult<BoxStream<'static, ::redis::Msg>> {
+        #[allow(deprecated)]
+        let mut pubsub = client.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(CHANNEL).await?;
+        Ok(pubsub.into_on_message().boxed())
+    }
+
+    fn apply(revocations: &mut Revocations, message: &::redis::Msg) {
+        let published = message
+            .get_payload::<String>()
+            .ok()
+            .and_then(|payload| serde_json::from_str::<Published>(&payload).ok());
+        match published {
+            Some(Published::Token { jti, until }) => {
+                revocations.tokens.insert(jti, timestamp(until));
+            }
+            Some(Published::User { user_id, at, until }) => {
+                let (at, until) = (timestamp(at), timestamp(until));
+                let entry = revocations.users.entry(user_id).or_insert((at, until));
+                *entry = (entry.0.max(at), entry.1.max(until));
+            }
+            None => tracing::warn!("Ignoring malformed token revocation message"),
+        }
+    }
+
+    /// What Redis holds, dropping entries expired at `now`
+    async fn load(connection: &::redis::aio::ConnectionManager, now: DateTime<Utc>) -> Result<Revocations, ApiError> {
+        let mut connection = connection.clone();
+
+        connection
+            .zrembyscore::<_, _, _, ()>(TOKENS_KEY, "-inf", now.timestamp())
+            .await
+            .map_err(redis_error)?;
+        let tokens: Vec<(String, i64)> = connection
+            .zrangebyscore_withscores(TOKENS_KEY, now.timestamp(), "+inf")
+            .await
+            .map_err(redis_error)?;
+        let users: HashMap<String, String> = connection.hgetall(USERS_KEY).await.map_err(redis_error)?;
+
+        let mut revocations = Revocations::default();
+        for (jti, until) in tokens {
+            revocations.tokens.insert(jti, timestamp(until));
+        }
+        let mut expired = Vec::new();
+        for (user_id, value) in users {
+            let Some((at, until)) = value.split_once(':') else { continue };
+            let (at, until) = (timestamp(at.parse().unwrap_or(0)), timestamp(until.parse().unwrap_or(0)));
+            if until <= now {
+                expired.push(user_id);
+            } else {
+                revocations.users.insert(user_id, (at, until));
+            }
+        }
+        if !expired.is_empty() {
+            connection.hdel::<_, _, ()>(USERS_KEY, expired).await.map_err(redis_error)?;
+        }
+
+        Ok(revocations)
+    }
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    fn redis_error(e: ::redis::RedisError) -> ApiError {
+        ApiError::InternalServerError(format!("Redis denylist error: {}", e))
+    }
+
+    #[async_trait::async_trait]
+    impl TokenDenylist for RedisTokenDenylist {
+        fn is_revoked(&self, claims: &Claims) -> bool {
+            self.local.is_revoked(claims)
+        }
+
+        async fn revoke_token(&self, jti: &str, until: DateTime<Utc>) -> Result<(), ApiError> {
+            let mut connection = self.connection.clone();
+            connection
+                .zadd::<_, _, _, ()>(TOKENS_KEY, jti, until.timestamp())
+                .await
+                .map_err(redis_error)?;
+            self.local.revoke_token(jti, until).await?;
+            self.publish(&Published::Token {
+                jti: jti.to_string(),
+                until: until.timestamp(),
+            })
+            .await
+        }
+
+        async fn revoke_user(&self, user_id: &str, at: DateTime<Utc>, until: DateTime<Utc>) -> Result<(), ApiError> {
+            let mut connection = self.connection.clone();
+            connection
+                .hset::<_, _, _, ()>(USERS_KEY, user_id, format!("{}:{}", at.timestamp(), until.timestamp()))
+                .await
+                .map_err(redis_error)?;
+            self.local.revoke_user(user_id, at, until).await?;
+            self.publish(&Published::User {
+                user_id: user_id.to_string(),
+                at: at.timestamp(),
+                until: until.timestamp(),
+            })
+            .await
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_apply_published_revocations() {
+            let mut revocations = Revocations::default();
+            let message = |published: Published| {
+                let payload = serde_json::to_string(&published).unwrap();
+                ::redis::Msg::from_value(&::redis::Value::Bulk(vec![
+                    ::redis::Value::Data(b"message".to_vec()),
+                    ::redis::Value::Data(CHANNEL.as_bytes().to_vec()),
+                    ::redis::Value::Data(payload.into_bytes()),
+                ]))
+                .unwrap()
+            };
+
+            apply(&mut revocations, &message(Published::Token { jti: "abc".to_string(), until: i64::MAX }));
+            apply(&mut revocations, &message(Published::User { user_id: "user-1".to_string(), at: 100, until: 200 }));
+            apply(&mut revocations, &message(Published::User { user_id: "user-1".to_string(), at: 50, until: 300 }));
+
+            assert!(revocations.tokens.contains_key("abc"));
+            assert_eq!(revocations.users["user-1"], (timestamp(100), timestamp(300)));
+        }
+    }
+}
+
+/// Revoke a single token
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct RevokeTokenRequest {
+    /// `jti` claim of the token
+    #[validate(length(min = 1, message = "Token ID is required"))]
+    pub jti: String,
+}
+
+/// Revoke every token of a user
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct RevokeUserRequest {
+    #[validate(length(min = 1, message = "User ID is required"))]
+    pub user_id: String,
+}
+
+fn denylist(config: &AuthConfig) -> Result<&Arc<dyn TokenDenylist>, ApiError> {
+    config
+        .denylist
+        .as_ref()
+        .ok_or_else(|| ApiError::InternalServerError("No token denylist configured".to_string()))
+}
+
+/// When an entry can be forgotten: once every token it covers has expired
+fn revocation_until(config: &AuthConfig) -> DateTime<Utc> {
+    let longest = config.access_token_expiry_secs.max(config.refresh_token_expiry_secs);
+    let longest = chrono::Duration::seconds(i64::try_from(longest).unwrap_or(i64::MAX / 1000));
+    config.clock.now().checked_add_signed(longest).unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Revoke a token by `jti` (admins only)
+pub async fn revoke_token(
+    user: AuthUser,
+    State(config): State<AuthConfig>,
+    ValidatedJson(payload): ValidatedJson<RevokeTokenRequest>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    if !user.has_role("admin") {
+        return Err(ApiError::Forbidden);
+    }
+    denylist(&config)?.revoke_token(&payload.jti, revocation_until(&config)).await?;
+
+    tracing::info!(admin_id = %user.id, jti = %payload.jti, "Token revoked");
+    let event = AuditEvent::new(AuditEventKind::TokensRevoked, config.clock.now())
+        .with_actor(&user.id)
+        .with_detail(format!("jti {}", payload.jti));
+    emit(config.audit.as_ref(), event).await;
+    Ok(Json(MessageResponse::new("Token revoked")))
+}
+
+/// Revoke every token of a user (admins only)
+pub async fn revoke_user(
+    user: AuthUser,
+    State(config): State<AuthConfig>,
+    ValidatedJson(payload): ValidatedJson<RevokeUserRequest>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    if !user.has_role("admin") {
+        return Err(ApiError::Forbidden);
+    }
+    denylist(&config)?
+        .revoke_user(&payload.user_id, config.clock.now(), revocation_until(&config))
+        .await?;
+
+    tracing::info!(admin_id = %user.id, user_id = %payload.user_id, "User tokens revoked");
+    let event = AuditEvent::new(AuditEventKind::TokensRevoked, config.clock.now())
+        .with_user(&payload.user_id)
+        .with_actor(&user.id);
+    emit(config.audit.as_ref(), event).await;
+    Ok(Json(MessageResponse::new("User tokens revoked")))
+}
+
+/// Revoke every token of the signed-in user
+pub async fn logout_everywhere(
+    user: AuthUser,
+    State(config): State<AuthConfig>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    denylist(&config)?
+        .revoke_user(&user.id, config.clock.now(), revocation_until(&config))
+        .await?;
+
+    tracing::info!(user_id = %user.id, "Logged out everywhere");
+    let event = AuditEvent::new(AuditEventKind::TokensRevoked, config.clock.now())
+        .with_user(&user.id)
+        .with_detail("logged out everywhere");
+    emit(config.audit.as_ref(), event).await;
+    Ok(Json(MessageResponse::new("Logged out everywhere")))
+}
+
+/// Revocation routes
+///
+/// `POST /auth/logout-all` for the signed-in user, and
+/// `POST /auth/revoke/token` and `POST /auth/revoke/user` for admins.
+pub fn revocation_routes(config: AuthConfig) -> Router {
+    Router::new()
+        .route("/auth/logout-all", post(logout_everywhere))
+        .route("/auth/revoke/token", post(revoke_token))
+        .route("/auth/revoke/user", post(revoke_user))
+        .with_state(config.clone())
+        .layer(Extension(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::{create_token_pair, verify_access_token, verify_refresh_token};
+    use crate::clock::Clock;
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_revocation() {
+        let clock = crate::clock::ManualClock::frozen();
+        let config = AuthConfig::new("test-secret")
+            .with_clock(clock.shared())
+            .with_denylist(InMemoryTokenDenylist::new());
+        let admin = create_token_pair("admin-1", "admin@example.com", vec!["admin".to_string()], &config).unwrap();
+        let first = create_token_pair("user-1", "ada@example.com", vec!["user".to_string()], &config).unwrap();
+        let second = create_token_pair("user-1", "ada@example.com", vec!["user".to_string()], &config).unwrap();
+        let router = revocation_routes(config.clone());
+
+        let call = |path: &str, token: &str, body: serde_json::Value| {
+            Request::post(path)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let jti = verify_access_token(&first.access_token, &config).unwrap().jti;
+        let request = call("/auth/revoke/token", &first.access_token, serde_json::json!({"jti": jti}));
+        assert_eq!(router.clone().oneshot(request).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let request = call("/auth/revoke/token", &admin.access_token, serde_json::json!({"jti": jti}));
+        assert_eq!(router.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+        assert!(verify_access_token(&first.access_token, &config).is_err());
+        assert!(verify_access_token(&second.access_token, &config).is_ok());
+
+        let request = call("/auth/logout-all", &second.access_token, serde_json::json!({}));
+        assert_eq!(router.oneshot(request).await.unwrap().status(), StatusCode::OK);
+        assert!(verify_access_token(&second.access_token, &config).is_err());
+        assert!(verify_refresh_token(&second.refresh_token, &config).is_err());
+
+        clock.advance(Duration::from_secs(1));
+        let fresh = create_token_pair("user-1", "ada@example.com", vec!["user".to_string()], &config).unwrap();
+        assert!(verify_access_token(&fresh.access_token, &config).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_prune_follows_clock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let denylist = InMemoryTokenDenylist::new().with_clock(clock.shared());
+        let until = clock.now() + chrono::Duration::seconds(60);
+
+        denylist.revoke_token("first", until).await.unwrap();
+        denylist.revoke_token("second", until).await.unwrap();
+        assert_eq!(denylist.revocations.read().unwrap().tokens.len(), 2);
+
+        clock.advance(Duration::from_secs(61));
+        denylist.revoke_token("third", clock.now() + chrono::Duration::seconds(60)).await.unwrap();
+        let tokens = &denylist.revocations.read().unwrap().tokens;
+        assert_eq!(tokens.keys().collect::<Vec<_>>(), ["third"]);
+    }
+}
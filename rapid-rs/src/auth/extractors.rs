@@ -13,6 +13,7 @@ use super::{
     config::AuthConfig,
     jwt::{verify_access_token, Claims},
 };
+use crate::middleware::RequestId;
 
 /// Authenticated user extracted from JWT token
 ///
@@ -96,6 +97,15 @@ impl AuthUser {
         }
     }
 
+    /// Require that the user signed in with a second factor
+    pub fn require_mfa(&self) -> Result<(), AuthError> {
+        if self.claims.is_mfa() {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden("Two-factor authentication required".to_string()))
+        }
+    }
+
     /// Require all of the specified roles
     pub fn require_all_roles(&self, roles: &[&str]) -> Result<(), AuthError> {
         if self.has_all_roles(roles) {
@@ -126,6 +136,8 @@ pub enum AuthError {
 struct AuthErrorResponse {
     code: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl IntoResponse for AuthError {
@@ -148,6 +160,7 @@ impl IntoResponse for AuthError {
         let body = AuthErrorResponse {
             code: code.to_string(),
             message,
+            request_id: RequestId::current().map(|id| id.0),
         };
 
         (status, Json(body)).into_response()
@@ -193,6 +206,7 @@ where
         // Verify token and extract claims
         let claims =
             verify_access_token(token, &auth_config).map_err(|_| AuthError::InvalidToken)?;
+        crate::reporting::record_user(&parts.extensions, &claims.sub);
 
         Ok(AuthUser::from_claims(claims))
     }
@@ -247,6 +261,8 @@ mod tests {
             iss: "test".to_string(),
             aud: "test".to_string(),
             jti: "test-jti".to_string(),
+            amr: vec![],
+            scopes: vec![],
         }
     }
 
@@ -7,45 +7,35 @@ use axum::{
     Router,
 };
 
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
 use super::{
+    audit::{emit, AuditEvent, AuditEventKind, AuditSink},
     config::AuthConfig,
-    jwt::{create_token_pair, verify_refresh_token},
+    jwt::{create_token_pair_with_amr, verify_refresh_token, TokenPair},
     models::*,
     extractors::AuthUser,
 };
+use crate::client_ip::ClientInfo;
 use crate::error::ApiError;
 use crate::extractors::ValidatedJson;
 
 /// User storage trait - implement this for your database
 /// 
 /// This trait defines the interface for user storage operations.
-/// Implement this for your specific database (PostgreSQL, MySQL, etc.)
+/// PostgreSQL is covered by [`PostgresUserStore`](super::PostgresUserStore)
+/// with the `database` feature; implement it for other databases.
 /// 
 /// # Example
 /// 
 /// ```rust,ignore
-/// use rapid_rs::auth::{UserStore, StoredUser};
-/// use sqlx::PgPool;
+/// use rapid_rs::auth::{auth_routes_with_store, AuthConfig, PostgresUserStore};
 /// 
-/// struct PostgresUserStore {
-///     pool: PgPool,
-/// }
+/// let store = PostgresUserStore::new(pool);
+/// store.init().await?;
 /// 
-/// #[async_trait]
-/// impl UserStore for PostgresUserStore {
-///     async fn find_by_email(&self, email: &str) -> Result<Option<StoredUser>, ApiError> {
-///         let user = sqlx::query_as!(
-///             StoredUser,
-///             "SELECT id, email, name, password_hash, roles FROM users WHERE email = $1",
-///             email
-///         )
-///         .fetch_optional(&self.pool)
-///         .await?;
-///         Ok(user)
-///     }
-///     
-///     // ... implement other methods
-/// }
+/// let routes = auth_routes_with_store(AuthConfig::default(), store);
 /// ```
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync + 'static {
@@ -112,7 +102,7 @@ impl UserStore for InMemoryUserStore {
     
     async fn create(&self, user: CreateUserData) -> Result<StoredUser, ApiError> {
         let mut users = self.users.lock().unwrap();
-        let id = uuid::Uuid::new_v4().to_string();
+        let id = crate::ids::new_id();
         let stored = StoredUser {
             id: id.clone(),
             email: user.email,
@@ -147,30 +137,69 @@ pub struct AuthAppState<S: UserStore> {
     pub user_store: S,
 }
 
+/// `amr` for a password sign-in
+pub(crate) fn password_amr() -> Vec<String> {
+    vec!["pwd".to_string()]
+}
+
 /// Login handler
 /// 
 /// Authenticates a user with email and password, returns JWT tokens.
 pub async fn login<S: UserStore>(
     State(state): State<AuthAppState<S>>,
+    client: Option<ClientInfo>,
     ValidatedJson(payload): ValidatedJson<LoginRequest>,
 ) -> Result<Json<AuthResponse>, ApiError> {
+    let config = &state.config;
+    let user = check_password(&state.user_store, config.audit.as_ref(), config.clock.now(), client, &payload).await?;
+    
+    // Generate tokens
+    let token_pair =
+        create_token_pair_with_amr(&user.id, &user.email, user.roles.clone(), password_amr(), config)?;
+    
+    emit(config.audit.as_ref(), login_succeeded(&user, config.clock.now(), client)).await;
+    Ok(Json(auth_response(user, token_pair)))
+}
+
+/// The user with these credentials, recording failed attempts
+pub(crate) async fn check_password<S: UserStore>(
+    store: &S,
+    audit: Option<&Arc<dyn AuditSink>>,
+    now: DateTime<Utc>,
+    client: Option<ClientInfo>,
+    payload: &LoginRequest,
+) -> Result<StoredUser, ApiError> {
+    let failed = AuditEvent::new(AuditEventKind::LoginFailed, now)
+        .with_email(&payload.email)
+        .with_client(client);
+    
     // Find user by email
-    let user = state
-        .user_store
-        .find_by_email(&payload.email)
-        .await?
-        .ok_or_else(|| ApiError::Unauthorized)?;
+    let Some(user) = store.find_by_email(&payload.email).await? else {
+        emit(audit, failed.with_detail("unknown email")).await;
+        return Err(ApiError::Unauthorized);
+    };
     
     // Verify password
     let password_valid = super::password::verify_password(&payload.password, &user.password_hash)?;
     if !password_valid {
+        emit(audit, failed.with_user(&user.id).with_detail("invalid password")).await;
         return Err(ApiError::Unauthorized);
     }
     
-    // Generate tokens
-    let token_pair = create_token_pair(&user.id, &user.email, user.roles.clone(), &state.config)?;
-    
-    Ok(Json(AuthResponse {
+    Ok(user)
+}
+
+/// Audit event for a completed sign-in
+pub(crate) fn login_succeeded(user: &StoredUser, now: DateTime<Utc>, client: Option<ClientInfo>) -> AuditEvent {
+    AuditEvent::new(AuditEventKind::LoginSucceeded, now)
+        .with_user(&user.id)
+        .with_email(&user.email)
+        .with_client(client)
+}
+
+/// Response body for a successful sign-in
+pub(crate) fn auth_response(user: StoredUser, token_pair: TokenPair) -> AuthResponse {
+    AuthResponse {
         access_token: token_pair.access_token,
         refresh_token: token_pair.refresh_token,
         token_type: token_pair.token_type,
@@ -181,7 +210,7 @@ pub async fn login<S: UserStore>(
             name: user.name,
             roles: user.roles,
         },
-    }))
+    }
 }
 
 /// Registration handler
@@ -189,6 +218,7 @@ pub async fn login<S: UserStore>(
 /// Creates a new user account and returns JWT tokens.
 pub async fn register<S: UserStore>(
     State(state): State<AuthAppState<S>>,
+    client: Option<ClientInfo>,
     ValidatedJson(payload): ValidatedJson<RegisterRequest>,
 ) -> Result<Json<AuthResponse>, ApiError> {
     // Validate password strength
@@ -213,22 +243,17 @@ pub async fn register<S: UserStore>(
         .await?;
     
     // Generate tokens
-    let token_pair = create_token_pair(&user.id, &user.email, user.roles.clone(), &state.config)?;
+    let token_pair =
+        create_token_pair_with_amr(&user.id, &user.email, user.roles.clone(), password_amr(), &state.config)?;
     
     tracing::info!(user_id = %user.id, "New user registered");
+    let event = AuditEvent::new(AuditEventKind::Registered, state.config.clock.now())
+        .with_user(&user.id)
+        .with_email(&user.email)
+        .with_client(client);
+    emit(state.config.audit.as_ref(), event).await;
     
-    Ok(Json(AuthResponse {
-        access_token: token_pair.access_token,
-        refresh_token: token_pair.refresh_token,
-        token_type: token_pair.token_type,
-        expires_in: token_pair.expires_in,
-        user: AuthUserInfo {
-            id: user.id,
-            email: user.email,
-            name: user.name,
-            roles: user.roles,
-        },
-    }))
+    Ok(Json(auth_response(user, token_pair)))
 }
 
 /// Refresh token handler
@@ -248,21 +273,13 @@ pub async fn refresh_token<S: UserStore>(
         .await?
         .ok_or_else(|| ApiError::Unauthorized)?;
     
-    // Generate new tokens
-    let token_pair = create_token_pair(&user.id, &user.email, user.roles.clone(), &state.config)?;
+    // Generate new tokens, keeping how the user originally signed in
+    let token_pair =
+        create_token_pair_with_amr(&user.id, &user.email, user.roles.clone(), claims.amr, &state.config)?;
     
-    Ok(Json(AuthResponse {
-        access_token: token_pair.access_token,
-        refresh_token: token_pair.refresh_token,
-        token_type: token_pair.token_type,
-        expires_in: token_pair.expires_in,
-        user: AuthUserInfo {
-            id: user.id,
-            email: user.email,
-            name: user.name,
-            roles: user.roles,
-        },
-    }))
+    let event = AuditEvent::new(AuditEventKind::TokenRefreshed, state.config.clock.now()).with_user(&user.id);
+    emit(state.config.audit.as_ref(), event).await;
+    Ok(Json(auth_response(user, token_pair)))
 }
 
 /// Logout handler
@@ -296,6 +313,31 @@ pub async fn me<S: UserStore>(
     }))
 }
 
+/// Change the signed-in user's password
+pub async fn change_password<S: UserStore>(
+    user: AuthUser,
+    State(state): State<AuthAppState<S>>,
+    ValidatedJson(payload): ValidatedJson<ChangePasswordRequest>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let stored_user = state
+        .user_store
+        .find_by_id(&user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+    
+    if !super::password::verify_password(&payload.current_password, &stored_user.password_hash)? {
+        return Err(ApiError::BadRequest("Current password is incorrect".to_string()));
+    }
+    super::password::validate_password_strength(&payload.new_password)?;
+    
+    let password_hash = super::password::hash_password(&payload.new_password, &state.config)?;
+    state.user_store.update_password(&user.id, &password_hash).await?;
+    
+    let event = AuditEvent::new(AuditEventKind::PasswordChanged, state.config.clock.now()).with_user(&user.id);
+    emit(state.config.audit.as_ref(), event).await;
+    Ok(Json(MessageResponse::new("Password changed")))
+}
+
 /// Create auth routes with a custom user store
 /// 
 /// # Example
@@ -323,6 +365,7 @@ pub fn auth_routes_with_store<S: UserStore + Clone>(
         .route("/auth/refresh", post(refresh_token::<S>))
         .route("/auth/logout", post(logout))
         .route("/auth/me", get(me::<S>))
+        .route("/auth/password", post(change_password::<S>))
         .with_state(state)
 }
 
@@ -1,6 +1,6 @@
 //! JWT token generation and verification
 
-use chrono::{Duration, Utc};
+use chrono::Duration;
 use jsonwebtoken::{
     decode, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
 };
@@ -43,6 +43,14 @@ pub struct Claims {
 
     /// JWT ID (unique identifier for this token)
     pub jti: String,
+
+    /// Authentication methods used to sign in (RFC 8176), e.g. `pwd`, `otp`, `mfa`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<String>,
+
+    /// Permissions granted to the token, checked by [`RouteAuth`](super::RouteAuth) scopes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
 }
 
 impl Claims {
@@ -53,7 +61,7 @@ impl Claims {
         roles: Vec<String>,
         config: &AuthConfig,
     ) -> Self {
-        let now = Utc::now();
+        let now = config.clock.now();
         let exp = now + Duration::seconds(config.access_token_expiry_secs as i64);
 
         Self {
@@ -67,6 +75,8 @@ impl Claims {
             iss: config.issuer.clone(),
             aud: config.audience.clone(),
             jti: Uuid::new_v4().to_string(),
+            amr: vec![],
+            scopes: vec![],
         }
     }
 
@@ -76,7 +86,7 @@ impl Claims {
         email: impl Into<String>,
         config: &AuthConfig,
     ) -> Self {
-        let now = Utc::now();
+        let now = config.clock.now();
         let exp = now + Duration::seconds(config.refresh_token_expiry_secs as i64);
 
         Self {
@@ -90,9 +100,28 @@ impl Claims {
             iss: config.issuer.clone(),
             aud: config.audience.clone(),
             jti: Uuid::new_v4().to_string(),
+            amr: vec![],
+            scopes: vec![],
         }
     }
 
+    /// Record how the user authenticated
+    pub fn with_amr(mut self, amr: Vec<String>) -> Self {
+        self.amr = amr;
+        self
+    }
+
+    /// Grant scopes to the token
+    pub fn with_scopes(mut self, scopes: Vec<String>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// Whether the user passed multi-factor authentication
+    pub fn is_mfa(&self) -> bool {
+        self.amr.iter().any(|method| method == "mfa")
+    }
+
     /// Check if this is an access token
     pub fn is_access_token(&self) -> bool {
         self.token_type == "access"
@@ -141,27 +170,32 @@ pub fn create_token_pair(
     email: impl Into<String>,
     roles: Vec<String>,
     config: &AuthConfig,
+) -> Result<TokenPair, ApiError> {
+    create_token_pair_with_amr(user_id, email, roles, vec![], config)
+}
+
+/// Create a token pair recording the authentication methods used
+///
+/// Both tokens carry `amr`, so refreshed tokens keep it.
+pub fn create_token_pair_with_amr(
+    user_id: impl Into<String>,
+    email: impl Into<String>,
+    roles: Vec<String>,
+    amr: Vec<String>,
+    config: &AuthConfig,
 ) -> Result<TokenPair, ApiError> {
     let user_id = user_id.into();
     let email = email.into();
 
     // Create access token
-    let access_claims = Claims::new_access(&user_id, &email, roles, config);
-    let access_token = encode(
-        &Header::new(Algorithm::HS256),
-        &access_claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
-    )
-    .map_err(|e| ApiError::InternalServerError(format!("Failed to create access token: {}", e)))?;
+    let access_claims = Claims::new_access(&user_id, &email, roles, config).with_amr(amr.clone());
+    let access_token = encode_claims(&access_claims, config)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to create access token: {}", e)))?;
 
     // Create refresh token
-    let refresh_claims = Claims::new_refresh(&user_id, &email, config);
-    let refresh_token = encode(
-        &Header::new(Algorithm::HS256),
-        &refresh_claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
-    )
-    .map_err(|e| ApiError::InternalServerError(format!("Failed to create refresh token: {}", e)))?;
+    let refresh_claims = Claims::new_refresh(&user_id, &email, config).with_amr(amr);
+    let refresh_token = encode_claims(&refresh_claims, config)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to create refresh token: {}", e)))?;
 
     Ok(TokenPair {
         access_token,
@@ -171,6 +205,15 @@ pub fn create_token_pair(
     })
 }
 
+/// Sign `claims` with the configured secret
+pub(crate) fn encode_claims(claims: &Claims, config: &AuthConfig) -> jsonwebtoken::errors::Result<String> {
+    encode(
+        &Header::new(Algorithm::HS256),
+        claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )
+}
+
 /// Verify a JWT token and return the claims
 pub fn verify_token(token: &str, config: &AuthConfig) -> Result<Claims, ApiError> {
     // Sanitize the token string to remove quotes or whitespace
@@ -180,8 +223,9 @@ pub fn verify_token(token: &str, config: &AuthConfig) -> Result<Claims, ApiError
     let mut validation = Validation::new(Algorithm::HS256);
     validation.set_issuer(&[&config.issuer]);
     validation.set_audience(&[&config.audience]);
-    validation.validate_exp = true;
-    validation.validate_nbf = true;
+    // exp/nbf are checked below against the configured clock
+    validation.validate_exp = false;
+    validation.validate_nbf = false;
 
     let token_data: TokenData<Claims> = decode(
         token,
@@ -197,7 +241,23 @@ pub fn verify_token(token: &str, config: &AuthConfig) -> Result<Claims, ApiError
         }
     })?;
 
-    Ok(token_data.claims)
+    let claims = token_data.claims;
+    let now = config.clock.now().timestamp();
+    let leeway = validation.leeway as i64;
+    if claims.exp < now - leeway {
+        tracing::debug!("Token verification failed: expired");
+        return Err(ApiError::Unauthorized);
+    }
+    if claims.nbf > now + leeway {
+        tracing::debug!("Token verification failed: not yet valid");
+        return Err(ApiError::Unauthorized);
+    }
+    if config.denylist.as_ref().is_some_and(|denylist| denylist.is_revoked(&claims)) {
+        tracing::debug!("Token verification failed: revoked");
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(claims)
 }
 
 /// Verify that a token is an access token
@@ -253,4 +313,19 @@ mod tests {
         assert_eq!(claims.sub, "user-123");
         assert!(claims.is_refresh_token());
     }
+
+    #[test]
+    fn test_expiry_uses_config_clock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let config = AuthConfig::default().with_clock(clock.shared());
+        let token_pair = create_token_pair("user-123", "test@example.com", vec![], &config).unwrap();
+
+        clock.advance(std::time::Duration::from_secs(config.access_token_expiry_secs));
+        assert!(verify_access_token(&token_pair.access_token, &config).is_ok());
+
+        // Past expiry plus the 60 second leeway
+        clock.advance(std::time::Duration::from_secs(61));
+        assert!(verify_access_token(&token_pair.access_token, &config).is_err());
+        assert!(verify_refresh_token(&token_pair.refresh_token, &config).is_ok());
+    }
 }
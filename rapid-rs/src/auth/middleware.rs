@@ -11,6 +11,7 @@ use serde::Serialize;
 use tower::{Layer, Service};
 
 use crate::auth::Claims;
+use crate::middleware::RequestId;
 
 use super::config::AuthConfig;
 use super::jwt::verify_access_token;
@@ -53,6 +54,8 @@ pub struct RequireAuth;
 struct AuthErrorResponse {
     code: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl RequireAuth {
@@ -75,6 +78,7 @@ impl RequireAuth {
                     Json(AuthErrorResponse {
                         code: "MISSING_TOKEN".to_string(),
                         message: "Authorization header missing or invalid".to_string(),
+                        request_id: RequestId::current().map(|id| id.0),
                     }),
                 )
                     .into_response();
@@ -84,6 +88,7 @@ impl RequireAuth {
         match verify_access_token(token, &config) {
             Ok(claims) => {
                 // Store claims so RequireRoles doesn't have to decode again
+                crate::reporting::record_user(request.extensions(), &claims.sub);
                 request.extensions_mut().insert(claims);
                 next.run(request).await
             }
@@ -92,6 +97,7 @@ impl RequireAuth {
                 Json(AuthErrorResponse {
                     code: "INVALID_TOKEN".to_string(),
                     message: "Invalid or expired token".to_string(),
+                    request_id: RequestId::current().map(|id| id.0),
                 }),
             )
                 .into_response(),
@@ -222,6 +228,7 @@ where
                     Json(AuthErrorResponse {
                         code: "FORBIDDEN".to_string(),
                         message: format!("Required roles: {:?}", roles),
+                        request_id: RequestId::current().map(|id| id.0),
                     }),
                 )
                     .into_response());
@@ -238,6 +245,7 @@ fn unauthorized_response(code: &str, message: &str) -> axum::response::Response
         Json(AuthErrorResponse {
             code: code.to_string(),
             message: message.to_string(),
+            request_id: RequestId::current().map(|id| id.0),
         }),
     )
         .into_response()
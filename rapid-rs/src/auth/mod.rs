@@ -20,18 +20,50 @@
 //! }
 //! ```
 
+pub mod audit;
 pub mod config;
+pub mod denylist;
 pub mod jwt;
 pub mod password;
 pub mod extractors;
 pub mod middleware;
 pub mod handlers;
 pub mod models;
+pub mod policy;
+pub mod route_auth;
+#[cfg(feature = "database")]
+pub mod postgres;
+#[cfg(feature = "oauth")]
+pub mod oauth;
+#[cfg(feature = "two-factor")]
+pub mod two_factor;
+#[cfg(feature = "sessions")]
+pub mod sessions;
 
+pub use audit::{audit_routes, AuditEvent, AuditEventKind, AuditQuery, AuditSink, InMemoryAuditSink, TracingAuditSink};
+#[cfg(feature = "database")]
+pub use audit::PostgresAuditSink;
 pub use config::AuthConfig;
-pub use jwt::{TokenPair, Claims, create_token_pair, verify_token};
+pub use denylist::{revocation_routes, InMemoryTokenDenylist, TokenDenylist};
+#[cfg(feature = "cache-redis")]
+pub use denylist::RedisTokenDenylist;
+pub use jwt::{TokenPair, Claims, create_token_pair, create_token_pair_with_amr, verify_token};
 pub use password::{hash_password, verify_password};
 pub use extractors::AuthUser;
 pub use middleware::RequireAuth;
+pub use policy::{Condition, Effect, Policy, PolicyLayer, Resource, Rule};
+pub use route_auth::{ApiKeyIdentity, ApiKeyStore, AuthScheme, RouteAuth, StaticApiKeys};
 pub use handlers::{auth_routes, login, register, refresh_token, logout, UserStore, StoredUser, CreateUserData, InMemoryUserStore, auth_routes_with_store, AuthAppState};
+#[cfg(feature = "database")]
+pub use postgres::PostgresUserStore;
+#[cfg(feature = "oauth")]
+pub use oauth::{oauth_routes, OAuthConfig, OAuthIdentity, OAuthProvider};
+#[cfg(all(feature = "oauth", feature = "two-factor"))]
+pub use oauth::oauth_routes_with_two_factor;
+#[cfg(feature = "two-factor")]
+pub use two_factor::{auth_routes_with_two_factor, InMemoryTwoFactorStore, PendingChallenge, TwoFactorRecord, TwoFactorStore};
+#[cfg(feature = "sessions")]
+pub use sessions::{session_routes, InMemorySessionStore, Session, SessionConfig, SessionStore, SessionUser, Sessions};
+#[cfg(all(feature = "sessions", feature = "two-factor"))]
+pub use sessions::{session_routes_with_two_factor, SessionTwoFactor};
 pub use models::{LoginRequest, RegisterRequest, AuthResponse, TokenRefreshRequest};
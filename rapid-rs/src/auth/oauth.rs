@@ -0,0 +1,817 @@
+//! OAuth2 / OpenID Connect social login
+//!
+//! Each provider gets two routes: `/auth/oauth/{provider}/login` redirects to
+//! the provider, and `/auth/oauth/{provider}/callback` exchanges the code,
+//! links the account to a user in the [`UserStore`] by verified email, and
+//! issues the usual token pair, or with [`oauth_routes_with_two_factor`] a
+//! two-factor challenge for users who enabled it. Sign-ins are audited like
+//! password logins. The authorization code flow always uses PKCE;
+//! the state and code verifier travel in a short-lived `HttpOnly` cookie, so
+//! no server-side session store is needed.
+//!
+//! ```rust,ignore
+//! let oauth = OAuthConfig::new("https://api.example.com")
+//!     .with_provider(OAuthProvider::google(google_id, google_secret))
+//!     .with_provider(OAuthProvider::github(github_id, github_secret))
+//!     .with_success_redirect("https://app.example.com/signed-in");
+//!
+//! App::new()
+//!     .auto_configure()
+//!     .mount(oauth_routes(auth_config, oauth, store))
+//! ```
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{
+    audit::{emit, AuditEvent, AuditEventKind},
+    config::AuthConfig,
+    handlers::{login_succeeded, CreateUserData, StoredUser, UserStore},
+    jwt::create_token_pair,
+    models::{AuthResponse, AuthUserInfo},
+};
+#[cfg(feature = "two-factor")]
+use super::two_factor::{issue_challenge, TwoFactorStore};
+use crate::client_ip::ClientInfo;
+use crate::error::ApiError;
+
+/// Cookie carrying `state.verifier` between login and callback
+const FLOW_COOKIE: &str = "rapid_oauth";
+
+/// How long a login attempt may take, in seconds
+const FLOW_MAX_AGE: u64 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProviderKind {
+    /// Standard OIDC userinfo endpoint
+    Oidc,
+    GitHub,
+}
+
+/// An OAuth2 identity provider
+#[derive(Debug, Clone)]
+pub struct OAuthProvider {
+    name: String,
+    client_id: String,
+    client_secret: String,
+    authorize_url: String,
+    token_url: String,
+    userinfo_url: String,
+    scopes: Vec<String>,
+    kind: ProviderKind,
+    trust_email: bool,
+}
+
+impl OAuthProvider {
+    /// Provider with an OIDC-style userinfo endpoint at known URLs
+    pub fn custom(
+        name: impl Into<String>,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+        authorize_url: impl Into<String>,
+        token_url: impl Into<String>,
+        userinfo_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            authorize_url: authorize_url.into(),
+            token_url: token_url.into(),
+            userinfo_url: userinfo_url.into(),
+            scopes: vec!["openid".to_string(), "email".to_string(), "profile".to_string()],
+            kind: ProviderKind::Oidc,
+            trust_email: false,
+        }
+    }
+
+    pub fn google(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        Self::custom(
+            "google",
+            client_id,
+            client_secret,
+            "https://accounts.google.com/o/oauth2/v2/auth",
+            "https://oauth2.googleapis.com/token",
+            "https://openidconnect.googleapis.com/v1/userinfo",
+        )
+    }
+
+    pub fn github(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        Self {
+            scopes: vec!["read:user".to_string(), "user:email".to_string()],
+            kind: ProviderKind::GitHub,
+            ..Self::custom(
+                "github",
+                client_id,
+                client_secret,
+                "https://github.com/login/oauth/authorize",
+                "https://github.com/login/oauth/access_token",
+                "https://api.github.com/user",
+            )
+        }
+    }
+
+    /// Microsoft identity platform for `tenant` (a tenant id, or `common`)
+    ///
+    /// Microsoft does not report whether emails are verified. Emails are
+    /// trusted for a single tenant, but not for `common`, `organizations` or
+    /// `consumers` where any directory can claim any address.
+    pub fn microsoft(
+        tenant: impl Into<String>,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Self {
+        let tenant = tenant.into();
+        let base = format!("https://login.microsoftonline.com/{}/oauth2/v2.0", tenant);
+        Self {
+            trust_email: !matches!(tenant.as_str(), "common" | "organizations" | "consumers"),
+            ..Self::custom(
+                "microsoft",
+                client_id,
+                client_secret,
+                format!("{}/authorize", base),
+                format!("{}/token", base),
+                "https://graph.microsoft.com/oidc/userinfo",
+            )
+        }
+    }
+
+    /// Any OpenID Connect provider, configured from its discovery document
+    pub async fn discover(
+        name: impl Into<String>,
+        issuer: &str,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Result<Self, ApiError> {
+        #[derive(Deserialize)]
+        struct Discovery {
+            authorization_endpoint: String,
+            token_endpoint: String,
+            userinfo_endpoint: String,
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+        let discovery: Discovery = Client::new()
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ApiError::InternalServerError(format!("OIDC discovery at {} failed: {}", url, e)))?
+            .json()
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid OIDC discovery document at {}: {}", url, e)))?;
+
+        Ok(Self::custom(
+            name,
+            client_id,
+            client_secret,
+            discovery.authorization_endpoint,
+            discovery.token_endpoint,
+            discovery.userinfo_endpoint,
+        ))
+    }
+
+    pub fn with_scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Link accounts by email even when the provider doesn't mark it verified
+    ///
+    /// Only for providers that verify every address they hand out.
+    pub fn with_trusted_email(mut self, trusted: bool) -> Self {
+        self.trust_email = trusted;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Providers and redirect settings for [`oauth_routes`]
+#[derive(Clone)]
+pub struct OAuthConfig {
+    base_url: String,
+    providers: HashMap<String, Arc<OAuthProvider>>,
+    success_redirect: Option<String>,
+    client: Client,
+}
+
+impl OAuthConfig {
+    /// `base_url` is the public URL of this app; callback URLs registered
+    /// with providers are `{base_url}/auth/oauth/{provider}/callback`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            providers: HashMap::new(),
+            success_redirect: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn with_provider(mut self, provider: OAuthProvider) -> Self {
+        self.providers.insert(provider.name.clone(), Arc::new(provider));
+        self
+    }
+
+    /// Redirect here after login with the tokens in the URL fragment,
+    /// instead of answering the callback with JSON
+    pub fn with_success_redirect(mut self, url: impl Into<String>) -> Self {
+        self.success_redirect = Some(url.into());
+        self
+    }
+
+    pub fn redirect_uri(&self, provider: &str) -> String {
+        format!("{}/auth/oauth/{}/callback", self.base_url, provider)
+    }
+
+    fn provider(&self, name: &str) -> Result<&OAuthProvider, ApiError> {
+        self.providers
+            .get(name)
+            .map(|provider| provider.as_ref())
+            .ok_or_else(|| ApiError::NotFound(format!("Unknown OAuth provider '{}'", name)))
+    }
+
+    fn flow_cookie(&self, value: &str, max_age: u64) -> HeaderValue {
+        let secure = if self.base_url.starts_with("https://") { "; Secure" } else { "" };
+        let cookie = format!(
+            "{}={}; Path=/auth/oauth; HttpOnly; SameSite=Lax; Max-Age={}{}",
+            FLOW_COOKIE, value, max_age, secure
+        );
+        HeaderValue::from_str(&cookie).expect("cookie is ASCII")
+    }
+}
+
+/// Identity reported by a provider
+#[derive(Debug, Clone)]
+pub struct OAuthIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+/// State for the OAuth routes
+#[derive(Clone)]
+pub struct OAuthAppState<S: UserStore> {
+    pub config: AuthConfig,
+    pub oauth: OAuthConfig,
+    pub user_store: S,
+    /// Set by [`oauth_routes_with_two_factor`]
+    #[cfg(feature = "two-factor")]
+    pub two_factor: Option<Arc<dyn TwoFactorStore>>,
+}
+
+/// Login and callback routes for every configured provider
+pub fn oauth_routes<S: UserStore + Clone>(config: AuthConfig, oauth: OAuthConfig, user_store: S) -> Router {
+    routes().with_state(OAuthAppState {
+        config,
+        oauth,
+        user_store,
+        #[cfg(feature = "two-factor")]
+        two_factor: None,
+    })
+}
+
+/// OAuth routes that ask for a second factor
+///
+/// Like [`oauth_routes`], but users with two-factor enabled in `two_factor`
+/// get a [`TwoFactorChallenge`](super::two_factor::TwoFactorChallenge) from
+/// the callback instead of tokens, to answer at `POST /auth/2fa/login` of
+/// [`auth_routes_with_two_factor`](super::auth_routes_with_two_factor) on
+/// the same store.
+#[cfg(feature = "two-factor")]
+pub fn oauth_routes_with_two_factor<S, T>(config: AuthConfig, oauth: OAuthConfig, user_store: S, two_factor: T) -> Router
+where
+    S: UserStore + Clone,
+    T: TwoFactorStore,
+{
+    routes().with_state(OAuthAppState {
+        config,
+        oauth,
+        user_store,
+        two_factor: Some(Arc::new(two_factor)),
+    })
+}
+
+fn routes<S: UserStore + Clone>() -> Router<OAuthAppState<S>> {
+    Router::new()
+        .route("/auth/oauth/:provider/login", get(oauth_login::<S>))
+        .route("/auth/oauth/:provider/callback", get(oauth_callback::<S>))
+}
+
+/// Redirect to the provider's consent page
+pub async fn oauth_login<S: UserStore>(
+    State(state): State<OAuthAppState<S>>,
+    Path(provider_name): Path<String>,
+) -> Result<Response, ApiError> {
+    let provider = state.oauth.provider(&provider_name)?;
+    let flow_state = random_token();
+    let verifier = random_token();
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+    let url = Url::parse_with_params(
+        &provider.authorize_url,
+        [
+            ("response_type", "code"),
+            ("client_id", provider.client_id.as_str()),
+            ("redirect_uri", state.oauth.redirect_uri(&provider.name).as_str()),
+            ("scope", provider.scopes.join(" ").as_str()),
+            ("state", flow_state.as_str()),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+    .map_err(|e| ApiError::InternalServerError(format!("Invalid authorize URL for {}: {}", provider.name, e)))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::LOCATION,
+        HeaderValue::from_str(url.as_str()).map_err(|e| ApiError::InternalServerError(e.to_string()))?,
+    );
+    headers.insert(
+        header::SET_COOKIE,
+        state.oauth.flow_cookie(&format!("{}.{}", flow_state, verifier), FLOW_MAX_AGE),
+    );
+    Ok((StatusCode::SEE_OTHER, headers).into_response())
+}
+
+/// Query parameters the provider sends back
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Finish the login: exchange the code, link the user and issue tokens
+pub async fn oauth_callback<S: UserStore>(
+    State(state): State<OAuthAppState<S>>,
+    Path(provider_name): Path<String>,
+    Query(params): Query<CallbackParams>,
+    headers: HeaderMap,
+    client: Option<ClientInfo>,
+) -> Result<Response, ApiError> {
+    let provider = state.oauth.provider(&provider_name)?;
+    let audit = state.config.audit.as_ref();
+    let failed = AuditEvent::new(AuditEventKind::LoginFailed, state.config.clock.now()).with_client(client);
+
+    if let Some(error) = params.error {
+        let description = params.error_description.unwrap_or_default();
+        tracing::warn!(provider = %provider.name, %error, %description, "OAuth login denied");
+        emit(audit, failed.with_detail(format!("{} denied the login: {}", provider.name, error))).await;
+        return Err(ApiError::Unauthorized);
+    }
+
+    // The state must match the one this browser started the flow with
+    let (expected_state, verifier) = flow_cookie(&headers)
+        .and_then(|value| value.split_once('.').map(|(s, v)| (s.to_string(), v.to_string())))
+        .ok_or_else(|| ApiError::BadRequest("OAuth login expired or was started elsewhere".to_string()))?;
+    if params.state.as_deref() != Some(expected_state.as_str()) {
+        return Err(ApiError::BadRequest("OAuth state mismatch".to_string()));
+    }
+    let code = params
+        .code
+        .ok_or_else(|| ApiError::BadRequest("Missing authorization code".to_string()))?;
+
+    let identity = async {
+        let access_token = exchange_code(&state.oauth, provider, &code, &verifier).await?;
+        fetch_identity(&state.oauth.client, provider, &access_token).await
+    };
+    let identity = match identity.await {
+        Ok(identity) => identity,
+        Err(error) => {
+            emit(audit, failed.with_detail(format!("{} sign-in failed", provider.name))).await;
+            return Err(error);
+        }
+    };
+    if !identity.email_verified && !provider.trust_email {
+        emit(audit, failed.with_email(&identity.email).with_detail(format!("{} email not verified", provider.name))).await;
+        return Err(ApiError::Forbidden);
+    }
+
+    let user = link_user(&state.user_store, &state.config, &identity).await?;
+
+    #[cfg(feature = "two-factor")]
+    if let Some(two_factor) = &state.two_factor {
+        if let Some(challenge) = issue_challenge(&**two_factor, &user, Vec::new(), &state.config).await? {
+            tracing::info!(user_id = %user.id, provider = %provider.name, "OAuth login awaiting second factor");
+            let fragment = format!(
+                "two_factor_required=true&challenge_token={}&expires_in={}",
+                challenge.challenge_token, challenge.expires_in
+            );
+            return respond(&state.oauth, fragment, Json(challenge));
+        }
+    }
+
+    let tokens = create_token_pair(&user.id, &user.email, user.roles.clone(), &state.config)?;
+    tracing::info!(user_id = %user.id, provider = %provider.name, "OAuth login");
+    emit(audit, login_succeeded(&user, state.config.clock.now(), client)).await;
+
+    let fragment = format!(
+        "access_token={}&refresh_token={}&token_type={}&expires_in={}",
+        tokens.access_token, tokens.refresh_token, tokens.token_type, tokens.expires_in
+    );
+    let body = Json(AuthResponse {
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        token_type: tokens.token_type,
+        expires_in: tokens.expires_in,
+        user: AuthUserInfo {
+            id: user.id,
+            email: user.email,
+            name: user.name,
+            roles: user.roles,
+        },
+    });
+    respond(&state.oauth, fragment, body)
+}
+
+/// End the flow: redirect with `fragment` to the success page if there is
+/// one, otherwise answer with `body`
+fn respond(oauth: &OAuthConfig, fragment: String, body: impl IntoResponse) -> Result<Response, ApiError> {
+    let clear_cookie = [(header::SET_COOKIE, oauth.flow_cookie("", 0))];
+    match &oauth.success_redirect {
+        Some(redirect) => {
+            let location = format!("{}#{}", redirect, fragment);
+            Ok((
+                StatusCode::SEE_OTHER,
+                clear_cookie,
+                [(header::LOCATION, HeaderValue::from_str(&location).map_err(|e| ApiError::InternalServerError(e.to_string()))?)],
+            )
+                .into_response())
+        }
+        None => Ok((clear_cookie, body).into_response()),
+    }
+}
+
+/// Existing user with the identity's email, or a new one
+///
+/// New users get an unguessable password, so they can only sign in through
+/// the provider until they set one.
+async fn link_user<S: UserStore>(
+    store: &S,
+    config: &AuthConfig,
+    identity: &OAuthIdentity,
+) -> Result<StoredUser, ApiError> {
+    if let Some(user) = store.find_by_email(&identity.email).await? {
+        return Ok(user);
+    }
+
+    let name = identity
+        .name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| identity.email.split('@').next().unwrap_or_default().to_string());
+    let password_hash = super::password::hash_password(&random_token(), config)?;
+
+    store
+        .create(CreateUserData {
+            email: identity.email.clone(),
+            name,
+            password_hash,
+        })
+        .await
+}
+
+async fn exchange_code(
+    oauth: &OAuthConfig,
+    provider: &OAuthProvider,
+    code: &str,
+    verifier: &str,
+) -> Result<String, ApiError> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        access_token: Option<String>,
+        error: Option<String>,
+    }
+
+    let redirect_uri = oauth.redirect_uri(&provider.name);
+    let response: TokenResponse = oauth
+        .client
+        .post(&provider.token_url)
+        .header(header::ACCEPT, "application/json")
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("client_secret", provider.client_secret.as_str()),
+            ("code_verifier", verifier),
+        ])
+        .send()
+        .await
+        .map_err(|e| ApiError::InternalServerError(format!("OAuth token request to {} failed: {}", provider.name, e)))?
+        .json()
+        .await
+        .map_err(|e| ApiError::InternalServerError(format!("Invalid OAuth token response from {}: {}", provider.name, e)))?;
+
+    response.access_token.ok_or_else(|| {
+        tracing::warn!(provider = %provider.name, error = ?response.error, "OAuth code exchange rejected");
+        ApiError::Unauthorized
+    })
+}
+
+async fn fetch_identity(client: &Client, provider: &OAuthProvider, access_token: &str) -> Result<OAuthIdentity, ApiError> {
+    let get = |url: String| {
+        client
+            .get(url)
+            .bearer_auth(access_token)
+            .header(header::USER_AGENT, "rapid-rs")
+            .header(header::ACCEPT, "application/json")
+            .send()
+    };
+    let failed = |e: reqwest::Error| {
+        ApiError::InternalServerError(format!("Fetching the {} user failed: {}", provider.name, e))
+    };
+
+    match provider.kind {
+        ProviderKind::Oidc => {
+            #[derive(Deserialize)]
+            struct UserInfo {
+                sub: String,
+                email: Option<String>,
+                #[serde(default)]
+                email_verified: Option<serde_json::Value>,
+                name: Option<String>,
+            }
+
+            let info: UserInfo = get(provider.userinfo_url.clone())
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(failed)?
+                .json()
+                .await
+                .map_err(failed)?;
+            // Some providers send the flag as a string
+            let email_verified = matches!(
+                info.email_verified,
+                Some(serde_json::Value::Bool(true))
+            ) || matches!(&info.email_verified, Some(serde_json::Value::String(s)) if s == "true");
+
+            Ok(OAuthIdentity {
+                provider: provider.name.clone(),
+                subject: info.sub,
+                email: info.email.ok_or(ApiError::Forbidden)?,
+                email_verified,
+                name: info.name,
+            })
+        }
+        ProviderKind::GitHub => {
+            #[derive(Deserialize)]
+            struct GitHubUser {
+                id: u64,
+                login: String,
+                name: Option<String>,
+            }
+            #[derive(Deserialize)]
+            struct GitHubEmail {
+                email: String,
+                primary: bool,
+                verified: bool,
+            }
+
+            let user: GitHubUser = get(provider.userinfo_url.clone())
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(failed)?
+                .json()
+                .await
+                .map_err(failed)?;
+            let emails: Vec<GitHubEmail> = get(format!("{}/emails", provider.userinfo_url))
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(failed)?
+                .json()
+                .await
+                .map_err(failed)?;
+            let email = emails
+                .into_iter()
+                .find(|email| email.primary && email.verified)
+                .ok_or(ApiError::Forbidden)?;
+
+            Ok(OAuthIdentity {
+                provider: provider.name.clone(),
+                subject: user.id.to_string(),
+                email: email.email,
+                email_verified: true,
+                name: user.name.or(Some(user.login)),
+            })
+        }
+    }
+}
+
+fn flow_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == FLOW_COOKIE && !value.is_empty()).then(|| value.to_string())
+        })
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{AuditSink, InMemoryUserStore};
+    use axum::{body::Body, http::Request, Form};
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    /// Minimal provider that checks the PKCE verifier against the challenge
+    async fn mock_provider(challenge: Arc<Mutex<String>>) -> String {
+        let token = move |Form(form): Form<HashMap<String, String>>| {
+            let challenge = challenge.lock().unwrap().clone();
+            async move {
+                let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+                let pkce_ok = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == challenge;
+                if form.get("code").map(String::as_str) == Some("good-code") && pkce_ok {
+                    Json(serde_json::json!({"access_token": "provider-token", "token_type": "Bearer"}))
+                } else {
+                    Json(serde_json::json!({"error": "invalid_grant"}))
+                }
+            }
+        };
+        let userinfo = |headers: HeaderMap| async move {
+            match headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+                Some("Bearer provider-token") => Json(serde_json::json!({
+                    "sub": "42",
+                    "email": "ada@example.com",
+                    "email_verified": true,
+                    "name": "Ada Lovelace"
+                }))
+                .into_response(),
+                _ => StatusCode::UNAUTHORIZED.into_response(),
+            }
+        };
+        let app = Router::new()
+            .route("/token", axum::routing::post(token))
+            .route("/userinfo", get(userinfo));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn provider(provider_url: &str) -> OAuthProvider {
+        OAuthProvider::custom(
+            "acme",
+            "client",
+            "secret",
+            format!("{}/authorize", provider_url),
+            format!("{}/token", provider_url),
+            format!("{}/userinfo", provider_url),
+        )
+    }
+
+    fn auth_config(audit: &crate::auth::InMemoryAuditSink) -> AuthConfig {
+        let mut config = AuthConfig::new("test-secret").with_audit_sink(audit.clone());
+        config.argon2_memory_cost = 1024;
+        config.argon2_time_cost = 1;
+        config
+    }
+
+    /// Start a login, returning the flow state and cookie for the callback
+    async fn start_login(router: &Router, challenge: &Mutex<String>) -> (String, String) {
+        let response = router
+            .clone()
+            .oneshot(Request::get("/auth/oauth/acme/login").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = Url::parse(response.headers()[header::LOCATION].to_str().unwrap()).unwrap();
+        let query: HashMap<String, String> = location.query_pairs().into_owned().collect();
+        assert_eq!(query["redirect_uri"], "http://localhost:3000/auth/oauth/acme/callback");
+        assert_eq!(query["code_challenge_method"], "S256");
+        *challenge.lock().unwrap() = query["code_challenge"].clone();
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        (query["state"].clone(), cookie.split(';').next().unwrap().to_string())
+    }
+
+    fn callback(state: &str, cookie: &str) -> Request<Body> {
+        Request::get(format!("/auth/oauth/acme/callback?code=good-code&state={}", state))
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn json(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_oauth_login_flow() {
+        let challenge = Arc::new(Mutex::new(String::new()));
+        let provider_url = mock_provider(challenge.clone()).await;
+        let audit = crate::auth::InMemoryAuditSink::new();
+        let store = InMemoryUserStore::new();
+        let oauth = OAuthConfig::new("http://localhost:3000").with_provider(provider(&provider_url));
+        let router = oauth_routes(auth_config(&audit), oauth, store.clone());
+
+        let (state, cookie) = start_login(&router, &challenge).await;
+        let response = router.clone().oneshot(callback("forged", &cookie)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = router.clone().oneshot(callback(&state, &cookie)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json(response).await;
+        assert_eq!(body["user"]["email"], "ada@example.com");
+        assert_eq!(body["user"]["name"], "Ada Lovelace");
+        let user = store.find_by_email("ada@example.com").await.unwrap().unwrap();
+
+        let denied = Request::get("/auth/oauth/acme/callback?error=access_denied").body(Body::empty()).unwrap();
+        assert_eq!(router.oneshot(denied).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let events = audit.query(&crate::auth::AuditQuery::default()).await.unwrap();
+        let kinds: Vec<_> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, [AuditEventKind::LoginFailed, AuditEventKind::LoginSucceeded]);
+        assert_eq!(events[1].user_id.as_deref(), Some(user.id.as_str()));
+    }
+
+    #[cfg(feature = "two-factor")]
+    #[tokio::test]
+    async fn test_oauth_login_asks_for_second_factor() {
+        use crate::auth::two_factor::{totp_code, InMemoryTwoFactorStore, TwoFactorRecord};
+
+        let challenge = Arc::new(Mutex::new(String::new()));
+        let provider_url = mock_provider(challenge.clone()).await;
+        let audit = crate::auth::InMemoryAuditSink::new();
+        let config = auth_config(&audit);
+        let store = InMemoryUserStore::new();
+        let user = link_user(
+            &store,
+            &config,
+            &OAuthIdentity {
+                provider: "acme".to_string(),
+                subject: "42".to_string(),
+                email: "ada@example.com".to_string(),
+                email_verified: true,
+                name: None,
+            },
+        )
+        .await
+        .unwrap();
+        let two_factor = InMemoryTwoFactorStore::new();
+        let mut record = TwoFactorRecord::new("JBSWY3DPEHPK3PXP".to_string());
+        record.enabled = true;
+        two_factor.save(&user.id, &record).await.unwrap();
+
+        let oauth = OAuthConfig::new("http://localhost:3000").with_provider(provider(&provider_url));
+        let router = oauth_routes_with_two_factor(config.clone(), oauth, store.clone(), two_factor.clone())
+            .merge(crate::auth::auth_routes_with_two_factor(config.clone(), store, two_factor));
+
+        let (state, cookie) = start_login(&router, &challenge).await;
+        let response = router.clone().oneshot(callback(&state, &cookie)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json(response).await;
+        assert_eq!(body["two_factor_required"], true);
+        assert!(body.get("access_token").is_none());
+        assert!(audit.query(&crate::auth::AuditQuery::default()).await.unwrap().is_empty());
+
+        let answer = serde_json::json!({
+            "challenge_token": body["challenge_token"],
+            "code": totp_code(&record.secret, config.clock.now()).unwrap(),
+        });
+        let request = Request::post("/auth/2fa/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(answer.to_string()))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json(response).await;
+        let claims = crate::auth::verify_token(body["access_token"].as_str().unwrap(), &config).unwrap();
+        assert_eq!(claims.amr, ["otp", "mfa"]);
+        let events = audit.query(&crate::auth::AuditQuery::default()).await.unwrap();
+        assert_eq!(events[0].kind, AuditEventKind::LoginSucceeded);
+    }
+}
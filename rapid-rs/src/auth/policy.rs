@@ -0,0 +1,561 @@
+//! Declarative authorization policies
+//!
+//! Instead of role checks scattered across handlers, rules live in one
+//! policy file. Each rule names a subject (`*`, `role:<name>` or
+//! `user:<id>`), a resource pattern, the actions it covers and optional
+//! conditions. Deny rules win over allow rules, and anything not allowed is
+//! denied:
+//!
+//! ```toml
+//! [[rules]]
+//! subject = "role:editor"
+//! resource = "/posts/**"
+//! actions = ["read", "update"]
+//!
+//! [[rules]]
+//! subject = "role:author"
+//! resource = "/posts/*"
+//! actions = ["update", "delete", "publish"]
+//! conditions = ["owner", "same_tenant"]
+//!
+//! [[rules]]
+//! effect = "deny"
+//! subject = "*"
+//! resource = "/posts/*/audit"
+//! actions = ["*"]
+//! ```
+//!
+//! In resource patterns `*` matches one path segment and `**` any number of
+//! them. [`PolicyLayer`] checks requests against the policy, mapping the
+//! method to `read`, `create`, `update` or `delete`; the [`authorize!`]
+//! macro checks loaded models in handlers:
+//!
+//! ```rust,ignore
+//! let policy = Policy::from_file("config/policy.toml")?;
+//! let posts = Router::new()
+//!     .route("/posts/:id", put(update_post))
+//!     .layer(PolicyLayer::new(policy));
+//!
+//! async fn update_post(user: AuthUser, Path(id): Path<Uuid>) -> ApiResult<Json<Post>> {
+//!     let post = load_post(id).await?;
+//!     authorize!(user, "update", post)?;
+//!     // ...
+//! }
+//! ```
+
+use axum::{
+    extract::Request,
+    http::{header::AUTHORIZATION, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+use super::{config::AuthConfig, extractors::AuthUser, jwt::verify_access_token, Claims};
+use crate::error::ApiError;
+
+/// Something rules can be written about
+///
+/// `path` is matched against rule resource patterns; using the model's URL
+/// lets one rule cover both the route and the model.
+pub trait Resource {
+    fn path(&self) -> String;
+
+    /// User ID of the owner, for the `owner` condition
+    fn owner(&self) -> Option<&str> {
+        None
+    }
+
+    /// Tenant the resource belongs to, for the `same_tenant` condition
+    fn tenant(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Whether a matching rule grants or refuses access
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// Extra requirement for a rule to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// The resource's owner is the user
+    Owner,
+    /// The resource belongs to the tenant the request was made for
+    SameTenant,
+}
+
+/// One policy rule
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub effect: Effect,
+    pub subject: String,
+    pub resource: String,
+    pub actions: Vec<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+impl Rule {
+    pub fn allow(
+        subject: impl Into<String>,
+        resource: impl Into<String>,
+        actions: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            effect: Effect::Allow,
+            subject: subject.into(),
+            resource: resource.into(),
+            actions: actions.into_iter().map(Into::into).collect(),
+            conditions: Vec::new(),
+        }
+    }
+
+    pub fn deny(
+        subject: impl Into<String>,
+        resource: impl Into<String>,
+        actions: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            effect: Effect::Deny,
+            ..Self::allow(subject, resource, actions)
+        }
+    }
+
+    /// Only apply when `condition` holds
+    pub fn when(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    fn applies_to(&self, subject: Option<&Subject>, action: &str, path: &str) -> bool {
+        let subject_matches = match (self.subject.as_str(), subject) {
+            ("*", _) => true,
+            (_, None) => false,
+            (rule, Some(subject)) => {
+                if let Some(role) = rule.strip_prefix("role:") {
+                    subject.roles.iter().any(|r| r == role)
+                } else if let Some(id) = rule.strip_prefix("user:") {
+                    subject.id == id
+                } else {
+                    false
+                }
+            }
+        };
+        subject_matches
+            && self.actions.iter().any(|a| a == "*" || a == action)
+            && matches_pattern(&self.resource, path)
+    }
+}
+
+/// Who is asking
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Subject {
+    id: String,
+    roles: Vec<String>,
+    tenant: Option<String>,
+}
+
+impl Subject {
+    fn new(id: &str, roles: &[String], tenant: Option<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            roles: roles.to_vec(),
+            tenant,
+        }
+    }
+}
+
+/// A set of authorization rules
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Load rules from a TOML, YAML or JSON file, by its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ApiError> {
+        Self::load(config::File::from(path.as_ref()))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ApiError> {
+        Self::load(config::File::from_str(toml, config::FileFormat::Toml))
+    }
+
+    fn load<S: config::Source + Send + Sync + 'static>(source: S) -> Result<Self, ApiError> {
+        config::Config::builder()
+            .add_source(source)
+            .build()
+            .and_then(|config| config.try_deserialize())
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid authorization policy: {}", e)))
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Whether `user` may perform `action` on `resource`, acting for `tenant`
+    pub fn is_allowed(&self, user: &AuthUser, tenant: Option<&str>, action: &str, resource: &impl Resource) -> bool {
+        let subject = Subject::new(&user.id, &user.roles, tenant.map(str::to_string));
+        self.decide(Some(&subject), action, &resource.path(), |condition| match condition {
+            Condition::Owner => resource.owner() == Some(subject.id.as_str()),
+            Condition::SameTenant => subject.tenant.is_some() && resource.tenant() == subject.tenant.as_deref(),
+        })
+    }
+
+    /// Deny rules win; without a matching allow rule, access is denied
+    fn decide(
+        &self,
+        subject: Option<&Subject>,
+        action: &str,
+        path: &str,
+        holds: impl Fn(Condition) -> bool,
+    ) -> bool {
+        let mut allowed = false;
+        for rule in self.rules.iter().filter(|rule| rule.applies_to(subject, action, path)) {
+            if !rule.conditions.iter().all(|&condition| holds(condition)) {
+                continue;
+            }
+            match rule.effect {
+                Effect::Deny => return false,
+                Effect::Allow => allowed = true,
+            }
+        }
+        allowed
+    }
+
+    /// Decision without the resource: conditions are assumed to hold for
+    /// allow rules and not to for deny rules
+    fn allows_route(&self, subject: Option<&Subject>, action: &str, path: &str) -> bool {
+        let mut allowed = false;
+        for rule in self.rules.iter().filter(|rule| rule.applies_to(subject, action, path)) {
+            match rule.effect {
+                Effect::Deny if rule.conditions.is_empty() => return false,
+                Effect::Deny => {}
+                Effect::Allow => allowed = true,
+            }
+        }
+        allowed
+    }
+
+    /// Whether any rule is about `path`
+    fn covers(&self, path: &str) -> bool {
+        self.rules.iter().any(|rule| matches_pattern(&rule.resource, path))
+    }
+}
+
+/// `*` matches one segment, `**` any number of them
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[&str], path: &[&str]) -> bool {
+        match (pattern.first(), path.first()) {
+            (Some(&"**"), _) => matches(&pattern[1..], path) || (!path.is_empty() && matches(pattern, &path[1..])),
+            (Some(&expected), Some(&segment)) => {
+                (expected == "*" || expected == segment) && matches(&pattern[1..], &path[1..])
+            }
+            (None, None) => true,
+            _ => false,
+        }
+    }
+    let pattern: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    matches(&pattern, &path)
+}
+
+/// Action a request method stands for
+fn method_action(method: &Method) -> &'static str {
+    match *method {
+        Method::POST => "create",
+        Method::PUT | Method::PATCH => "update",
+        Method::DELETE => "delete",
+        _ => "read",
+    }
+}
+
+tokio::task_local! {
+    static ACTIVE: (Arc<Policy>, Option<String>);
+}
+
+/// Check `action` on `resource` against the policy of the current request
+///
+/// Prefer the [`authorize!`] macro. Fails with 403 when the policy denies
+/// it, and with 500 outside a [`PolicyLayer`].
+pub fn authorize(user: &AuthUser, action: &str, resource: &impl Resource) -> Result<(), ApiError> {
+    let (policy, tenant) = ACTIVE
+        .try_with(|(policy, tenant)| (policy.clone(), tenant.clone()))
+        .map_err(|_| ApiError::InternalServerError("No authorization policy for this request".to_string()))?;
+    if policy.is_allowed(user, tenant.as_deref(), action, resource) {
+        Ok(())
+    } else {
+        Err(ApiError::custom(
+            StatusCode::FORBIDDEN,
+            "FORBIDDEN",
+            format!("Not allowed to {} {}", action, resource.path()),
+        ))
+    }
+}
+
+/// Check that `user` may perform `action` on `resource` under the request's
+/// [`Policy`], e.g. `authorize!(user, "edit", post)?`
+#[macro_export]
+macro_rules! authorize {
+    ($user:expr, $action:expr, $resource:expr) => {
+        $crate::auth::policy::authorize(&$user, $action, &$resource)
+    };
+}
+
+/// Enforces a [`Policy`] on the routes it wraps
+///
+/// Only paths some rule is about are checked; others pass through. The
+/// user comes from a bearer token, if any; rules for `*` also cover
+/// anonymous requests. Conditions need the loaded resource, so rules with
+/// conditions grant provisionally here (and deny rules with conditions are
+/// skipped): handlers must confirm with [`authorize!`].
+#[derive(Clone)]
+pub struct PolicyLayer {
+    policy: Arc<Policy>,
+}
+
+impl PolicyLayer {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl<S> Layer<S> for PolicyLayer {
+    type Service = PolicyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PolicyService {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PolicyService<S> {
+    inner: S,
+    policy: Arc<Policy>,
+}
+
+impl<S> Service<Request> for PolicyService<S>
+where
+    S: Service<Request, Response = Response> + Send + Clone + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let policy = self.policy.clone();
+        let mut inner = self.inner.clone();
+
+        Box::pin(async move {
+            #[cfg(feature = "multi-tenancy")]
+            let tenant = req
+                .extensions()
+                .get::<crate::multi_tenancy::TenantContext>()
+                .map(|context| context.tenant_id().as_str().to_string());
+            #[cfg(not(feature = "multi-tenancy"))]
+            let tenant: Option<String> = None;
+
+            let path = req.uri().path().to_string();
+            if policy.covers(&path) {
+                let claims = match request_claims(&req) {
+                    Ok(claims) => claims,
+                    Err(error) => return Ok(error.into_response()),
+                };
+                let subject = claims.map(|claims| Subject::new(&claims.sub, &claims.roles, tenant.clone()));
+                let action = method_action(req.method());
+                if !policy.allows_route(subject.as_ref(), action, &path) {
+                    let error = match subject {
+                        None => ApiError::Unauthorized,
+                        Some(_) => ApiError::custom(
+                            StatusCode::FORBIDDEN,
+                            "FORBIDDEN",
+                            format!("Not allowed to {} {}", action, path),
+                        ),
+                    };
+                    return Ok(error.into_response());
+                }
+            }
+
+            ACTIVE.scope((policy, tenant), inner.call(req)).await
+        })
+    }
+}
+
+/// Claims decoded earlier, or from the bearer token; `None` without one
+fn request_claims(req: &Request) -> Result<Option<Claims>, ApiError> {
+    if let Some(claims) = req.extensions().get::<Claims>() {
+        return Ok(Some(claims.clone()));
+    }
+    let Some(token) = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Ok(None);
+    };
+    let config = req.extensions().get::<AuthConfig>().cloned().unwrap_or_else(AuthConfig::from_env);
+    verify_access_token(token, &config).map(Some).map_err(|_| ApiError::Unauthorized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::encode_claims;
+    use axum::{body::Body, routing::put, Router};
+    use tower::ServiceExt;
+
+    struct Post {
+        id: u32,
+        author: String,
+        tenant: String,
+    }
+
+    impl Resource for Post {
+        fn path(&self) -> String {
+            format!("/posts/{}", self.id)
+        }
+
+        fn owner(&self) -> Option<&str> {
+            Some(&self.author)
+        }
+
+        fn tenant(&self) -> Option<&str> {
+            Some(&self.tenant)
+        }
+    }
+
+    fn policy() -> Policy {
+        Policy::from_toml(
+            r#"
+            [[rules]]
+            subject = "role:editor"
+            resource = "/posts/**"
+            actions = ["read", "update"]
+
+            [[rules]]
+            subject = "role:author"
+            resource = "/posts/*"
+            actions = ["update", "edit"]
+            conditions = ["owner", "same_tenant"]
+
+            [[rules]]
+            subject = "*"
+            resource = "/posts/*"
+            actions = ["read"]
+
+            [[rules]]
+            effect = "deny"
+            subject = "*"
+            resource = "/posts/*/audit"
+            actions = ["*"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn user(id: &str, roles: &[&str]) -> AuthUser {
+        let config = AuthConfig::default();
+        AuthUser::from_claims(Claims::new_access(id, "u@example.com", roles.iter().map(|r| r.to_string()).collect(), &config))
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(matches_pattern("/posts/*", "/posts/1"));
+        assert!(!matches_pattern("/posts/*", "/posts/1/comments"));
+        assert!(matches_pattern("/posts/**", "/posts"));
+        assert!(matches_pattern("/posts/**/edit", "/posts/1/drafts/2/edit"));
+        assert!(!matches_pattern("/posts/*", "/pages/1"));
+    }
+
+    #[test]
+    fn test_rules_and_conditions() {
+        let policy = policy();
+        let post = Post { id: 1, author: "alice".to_string(), tenant: "acme".to_string() };
+
+        assert!(policy.is_allowed(&user("alice", &["author"]), Some("acme"), "edit", &post));
+        assert!(!policy.is_allowed(&user("bob", &["author"]), Some("acme"), "edit", &post));
+        assert!(!policy.is_allowed(&user("alice", &["author"]), Some("globex"), "edit", &post));
+        assert!(!policy.is_allowed(&user("alice", &["author"]), Some("acme"), "delete", &post));
+        assert!(policy.is_allowed(&user("carol", &["editor"]), None, "update", &post));
+
+        let with_deny = policy.with_rule(Rule::deny("user:carol", "/posts/*", ["*"]));
+        assert!(!with_deny.is_allowed(&user("carol", &["editor"]), None, "update", &post));
+    }
+
+    #[tokio::test]
+    async fn test_policy_layer_and_authorize() {
+        let config = AuthConfig {
+            jwt_secret: "policy-test-secret-at-least-32-bytes-long".to_string(),
+            ..AuthConfig::default()
+        };
+        let token = |id: &str, role: &str| {
+            encode_claims(&Claims::new_access(id, "u@example.com", vec![role.to_string()], &config), &config).unwrap()
+        };
+        let (alice, bob) = (token("alice", "author"), token("bob", "author"));
+
+        let update = |user: AuthUser| async move {
+            let post = Post { id: 1, author: "alice".to_string(), tenant: String::new() };
+            authorize!(user, "update", post).map(|_| "updated")
+        };
+        let config_layer = config.clone();
+        let router = Router::new()
+            .route("/posts/:id", put(update).get(|| async { "post" }))
+            .route("/health", put(|| async { "ok" }))
+            .layer(PolicyLayer::new(policy().with_rule(Rule::allow("role:author", "/posts/*", ["update"]).when(Condition::Owner))))
+            .layer(axum::middleware::from_fn(move |mut req: Request, next: axum::middleware::Next| {
+                req.extensions_mut().insert(config_layer.clone());
+                next.run(req)
+            }));
+        let send = |method: Method, uri: &str, token: Option<&str>| {
+            let mut request = Request::builder().method(method).uri(uri);
+            if let Some(token) = token {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        assert_eq!(send(Method::GET, "/posts/1", None).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send(Method::PUT, "/posts/1", None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(send(Method::PUT, "/posts/1", Some(&alice)).await.unwrap().status(), StatusCode::OK);
+        // Passes the layer on the owner rule, refused once the post is loaded
+        assert_eq!(send(Method::PUT, "/posts/1", Some(&bob)).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(Method::PUT, "/health", None).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send(Method::PUT, "/posts/1", Some("garbage")).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        assert!(authorize(&user("alice", &["author"]), "read", &Post { id: 1, author: String::new(), tenant: String::new() }).is_err());
+    }
+}
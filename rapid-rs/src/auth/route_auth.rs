@@ -0,0 +1,345 @@
+//! Per-route authentication requirements
+//!
+//! A [`RouteAuth`] attached to a route's [`RouteDoc`](crate::openapi::RouteDoc)
+//! is both enforced on the route and published as the operation's OpenAPI
+//! `security`, with the matching `components.securitySchemes`, so the spec
+//! can't drift from what the server checks.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .with_api_keys(StaticApiKeys::new().with_key(key, ApiKeyIdentity::new("billing").with_scopes(["invoices:read"])))
+//!     .route_with_doc("/health", get(health), RouteDoc::get().auth(RouteAuth::public()))
+//!     .route_with_doc("/me", get(me), RouteDoc::get().auth(RouteAuth::bearer()))
+//!     .route_with_doc(
+//!         "/invoices",
+//!         get(invoices),
+//!         RouteDoc::get().auth(RouteAuth::bearer().or_api_key().with_scopes(["invoices:read"])),
+//!     )
+//!     .route_with_doc("/admin", get(admin), RouteDoc::get().auth(RouteAuth::bearer().with_roles(["admin"])))
+//! ```
+
+use axum::{
+    extract::Request,
+    http::{header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+
+use super::{config::AuthConfig, extractors::AuthError, jwt::verify_access_token};
+use crate::dependencies::Dependencies;
+use crate::error::ApiError;
+
+/// Header carrying API keys
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// How a request may authenticate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <JWT>` verified against the app's [`AuthConfig`]
+    Bearer,
+    /// `X-API-Key` verified by the app's [`ApiKeyStore`]
+    ApiKey,
+}
+
+impl AuthScheme {
+    /// Name under `components.securitySchemes`
+    pub fn scheme_name(self) -> &'static str {
+        match self {
+            AuthScheme::Bearer => "bearerAuth",
+            AuthScheme::ApiKey => "apiKeyAuth",
+        }
+    }
+
+    pub(crate) fn security_scheme(self) -> SecurityScheme {
+        match self {
+            AuthScheme::Bearer => {
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build())
+            }
+            AuthScheme::ApiKey => SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        }
+    }
+}
+
+/// Authentication a route requires
+///
+/// Roles are alternatives (any one will do); scopes are all required.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteAuth {
+    schemes: Vec<AuthScheme>,
+    roles: Vec<String>,
+    scopes: Vec<String>,
+}
+
+impl RouteAuth {
+    /// No authentication, also in the spec (`security: []`)
+    pub fn public() -> Self {
+        Self::default()
+    }
+
+    pub fn bearer() -> Self {
+        Self::public().or(AuthScheme::Bearer)
+    }
+
+    pub fn api_key() -> Self {
+        Self::public().or(AuthScheme::ApiKey)
+    }
+
+    /// Also accept `scheme`
+    pub fn or(mut self, scheme: AuthScheme) -> Self {
+        if !self.schemes.contains(&scheme) {
+            self.schemes.push(scheme);
+        }
+        self
+    }
+
+    pub fn or_bearer(self) -> Self {
+        self.or(AuthScheme::Bearer)
+    }
+
+    pub fn or_api_key(self) -> Self {
+        self.or(AuthScheme::ApiKey)
+    }
+
+    /// Require one of `roles`
+    pub fn with_roles(mut self, roles: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    /// Require all of `scopes`
+    pub fn with_scopes(mut self, scopes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.scopes.extend(scopes.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.schemes.is_empty()
+    }
+
+    pub fn schemes(&self) -> &[AuthScheme] {
+        &self.schemes
+    }
+
+    /// Whether a check beyond authentication can fail with 403
+    pub(crate) fn has_permissions(&self) -> bool {
+        !self.roles.is_empty() || !self.scopes.is_empty()
+    }
+
+    /// The operation's `security`: one alternative per scheme, listing the
+    /// roles and scopes it needs
+    pub(crate) fn security(&self) -> Vec<SecurityRequirement> {
+        let required: Vec<&String> = self.roles.iter().chain(&self.scopes).collect();
+        self.schemes
+            .iter()
+            .map(|scheme| SecurityRequirement::new(scheme.scheme_name(), required.iter().map(|s| s.as_str())))
+            .collect()
+    }
+
+    /// Authenticate with the first scheme the request presents credentials for
+    async fn authorize(&self, parts: &mut Parts) -> Result<(), AuthError> {
+        let bearer = self.schemes.contains(&AuthScheme::Bearer)
+            && parts
+                .headers
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.starts_with("Bearer "));
+        let api_key = self.schemes.contains(&AuthScheme::ApiKey) && parts.headers.contains_key(API_KEY_HEADER);
+
+        let (roles, scopes) = if bearer {
+            let config = parts.extensions.get::<AuthConfig>().cloned().unwrap_or_else(AuthConfig::from_env);
+            let token = parts
+                .headers
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .ok_or(AuthError::MissingToken)?;
+            let claims = verify_access_token(token, &config).map_err(|_| AuthError::InvalidToken)?;
+            crate::reporting::record_user(&parts.extensions, &claims.sub);
+            let granted = (claims.roles.clone(), claims.scopes.clone());
+            parts.extensions.insert(claims);
+            granted
+        } else if api_key {
+            let key = parts
+                .headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .ok_or(AuthError::InvalidToken)?;
+            let store = parts
+                .extensions
+                .get::<Dependencies>()
+                .and_then(|dependencies| dependencies.get::<dyn ApiKeyStore>())
+                .ok_or_else(|| AuthError::Internal("No ApiKeyStore provided".to_string()))?;
+            let identity = store
+                .verify(key)
+                .await
+                .map_err(|e| AuthError::Internal(e.to_string()))?
+                .ok_or(AuthError::InvalidToken)?;
+            crate::reporting::record_user(&parts.extensions, &identity.name);
+            let granted = (identity.roles.clone(), identity.scopes.clone());
+            parts.extensions.insert(identity);
+            granted
+        } else {
+            return Err(AuthError::MissingToken);
+        };
+
+        if !self.roles.is_empty() && !self.roles.iter().any(|role| roles.contains(role)) {
+            return Err(AuthError::Forbidden(format!("One of roles {:?} required", self.roles)));
+        }
+        if let Some(missing) = self.scopes.iter().find(|scope| !scopes.contains(scope)) {
+            return Err(AuthError::Forbidden(format!("Scope '{}' required", missing)));
+        }
+        Ok(())
+    }
+}
+
+/// Enforce `auth` before the route's handler
+pub(crate) async fn enforce(auth: Arc<RouteAuth>, request: Request, next: Next) -> Response {
+    if auth.is_public() {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    match auth.authorize(&mut parts).await {
+        Ok(()) => next.run(Request::from_parts(parts, body)).await,
+        Err(error) => error.into_response(),
+    }
+}
+
+/// Who an API key belongs to and what it may do
+///
+/// Added to request extensions on routes that accept the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyIdentity {
+    pub name: String,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+impl ApiKeyIdentity {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            roles: Vec::new(),
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn with_roles(mut self, roles: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_scopes(mut self, scopes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.scopes.extend(scopes.into_iter().map(Into::into));
+        self
+    }
+}
+
+/// Verifies API keys for [`AuthScheme::ApiKey`] routes
+///
+/// Register one with [`App::with_api_keys`](crate::App::with_api_keys).
+#[async_trait::async_trait]
+pub trait ApiKeyStore: Send + Sync + 'static {
+    /// The key's identity, or `None` for an unknown or revoked key
+    async fn verify(&self, key: &str) -> Result<Option<ApiKeyIdentity>, ApiError>;
+}
+
+/// Fixed set of API keys, e.g. from configuration
+#[derive(Clone, Default)]
+pub struct StaticApiKeys {
+    keys: HashMap<String, ApiKeyIdentity>,
+}
+
+impl std::fmt::Debug for StaticApiKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticApiKeys").field("keys", &self.keys.len()).finish_non_exhaustive()
+    }
+}
+
+impl StaticApiKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>, identity: ApiKeyIdentity) -> Self {
+        self.keys.insert(key.into(), identity);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for StaticApiKeys {
+    async fn verify(&self, key: &str) -> Result<Option<ApiKeyIdentity>, ApiError> {
+        Ok(self.keys.get(key).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::{encode_claims, Claims};
+    use crate::openapi::RouteDoc;
+    use crate::App;
+    use axum::{body::Body, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_route_auth_enforced_and_documented() {
+        let config = AuthConfig {
+            jwt_secret: "route-auth-test-secret-at-least-32-bytes".to_string(),
+            ..AuthConfig::default()
+        };
+        let admin = encode_claims(&Claims::new_access("u1", "a@example.com", vec!["admin".into()], &config), &config).unwrap();
+        let reader = encode_claims(
+            &Claims::new_access("u2", "r@example.com", vec![], &config).with_scopes(vec!["reports:read".into()]),
+            &config,
+        )
+        .unwrap();
+
+        let app = App::new()
+            .with_auth(config)
+            .with_api_keys(StaticApiKeys::new().with_key("k-1", ApiKeyIdentity::new("billing").with_scopes(["reports:read"])))
+            .route_with_doc("/open", get(|| async { "ok" }), RouteDoc::get().auth(RouteAuth::public()))
+            .route_with_doc("/admin", get(|| async { "ok" }), RouteDoc::get().auth(RouteAuth::bearer().with_roles(["admin"])))
+            .route_with_doc(
+                "/reports",
+                get(|| async { "ok" }),
+                RouteDoc::get().auth(RouteAuth::bearer().or_api_key().with_scopes(["reports:read"])),
+            )
+            .with_api_docs();
+        let router = app.into_router();
+        let send = |uri: &str, header: Option<(&str, String)>| {
+            let mut request = Request::get(uri);
+            if let Some((name, value)) = header {
+                request = request.header(name, value);
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        let bearer = |token: &str| Some(("authorization", format!("Bearer {}", token)));
+
+        assert_eq!(send("/open", None).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("/admin", None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(send("/admin", bearer(&admin)).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("/admin", bearer(&reader)).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert_eq!(send("/reports", bearer(&reader)).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("/reports", bearer(&admin)).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert_eq!(send("/reports", Some(("x-api-key", "k-1".into()))).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("/reports", Some(("x-api-key", "nope".into()))).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let response = send("/openapi.json", None).await.unwrap();
+        let spec = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&spec).unwrap();
+        assert_eq!(spec["components"]["securitySchemes"]["bearerAuth"]["scheme"], "bearer");
+        assert_eq!(spec["components"]["securitySchemes"]["apiKeyAuth"]["name"], "X-API-Key");
+        assert_eq!(spec["paths"]["/open"]["get"]["security"], serde_json::json!([]));
+        assert_eq!(spec["paths"]["/admin"]["get"]["security"], serde_json::json!([{ "bearerAuth": ["admin"] }]));
+        assert_eq!(
+            spec["paths"]["/reports"]["get"]["security"],
+            serde_json::json!([{ "bearerAuth": ["reports:read"] }, { "apiKeyAuth": ["reports:read"] }])
+        );
+        assert!(spec["paths"]["/reports"]["get"]["responses"]["403"].is_object());
+    }
+}
@@ -0,0 +1,763 @@
+//! Cookie sessions as an alternative to JWTs
+//!
+//! The session ID lives in an `HttpOnly` cookie that page scripts can't read,
+//! which suits server-rendered pages and browser apps. Unsafe requests
+//! (anything but GET, HEAD and OPTIONS) must echo the session's CSRF token in
+//! the `x-csrf-token` header; [`SessionUser`] enforces this.
+//!
+//! ```rust,ignore
+//! let sessions = Sessions::new(InMemorySessionStore::new(), SessionConfig::default());
+//!
+//! App::new()
+//!     .auto_configure()
+//!     .with_sessions(sessions.clone())
+//!     .mount(session_routes(sessions, user_store))
+//!     .route("/dashboard", get(|user: SessionUser| async move { user.email }))
+//! ```
+//!
+//! When users can enrol in two-factor authentication, mount
+//! [`session_routes_with_two_factor`] instead, so their sign-in stops at a
+//! challenge until `POST /auth/session/2fa/login` receives a code.
+
+#[cfg(feature = "database")]
+pub mod postgres;
+#[cfg(feature = "cache-redis")]
+pub mod redis;
+pub mod replicated;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::{
+    audit::{emit, AuditSink},
+    extractors::AuthError,
+    handlers::{check_password, login_succeeded, StoredUser, UserStore},
+    models::{AuthUserInfo, LoginRequest, MessageResponse},
+};
+#[cfg(feature = "two-factor")]
+use super::{
+    config::AuthConfig,
+    handlers::password_amr,
+    two_factor::{answer_challenge, issue_challenge, TwoFactorLoginRequest, TwoFactorStore},
+};
+use crate::clock::SharedClock;
+use crate::client_ip::ClientInfo;
+use crate::error::ApiError;
+use crate::extractors::ValidatedJson;
+
+#[cfg(feature = "database")]
+pub use postgres::PostgresSessionStore;
+#[cfg(feature = "cache-redis")]
+pub use redis::RedisSessionStore;
+pub use replicated::ReplicatedSessionStore;
+
+/// A signed-in browser session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_id: String,
+    pub email: String,
+    pub roles: Vec<String>,
+    /// Must accompany unsafe requests in the CSRF header
+    pub csrf_token: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Storage for sessions, keyed by session ID
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync + 'static {
+    async fn save(&self, session: &Session) -> Result<(), ApiError>;
+
+    async fn load(&self, id: &str) -> Result<Option<Session>, ApiError>;
+
+    async fn delete(&self, id: &str) -> Result<(), ApiError>;
+
+    /// Remove sessions that expired before `now`, returning how many
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<u64, ApiError>;
+}
+
+/// In-memory session store for development/testing
+///
+/// Sessions are lost on restart and not shared between instances.
+#[derive(Clone, Default)]
+pub struct InMemorySessionStore {
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn save(&self, session: &Session) -> Result<(), ApiError> {
+        self.sessions.lock().unwrap().insert(session.id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        Ok(self.sessions.lock().unwrap().get(id).cloned())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), ApiError> {
+        self.sessions.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<u64, ApiError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.expires_at > now);
+        Ok((before - sessions.len()) as u64)
+    }
+}
+
+/// Cookie `SameSite` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Cross-site requests too; browsers require `Secure` with this
+    None,
+}
+
+impl SameSite {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// Session cookie and lifetime settings
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub cookie_name: String,
+    /// How long a session lasts after sign-in
+    pub ttl: Duration,
+    /// Only send the cookie over HTTPS; turn off for local HTTP development
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub path: String,
+    pub domain: Option<String>,
+    /// Header unsafe requests carry the CSRF token in
+    pub csrf_header: String,
+    pub clock: SharedClock,
+    /// Where sign-ins are recorded
+    pub audit: Option<Arc<dyn AuditSink>>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: "rapid_session".to_string(),
+            ttl: Duration::from_secs(7 * 24 * 3600),
+            secure: true,
+            same_site: SameSite::Lax,
+            path: "/".to_string(),
+            domain: None,
+            csrf_header: "x-csrf-token".to_string(),
+            clock: crate::clock::system(),
+            audit: None,
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Expire sessions against `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record sign-ins to `sink`
+    pub fn with_audit_sink(mut self, sink: impl AuditSink) -> Self {
+        self.audit = Some(Arc::new(sink));
+        self
+    }
+}
+
+/// Session store plus cookie settings, shared by handlers and [`SessionUser`]
+#[derive(Clone)]
+pub struct Sessions {
+    store: Arc<dyn SessionStore>,
+    config: Arc<SessionConfig>,
+}
+
+impl Sessions {
+    pub fn new(store: impl SessionStore, config: SessionConfig) -> Self {
+        Self {
+            store: Arc::new(store),
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Create a session for `user`
+    pub async fn start(&self, user: &StoredUser) -> Result<Session, ApiError> {
+        let now = self.config.clock.now();
+        let ttl = chrono::Duration::from_std(self.config.ttl).unwrap_or(chrono::Duration::MAX);
+        let session = Session {
+            id: random_token(),
+            user_id: user.id.clone(),
+            email: user.email.clone(),
+            roles: user.roles.clone(),
+            csrf_token: random_token(),
+            created_at: now,
+            expires_at: now.checked_add_signed(ttl).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        };
+        self.store.save(&session).await?;
+        Ok(session)
+    }
+
+    /// The live session named by the request's cookie
+    pub async fn load(&self, headers: &HeaderMap) -> Result<Option<Session>, ApiError> {
+        let Some(id) = self.cookie_value(headers) else {
+            return Ok(None);
+        };
+        match self.store.load(&id).await? {
+            Some(session) if session.expires_at > self.config.clock.now() => Ok(Some(session)),
+            Some(_) => {
+                self.store.delete(&id).await?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn end(&self, session: &Session) -> Result<(), ApiError> {
+        self.store.delete(&session.id).await
+    }
+
+    /// Remove expired sessions from the store
+    pub async fn delete_expired(&self) -> Result<u64, ApiError> {
+        self.store.delete_expired(self.config.clock.now()).await
+    }
+
+    /// `Set-Cookie` value carrying `session`
+    pub fn cookie(&self, session: &Session) -> HeaderValue {
+        self.set_cookie(&session.id, self.config.ttl.as_secs())
+    }
+
+    /// `Set-Cookie` value removing the session cookie
+    pub fn clear_cookie(&self) -> HeaderValue {
+        self.set_cookie("", 0)
+    }
+
+    fn set_cookie(&self, value: &str, max_age: u64) -> HeaderValue {
+        let config = &self.config;
+        let mut cookie = format!(
+            "{}={}; Path={}; HttpOnly; SameSite={}; Max-Age={}",
+            config.cookie_name,
+            value,
+            config.path,
+            config.same_site.as_str(),
+            max_age
+        );
+        if let Some(domain) = &config.domain {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        if config.secure {
+            cookie.push_str("; Secure");
+        }
+        HeaderValue::from_str(&cookie).expect("cookie settings are valid header text")
+    }
+
+    fn cookie_value(&self, headers: &HeaderMap) -> Option<String> {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .find_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                (name == self.config.cookie_name && !value.is_empty()).then(|| value.to_string())
+            })
+    }
+}
+
+/// User signed in with a session cookie
+///
+/// Requires [`Sessions`] in the request extensions, e.g. via
+/// [`App::with_sessions`](crate::App::with_sessions). Rejects unsafe requests
+/// whose CSRF header doesn't match the session.
+///
+/// ```rust,ignore
+/// async fn update_profile(user: SessionUser, Json(body): Json<Profile>) -> impl IntoResponse {
+///     format!("Updated {}", user.email)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionUser {
+    pub id: String,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub session: Session,
+}
+
+impl SessionUser {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    /// Require a specific role, returning an error if not present
+    pub fn require_role(&self, role: &str) -> Result<(), AuthError> {
+        if self.has_role(role) {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden(format!("Role '{}' required", role)))
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SessionUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let sessions = parts
+            .extensions
+            .get::<Sessions>()
+            .cloned()
+            .ok_or_else(|| AuthError::Internal("Sessions are not configured".to_string()))?;
+
+        let session = sessions
+            .load(&parts.headers)
+            .await
+            .map_err(|e| AuthError::Internal(e.to_string()))?
+            .ok_or(AuthError::MissingToken)?;
+
+        if !is_safe(&parts.method) {
+            let csrf = parts
+                .headers
+                .get(sessions.config.csrf_header.as_str())
+                .map(|value| value.as_bytes())
+                .unwrap_or_default();
+            if !constant_time_eq(csrf, session.csrf_token.as_bytes()) {
+                return Err(AuthError::Forbidden("Invalid CSRF token".to_string()));
+            }
+        }
+
+        Ok(SessionUser {
+            id: session.user_id.clone(),
+            email: session.email.clone(),
+            roles: session.roles.clone(),
+            session,
+        })
+    }
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The signed-in user and the CSRF token to send with unsafe requests
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SessionResponse {
+    pub user: AuthUserInfo,
+    pub csrf_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+fn session_response(user: StoredUser, session: &Session) -> SessionResponse {
+    SessionResponse {
+        user: AuthUserInfo {
+            id: user.id,
+            email: user.email,
+            name: user.name,
+            roles: user.roles,
+        },
+        csrf_token: session.csrf_token.clone(),
+        expires_at: session.expires_at,
+    }
+}
+
+/// Two-factor enrolments checked at sign-in, and the auth settings the
+/// challenge tokens are signed with
+#[cfg(feature = "two-factor")]
+#[derive(Clone)]
+pub struct SessionTwoFactor {
+    pub config: AuthConfig,
+    pub store: Arc<dyn TwoFactorStore>,
+}
+
+/// State for session routes
+#[derive(Clone)]
+pub struct SessionAppState<S: UserStore> {
+    pub sessions: Sessions,
+    pub user_store: S,
+    /// Set by [`session_routes_with_two_factor`]
+    #[cfg(feature = "two-factor")]
+    pub two_factor: Option<SessionTwoFactor>,
+}
+
+/// Sign in with email and password, setting the session cookie
+///
+/// Users with two-factor enabled get a
+/// [`TwoFactorChallenge`](super::two_factor::TwoFactorChallenge) and no
+/// session until they answer it.
+pub async fn login<S: UserStore>(
+    State(state): State<SessionAppState<S>>,
+    headers: HeaderMap,
+    client: Option<ClientInfo>,
+    ValidatedJson(payload): ValidatedJson<LoginRequest>,
+) -> Result<Response, ApiError> {
+    let config = state.sessions.config();
+    let user = check_password(&state.user_store, config.audit.as_ref(), config.clock.now(), client, &payload).await?;
+
+    #[cfg(feature = "two-factor")]
+    if let Some(two_factor) = &state.two_factor {
+        if let Some(challenge) = issue_challenge(&*two_factor.store, &user, password_amr(), &two_factor.config).await? {
+            return Ok(Json(challenge).into_response());
+        }
+    }
+
+    start_session(&state, &headers, user, client).await
+}
+
+/// Finish a sign-in with a TOTP or recovery code, setting the session cookie
+#[cfg(feature = "two-factor")]
+pub async fn login_second_factor<S: UserStore>(
+    State(state): State<SessionAppState<S>>,
+    headers: HeaderMap,
+    client: Option<ClientInfo>,
+    ValidatedJson(payload): ValidatedJson<TwoFactorLoginRequest>,
+) -> Result<Response, ApiError> {
+    let two_factor = state
+        .two_factor
+        .as_ref()
+        .ok_or_else(|| ApiError::InternalServerError("Two-factor is not configured for sessions".to_string()))?;
+    let (claims, _, _) = answer_challenge(&*two_factor.store, &two_factor.config, client, &payload).await?;
+
+    let user = state
+        .user_store
+        .find_by_id(&claims.sub)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+    start_session(&state, &headers, user, client).await
+}
+
+async fn start_session<S: UserStore>(
+    state: &SessionAppState<S>,
+    headers: &HeaderMap,
+    user: StoredUser,
+    client: Option<ClientInfo>,
+) -> Result<Response, ApiError> {
+    let config = state.sessions.config();
+
+    // A fresh ID on every sign-in prevents session fixation
+    if let Some(previous) = state.sessions.load(headers).await? {
+        state.sessions.end(&previous).await?;
+    }
+    let session = state.sessions.start(&user).await?;
+
+    tracing::info!(user_id = %user.id, "Session started");
+    emit(config.audit.as_ref(), login_succeeded(&user, config.clock.now(), client)).await;
+    Ok((
+        [(header::SET_COOKIE, state.sessions.cookie(&session))],
+        Json(session_response(user, &session)),
+    )
+        .into_response())
+}
+
+/// End the current session and clear the cookie
+pub async fn logout<S: UserStore>(
+    user: SessionUser,
+    State(state): State<SessionAppState<S>>,
+) -> Result<impl IntoResponse, ApiError> {
+    state.sessions.end(&user.session).await?;
+    Ok((
+        [(header::SET_COOKIE, state.sessions.clear_cookie())],
+        Json(MessageResponse::new("Successfully logged out")),
+    ))
+}
+
+/// The current session, including its CSRF token
+pub async fn current<S: UserStore>(
+    user: SessionUser,
+    State(state): State<SessionAppState<S>>,
+) -> Result<Json<SessionResponse>, ApiError> {
+    let stored_user = state
+        .user_store
+        .find_by_id(&user.id)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    Ok(Json(session_response(stored_user, &user.session)))
+}
+
+/// Session sign-in routes under `/auth/session`
+///
+/// `POST /auth/session/login`, `POST /auth/session/logout` and
+/// `GET /auth/session`.
+pub fn session_routes<S: UserStore + Clone>(sessions: Sessions, user_store: S) -> Router {
+    let state = SessionAppState {
+        sessions: sessions.clone(),
+        user_store,
+        #[cfg(feature = "two-factor")]
+        two_factor: None,
+    };
+
+    routes().with_state(state).layer(Extension(sessions))
+}
+
+/// Session sign-in routes that ask for a second factor
+///
+/// Like [`session_routes`], plus `POST /auth/session/2fa/login`. Users with
+/// two-factor enabled in `two_factor` get a challenge from
+/// `/auth/session/login`, signed with `config`.
+#[cfg(feature = "two-factor")]
+pub fn session_routes_with_two_factor<S, T>(sessions: Sessions, user_store: S, config: AuthConfig, two_factor: T) -> Router
+where
+    S: UserStore + Clone,
+    T: TwoFactorStore,
+{
+    let state = SessionAppState {
+        sessions: sessions.clone(),
+        user_store,
+        two_factor: Some(SessionTwoFactor {
+            config,
+            store: Arc::new(two_factor),
+        }),
+    };
+
+    routes()
+        .route("/auth/session/2fa/login", post(login_second_factor::<S>))
+        .with_state(state)
+        .layer(Extension(sessions))
+}
+
+fn routes<S: UserStore + Clone>() -> Router<SessionAppState<S>> {
+    Router::new()
+        .route("/auth/session", get(current::<S>))
+        .route("/auth/session/login", post(login::<S>))
+        .route("/auth/session/logout", post(logout::<S>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{hash_password, AuthConfig, CreateUserData, InMemoryUserStore};
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_session_login_and_csrf() {
+        let mut auth = AuthConfig::new("test-secret");
+        auth.argon2_memory_cost = 1024;
+        auth.argon2_time_cost = 1;
+        let users = InMemoryUserStore::new();
+        users
+            .create(CreateUserData {
+                email: "ada@example.com".to_string(),
+                name: "Ada".to_string(),
+                password_hash: hash_password("Secret123", &auth).unwrap(),
+            })
+            .await
+            .unwrap();
+
+        let clock = crate::clock::ManualClock::frozen();
+        let config = SessionConfig::default()
+            .with_ttl(Duration::from_secs(3600))
+            .with_clock(clock.shared());
+        let sessions = Sessions::new(InMemorySessionStore::new(), config);
+        let router = session_routes(sessions.clone(), users)
+            .route("/profile", post(|user: SessionUser| async move { user.email }))
+            .layer(Extension(sessions.clone()));
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::post("/auth/session/login")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"email":"ada@example.com","password":"Secret123"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap().to_string();
+        assert!(set_cookie.contains("HttpOnly") && set_cookie.contains("Secure"));
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let csrf = body["csrf_token"].as_str().unwrap().to_string();
+
+        let request = |method: Method, path: &str, csrf: Option<&str>| {
+            let mut request = Request::builder().method(method).uri(path).header(header::COOKIE, &cookie);
+            if let Some(csrf) = csrf {
+                request = request.header("x-csrf-token", csrf);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = router.clone().oneshot(request(Method::GET, "/auth/session", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router.clone().oneshot(request(Method::POST, "/auth/session/logout", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = router
+            .clone()
+            .oneshot(request(Method::POST, "/auth/session/logout", Some("forged")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = router.clone().oneshot(request(Method::POST, "/profile", Some(&csrf))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        clock.advance(Duration::from_secs(3600));
+        let response = router.clone().oneshot(request(Method::GET, "/auth/session", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(sessions.delete_expired().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_logout_ends_session() {
+        let sessions = Sessions::new(InMemorySessionStore::new(), SessionConfig::default().with_secure(false));
+        let user = StoredUser {
+            id: "user-1".to_string(),
+            email: "ada@example.com".to_string(),
+            name: "Ada".to_string(),
+            password_hash: String::new(),
+            roles: vec!["user".to_string()],
+        };
+        let users = InMemoryUserStore::new();
+        let router = session_routes(sessions.clone(), users);
+
+        let session = sessions.start(&user).await.unwrap();
+        assert!(!sessions.cookie(&session).to_str().unwrap().contains("Secure"));
+        let cookie = format!("rapid_session={}", session.id);
+
+        let logout = Request::post("/auth/session/logout")
+            .header(header::COOKIE, &cookie)
+            .header("x-csrf-token", &session.csrf_token)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(logout).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::SET_COOKIE].to_str().unwrap().contains("Max-Age=0"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, cookie.parse().unwrap());
+        assert!(sessions.load(&headers).await.unwrap().is_none());
+    }
+
+    #[cfg(feature = "two-factor")]
+    #[tokio::test]
+    async fn test_two_factor_users_need_a_code() {
+        use crate::auth::two_factor::totp_code;
+        use crate::auth::{InMemoryTwoFactorStore, TwoFactorRecord, TwoFactorStore};
+        use crate::clock::Clock;
+
+        let clock = crate::clock::ManualClock::frozen();
+        let mut auth = AuthConfig::new("test-secret").with_clock(clock.shared());
+        auth.argon2_memory_cost = 1024;
+        auth.argon2_time_cost = 1;
+        let users = InMemoryUserStore::new();
+        let user = users
+            .create(CreateUserData {
+                email: "ada@example.com".to_string(),
+                name: "Ada".to_string(),
+                password_hash: hash_password("Secret123", &auth).unwrap(),
+            })
+            .await
+            .unwrap();
+        let two_factor = InMemoryTwoFactorStore::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string();
+        let record = TwoFactorRecord {
+            secret: secret.clone(),
+            enabled: true,
+            recovery_codes: vec![],
+            last_step: None,
+            challenge: None,
+        };
+        two_factor.save(&user.id, &record).await.unwrap();
+
+        let sessions = Sessions::new(InMemorySessionStore::new(), SessionConfig::default().with_clock(clock.shared()));
+        let router = session_routes_with_two_factor(sessions, users, auth, two_factor);
+        let post = |path: &str, body: serde_json::Value| {
+            Request::post(path)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let credentials = serde_json::json!({"email": "ada@example.com", "password": "Secret123"});
+        let response = router.clone().oneshot(post("/auth/session/login", credentials)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["two_factor_required"], true);
+        assert!(body.get("csrf_token").is_none());
+
+        let code = totp_code(&secret, clock.now()).unwrap();
+        let second_step = serde_json::json!({"challenge_token": body["challenge_token"], "code": code});
+        let response = router.oneshot(post("/auth/session/2fa/login", second_step)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::SET_COOKIE].to_str().unwrap().starts_with("rapid_session="));
+    }
+}
@@ -0,0 +1,67 @@
+//! Redis session storage
+
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+
+use super::{Session, SessionStore};
+use crate::error::ApiError;
+
+/// [`SessionStore`] in Redis, shared by every instance of the app
+///
+/// Sessions are stored under `session:{id}` and expire server-side.
+#[derive(Clone)]
+pub struct RedisSessionStore {
+    connection: redis::aio::ConnectionManager,
+}
+
+impl RedisSessionStore {
+    pub async fn new(redis_url: &str) -> Result<Self, ApiError> {
+        let client = redis::Client::open(redis_url)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to create Redis client: {}", e)))?;
+        let connection = redis::aio::ConnectionManager::new(client)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to connect to Redis: {}", e)))?;
+
+        Ok(Self { connection })
+    }
+
+    fn key(id: &str) -> String {
+        format!("session:{}", id)
+    }
+}
+
+fn redis_error(e: redis::RedisError) -> ApiError {
+    ApiError::InternalServerError(format!("Redis session error: {}", e))
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn save(&self, session: &Session) -> Result<(), ApiError> {
+        let bytes = serde_json::to_vec(session)
+            .map_err(|e| ApiError::InternalServerError(format!("Session serialization error: {}", e)))?;
+        let ttl = (session.expires_at - Utc::now()).num_seconds().max(1) as u64;
+
+        let mut connection = self.connection.clone();
+        connection
+            .set_ex::<_, _, ()>(Self::key(&session.id), bytes, ttl)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        let mut connection = self.connection.clone();
+        let bytes: Option<Vec<u8>> = connection.get(Self::key(id)).await.map_err(redis_error)?;
+
+        Ok(bytes.and_then(|bytes| serde_json::from_slice(&bytes).ok()))
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), ApiError> {
+        let mut connection = self.connection.clone();
+        connection.del::<_, ()>(Self::key(id)).await.map_err(redis_error)
+    }
+
+    async fn delete_expired(&self, _now: DateTime<Utc>) -> Result<u64, ApiError> {
+        // Redis expires keys itself
+        Ok(0)
+    }
+}
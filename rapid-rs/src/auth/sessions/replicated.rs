@@ -0,0 +1,189 @@
+//! Sessions replicated to a second region
+
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use super::{Session, SessionStore};
+use crate::error::ApiError;
+use crate::replication::{replicate, ConflictPolicy, ReplicationMode};
+
+/// [`SessionStore`] writing through to a store in another region
+///
+/// Reads go to the primary. The secondary is read too when the primary
+/// errors or lacks the session, or with [`ConflictPolicy::LatestWins`] to
+/// compare copies; a winning secondary copy is written back to the primary.
+/// When the primary is down, writes still succeed as long as the secondary
+/// takes them, so users stay signed in:
+///
+/// ```rust,ignore
+/// let store = ReplicatedSessionStore::new(
+///     RedisSessionStore::new(&config.redis_url).await?,
+///     RedisSessionStore::new(&config.replica_redis_url).await?,
+/// )
+/// .with_mode(ReplicationMode::Background);
+/// let sessions = Sessions::new(store, SessionConfig::default());
+/// ```
+#[derive(Clone)]
+pub struct ReplicatedSessionStore {
+    primary: Arc<dyn SessionStore>,
+    secondary: Arc<dyn SessionStore>,
+    mode: ReplicationMode,
+    conflicts: ConflictPolicy,
+}
+
+impl ReplicatedSessionStore {
+    pub fn new(primary: impl SessionStore, secondary: impl SessionStore) -> Self {
+        Self {
+            primary: Arc::new(primary),
+            secondary: Arc::new(secondary),
+            mode: ReplicationMode::default(),
+            conflicts: ConflictPolicy::default(),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: ReplicationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_conflict_policy(mut self, conflicts: ConflictPolicy) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for ReplicatedSessionStore {
+    async fn save(&self, session: &Session) -> Result<(), ApiError> {
+        if let Err(error) = self.primary.save(session).await {
+            tracing::warn!(error = %error, "Primary session store failed, saving to the secondary only");
+            return self.secondary.save(session).await;
+        }
+        let (secondary, session) = (self.secondary.clone(), session.clone());
+        replicate(self.mode, "sessions", "save", async move { secondary.save(&session).await }).await;
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        let primary = match self.primary.load(id).await {
+            Ok(primary) => primary,
+            Err(error) => {
+                tracing::warn!(error = %error, "Primary session store failed, reading the secondary");
+                return self.secondary.load(id).await;
+            }
+        };
+        if primary.is_some() && self.conflicts == ConflictPolicy::PrimaryWins {
+            return Ok(primary);
+        }
+
+        let secondary = self.secondary.load(id).await.unwrap_or_else(|error| {
+            tracing::warn!(error = %error, "Secondary session store failed");
+            None
+        });
+        match (primary, secondary) {
+            (Some(primary), Some(secondary)) if secondary.expires_at > primary.expires_at => {
+                self.primary.save(&secondary).await?;
+                Ok(Some(secondary))
+            }
+            (None, Some(secondary)) => {
+                self.primary.save(&secondary).await?;
+                Ok(Some(secondary))
+            }
+            (primary, _) => Ok(primary),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), ApiError> {
+        // Both copies must go, or the secondary would bring the session back
+        let primary = self.primary.delete(id).await;
+        let secondary = self.secondary.delete(id).await;
+        primary.and(secondary)
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<u64, ApiError> {
+        let deleted = self.primary.delete_expired(now).await?;
+        let secondary = self.secondary.clone();
+        replicate(self.mode, "sessions", "delete_expired", async move {
+            secondary.delete_expired(now).await.map(|_| ())
+        })
+        .await;
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::sessions::InMemorySessionStore;
+
+    /// A region that is down
+    struct Unavailable;
+
+    #[async_trait::async_trait]
+    impl SessionStore for Unavailable {
+        async fn save(&self, _: &Session) -> Result<(), ApiError> {
+            Err(ApiError::ServiceUnavailable("region down".to_string()))
+        }
+
+        async fn load(&self, _: &str) -> Result<Option<Session>, ApiError> {
+            Err(ApiError::ServiceUnavailable("region down".to_string()))
+        }
+
+        async fn delete(&self, _: &str) -> Result<(), ApiError> {
+            Err(ApiError::ServiceUnavailable("region down".to_string()))
+        }
+
+        async fn delete_expired(&self, _: DateTime<Utc>) -> Result<u64, ApiError> {
+            Err(ApiError::ServiceUnavailable("region down".to_string()))
+        }
+    }
+
+    fn session(id: &str, expires_in: i64) -> Session {
+        let now = Utc::now();
+        Session {
+            id: id.to_string(),
+            user_id: "user-1".to_string(),
+            email: "user@example.com".to_string(),
+            roles: vec![],
+            csrf_token: "csrf".to_string(),
+            created_at: now,
+            expires_at: now + chrono::Duration::minutes(expires_in),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replicates_and_fails_over() {
+        let (east, west) = (InMemorySessionStore::new(), InMemorySessionStore::new());
+        let store = ReplicatedSessionStore::new(east.clone(), west.clone());
+        store.save(&session("s1", 30)).await.unwrap();
+        assert!(west.load("s1").await.unwrap().is_some());
+
+        // West takes over with east down; the session survives
+        let failed_over = ReplicatedSessionStore::new(Unavailable, west.clone());
+        assert!(failed_over.load("s1").await.unwrap().is_some());
+        failed_over.save(&session("s2", 30)).await.unwrap();
+
+        // Back in east, sessions only west has are copied over
+        assert!(store.load("s2").await.unwrap().is_some());
+        assert!(east.load("s2").await.unwrap().is_some());
+
+        store.delete("s1").await.unwrap();
+        assert!(west.load("s1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_conflict_policy() {
+        let (east, west) = (InMemorySessionStore::new(), InMemorySessionStore::new());
+        east.save(&session("s1", 10)).await.unwrap();
+        west.save(&session("s1", 60)).await.unwrap();
+        let expires = |session: Option<Session>| session.unwrap().expires_at;
+
+        let primary_wins = ReplicatedSessionStore::new(east.clone(), west.clone());
+        assert_eq!(expires(primary_wins.load("s1").await.unwrap()), expires(east.load("s1").await.unwrap()));
+
+        let latest_wins = primary_wins.with_conflict_policy(ConflictPolicy::LatestWins);
+        let loaded = expires(latest_wins.load("s1").await.unwrap());
+        assert_eq!(loaded, expires(west.load("s1").await.unwrap()));
+        assert_eq!(expires(east.load("s1").await.unwrap()), loaded);
+    }
+}
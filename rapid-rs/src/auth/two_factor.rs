@@ -0,0 +1,701 @@
+//! TOTP two-factor authentication
+//!
+//! Users enrol with an authenticator app (`POST /auth/2fa/setup`, then
+//! `POST /auth/2fa/verify` with the first code) and receive one-time recovery
+//! codes. From then on, `POST /auth/login` answers with a short-lived
+//! challenge token instead of tokens, and `POST /auth/2fa/login` exchanges it
+//! plus a code for tokens whose `amr` claim includes `mfa`. Only the latest
+//! challenge of a user can be answered; it is used up by the first right code
+//! or after five wrong ones.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .auto_configure()
+//!     .with_auth(config.clone())
+//!     .mount(auth_routes_with_two_factor(config, user_store, InMemoryTwoFactorStore::new()))
+//! ```
+//!
+//! Six-digit codes can be guessed given enough attempts, so put these routes
+//! behind the rate limiter.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    extract::{FromRef, State},
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    audit::{emit, AuditEvent, AuditEventKind},
+    config::AuthConfig,
+    extractors::AuthUser,
+    handlers::{self, auth_response, check_password, login_succeeded, password_amr, AuthAppState, StoredUser, UserStore},
+    jwt::{create_token_pair_with_amr, encode_claims, verify_token, Claims},
+    models::{AuthResponse, LoginRequest},
+};
+use crate::client_ip::ClientInfo;
+use crate::error::ApiError;
+use crate::extractors::ValidatedJson;
+
+/// Seconds each code is valid for
+const STEP_SECS: i64 = 30;
+
+const DIGITS: u32 = 6;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Token type of the challenge issued after the password step
+const CHALLENGE_TOKEN_TYPE: &str = "2fa_challenge";
+
+/// How long the second step may take, in seconds
+const CHALLENGE_EXPIRY_SECS: u64 = 300;
+
+/// Wrong codes a challenge takes before the user has to sign in again
+const CHALLENGE_MAX_ATTEMPTS: u32 = 5;
+
+/// A user's two-factor enrolment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorRecord {
+    /// Base32 TOTP secret
+    pub secret: String,
+    /// False until the user confirms setup with a valid code
+    pub enabled: bool,
+    /// SHA-256 hashes of the unused recovery codes
+    pub recovery_codes: Vec<String>,
+    /// Time step of the last accepted code, so codes can't be replayed
+    pub last_step: Option<i64>,
+    /// The login challenge waiting for a code, if any
+    #[serde(default)]
+    pub challenge: Option<PendingChallenge>,
+}
+
+/// A challenge issued by the password step and not yet used up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChallenge {
+    /// `jti` of the challenge token
+    pub id: String,
+    pub failed_attempts: u32,
+}
+
+/// Storage for two-factor enrolments, keyed by user ID
+#[async_trait::async_trait]
+pub trait TwoFactorStore: Send + Sync + 'static {
+    async fn get(&self, user_id: &str) -> Result<Option<TwoFactorRecord>, ApiError>;
+
+    async fn save(&self, user_id: &str, record: &TwoFactorRecord) -> Result<(), ApiError>;
+
+    async fn delete(&self, user_id: &str) -> Result<(), ApiError>;
+}
+
+/// In-memory two-factor store for development/testing
+#[derive(Clone, Default)]
+pub struct InMemoryTwoFactorStore {
+    records: Arc<Mutex<HashMap<String, TwoFactorRecord>>>,
+}
+
+impl InMemoryTwoFactorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFactorStore for InMemoryTwoFactorStore {
+    async fn get(&self, user_id: &str) -> Result<Option<TwoFactorRecord>, ApiError> {
+        Ok(self.records.lock().unwrap().get(user_id).cloned())
+    }
+
+    async fn save(&self, user_id: &str, record: &TwoFactorRecord) -> Result<(), ApiError> {
+        self.records.lock().unwrap().insert(user_id.to_string(), record.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), ApiError> {
+        self.records.lock().unwrap().remove(user_id);
+        Ok(())
+    }
+}
+
+/// The code for `secret` (base32) at `at`
+pub fn totp_code(secret: &str, at: DateTime<Utc>) -> Result<String, ApiError> {
+    Ok(hotp(&decode_secret(secret)?, at.timestamp().div_euclid(STEP_SECS)))
+}
+
+fn hotp(key: &[u8], counter: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+fn decode_secret(secret: &str) -> Result<Vec<u8>, ApiError> {
+    BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .map_err(|e| ApiError::InternalServerError(format!("Invalid TOTP secret: {}", e)))
+}
+
+/// How a second factor was proven
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Factor {
+    Totp,
+    RecoveryCode,
+}
+
+impl TwoFactorRecord {
+    pub(crate) fn new(secret: String) -> Self {
+        Self {
+            secret,
+            enabled: false,
+            recovery_codes: Vec::new(),
+            last_step: None,
+            challenge: None,
+        }
+    }
+
+    /// Accept a current TOTP code, allowing one step of clock drift
+    fn accept_totp(&mut self, code: &str, now: DateTime<Utc>) -> Result<bool, ApiError> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        if code.len() != DIGITS as usize {
+            return Ok(false);
+        }
+
+        let key = decode_secret(&self.secret)?;
+        let current = now.timestamp().div_euclid(STEP_SECS);
+        for step in current - 1..=current + 1 {
+            if self.last_step.is_some_and(|last| step <= last) {
+                continue;
+            }
+            if constant_time_eq(hotp(&key, step).as_bytes(), code.as_bytes()) {
+                self.last_step = Some(step);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Accept and use up a recovery code
+    fn accept_recovery_code(&mut self, code: &str) -> bool {
+        let hash = hash_recovery_code(code);
+        match self.recovery_codes.iter().position(|stored| constant_time_eq(stored.as_bytes(), hash.as_bytes())) {
+            Some(index) => {
+                self.recovery_codes.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn accept(&mut self, code: &str, now: DateTime<Utc>) -> Result<Option<Factor>, ApiError> {
+        if self.accept_totp(code, now)? {
+            return Ok(Some(Factor::Totp));
+        }
+        Ok(self.accept_recovery_code(code).then_some(Factor::RecoveryCode))
+    }
+
+    /// Replace the recovery codes, returning the new ones in plain text
+    fn regenerate_recovery_codes(&mut self) -> Vec<String> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| new_recovery_code()).collect();
+        self.recovery_codes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+        codes
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn new_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// `xxxx-xxxx` from the base32 alphabet
+fn new_recovery_code() -> String {
+    let mut bytes = [0u8; 5];
+    OsRng.fill_bytes(&mut bytes);
+    let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    HEXLOWER.encode(&Sha256::digest(normalized.as_bytes()))
+}
+
+/// `otpauth://` URI understood by authenticator apps
+fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Response to `POST /auth/2fa/setup`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TwoFactorSetupResponse {
+    /// Base32 secret, for entering into an authenticator app by hand
+    pub secret: String,
+    pub otpauth_uri: String,
+    /// Text to render as a QR code for authenticator apps to scan
+    pub qr_payload: String,
+}
+
+/// A TOTP or recovery code
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct TwoFactorCodeRequest {
+    #[validate(length(min = 6, max = 20, message = "Code must be between 6 and 20 characters"))]
+    pub code: String,
+}
+
+/// Second login step
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct TwoFactorLoginRequest {
+    /// Token from the `two_factor_required` login response
+    #[validate(length(min = 1, message = "Challenge token is required"))]
+    pub challenge_token: String,
+
+    /// TOTP or recovery code
+    #[validate(length(min = 6, max = 20, message = "Code must be between 6 and 20 characters"))]
+    pub code: String,
+}
+
+/// Freshly issued recovery codes, shown to the user once
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Login response asking for a second factor
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TwoFactorChallenge {
+    /// Always true
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    /// Seconds until the challenge token expires
+    pub expires_in: u64,
+}
+
+/// Tokens, or a challenge when the user has two-factor enabled
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+/// State for auth routes with two-factor support
+#[derive(Clone)]
+pub struct TwoFactorAppState<S: UserStore, T: TwoFactorStore> {
+    pub config: AuthConfig,
+    pub user_store: S,
+    pub two_factor: T,
+}
+
+impl<S: UserStore + Clone, T: TwoFactorStore> FromRef<TwoFactorAppState<S, T>> for AuthAppState<S> {
+    fn from_ref(state: &TwoFactorAppState<S, T>) -> Self {
+        AuthAppState {
+            config: state.config.clone(),
+            user_store: state.user_store.clone(),
+        }
+    }
+}
+
+/// Password login that asks for a second factor when the user has one
+pub async fn login<S: UserStore, T: TwoFactorStore>(
+    State(state): State<TwoFactorAppState<S, T>>,
+    client: Option<ClientInfo>,
+    ValidatedJson(payload): ValidatedJson<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let config = &state.config;
+    let user = check_password(&state.user_store, config.audit.as_ref(), config.clock.now(), client, &payload).await?;
+
+    if let Some(challenge) = issue_challenge(&state.two_factor, &user, password_amr(), config).await? {
+        return Ok(Json(LoginResponse::TwoFactorRequired(challenge)));
+    }
+
+    let token_pair =
+        create_token_pair_with_amr(&user.id, &user.email, user.roles.clone(), password_amr(), &state.config)?;
+    emit(config.audit.as_ref(), login_succeeded(&user, config.clock.now(), client)).await;
+    Ok(Json(LoginResponse::Authenticated(auth_response(user, token_pair))))
+}
+
+/// Finish a login with a TOTP or recovery code
+pub async fn login_second_factor<S: UserStore, T: TwoFactorStore>(
+    State(state): State<TwoFactorAppState<S, T>>,
+    client: Option<ClientInfo>,
+    ValidatedJson(payload): ValidatedJson<TwoFactorLoginRequest>,
+) -> Result<Json<AuthResponse>, ApiError> {
+    let (claims, factor, record) = answer_challenge(&state.two_factor, &state.config, client, &payload).await?;
+
+    let user: StoredUser = state
+        .user_store
+        .find_by_id(&claims.sub)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    let mut amr = claims.amr;
+    if factor == Factor::Totp {
+        amr.push("otp".to_string());
+    }
+    amr.push("mfa".to_string());
+    if factor == Factor::RecoveryCode {
+        tracing::warn!(user_id = %user.id, remaining = record.recovery_codes.len(), "Recovery code used to sign in");
+    }
+
+    let token_pair = create_token_pair_with_amr(&user.id, &user.email, user.roles.clone(), amr, &state.config)?;
+    emit(state.config.audit.as_ref(), login_succeeded(&user, state.config.clock.now(), client)).await;
+    Ok(Json(auth_response(user, token_pair)))
+}
+
+/// Start enrolment with a new secret
+pub async fn setup<S: UserStore, T: TwoFactorStore>(
+    user: AuthUser,
+    State(state): State<TwoFactorAppState<S, T>>,
+) -> Result<Json<TwoFactorSetupResponse>, ApiError> {
+    if state.two_factor.get(&user.id).await?.is_some_and(|record| record.enabled) {
+        return Err(ApiError::BadRequest("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = new_secret();
+    state.two_factor.save(&user.id, &TwoFactorRecord::new(secret.clone())).await?;
+
+    let otpauth_uri = otpauth_uri(&state.config.issuer, &user.email, &secret);
+    Ok(Json(TwoFactorSetupResponse {
+        secret,
+        qr_payload: otpauth_uri.clone(),
+        otpauth_uri,
+    }))
+}
+
+/// Confirm enrolment with a code from the app and issue recovery codes
+pub async fn verify<S: UserStore, T: TwoFactorStore>(
+    user: AuthUser,
+    State(state): State<TwoFactorAppState<S, T>>,
+    ValidatedJson(payload): ValidatedJson<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let mut record = state
+        .two_factor
+        .get(&user.id)
+        .await?
+        .filter(|record| !record.enabled)
+        .ok_or_else(|| ApiError::BadRequest("Two-factor setup has not been started".to_string()))?;
+
+    if !record.accept_totp(&payload.code, state.config.clock.now())? {
+        return Err(ApiError::BadRequest("Invalid code".to_string()));
+    }
+    record.enabled = true;
+    let recovery_codes = record.regenerate_recovery_codes();
+    state.two_factor.save(&user.id, &record).await?;
+
+    tracing::info!(user_id = %user.id, "Two-factor authentication enabled");
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Replace the recovery codes; needs a current TOTP code
+pub async fn regenerate_recovery_codes<S: UserStore, T: TwoFactorStore>(
+    user: AuthUser,
+    State(state): State<TwoFactorAppState<S, T>>,
+    ValidatedJson(payload): ValidatedJson<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let mut record = enabled_record(&state.two_factor, &user.id).await?;
+    if !record.accept_totp(&payload.code, state.config.clock.now())? {
+        return Err(ApiError::BadRequest("Invalid code".to_string()));
+    }
+    let recovery_codes = record.regenerate_recovery_codes();
+    state.two_factor.save(&user.id, &record).await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Turn two-factor off; needs a TOTP or recovery code
+pub async fn disable<S: UserStore, T: TwoFactorStore>(
+    user: AuthUser,
+    State(state): State<TwoFactorAppState<S, T>>,
+    ValidatedJson(payload): ValidatedJson<TwoFactorCodeRequest>,
+) -> Result<Json<super::models::MessageResponse>, ApiError> {
+    let mut record = enabled_record(&state.two_factor, &user.id).await?;
+    if record.accept(&payload.code, state.config.clock.now())?.is_none() {
+        return Err(ApiError::BadRequest("Invalid code".to_string()));
+    }
+    state.two_factor.delete(&user.id).await?;
+
+    tracing::info!(user_id = %user.id, "Two-factor authentication disabled");
+    Ok(Json(super::models::MessageResponse::new("Two-factor authentication disabled")))
+}
+
+/// A challenge for `user` if they have two-factor enabled
+///
+/// `amr` records how the first step signed in; the tokens issued for the
+/// answer carry it too. It replaces any challenge the user still had
+/// outstanding.
+pub(crate) async fn issue_challenge<T: TwoFactorStore + ?Sized>(
+    two_factor: &T,
+    user: &StoredUser,
+    amr: Vec<String>,
+    config: &AuthConfig,
+) -> Result<Option<TwoFactorChallenge>, ApiError> {
+    let Some(mut record) = two_factor.get(&user.id).await?.filter(|record| record.enabled) else {
+        return Ok(None);
+    };
+
+    let mut claims = Claims::new_access(&user.id, &user.email, vec![], config).with_amr(amr);
+    claims.token_type = CHALLENGE_TOKEN_TYPE.to_string();
+    claims.exp = claims.iat + CHALLENGE_EXPIRY_SECS as i64;
+    let challenge_token = encode_claims(&claims, config)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to create challenge token: {}", e)))?;
+
+    record.challenge = Some(PendingChallenge {
+        id: claims.jti,
+        failed_attempts: 0,
+    });
+    two_factor.save(&user.id, &record).await?;
+
+    Ok(Some(TwoFactorChallenge {
+        two_factor_required: true,
+        challenge_token,
+        expires_in: CHALLENGE_EXPIRY_SECS,
+    }))
+}
+
+/// Check the code of a second login step against its challenge
+///
+/// Returns the challenge's claims, the factor used and the updated record.
+/// A right code uses the challenge up, as do [`CHALLENGE_MAX_ATTEMPTS`] wrong ones.
+pub(crate) async fn answer_challenge<T: TwoFactorStore + ?Sized>(
+    two_factor: &T,
+    config: &AuthConfig,
+    client: Option<ClientInfo>,
+    payload: &TwoFactorLoginRequest,
+) -> Result<(Claims, Factor, TwoFactorRecord), ApiError> {
+    let claims = verify_token(&payload.challenge_token, config)?;
+    if claims.token_type != CHALLENGE_TOKEN_TYPE {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut record = enabled_record(two_factor, &claims.sub).await?;
+    if record.challenge.as_ref().is_none_or(|pending| pending.id != claims.jti) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let Some(factor) = record.accept(&payload.code, config.clock.now())? else {
+        if let Some(pending) = record.challenge.as_mut() {
+            pending.failed_attempts += 1;
+            if pending.failed_attempts >= CHALLENGE_MAX_ATTEMPTS {
+                record.challenge = None;
+            }
+        }
+        two_factor.save(&claims.sub, &record).await?;
+
+        let event = AuditEvent::new(AuditEventKind::LoginFailed, config.clock.now())
+            .with_user(&claims.sub)
+            .with_detail("invalid second factor")
+            .with_client(client);
+        emit(config.audit.as_ref(), event).await;
+        return Err(ApiError::Unauthorized);
+    };
+    record.challenge = None;
+    two_factor.save(&claims.sub, &record).await?;
+
+    Ok((claims, factor, record))
+}
+
+async fn enabled_record<T: TwoFactorStore + ?Sized>(store: &T, user_id: &str) -> Result<TwoFactorRecord, ApiError> {
+    store
+        .get(user_id)
+        .await?
+        .filter(|record| record.enabled)
+        .ok_or_else(|| ApiError::BadRequest("Two-factor authentication is not enabled".to_string()))
+}
+
+/// Auth routes with two-factor login and enrolment
+///
+/// Replaces [`auth_routes_with_store`](super::auth_routes_with_store); the
+/// `/auth/login` response is a [`LoginResponse`].
+pub fn auth_routes_with_two_factor<S, T>(config: AuthConfig, user_store: S, two_factor: T) -> Router
+where
+    S: UserStore + Clone,
+    T: TwoFactorStore + Clone,
+{
+    let state = TwoFactorAppState {
+        config,
+        user_store,
+        two_factor,
+    };
+
+    Router::new()
+        .route("/auth/login", post(login::<S, T>))
+        .route("/auth/register", post(handlers::register::<S>))
+        .route("/auth/refresh", post(handlers::refresh_token::<S>))
+        .route("/auth/logout", post(handlers::logout))
+        .route("/auth/me", get(handlers::me::<S>))
+        .route("/auth/2fa/setup", post(setup::<S, T>))
+        .route("/auth/2fa/verify", post(verify::<S, T>))
+        .route("/auth/2fa/login", post(login_second_factor::<S, T>))
+        .route("/auth/2fa/recovery-codes", post(regenerate_recovery_codes::<S, T>))
+        .route("/auth/2fa/disable", post(disable::<S, T>))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{hash_password, jwt::verify_access_token, CreateUserData, InMemoryUserStore};
+    use crate::clock::Clock;
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_rfc6238_vector() {
+        let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+        let at = DateTime::from_timestamp(59, 0).unwrap();
+        assert_eq!(totp_code(&secret, at).unwrap(), "287082");
+        assert!(otpauth_uri("My App", "a@b.c", &secret).starts_with("otpauth://totp/My%20App:a%40b.c?secret="));
+    }
+
+    #[tokio::test]
+    async fn test_two_factor_login() {
+        let clock = crate::clock::ManualClock::frozen();
+        let mut config = AuthConfig::new("test-secret").with_clock(clock.shared());
+        config.argon2_memory_cost = 1024;
+        config.argon2_time_cost = 1;
+
+        let users = InMemoryUserStore::new();
+        let user = users
+            .create(CreateUserData {
+                email: "ada@example.com".to_string(),
+                name: "Ada".to_string(),
+                password_hash: hash_password("Secret123", &config).unwrap(),
+            })
+            .await
+            .unwrap();
+        let router = auth_routes_with_two_factor(config.clone(), users, InMemoryTwoFactorStore::new())
+            .layer(axum::Extension(config.clone()));
+
+        let call = |path: &str, token: Option<&str>, body: serde_json::Value| {
+            let mut request = Request::post(path).header("content-type", "application/json");
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+            }
+        };
+        let credentials = serde_json::json!({"email": "ada@example.com", "password": "Secret123"});
+
+        let (_, body) = call("/auth/login", None, credentials.clone()).await;
+        let access_token = body["access_token"].as_str().unwrap().to_string();
+
+        let (status, body) = call("/auth/2fa/setup", Some(&access_token), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        let secret = body["secret"].as_str().unwrap().to_string();
+        let code = totp_code(&secret, clock.now()).unwrap();
+        let (status, body) = call("/auth/2fa/verify", Some(&access_token), serde_json::json!({"code": code})).await;
+        assert_eq!(status, StatusCode::OK);
+        let recovery_code = body["recovery_codes"][0].as_str().unwrap().to_string();
+
+        let (_, body) = call("/auth/login", None, credentials.clone()).await;
+        assert_eq!(body["two_factor_required"], true);
+        let challenge = body["challenge_token"].as_str().unwrap().to_string();
+
+        // The code used to enrol can't be replayed
+        let second_step = |code: String| serde_json::json!({"challenge_token": challenge, "code": code});
+        let (status, _) = call("/auth/2fa/login", None, second_step(code)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        clock.advance(Duration::from_secs(30));
+        let code = totp_code(&secret, clock.now()).unwrap();
+        let (status, body) = call("/auth/2fa/login", None, second_step(code)).await;
+        assert_eq!(status, StatusCode::OK);
+        let claims = verify_access_token(body["access_token"].as_str().unwrap(), &config).unwrap();
+        assert_eq!(claims.sub, user.id);
+        assert_eq!(claims.amr, ["pwd", "otp", "mfa"]);
+
+        // The challenge is used up
+        let (status, _) = call("/auth/2fa/login", None, second_step(recovery_code.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (_, body) = call("/auth/login", None, credentials.clone()).await;
+        let challenge = body["challenge_token"].as_str().unwrap().to_string();
+        let second_step = |code: &str| serde_json::json!({"challenge_token": challenge, "code": code});
+        let (status, _) = call("/auth/2fa/login", None, second_step(&recovery_code)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call("/auth/2fa/login", None, second_step(&recovery_code)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_attempts_are_capped() {
+        let clock = crate::clock::ManualClock::frozen();
+        let config = AuthConfig::new("test-secret").with_clock(clock.shared());
+        let store = InMemoryTwoFactorStore::new();
+        let mut record = TwoFactorRecord::new(new_secret());
+        record.enabled = true;
+        store.save("user-1", &record).await.unwrap();
+        let user = StoredUser {
+            id: "user-1".to_string(),
+            email: "ada@example.com".to_string(),
+            name: "Ada".to_string(),
+            password_hash: String::new(),
+            roles: vec![],
+        };
+
+        let first = issue_challenge(&store, &user, password_amr(), &config).await.unwrap().unwrap();
+        let second = issue_challenge(&store, &user, password_amr(), &config).await.unwrap().unwrap();
+        let answer = |challenge: &TwoFactorChallenge, code: String| TwoFactorLoginRequest {
+            challenge_token: challenge.challenge_token.clone(),
+            code,
+        };
+        let code = totp_code(&record.secret, clock.now()).unwrap();
+
+        // Only the latest challenge counts
+        assert!(answer_challenge(&store, &config, None, &answer(&first, code.clone())).await.is_err());
+        for _ in 0..CHALLENGE_MAX_ATTEMPTS {
+            assert!(answer_challenge(&store, &config, None, &answer(&second, "000000".to_string())).await.is_err());
+        }
+        assert!(answer_challenge(&store, &config, None, &answer(&second, code.clone())).await.is_err());
+
+        let third = issue_challenge(&store, &user, password_amr(), &config).await.unwrap().unwrap();
+        assert!(answer_challenge(&store, &config, None, &answer(&third, code)).await.is_ok());
+    }
+}
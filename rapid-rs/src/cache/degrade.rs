@@ -0,0 +1,285 @@
+//! Serve-stale reads for cache and database outages
+//!
+//! [`Degrade`] reads through the cache to a loader (usually a database query)
+//! and keeps a long-lived stale copy of every value it loads. When the loader
+//! fails with a server error, the stale copy is served instead, so read
+//! endpoints stay up while a replica is down. Cache errors are treated as
+//! misses, so a Redis outage only costs extra loads.
+//!
+//! ```rust,ignore
+//! let products = Degrade::new(cache.clone(), "products")
+//!     .with_ttl(Duration::from_secs(60))
+//!     .with_stale_ttl(Duration::from_secs(24 * 3600));
+//!
+//! let product: Product = products
+//!     .get(&format!("product:{}", id), || repo.find(id))
+//!     .await?;
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Cache;
+use crate::error::ApiError;
+
+/// Where a [`Degrade`] read was answered from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DegradePath {
+    /// Fresh cache entry
+    Cache,
+    /// The loader
+    Source,
+    /// Stale copy, because the loader failed
+    Stale,
+    /// Nothing could answer
+    Failed,
+}
+
+impl DegradePath {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DegradePath::Cache => "cache",
+            DegradePath::Source => "source",
+            DegradePath::Stale => "stale",
+            DegradePath::Failed => "failed",
+        }
+    }
+}
+
+/// How many reads took each path
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct DegradeStats {
+    pub cache: u64,
+    pub source: u64,
+    pub stale: u64,
+    pub failed: u64,
+    /// Cache operations that errored or timed out
+    pub cache_errors: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    cache: AtomicU64,
+    source: AtomicU64,
+    stale: AtomicU64,
+    failed: AtomicU64,
+    cache_errors: AtomicU64,
+}
+
+/// Cache, then loader, then stale cache entry
+#[derive(Clone)]
+pub struct Degrade {
+    cache: Arc<Cache>,
+    name: String,
+    ttl: Duration,
+    stale_ttl: Duration,
+    cache_timeout: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl Degrade {
+    /// `name` labels this read path in logs and metrics
+    pub fn new(cache: Arc<Cache>, name: impl Into<String>) -> Self {
+        Self {
+            cache,
+            name: name.into(),
+            ttl: Duration::from_secs(60),
+            stale_ttl: Duration::from_secs(24 * 3600),
+            cache_timeout: None,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// How long loaded values are served fresh (default 60s)
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// How long a stale copy is kept for outages (default 24h)
+    pub fn with_stale_ttl(mut self, stale_ttl: Duration) -> Self {
+        self.stale_ttl = stale_ttl;
+        self
+    }
+
+    /// Give up on a slow cache after `timeout` and treat it as a miss
+    pub fn with_cache_timeout(mut self, timeout: Duration) -> Self {
+        self.cache_timeout = Some(timeout);
+        self
+    }
+
+    /// Read `key`, calling `load` on a cache miss
+    pub async fn get<T, F, Fut>(&self, key: &str, load: F) -> Result<T, ApiError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        self.get_with_path(key, load).await.map(|(value, _)| value)
+    }
+
+    /// Like [`get`](Self::get), also reporting where the value came from
+    pub async fn get_with_path<T, F, Fut>(&self, key: &str, load: F) -> Result<(T, DegradePath), ApiError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        if let Some(value) = self.cache_get(key).await {
+            self.record(DegradePath::Cache);
+            return Ok((value, DegradePath::Cache));
+        }
+
+        let error = match load().await {
+            Ok(value) => {
+                self.cache_set(key, &value, self.ttl).await;
+                self.cache_set(&stale_key(key), &value, self.stale_ttl).await;
+                self.record(DegradePath::Source);
+                return Ok((value, DegradePath::Source));
+            }
+            Err(e) if is_outage(&e) => e,
+            // Not an outage (e.g. NotFound): the answer is authoritative
+            Err(e) => return Err(e),
+        };
+
+        if let Some(value) = self.cache_get(&stale_key(key)).await {
+            tracing::warn!(name = %self.name, key = %key, error = %error, "Serving stale value");
+            self.record(DegradePath::Stale);
+            return Ok((value, DegradePath::Stale));
+        }
+
+        self.record(DegradePath::Failed);
+        Err(error)
+    }
+
+    /// Drop the fresh and stale copies of `key`, e.g. after a write
+    pub async fn invalidate(&self, key: &str) -> Result<(), ApiError> {
+        self.cache.delete(key).await?;
+        self.cache.delete(&stale_key(key)).await
+    }
+
+    pub fn stats(&self) -> DegradeStats {
+        let c = &self.counters;
+        DegradeStats {
+            cache: c.cache.load(Ordering::Relaxed),
+            source: c.source.load(Ordering::Relaxed),
+            stale: c.stale.load(Ordering::Relaxed),
+            failed: c.failed.load(Ordering::Relaxed),
+            cache_errors: c.cache_errors.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn cache_get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let result = match self.cache_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.cache.get(key))
+                .await
+                .unwrap_or_else(|_| Err(ApiError::ServiceUnavailable("Cache timed out".to_string()))),
+            None => self.cache.get(key).await,
+        };
+        result.unwrap_or_else(|e| {
+            self.cache_error(key, &e);
+            None
+        })
+    }
+
+    async fn cache_set<T: Serialize + Send + Sync>(&self, key: &str, value: &T, ttl: Duration) {
+        let result = match self.cache_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.cache.set(key, value, ttl))
+                .await
+                .unwrap_or_else(|_| Err(ApiError::ServiceUnavailable("Cache timed out".to_string()))),
+            None => self.cache.set(key, value, ttl).await,
+        };
+        if let Err(e) = result {
+            self.cache_error(key, &e);
+        }
+    }
+
+    fn cache_error(&self, key: &str, error: &ApiError) {
+        tracing::warn!(name = %self.name, key = %key, error = %error, "Cache unavailable");
+        self.counters.cache_errors.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "observability")]
+        crate::metrics::record_counter("cache_degrade_errors_total", 1, &[("name", self.name.clone())]);
+    }
+
+    fn record(&self, path: DegradePath) {
+        let counter = match path {
+            DegradePath::Cache => &self.counters.cache,
+            DegradePath::Source => &self.counters.source,
+            DegradePath::Stale => &self.counters.stale,
+            DegradePath::Failed => &self.counters.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "observability")]
+        crate::metrics::record_counter(
+            "cache_degrade_reads_total",
+            1,
+            &[("name", self.name.clone()), ("path", path.as_str().to_string())],
+        );
+    }
+}
+
+fn stale_key(key: &str) -> String {
+    format!("stale:{}", key)
+}
+
+/// Errors that mean the source is down rather than giving an answer
+fn is_outage(error: &ApiError) -> bool {
+    match error {
+        ApiError::InternalServerError(_) | ApiError::ServiceUnavailable(_) | ApiError::DatabaseError(_) => true,
+        ApiError::Custom { status, .. } => status.is_server_error(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+
+    #[tokio::test]
+    async fn test_serves_stale_on_outage() {
+        let clock = crate::clock::ManualClock::frozen();
+        let cache = Arc::new(Cache::new(CacheConfig::default().with_clock(clock.shared())));
+        let reads = Degrade::new(cache, "products")
+            .with_ttl(Duration::from_secs(60))
+            .with_stale_ttl(Duration::from_secs(3600));
+        let down = || async { Err::<String, _>(ApiError::ServiceUnavailable("replica down".to_string())) };
+
+        let (value, path) = reads.get_with_path("p:1", || async { Ok("widget".to_string()) }).await.unwrap();
+        assert_eq!((value.as_str(), path), ("widget", DegradePath::Source));
+        let (_, path) = reads.get_with_path("p:1", down).await.unwrap();
+        assert_eq!(path, DegradePath::Cache);
+
+        clock.advance(Duration::from_secs(120));
+        let (value, path) = reads.get_with_path("p:1", down).await.unwrap();
+        assert_eq!((value.as_str(), path), ("widget", DegradePath::Stale));
+
+        // Authoritative errors are not papered over
+        let missing = || async { Err::<String, _>(ApiError::NotFound("gone".to_string())) };
+        assert!(matches!(reads.get("p:1", missing).await, Err(ApiError::NotFound(_))));
+
+        clock.advance(Duration::from_secs(3600));
+        assert!(reads.get("p:1", down).await.is_err());
+
+        let stats = reads.stats();
+        assert_eq!((stats.source, stats.cache, stats.stale, stats.failed), (1, 1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_stale_copy_outlives_default_ttl() {
+        // Real time: moka's own eviction must honour stale_ttl
+        let cache = Arc::new(Cache::new(CacheConfig::default().with_default_ttl(1)));
+        let reads = Degrade::new(cache, "products")
+            .with_ttl(Duration::from_millis(100))
+            .with_stale_ttl(Duration::from_secs(30));
+        let down = || async { Err::<String, _>(ApiError::ServiceUnavailable("replica down".to_string())) };
+
+        reads.get("p:1", || async { Ok("widget".to_string()) }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        let (value, path) = reads.get_with_path("p:1", down).await.unwrap();
+        assert_eq!((value.as_str(), path), ("widget", DegradePath::Stale));
+    }
+}
@@ -0,0 +1,380 @@
+//! Whole-response caching for `GET` endpoints
+//!
+//! [`http_cache_middleware`] stores successful `GET` responses in a [`Cache`]
+//! and answers repeated requests from it, marking responses `X-Cache: HIT` or
+//! `X-Cache: MISS`. Entries are keyed by host, tenant, path, query and the
+//! request headers named with [`HttpCache::vary_by`]. The tenant is the
+//! resolved `TenantContext` when the tenant middleware runs first, otherwise
+//! the raw `X-Tenant-ID` header, so tenants never see each other's responses:
+//!
+//! ```rust,ignore
+//! let http_cache = HttpCache::new(cache.clone())
+//!     .with_route("/products", Duration::from_secs(30))
+//!     .with_route("/products/:id", Duration::from_secs(300))
+//!     .vary_by(header::ACCEPT_LANGUAGE);
+//!
+//! let app = App::new()
+//!     .route("/products", get(list_products))
+//!     .route("/products/:id", get(get_product))
+//!     .into_router()
+//!     .layer(middleware::from_fn_with_state(http_cache, http_cache_middleware));
+//! ```
+//!
+//! `Cache-Control` is honored both ways: requests with `no-cache` skip the
+//! lookup (`no-store` also skips storing), and responses are not stored when
+//! marked `no-store`, `no-cache` or `private`, while their `s-maxage` or
+//! `max-age` replaces the route's TTL. Requests carrying credentials
+//! (`Authorization`, `Proxy-Authorization`, `X-API-Key` or a `Cookie`) bypass
+//! the cache entirely, since route auth may run inside this middleware, and
+//! responses setting cookies are never cached.
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Cache;
+
+/// Header telling whether a response came from the cache
+pub const X_CACHE: &str = "x-cache";
+
+/// Which responses [`http_cache_middleware`] caches, and for how long
+#[derive(Clone)]
+pub struct HttpCache {
+    cache: Arc<Cache>,
+    routes: HashMap<String, Duration>,
+    default_ttl: Option<Duration>,
+    vary: Vec<HeaderName>,
+    max_body_size: usize,
+}
+
+impl HttpCache {
+    /// Caches nothing until routes or a default TTL are added
+    pub fn new(cache: Arc<Cache>) -> Self {
+        Self {
+            cache,
+            routes: HashMap::new(),
+            default_ttl: None,
+            vary: Vec::new(),
+            max_body_size: 1024 * 1024,
+        }
+    }
+
+    /// Cache the route registered as `path` (e.g. `/products/:id`) for `ttl`
+    pub fn with_route(mut self, path: impl Into<String>, ttl: Duration) -> Self {
+        self.routes.insert(path.into(), ttl);
+        self
+    }
+
+    /// Cache every other `GET` route for `ttl`
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Keep separate entries per value of the request header `name`
+    pub fn vary_by(mut self, name: HeaderName) -> Self {
+        self.vary.push(name);
+        self
+    }
+
+    /// Larger responses are not cached (default 1 MiB)
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    fn ttl_for(&self, request: &Request) -> Option<Duration> {
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(MatchedPath::as_str)
+            .unwrap_or(request.uri().path());
+        self.routes.get(route).copied().or(self.default_ttl)
+    }
+
+    fn key(&self, request: &Request) -> String {
+        let host = request
+            .headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or(request.uri().host())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut key = format!("http:{}|{}|{}", host, tenant(request).unwrap_or_default(), request.uri().path());
+        if let Some(query) = request.uri().query() {
+            key.push('?');
+            key.push_str(query);
+        }
+        for name in &self.vary {
+            let value = request.headers().get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+            key.push_str(&format!("|{}={}", name, value));
+        }
+        key
+    }
+}
+
+/// The tenant a request belongs to: the resolved context if the tenant
+/// middleware already ran, else the header it would resolve from
+fn tenant(request: &Request) -> Option<String> {
+    #[cfg(feature = "multi-tenancy")]
+    if let Some(context) = request.extensions().get::<crate::multi_tenancy::TenantContext>() {
+        return Some(context.tenant_id().as_str().to_string());
+    }
+    request.headers().get("x-tenant-id").and_then(|value| value.to_str().ok()).map(str::to_string)
+}
+
+/// A stored response; only UTF-8 bodies are cached
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+/// `Cache-Control` directives, lowercased, with their values
+fn cache_control(headers: &HeaderMap) -> HashMap<String, Option<String>> {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter(|directive| !directive.trim().is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), Some(value.trim().trim_matches('"').to_string())),
+            None => (directive.trim().to_ascii_lowercase(), None),
+        })
+        .collect()
+}
+
+/// How long `response` may be stored, or `None` if it must not be
+fn storable_for(response: &Response, ttl: Duration, vary: &[HeaderName]) -> Option<Duration> {
+    if response.status() != StatusCode::OK || response.headers().contains_key(header::SET_COOKIE) {
+        return None;
+    }
+    // Entries are only keyed by the configured headers
+    let varies_otherwise = response
+        .headers()
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|name| name == "*" || !vary.iter().any(|configured| configured.as_str().eq_ignore_ascii_case(name)));
+    if varies_otherwise {
+        return None;
+    }
+
+    let directives = cache_control(response.headers());
+    if ["no-store", "no-cache", "private"].iter().any(|directive| directives.contains_key(*directive)) {
+        return None;
+    }
+    let max_age = |name: &str| directives.get(name)?.as_deref()?.parse().ok().map(Duration::from_secs);
+    let ttl = max_age("s-maxage").or_else(|| max_age("max-age")).unwrap_or(ttl);
+    (!ttl.is_zero()).then_some(ttl)
+}
+
+/// Headers that identify the caller, so responses to them are never shared
+const CREDENTIAL_HEADERS: [&str; 4] = ["authorization", "proxy-authorization", "x-api-key", "cookie"];
+
+fn has_credentials(headers: &HeaderMap) -> bool {
+    CREDENTIAL_HEADERS.iter().any(|name| headers.contains_key(*name))
+}
+
+fn mark(mut response: Response, outcome: &'static str) -> Response {
+    response.headers_mut().insert(X_CACHE, HeaderValue::from_static(outcome));
+    response
+}
+
+/// Serve `GET`s from the cache and store cacheable responses, see the [module docs](self)
+pub async fn http_cache_middleware(State(http_cache): State<HttpCache>, request: Request, next: Next) -> Response {
+    let ttl = match http_cache.ttl_for(&request) {
+        Some(ttl) if request.method() == Method::GET && !has_credentials(request.headers()) => ttl,
+        _ => return next.run(request).await,
+    };
+
+    let key = http_cache.key(&request);
+    let directives = cache_control(request.headers());
+    if !directives.contains_key("no-cache") && !directives.contains_key("no-store") {
+        match http_cache.cache.get::<CachedResponse>(&key).await {
+            Ok(Some(cached)) => return mark(cached.into_response(), "HIT"),
+            Ok(None) => {}
+            Err(error) => tracing::warn!(key, error = %error, "HTTP cache lookup failed"),
+        }
+    }
+    let store = !directives.contains_key("no-store");
+
+    let response = next.run(request).await;
+    let ttl = match storable_for(&response, ttl, &http_cache.vary) {
+        Some(ttl) if store => ttl,
+        _ => return mark(response, "MISS"),
+    };
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= http_cache.max_body_size as u64);
+    if !fits {
+        return mark(response, "MISS");
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            tracing::error!(error = %error, "Failed to read response body for caching");
+            return mark(StatusCode::INTERNAL_SERVER_ERROR.into_response(), "MISS");
+        }
+    };
+    if let Ok(body) = std::str::from_utf8(&bytes) {
+        let cached = CachedResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter(|(name, _)| *name != header::CONTENT_LENGTH)
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                .collect(),
+            body: body.to_string(),
+        };
+        if let Err(error) = http_cache.cache.set(&key, &cached, ttl).await {
+            tracing::warn!(key, error = %error, "Failed to store response in HTTP cache");
+        }
+    }
+    mark(Response::from_parts(parts, Body::from(bytes)), "MISS")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+    use axum::{routing::get, Router};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tower::ServiceExt;
+
+    fn router(calls: Arc<AtomicU64>) -> Router {
+        let http_cache = HttpCache::new(Arc::new(Cache::new(CacheConfig::default())))
+            .with_route("/products/:id", Duration::from_secs(60))
+            .vary_by(header::ACCEPT_LANGUAGE);
+        let counted = move |headers: HeaderMap| {
+            let calls = calls.clone();
+            async move {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                let cache_control = if headers.contains_key("x-private") { "private" } else { "public" };
+                ([(header::CACHE_CONTROL, cache_control)], format!("call {}", call))
+            }
+        };
+        Router::new()
+            .route("/products/:id", get(counted.clone()))
+            .route("/uncached", get(counted))
+            .layer(axum::middleware::from_fn_with_state(http_cache, http_cache_middleware))
+    }
+
+    async fn call(router: &Router, uri: &str, headers: &[(&str, &str)]) -> (String, String) {
+        let mut request = Request::get(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let outcome = response.headers().get(X_CACHE).map(|value| value.to_str().unwrap().to_string()).unwrap_or_default();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (outcome, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_caches_get_responses() {
+        let router = router(Arc::new(AtomicU64::new(0)));
+
+        assert_eq!(call(&router, "/products/1", &[]).await, ("MISS".to_string(), "call 1".to_string()));
+        assert_eq!(call(&router, "/products/1", &[]).await, ("HIT".to_string(), "call 1".to_string()));
+        assert_eq!(call(&router, "/products/1?page=2", &[]).await.1, "call 2");
+        assert_eq!(call(&router, "/products/1", &[("accept-language", "fr")]).await.1, "call 3");
+        assert_eq!(call(&router, "/products/1", &[("cache-control", "no-cache")]).await.1, "call 4");
+        assert_eq!(call(&router, "/products/1", &[]).await.1, "call 4");
+        assert_eq!(call(&router, "/uncached", &[]).await, (String::new(), "call 5".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_respects_cache_control() {
+        let router = router(Arc::new(AtomicU64::new(0)));
+
+        assert_eq!(call(&router, "/products/2", &[("x-private", "1")]).await.1, "call 1");
+        assert_eq!(call(&router, "/products/2", &[]).await, ("MISS".to_string(), "call 2".to_string()));
+        assert_eq!(call(&router, "/products/3", &[("authorization", "Bearer x")]).await.0, "");
+        assert_eq!(call(&router, "/products/3", &[("cookie", "session=abc")]).await.0, "");
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_per_host_and_tenant() {
+        let router = router(Arc::new(AtomicU64::new(0)));
+
+        assert_eq!(call(&router, "/products/4", &[("x-tenant-id", "acme")]).await.1, "call 1");
+        assert_eq!(call(&router, "/products/4", &[("x-tenant-id", "acme")]).await, ("HIT".to_string(), "call 1".to_string()));
+        assert_eq!(call(&router, "/products/4", &[("x-tenant-id", "globex")]).await.1, "call 2");
+        assert_eq!(call(&router, "/products/4", &[]).await.1, "call 3");
+        assert_eq!(call(&router, "/products/4", &[("host", "a.example.com")]).await.1, "call 4");
+        assert_eq!(call(&router, "/products/4", &[("host", "b.example.com")]).await.1, "call 5");
+        assert_eq!(call(&router, "/products/4", &[("host", "A.example.com")]).await.0, "HIT");
+    }
+
+    #[cfg(feature = "multi-tenancy")]
+    #[tokio::test]
+    async fn test_resolved_tenant_keys_entries() {
+        use crate::multi_tenancy::{TenantContext, TenantId, TenantInfo};
+
+        // Stands in for the tenant middleware resolving from the subdomain
+        let router = router(Arc::new(AtomicU64::new(0))).layer(axum::middleware::from_fn(
+            |mut request: Request, next: Next| async move {
+                let id = request.headers().get("x-subdomain").unwrap().to_str().unwrap().to_string();
+                let info = TenantInfo { id: TenantId::new(&id), name: id, features: Vec::new(), metadata: HashMap::new() };
+                request.extensions_mut().insert(TenantContext::new(info));
+                next.run(request).await
+            },
+        ));
+
+        assert_eq!(call(&router, "/products/5", &[("x-subdomain", "acme")]).await.1, "call 1");
+        assert_eq!(call(&router, "/products/5", &[("x-subdomain", "globex")]).await.1, "call 2");
+        let spoofed = [("x-subdomain", "acme"), ("x-tenant-id", "globex")];
+        assert_eq!(call(&router, "/products/5", &spoofed).await, ("HIT".to_string(), "call 1".to_string()));
+    }
+
+    #[cfg(feature = "auth")]
+    #[tokio::test]
+    async fn test_credentials_bypass_cache_in_front_of_route_auth() {
+        use crate::auth::{ApiKeyIdentity, RouteAuth, StaticApiKeys};
+        use crate::openapi::RouteDoc;
+
+        let http_cache = HttpCache::new(Arc::new(Cache::new(CacheConfig::default())))
+            .with_route("/reports", Duration::from_secs(60));
+        let router = crate::App::new()
+            .with_api_keys(StaticApiKeys::new().with_key("k-1", ApiKeyIdentity::new("billing")))
+            .route_with_doc("/reports", get(|| async { "secret report" }), RouteDoc::get().auth(RouteAuth::api_key()))
+            .into_router()
+            .layer(axum::middleware::from_fn_with_state(http_cache, http_cache_middleware));
+
+        assert_eq!(call(&router, "/reports", &[("x-api-key", "k-1")]).await, (String::new(), "secret report".to_string()));
+        let (outcome, body) = call(&router, "/reports", &[]).await;
+        assert_ne!(outcome, "HIT");
+        assert_ne!(body, "secret report");
+    }
+}
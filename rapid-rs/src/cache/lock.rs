@@ -0,0 +1,264 @@
+//! Distributed locks on the cache
+//!
+//! With the Redis backend, a lock taken by one instance is seen by all of
+//! them, so schedulers and jobs can elect a leader or skip work another
+//! instance is already doing:
+//!
+//! ```rust,ignore
+//! if let Some(lock) = cache.try_lock("jobs:nightly-report", Duration::from_secs(300)).await? {
+//!     build_report().await?;
+//!     lock.release().await?;
+//! }
+//! ```
+//!
+//! Each lock holds a random token and is only released or extended by
+//! whoever holds that token, so a holder whose lock expired can't release
+//! the next holder's. Locks expire after their TTL even if the holder
+//! crashes; long-running holders should [`extend`](CacheLock::extend) them.
+//! The memory backend's locks only span one process; the memcached
+//! backend's need memcached 1.6 or later.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::SharedClock;
+use crate::error::ApiError;
+
+/// Lock tokens and expiry by key, for the memory backend
+pub(crate) type LockTable = Arc<Mutex<HashMap<String, (String, DateTime<Utc>)>>>;
+
+/// Longest wait between attempts in [`Cache::lock`](super::Cache::lock)
+pub(crate) const MAX_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Deletes the lock only if it still holds our token
+#[cfg(feature = "cache-redis")]
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+/// Pushes the expiry only if the lock still holds our token
+#[cfg(feature = "cache-redis")]
+const EXTEND_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+/// Key holding the lock on `key` in Redis or memcached
+#[cfg(any(feature = "cache-redis", feature = "cache-memcached"))]
+pub(crate) fn lock_key(key: &str) -> String {
+    format!("__lock:{}", key)
+}
+
+pub(crate) enum Holder {
+    Memory { locks: LockTable, clock: SharedClock },
+    #[cfg(feature = "cache-redis")]
+    Redis(redis::aio::ConnectionManager),
+    #[cfg(feature = "cache-memcached")]
+    Memcached(Arc<super::memcached::Server>),
+}
+
+/// A held lock, released when dropped
+///
+/// Prefer [`release`](Self::release), which reports errors; dropping
+/// releases in the background and otherwise leaves the lock to expire.
+pub struct CacheLock {
+    key: String,
+    token: String,
+    holder: Holder,
+    released: bool,
+}
+
+impl std::fmt::Debug for CacheLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheLock").field("key", &self.key).finish_non_exhaustive()
+    }
+}
+
+impl CacheLock {
+    pub(crate) fn new(key: &str, token: String, holder: Holder) -> Self {
+        Self {
+            key: key.to_string(),
+            token,
+            holder,
+            released: false,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Keep the lock for `ttl` from now; `false` if it was lost meanwhile
+    pub async fn extend(&self, ttl: Duration) -> Result<bool, ApiError> {
+        match &self.holder {
+            Holder::Memory { locks, clock } => {
+                let mut locks = locks.lock().unwrap();
+                match locks.get_mut(&self.key) {
+                    Some((token, expires_at)) if *token == self.token && *expires_at > clock.now() => {
+                        *expires_at = clock.now() + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            #[cfg(feature = "cache-redis")]
+            Holder::Redis(conn) => {
+                let extended: i64 = redis::Script::new(EXTEND_SCRIPT)
+                    .key(lock_key(&self.key))
+                    .arg(&self.token)
+                    .arg(ttl.as_millis() as u64)
+                    .invoke_async(&mut conn.clone())
+                    .await
+                    .map_err(|e| ApiError::InternalServerError(format!("Redis lock error: {}", e)))?;
+                Ok(extended == 1)
+            }
+            #[cfg(feature = "cache-memcached")]
+            Holder::Memcached(server) => super::memcached::extend_lock(server, &self.key, &self.token, ttl).await,
+        }
+    }
+
+    /// Give the lock up; `false` if it had already expired or been taken over
+    pub async fn release(mut self) -> Result<bool, ApiError> {
+        self.released = true;
+        match &self.holder {
+            Holder::Memory { locks, clock } => Ok(release_memory(locks, clock, &self.key, &self.token)),
+            #[cfg(feature = "cache-redis")]
+            Holder::Redis(conn) => release_redis(conn.clone(), &self.key, &self.token).await,
+            #[cfg(feature = "cache-memcached")]
+            Holder::Memcached(server) => super::memcached::release_lock(server, &self.key, &self.token).await,
+        }
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        match &self.holder {
+            Holder::Memory { locks, clock } => {
+                release_memory(locks, clock, &self.key, &self.token);
+            }
+            #[cfg(feature = "cache-redis")]
+            Holder::Redis(conn) => {
+                let (conn, key, token) = (conn.clone(), self.key.clone(), self.token.clone());
+                if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                    runtime.spawn(async move {
+                        if let Err(error) = release_redis(conn, &key, &token).await {
+                            tracing::warn!(key, error = %error, "Failed to release cache lock");
+                        }
+                    });
+                }
+            }
+            #[cfg(feature = "cache-memcached")]
+            Holder::Memcached(server) => {
+                let (server, key, token) = (server.clone(), self.key.clone(), self.token.clone());
+                if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                    runtime.spawn(async move {
+                        if let Err(error) = super::memcached::release_lock(&server, &key, &token).await {
+                            tracing::warn!(key, error = %error, "Failed to release cache lock");
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn release_memory(locks: &LockTable, clock: &SharedClock, key: &str, token: &str) -> bool {
+    let mut locks = locks.lock().unwrap();
+    match locks.get(key) {
+        Some((held, expires_at)) if held == token => {
+            let live = *expires_at > clock.now();
+            locks.remove(key);
+            live
+        }
+        _ => false,
+    }
+}
+
+#[cfg(feature = "cache-redis")]
+async fn release_redis(mut conn: redis::aio::ConnectionManager, key: &str, token: &str) -> Result<bool, ApiError> {
+    let deleted: i64 = redis::Script::new(RELEASE_SCRIPT)
+        .key(lock_key(key))
+        .arg(token)
+        .invoke_async(&mut conn)
+        .await
+        .map_err(|e| ApiError::InternalServerError(format!("Redis lock error: {}", e)))?;
+    Ok(deleted == 1)
+}
+
+/// Take the memory lock on `key` unless someone else holds it
+pub(crate) fn try_lock_memory(locks: &LockTable, clock: &SharedClock, key: &str, ttl: Duration) -> Option<CacheLock> {
+    let now = clock.now();
+    let token = crate::ids::new_id();
+    {
+        let mut table = locks.lock().unwrap();
+        if table.get(key).is_some_and(|(_, expires_at)| *expires_at > now) {
+            return None;
+        }
+        let expires_at = now + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        table.insert(key.to_string(), (token.clone(), expires_at));
+    }
+    Some(CacheLock::new(
+        key,
+        token,
+        Holder::Memory {
+            locks: locks.clone(),
+            clock: clock.clone(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Cache, CacheConfig};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_lock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let cache = Cache::new(CacheConfig::default().with_clock(clock.shared()));
+        let ttl = Duration::from_secs(30);
+
+        let lock = cache.try_lock("report", ttl).await.unwrap().unwrap();
+        assert!(cache.try_lock("report", ttl).await.unwrap().is_none());
+        assert!(cache.try_lock("other", ttl).await.unwrap().is_some());
+        assert!(lock.release().await.unwrap());
+
+        // An expired lock can be taken over, and its old holder can't release it
+        let stale = cache.try_lock("report", ttl).await.unwrap().unwrap();
+        clock.advance(Duration::from_secs(31));
+        let current = cache.lock("report", ttl).await.unwrap();
+        assert!(!stale.extend(ttl).await.unwrap());
+        assert!(!stale.release().await.unwrap());
+        assert!(current.extend(Duration::from_secs(60)).await.unwrap());
+        clock.advance(Duration::from_secs(45));
+        assert!(cache.try_lock("report", ttl).await.unwrap().is_none());
+
+        drop(current);
+        assert!(cache.try_lock("report", ttl).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_lock_waits_for_release() {
+        let cache = Arc::new(Cache::new(CacheConfig::default()));
+        let held = cache.try_lock("leader", Duration::from_secs(30)).await.unwrap().unwrap();
+
+        let waiter = tokio::spawn({
+            let cache = cache.clone();
+            async move { cache.lock("leader", Duration::from_secs(30)).await.map(|lock| lock.key().to_string()) }
+        });
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(!waiter.is_finished());
+        held.release().await.unwrap();
+        assert_eq!(waiter.await.unwrap().unwrap(), "leader");
+    }
+}
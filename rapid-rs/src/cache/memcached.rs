@@ -0,0 +1,526 @@
+//! Memcached cache backend
+//!
+//! Speaks the memcached text protocol directly, so no client library is
+//! needed. Keys are spread over the servers by hash, the same way on every
+//! instance:
+//!
+//! ```rust,ignore
+//! let cache = Cache::with_memcached(&["cache-1:11211", "cache-2:11211"], CacheConfig::default()).await?;
+//! ```
+//!
+//! Memcached can't list or group keys, so tags are kept as index entries
+//! that never expire (they can still be evicted under memory pressure), and
+//! [`delete_prefix`](MemcachedCache::delete_prefix) needs the LRU crawler of
+//! memcached 1.4.31 or later. Locks use meta commands (memcached 1.6+).
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+
+use super::lock::{lock_key, CacheLock, Holder};
+use super::serializer::Codec;
+use super::{CacheConfig, CacheStats};
+use crate::error::ApiError;
+
+/// Longest key memcached accepts
+const MAX_KEY_LEN: usize = 250;
+
+/// Expiry times above this are read by memcached as Unix timestamps
+const MAX_RELATIVE_EXPIRY: u64 = 30 * 24 * 3600;
+
+/// Idle connections kept per server
+const MAX_IDLE: usize = 16;
+
+/// Index entry listing the keys stored under `tag`
+fn tag_key(tag: &str) -> String {
+    format!("__tag:{}", tag)
+}
+
+/// Expiry to send for `ttl`, rounded up to whole seconds
+fn expiry(ttl: Duration) -> u64 {
+    let seconds = (ttl.as_millis().div_ceil(1000) as u64).max(1);
+    if seconds > MAX_RELATIVE_EXPIRY {
+        chrono::Utc::now().timestamp() as u64 + seconds
+    } else {
+        seconds
+    }
+}
+
+/// FNV-1a, stable across processes unlike `DefaultHasher`
+fn hash(key: &str) -> u64 {
+    key.bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// `%xx` escapes in keys from `lru_crawler metadump` decoded
+fn decode_key(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn check_key(key: &str) -> io::Result<()> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN || key.bytes().any(|b| b.is_ascii_whitespace() || b.is_ascii_control()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not a valid memcached key", key),
+        ));
+    }
+    Ok(())
+}
+
+fn protocol_error(line: &str) -> io::Error {
+    io::Error::other(format!("unexpected reply '{}'", line))
+}
+
+/// One connection to a memcached server
+struct Connection {
+    stream: BufStream<TcpStream>,
+}
+
+impl Connection {
+    async fn send(&mut self, line: &str, data: Option<&[u8]>) -> io::Result<()> {
+        self.stream.write_all(line.as_bytes()).await?;
+        self.stream.write_all(b"\r\n").await?;
+        if let Some(data) = data {
+            self.stream.write_all(data).await?;
+            self.stream.write_all(b"\r\n").await?;
+        }
+        self.stream.flush().await
+    }
+
+    async fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line == "ERROR" || line.starts_with("CLIENT_ERROR") || line.starts_with("SERVER_ERROR") {
+            return Err(io::Error::other(line));
+        }
+        Ok(line)
+    }
+
+    /// The value and CAS id stored under `key`
+    async fn get(&mut self, key: &str) -> io::Result<Option<(Vec<u8>, u64)>> {
+        check_key(key)?;
+        self.send(&format!("gets {}", key), None).await?;
+        let mut value = None;
+        loop {
+            let line = self.read_line().await?;
+            if line == "END" {
+                return Ok(value);
+            }
+            // VALUE <key> <flags> <bytes> <cas>
+            let fields: Vec<&str> = line.split(' ').collect();
+            let (Some(len), Some(cas)) = (
+                fields.get(3).and_then(|len| len.parse::<usize>().ok()),
+                fields.get(4).and_then(|cas| cas.parse::<u64>().ok()),
+            ) else {
+                return Err(protocol_error(&line));
+            };
+            let mut data = vec![0; len + 2];
+            self.stream.read_exact(&mut data).await?;
+            data.truncate(len);
+            value = Some((data, cas));
+        }
+    }
+
+    /// Run a storage command (`set`, `add`, `append`, `cas`), returning
+    /// whether it stored
+    async fn store(&mut self, command: &str, key: &str, data: &[u8], expiry: u64, cas: Option<u64>) -> io::Result<bool> {
+        check_key(key)?;
+        let line = match cas {
+            Some(cas) => format!("{} {} 0 {} {} {}", command, key, expiry, data.len(), cas),
+            None => format!("{} {} 0 {} {}", command, key, expiry, data.len()),
+        };
+        self.send(&line, Some(data)).await?;
+        match self.read_line().await?.as_str() {
+            "STORED" => Ok(true),
+            "NOT_STORED" | "EXISTS" | "NOT_FOUND" => Ok(false),
+            line => Err(protocol_error(line)),
+        }
+    }
+
+    async fn delete(&mut self, key: &str) -> io::Result<bool> {
+        check_key(key)?;
+        self.send(&format!("delete {}", key), None).await?;
+        match self.read_line().await?.as_str() {
+            "DELETED" => Ok(true),
+            "NOT_FOUND" => Ok(false),
+            line => Err(protocol_error(line)),
+        }
+    }
+
+    /// Delete `key` only if its CAS id is still `cas`
+    async fn delete_if(&mut self, key: &str, cas: u64) -> io::Result<bool> {
+        check_key(key)?;
+        self.send(&format!("md {} C{}", key, cas), None).await?;
+        match self.read_line().await?.as_str() {
+            "HD" => Ok(true),
+            "NF" | "EX" => Ok(false),
+            line => Err(protocol_error(line)),
+        }
+    }
+
+    /// Lines of a multi-line reply up to `END`
+    async fn lines(&mut self, command: &str) -> io::Result<Vec<String>> {
+        self.send(command, None).await?;
+        let mut lines = Vec::new();
+        loop {
+            match self.read_line().await? {
+                line if line == "END" => return Ok(lines),
+                line => lines.push(line),
+            }
+        }
+    }
+}
+
+/// A memcached server and its idle connections
+pub(crate) struct Server {
+    address: String,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl Server {
+    async fn connection(&self) -> io::Result<Connection> {
+        let idle = self.idle.lock().unwrap().pop();
+        match idle {
+            Some(connection) => Ok(connection),
+            None => Ok(Connection {
+                stream: BufStream::new(TcpStream::connect(&self.address).await?),
+            }),
+        }
+    }
+
+    /// Hand `connection` back once `result` shows it's still in a known state
+    fn finish<T>(&self, connection: Connection, result: io::Result<T>, operation: &str) -> Result<T, ApiError> {
+        match result {
+            Ok(value) => {
+                let mut idle = self.idle.lock().unwrap();
+                if idle.len() < MAX_IDLE {
+                    idle.push(connection);
+                }
+                Ok(value)
+            }
+            Err(e) => Err(ApiError::InternalServerError(format!(
+                "Memcached {} error on {}: {}",
+                operation, self.address, e
+            ))),
+        }
+    }
+
+    fn connect_error(&self, e: io::Error) -> ApiError {
+        ApiError::InternalServerError(format!("Failed to connect to memcached at {}: {}", self.address, e))
+    }
+}
+
+/// Run `$op` on a connection to `$server`, pooling the connection afterwards
+macro_rules! with_connection {
+    ($server:expr, $operation:literal, |$conn:ident| $op:expr) => {{
+        let server = &$server;
+        let mut $conn = server.connection().await.map_err(|e| server.connect_error(e))?;
+        let result = $op.await;
+        server.finish($conn, result, $operation)
+    }};
+}
+
+/// Memcached cache backend
+pub struct MemcachedCache {
+    servers: Vec<Arc<Server>>,
+    codec: Codec,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl MemcachedCache {
+    /// Connect to `servers`, given as `host:port` (optionally `memcache://host:port`)
+    pub async fn new(servers: &[impl AsRef<str>], config: CacheConfig) -> Result<Self, ApiError> {
+        if servers.is_empty() {
+            return Err(ApiError::InternalServerError("No memcached servers configured".to_string()));
+        }
+        let servers: Vec<Arc<Server>> = servers
+            .iter()
+            .map(|address| {
+                let address = address.as_ref();
+                let address = address
+                    .strip_prefix("memcache://")
+                    .or_else(|| address.strip_prefix("memcached://"))
+                    .unwrap_or(address);
+                Arc::new(Server {
+                    address: address.trim_end_matches('/').to_string(),
+                    idle: Mutex::new(Vec::new()),
+                })
+            })
+            .collect();
+
+        // Fail early on unreachable servers, keeping the connections
+        for server in &servers {
+            let connection = server.connection().await.map_err(|e| server.connect_error(e))?;
+            server.finish(connection, Ok(()), "connect")?;
+        }
+
+        Ok(Self {
+            servers,
+            codec: Codec::new(&config),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    fn server(&self, key: &str) -> &Arc<Server> {
+        &self.servers[(hash(key) % self.servers.len() as u64) as usize]
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, ApiError> {
+        let value = with_connection!(self.server(key), "get", |conn| conn.get(key))?;
+        match value {
+            Some((bytes, _)) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(self.codec.decode(&bytes)?))
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+        }
+    }
+
+    pub async fn set<T: Serialize + Send + Sync>(&self, key: &str, value: &T, ttl: Duration) -> Result<(), ApiError> {
+        self.store(key, self.encode(value)?, ttl, &[]).await
+    }
+
+    pub async fn set_with_tags<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> Result<(), ApiError> {
+        self.store(key, self.encode(value)?, ttl, tags).await
+    }
+
+    pub(crate) fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        self.codec.encode(value)
+    }
+
+    /// Store an entry already [encoded](Self::encode)
+    pub(crate) async fn store(&self, key: &str, bytes: Vec<u8>, ttl: Duration, tags: &[&str]) -> Result<(), ApiError> {
+        with_connection!(self.server(key), "set", |conn| conn.store("set", key, &bytes, expiry(ttl), None))?;
+
+        // Index entries are newline-separated keys, appended atomically
+        let entry = format!("{}\n", key);
+        for tag in tags {
+            let tag_key = tag_key(tag);
+            with_connection!(self.server(&tag_key), "tag", |conn| async {
+                if conn.store("append", &tag_key, entry.as_bytes(), 0, None).await? {
+                    return Ok(());
+                }
+                // No index yet; if another instance creates it first, append after all
+                if !conn.store("add", &tag_key, entry.as_bytes(), 0, None).await? {
+                    conn.store("append", &tag_key, entry.as_bytes(), 0, None).await?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<u64, ApiError> {
+        let tag_key = tag_key(tag);
+        let index = with_connection!(self.server(&tag_key), "tag", |conn| conn.get(&tag_key))?;
+        let Some((index, _)) = index else {
+            return Ok(0);
+        };
+
+        let mut keys: Vec<&str> = std::str::from_utf8(&index).unwrap_or_default().lines().collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let mut deleted = 0;
+        for key in keys {
+            if with_connection!(self.server(key), "delete", |conn| conn.delete(key))? {
+                deleted += 1;
+            }
+        }
+        with_connection!(self.server(&tag_key), "delete", |conn| conn.delete(&tag_key))?;
+        Ok(deleted)
+    }
+
+    /// Delete keys starting with `prefix`, found with `lru_crawler metadump`
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<u64, ApiError> {
+        let mut deleted = 0;
+        for server in &self.servers {
+            // key=<url-encoded key> exp=... la=... ...
+            let dump = with_connection!(server, "metadump", |conn| conn.lines("lru_crawler metadump all"))?;
+            let keys = dump
+                .iter()
+                .filter_map(|line| line.strip_prefix("key="))
+                .map(|rest| decode_key(rest.split(' ').next().unwrap_or_default()))
+                .filter(|key| key.starts_with(prefix));
+            for key in keys {
+                if with_connection!(server, "delete", |conn| conn.delete(&key))? {
+                    deleted += 1;
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), ApiError> {
+        with_connection!(self.server(key), "delete", |conn| conn.delete(key)).map(|_| ())
+    }
+
+    /// Lock `key` with `add`, so only the first instance to ask gets it
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> Result<Option<CacheLock>, ApiError> {
+        let (lock_key, token) = (lock_key(key), crate::ids::new_id());
+        let server = self.server(&lock_key);
+        let acquired = with_connection!(server, "lock", |conn| conn.store("add", &lock_key, token.as_bytes(), expiry(ttl), None))?;
+        Ok(acquired.then(|| CacheLock::new(key, token, Holder::Memcached(server.clone()))))
+    }
+
+    pub async fn exists(&self, key: &str) -> Result<bool, ApiError> {
+        with_connection!(self.server(key), "get", |conn| conn.get(key)).map(|value| value.is_some())
+    }
+
+    pub async fn clear(&self) -> Result<(), ApiError> {
+        for server in &self.servers {
+            with_connection!(server, "clear", |conn| async {
+                conn.send("flush_all", None).await?;
+                match conn.read_line().await?.as_str() {
+                    "OK" => Ok(()),
+                    line => Err(protocol_error(line)),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    pub async fn stats(&self) -> Result<CacheStats, ApiError> {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let total = hits + misses;
+        let hit_rate = if total > 0 { hits as f64 / total as f64 } else { 0.0 };
+
+        let mut entries = 0;
+        for server in &self.servers {
+            let stats = with_connection!(server, "stats", |conn| conn.lines("stats"))?;
+            entries += stats
+                .iter()
+                .find_map(|line| line.strip_prefix("STAT curr_items "))
+                .and_then(|count| count.parse::<u64>().ok())
+                .unwrap_or(0);
+        }
+
+        Ok(CacheStats {
+            hits,
+            misses,
+            entries,
+            hit_rate,
+        })
+    }
+}
+
+/// Extend the lock on `key` if it still holds `token`, for [`CacheLock::extend`]
+pub(crate) async fn extend_lock(server: &Server, key: &str, token: &str, ttl: Duration) -> Result<bool, ApiError> {
+    let lock_key = lock_key(key);
+    with_connection!(server, "lock", |conn| async {
+        match conn.get(&lock_key).await? {
+            Some((held, cas)) if held == token.as_bytes() => {
+                conn.store("cas", &lock_key, token.as_bytes(), expiry(ttl), Some(cas)).await
+            }
+            _ => Ok(false),
+        }
+    })
+}
+
+/// Release the lock on `key` if it still holds `token`
+pub(crate) async fn release_lock(server: &Server, key: &str, token: &str) -> Result<bool, ApiError> {
+    let lock_key = lock_key(key);
+    with_connection!(server, "lock", |conn| async {
+        match conn.get(&lock_key).await? {
+            Some((held, cas)) if held == token.as_bytes() => conn.delete_if(&lock_key, cas).await,
+            _ => Ok(false),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_and_expiry() {
+        assert_eq!(hash("user:1"), hash("user:1"));
+        assert_ne!(hash("user:1"), hash("user:2"));
+        assert_eq!(decode_key("session%3Aa%2Fb%zz"), "session:a/b%zz");
+        assert!(check_key("user:1").is_ok());
+        assert!(check_key("user 1").is_err());
+        assert!(check_key(&"k".repeat(251)).is_err());
+
+        assert_eq!(expiry(Duration::from_millis(1500)), 2);
+        assert_eq!(expiry(Duration::ZERO), 1);
+        assert!(expiry(Duration::from_secs(MAX_RELATIVE_EXPIRY + 1)) > 1_000_000_000);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_memcached_cache() {
+        let cache = MemcachedCache::new(&["127.0.0.1:11211"], CacheConfig::default())
+            .await
+            .unwrap();
+        let ttl = Duration::from_secs(60);
+
+        cache.set("test_key", &"test_value", ttl).await.unwrap();
+        let value: Option<String> = cache.get("test_key").await.unwrap();
+        assert_eq!(value, Some("test_value".to_string()));
+        assert!(cache.exists("test_key").await.unwrap());
+        cache.delete("test_key").await.unwrap();
+        assert!(!cache.exists("test_key").await.unwrap());
+
+        cache.set_with_tags("user:42:profile", &1, ttl, &["user:42"]).await.unwrap();
+        cache.set_with_tags("user:42:orders", &2, ttl, &["user:42"]).await.unwrap();
+        assert_eq!(cache.invalidate_tag("user:42").await.unwrap(), 2);
+        assert!(!cache.exists("user:42:orders").await.unwrap());
+
+        cache.set("session:a", &1, ttl).await.unwrap();
+        cache.set("sessions", &2, ttl).await.unwrap();
+        assert_eq!(cache.delete_prefix("session:").await.unwrap(), 1);
+        assert!(cache.stats().await.unwrap().entries >= 1);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_memcached_lock() {
+        let cache = MemcachedCache::new(&["127.0.0.1:11211"], CacheConfig::default())
+            .await
+            .unwrap();
+        let ttl = Duration::from_secs(5);
+
+        let lock = cache.try_lock("test_lock", ttl).await.unwrap().unwrap();
+        assert!(cache.try_lock("test_lock", ttl).await.unwrap().is_none());
+        assert!(lock.extend(ttl).await.unwrap());
+        assert!(lock.release().await.unwrap());
+        assert!(cache.try_lock("test_lock", ttl).await.unwrap().unwrap().release().await.unwrap());
+    }
+}
@@ -1,43 +1,100 @@
 //! In-memory cache implementation using Moka
 
+use chrono::{DateTime, Utc};
 use moka::future::Cache as MokaCache;
+use moka::Expiry;
 use serde::{de::DeserializeOwned, Serialize};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use super::lock::{try_lock_memory, CacheLock, LockTable};
+use super::serializer::Codec;
 use super::{CacheConfig, CacheStats};
+use crate::clock::SharedClock;
 use crate::error::ApiError;
 
+#[derive(Clone)]
+struct Entry {
+    bytes: Vec<u8>,
+    expires_at: DateTime<Utc>,
+    ttl: Duration,
+}
+
+/// Lets moka drop each entry once its own TTL has passed
+struct EntryExpiry;
+
+impl Expiry<String, Entry> for EntryExpiry {
+    fn expire_after_create(&self, _key: &String, entry: &Entry, _created_at: Instant) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        entry: &Entry,
+        _updated_at: Instant,
+        _remaining: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+}
+
+/// In-memory cache
+///
+/// Entries expire after the TTL passed to `set`, measured on the configured
+/// clock; moka frees them once that TTL has passed in real time.
 pub struct MemoryCache {
-    cache: MokaCache<String, Vec<u8>>,
+    cache: MokaCache<String, Entry>,
+    clock: SharedClock,
+    codec: Codec,
+    /// Keys stored under each tag
+    tags: Mutex<HashMap<String, HashSet<String>>>,
+    locks: LockTable,
     hits: Arc<AtomicU64>,
     misses: Arc<AtomicU64>,
 }
 
 impl MemoryCache {
     pub fn new(config: CacheConfig) -> Self {
-        let cache = MokaCache::builder()
+        let builder = MokaCache::builder()
             .max_capacity(config.max_entries)
-            .time_to_live(Duration::from_secs(config.default_ttl_seconds))
-            .build();
+            .expire_after(EntryExpiry);
+        #[cfg(feature = "observability")]
+        let builder = builder.eviction_listener(|_, _, cause| match cause {
+            moka::notification::RemovalCause::Expired => super::metrics::record_eviction("memory", "expired"),
+            moka::notification::RemovalCause::Size => super::metrics::record_eviction("memory", "size"),
+            _ => {}
+        });
+        let cache = builder.build();
         
         Self {
             cache,
+            codec: Codec::new(&config),
+            clock: config.clock,
+            tags: Mutex::new(HashMap::new()),
+            locks: LockTable::default(),
             hits: Arc::new(AtomicU64::new(0)),
             misses: Arc::new(AtomicU64::new(0)),
         }
     }
     
+    /// Live entry for `key`, dropping it if it has expired
+    async fn entry(&self, key: &str) -> Option<Entry> {
+        let entry = self.cache.get(key).await?;
+        if entry.expires_at <= self.clock.now() {
+            self.cache.invalidate(key).await;
+            return None;
+        }
+        Some(entry)
+    }
+    
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, ApiError> {
-        match self.cache.get(key).await {
-            Some(bytes) => {
+        match self.entry(key).await {
+            Some(entry) => {
                 self.hits.fetch_add(1, Ordering::Relaxed);
-                let value = serde_json::from_slice(&bytes)
-                    .map_err(|e| ApiError::InternalServerError(
-                        format!("Cache deserialization error: {}", e)
-                    ))?;
-                Ok(Some(value))
+                Ok(Some(self.codec.decode(&entry.bytes)?))
             }
             None => {
                 self.misses.fetch_add(1, Ordering::Relaxed);
@@ -50,28 +107,82 @@ impl MemoryCache {
         &self,
         key: &str,
         value: &T,
-        _ttl: Duration,
+        ttl: Duration,
+    ) -> Result<(), ApiError> {
+        self.store(key, self.encode(value)?, ttl, &[]).await
+    }
+    
+    pub async fn set_with_tags<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
     ) -> Result<(), ApiError> {
-        let bytes = serde_json::to_vec(value)
-            .map_err(|e| ApiError::InternalServerError(
-                format!("Cache serialization error: {}", e)
-            ))?;
+        self.store(key, self.encode(value)?, ttl, tags).await
+    }
+    
+    pub(crate) fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        self.codec.encode(value)
+    }
+    
+    /// Store an entry already [encoded](Self::encode)
+    pub(crate) async fn store(&self, key: &str, bytes: Vec<u8>, ttl: Duration, tags: &[&str]) -> Result<(), ApiError> {
+        let expires_at = chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| self.clock.now().checked_add_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
         
-        self.cache.insert(key.to_string(), bytes).await;
+        self.cache.insert(key.to_string(), Entry { bytes, expires_at, ttl }).await;
+        #[cfg(feature = "observability")]
+        super::metrics::record_entries("memory", self.cache.entry_count());
+        if !tags.is_empty() {
+            let mut index = self.tags.lock().unwrap();
+            for tag in tags {
+                index.entry(tag.to_string()).or_default().insert(key.to_string());
+            }
+        }
         Ok(())
     }
     
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<u64, ApiError> {
+        let keys = self.tags.lock().unwrap().remove(tag).unwrap_or_default();
+        for key in &keys {
+            self.cache.invalidate(key).await;
+        }
+        Ok(keys.len() as u64)
+    }
+    
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<u64, ApiError> {
+        let keys: Vec<Arc<String>> = self
+            .cache
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| key.starts_with(prefix))
+            .collect();
+        for key in &keys {
+            self.cache.invalidate(key.as_str()).await;
+        }
+        Ok(keys.len() as u64)
+    }
+    
     pub async fn delete(&self, key: &str) -> Result<(), ApiError> {
         self.cache.invalidate(key).await;
         Ok(())
     }
     
+    /// Lock `key` for this process unless it is already locked
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> Result<Option<CacheLock>, ApiError> {
+        Ok(try_lock_memory(&self.locks, &self.clock, key, ttl))
+    }
+    
     pub async fn exists(&self, key: &str) -> Result<bool, ApiError> {
-        Ok(self.cache.get(key).await.is_some())
+        Ok(self.entry(key).await.is_some())
     }
     
     pub async fn clear(&self) -> Result<(), ApiError> {
         self.cache.invalidate_all();
+        self.tags.lock().unwrap().clear();
         Ok(())
     }
     
@@ -85,6 +196,8 @@ impl MemoryCache {
             0.0
         };
         
+        // Settle pending inserts and evictions so the count is current
+        self.cache.run_pending_tasks().await;
         Ok(CacheStats {
             hits,
             misses,
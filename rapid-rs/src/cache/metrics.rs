@@ -0,0 +1,90 @@
+//! Prometheus metrics for caches
+//!
+//! With `observability` on, every [`Cache`](super::Cache) reports:
+//!
+//! - `cache_hits_total` and `cache_misses_total`
+//! - `cache_operation_duration_seconds` per operation, and
+//!   `cache_errors_total` for failed ones
+//! - `cache_evictions_total` by cause (memory backend)
+//! - `cache_entries`, refreshed by writes to the memory backend and by
+//!   [`stats`](super::Cache::stats)
+//!
+//! All are labelled with the `backend`, and those about keys with the
+//! [`namespace`](super::Cache::namespace) they were used through (empty for
+//! the cache itself). Each namespace adds its own series, so keep them to a
+//! bounded set.
+
+use std::time::Duration;
+
+use crate::metrics::{record_counter, record_gauge, record_histogram};
+
+fn labels(backend: &'static str, namespace: Option<&str>) -> Vec<(&'static str, String)> {
+    vec![("backend", backend.to_string()), ("namespace", namespace.unwrap_or_default().to_string())]
+}
+
+pub(crate) fn record_lookup(backend: &'static str, namespace: Option<&str>, hit: bool) {
+    let name = if hit { "cache_hits_total" } else { "cache_misses_total" };
+    record_counter(name, 1, &labels(backend, namespace));
+}
+
+pub(crate) fn record_operation(
+    backend: &'static str,
+    namespace: Option<&str>,
+    operation: &'static str,
+    duration: Duration,
+    ok: bool,
+) {
+    let mut labels = labels(backend, namespace);
+    labels.push(("operation", operation.to_string()));
+    if !ok {
+        record_counter("cache_errors_total", 1, &labels);
+    }
+    record_histogram("cache_operation_duration_seconds", duration.as_secs_f64(), &labels);
+}
+
+pub(crate) fn record_eviction(backend: &'static str, cause: &'static str) {
+    record_counter(
+        "cache_evictions_total",
+        1,
+        &[("backend", backend.to_string()), ("cause", cause.to_string())],
+    );
+}
+
+pub(crate) fn record_entries(backend: &'static str, entries: u64) {
+    record_gauge("cache_entries", entries as f64, &[("backend", backend.to_string())]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Cache, CacheConfig};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cache_metrics() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let cache = Arc::new(Cache::new(CacheConfig::default()));
+                let acme = cache.namespace("tenant:acme");
+                let ttl = Duration::from_secs(60);
+
+                cache.set("a", &1, ttl).await.unwrap();
+                let _: Option<u32> = cache.get("a").await.unwrap();
+                acme.set("b", &2, ttl).await.unwrap();
+                let _: Option<u32> = acme.get("missing").await.unwrap();
+                cache.stats().await.unwrap();
+            })
+        });
+
+        let output = handle.render();
+        assert!(output.contains(r#"cache_hits_total{backend="memory",namespace=""} 1"#));
+        assert!(output.contains(r#"cache_misses_total{backend="memory",namespace="tenant:acme"} 1"#));
+        assert!(output.contains(r#"cache_operation_duration_seconds_count{backend="memory",namespace="tenant:acme",operation="set"} 1"#));
+        assert!(output.contains(r#"cache_entries{backend="memory"} 2"#));
+    }
+}
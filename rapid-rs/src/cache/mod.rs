@@ -1,25 +1,52 @@
 //! Caching layer with multiple backends
 
+pub mod degrade;
+pub mod http;
+pub mod lock;
 pub mod memory;
+#[cfg(feature = "observability")]
+mod metrics;
+pub mod namespace;
+pub mod serializer;
 
 #[cfg(feature = "cache-redis")]
 pub mod redis;
 
+#[cfg(feature = "cache-memcached")]
+pub mod memcached;
+
 use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::clock::SharedClock;
 use crate::error::ApiError;
+use crate::replication::{replicate, ReplicationMode};
 
+pub use degrade::{Degrade, DegradePath, DegradeStats};
+pub use http::{http_cache_middleware, HttpCache};
+pub use lock::CacheLock;
 pub use memory::MemoryCache;
+pub use namespace::CacheNamespace;
+pub use serializer::{CacheFormat, CacheSerializer};
 
 #[cfg(feature = "cache-redis")]
 pub use redis::RedisCache;
 
+#[cfg(feature = "cache-memcached")]
+pub use memcached::MemcachedCache;
+
 /// Cache configuration
 #[derive(Debug, Clone)]
 pub struct CacheConfig {
     pub default_ttl_seconds: u64,
     pub max_entries: u64,
+    /// Time source for entry expiry in the memory backend
+    pub clock: SharedClock,
+    /// How values are serialized
+    pub format: CacheFormat,
+    /// Compress entries larger than this many bytes
+    pub compress_above: Option<usize>,
 }
 
 impl Default for CacheConfig {
@@ -27,6 +54,9 @@ impl Default for CacheConfig {
         Self {
             default_ttl_seconds: 300,
             max_entries: 10_000,
+            clock: crate::clock::system(),
+            format: CacheFormat::default(),
+            compress_above: None,
         }
     }
 }
@@ -45,6 +75,24 @@ impl CacheConfig {
         self.max_entries = max;
         self
     }
+    
+    /// Expire memory entries against `clock`; Redis expiry stays server-side
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Serialize values as `format` instead of JSON, see [`serializer`]
+    pub fn with_format(mut self, format: CacheFormat) -> Self {
+        self.format = format;
+        self
+    }
+    
+    /// Compress entries whose serialized size exceeds `bytes`
+    pub fn with_compression(mut self, bytes: usize) -> Self {
+        self.compress_above = Some(bytes);
+        self
+    }
 }
 
 /// Cache statistics
@@ -71,14 +119,29 @@ pub enum CacheBackend {
     Memory(MemoryCache),
     #[cfg(feature = "cache-redis")]
     Redis(RedisCache),
+    #[cfg(feature = "cache-memcached")]
+    Memcached(MemcachedCache),
 }
 
 impl CacheBackend {
+    /// `backend` label of the cache metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            CacheBackend::Memory(_) => "memory",
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(_) => "redis",
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(_) => "memcached",
+        }
+    }
+    
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, ApiError> {
         match self {
             CacheBackend::Memory(cache) => cache.get(key).await,
             #[cfg(feature = "cache-redis")]
             CacheBackend::Redis(cache) => cache.get(key).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.get(key).await,
         }
     }
     
@@ -92,6 +155,8 @@ impl CacheBackend {
             CacheBackend::Memory(cache) => cache.set(key, value, ttl).await,
             #[cfg(feature = "cache-redis")]
             CacheBackend::Redis(cache) => cache.set(key, value, ttl).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.set(key, value, ttl).await,
         }
     }
     
@@ -100,6 +165,74 @@ impl CacheBackend {
             CacheBackend::Memory(cache) => cache.delete(key).await,
             #[cfg(feature = "cache-redis")]
             CacheBackend::Redis(cache) => cache.delete(key).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.delete(key).await,
+        }
+    }
+    
+    pub async fn set_with_tags<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> Result<(), ApiError> {
+        match self {
+            CacheBackend::Memory(cache) => cache.set_with_tags(key, value, ttl, tags).await,
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(cache) => cache.set_with_tags(key, value, ttl, tags).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.set_with_tags(key, value, ttl, tags).await,
+        }
+    }
+    
+    pub(crate) fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        match self {
+            CacheBackend::Memory(cache) => cache.encode(value),
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(cache) => cache.encode(value),
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.encode(value),
+        }
+    }
+    
+    pub(crate) async fn store(&self, key: &str, bytes: Vec<u8>, ttl: Duration, tags: &[&str]) -> Result<(), ApiError> {
+        match self {
+            CacheBackend::Memory(cache) => cache.store(key, bytes, ttl, tags).await,
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(cache) => cache.store(key, bytes, ttl, tags).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.store(key, bytes, ttl, tags).await,
+        }
+    }
+    
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<u64, ApiError> {
+        match self {
+            CacheBackend::Memory(cache) => cache.invalidate_tag(tag).await,
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(cache) => cache.invalidate_tag(tag).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.invalidate_tag(tag).await,
+        }
+    }
+    
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<u64, ApiError> {
+        match self {
+            CacheBackend::Memory(cache) => cache.delete_prefix(prefix).await,
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(cache) => cache.delete_prefix(prefix).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.delete_prefix(prefix).await,
+        }
+    }
+    
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> Result<Option<CacheLock>, ApiError> {
+        match self {
+            CacheBackend::Memory(cache) => cache.try_lock(key, ttl).await,
+            #[cfg(feature = "cache-redis")]
+            CacheBackend::Redis(cache) => cache.try_lock(key, ttl).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.try_lock(key, ttl).await,
         }
     }
     
@@ -108,6 +241,8 @@ impl CacheBackend {
             CacheBackend::Memory(cache) => cache.exists(key).await,
             #[cfg(feature = "cache-redis")]
             CacheBackend::Redis(cache) => cache.exists(key).await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.exists(key).await,
         }
     }
     
@@ -116,6 +251,8 @@ impl CacheBackend {
             CacheBackend::Memory(cache) => cache.clear().await,
             #[cfg(feature = "cache-redis")]
             CacheBackend::Redis(cache) => cache.clear().await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.clear().await,
         }
     }
     
@@ -124,6 +261,8 @@ impl CacheBackend {
             CacheBackend::Memory(cache) => cache.stats().await,
             #[cfg(feature = "cache-redis")]
             CacheBackend::Redis(cache) => cache.stats().await,
+            #[cfg(feature = "cache-memcached")]
+            CacheBackend::Memcached(cache) => cache.stats().await,
         }
     }
 }
@@ -131,18 +270,27 @@ impl CacheBackend {
 /// Main cache interface
 pub struct Cache {
     backend: CacheBackend,
+    replica: Option<(Arc<Cache>, ReplicationMode)>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<crate::chaos::Chaos>>,
 }
 
 impl Cache {
     pub fn new(config: CacheConfig) -> Self {
         Self {
             backend: CacheBackend::Memory(MemoryCache::new(config)),
+            replica: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
         }
     }
     
     pub fn with_memory(config: CacheConfig) -> Self {
         Self {
             backend: CacheBackend::Memory(MemoryCache::new(config)),
+            replica: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
         }
     }
     
@@ -150,11 +298,79 @@ impl Cache {
     pub async fn with_redis(redis_url: &str, config: CacheConfig) -> Result<Self, ApiError> {
         Ok(Self {
             backend: CacheBackend::Redis(RedisCache::new(redis_url, config).await?),
+            replica: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        })
+    }
+    
+    /// Memcached at `servers` (`host:port`), see [`memcached`]
+    #[cfg(feature = "cache-memcached")]
+    pub async fn with_memcached(servers: &[impl AsRef<str>], config: CacheConfig) -> Result<Self, ApiError> {
+        Ok(Self {
+            backend: CacheBackend::Memcached(MemcachedCache::new(servers, config).await?),
+            replica: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
         })
     }
     
+    /// Write through to `replica`, typically a Redis in another region
+    ///
+    /// Writes and invalidations are repeated on the replica as `mode` says,
+    /// and reads fall back to it while this cache errors. See
+    /// [`replication`](crate::replication).
+    pub fn with_replica(mut self, replica: Cache, mode: ReplicationMode) -> Self {
+        self.replica = Some((Arc::new(replica), mode));
+        self
+    }
+    
+    /// Inject the cache faults of `chaos` into every operation, see [`chaos`](crate::chaos)
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, chaos: Arc<crate::chaos::Chaos>) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+    
+    /// Time `operation` for the cache metrics, labelled with `namespace`
+    #[cfg_attr(not(feature = "observability"), allow(unused_variables))]
+    async fn observe<T>(
+        &self,
+        operation: &'static str,
+        namespace: Option<&str>,
+        run: impl std::future::Future<Output = Result<T, ApiError>>,
+    ) -> Result<T, ApiError> {
+        #[cfg(feature = "observability")]
+        let started = std::time::Instant::now();
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &self.chaos {
+            chaos.inject(crate::chaos::ChaosTarget::Cache).await?;
+        }
+        let result = run.await;
+        #[cfg(feature = "observability")]
+        metrics::record_operation(self.backend.name(), namespace, operation, started.elapsed(), result.is_ok());
+        result
+    }
+    
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, ApiError> {
-        self.backend.get(key).await
+        self.get_in(None, key).await
+    }
+    
+    pub(crate) async fn get_in<T: DeserializeOwned>(&self, namespace: Option<&str>, key: &str) -> Result<Option<T>, ApiError> {
+        let value = self
+            .observe("get", namespace, async {
+                match (self.backend.get(key).await, &self.replica) {
+                    (Err(error), Some((replica, _))) => {
+                        tracing::warn!(key, error = %error, "Cache failed, reading the replica");
+                        replica.backend.get(key).await
+                    }
+                    (result, _) => result,
+                }
+            })
+            .await?;
+        #[cfg(feature = "observability")]
+        metrics::record_lookup(self.backend.name(), namespace, value.is_some());
+        Ok(value)
     }
     
     pub async fn set<T: Serialize + Send + Sync>(
@@ -163,23 +379,149 @@ impl Cache {
         value: &T,
         ttl: Duration,
     ) -> Result<(), ApiError> {
-        self.backend.set(key, value, ttl).await
+        self.set_in(None, key, value, ttl).await
+    }
+    
+    pub(crate) async fn set_in<T: Serialize + Send + Sync>(
+        &self,
+        namespace: Option<&str>,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), ApiError> {
+        self.observe("set", namespace, self.backend.set(key, value, ttl)).await?;
+        if let Some((replica, mode)) = &self.replica {
+            let (bytes, replica, key) = (replica.backend.encode(value)?, replica.clone(), key.to_string());
+            replicate(*mode, "cache", "set", async move { replica.backend.store(&key, bytes, ttl, &[]).await }).await;
+        }
+        Ok(())
     }
     
     pub async fn delete(&self, key: &str) -> Result<(), ApiError> {
-        self.backend.delete(key).await
+        self.delete_in(None, key).await
+    }
+    
+    pub(crate) async fn delete_in(&self, namespace: Option<&str>, key: &str) -> Result<(), ApiError> {
+        self.observe("delete", namespace, self.backend.delete(key)).await?;
+        if let Some((replica, mode)) = &self.replica {
+            let (replica, key) = (replica.clone(), key.to_string());
+            replicate(*mode, "cache", "delete", async move { replica.backend.delete(&key).await }).await;
+        }
+        Ok(())
+    }
+    
+    /// Store `value` under `key`, to be dropped along with everything tagged
+    /// the same by [`invalidate_tag`](Self::invalidate_tag)
+    pub async fn set_with_tags<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> Result<(), ApiError> {
+        self.set_with_tags_in(None, key, value, ttl, tags).await
+    }
+    
+    pub(crate) async fn set_with_tags_in<T: Serialize + Send + Sync>(
+        &self,
+        namespace: Option<&str>,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> Result<(), ApiError> {
+        self.observe("set_with_tags", namespace, self.backend.set_with_tags(key, value, ttl, tags)).await?;
+        if let Some((replica, mode)) = &self.replica {
+            let (bytes, replica, key) = (replica.backend.encode(value)?, replica.clone(), key.to_string());
+            let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+            replicate(*mode, "cache", "set_with_tags", async move {
+                let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+                replica.backend.store(&key, bytes, ttl, &tags).await
+            })
+            .await;
+        }
+        Ok(())
+    }
+    
+    /// Delete every key stored with `tag`, returning how many there were
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<u64, ApiError> {
+        self.invalidate_tag_in(None, tag).await
+    }
+    
+    pub(crate) async fn invalidate_tag_in(&self, namespace: Option<&str>, tag: &str) -> Result<u64, ApiError> {
+        let deleted = self.observe("invalidate_tag", namespace, self.backend.invalidate_tag(tag)).await?;
+        if let Some((replica, mode)) = &self.replica {
+            let (replica, tag) = (replica.clone(), tag.to_string());
+            replicate(*mode, "cache", "invalidate_tag", async move {
+                replica.backend.invalidate_tag(&tag).await.map(|_| ())
+            })
+            .await;
+        }
+        Ok(deleted)
+    }
+    
+    /// Delete every key starting with `prefix`, returning how many there were
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<u64, ApiError> {
+        self.delete_prefix_in(None, prefix).await
+    }
+    
+    pub(crate) async fn delete_prefix_in(&self, namespace: Option<&str>, prefix: &str) -> Result<u64, ApiError> {
+        let deleted = self.observe("delete_prefix", namespace, self.backend.delete_prefix(prefix)).await?;
+        if let Some((replica, mode)) = &self.replica {
+            let (replica, prefix) = (replica.clone(), prefix.to_string());
+            replicate(*mode, "cache", "delete_prefix", async move {
+                replica.backend.delete_prefix(&prefix).await.map(|_| ())
+            })
+            .await;
+        }
+        Ok(deleted)
     }
     
     pub async fn exists(&self, key: &str) -> Result<bool, ApiError> {
-        self.backend.exists(key).await
+        self.exists_in(None, key).await
+    }
+    
+    pub(crate) async fn exists_in(&self, namespace: Option<&str>, key: &str) -> Result<bool, ApiError> {
+        self.observe("exists", namespace, self.backend.exists(key)).await
+    }
+    
+    /// Lock `key` for `ttl` unless it is already locked, see [`lock`](self::lock)
+    ///
+    /// Locks are taken on this cache only, never on its replica.
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> Result<Option<CacheLock>, ApiError> {
+        self.backend.try_lock(key, ttl).await
     }
     
+    /// Lock `key` for `ttl`, waiting for the current holder to release it
+    /// or let it expire
+    ///
+    /// Waits as long as it takes; wrap in `tokio::time::timeout` to give up.
+    pub async fn lock(&self, key: &str, ttl: Duration) -> Result<CacheLock, ApiError> {
+        let mut delay = Duration::from_millis(10);
+        loop {
+            if let Some(lock) = self.backend.try_lock(key, ttl).await? {
+                return Ok(lock);
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(lock::MAX_RETRY_DELAY);
+        }
+    }
+    
+    /// Drop every entry; to drop one tenant's, clear its [`namespace`](Self::namespace)
     pub async fn clear(&self) -> Result<(), ApiError> {
-        self.backend.clear().await
+        self.observe("clear", None, self.backend.clear()).await?;
+        if let Some((replica, mode)) = &self.replica {
+            let replica = replica.clone();
+            replicate(*mode, "cache", "clear", async move { replica.backend.clear().await }).await;
+        }
+        Ok(())
     }
     
     pub async fn stats(&self) -> Result<CacheStats, ApiError> {
-        self.backend.stats().await
+        let stats = self.backend.stats().await?;
+        #[cfg(feature = "observability")]
+        metrics::record_entries(self.backend.name(), stats.entries);
+        Ok(stats)
     }
     
     pub async fn get_or_compute<T, F, Fut>(
@@ -193,12 +535,27 @@ impl Cache {
         F: FnOnce() -> Fut,
         Fut: std::future::Future<Output = Result<T, ApiError>>,
     {
-        if let Some(value) = self.get(key).await? {
+        self.get_or_compute_in(None, key, ttl, compute).await
+    }
+    
+    pub(crate) async fn get_or_compute_in<T, F, Fut>(
+        &self,
+        namespace: Option<&str>,
+        key: &str,
+        ttl: Duration,
+        compute: F,
+    ) -> Result<T, ApiError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ApiError>>,
+    {
+        if let Some(value) = self.get_in(namespace, key).await? {
             return Ok(value);
         }
         
         let value = compute().await?;
-        self.set(key, &value, ttl).await?;
+        self.set_in(namespace, key, &value, ttl).await?;
         Ok(value)
     }
 }
@@ -250,4 +607,58 @@ mod tests {
         assert_eq!(stats.misses, 1);
         assert_eq!(stats.total_requests(), 2);
     }
+    
+    #[tokio::test]
+    async fn test_tag_and_prefix_invalidation() {
+        let cache = Cache::new(CacheConfig::default());
+        let ttl = Duration::from_secs(60);
+        
+        cache.set_with_tags("user:42:profile", &"alice", ttl, &["user:42"]).await.unwrap();
+        cache.set_with_tags("user:42:orders", &"[]", ttl, &["user:42", "orders"]).await.unwrap();
+        cache.set_with_tags("user:7:orders", &"[]", ttl, &["orders"]).await.unwrap();
+        
+        assert_eq!(cache.invalidate_tag("user:42").await.unwrap(), 2);
+        assert!(!cache.exists("user:42:profile").await.unwrap());
+        assert!(cache.exists("user:7:orders").await.unwrap());
+        assert_eq!(cache.invalidate_tag("user:42").await.unwrap(), 0);
+        
+        cache.set("session:a", &1, ttl).await.unwrap();
+        cache.set("session:b", &2, ttl).await.unwrap();
+        cache.set("sessions", &3, ttl).await.unwrap();
+        assert_eq!(cache.delete_prefix("session:").await.unwrap(), 2);
+        assert!(cache.exists("sessions").await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_replica_write_through() {
+        let cache = Cache::new(CacheConfig::default())
+            .with_replica(Cache::new(CacheConfig::default()), ReplicationMode::WriteThrough);
+        let replica_backend = &cache.replica.as_ref().unwrap().0.backend;
+        let ttl = Duration::from_secs(60);
+        
+        cache.set("user:1", &"alice", ttl).await.unwrap();
+        cache.set_with_tags("user:2", &"bob", ttl, &["users"]).await.unwrap();
+        assert_eq!(replica_backend.get::<String>("user:1").await.unwrap().as_deref(), Some("alice"));
+        
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 1);
+        cache.delete("user:1").await.unwrap();
+        assert!(!replica_backend.exists("user:1").await.unwrap());
+        assert!(!replica_backend.exists("user:2").await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_cache_expiry_follows_clock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let cache = Cache::new(CacheConfig::default().with_clock(clock.shared()));
+        
+        cache.set("key", &"value", Duration::from_secs(60)).await.unwrap();
+        
+        clock.advance(Duration::from_secs(59));
+        assert!(cache.exists("key").await.unwrap());
+        
+        clock.advance(Duration::from_secs(1));
+        assert!(!cache.exists("key").await.unwrap());
+        let value: Option<String> = cache.get("key").await.unwrap();
+        assert_eq!(value, None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,140 @@
+//! Cache handles scoped to a key namespace
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Cache, CacheLock};
+use crate::error::ApiError;
+
+/// A view of a [`Cache`] whose keys and tags all live under one namespace
+///
+/// Cloning is cheap. [`clear`](Self::clear) only drops this namespace, so one
+/// tenant's data can be flushed without touching the others:
+///
+/// ```rust,ignore
+/// let cache = Arc::new(Cache::with_redis(&redis_url, CacheConfig::default()).await?);
+/// let acme = cache.namespace("tenant:acme");
+/// acme.set("plans", &plans, ttl).await?; // stored as "tenant:acme:plans"
+/// acme.clear().await?;
+/// ```
+#[derive(Clone)]
+pub struct CacheNamespace {
+    cache: Arc<Cache>,
+    prefix: String,
+}
+
+impl Cache {
+    /// Handle storing every key under `name`
+    pub fn namespace(self: &Arc<Self>, name: impl Into<String>) -> CacheNamespace {
+        CacheNamespace {
+            cache: self.clone(),
+            prefix: format!("{}:", name.into()),
+        }
+    }
+}
+
+impl CacheNamespace {
+    /// Namespace nested in this one, e.g. `tenant:acme:users`
+    pub fn namespace(&self, name: impl AsRef<str>) -> CacheNamespace {
+        CacheNamespace {
+            cache: self.cache.clone(),
+            prefix: format!("{}{}:", self.prefix, name.as_ref()),
+        }
+    }
+
+    /// The namespace, without the trailing separator
+    pub fn name(&self) -> &str {
+        self.prefix.trim_end_matches(':')
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, ApiError> {
+        self.cache.get_in(Some(self.name()), &self.key(key)).await
+    }
+
+    pub async fn set<T: Serialize + Send + Sync>(&self, key: &str, value: &T, ttl: Duration) -> Result<(), ApiError> {
+        self.cache.set_in(Some(self.name()), &self.key(key), value, ttl).await
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), ApiError> {
+        self.cache.delete_in(Some(self.name()), &self.key(key)).await
+    }
+
+    /// Tags are namespaced too, so invalidating one leaves other namespaces alone
+    pub async fn set_with_tags<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> Result<(), ApiError> {
+        let tags: Vec<String> = tags.iter().map(|tag| self.key(tag)).collect();
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        self.cache.set_with_tags_in(Some(self.name()), &self.key(key), value, ttl, &tags).await
+    }
+
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<u64, ApiError> {
+        self.cache.invalidate_tag_in(Some(self.name()), &self.key(tag)).await
+    }
+
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<u64, ApiError> {
+        self.cache.delete_prefix_in(Some(self.name()), &self.key(prefix)).await
+    }
+
+    pub async fn exists(&self, key: &str) -> Result<bool, ApiError> {
+        self.cache.exists_in(Some(self.name()), &self.key(key)).await
+    }
+
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> Result<Option<CacheLock>, ApiError> {
+        self.cache.try_lock(&self.key(key), ttl).await
+    }
+
+    pub async fn lock(&self, key: &str, ttl: Duration) -> Result<CacheLock, ApiError> {
+        self.cache.lock(&self.key(key), ttl).await
+    }
+
+    /// Delete every key in this namespace, returning how many there were
+    pub async fn clear(&self) -> Result<u64, ApiError> {
+        self.cache.delete_prefix_in(Some(self.name()), &self.prefix).await
+    }
+
+    pub async fn get_or_compute<T, F, Fut>(&self, key: &str, ttl: Duration, compute: F) -> Result<T, ApiError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ApiError>>,
+    {
+        self.cache.get_or_compute_in(Some(self.name()), &self.key(key), ttl, compute).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated() {
+        let cache = Arc::new(Cache::new(CacheConfig::default()));
+        let (acme, globex) = (cache.namespace("tenant:acme"), cache.namespace("tenant:globex"));
+        let ttl = Duration::from_secs(60);
+
+        acme.set("plans", &"pro", ttl).await.unwrap();
+        globex.set("plans", &"free", ttl).await.unwrap();
+        acme.namespace("users").set_with_tags("1", &"alice", ttl, &["users"]).await.unwrap();
+        globex.set_with_tags("users:1", &"bob", ttl, &["users"]).await.unwrap();
+        assert_eq!(cache.get::<String>("tenant:acme:plans").await.unwrap().as_deref(), Some("pro"));
+        assert_eq!(acme.namespace("users").name(), "tenant:acme:users");
+
+        assert_eq!(acme.namespace("users").invalidate_tag("users").await.unwrap(), 1);
+        assert!(globex.exists("users:1").await.unwrap());
+
+        assert_eq!(acme.clear().await.unwrap(), 1);
+        assert!(!acme.exists("plans").await.unwrap());
+        assert_eq!(globex.get::<String>("plans").await.unwrap().as_deref(), Some("free"));
+    }
+}
@@ -11,23 +11,51 @@ use std::sync::atomic::{AtomicU64, Ordering};
 #[cfg(feature = "cache-redis")]
 use std::time::Duration;
 
+#[cfg(feature = "cache-redis")]
+use super::lock::{lock_key, CacheLock, Holder};
+#[cfg(feature = "cache-redis")]
+use super::serializer::Codec;
 #[cfg(feature = "cache-redis")]
 use super::{CacheConfig, CacheStats};
 #[cfg(feature = "cache-redis")]
 use crate::error::ApiError;
 
+/// Keys deleted per `DEL` command
+#[cfg(feature = "cache-redis")]
+const DELETE_BATCH: usize = 500;
+
+/// Set holding the keys stored under `tag`
+#[cfg(feature = "cache-redis")]
+fn tag_key(tag: &str) -> String {
+    format!("__tag:{}", tag)
+}
+
+/// `prefix` with `SCAN MATCH` wildcards taken literally
+#[cfg(feature = "cache-redis")]
+fn escape_glob(prefix: &str) -> String {
+    let mut escaped = String::with_capacity(prefix.len());
+    for c in prefix.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 /// Redis cache backend
 #[cfg(feature = "cache-redis")]
 pub struct RedisCache {
     client: redis::Client,
     connection_manager: Arc<tokio::sync::Mutex<redis::aio::ConnectionManager>>,
+    codec: Codec,
     hits: Arc<AtomicU64>,
     misses: Arc<AtomicU64>,
 }
 
 #[cfg(feature = "cache-redis")]
 impl RedisCache {
-    pub async fn new(redis_url: &str, _config: CacheConfig) -> Result<Self, ApiError> {
+    pub async fn new(redis_url: &str, config: CacheConfig) -> Result<Self, ApiError> {
         let client = redis::Client::open(redis_url)
             .map_err(|e| ApiError::InternalServerError(format!("Failed to create Redis client: {}", e)))?;
         
@@ -38,6 +66,7 @@ impl RedisCache {
         Ok(Self {
             client,
             connection_manager: Arc::new(tokio::sync::Mutex::new(connection_manager)),
+            codec: Codec::new(&config),
             hits: Arc::new(AtomicU64::new(0)),
             misses: Arc::new(AtomicU64::new(0)),
         })
@@ -54,12 +83,7 @@ impl RedisCache {
             Ok(Some(bytes)) => {
                 self.hits.fetch_add(1, Ordering::Relaxed);
                 
-                let value = serde_json::from_slice(&bytes)
-                    .map_err(|e| ApiError::InternalServerError(
-                        format!("Cache deserialization error: {}", e)
-                    ))?;
-                
-                Ok(Some(value))
+                Ok(Some(self.codec.decode(&bytes)?))
             }
             Ok(None) => {
                 self.misses.fetch_add(1, Ordering::Relaxed);
@@ -77,11 +101,25 @@ impl RedisCache {
         value: &T,
         ttl: Duration,
     ) -> Result<(), ApiError> {
-        let bytes = serde_json::to_vec(value)
-            .map_err(|e| ApiError::InternalServerError(
-                format!("Cache serialization error: {}", e)
-            ))?;
-        
+        self.store(key, self.encode(value)?, ttl, &[]).await
+    }
+    
+    pub async fn set_with_tags<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> Result<(), ApiError> {
+        self.store(key, self.encode(value)?, ttl, tags).await
+    }
+    
+    pub(crate) fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        self.codec.encode(value)
+    }
+    
+    /// Store an entry already [encoded](Self::encode)
+    pub(crate) async fn store(&self, key: &str, bytes: Vec<u8>, ttl: Duration, tags: &[&str]) -> Result<(), ApiError> {
         let mut conn = self.get_connection().await;
         
         // Fix: u64 not usize, and add type annotation
@@ -91,9 +129,67 @@ impl RedisCache {
                 format!("Redis set error: {}", e)
             ))?;
         
+        for tag in tags {
+            let tag_key = tag_key(tag);
+            // The set lives as long as its longest-lived key
+            let (_, remaining): ((), i64) = redis::pipe()
+                .sadd(&tag_key, key)
+                .ttl(&tag_key)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| ApiError::InternalServerError(format!("Redis tag error: {}", e)))?;
+            if remaining < 0 || (remaining as u64) < ttl.as_secs() {
+                conn.expire::<_, ()>(&tag_key, ttl.as_secs() as i64)
+                    .await
+                    .map_err(|e| ApiError::InternalServerError(format!("Redis tag error: {}", e)))?;
+            }
+        }
         Ok(())
     }
     
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<u64, ApiError> {
+        let mut conn = self.get_connection().await;
+        let tag_key = tag_key(tag);
+        
+        let keys: Vec<String> = conn
+            .smembers(&tag_key)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Redis tag error: {}", e)))?;
+        for chunk in keys.chunks(DELETE_BATCH) {
+            conn.del::<_, ()>(chunk)
+                .await
+                .map_err(|e| ApiError::InternalServerError(format!("Redis delete error: {}", e)))?;
+        }
+        conn.del::<_, ()>(&tag_key)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Redis delete error: {}", e)))?;
+        Ok(keys.len() as u64)
+    }
+    
+    /// Delete keys starting with `prefix`, found with `SCAN` so Redis isn't blocked
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<u64, ApiError> {
+        let mut conn = self.get_connection().await;
+        
+        let pattern = format!("{}*", escape_glob(prefix));
+        let keys: Vec<String> = {
+            let mut scan = conn
+                .scan_match::<_, String>(pattern)
+                .await
+                .map_err(|e| ApiError::InternalServerError(format!("Redis scan error: {}", e)))?;
+            let mut keys = Vec::new();
+            while let Some(key) = scan.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        for chunk in keys.chunks(DELETE_BATCH) {
+            conn.del::<_, ()>(chunk)
+                .await
+                .map_err(|e| ApiError::InternalServerError(format!("Redis delete error: {}", e)))?;
+        }
+        Ok(keys.len() as u64)
+    }
+    
     pub async fn delete(&self, key: &str) -> Result<(), ApiError> {
         let mut conn = self.get_connection().await;
         
@@ -106,6 +202,35 @@ impl RedisCache {
         Ok(())
     }
     
+    /// Lock `key` across every instance sharing this Redis, unless it is
+    /// already locked (`SET NX PX` with a random token)
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> Result<Option<CacheLock>, ApiError> {
+        let mut conn = self.get_connection().await;
+        let token = crate::ids::new_id();
+        
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(lock_key(key))
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Redis lock error: {}", e)))?;
+        Ok(acquired.map(|_| CacheLock::new(key, token, Holder::Redis(conn))))
+    }
+    
+    /// Round-trip a `PING` to the server
+    pub async fn ping(&self) -> Result<(), ApiError> {
+        let mut conn = self.get_connection().await;
+
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|e| ApiError::ServiceUnavailable(format!("Redis ping error: {}", e)))
+    }
+
     pub async fn exists(&self, key: &str) -> Result<bool, ApiError> {
         let mut conn = self.get_connection().await;
         
@@ -179,4 +304,49 @@ mod tests {
         let value: Option<String> = cache.get("test_key").await.unwrap();
         assert_eq!(value, None);
     }
+    
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_invalidation() {
+        let cache = RedisCache::new("redis://127.0.0.1/", CacheConfig::default())
+            .await
+            .unwrap();
+        let ttl = Duration::from_secs(60);
+        
+        cache.set_with_tags("user:42:profile", &1, ttl, &["user:42"]).await.unwrap();
+        cache.set_with_tags("user:42:orders", &2, ttl, &["user:42"]).await.unwrap();
+        assert_eq!(cache.invalidate_tag("user:42").await.unwrap(), 2);
+        assert!(!cache.exists("user:42:orders").await.unwrap());
+        
+        cache.set("session:a*", &1, ttl).await.unwrap();
+        cache.set("session:b", &2, ttl).await.unwrap();
+        assert_eq!(cache.delete_prefix("session:a*").await.unwrap(), 1);
+        assert!(cache.exists("session:b").await.unwrap());
+        assert_eq!(cache.delete_prefix("session:").await.unwrap(), 1);
+    }
+    
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_lock() {
+        let cache = RedisCache::new("redis://127.0.0.1/", CacheConfig::default())
+            .await
+            .unwrap();
+        let other = RedisCache::new("redis://127.0.0.1/", CacheConfig::default())
+            .await
+            .unwrap();
+        let ttl = Duration::from_secs(5);
+        
+        let lock = cache.try_lock("test_lock", ttl).await.unwrap().unwrap();
+        assert!(other.try_lock("test_lock", ttl).await.unwrap().is_none());
+        assert!(lock.extend(ttl).await.unwrap());
+        assert!(lock.release().await.unwrap());
+        
+        let lock = other.try_lock("test_lock", ttl).await.unwrap().unwrap();
+        assert!(lock.release().await.unwrap());
+    }
+    
+    #[test]
+    fn test_escape_glob() {
+        assert_eq!(escape_glob("session:[1]*?"), "session:\\[1\\]\\*\\?");
+    }
 }
\ No newline at end of file
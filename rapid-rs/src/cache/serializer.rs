@@ -0,0 +1,213 @@
+//! How cached values are encoded
+//!
+//! Values are JSON by default. For large structs, bincode (`cache-bincode`
+//! feature) or MessagePack (`cache-msgpack`) are faster and smaller, and
+//! compression shrinks big entries further:
+//!
+//! ```rust,ignore
+//! let config = CacheConfig::new()
+//!     .with_format(CacheFormat::MessagePack)
+//!     .with_compression(4 * 1024);
+//! ```
+//!
+//! Entries written in one format can't be read in another, so flush the
+//! cache (or change key prefixes) when switching. bincode is not
+//! self-describing: it can't hold `serde_json::Value`, untagged enums or
+//! fields skipped conditionally.
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Read, Write};
+
+use crate::error::ApiError;
+
+/// Turns values into cache entries and back
+pub trait CacheSerializer: Send + Sync {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError>;
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ApiError>;
+}
+
+fn serialization_error(e: impl std::fmt::Display) -> ApiError {
+    ApiError::InternalServerError(format!("Cache serialization error: {}", e))
+}
+
+fn deserialization_error(e: impl std::fmt::Display) -> ApiError {
+    ApiError::InternalServerError(format!("Cache deserialization error: {}", e))
+}
+
+pub struct Json;
+
+impl CacheSerializer for Json {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        serde_json::to_vec(value).map_err(serialization_error)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ApiError> {
+        serde_json::from_slice(bytes).map_err(deserialization_error)
+    }
+}
+
+#[cfg(feature = "cache-bincode")]
+pub struct Bincode;
+
+#[cfg(feature = "cache-bincode")]
+impl CacheSerializer for Bincode {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        bincode::serialize(value).map_err(serialization_error)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ApiError> {
+        bincode::deserialize(bytes).map_err(deserialization_error)
+    }
+}
+
+/// MessagePack with field names, so structs can gain optional fields
+#[cfg(feature = "cache-msgpack")]
+pub struct MessagePack;
+
+#[cfg(feature = "cache-msgpack")]
+impl CacheSerializer for MessagePack {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        rmp_serde::to_vec_named(value).map_err(serialization_error)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ApiError> {
+        rmp_serde::from_slice(bytes).map_err(deserialization_error)
+    }
+}
+
+/// The serializer a cache uses, set with [`CacheConfig::with_format`](super::CacheConfig::with_format)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheFormat {
+    #[default]
+    Json,
+    #[cfg(feature = "cache-bincode")]
+    Bincode,
+    #[cfg(feature = "cache-msgpack")]
+    MessagePack,
+}
+
+impl CacheSerializer for CacheFormat {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        match self {
+            CacheFormat::Json => Json.serialize(value),
+            #[cfg(feature = "cache-bincode")]
+            CacheFormat::Bincode => Bincode.serialize(value),
+            #[cfg(feature = "cache-msgpack")]
+            CacheFormat::MessagePack => MessagePack.serialize(value),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ApiError> {
+        match self {
+            CacheFormat::Json => Json.deserialize(bytes),
+            #[cfg(feature = "cache-bincode")]
+            CacheFormat::Bincode => Bincode.deserialize(bytes),
+            #[cfg(feature = "cache-msgpack")]
+            CacheFormat::MessagePack => MessagePack.deserialize(bytes),
+        }
+    }
+}
+
+/// First byte of entries when compression is on
+const UNCOMPRESSED: u8 = 0;
+const ZLIB: u8 = 1;
+
+/// Format plus optional compression, as configured
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Codec {
+    format: CacheFormat,
+    compress_above: Option<usize>,
+}
+
+impl Codec {
+    pub(crate) fn new(config: &super::CacheConfig) -> Self {
+        Self {
+            format: config.format,
+            compress_above: config.compress_above,
+        }
+    }
+
+    pub(crate) fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        let bytes = self.format.serialize(value)?;
+        // Without compression, entries are exactly the serialized value
+        let Some(threshold) = self.compress_above else {
+            return Ok(bytes);
+        };
+        if bytes.len() <= threshold {
+            let mut entry = Vec::with_capacity(bytes.len() + 1);
+            entry.push(UNCOMPRESSED);
+            entry.extend_from_slice(&bytes);
+            return Ok(entry);
+        }
+
+        let mut encoder = ZlibEncoder::new(vec![ZLIB], Compression::fast());
+        encoder.write_all(&bytes).map_err(serialization_error)?;
+        encoder.finish().map_err(serialization_error)
+    }
+
+    pub(crate) fn decode<T: DeserializeOwned>(&self, entry: &[u8]) -> Result<T, ApiError> {
+        if self.compress_above.is_none() {
+            return self.format.deserialize(entry);
+        }
+        match entry.split_first() {
+            Some((&UNCOMPRESSED, bytes)) => self.format.deserialize(bytes),
+            Some((&ZLIB, compressed)) => {
+                let mut bytes = Vec::new();
+                ZlibDecoder::new(compressed)
+                    .read_to_end(&mut bytes)
+                    .map_err(deserialization_error)?;
+                self.format.deserialize(&bytes)
+            }
+            _ => Err(deserialization_error("unknown entry encoding")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Report {
+        id: u64,
+        rows: Vec<String>,
+    }
+
+    fn report() -> Report {
+        Report { id: 7, rows: vec!["row".repeat(20); 100] }
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let plain = Codec::new(&CacheConfig::default());
+        let compressed = Codec::new(&CacheConfig::default().with_compression(256));
+
+        let entry = plain.encode(&report()).unwrap();
+        assert_eq!(entry, serde_json::to_vec(&report()).unwrap());
+        let small = compressed.encode(&1u8).unwrap();
+        assert_eq!(small, [UNCOMPRESSED, b'1']);
+        let large = compressed.encode(&report()).unwrap();
+        assert_eq!(large[0], ZLIB);
+        assert!(large.len() < entry.len() / 10);
+
+        assert_eq!(compressed.decode::<Report>(&large).unwrap(), report());
+        assert_eq!(compressed.decode::<u8>(&small).unwrap(), 1);
+    }
+
+    #[cfg(all(feature = "cache-bincode", feature = "cache-msgpack"))]
+    #[test]
+    fn test_binary_formats() {
+        let readings: Vec<f64> = (0..100).map(|i| i as f64 * 0.1).collect();
+        for format in [CacheFormat::Bincode, CacheFormat::MessagePack] {
+            let codec = Codec::new(&CacheConfig::default().with_format(format));
+            let entry = codec.encode(&readings).unwrap();
+            assert!(entry.len() < serde_json::to_vec(&readings).unwrap().len());
+            assert_eq!(codec.decode::<Vec<f64>>(&entry).unwrap(), readings);
+            assert_eq!(codec.decode::<Report>(&codec.encode(&report()).unwrap()).unwrap(), report());
+        }
+    }
+}
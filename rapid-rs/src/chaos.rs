@@ -0,0 +1,304 @@
+//! Fault injection for resilience testing
+//!
+//! A [`Chaos`] layer slows down, fails or cuts off a share of route calls,
+//! database connection checkouts and cache operations, so retries, timeouts
+//! and circuit breakers can be exercised before a real outage does it. It
+//! runs the profile named by `[chaos] profile` in config, but only with
+//! `dev_mode = true`:
+//!
+//! ```toml
+//! dev_mode = true
+//!
+//! [chaos]
+//! profile = "flaky"
+//!
+//! [chaos.profiles.flaky]
+//! paths = ["/api/orders"]
+//! routes = { error_rate = 0.05, drop_rate = 0.01 }
+//! database = { latency_rate = 0.5, latency_ms = [100, 2000] }
+//! cache = { error_rate = 0.2 }
+//! ```
+//!
+//! Tests can install one directly and flip it on and off:
+//!
+//! ```rust,ignore
+//! let chaos = Arc::new(Chaos::new(profile).with_seed(7));
+//! let cache = Cache::new(CacheConfig::new()).with_chaos(chaos.clone());
+//! let app = App::new().route(...).with_chaos(chaos.clone());
+//! chaos.disable();
+//! ```
+//!
+//! Database faults apply to connections taken with
+//! [`DbConn`](crate::database::DbConn); cache faults to caches given the
+//! layer with [`Cache::with_chaos`](crate::cache::Cache::with_chaos). Other
+//! calls can opt in with [`Chaos::inject`].
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+pub use crate::config::{ChaosFaults, ChaosProfile};
+use crate::config::AppConfig;
+use crate::error::ApiError;
+
+/// Which kind of call a fault is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChaosTarget {
+    Route,
+    Database,
+    Cache,
+}
+
+impl ChaosTarget {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChaosTarget::Route => "route",
+            ChaosTarget::Database => "database",
+            ChaosTarget::Cache => "cache",
+        }
+    }
+}
+
+/// What happens to one call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    None,
+    Error,
+    Drop,
+}
+
+/// Injects the faults of a [`ChaosProfile`]
+#[derive(Debug)]
+pub struct Chaos {
+    profile: RwLock<ChaosProfile>,
+    enabled: AtomicBool,
+    rng: Mutex<StdRng>,
+}
+
+impl Chaos {
+    pub fn new(profile: ChaosProfile) -> Self {
+        Self {
+            profile: RwLock::new(profile),
+            enabled: AtomicBool::new(true),
+            rng: Mutex::new(StdRng::from_entropy()),
+        }
+    }
+
+    /// The profile selected in `config`, if chaos should run at all
+    ///
+    /// Nothing runs outside `dev_mode`; naming a profile that isn't defined
+    /// is an error.
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>, ApiError> {
+        let Some(name) = &config.chaos.profile else {
+            return Ok(None);
+        };
+        if !config.dev_mode {
+            tracing::warn!(profile = %name, "Ignoring chaos profile outside dev_mode");
+            return Ok(None);
+        }
+        let profile = config
+            .chaos
+            .profiles
+            .get(name)
+            .ok_or_else(|| ApiError::InternalServerError(format!("Unknown chaos profile: {}", name)))?;
+        Ok(Some(Self::new(profile.clone())))
+    }
+
+    /// Make which calls fail repeatable
+    pub fn with_seed(self, seed: u64) -> Self {
+        *self.rng.lock().unwrap() = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Stop injecting faults until [`enable`](Self::enable)
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn profile(&self) -> ChaosProfile {
+        self.profile.read().unwrap().clone()
+    }
+
+    pub fn set_profile(&self, profile: ChaosProfile) {
+        *self.profile.write().unwrap() = profile;
+    }
+
+    /// Delay, then maybe fail, a call to `target`
+    pub async fn inject(&self, target: ChaosTarget) -> Result<(), ApiError> {
+        match self.apply(target).await {
+            Fault::None => Ok(()),
+            Fault::Error => Err(ApiError::ServiceUnavailable(format!("Chaos: injected {} failure", target.as_str()))),
+            Fault::Drop => Err(ApiError::ServiceUnavailable(format!("Chaos: dropped {} connection", target.as_str()))),
+        }
+    }
+
+    async fn apply(&self, target: ChaosTarget) -> Fault {
+        if !self.is_enabled() {
+            return Fault::None;
+        }
+        let (delay, fault) = self.roll(target);
+        if let Some(delay) = delay {
+            tracing::debug!(target = target.as_str(), delay_ms = delay.as_millis() as u64, "Chaos: injecting latency");
+            tokio::time::sleep(delay).await;
+        }
+        if fault != Fault::None {
+            tracing::debug!(target = target.as_str(), fault = ?fault, "Chaos: injecting fault");
+        }
+        fault
+    }
+
+    fn roll(&self, target: ChaosTarget) -> (Option<Duration>, Fault) {
+        let profile = self.profile.read().unwrap();
+        let faults = match target {
+            ChaosTarget::Route => &profile.routes,
+            ChaosTarget::Database => &profile.database,
+            ChaosTarget::Cache => &profile.cache,
+        };
+        let mut rng = self.rng.lock().unwrap();
+
+        let delay = (rng.gen::<f64>() < faults.latency_rate).then(|| {
+            let (min, max) = faults.latency_ms;
+            Duration::from_millis(rng.gen_range(min.min(max)..=max.max(min)))
+        });
+        let roll = rng.gen::<f64>();
+        let fault = if roll < faults.drop_rate {
+            Fault::Drop
+        } else if roll < faults.drop_rate + faults.error_rate {
+            Fault::Error
+        } else {
+            Fault::None
+        };
+        (delay, fault)
+    }
+
+    fn covers(&self, path: &str) -> bool {
+        let profile = self.profile.read().unwrap();
+        profile.paths.is_empty() || profile.paths.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    fn error_status(&self) -> StatusCode {
+        self.profile
+            .read()
+            .unwrap()
+            .routes
+            .error_status
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .unwrap_or(StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+/// Apply route faults, and hand the layer to [`DbConn`](crate::database::DbConn)
+pub(crate) async fn inject_faults(chaos: Arc<Chaos>, mut request: Request, next: Next) -> Response {
+    request.extensions_mut().insert(chaos.clone());
+    if !chaos.covers(request.uri().path()) {
+        return next.run(request).await;
+    }
+    match chaos.apply(ChaosTarget::Route).await {
+        Fault::None => next.run(request).await,
+        Fault::Error => ApiError::custom(chaos.error_status(), "CHAOS_INJECTED", "Chaos: injected route failure").into_response(),
+        Fault::Drop => {
+            // A body that fails straight away makes the server reset the connection
+            let body = futures::stream::once(async {
+                Err::<axum::body::Bytes, _>(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "Chaos: dropped connection"))
+            });
+            (StatusCode::OK, Body::from_stream(body)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::routing::get;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn faults(latency_rate: f64, error_rate: f64, drop_rate: f64) -> ChaosFaults {
+        ChaosFaults {
+            latency_rate,
+            latency_ms: (20, 30),
+            error_rate,
+            drop_rate,
+            ..ChaosFaults::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chaos_faults() {
+        let chaos = Arc::new(
+            Chaos::new(ChaosProfile {
+                paths: vec!["/api".to_string()],
+                routes: ChaosFaults {
+                    error_status: Some(502),
+                    ..faults(1.0, 1.0, 0.0)
+                },
+                cache: faults(0.0, 1.0, 0.0),
+                ..ChaosProfile::default()
+            })
+            .with_seed(7),
+        );
+        let router = App::new()
+            .route("/api/orders", get(|| async { "ok" }))
+            .route("/health", get(|| async { "ok" }))
+            .with_chaos(chaos.clone())
+            .into_router();
+        let call = |path: &'static str| router.clone().oneshot(Request::get(path).body(Body::empty()).unwrap());
+
+        let started = tokio::time::Instant::now();
+        assert_eq!(call("/api/orders").await.unwrap().status(), StatusCode::BAD_GATEWAY);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(call("/health").await.unwrap().status(), StatusCode::OK);
+
+        chaos.set_profile(ChaosProfile {
+            routes: faults(0.0, 0.0, 1.0),
+            ..chaos.profile()
+        });
+        let response = call("/api/orders").await.unwrap();
+        assert!(response.into_body().collect().await.is_err());
+
+        #[cfg(feature = "cache")]
+        let cache = crate::cache::Cache::new(crate::cache::CacheConfig::new()).with_chaos(chaos.clone());
+        #[cfg(feature = "cache")]
+        assert!(matches!(cache.get::<String>("key").await, Err(ApiError::ServiceUnavailable(_))));
+
+        chaos.disable();
+        assert_eq!(call("/api/orders").await.unwrap().status(), StatusCode::OK);
+        #[cfg(feature = "cache")]
+        assert!(cache.get::<String>("key").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_chaos_from_config() {
+        let mut config = AppConfig {
+            chaos: crate::config::ChaosConfig {
+                profile: Some("flaky".to_string()),
+                ..Default::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(Chaos::from_config(&config).unwrap().is_none());
+
+        config.dev_mode = true;
+        assert!(Chaos::from_config(&config).is_err());
+
+        config.chaos.profiles.insert("flaky".to_string(), ChaosProfile::default());
+        assert!(Chaos::from_config(&config).unwrap().is_some());
+    }
+}
@@ -0,0 +1,347 @@
+//! Client addresses behind reverse proxies
+//!
+//! Behind a load balancer every connection comes from the proxy, and the
+//! real client is only named in `X-Forwarded-For` (or `Forwarded`). Those
+//! headers are set by whoever sends the request, so they are only honored
+//! when the connection comes from a proxy listed in [`TrustedProxies`]:
+//!
+//! ```rust,ignore
+//! App::new().with_trusted_proxies(TrustedProxies::new(["10.0.0.0/8", "127.0.0.1"])?)
+//! ```
+//!
+//! or `trusted_proxies = ["10.0.0.0/8"]` under `[server]` in config. The
+//! header is then read right to left, skipping trusted hops, and the first
+//! address not in the list is the client. Handlers, audit events and
+//! websocket connections see the result through [`ClientInfo`]; with no
+//! trusted proxies it is always the connection's peer address. The scheme
+//! the client used is taken from `X-Forwarded-Proto` (or `proto=` in
+//! `Forwarded`) the same way, and is otherwise the listener's own.
+//!
+//! ```rust,ignore
+//! async fn whoami(client: ClientInfo) -> String {
+//!     client.ip.to_string()
+//! }
+//! ```
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{request::Parts, Extensions, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::error::ApiError;
+
+/// An entry in [`TrustedProxies`] that is neither an IP address nor a CIDR range
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid trusted proxy '{0}', expected an IP address or CIDR range")]
+pub struct InvalidProxy(pub String);
+
+/// Which header trusted proxies record the client chain in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForwardedHeader {
+    /// `X-Forwarded-For: client, proxy1, proxy2`
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded: for=client, for=proxy1`
+    Forwarded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn parse(value: &str) -> Result<Self, InvalidProxy> {
+        let invalid = || InvalidProxy(value.to_string());
+        let (addr, prefix) = match value.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().map_err(|_| invalid())?)),
+            None => (value.trim(), None),
+        };
+        let addr = canonical(addr.parse::<IpAddr>().map_err(|_| invalid())?);
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// IPv4-mapped IPv6 addresses (`::ffff:10.0.0.1`) as plain IPv4
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        ip => ip,
+    }
+}
+
+/// Proxies whose forwarding headers are believed
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<Network>,
+    header: ForwardedHeader,
+}
+
+impl TrustedProxies {
+    /// Trust the given IP addresses and CIDR ranges
+    pub fn new<I, S>(proxies: I) -> Result<Self, InvalidProxy>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let networks = proxies
+            .into_iter()
+            .map(|proxy| Network::parse(proxy.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            networks,
+            header: ForwardedHeader::default(),
+        })
+    }
+
+    /// Read the client chain from `header` instead of `X-Forwarded-For`
+    ///
+    /// Only the header the proxies actually set can be used; any other is
+    /// passed through from the client untouched.
+    pub fn with_header(mut self, header: ForwardedHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Whether no proxy is trusted
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    /// Whether `ip` is a trusted proxy
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// The client address for a request from `peer` carrying `headers`
+    ///
+    /// A hop that can't be parsed ends the walk, and the last trusted
+    /// address before it is taken as the client.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = canonical(peer);
+        if !self.contains(client) {
+            return client;
+        }
+        for hop in self.hops(headers).iter().rev() {
+            match hop {
+                Some(ip) => {
+                    client = canonical(*ip);
+                    if !self.contains(client) {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+        client
+    }
+
+    fn hops(&self, headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+        match self.header {
+            ForwardedHeader::XForwardedFor => headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .flat_map(|value| value.to_str().unwrap_or("").split(','))
+                .map(|hop| parse_hop(hop.trim()))
+                .collect(),
+            ForwardedHeader::Forwarded => headers
+                .get_all("forwarded")
+                .iter()
+                .flat_map(|value| value.to_str().unwrap_or("").split(','))
+                .map(|element| {
+                    element
+                        .split(';')
+                        .filter_map(|pair| pair.split_once('='))
+                        .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                        .and_then(|(_, value)| parse_hop(value.trim().trim_matches('"')))
+                })
+                .collect(),
+        }
+    }
+
+    /// The scheme the client used for a request from `peer` to a listener serving `scheme`
+    ///
+    /// Only a trusted proxy can claim another scheme; the value nearest to
+    /// it wins, and anything but `http` or `https` is ignored.
+    pub fn resolve_scheme(&self, peer: IpAddr, scheme: &'static str, headers: &HeaderMap) -> &'static str {
+        if !self.contains(peer) {
+            return scheme;
+        }
+        let claimed = match self.header {
+            ForwardedHeader::XForwardedFor => headers
+                .get_all("x-forwarded-proto")
+                .iter()
+                .flat_map(|value| value.to_str().unwrap_or("").split(','))
+                .next_back()
+                .map(str::to_string),
+            ForwardedHeader::Forwarded => headers
+                .get_all("forwarded")
+                .iter()
+                .flat_map(|value| value.to_str().unwrap_or("").split(&[',', ';'][..]))
+                .filter_map(|pair| pair.split_once('='))
+                .rfind(|(key, _)| key.trim().eq_ignore_ascii_case("proto"))
+                .map(|(_, value)| value.trim().trim_matches('"').to_string()),
+        };
+        match claimed.as_deref().map(str::trim) {
+            Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+            Some(proto) if proto.eq_ignore_ascii_case("http") => "http",
+            _ => scheme,
+        }
+    }
+}
+
+/// The scheme a listener serves, set on each request it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ListenerScheme(pub(crate) &'static str);
+
+fn listener_scheme(extensions: &Extensions) -> &'static str {
+    extensions.get::<ListenerScheme>().map_or("http", |ListenerScheme(scheme)| scheme)
+}
+
+/// A hop as `ip`, `ip:port`, `[ipv6]` or `[ipv6]:port`
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    if let Some(rest) = hop.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    hop.parse::<IpAddr>()
+        .ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Who sent the request
+///
+/// `ip` is the client as resolved through [`TrustedProxies`]; `peer` is the
+/// address of the connection itself, which differs when the request came
+/// through a trusted proxy. `scheme` is `http` or `https` as the client
+/// used it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub ip: IpAddr,
+    pub peer: SocketAddr,
+    pub scheme: &'static str,
+}
+
+impl ClientInfo {
+    /// The client of a request, from what the listener and
+    /// [`TrustedProxies`] recorded in its extensions
+    pub(crate) fn from_extensions(extensions: &Extensions) -> Option<Self> {
+        if let Some(client) = extensions.get::<ClientInfo>() {
+            return Some(*client);
+        }
+        // No trusted proxies: the peer is the client
+        extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(peer)| ClientInfo {
+            ip: canonical(peer.ip()),
+            peer: *peer,
+            scheme: listener_scheme(extensions),
+        })
+    }
+
+    /// Whether the client address came from a forwarding header
+    pub fn is_forwarded(&self) -> bool {
+        self.ip != canonical(self.peer.ip())
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        ClientInfo::from_extensions(&parts.extensions)
+            .ok_or_else(|| ApiError::InternalServerError("Client address is not available".to_string()))
+    }
+}
+
+/// Resolve the client of each request once, for [`ClientInfo`]
+pub(crate) async fn resolve(proxies: Arc<TrustedProxies>, mut request: Request, next: Next) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let ip = proxies.resolve(peer.ip(), request.headers());
+        let scheme = proxies.resolve_scheme(peer.ip(), listener_scheme(request.extensions()), request.headers());
+        request.extensions_mut().insert(ClientInfo { ip, peer, scheme });
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_client_ip() {
+        let proxies = TrustedProxies::new(["10.0.0.0/8", "::1"]).unwrap();
+        let chain = headers("x-forwarded-for", "1.2.3.4, 203.0.113.7, 10.0.0.2");
+
+        // Untrusted peers can't claim another address
+        assert_eq!(proxies.resolve(ip("198.51.100.1"), &chain), ip("198.51.100.1"));
+        // A spoofed leading entry stops at the first untrusted hop
+        assert_eq!(proxies.resolve(ip("10.1.2.3"), &chain), ip("203.0.113.7"));
+        assert_eq!(proxies.resolve(ip("::ffff:10.1.2.3"), &chain), ip("203.0.113.7"));
+        // No header, or an unparseable hop, leaves the nearest trusted address
+        assert_eq!(proxies.resolve(ip("::1"), &HeaderMap::new()), ip("::1"));
+        let garbled = headers("x-forwarded-for", "1.2.3.4, nonsense, 10.0.0.2");
+        assert_eq!(proxies.resolve(ip("10.1.2.3"), &garbled), ip("10.0.0.2"));
+
+        let forwarded = headers("forwarded", r#"for=1.2.3.4, for="[2001:db8::17]:4711";proto=https, for=10.0.0.2:80"#);
+        assert_eq!(proxies.resolve(ip("10.1.2.3"), &forwarded), ip("10.1.2.3"));
+        let proxies = proxies.with_header(ForwardedHeader::Forwarded);
+        assert_eq!(proxies.resolve(ip("10.1.2.3"), &forwarded), ip("2001:db8::17"));
+
+        assert!(TrustedProxies::new(["10.0.0.0/33"]).is_err());
+        assert!(TrustedProxies::new(["proxy.internal"]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_scheme() {
+        let proxies = TrustedProxies::new(["10.0.0.0/8"]).unwrap();
+        let proto = headers("x-forwarded-proto", "http, https");
+
+        assert_eq!(proxies.resolve_scheme(ip("10.1.2.3"), "http", &proto), "https");
+        // Untrusted peers keep the listener's scheme
+        assert_eq!(proxies.resolve_scheme(ip("198.51.100.1"), "http", &proto), "http");
+        assert_eq!(proxies.resolve_scheme(ip("10.1.2.3"), "https", &HeaderMap::new()), "https");
+        let bogus = headers("x-forwarded-proto", "gopher");
+        assert_eq!(proxies.resolve_scheme(ip("10.1.2.3"), "http", &bogus), "http");
+
+        let forwarded = headers("forwarded", r#"for=1.2.3.4;proto=http, for=10.0.0.2;proto="https""#);
+        assert_eq!(proxies.resolve_scheme(ip("10.1.2.3"), "http", &forwarded), "http");
+        let proxies = proxies.with_header(ForwardedHeader::Forwarded);
+        assert_eq!(proxies.resolve_scheme(ip("10.1.2.3"), "http", &forwarded), "https");
+    }
+}
@@ -0,0 +1,132 @@
+//! Time source shared by jobs, scheduling, caching, auth and rate limiting
+//!
+//! Production code uses [`SystemClock`]. Tests swap in a [`ManualClock`] to
+//! freeze time and advance it explicitly instead of sleeping.
+//!
+//! ```rust,ignore
+//! let clock = ManualClock::frozen();
+//! let config = AuthConfig::default().with_clock(clock.shared());
+//! let tokens = create_token_pair("user-1", "a@b.c", vec![], &config)?;
+//!
+//! clock.advance(Duration::from_secs(16 * 60));
+//! assert!(verify_access_token(&tokens.access_token, &config).is_err());
+//! ```
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// A source of the current time
+#[async_trait]
+pub trait Clock: Send + Sync + Debug {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wait until the clock reads `deadline` or later
+    async fn sleep_until(&self, deadline: DateTime<Utc>);
+
+    async fn sleep(&self, duration: Duration) {
+        let step = chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        let deadline = self.now().checked_add_signed(step).unwrap_or(DateTime::<Utc>::MAX_UTC);
+        self.sleep_until(deadline).await
+    }
+}
+
+/// Clock handle stored by subsystems
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system clock
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Wall-clock time from the operating system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        if let Ok(remaining) = (deadline - Utc::now()).to_std() {
+            tokio::time::sleep(remaining).await;
+        }
+    }
+}
+
+/// Clock that only moves when told to
+///
+/// Clones share the same time. Sleepers wake as soon as [`ManualClock::advance`]
+/// or [`ManualClock::set`] moves the time past their deadline.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<watch::Sender<DateTime<Utc>>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        let (now, _) = watch::channel(start);
+        Self { now: Arc::new(now) }
+    }
+
+    /// Clock frozen at the current system time
+    pub fn frozen() -> Self {
+        Self::new(Utc::now())
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.now.send_replace(now);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let step = chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        self.now.send_modify(|now| *now += step);
+    }
+
+    /// This clock as a [`SharedClock`]
+    pub fn shared(&self) -> SharedClock {
+        Arc::new(self.clone())
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        let mut receiver = self.now.subscribe();
+        let _ = receiver.wait_for(|now| *now >= deadline).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_wakes_sleepers() {
+        let clock = ManualClock::frozen();
+        let start = clock.now();
+
+        let sleeper = {
+            let clock = clock.clone();
+            tokio::spawn(async move { clock.sleep(Duration::from_secs(60)).await })
+        };
+        tokio::task::yield_now().await;
+
+        clock.advance(Duration::from_secs(30));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+
+        clock.advance(Duration::from_secs(30));
+        tokio::time::timeout(Duration::from_secs(1), sleeper).await.unwrap().unwrap();
+        assert_eq!(clock.now() - start, chrono::Duration::seconds(60));
+    }
+}
@@ -0,0 +1,361 @@
+//! ETags and conditional `GET`s
+//!
+//! With [`App::with_conditional_requests`](crate::App::with_conditional_requests),
+//! successful JSON responses to `GET` get an `ETag` computed from their body,
+//! and requests whose `If-None-Match` (or, without it, `If-Modified-Since`)
+//! still matches are answered with an empty `304 Not Modified`, which saves
+//! the transfer for polling clients.
+//!
+//! Handlers that know their validators up front, say a row's version and
+//! `updated_at`, can set them with [`Conditional`] instead of having the body
+//! hashed:
+//!
+//! ```rust,ignore
+//! async fn get_doc(Path(id): Path<Uuid>, Dep(pool): Dep<PgPool>) -> ApiResult<Conditional<Json<Doc>>> {
+//!     let doc = load(&pool, id).await?;
+//!     Ok(Conditional::new(Json(doc.clone()))
+//!         .with_etag(ETag::strong(doc.version.to_string()))
+//!         .with_last_modified(doc.updated_at))
+//! }
+//!
+//! App::new().with_conditional_requests(ConditionalRequests::new())
+//! ```
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::sync::Arc;
+
+/// An entity tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ETag {
+    tag: String,
+    weak: bool,
+}
+
+impl ETag {
+    /// Tag for byte-identical representations; `tag` must not contain `"`
+    pub fn strong(tag: impl Into<String>) -> Self {
+        Self { tag: tag.into(), weak: false }
+    }
+
+    /// Tag for semantically equivalent representations, sent as `W/"tag"`
+    pub fn weak(tag: impl Into<String>) -> Self {
+        Self { tag: tag.into(), weak: true }
+    }
+
+    /// Strong tag derived from the content
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        // FNV-1a: cheap, and stable across builds and instances
+        let hash = bytes
+            .iter()
+            .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3));
+        Self::strong(format!("{:016x}-{:x}", hash, bytes.len()))
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Parse one `ETag` header value
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (weak, quoted) = match value.strip_prefix("W/") {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+        (!tag.contains('"')).then(|| Self { tag: tag.to_string(), weak })
+    }
+
+    /// Whether an `If-None-Match` value lists this tag (weak comparison)
+    pub fn matches_any(&self, if_none_match: &str) -> bool {
+        if if_none_match.trim() == "*" {
+            return true;
+        }
+        if_none_match
+            .split(',')
+            .filter_map(ETag::parse)
+            .any(|candidate| candidate.tag == self.tag)
+    }
+
+    fn header_value(&self) -> Option<HeaderValue> {
+        HeaderValue::from_str(&self.to_string()).ok()
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/\"{}\"", self.tag)
+        } else {
+            write!(f, "\"{}\"", self.tag)
+        }
+    }
+}
+
+/// A response with its validators set by the handler
+///
+/// Without [`App::with_conditional_requests`](crate::App::with_conditional_requests)
+/// the headers are still sent, but requests are never answered with 304.
+pub struct Conditional<T> {
+    body: T,
+    etag: Option<ETag>,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl<T> Conditional<T> {
+    pub fn new(body: T) -> Self {
+        Self {
+            body,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    pub fn with_etag(mut self, etag: ETag) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
+    pub fn with_last_modified(mut self, at: DateTime<Utc>) -> Self {
+        self.last_modified = Some(at);
+        self
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Conditional<T> {
+    fn into_response(self) -> Response {
+        let mut response = self.body.into_response();
+        if !response.status().is_success() {
+            return response;
+        }
+        if let Some(value) = self.etag.as_ref().and_then(ETag::header_value) {
+            response.headers_mut().insert(header::ETAG, value);
+        }
+        if let Some(value) = self.last_modified.and_then(|at| HeaderValue::from_str(&http_date(at)).ok()) {
+            response.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+        response
+    }
+}
+
+/// How [`App::with_conditional_requests`](crate::App::with_conditional_requests) tags responses
+#[derive(Debug, Clone)]
+pub struct ConditionalRequests {
+    weak: bool,
+    max_body_size: usize,
+}
+
+impl Default for ConditionalRequests {
+    fn default() -> Self {
+        Self {
+            weak: false,
+            max_body_size: 1024 * 1024,
+        }
+    }
+}
+
+impl ConditionalRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send computed tags as weak (`W/"..."`), e.g. when a proxy re-encodes bodies
+    pub fn weak_etags(mut self) -> Self {
+        self.weak = true;
+        self
+    }
+
+    /// Larger bodies are not hashed (default 1 MiB)
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+}
+
+fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|essence| essence.trim().to_ascii_lowercase())
+        .is_some_and(|essence| essence == "application/json" || essence.ends_with("+json"))
+}
+
+/// Whether the client's copy, described by the request's preconditions, is current
+fn not_modified(request: &HeaderMap, response: &HeaderMap) -> bool {
+    fn header(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+        headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    // If-None-Match takes precedence, If-Modified-Since is then ignored
+    if let Some(if_none_match) = header(request, header::IF_NONE_MATCH) {
+        if if_none_match.trim() == "*" {
+            return true;
+        }
+        return header(response, header::ETAG)
+            .and_then(ETag::parse)
+            .is_some_and(|etag| etag.matches_any(if_none_match));
+    }
+
+    let since = header(request, header::IF_MODIFIED_SINCE).and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    let modified = header(response, header::LAST_MODIFIED).and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// The 304 for `response`, keeping the headers a cache needs
+fn not_modified_response(response: &Response) -> Response {
+    let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+    for name in [
+        header::ETAG,
+        header::LAST_MODIFIED,
+        header::CACHE_CONTROL,
+        header::VARY,
+        header::EXPIRES,
+        header::CONTENT_LOCATION,
+        header::DATE,
+    ] {
+        if let Some(value) = response.headers().get(&name) {
+            not_modified.headers_mut().insert(name, value.clone());
+        }
+    }
+    not_modified
+}
+
+/// Tag successful JSON responses and answer fresh preconditions with 304
+pub(crate) async fn conditional(config: Arc<ConditionalRequests>, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    if method != Method::GET && method != Method::HEAD {
+        return next.run(request).await;
+    }
+    let preconditions = request.headers().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    // HEAD bodies are already stripped, so only handler-set tags apply
+    let hashable = method == Method::GET
+        && !response.headers().contains_key(header::ETAG)
+        && is_json(response.headers())
+        && response.body().size_hint().upper().is_some_and(|size| size <= config.max_body_size as u64);
+    let response = if hashable {
+        let (mut parts, body) = response.into_parts();
+        let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                tracing::warn!("Could not buffer response to compute its ETag: {}", error);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        let etag = ETag::from_bytes(&bytes);
+        let etag = if config.weak { ETag::weak(etag.tag) } else { etag };
+        if let Some(value) = etag.header_value() {
+            parts.headers.insert(header::ETAG, value);
+        }
+        Response::from_parts(parts, Body::from(bytes))
+    } else {
+        response
+    };
+
+    if not_modified(&preconditions, response.headers()) {
+        not_modified_response(&response)
+    } else {
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::{routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        App::new()
+            .route("/items", get(|| async { Json(serde_json::json!({ "items": [1, 2, 3] })) }))
+            .route(
+                "/doc",
+                get(|| async {
+                    Conditional::new(Json("doc"))
+                        .with_etag(ETag::strong("v7"))
+                        .with_last_modified(DateTime::from_timestamp(1_700_000_000, 0).unwrap())
+                }),
+            )
+            .route("/text", get(|| async { "plain" }))
+            .with_conditional_requests(ConditionalRequests::new())
+            .into_router()
+    }
+
+    async fn call(router: &Router, uri: &str, headers: &[(&str, &str)]) -> Response {
+        let mut request = Request::get(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[test]
+    fn test_etag_parsing() {
+        assert_eq!(ETag::parse("W/\"abc\""), Some(ETag::weak("abc")));
+        assert_eq!(ETag::parse("\"abc\"").unwrap().to_string(), "\"abc\"");
+        assert_eq!(ETag::parse("abc"), None);
+        assert!(ETag::strong("abc").matches_any("\"x\", W/\"abc\""));
+        assert!(ETag::strong("abc").matches_any("*"));
+        assert_eq!(ETag::from_bytes(b"same"), ETag::from_bytes(b"same"));
+        assert_ne!(ETag::from_bytes(b"same"), ETag::from_bytes(b"other"));
+    }
+
+    #[tokio::test]
+    async fn test_computed_etags() {
+        let router = app();
+
+        let response = call(&router, "/items", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let response = call(&router, "/items", &[("if-none-match", &etag)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+
+        let response = call(&router, "/items", &[("if-none-match", "\"stale\"")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call(&router, "/text", &[]).await;
+        assert!(response.headers().get(header::ETAG).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handler_validators() {
+        let router = app();
+
+        let response = call(&router, "/doc", &[("if-none-match", "W/\"v7\"")]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = call(&router, "/doc", &[("if-modified-since", "Tue, 14 Nov 2023 22:13:20 GMT")]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::LAST_MODIFIED], "Tue, 14 Nov 2023 22:13:20 GMT");
+
+        let response = call(&router, "/doc", &[("if-modified-since", "Mon, 13 Nov 2023 00:00:00 GMT")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], "\"v7\"");
+    }
+}
@@ -83,6 +83,43 @@ pub async fn run_migrations(
     Ok(())
 }
 
+/// Migrations in `config.migrations_path` not yet applied, as `<version>_<description>`
+pub async fn pending_migrations(
+    pool: &PgPool,
+    config: &MigrationConfig,
+) -> Result<Vec<String>, ApiError> {
+    let migrations_path = Path::new(&config.migrations_path);
+    if !migrations_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let migrator = sqlx::migrate::Migrator::new(migrations_path)
+        .await
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to load migrations: {}", e)))?;
+
+    let query_failed = |e: sqlx::Error| ApiError::InternalServerError(format!("Failed to read applied migrations: {}", e));
+    // Nothing has run yet if sqlx hasn't created its table
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await
+        .map_err(query_failed)?;
+    let applied: Vec<i64> = if has_table {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await
+            .map_err(query_failed)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| format!("{}_{}", migration.version, migration.description))
+        .collect())
+}
+
 /// Create database if it doesn't exist
 pub async fn ensure_database_exists(database_url: &str) -> Result<(), ApiError> {
     if !Postgres::database_exists(database_url)
@@ -2,9 +2,17 @@
 
 pub mod migrations;
 pub mod backends;
+pub mod bulk;
+pub mod deadline;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 
 pub use sqlx::{PgPool, Postgres, Transaction};
-pub use migrations::{MigrationConfig, run_migrations, connect_and_migrate, ensure_database_exists};
+pub use bulk::{BulkInsert, BulkReport, BulkRow, OnConflict, RowOutcome};
+pub use deadline::{DbConn, RequestDeadline};
+#[cfg(feature = "encryption")]
+pub use encryption::{EncryptedJson, EncryptedString, EnvKeyProvider, FieldEncryption, KeyProvider};
+pub use migrations::{MigrationConfig, run_migrations, connect_and_migrate, ensure_database_exists, pending_migrations};
 
 #[cfg(feature = "db-sqlite")]
 pub use backends::sqlite;
@@ -0,0 +1,164 @@
+//! Shared dependencies resolved by type
+//!
+//! Register services once on the [`App`](crate::App) and extract them in
+//! handlers with [`Dep`]. Trait objects work too, which is what lets tests
+//! swap real implementations for fakes (see `testing::TestApp`).
+//!
+//! ```rust,ignore
+//! let app = App::new()
+//!     .provide(pool.clone())
+//!     .provide_arc::<dyn EmailProvider>(Arc::new(SmtpEmailProvider::new(config)))
+//!     .route("/signup", post(signup));
+//!
+//! async fn signup(Dep(mailer): Dep<dyn EmailProvider>, Dep(pool): Dep<PgPool>) { /* ... */ }
+//! ```
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::error::ApiError;
+
+/// Type-keyed collection of shared services
+///
+/// Cloning is cheap; values are stored behind `Arc`.
+#[derive(Clone, Default)]
+pub struct Dependencies {
+    // Each value is an `Arc<T>` boxed as `Any`, so unsized `T` (trait objects) work
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Dependencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `value`, replacing any previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.insert_arc(Arc::new(value));
+    }
+
+    /// Register a shared value, e.g. `Arc<dyn EmailProvider>`
+    pub fn insert_arc<T: ?Sized + Send + Sync + 'static>(&mut self, value: Arc<T>) {
+        self.values.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn get<T: ?Sized + Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<Arc<T>>())
+            .cloned()
+    }
+
+    pub fn contains<T: ?Sized + 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Values from `overrides` replace values of the same type
+    pub fn merge(&mut self, overrides: &Dependencies) {
+        for (type_id, value) in &overrides.values {
+            self.values.insert(*type_id, value.clone());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Dependencies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dependencies").field("len", &self.values.len()).finish()
+    }
+}
+
+/// Extractor for a dependency registered with `App::provide`
+///
+/// Rejects with a 500 if the type was never registered.
+pub struct Dep<T: ?Sized>(pub Arc<T>);
+
+impl<T: ?Sized> Deref for Dep<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> Clone for Dep<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Dep<T>
+where
+    S: Send + Sync,
+    T: ?Sized + Send + Sync + 'static,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Dependencies>()
+            .and_then(|dependencies| dependencies.get::<T>())
+            .map(Dep)
+            .ok_or_else(|| {
+                ApiError::InternalServerError(format!(
+                    "Dependency not registered: {}",
+                    std::any::type_name::<T>()
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    trait Greeter: Send + Sync {
+        fn greet(&self) -> String;
+    }
+
+    struct English;
+
+    impl Greeter for English {
+        fn greet(&self) -> String {
+            "hello".to_string()
+        }
+    }
+
+    struct French;
+
+    impl Greeter for French {
+        fn greet(&self) -> String {
+            "bonjour".to_string()
+        }
+    }
+
+    #[test]
+    fn test_insert_and_override() {
+        let mut dependencies = Dependencies::new();
+        dependencies.insert(42u32);
+        dependencies.insert_arc::<dyn Greeter>(Arc::new(English));
+
+        assert_eq!(*dependencies.get::<u32>().unwrap(), 42);
+        assert_eq!(dependencies.get::<dyn Greeter>().unwrap().greet(), "hello");
+        assert!(dependencies.get::<String>().is_none());
+
+        let mut overrides = Dependencies::new();
+        overrides.insert_arc::<dyn Greeter>(Arc::new(French));
+        dependencies.merge(&overrides);
+
+        assert_eq!(dependencies.get::<dyn Greeter>().unwrap().greet(), "bonjour");
+        assert_eq!(dependencies.len(), 2);
+    }
+}
@@ -0,0 +1,241 @@
+//! Deprecating endpoints ahead of their removal
+//!
+//! A [`Deprecation`] attached with [`RouteDoc::deprecated`](crate::openapi::RouteDoc::deprecated)
+//! (or `deprecated(...)` in `#[api_handler]`) flags the operation in the
+//! docs, adds `Deprecation`/`Sunset`/`Link` headers (RFC 9745, RFC 8594) to
+//! its responses, and counts calls per client so you know who still has to
+//! migrate:
+//!
+//! ```rust,ignore
+//! App::new().route_with_doc(
+//!     "/v1/users",
+//!     get(list_users_v1),
+//!     RouteDoc::get().deprecated(
+//!         Deprecation::new()
+//!             .since(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap())
+//!             .sunset(NaiveDate::from_ymd_opt(2025, 7, 1).unwrap())
+//!             .with_successor("/v2/users"),
+//!     ),
+//! )
+//! ```
+//!
+//! With `observability` the counter is `http_deprecated_requests_total`,
+//! labelled by route, method and client. The client is the `X-Client-Id`
+//! header, else the API key or token subject, else `anonymous`.
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDate, Utc};
+use std::sync::Arc;
+
+use crate::error::ApiError;
+
+/// Header clients can send to be told apart in deprecation metrics
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+
+/// When and how an endpoint is being retired
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    pub since: Option<NaiveDate>,
+    pub sunset: Option<NaiveDate>,
+    /// Page explaining the deprecation
+    pub link: Option<String>,
+    /// Endpoint to use instead
+    pub successor: Option<String>,
+    /// Answer `410 Gone` once the sunset date has passed
+    pub reject_after_sunset: bool,
+}
+
+impl Deprecation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn since(mut self, date: NaiveDate) -> Self {
+        self.since = Some(date);
+        self
+    }
+
+    /// Date after which the endpoint may stop working
+    pub fn sunset(mut self, date: NaiveDate) -> Self {
+        self.sunset = Some(date);
+        self
+    }
+
+    pub fn with_link(mut self, url: impl Into<String>) -> Self {
+        self.link = Some(url.into());
+        self
+    }
+
+    pub fn with_successor(mut self, url: impl Into<String>) -> Self {
+        self.successor = Some(url.into());
+        self
+    }
+
+    pub fn reject_after_sunset(mut self) -> Self {
+        self.reject_after_sunset = true;
+        self
+    }
+
+    pub fn is_sunset(&self) -> bool {
+        self.sunset.is_some_and(|sunset| Utc::now().date_naive() >= sunset)
+    }
+
+    /// Sentence appended to the operation's description in the docs
+    pub(crate) fn describe(&self) -> String {
+        let mut text = match self.since {
+            Some(since) => format!("**Deprecated** since {}.", since),
+            None => "**Deprecated**.".to_string(),
+        };
+        if let Some(sunset) = self.sunset {
+            text.push_str(&format!(" Will be removed after {}.", sunset));
+        }
+        if let Some(successor) = &self.successor {
+            text.push_str(&format!(" Use `{}` instead.", successor));
+        }
+        if let Some(link) = &self.link {
+            text.push_str(&format!(" See {}.", link));
+        }
+        text
+    }
+
+    fn add_headers(&self, response: &mut Response) {
+        let headers = response.headers_mut();
+        let deprecation = match self.since {
+            Some(since) => format!("@{}", since.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp()),
+            None => "true".to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&deprecation) {
+            headers.insert("deprecation", value);
+        }
+        if let Some(sunset) = self.sunset {
+            let date = sunset.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+            if let Ok(value) = HeaderValue::from_str(&date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()) {
+                headers.insert("sunset", value);
+            }
+        }
+        let links = [(&self.link, "deprecation"), (&self.successor, "successor-version")];
+        for (url, rel) in links.iter().filter_map(|(url, rel)| Some(((*url).as_ref()?, rel))) {
+            if let Ok(value) = HeaderValue::from_str(&format!("<{}>; rel=\"{}\"", url, rel)) {
+                headers.append(header::LINK, value);
+            }
+        }
+    }
+}
+
+/// Who is calling, for usage metrics
+fn client(request: &Request) -> String {
+    if let Some(id) = request.headers().get(CLIENT_ID_HEADER).and_then(|value| value.to_str().ok()) {
+        return id.to_string();
+    }
+    #[cfg(feature = "auth")]
+    {
+        if let Some(identity) = request.extensions().get::<crate::auth::ApiKeyIdentity>() {
+            return identity.name.clone();
+        }
+        if let Some(claims) = request.extensions().get::<crate::auth::Claims>() {
+            return claims.sub.clone();
+        }
+    }
+    "anonymous".to_string()
+}
+
+/// Add the deprecation headers and count the call
+pub(crate) async fn mark_deprecated(deprecation: Arc<Deprecation>, route: Arc<str>, request: Request, next: Next) -> Response {
+    let client = client(&request);
+    let method = request.method().to_string();
+    tracing::debug!(route = %route, method, client, "Deprecated endpoint called");
+    #[cfg(feature = "observability")]
+    crate::metrics::record_counter(
+        "http_deprecated_requests_total",
+        1,
+        &[("route", route.to_string()), ("method", method), ("client", client)],
+    );
+    #[cfg(not(feature = "observability"))]
+    let _ = (route, method, client);
+
+    let mut response = if deprecation.reject_after_sunset && deprecation.is_sunset() {
+        ApiError::custom(StatusCode::GONE, "ENDPOINT_RETIRED", deprecation.describe().replace("**", "")).into_response()
+    } else {
+        next.run(request).await
+    };
+    deprecation.add_headers(&mut response);
+    response
+}
+
+/// `NaiveDate` for `#[api_handler(deprecated(...))]`, which checks the date
+#[doc(hidden)]
+pub fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("invalid deprecation date")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openapi::RouteDoc;
+    use crate::App;
+    use axum::{body::Body, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_deprecated_route() {
+        let deprecation = Deprecation::new()
+            .since(ymd(2024, 1, 1))
+            .sunset(ymd(2999, 1, 1))
+            .with_link("https://example.com/migrate")
+            .with_successor("/v2/users");
+        let app = App::new()
+            .route_with_doc("/v1/users", get(|| async { "users" }), RouteDoc::get().description("List users").deprecated(deprecation))
+            .route_with_doc("/v2/users", get(|| async { "users" }), RouteDoc::get())
+            .with_api_docs();
+        let router = app.into_router();
+
+        let response = router.clone().oneshot(Request::get("/v1/users").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "@1704067200");
+        assert_eq!(response.headers()["sunset"], "Tue, 01 Jan 2999 00:00:00 GMT");
+        let links: Vec<_> = response.headers().get_all(header::LINK).iter().collect();
+        assert_eq!(links, ["<https://example.com/migrate>; rel=\"deprecation\"", "</v2/users>; rel=\"successor-version\""]);
+
+        let response = router.clone().oneshot(Request::get("/v2/users").body(Body::empty()).unwrap()).await.unwrap();
+        assert!(response.headers().get("deprecation").is_none());
+
+        let response = router.oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap()).await.unwrap();
+        let spec = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&spec).unwrap();
+        let operation = &spec["paths"]["/v1/users"]["get"];
+        assert_eq!(operation["deprecated"], true);
+        assert!(operation["description"].as_str().unwrap().starts_with("List users\n\n**Deprecated** since 2024-01-01."));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_after_sunset() {
+        let deprecation = Deprecation::new().sunset(ymd(2020, 1, 1)).reject_after_sunset();
+        let router = App::new()
+            .route_with_doc("/old", get(|| async { "old" }), RouteDoc::get().deprecated(deprecation))
+            .into_router();
+
+        let response = router.oneshot(Request::get("/old").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+        assert_eq!(response.headers()["deprecation"], "true");
+    }
+
+    #[crate::api_handler(get, deprecated(since = "2024-02-29", sunset = "2999-01-01", successor = "/v2/items"))]
+    async fn list_items() -> &'static str {
+        "items"
+    }
+
+    #[test]
+    fn test_api_handler_deprecated() {
+        let doc = list_items_doc();
+        let deprecation = doc.route_deprecation().unwrap();
+        assert_eq!(deprecation.since, Some(ymd(2024, 2, 29)));
+        assert_eq!(deprecation.sunset, Some(ymd(2999, 1, 1)));
+        assert_eq!(deprecation.successor.as_deref(), Some("/v2/items"));
+        let _router: axum::Router = axum::Router::new().route("/v1/items", get(list_items));
+    }
+}
@@ -0,0 +1,390 @@
+//! Stand-ins for external integrations during local development
+//!
+//! [`App::with_dev_integrations`](crate::App::with_dev_integrations), or
+//! `dev_mode = true` in config (`APP__DEV_MODE=true`), replaces the app's
+//! `dyn EmailProvider`, `dyn SmsProvider` and `dyn UploadStorage` with local
+//! stubs that log what they would have done, so the app runs without any
+//! credentials:
+//!
+//! - Emails and SMS land in a [`DevMailbox`], browsable at `/dev/mailbox`
+//!   (JSON at `/dev/mailbox/messages`)
+//! - Uploads go to a [`MemoryStorage`] served from `/dev/files`
+//!
+//! Stubs for the app's own clients (payments, outgoing webhooks) can record
+//! what they would have sent with [`DevMailbox::capture`], taking the
+//! mailbox from [`App::dev_integrations`](crate::App::dev_integrations).
+//!
+//! Never enable it in production: the routes are unauthenticated.
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+use crate::dependencies::Dependencies;
+
+/// A message captured instead of being sent
+#[derive(Debug, Clone, Serialize)]
+pub struct DevMessage {
+    pub id: String,
+    /// `email`, `sms`, or whatever a custom stub records
+    pub channel: &'static str,
+    pub to: Vec<String>,
+    pub subject: Option<String>,
+    pub body: String,
+    pub html_body: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Local inbox for outgoing email and SMS
+#[derive(Debug, Default)]
+pub struct DevMailbox {
+    messages: RwLock<Vec<DevMessage>>,
+}
+
+impl DevMailbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Captured messages, newest first
+    pub fn messages(&self) -> Vec<DevMessage> {
+        self.messages.read().unwrap().iter().rev().cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<DevMessage> {
+        self.messages.read().unwrap().iter().find(|message| message.id == id).cloned()
+    }
+
+    pub fn clear(&self) {
+        self.messages.write().unwrap().clear();
+    }
+
+    /// Record a message from any stubbed integration, e.g. an app's own
+    /// webhook or payment client in dev mode
+    pub fn capture(&self, channel: &'static str, to: Vec<String>, subject: Option<String>, body: String, html_body: Option<String>) {
+        let message = DevMessage {
+            id: crate::ids::new_id(),
+            channel,
+            to,
+            subject,
+            body,
+            html_body,
+            sent_at: Utc::now(),
+        };
+        tracing::info!(
+            channel,
+            to = ?message.to,
+            subject = message.subject.as_deref().unwrap_or(""),
+            "📬 Captured by dev mailbox, not sent: /dev/mailbox/{}",
+            message.id
+        );
+        self.messages.write().unwrap().push(message);
+    }
+}
+
+#[cfg(feature = "notifications")]
+#[async_trait::async_trait]
+impl crate::notifications::EmailProvider for DevMailbox {
+    async fn send(&self, message: crate::notifications::EmailMessage) -> Result<(), crate::error::ApiError> {
+        let to = message.to.into_iter().chain(message.cc).collect();
+        self.capture("email", to, Some(message.subject), message.body, message.html_body);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "notifications-sms")]
+#[async_trait::async_trait]
+impl crate::notifications::SmsProvider for DevMailbox {
+    async fn send(&self, message: crate::notifications::SmsMessage) -> Result<(), crate::error::ApiError> {
+        self.capture("sms", vec![message.to], None, message.body, None);
+        Ok(())
+    }
+}
+
+/// Upload storage kept in memory and served from `/dev/files`
+#[cfg(feature = "file-uploads")]
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// stored name -> (content type, data)
+    files: RwLock<std::collections::HashMap<String, (String, Vec<u8>)>>,
+}
+
+#[cfg(feature = "file-uploads")]
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn file(&self, stored_name: &str) -> Option<(String, Vec<u8>)> {
+        self.files.read().unwrap().get(stored_name).cloned()
+    }
+}
+
+#[cfg(feature = "file-uploads")]
+#[async_trait::async_trait]
+impl crate::uploads::UploadStorage for MemoryStorage {
+    async fn save(&self, filename: &str, content_type: &str, data: &[u8]) -> Result<crate::uploads::UploadedFile, crate::error::ApiError> {
+        let mut file = self.save_named(&crate::ids::new_id(), content_type, data).await?;
+        file.original_name = filename.to_string();
+        Ok(file)
+    }
+
+    async fn save_named(&self, stored_name: &str, content_type: &str, data: &[u8]) -> Result<crate::uploads::UploadedFile, crate::error::ApiError> {
+        tracing::info!(stored_name, size = data.len(), "🗄️ Stored in dev memory storage: /dev/files/{}", stored_name);
+        self.files
+            .write()
+            .unwrap()
+            .insert(stored_name.to_string(), (content_type.to_string(), data.to_vec()));
+        Ok(crate::uploads::UploadedFile::new(
+            stored_name.to_string(),
+            stored_name.to_string(),
+            content_type.to_string(),
+            data.len(),
+            self.url(stored_name).await,
+        ))
+    }
+
+    async fn read(&self, stored_name: &str) -> Result<Vec<u8>, crate::error::ApiError> {
+        self.file(stored_name)
+            .map(|(_, data)| data)
+            .ok_or_else(|| crate::error::ApiError::NotFound(format!("File '{}' not found", stored_name)))
+    }
+
+    async fn delete(&self, stored_name: &str) -> Result<(), crate::error::ApiError> {
+        self.files.write().unwrap().remove(stored_name);
+        Ok(())
+    }
+
+    async fn url(&self, stored_name: &str) -> String {
+        format!("/dev/files/{}", stored_name)
+    }
+
+    async fn signed_url(&self, stored_name: &str, _ttl: std::time::Duration) -> Result<String, crate::error::ApiError> {
+        Ok(self.url(stored_name).await)
+    }
+}
+
+/// The stubs installed by dev mode
+#[derive(Debug, Clone, Default)]
+pub struct DevIntegrations {
+    mailbox: Arc<DevMailbox>,
+    #[cfg(feature = "file-uploads")]
+    storage: Arc<MemoryStorage>,
+}
+
+impl DevIntegrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mailbox(&self) -> Arc<DevMailbox> {
+        self.mailbox.clone()
+    }
+
+    #[cfg(feature = "file-uploads")]
+    pub fn storage(&self) -> Arc<MemoryStorage> {
+        self.storage.clone()
+    }
+
+    /// Register the stubs, replacing real integrations
+    pub(crate) fn install(&self, dependencies: &mut Dependencies) {
+        dependencies.insert_arc(self.mailbox.clone());
+        #[cfg(feature = "notifications")]
+        dependencies.insert_arc::<dyn crate::notifications::EmailProvider>(self.mailbox.clone());
+        #[cfg(feature = "notifications-sms")]
+        dependencies.insert_arc::<dyn crate::notifications::SmsProvider>(self.mailbox.clone());
+        #[cfg(feature = "file-uploads")]
+        {
+            dependencies.insert_arc(self.storage.clone());
+            dependencies.insert_arc::<dyn crate::uploads::UploadStorage>(self.storage.clone());
+        }
+    }
+
+    pub(crate) fn router(&self) -> Router {
+        let mailbox = self.mailbox.clone();
+        let list = {
+            let mailbox = mailbox.clone();
+            move || async move { Html(render_mailbox(&mailbox.messages())) }
+        };
+        let show = {
+            let mailbox = mailbox.clone();
+            move |Path(id): Path<String>| async move {
+                match mailbox.get(&id) {
+                    Some(message) => Html(render_message(&message)).into_response(),
+                    None => StatusCode::NOT_FOUND.into_response(),
+                }
+            }
+        };
+        let messages = {
+            let mailbox = mailbox.clone();
+            move || async move { Json(mailbox.messages()) }
+        };
+        let clear = move || async move {
+            mailbox.clear();
+            StatusCode::NO_CONTENT
+        };
+
+        let router = Router::new()
+            .route("/dev/mailbox", get(list))
+            .route("/dev/mailbox/messages", get(messages).delete(clear))
+            .route("/dev/mailbox/:id", get(show));
+
+        #[cfg(feature = "file-uploads")]
+        let router = {
+            let storage = self.storage.clone();
+            router.route(
+                "/dev/files/:name",
+                get(move |Path(name): Path<String>| async move { serve_file(&storage, &name) }),
+            )
+        };
+
+        router
+    }
+}
+
+#[cfg(feature = "file-uploads")]
+fn serve_file(storage: &MemoryStorage, name: &str) -> axum::response::Response {
+    match storage.file(name) {
+        Some((content_type, data)) => ([(axum::http::header::CONTENT_TYPE, content_type)], data).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,sans-serif;max-width:960px;margin:2rem auto;padding:0 1rem;color:#1e293b}\
+table{width:100%;border-collapse:collapse}td,th{text-align:left;padding:.5rem;border-bottom:1px solid #e2e8f0}\
+.muted{color:#64748b}pre{white-space:pre-wrap;background:#f8fafc;padding:1rem}iframe{width:100%;height:60vh;border:1px solid #e2e8f0}";
+
+fn render_mailbox(messages: &[DevMessage]) -> String {
+    let rows: String = messages
+        .iter()
+        .map(|message| {
+            format!(
+                "<tr><td class=\"muted\">{}</td><td>{}</td><td>{}</td><td><a href=\"/dev/mailbox/{}\">{}</a></td></tr>",
+                message.sent_at.format("%H:%M:%S"),
+                message.channel,
+                escape(&message.to.join(", ")),
+                escape(&message.id),
+                escape(message.subject.as_deref().unwrap_or(&message.body)),
+            )
+        })
+        .collect();
+    let rows = if rows.is_empty() {
+        "<tr><td colspan=\"4\" class=\"muted\">Nothing sent yet</td></tr>".to_string()
+    } else {
+        rows
+    };
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>Dev mailbox</title><style>{STYLE}</style></head>\
+<body><h1>Dev mailbox</h1><p class=\"muted\">Messages captured instead of sent.</p>\
+<table><tr><th>Sent</th><th>Channel</th><th>To</th><th>Subject</th></tr>{rows}</table></body></html>"
+    )
+}
+
+fn render_message(message: &DevMessage) -> String {
+    let html = message
+        .html_body
+        .as_deref()
+        .map(|html| format!("<h2>HTML</h2><iframe sandbox srcdoc=\"{}\"></iframe>", escape(html)))
+        .unwrap_or_default();
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>{subject}</title><style>{STYLE}</style></head>\
+<body><p><a href=\"/dev/mailbox\">&larr; Mailbox</a></p><h1>{subject}</h1>\
+<p class=\"muted\">{channel} to {to} at {sent_at}</p><h2>Text</h2><pre>{body}</pre>{html}</body></html>",
+        subject = escape(message.subject.as_deref().unwrap_or("(no subject)")),
+        channel = message.channel,
+        to = escape(&message.to.join(", ")),
+        sent_at = message.sent_at.to_rfc3339(),
+        body = escape(&message.body),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::App;
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_dev_mailbox_pages() {
+        let app = App::new().with_dev_integrations();
+        let mailbox = app.dev_integrations().unwrap().mailbox();
+        let router = app.into_router();
+        mailbox.capture("webhook", vec!["https://hooks.example.com".into()], Some("order <created>".into()), "{}".into(), None);
+        let id = mailbox.messages()[0].id.clone();
+
+        let response = router.clone().oneshot(Request::get("/dev/mailbox").body(Body::empty()).unwrap()).await.unwrap();
+        let page = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page = String::from_utf8(page.to_vec()).unwrap();
+        assert!(page.contains("order &lt;created&gt;"));
+        assert!(page.contains(&format!("/dev/mailbox/{}", id)));
+
+        let uri = format!("/dev/mailbox/{}", id);
+        let response = router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router.oneshot(Request::delete("/dev/mailbox/messages").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(mailbox.messages().is_empty());
+    }
+
+    #[cfg(feature = "notifications")]
+    #[tokio::test]
+    async fn test_dev_mailbox_replaces_mailer() {
+        use crate::dependencies::Dep;
+        use crate::notifications::{EmailConfig, EmailMessage, EmailProvider, SmtpEmailProvider};
+        use std::sync::Arc;
+
+        let app = App::new()
+            .provide_arc::<dyn EmailProvider>(Arc::new(SmtpEmailProvider::new(EmailConfig::new())))
+            .route(
+                "/signup",
+                axum::routing::post(|Dep(mailer): Dep<dyn EmailProvider>| async move {
+                    mailer
+                        .send(EmailMessage::new("new@example.com", "Welcome", "Hi there").with_html("<b>Hi</b>"))
+                        .await
+                }),
+            )
+            .with_dev_integrations();
+        let mailbox = app.dev_integrations().unwrap().mailbox();
+        let router = app.into_router();
+
+        let response = router.oneshot(Request::post("/signup").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let messages = mailbox.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!((messages[0].channel, messages[0].to.as_slice()), ("email", &["new@example.com".to_string()][..]));
+        assert_eq!(messages[0].html_body.as_deref(), Some("<b>Hi</b>"));
+    }
+
+    #[cfg(feature = "file-uploads")]
+    #[tokio::test]
+    async fn test_dev_storage_serves_files() {
+        use crate::uploads::UploadStorage;
+
+        let app = App::new().with_dev_integrations();
+        let storage = app.dev_integrations().unwrap().storage();
+        let router = app.into_router();
+
+        let file = storage.save("notes.txt", "text/plain", b"hello").await.unwrap();
+        let response = router.oneshot(Request::get(&file.url).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"hello");
+    }
+}
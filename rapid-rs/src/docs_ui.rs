@@ -0,0 +1,526 @@
+//! Served API documentation: renderer, branding and what's shown
+//!
+//! Set with [`App::with_docs_ui`](crate::App::with_docs_ui) or the `[docs]`
+//! config section:
+//!
+//! ```toml
+//! [docs]
+//! renderer = "redoc"            # swagger-ui (default), redoc or scalar
+//! title = "Acme API"
+//! logo_url = "https://acme.example/logo.svg"
+//! primary_color = "#7c3aed"
+//! dark_mode = true
+//! tag_order = ["users", "billing"]
+//! code_samples = ["curl", "python"]
+//! hide_tags = ["internal"]
+//! hide_paths = ["/admin", "/dev"]
+//! ```
+//!
+//! Hidden operations are left out of `/openapi.json` as well as the docs
+//! page; operations marked `x-internal: true` are always hidden. Redoc and
+//! Scalar load from their CDNs; Swagger UI is embedded with `swagger-ui`.
+
+use axum::{response::Html, routing::get, Json, Router};
+#[cfg(feature = "swagger-ui")]
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::openapi::ApiDocs;
+
+/// Which docs page to serve at `/docs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DocsRenderer {
+    #[default]
+    SwaggerUi,
+    Redoc,
+    Scalar,
+}
+
+/// Languages for generated request samples (`x-codeSamples`)
+const CODE_SAMPLE_LANGUAGES: [&str; 3] = ["curl", "javascript", "python"];
+
+/// How the API docs are presented
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocsUi {
+    pub renderer: DocsRenderer,
+    /// Page title, the spec's title by default
+    pub title: Option<String>,
+    pub logo_url: Option<String>,
+    /// CSS color for links, buttons and highlights
+    pub primary_color: Option<String>,
+    pub dark_mode: bool,
+    /// Tags listed first, in this order; the rest follow alphabetically
+    pub tag_order: Vec<String>,
+    /// Request samples to generate: `curl`, `javascript`, `python`
+    pub code_samples: Vec<String>,
+    /// Operations with any of these tags are hidden
+    pub hide_tags: Vec<String>,
+    /// Paths at or below any of these prefixes are hidden
+    pub hide_paths: Vec<String>,
+}
+
+impl DocsUi {
+    pub fn new(renderer: DocsRenderer) -> Self {
+        Self {
+            renderer,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_logo(mut self, url: impl Into<String>) -> Self {
+        self.logo_url = Some(url.into());
+        self
+    }
+
+    pub fn with_primary_color(mut self, color: impl Into<String>) -> Self {
+        self.primary_color = Some(color.into());
+        self
+    }
+
+    pub fn with_dark_mode(mut self) -> Self {
+        self.dark_mode = true;
+        self
+    }
+
+    pub fn with_tag_order(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.tag_order = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_code_samples(mut self, languages: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.code_samples = languages.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn hide_tag(mut self, tag: impl Into<String>) -> Self {
+        self.hide_tags.push(tag.into());
+        self
+    }
+
+    pub fn hide_path(mut self, prefix: impl Into<String>) -> Self {
+        self.hide_paths.push(prefix.into());
+        self
+    }
+
+    /// The spec as published: hidden operations removed, tags ordered,
+    /// logo and code samples added
+    pub fn prepare_spec(&self, mut spec: Value) -> Value {
+        if let Some(Value::Object(paths)) = spec.get_mut("paths") {
+            paths.retain(|path, _| !self.hide_paths.iter().any(|prefix| under(path, prefix)));
+            for item in paths.values_mut() {
+                if let Value::Object(item) = item {
+                    item.retain(|_, operation| !self.is_hidden(operation));
+                }
+            }
+            paths.retain(|_, item| item.as_object().is_some_and(|item| item.keys().any(|key| is_method(key))));
+        }
+
+        self.order_tags(&mut spec);
+
+        if let Some(logo) = &self.logo_url {
+            spec["info"]["x-logo"] = json!({ "url": logo });
+        }
+
+        let languages: Vec<&str> = self
+            .code_samples
+            .iter()
+            .filter_map(|language| CODE_SAMPLE_LANGUAGES.iter().find(|known| known.eq_ignore_ascii_case(language)).copied())
+            .collect();
+        if !languages.is_empty() {
+            let base = spec
+                .pointer("/servers/0/url")
+                .and_then(Value::as_str)
+                .unwrap_or("http://localhost:3000")
+                .trim_end_matches('/')
+                .to_string();
+            if let Some(Value::Object(paths)) = spec.get_mut("paths") {
+                for (path, item) in paths.iter_mut() {
+                    let Value::Object(item) = item else { continue };
+                    for (method, operation) in item.iter_mut().filter(|(method, _)| is_method(method)) {
+                        let samples: Vec<Value> = languages
+                            .iter()
+                            .map(|language| code_sample(language, method, &format!("{}{}", base, path), operation))
+                            .collect();
+                        operation["x-codeSamples"] = Value::Array(samples);
+                    }
+                }
+            }
+        }
+
+        spec
+    }
+
+    fn is_hidden(&self, operation: &Value) -> bool {
+        if operation.get("x-internal").and_then(Value::as_bool) == Some(true) {
+            return true;
+        }
+        operation
+            .get("tags")
+            .and_then(Value::as_array)
+            .is_some_and(|tags| tags.iter().filter_map(Value::as_str).any(|tag| self.hide_tags.iter().any(|hidden| hidden == tag)))
+    }
+
+    /// Rewrite the top-level `tags` to the configured order, dropping tags
+    /// that no longer have operations
+    fn order_tags(&self, spec: &mut Value) {
+        let mut used: Vec<String> = spec
+            .get("paths")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flat_map(|paths| paths.values())
+            .filter_map(Value::as_object)
+            .flat_map(|item| item.values())
+            .filter_map(|operation| operation.get("tags").and_then(Value::as_array))
+            .flatten()
+            .filter_map(|tag| tag.as_str().map(str::to_string))
+            .collect();
+        used.sort();
+        used.dedup();
+        if used.is_empty() && self.tag_order.is_empty() {
+            return;
+        }
+
+        let mut declared: Map<String, Value> = spec
+            .get("tags")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| Some((tag.get("name")?.as_str()?.to_string(), tag.clone())))
+            .collect();
+        let order = self
+            .tag_order
+            .iter()
+            .filter(|tag| used.contains(tag))
+            .chain(used.iter().filter(|tag| !self.tag_order.contains(tag)));
+        let tags: Vec<Value> = order
+            .map(|name| declared.remove(name).unwrap_or_else(|| json!({ "name": name })))
+            .collect();
+        spec["tags"] = Value::Array(tags);
+    }
+
+    fn page_title(&self, spec: &Value) -> String {
+        self.title
+            .clone()
+            .or_else(|| spec.pointer("/info/title").and_then(Value::as_str).map(str::to_string))
+            .unwrap_or_else(|| "API Docs".to_string())
+    }
+
+    /// CSS layered over Swagger UI for the title, logo and colors
+    #[cfg(feature = "swagger-ui")]
+    fn swagger_css(&self) -> String {
+        let mut css = String::new();
+        if let Some(color) = &self.primary_color {
+            css.push_str(&format!(
+                ".swagger-ui .topbar{{background:{c}}}.swagger-ui .btn.execute,.swagger-ui .btn.authorize{{background:{c};border-color:{c};color:#fff}}.swagger-ui a{{color:{c}}}",
+                c = css_value(color)
+            ));
+        }
+        if let Some(logo) = &self.logo_url {
+            css.push_str(&format!(
+                ".swagger-ui .topbar-wrapper .link svg{{display:none}}.swagger-ui .topbar-wrapper .link{{background:url(\"{}\") no-repeat left center/contain;height:40px;width:160px}}",
+                css_value(logo)
+            ));
+        }
+        if self.dark_mode {
+            css.push_str("html{filter:invert(88%) hue-rotate(180deg)}.swagger-ui img,.swagger-ui .topbar{filter:invert(100%) hue-rotate(180deg)}");
+        }
+        css
+    }
+
+    fn redoc_page(&self, spec: &Value) -> String {
+        let mut theme = json!({});
+        if let Some(color) = &self.primary_color {
+            theme["colors"] = json!({ "primary": { "main": color } });
+        }
+        if self.dark_mode {
+            theme["sidebar"] = json!({ "backgroundColor": "#111827", "textColor": "#e5e7eb" });
+            theme["rightPanel"] = json!({ "backgroundColor": "#030712" });
+        }
+        let options = json!({ "theme": theme, "sortTagsAlphabetically": false });
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>{title}</title>
+    <style>body {{ margin: 0; padding: 0; }}</style>
+</head>
+<body>
+    <div id="redoc"></div>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+    <script>Redoc.init("/openapi.json", {options}, document.getElementById("redoc"));</script>
+</body>
+</html>"#,
+            title = escape_html(&self.page_title(spec)),
+            options = script_json(&options),
+        )
+    }
+
+    fn scalar_page(&self, spec: &Value) -> String {
+        let mut configuration = json!({ "darkMode": self.dark_mode });
+        if let Some(color) = &self.primary_color {
+            configuration["customCss"] = json!(format!(
+                ".light-mode, .dark-mode {{ --scalar-color-accent: {}; }}",
+                css_value(color)
+            ));
+        }
+        let logo = self
+            .logo_url
+            .as_deref()
+            .map(|url| format!(r#"<header style="padding: 12px 24px"><img src="{}" alt="" style="height: 32px"></header>"#, escape_html(url)))
+            .unwrap_or_default();
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>{title}</title>
+</head>
+<body>
+    {logo}
+    <script id="api-reference" data-url="/openapi.json" data-configuration="{configuration}"></script>
+    <script src="https://cdn.jsdelivr.net/npm/@scalar/api-reference"></script>
+</body>
+</html>"#,
+            title = escape_html(&self.page_title(spec)),
+            configuration = escape_html(&configuration.to_string()),
+        )
+    }
+}
+
+/// Whether `path` is `prefix` or below it
+fn under(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn is_method(key: &str) -> bool {
+    matches!(key, "get" | "put" | "post" | "delete" | "options" | "head" | "patch" | "trace")
+}
+
+fn code_sample(language: &str, method: &str, url: &str, operation: &Value) -> Value {
+    let method = method.to_uppercase();
+    let body = operation.get("requestBody").is_some();
+    let bearer = operation
+        .get("security")
+        .and_then(Value::as_array)
+        .is_some_and(|security| security.iter().any(|requirement| requirement.get("bearerAuth").is_some()));
+
+    let (label, source) = match language {
+        "curl" => {
+            let mut source = format!("curl -X {} '{}'", method, url);
+            if bearer {
+                source.push_str(" \\\n  -H 'Authorization: Bearer $TOKEN'");
+            }
+            if body {
+                source.push_str(" \\\n  -H 'Content-Type: application/json' \\\n  -d '{}'");
+            }
+            ("cURL", source)
+        }
+        "javascript" => {
+            let mut headers = Vec::new();
+            if bearer {
+                headers.push("\"Authorization\": `Bearer ${token}`".to_string());
+            }
+            if body {
+                headers.push("\"Content-Type\": \"application/json\"".to_string());
+            }
+            let mut source = format!("const response = await fetch(\"{}\", {{\n  method: \"{}\"", url, method);
+            if !headers.is_empty() {
+                source.push_str(&format!(",\n  headers: {{ {} }}", headers.join(", ")));
+            }
+            if body {
+                source.push_str(",\n  body: JSON.stringify({})");
+            }
+            source.push_str("\n});\nconst data = await response.json();");
+            ("JavaScript", source)
+        }
+        _ => {
+            let mut arguments = vec![format!("\"{}\"", url)];
+            if bearer {
+                arguments.push("headers={\"Authorization\": f\"Bearer {token}\"}".to_string());
+            }
+            if body {
+                arguments.push("json={}".to_string());
+            }
+            let source = format!(
+                "import requests\n\nresponse = requests.{}({})\ndata = response.json()",
+                method.to_lowercase(),
+                arguments.join(", ")
+            );
+            ("Python", source)
+        }
+    };
+    json!({ "lang": language, "label": label, "source": source })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Keep configured values from closing the surrounding CSS rule or string
+fn css_value(value: &str) -> String {
+    value.chars().filter(|c| !matches!(c, '{' | '}' | ';' | '"' | '<' | '>' | '\\')).collect()
+}
+
+/// JSON safe to embed in a `<script>` block
+fn script_json(value: &Value) -> String {
+    value.to_string().replace('<', "\\u003c")
+}
+
+/// `/openapi.json` (also at the older `/api-docs/openapi.json`) and the docs page at `/docs`
+pub(crate) fn router(docs: &ApiDocs, ui: &DocsUi) -> Router {
+    let spec = ui.prepare_spec(docs.to_json());
+    let serve_spec = {
+        let spec = spec.clone();
+        get(move || async move { Json(spec) })
+    };
+    let page = |html: String| get(move || async move { Html(html) });
+
+    match ui.renderer {
+        DocsRenderer::Redoc => Router::new()
+            .route("/openapi.json", serve_spec.clone())
+            .route("/api-docs/openapi.json", serve_spec)
+            .route("/docs", page(ui.redoc_page(&spec))),
+        DocsRenderer::Scalar => Router::new()
+            .route("/openapi.json", serve_spec.clone())
+            .route("/api-docs/openapi.json", serve_spec)
+            .route("/docs", page(ui.scalar_page(&spec))),
+        #[cfg(feature = "swagger-ui")]
+        DocsRenderer::SwaggerUi => {
+            let config = utoipa_swagger_ui::Config::new(["/openapi.json"])
+                .request_snippets_enabled(!ui.code_samples.is_empty());
+            let swagger = Router::from(
+                utoipa_swagger_ui::SwaggerUi::new("/docs")
+                    .external_url_unchecked("/openapi.json", spec.clone())
+                    .config(config),
+            );
+            let head = format!(
+                "<title>{}</title><style>{}</style></head>",
+                escape_html(&ui.page_title(&spec)),
+                ui.swagger_css()
+            );
+            swagger
+                .layer(axum::middleware::map_response(move |response| {
+                    let head = head.clone();
+                    async move { brand_swagger_index(response, &head).await }
+                }))
+                .route("/api-docs/openapi.json", serve_spec)
+        }
+        #[cfg(not(feature = "swagger-ui"))]
+        DocsRenderer::SwaggerUi => Router::new()
+            .route("/openapi.json", serve_spec.clone())
+            .route("/api-docs/openapi.json", serve_spec),
+    }
+}
+
+/// Put the configured title and CSS into Swagger UI's `index.html`
+#[cfg(feature = "swagger-ui")]
+async fn brand_swagger_index(response: axum::response::Response, head: &str) -> axum::response::Response {
+    let is_html = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let html = String::from_utf8_lossy(&bytes).replace("<title>Swagger UI</title>", "").replace("</head>", head);
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    axum::response::Response::from_parts(parts, axum::body::Body::from(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openapi::RouteDoc;
+
+    fn docs() -> ApiDocs {
+        let mut docs = ApiDocs::default();
+        docs.add_route("/users", RouteDoc::get().tag("users"));
+        docs.add_route("/users", RouteDoc::post().tag("users").request::<crate::error::ErrorResponse>());
+        docs.add_route("/billing/invoices", RouteDoc::get().tag("billing"));
+        docs.add_route("/ops/reindex", RouteDoc::post().tag("internal"));
+        docs.add_route("/admin/stats", RouteDoc::get().tag("admin"));
+        docs
+    }
+
+    #[test]
+    fn test_prepare_spec_hides_orders_and_samples() {
+        let ui = DocsUi::new(DocsRenderer::Redoc)
+            .with_logo("https://example.com/logo.svg")
+            .with_tag_order(["users"])
+            .with_code_samples(["curl", "python", "cobol"])
+            .hide_tag("internal")
+            .hide_path("/admin");
+        let spec = ui.prepare_spec(docs().to_json());
+
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/users") && paths.contains_key("/billing/invoices"));
+        assert!(!paths.contains_key("/ops/reindex") && !paths.contains_key("/admin/stats"));
+        assert_eq!(spec["tags"], json!([{ "name": "users" }, { "name": "billing" }]));
+        assert_eq!(spec["info"]["x-logo"]["url"], "https://example.com/logo.svg");
+
+        let samples = spec["paths"]["/users"]["post"]["x-codeSamples"].as_array().unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0]["lang"], "curl");
+        assert!(samples[0]["source"].as_str().unwrap().contains("-d '{}'"));
+        assert!(samples[1]["source"].as_str().unwrap().contains("requests.post(\"http://localhost:3000/users\", json={})"));
+    }
+
+    #[test]
+    fn test_docs_ui_from_config() {
+        let ui: DocsUi = serde_json::from_value(json!({
+            "renderer": "scalar",
+            "title": "Acme <API>",
+            "primary_color": "#7c3aed",
+            "hide_paths": ["/admin"]
+        }))
+        .unwrap();
+        assert_eq!(ui.renderer, DocsRenderer::Scalar);
+        let page = ui.scalar_page(&json!({}));
+        assert!(page.contains("<title>Acme &lt;API&gt;</title>"));
+        assert!(page.contains("--scalar-color-accent: #7c3aed"));
+    }
+
+    #[cfg(feature = "swagger-ui")]
+    #[tokio::test]
+    async fn test_swagger_ui_branding() {
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+
+        let ui = DocsUi::default().with_title("Acme API").with_primary_color("#7c3aed").hide_path("/admin");
+        let router = router(&docs(), &ui);
+
+        let response = router.clone().oneshot(Request::get("/docs/").body(Body::empty()).unwrap()).await.unwrap();
+        let page = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page = String::from_utf8(page.to_vec()).unwrap();
+        assert!(page.contains("<title>Acme API</title>"));
+        assert!(!page.contains("<title>Swagger UI</title>"));
+        assert!(page.contains("background:#7c3aed"));
+
+        let response = router.oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap()).await.unwrap();
+        let spec = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: Value = serde_json::from_slice(&spec).unwrap();
+        assert!(spec["paths"].get("/admin/stats").is_none());
+    }
+}
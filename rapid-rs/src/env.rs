@@ -0,0 +1,236 @@
+//! Typed configuration from environment variables
+//!
+//! `#[derive(FromEnv)]` reads every field at once and reports all missing or
+//! malformed variables together, so a misconfigured deployment fails with one
+//! readable message instead of one restart per typo.
+//!
+//! ```rust,ignore
+//! #[derive(FromEnv)]
+//! #[env(prefix = "APP_")]
+//! struct Settings {
+//!     #[env(name = "DATABASE_URL", secret)]
+//!     database_url: String,
+//!     #[env(default = "8080")]
+//!     port: u16,              // APP_PORT
+//!     sentry_dsn: Option<String>,
+//! }
+//!
+//! let settings = Settings::from_env().unwrap_or_else(|e| panic!("{e}"));
+//! println!("{settings:?}"); // database_url: [REDACTED]
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+pub use rapid_rs_macros::FromEnv;
+
+/// Load a value from environment variables
+///
+/// Usually derived; see the [module docs](self).
+pub trait FromEnv: Sized {
+    /// Load using `lookup` to read variables, e.g. a map in tests
+    fn from_env_with(lookup: &dyn Fn(&str) -> Option<String>) -> Result<Self, EnvError>;
+
+    /// Load from the process environment
+    fn from_env() -> Result<Self, EnvError> {
+        Self::from_env_with(&|var| std::env::var(var).ok())
+    }
+}
+
+/// One problem with one variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVarError {
+    pub var: String,
+    pub message: String,
+}
+
+/// Every problem found while loading a config
+#[derive(Debug, Clone)]
+pub struct EnvError {
+    pub errors: Vec<EnvVarError>,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.errors.len();
+        write!(
+            f,
+            "Invalid environment configuration ({} {}):",
+            count,
+            if count == 1 { "problem" } else { "problems" }
+        )?;
+        for error in &self.errors {
+            write!(f, "\n  - {}: {}", error.var, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// Reads variables and collects errors instead of stopping at the first one
+pub struct EnvReader<'a> {
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+    errors: Vec<EnvVarError>,
+}
+
+impl<'a> EnvReader<'a> {
+    pub fn new(lookup: &'a dyn Fn(&str) -> Option<String>) -> Self {
+        Self {
+            lookup,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parse `var`, falling back to `default`; records an error if neither works
+    pub fn required<T>(&mut self, var: &str, default: Option<&str>, secret: bool) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match (self.lookup)(var) {
+            Some(raw) => self.parse(var, &raw, secret),
+            None => match default {
+                Some(default) => self.parse(var, default, secret),
+                None => {
+                    self.error(var, "is required but not set".to_string());
+                    None
+                }
+            },
+        }
+    }
+
+    /// Parse `var` if it is set
+    pub fn optional<T>(&mut self, var: &str, secret: bool) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let raw = (self.lookup)(var)?;
+        self.parse(var, &raw, secret)
+    }
+
+    /// `Err` with every recorded problem, if there were any
+    pub fn finish(self) -> Result<(), EnvError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(EnvError { errors: self.errors })
+        }
+    }
+
+    fn parse<T>(&mut self, var: &str, raw: &str, secret: bool) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match raw.parse() {
+            Ok(value) => Some(value),
+            Err(e) if secret => {
+                self.error(var, format!("invalid value: {}", e));
+                None
+            }
+            Err(e) => {
+                self.error(var, format!("invalid value '{}': {}", raw, e));
+                None
+            }
+        }
+    }
+
+    fn error(&mut self, var: &str, message: String) {
+        self.errors.push(EnvVarError {
+            var: var.to_string(),
+            message,
+        });
+    }
+}
+
+/// Run `validator` rules on a loaded config, reporting by variable name
+///
+/// `vars` maps field names to the variables they were read from.
+pub fn validate<T: validator::Validate>(config: &T, vars: &[(&str, &str)]) -> Result<(), EnvError> {
+    let Err(validation_errors) = config.validate() else {
+        return Ok(());
+    };
+
+    let mut errors: Vec<EnvVarError> = validation_errors
+        .field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            let var = vars
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, var)| var.to_string())
+                .unwrap_or_else(|| field.to_string());
+            errors.iter().map(move |error| EnvVarError {
+                var: var.clone(),
+                message: error
+                    .message
+                    .as_ref()
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| format!("failed validation '{}'", error.code)),
+            })
+        })
+        .collect();
+    errors.sort_by(|a, b| a.var.cmp(&b.var));
+
+    Err(EnvError { errors })
+}
+
+/// Stand-in printed for secret fields
+pub struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use validator::Validate;
+
+    #[derive(FromEnv, Validate)]
+    #[env(prefix = "APP_", validate)]
+    struct Settings {
+        #[env(name = "DATABASE_URL", secret)]
+        database_url: String,
+        #[env(default = "8080")]
+        #[validate(range(min = 1024))]
+        port: u16,
+        workers: Option<usize>,
+    }
+
+    fn load(vars: &[(&str, &str)]) -> Result<Settings, EnvError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Settings::from_env_with(&|var| vars.get(var).cloned())
+    }
+
+    #[test]
+    fn test_from_env() {
+        let settings = load(&[("DATABASE_URL", "postgres://u:hunter2@db/app"), ("APP_WORKERS", "4")]).unwrap();
+        assert_eq!(settings.database_url, "postgres://u:hunter2@db/app");
+        assert_eq!(settings.port, 8080);
+        assert_eq!(settings.workers, Some(4));
+
+        let debug = format!("{:?}", settings);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_errors_are_aggregated() {
+        let err = load(&[("APP_PORT", "http"), ("APP_WORKERS", "many")]).unwrap_err();
+        let vars: Vec<&str> = err.errors.iter().map(|e| e.var.as_str()).collect();
+        assert_eq!(vars, ["DATABASE_URL", "APP_PORT", "APP_WORKERS"]);
+        assert!(err.to_string().starts_with("Invalid environment configuration (3 problems):"));
+
+        let err = load(&[("DATABASE_URL", "postgres://db"), ("APP_PORT", "80")]).unwrap_err();
+        assert_eq!(err.errors[0].var, "APP_PORT");
+    }
+}
@@ -4,9 +4,27 @@ use axum::{
     Json,
 };
 use serde::Serialize;
+use std::sync::Arc;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 /// Standard API error type
+///
+/// Domain errors that don't fit a built-in variant can use
+/// [`ApiError::Custom`], typically through a `From` impl:
+///
+/// ```rust,ignore
+/// impl From<OrderError> for ApiError {
+///     fn from(error: OrderError) -> Self {
+///         match error {
+///             OrderError::OutOfStock { sku } => {
+///                 ApiError::custom(StatusCode::CONFLICT, "OUT_OF_STOCK", "Item is out of stock")
+///                     .with_details(json!({ "sku": sku }))
+///             }
+///         }
+///     }
+/// }
+/// ```
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("Not found: {0}")]
@@ -27,42 +45,148 @@ pub enum ApiError {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+
+    /// Any status, with a machine-readable `code` and optional structured details
+    #[error("{message}")]
+    Custom {
+        status: StatusCode,
+        code: String,
+        message: String,
+        details: serde_json::Value,
+    },
+}
+
+/// Attach structured details to an error response
+pub trait WithDetails {
+    /// Add `details` to the response body; the status and code stay the same
+    fn with_details(self, details: impl Serialize) -> Self;
+}
+
+impl WithDetails for ApiError {
+    fn with_details(self, details: impl Serialize) -> Self {
+        let details = serde_json::to_value(details).unwrap_or_default();
+        match self {
+            ApiError::Custom { status, code, message, .. } => ApiError::Custom { status, code, message, details },
+            error => ApiError::Custom {
+                status: error.status_code(),
+                code: error.error_code().to_string(),
+                message: error.to_string(),
+                details,
+            },
+        }
+    }
+}
+
+impl<T> WithDetails for Result<T, ApiError> {
+    fn with_details(self, details: impl Serialize) -> Self {
+        self.map_err(|error| error.with_details(details))
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl From<anyhow::Error> for ApiError {
+    /// Keeps an `ApiError` or `sqlx::Error` at the root; anything else is a 500
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ApiError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        match error.downcast::<sqlx::Error>() {
+            Ok(error) => ApiError::DatabaseError(error),
+            Err(error) => ApiError::InternalServerError(error.to_string()),
+        }
+    }
 }
 
 impl ApiError {
-    fn status_code(&self) -> StatusCode {
+    /// Error with its own status and `code`, for domain errors
+    pub fn custom(status: StatusCode, code: impl Into<String>, message: impl Into<String>) -> Self {
+        ApiError::Custom {
+            status,
+            code: code.into(),
+            message: message.into(),
+            details: serde_json::Value::Null,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
         match self {
             ApiError::NotFound(_) => StatusCode::NOT_FOUND,
             ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
             ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
             ApiError::Forbidden => StatusCode::FORBIDDEN,
             ApiError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Custom { status, .. } => *status,
         }
     }
 
-    fn error_code(&self) -> &str {
+    /// Status and description for the variant named `variant`, for API docs
+    pub(crate) fn documented_status(variant: &str) -> Option<(StatusCode, &'static str)> {
+        let status = match variant {
+            "NotFound" => (StatusCode::NOT_FOUND, "Not found"),
+            "BadRequest" => (StatusCode::BAD_REQUEST, "Bad request"),
+            "Unauthorized" => (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            "Forbidden" => (StatusCode::FORBIDDEN, "Forbidden"),
+            "ValidationError" => (StatusCode::UNPROCESSABLE_ENTITY, "Validation error"),
+            "ServiceUnavailable" => (StatusCode::SERVICE_UNAVAILABLE, "Service unavailable"),
+            "InternalServerError" | "DatabaseError" => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
+            _ => return None,
+        };
+        Some(status)
+    }
+
+    pub fn error_code(&self) -> &str {
         match self {
             ApiError::NotFound(_) => "NOT_FOUND",
             ApiError::BadRequest(_) => "BAD_REQUEST",
             ApiError::Unauthorized => "UNAUTHORIZED",
             ApiError::Forbidden => "FORBIDDEN",
             ApiError::ValidationError(_) => "VALIDATION_ERROR",
+            ApiError::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
             ApiError::InternalServerError(_) => "INTERNAL_SERVER_ERROR",
             ApiError::DatabaseError(_) => "DATABASE_ERROR",
+            ApiError::Custom { code, .. } => code,
         }
     }
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
+/// Body of every `ApiError` response
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorResponse {
     code: String,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    details: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    details: Option<serde_json::Value>,
+    /// Set when the app tracks request ids, see [`RequestId`](crate::middleware::RequestId)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// Rewrites errors before they become responses, see [`App::map_errors`](crate::App::map_errors)
+pub(crate) type ErrorMapper = Arc<dyn Fn(&ApiError) -> Option<ApiError> + Send + Sync>;
+
+/// The error a response was rendered from, kept for error mappers
+#[derive(Clone)]
+pub(crate) struct RenderedError(pub(crate) Arc<ApiError>);
+
+/// Re-render `response` if a mapper replaces the error it came from
+pub(crate) fn map_error_response(mappers: &[ErrorMapper], response: Response) -> Response {
+    let Some(RenderedError(error)) = response.extensions().get::<RenderedError>().cloned() else {
+        return response;
+    };
+    mappers
+        .iter()
+        .find_map(|mapper| mapper(&error))
+        .map_or(response, IntoResponse::into_response)
 }
 
 impl IntoResponse for ApiError {
@@ -79,15 +203,84 @@ impl IntoResponse for ApiError {
             "API error occurred"
         );
 
+        let details = match &self {
+            ApiError::Custom { details, .. } if !details.is_null() => Some(details.clone()),
+            _ => None,
+        };
         let error_response = ErrorResponse {
             code: error_code,
             message,
-            details: None,
+            details,
+            request_id: crate::middleware::RequestId::current().map(|id| id.0),
         };
 
-        (status_code, Json(error_response)).into_response()
+        let mut response = (status_code, Json(error_response)).into_response();
+        response.extensions_mut().insert(RenderedError(Arc::new(self)));
+        response
     }
 }
 
 /// Convenient Result type for API handlers
 pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    async fn body_of(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_custom_error_with_details() {
+        let error = ApiError::custom(StatusCode::CONFLICT, "OUT_OF_STOCK", "Item is out of stock")
+            .with_details(serde_json::json!({ "sku": "A-1" }));
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = body_of(response).await;
+        assert_eq!(body["code"], "OUT_OF_STOCK");
+        assert_eq!(body["message"], "Item is out of stock");
+        assert_eq!(body["details"]["sku"], "A-1");
+
+        let error = ApiError::NotFound("order 7".to_string()).with_details(["order_id", "7"]);
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(error.error_code(), "NOT_FOUND");
+        let body = body_of(error.into_response()).await;
+        assert_eq!(body["details"][1], "7");
+    }
+
+    #[tokio::test]
+    async fn test_error_mapper() {
+        let router = crate::App::new()
+            .route("/missing", get(|| async { Err::<(), _>(ApiError::NotFound("user".to_string())) }))
+            .route("/forbidden", get(|| async { Err::<(), _>(ApiError::Forbidden) }))
+            .map_errors(|error| match error {
+                ApiError::NotFound(what) => Some(ApiError::custom(
+                    StatusCode::GONE,
+                    "GONE",
+                    format!("{} was removed", what),
+                )),
+                _ => None,
+            })
+            .into_router();
+
+        let response = router.clone().oneshot(Request::get("/missing").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+        assert_eq!(body_of(response).await["message"], "user was removed");
+
+        let response = router.oneshot(Request::get("/forbidden").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_from_anyhow() {
+        let error: ApiError = anyhow::Error::new(ApiError::Forbidden).into();
+        assert!(matches!(error, ApiError::Forbidden));
+        let error: ApiError = anyhow::anyhow!("disk full").into();
+        assert!(matches!(error, ApiError::InternalServerError(message) if message == "disk full"));
+    }
+}
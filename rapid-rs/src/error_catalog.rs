@@ -0,0 +1,163 @@
+//! Every error code the API can return
+//!
+//! The catalog lists the `code` values of error bodies with their HTTP status
+//! and meaning, so clients can map them to messages without scraping docs.
+//! It holds the framework's codes for the enabled features, plus the app's
+//! own domain errors registered with [`App::with_error_code`](crate::App::with_error_code):
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .auto_configure()
+//!     .with_error_code(StatusCode::CONFLICT, "OUT_OF_STOCK", "The item is out of stock")
+//! ```
+//!
+//! The served OpenAPI document carries it as the `ErrorCode` schema (a string
+//! enum) and in full under `components.x-error-codes`.
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// One error code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorCode {
+    pub code: String,
+    pub status: u16,
+    pub description: String,
+    /// Where it comes from: `core`, a feature such as `auth`, or `app`
+    pub source: String,
+}
+
+/// Error codes by name
+#[derive(Debug, Clone)]
+pub struct ErrorCatalog {
+    codes: BTreeMap<String, ErrorCode>,
+}
+
+impl Default for ErrorCatalog {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ErrorCatalog {
+    /// No codes at all
+    pub fn empty() -> Self {
+        Self { codes: BTreeMap::new() }
+    }
+
+    /// The codes rapid-rs itself returns with the enabled features
+    pub fn builtin() -> Self {
+        let mut catalog = Self::empty();
+        let mut add = |source: &str, status: StatusCode, code: &str, description: &str| {
+            catalog.insert(source, status, code, description);
+        };
+
+        add("core", StatusCode::NOT_FOUND, "NOT_FOUND", "The resource does not exist");
+        add("core", StatusCode::BAD_REQUEST, "BAD_REQUEST", "The request is malformed");
+        add("core", StatusCode::UNAUTHORIZED, "UNAUTHORIZED", "Authentication is required");
+        add("core", StatusCode::FORBIDDEN, "FORBIDDEN", "The caller may not do this");
+        add("core", StatusCode::UNPROCESSABLE_ENTITY, "VALIDATION_ERROR", "The payload broke validation rules; see `errors`");
+        add("core", StatusCode::SERVICE_UNAVAILABLE, "SERVICE_UNAVAILABLE", "A dependency is down; retry later");
+        add("core", StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_SERVER_ERROR", "Unexpected server failure");
+        add("core", StatusCode::INTERNAL_SERVER_ERROR, "DATABASE_ERROR", "The database query failed");
+        add("core", StatusCode::BAD_REQUEST, "INVALID_JSON", "The JSON body does not fit the expected type");
+        add("core", StatusCode::BAD_REQUEST, "INVALID_FORM", "The form body does not fit the expected type");
+        add("core", StatusCode::BAD_REQUEST, "INVALID_QUERY", "The query string does not fit the expected type");
+        add("core", StatusCode::PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE", "The request body exceeds the size limit");
+        add("core", StatusCode::REQUEST_TIMEOUT, "REQUEST_TIMEOUT", "The request took longer than its deadline");
+        add("core", StatusCode::GONE, "ENDPOINT_RETIRED", "The endpoint is past its sunset date");
+        add("core", StatusCode::BAD_REQUEST, "UNSUPPORTED_API_VERSION", "The requested API version is not served");
+
+        #[cfg(feature = "auth")]
+        {
+            add("auth", StatusCode::UNAUTHORIZED, "MISSING_TOKEN", "No credentials were sent");
+            add("auth", StatusCode::UNAUTHORIZED, "INVALID_TOKEN", "The credentials are invalid or expired");
+            add("auth", StatusCode::INTERNAL_SERVER_ERROR, "AUTH_ERROR", "Authentication failed on the server");
+        }
+
+        #[cfg(feature = "multi-tenancy")]
+        add("multi-tenancy", StatusCode::BAD_REQUEST, "TENANT_REQUIRED", "No tenant could be resolved for the request");
+
+        #[cfg(feature = "rate-limit")]
+        add("rate-limit", StatusCode::TOO_MANY_REQUESTS, "RATE_LIMIT_EXCEEDED", "Too many requests; see `Retry-After`");
+
+        catalog
+    }
+
+    fn insert(&mut self, source: &str, status: StatusCode, code: &str, description: &str) {
+        self.codes.insert(
+            code.to_string(),
+            ErrorCode {
+                code: code.to_string(),
+                status: status.as_u16(),
+                description: description.to_string(),
+                source: source.to_string(),
+            },
+        );
+    }
+
+    /// Add a domain error; replaces a code of the same name
+    pub fn with_code(mut self, status: StatusCode, code: impl Into<String>, description: impl Into<String>) -> Self {
+        self.add(status, code, description);
+        self
+    }
+
+    pub fn add(&mut self, status: StatusCode, code: impl Into<String>, description: impl Into<String>) {
+        self.insert("app", status, &code.into(), &description.into());
+    }
+
+    pub fn get(&self, code: &str) -> Option<&ErrorCode> {
+        self.codes.get(code)
+    }
+
+    /// Codes in alphabetical order
+    pub fn codes(&self) -> impl Iterator<Item = &ErrorCode> {
+        self.codes.values()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!(self.codes().collect::<Vec<_>>())
+    }
+
+    /// Add the catalog to an OpenAPI document's components
+    pub(crate) fn apply(&self, document: &mut Value) {
+        let description = self
+            .codes()
+            .map(|code| format!("- `{}` ({}): {}", code.code, code.status, code.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let components = &mut document["components"];
+        components["schemas"]["ErrorCode"] = json!({
+            "type": "string",
+            "description": description,
+            "enum": self.codes().map(|code| &code.code).collect::<Vec<_>>(),
+        });
+        components["x-error-codes"] = self.to_json();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let catalog = ErrorCatalog::builtin().with_code(StatusCode::CONFLICT, "OUT_OF_STOCK", "Item is out of stock");
+        assert_eq!(catalog.get("NOT_FOUND").unwrap().status, 404);
+        assert_eq!(catalog.get("OUT_OF_STOCK").unwrap().source, "app");
+
+        let codes: Vec<_> = catalog.codes().map(|code| code.code.as_str()).collect();
+        let mut sorted = codes.clone();
+        sorted.sort();
+        assert_eq!(codes, sorted);
+
+        let mut document = json!({ "openapi": "3.1.0" });
+        catalog.apply(&mut document);
+        let schema = &document["components"]["schemas"]["ErrorCode"];
+        assert!(schema["enum"].as_array().unwrap().contains(&json!("OUT_OF_STOCK")));
+        assert!(schema["description"].as_str().unwrap().contains("`OUT_OF_STOCK` (409)"));
+        assert_eq!(document["components"]["x-error-codes"], catalog.to_json());
+    }
+}
@@ -0,0 +1,171 @@
+//! In-process event bus
+//!
+//! Parts of an app publish [`Event`]s on an [`EventBus`]; subscribers such as
+//! cache invalidation or search indexing receive every event on the topics
+//! they care about. With the `database` feature, [`ChangeCapture`] publishes
+//! row changes from PostgreSQL tables and [`Projector`] maintains read models
+//! from them.
+//!
+//! ```rust,ignore
+//! let bus = EventBus::new();
+//! let mut orders = bus.subscribe_prefix("db.orders.");
+//!
+//! tokio::spawn(async move {
+//!     while let Some(event) = orders.recv().await {
+//!         cache.invalidate(event.key.as_deref().unwrap_or_default()).await;
+//!     }
+//! });
+//!
+//! bus.publish(Event::new("orders.shipped", json!({"id": 42})));
+//! ```
+
+#[cfg(feature = "database")]
+pub mod cdc;
+
+#[cfg(feature = "database")]
+pub mod projections;
+
+#[cfg(feature = "database")]
+pub use cdc::{ChangeCapture, ChangeEvent, ChangeOp, ChangePosition};
+#[cfg(feature = "database")]
+pub use projections::{Projection, ProjectionStatus, Projector};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Buffered events before slow subscribers start skipping
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Something that happened, published on a topic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: String,
+    /// Dotted name, e.g. `orders.shipped` or `db.orders.update`
+    pub topic: String,
+    /// Entity the event is about, if any
+    pub key: Option<String>,
+    pub payload: serde_json::Value,
+    pub at: DateTime<Utc>,
+}
+
+impl Event {
+    pub fn new(topic: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self {
+            id: crate::ids::new_id(),
+            topic: topic.into(),
+            key: None,
+            payload,
+            at: Utc::now(),
+        }
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    pub fn with_time(mut self, at: DateTime<Utc>) -> Self {
+        self.at = at;
+        self
+    }
+}
+
+/// Fans events out to every subscriber in this process
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Buffer at most `capacity` events for subscribers that fall behind
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish an event, returning how many subscribers will receive it
+    pub fn publish(&self, event: Event) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Receive every event published from now on
+    pub fn subscribe(&self) -> EventSubscriber {
+        EventSubscriber {
+            receiver: self.sender.subscribe(),
+            prefix: None,
+        }
+    }
+
+    /// Receive events whose topic starts with `prefix`
+    pub fn subscribe_prefix(&self, prefix: impl Into<String>) -> EventSubscriber {
+        EventSubscriber {
+            receiver: self.sender.subscribe(),
+            prefix: Some(prefix.into()),
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receiving end of an [`EventBus`] subscription
+#[derive(Debug)]
+pub struct EventSubscriber {
+    receiver: broadcast::Receiver<Event>,
+    prefix: Option<String>,
+}
+
+impl EventSubscriber {
+    /// Next matching event, or `None` once the bus is dropped
+    ///
+    /// A subscriber that falls more than the bus capacity behind skips the
+    /// oldest events and logs a warning.
+    pub async fn recv(&mut self) -> Option<Event> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.matches(&event) => return Some(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Event subscriber fell behind, skipping events");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        self.prefix.as_ref().is_none_or(|prefix| event.topic.starts_with(prefix.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_prefix() {
+        let bus = EventBus::new();
+        let mut all = bus.subscribe();
+        let mut orders = bus.subscribe_prefix("orders.");
+
+        bus.publish(Event::new("users.created", serde_json::json!({"id": 1})));
+        assert_eq!(bus.publish(Event::new("orders.shipped", serde_json::json!({"id": 2})).with_key("2")), 2);
+
+        assert_eq!(all.recv().await.unwrap().topic, "users.created");
+        assert_eq!(all.recv().await.unwrap().topic, "orders.shipped");
+        let event = orders.recv().await.unwrap();
+        assert_eq!(event.topic, "orders.shipped");
+        assert_eq!(event.key.as_deref(), Some("2"));
+
+        drop(bus);
+        assert!(orders.recv().await.is_none());
+    }
+}
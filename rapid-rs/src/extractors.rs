@@ -1,15 +1,27 @@
 use axum::{
     async_trait,
-    extract::{FromRequest, Request},
-    http::StatusCode,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{de::DeserializeOwned, Serialize};
-use validator::Validate;
+use std::sync::Arc;
+use validator::{Validate, ValidationError};
+
+use crate::i18n::{self, SharedTranslator, Translator};
+use crate::dependencies::Dependencies;
+use crate::error::ApiError;
+use crate::middleware::RequestId;
+use crate::query::{self, QueryError, QueryMode};
+use crate::validation::{self, ValidatorRegistry};
 
 /// Extractor that deserializes and validates JSON payloads
 ///
+/// Errors in nested structs and lists (`#[validate(nested)]`) are reported
+/// with their path, e.g. `items[2].price`.
+///
 /// # Example
 ///
 /// ```rust,ignore
@@ -38,66 +50,722 @@ struct ValidationErrorResponse {
     code: String,
     message: String,
     errors: Vec<ValidationFieldError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
-#[derive(Serialize)]
-struct ValidationFieldError {
+#[derive(Serialize, Default)]
+pub(crate) struct ValidationFieldError {
     field: String,
+    code: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<serde_json::Value>,
+    /// Type the payload should have had, for `INVALID_JSON` errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+}
+
+impl ValidationFieldError {
+    /// Describe why the body could not be deserialized
+    ///
+    /// `field` is the path to the offending value (`items[2].price`), or
+    /// empty when the body is not valid JSON at all.
+    fn from_json_error(path: String, inner: &serde_json::Error) -> Self {
+        let (line, column) = (inner.line(), inner.column());
+        let text = inner.to_string();
+        let message = text
+            .strip_suffix(&format!(" at line {} column {}", line, column))
+            .unwrap_or(&text)
+            .to_string();
+
+        let (field, code) = if inner.is_syntax() || inner.is_eof() {
+            (path, "syntax")
+        } else if let Some(name) = quoted_name(&message, "missing field `") {
+            (join_path(&path, name), "missing_field")
+        } else if let Some(name) = quoted_name(&message, "unknown field `") {
+            (join_path(&path, name), "unknown_field")
+        } else if message.starts_with("invalid type") {
+            (path, "invalid_type")
+        } else {
+            (path, "invalid_value")
+        };
+
+        let expected = match code {
+            "invalid_type" | "invalid_value" => message
+                .rsplit_once(", expected ")
+                .map(|(_, expected)| expected.to_string()),
+            _ => None,
+        };
+
+        Self {
+            field,
+            code: code.to_string(),
+            message,
+            expected,
+            line: (line > 0).then_some(line),
+            column: (line > 0).then_some(column),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn new(field: impl Into<String>, code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    fn from_form_error(error: serde_path_to_error::Error<serde_urlencoded::de::Error>) -> Self {
+        let path = error.path().to_string();
+        Self::from_value_error(path, error.into_inner().to_string())
+    }
+
+    fn from_query_error(error: serde_path_to_error::Error<QueryError>) -> Self {
+        let path = error.path().to_string();
+        Self::from_value_error(path, error.into_inner().to_string())
+    }
+
+    /// Error for a form or query value at `path` that doesn't fit its type
+    fn from_value_error(path: String, message: String) -> Self {
+        let path = if path == "." { String::new() } else { path };
+        let (field, code) = if let Some(name) = quoted_name(&message, "missing field `") {
+            (join_path(&path, name), "missing_field")
+        } else if let Some(name) = quoted_name(&message, "unknown field `") {
+            (join_path(&path, name), "unknown_field")
+        } else {
+            (path, "invalid_value")
+        };
+
+        Self {
+            field,
+            code: code.to_string(),
+            message,
+            ..Default::default()
+        }
+    }
+}
+
+fn quoted_name<'a>(message: &'a str, prefix: &str) -> Option<&'a str> {
+    message.strip_prefix(prefix)?.split('`').next()
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn is_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence == "application/json" || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+fn has_content_type(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|essence| essence.trim().eq_ignore_ascii_case(expected))
+}
+
+pub(crate) fn validation_failed(errors: Vec<ValidationFieldError>) -> Response {
+    let error_response = ValidationErrorResponse {
+        code: "VALIDATION_ERROR".to_string(),
+        message: "Request validation failed".to_string(),
+        errors,
+        request_id: RequestId::current().map(|id| id.0),
+    };
+
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(error_response)).into_response()
+}
+
+fn invalid_json(errors: Vec<ValidationFieldError>) -> Response {
+    let error_response = ValidationErrorResponse {
+        code: "INVALID_JSON".to_string(),
+        message: "Invalid JSON payload".to_string(),
+        errors,
+        request_id: RequestId::current().map(|id| id.0),
+    };
+
+    (StatusCode::BAD_REQUEST, Json(error_response)).into_response()
+}
+
+pub(crate) fn invalid_form(errors: Vec<ValidationFieldError>) -> Response {
+    let error_response = ValidationErrorResponse {
+        code: "INVALID_FORM".to_string(),
+        message: "Invalid form payload".to_string(),
+        errors,
+        request_id: RequestId::current().map(|id| id.0),
+    };
+
+    (StatusCode::BAD_REQUEST, Json(error_response)).into_response()
+}
+
+fn invalid_query(errors: Vec<ValidationFieldError>) -> Response {
+    let error_response = ValidationErrorResponse {
+        code: "INVALID_QUERY".to_string(),
+        message: "Invalid query string".to_string(),
+        errors,
+        request_id: RequestId::current().map(|id| id.0),
+    };
+
+    (StatusCode::BAD_REQUEST, Json(error_response)).into_response()
+}
+
+/// Response for a form body that doesn't fit its type
+pub(crate) fn form_value_error(error: serde_path_to_error::Error<serde_urlencoded::de::Error>) -> Response {
+    tracing::error!("Form deserialization failed: {}", error);
+    invalid_form(vec![ValidationFieldError::from_form_error(error)])
+}
+
+/// How [`ValidatedJson`] reports validation failures
+///
+/// Register with `App::with_validation`. Messages are resolved against the
+/// translator for the request's `Accept-Language`, trying in order: the
+/// validator `message` used as a key, `validation.<field>.<code>`, then
+/// `validation.<code>`. Without a match the literal `message` is used.
+/// Templates can reference validator params such as `{min}` and `{max}`.
+///
+/// ```rust,ignore
+/// let messages = Catalog::new()
+///     .with_message("en", "validation.length", "Must be {min} to {max} characters")
+///     .with_message("fr", "validation.length", "Doit contenir entre {min} et {max} caractères");
+///
+/// App::new().with_validation(ValidationConfig::new().with_translator(messages))
+/// ```
+#[derive(Clone)]
+pub struct ValidationConfig {
+    translator: Option<SharedTranslator>,
+    default_locale: String,
+    redacted_fields: Vec<String>,
+    include_values: bool,
+    query_mode: QueryMode,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            translator: None,
+            default_locale: "en".to_string(),
+            redacted_fields: vec!["password".to_string(), "secret".to_string(), "token".to_string()],
+            include_values: true,
+            query_mode: QueryMode::default(),
+        }
+    }
+}
+
+impl ValidationConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_translator(mut self, translator: impl Translator + 'static) -> Self {
+        self.translator = Some(Arc::new(translator));
+        self
+    }
+    
+    /// Locale used when the request has no `Accept-Language` match (default `en`)
+    pub fn with_default_locale(mut self, locale: impl Into<String>) -> Self {
+        self.default_locale = locale.into();
+        self
+    }
+    
+    /// Report `[REDACTED]` instead of the rejected value for fields whose
+    /// name contains `pattern` (`password`, `secret` and `token` by default)
+    pub fn with_redacted_field(mut self, pattern: impl Into<String>) -> Self {
+        self.redacted_fields.push(pattern.into().to_lowercase());
+        self
+    }
+    
+    /// Leave rejected values out of error responses entirely
+    pub fn without_values(mut self) -> Self {
+        self.include_values = false;
+        self
+    }
+    
+    /// How [`ValidatedQuery`] reads arrays and nested objects (default [`QueryMode::Nested`])
+    pub fn with_query_mode(mut self, mode: QueryMode) -> Self {
+        self.query_mode = mode;
+        self
+    }
+    
+    fn field_error(&self, locales: &[String], field: &str, error: &ValidationError) -> ValidationFieldError {
+        let params = error.params.iter().filter(|(name, _)| *name != "value");
+        
+        let keys = [
+            error.message.as_ref().map(|m| m.to_string()),
+            Some(format!("validation.{}.{}", validation::without_indices(field), error.code)),
+            Some(format!("validation.{}", error.code)),
+        ];
+        let translated = self.translator.as_ref().and_then(|translator| {
+            locales
+                .iter()
+                .chain(std::iter::once(&self.default_locale))
+                .find_map(|locale| keys.iter().flatten().find_map(|key| translator.translate(locale, key)))
+        });
+        let message = match translated.or_else(|| error.message.as_ref().map(|m| m.to_string())) {
+            Some(template) => i18n::interpolate(&template, params),
+            None => "Validation failed".to_string(),
+        };
+        
+        let value = error.params.get("value").filter(|_| self.include_values).map(|value| {
+            let field = field.to_lowercase();
+            if self.redacted_fields.iter().any(|pattern| field.contains(pattern.as_str())) {
+                serde_json::Value::String("[REDACTED]".to_string())
+            } else {
+                value.clone()
+            }
+        });
+        
+        ValidationFieldError {
+            field: field.to_string(),
+            code: error.code.to_string(),
+            message,
+            value,
+            ..Default::default()
+        }
+    }
 }
 
 #[async_trait]
 impl<T, S> FromRequest<S> for ValidatedJson<T>
 where
-    T: DeserializeOwned + Validate,
+    T: DeserializeOwned + Validate + Send + 'static,
     S: Send + Sync,
 {
     type Rejection = Response;
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
-        // First, extract JSON
-        let Json(value) = Json::<T>::from_request(req, state)
+        let validation = RequestValidation::new(req.headers(), req.extensions());
+        
+        // First, deserialize, keeping track of where it failed
+        if !is_json_content_type(req.headers()) {
+            return Err(invalid_json(vec![ValidationFieldError {
+                code: "content_type".to_string(),
+                message: "Expected request with `Content-Type: application/json`".to_string(),
+                ..Default::default()
+            }]));
+        }
+
+        let body = Bytes::from_request(req, state)
             .await
-            .map_err(|rejection| {
-                tracing::error!("JSON deserialization failed: {:?}", rejection);
+            .map_err(IntoResponse::into_response)?;
 
-                let error_response = ValidationErrorResponse {
-                    code: "INVALID_JSON".to_string(),
-                    message: "Invalid JSON payload".to_string(),
-                    errors: vec![],
-                };
+        let mut deserializer = serde_json::Deserializer::from_slice(&body);
+        let value = serde_path_to_error::deserialize::<_, T>(&mut deserializer).map_err(|error| {
+            tracing::error!("JSON deserialization failed: {}", error);
+            let path = match error.path().to_string() {
+                root if root == "." => String::new(),
+                path => path,
+            };
+            invalid_json(vec![ValidationFieldError::from_json_error(path, error.inner())])
+        })?;
+        // Trailing characters after the value
+        deserializer.end().map_err(|error| {
+            tracing::error!("JSON deserialization failed: {}", error);
+            invalid_json(vec![ValidationFieldError::from_json_error(String::new(), &error)])
+        })?;
 
-                (StatusCode::BAD_REQUEST, Json(error_response)).into_response()
-            })?;
+        // Then validate, including async rules registered for this payload type
+        let value = validation
+            .check(value, || serde_json::from_slice(&body).unwrap_or_default())
+            .await?;
 
-        // Then validate
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// Validation settings and async rules in effect for a request
+pub(crate) struct RequestValidation {
+    config: ValidationConfig,
+    locales: Vec<String>,
+    validators: Option<Arc<ValidatorRegistry>>,
+    dependencies: Dependencies,
+}
+
+impl RequestValidation {
+    pub(crate) fn new(headers: &HeaderMap, extensions: &axum::http::Extensions) -> Self {
+        Self {
+            config: extensions.get::<ValidationConfig>().cloned().unwrap_or_default(),
+            locales: headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .map(i18n::preferred_locales)
+                .unwrap_or_default(),
+            validators: extensions.get::<Arc<ValidatorRegistry>>().cloned(),
+            dependencies: extensions.get::<Dependencies>().cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Run `value`'s validation rules, then the async rules registered for `T`
+    ///
+    /// `body` gives the payload as JSON for async rules; it is only built when
+    /// `T` has any.
+    pub(crate) async fn check<T: Validate + Send + 'static>(
+        &self,
+        value: T,
+        body: impl FnOnce() -> serde_json::Value + Send,
+    ) -> Result<T, Response> {
         value.validate().map_err(|validation_errors| {
             tracing::error!("Validation failed: {:?}", validation_errors);
 
-            let errors: Vec<ValidationFieldError> = validation_errors
-                .field_errors()
+            let errors: Vec<ValidationFieldError> = validation::flatten_errors(&validation_errors)
                 .into_iter()
-                .flat_map(|(field, errors)| {
-                    errors.iter().map(move |error| ValidationFieldError {
-                        field: field.to_string(),
-                        message: error
-                            .message
-                            .as_ref()
-                            .map(|m| m.to_string())
-                            .unwrap_or_else(|| "Validation failed".to_string()),
-                    })
-                })
+                .map(|(field, error)| self.config.field_error(&self.locales, &field, error))
                 .collect();
 
-            let error_response = ValidationErrorResponse {
-                code: "VALIDATION_ERROR".to_string(),
-                message: "Request validation failed".to_string(),
-                errors,
-            };
+            validation_failed(errors)
+        })?;
+
+        let type_id = std::any::TypeId::of::<T>();
+        if let Some(validators) = self.validators.as_ref().filter(|validators| validators.has_rules(type_id)) {
+            let failures = validators
+                .run(type_id, &body(), &self.dependencies)
+                .await
+                .map_err(|name| {
+                    ApiError::InternalServerError(format!("Async validator '{}' is not registered", name))
+                        .into_response()
+                })?;
+
+            if !failures.is_empty() {
+                tracing::error!("Async validation failed: {:?}", failures);
+                let errors = failures
+                    .iter()
+                    .map(|(field, error)| self.config.field_error(&self.locales, field, error))
+                    .collect();
+                return Err(validation_failed(errors));
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Form values as a JSON object of strings, for async rules
+pub(crate) fn form_json<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> serde_json::Value {
+    pairs
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), serde_json::Value::String(value.to_string())))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Extractor that deserializes and validates `application/x-www-form-urlencoded` bodies
+///
+/// Reports errors like [`ValidatedJson`]; malformed bodies get an
+/// `INVALID_FORM` response.
+///
+/// ```rust,ignore
+/// async fn subscribe(ValidatedForm(form): ValidatedForm<Subscribe>) -> impl IntoResponse {
+///     // form is guaranteed to be valid
+/// }
+/// ```
+pub struct ValidatedForm<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedForm<T>
+where
+    T: DeserializeOwned + Validate + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = Response;
 
-            (StatusCode::UNPROCESSABLE_ENTITY, Json(error_response)).into_response()
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let validation = RequestValidation::new(req.headers(), req.extensions());
+
+        if !has_content_type(req.headers(), "application/x-www-form-urlencoded") {
+            return Err(invalid_form(vec![ValidationFieldError::new(
+                "",
+                "content_type",
+                "Expected request with `Content-Type: application/x-www-form-urlencoded`",
+            )]));
+        }
+
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(&body));
+        let value = serde_path_to_error::deserialize::<_, T>(deserializer).map_err(form_value_error)?;
+
+        let value = validation
+            .check(value, || {
+                let pairs: Vec<_> = form_urlencoded::parse(&body).collect();
+                form_json(pairs.iter().map(|(key, value)| (key.as_ref(), value.as_ref())))
+            })
+            .await?;
+
+        Ok(ValidatedForm(value))
+    }
+}
+
+/// Extractor that deserializes and validates the query string
+///
+/// Unlike `axum::extract::Query`, repeated keys (`?tag=a&tag=b`), bracketed
+/// arrays (`?tag[]=a`) and nested objects (`?filter[status]=active`) are
+/// understood; see [`query`](crate::query). Values that don't fit get an
+/// `INVALID_QUERY` response, failed rules are reported like [`ValidatedJson`].
+///
+/// ```rust,ignore
+/// async fn search(ValidatedQuery(search): ValidatedQuery<Search>) -> ApiResult<Vec<Item>> {
+///     // search is guaranteed to be valid
+/// }
+/// ```
+pub struct ValidatedQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let validation = RequestValidation::new(&parts.headers, &parts.extensions);
+        let mode = validation.config.query_mode;
+        let raw = parts.uri.query().unwrap_or_default();
+
+        let value = query::from_query::<T>(raw, mode).map_err(|error| {
+            tracing::error!("Query deserialization failed: {}", error);
+            invalid_query(vec![ValidationFieldError::from_query_error(error)])
         })?;
 
-        Ok(ValidatedJson(value))
+        let value = validation.check(value, || query::to_json(raw, mode)).await?;
+
+        Ok(ValidatedQuery(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Catalog;
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use serde_json::Value;
+    use tower::ServiceExt;
+    
+    #[derive(Deserialize, Validate)]
+    struct Signup {
+        #[validate(length(min = 8))]
+        password: String,
+        #[validate(email(message = "signup.email"))]
+        email: String,
+    }
+    
+    async fn field_errors(router: Router, accept_language: &str) -> Vec<Value> {
+        let request = axum::http::Request::post("/signup")
+            .header("content-type", "application/json")
+            .header("accept-language", accept_language)
+            .body(Body::from(r#"{"password": "short", "email": "nope"}"#))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let mut errors = body["errors"].as_array().unwrap().clone();
+        errors.sort_by_key(|error| error["field"].as_str().unwrap().to_string());
+        errors
+    }
+    
+    #[tokio::test]
+    async fn test_localized_validation_errors() {
+        let messages = Catalog::new()
+            .with_message("fr", "validation.length", "Au moins {min} caractères")
+            .with_message("fr", "signup.email", "Adresse e-mail invalide");
+        let router = crate::App::new()
+            .route("/signup", post(|ValidatedJson(_): ValidatedJson<Signup>| async {}))
+            .with_validation(ValidationConfig::new().with_translator(messages))
+            .into_router();
+        
+        let errors = field_errors(router.clone(), "fr-FR, en;q=0.5").await;
+        assert_eq!(errors[0]["field"], "email");
+        assert_eq!(errors[0]["code"], "email");
+        assert_eq!(errors[0]["message"], "Adresse e-mail invalide");
+        assert_eq!(errors[0]["value"], "nope");
+        assert_eq!(errors[1]["code"], "length");
+        assert_eq!(errors[1]["message"], "Au moins 8 caractères");
+        assert_eq!(errors[1]["value"], "[REDACTED]");
+        
+        let errors = field_errors(router, "de").await;
+        assert_eq!(errors[1]["message"], "Validation failed");
+    }
+    
+    #[derive(Deserialize, Validate)]
+    #[allow(dead_code)]
+    struct Order {
+        items: Vec<Item>,
+    }
+    
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Item {
+        price: f64,
+    }
+    
+    async fn json_error(body: &'static str) -> Value {
+        let router = Router::new().route("/orders", post(|ValidatedJson(_): ValidatedJson<Order>| async {}));
+        let request = axum::http::Request::post("/orders")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "INVALID_JSON");
+        body["errors"][0].clone()
+    }
+    
+    #[tokio::test]
+    async fn test_invalid_json_details() {
+        let error = json_error(r#"{"items": [{"price": 1}, {"price": "free"}]}"#).await;
+        assert_eq!(error["field"], "items[1].price");
+        assert_eq!(error["code"], "invalid_type");
+        assert_eq!(error["expected"], "f64");
+        assert_eq!(error["line"], 1);
+        assert_eq!(error["column"], 41);
+        
+        let error = json_error(r#"{"items": [{}]}"#).await;
+        assert_eq!(error["field"], "items[0].price");
+        assert_eq!(error["code"], "missing_field");
+        
+        let error = json_error("{\n  \"items\": [,]\n}").await;
+        assert_eq!(error["field"], "items[0]");
+        assert_eq!(error["code"], "syntax");
+        assert_eq!(error["line"], 2);
+    }
+    
+    #[derive(Deserialize, Validate, validation::AsyncValidate)]
+    #[allow(dead_code)]
+    struct Register {
+        #[validate(email)]
+        #[validate_async(unique_email)]
+        email: String,
+    }
+    
+    #[tokio::test]
+    async fn test_async_validators() {
+        let validators = ValidatorRegistry::new()
+            .with_validator("unique_email", |email: Value, deps: Dependencies| async move {
+                match deps.get::<String>().is_some_and(|taken| email == taken.as_str()) {
+                    true => Err(ValidationError::new("unique_email").with_message("Already registered".into())),
+                    false => Ok(()),
+                }
+            })
+            .with_rules_for::<Register>();
+        let router = crate::App::new()
+            .route("/register", post(|ValidatedJson(_): ValidatedJson<Register>| async {}))
+            .provide("taken@example.com".to_string())
+            .with_validators(validators)
+            .into_router();
+        
+        let register = |email: &str| {
+            axum::http::Request::post("/register")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::json!({ "email": email }).to_string()))
+                .unwrap()
+        };
+        
+        let response = router.clone().oneshot(register("new@example.com")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        let response = router.oneshot(register("taken@example.com")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["errors"][0]["field"], "email");
+        assert_eq!(body["errors"][0]["code"], "unique_email");
+        assert_eq!(body["errors"][0]["message"], "Already registered");
+    }
+    
+    #[derive(Deserialize, Validate)]
+    #[allow(dead_code)]
+    struct Subscribe {
+        #[validate(email)]
+        email: String,
+        weekly: bool,
+    }
+    
+    #[tokio::test]
+    async fn test_validated_form() {
+        let router = Router::new().route(
+            "/subscribe",
+            post(|ValidatedForm(form): ValidatedForm<Subscribe>| async move { form.email }),
+        );
+        let submit = |content_type: &str, body: &'static str| {
+            axum::http::Request::post("/subscribe")
+                .header("content-type", content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let form = "application/x-www-form-urlencoded";
+        
+        let response = router.clone().oneshot(submit(form, "email=ada%40example.com&weekly=true")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"ada@example.com");
+        
+        let response = router.clone().oneshot(submit(form, "email=nope&weekly=true")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        
+        let response = router.clone().oneshot(submit(form, "email=ada%40example.com")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "INVALID_FORM");
+        assert_eq!(body["errors"][0]["field"], "weekly");
+        assert_eq!(body["errors"][0]["code"], "missing_field");
+        
+        let response = router.oneshot(submit("application/json", "{}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[derive(Deserialize, Validate)]
+    #[allow(dead_code)]
+    struct Search {
+        #[validate(length(max = 2))]
+        tag: Vec<String>,
+        status: Option<String>,
+        page: Option<u32>,
+    }
+    
+    #[tokio::test]
+    async fn test_validated_query() {
+        let router = Router::new().route(
+            "/search",
+            axum::routing::get(|ValidatedQuery(search): ValidatedQuery<Search>| async move { search.tag.join(",") }),
+        );
+        let search = |uri: &str| axum::http::Request::get(uri).body(Body::empty()).unwrap();
+        
+        let response = router.clone().oneshot(search("/search?tag[]=a&tag[]=b")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"a,b");
+        
+        let response = router.clone().oneshot(search("/search?tag=a&tag=b&tag=c")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        
+        let response = router.oneshot(search("/search?tag=a&page=two")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "INVALID_QUERY");
+        assert_eq!(body["errors"][0]["field"], "page");
     }
 }
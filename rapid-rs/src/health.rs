@@ -0,0 +1,438 @@
+//! Liveness and readiness checks
+//!
+//! Components register [`HealthCheck`]s with
+//! [`App::with_health_check`](crate::App::with_health_check), and
+//! [`App::auto_configure`](crate::App::auto_configure) serves:
+//!
+//! - `/health/live`: the process is up and serving requests
+//! - `/health/ready`: every check passes; `503` if any is down, or while the
+//!   instance is draining
+//!
+//! On SIGTERM, [`App::run`](crate::App::run) marks the instance as draining
+//! ("lame duck") so load balancers stop routing to it, keeps serving for the
+//! pre-stop delay while they notice, and only then stops accepting and drains
+//! in-flight requests. Responses sent while draining carry `Connection: close`
+//! so keep-alive clients reconnect to another instance.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .auto_configure()
+//!     .with_health_check(PostgresCheck::new(pool.clone()))
+//!     .with_health_check(QueueDepthCheck::new(queue.clone(), 10_000))
+//!     .with_health_check(health::check_fn("search", move || {
+//!         let search = search.clone();
+//!         async move { search.ping().await.map_err(|e| e.to_string()) }
+//!     }))
+//! ```
+
+use axum::{async_trait, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// How long a check may take before it counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    /// Working, but impaired; still ready for traffic
+    Degraded,
+    Down,
+}
+
+/// Outcome of one [`HealthCheck`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl CheckResult {
+    pub fn up() -> Self {
+        Self {
+            status: HealthStatus::Up,
+            message: None,
+        }
+    }
+
+    pub fn degraded(message: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Degraded,
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn down(message: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Down,
+            message: Some(message.into()),
+        }
+    }
+}
+
+impl<E: std::fmt::Display> From<Result<(), E>> for CheckResult {
+    fn from(result: Result<(), E>) -> Self {
+        match result {
+            Ok(()) => Self::up(),
+            Err(e) => Self::down(e.to_string()),
+        }
+    }
+}
+
+/// A dependency readiness depends on
+#[async_trait]
+pub trait HealthCheck: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    async fn check(&self) -> CheckResult;
+}
+
+/// A check from an async closure returning a [`CheckResult`] or `Result<(), E>`
+pub fn check_fn<F, Fut, R>(name: impl Into<String>, check: F) -> impl HealthCheck
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send,
+    R: Into<CheckResult>,
+{
+    FnCheck {
+        name: name.into(),
+        check,
+    }
+}
+
+struct FnCheck<F> {
+    name: String,
+    check: F,
+}
+
+#[async_trait]
+impl<F, Fut, R> HealthCheck for FnCheck<F>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send,
+    R: Into<CheckResult>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn check(&self) -> CheckResult {
+        (self.check)().await.into()
+    }
+}
+
+/// Runs `SELECT 1` against a Postgres pool
+#[derive(Debug, Clone)]
+pub struct PostgresCheck {
+    pool: sqlx::PgPool,
+}
+
+impl PostgresCheck {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for PostgresCheck {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self) -> CheckResult {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map(|_| ()).into()
+    }
+}
+
+/// Pings the Redis server behind a [`RedisCache`](crate::cache::RedisCache)
+#[cfg(feature = "cache-redis")]
+pub struct RedisCheck {
+    cache: Arc<crate::cache::RedisCache>,
+}
+
+#[cfg(feature = "cache-redis")]
+impl RedisCheck {
+    pub fn new(cache: Arc<crate::cache::RedisCache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[cfg(feature = "cache-redis")]
+#[async_trait]
+impl HealthCheck for RedisCheck {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn check(&self) -> CheckResult {
+        self.cache.ping().await.into()
+    }
+}
+
+/// Degraded once more than `degraded_above` jobs are pending
+#[cfg(feature = "jobs")]
+pub struct QueueDepthCheck<S: crate::jobs::JobStorage> {
+    queue: Arc<crate::jobs::JobQueue<S>>,
+    degraded_above: usize,
+    down_above: Option<usize>,
+}
+
+#[cfg(feature = "jobs")]
+impl<S: crate::jobs::JobStorage> QueueDepthCheck<S> {
+    pub fn new(queue: Arc<crate::jobs::JobQueue<S>>, degraded_above: usize) -> Self {
+        Self {
+            queue,
+            degraded_above,
+            down_above: None,
+        }
+    }
+
+    /// Report down, taking the instance out of rotation, past `pending` jobs
+    pub fn with_down_above(mut self, pending: usize) -> Self {
+        self.down_above = Some(pending);
+        self
+    }
+}
+
+#[cfg(feature = "jobs")]
+#[async_trait]
+impl<S: crate::jobs::JobStorage + 'static> HealthCheck for QueueDepthCheck<S> {
+    fn name(&self) -> &str {
+        "job_queue"
+    }
+
+    async fn check(&self) -> CheckResult {
+        let pending = match self.queue.stats().await {
+            Ok(stats) => stats.pending,
+            Err(e) => return CheckResult::down(e.to_string()),
+        };
+        let message = format!("{} jobs pending", pending);
+        if self.down_above.is_some_and(|limit| pending > limit) {
+            CheckResult::down(message)
+        } else if pending > self.degraded_above {
+            CheckResult::degraded(message)
+        } else {
+            CheckResult::up()
+        }
+    }
+}
+
+/// Switch for taking an instance out of rotation, see [`App::readiness`](crate::App::readiness)
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    draining: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    /// Report not ready and ask clients to close connections
+    pub fn start_draining(&self) {
+        if !self.draining.swap(true, Ordering::SeqCst) {
+            tracing::info!("Instance is draining, reporting not ready");
+        }
+    }
+
+    /// Report ready again
+    pub fn resume(&self) {
+        self.draining.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+}
+
+/// Checks registered with an [`App`](crate::App), shared with the health routes
+#[derive(Clone, Default)]
+pub(crate) struct HealthChecks {
+    checks: Arc<RwLock<Vec<Arc<dyn HealthCheck>>>>,
+    pub(crate) readiness: Readiness,
+}
+
+/// One check in a readiness report
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    pub name: String,
+    #[serde(flatten)]
+    pub result: CheckResult,
+    pub latency_ms: u64,
+}
+
+/// Body of `/health/ready`
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub draining: bool,
+    pub checks: Vec<CheckReport>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl HealthChecks {
+    pub(crate) fn add(&self, check: impl HealthCheck) {
+        self.add_shared(Arc::new(check));
+    }
+
+    pub(crate) fn add_shared(&self, check: Arc<dyn HealthCheck>) {
+        self.checks.write().unwrap().push(check);
+    }
+
+    /// Run every check concurrently
+    pub(crate) async fn run(&self) -> HealthReport {
+        let checks = self.checks.read().unwrap().clone();
+        let handles: Vec<_> = checks
+            .into_iter()
+            .map(|check| {
+                tokio::spawn(async move {
+                    let started = Instant::now();
+                    let result = tokio::time::timeout(CHECK_TIMEOUT, check.check())
+                        .await
+                        .unwrap_or_else(|_| CheckResult::down("timed out"));
+                    CheckReport {
+                        name: check.name().to_string(),
+                        result,
+                        latency_ms: started.elapsed().as_millis() as u64,
+                    }
+                })
+            })
+            .collect();
+
+        let mut reports = Vec::with_capacity(handles.len());
+        for handle in handles {
+            reports.push(handle.await.unwrap_or_else(|e| CheckReport {
+                name: "unknown".to_string(),
+                result: CheckResult::down(format!("check panicked: {}", e)),
+                latency_ms: 0,
+            }));
+        }
+
+        HealthReport {
+            status: reports.iter().map(|report| report.result.status).max().unwrap_or(HealthStatus::Up),
+            draining: false,
+            checks: reports,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Add `Connection: close` to responses while draining
+    pub(crate) fn close_while_draining(&self, mut response: axum::response::Response) -> axum::response::Response {
+        if self.readiness.is_draining() {
+            response
+                .headers_mut()
+                .insert(axum::http::header::CONNECTION, axum::http::HeaderValue::from_static("close"));
+        }
+        response
+    }
+
+    /// `/health/live` and `/health/ready`
+    pub(crate) fn router(&self) -> Router {
+        let checks = self.clone();
+        Router::new()
+            .route(
+                "/health/live",
+                get(|| async { Json(serde_json::json!({ "status": HealthStatus::Up, "timestamp": Utc::now() })) }),
+            )
+            .route(
+                "/health/ready",
+                get(move || {
+                    let checks = checks.clone();
+                    async move {
+                        if checks.readiness.is_draining() {
+                            let report = HealthReport {
+                                status: HealthStatus::Down,
+                                draining: true,
+                                checks: Vec::new(),
+                                timestamp: Utc::now(),
+                            };
+                            return (StatusCode::SERVICE_UNAVAILABLE, Json(report)).into_response();
+                        }
+
+                        let report = checks.run().await;
+                        let status = match report.status {
+                            HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+                            _ => StatusCode::OK,
+                        };
+                        (status, Json(report)).into_response()
+                    }
+                }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn ready(checks: &HealthChecks) -> (StatusCode, serde_json::Value) {
+        let response = checks
+            .router()
+            .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_readiness_aggregates_checks() {
+        let checks = HealthChecks::default();
+        checks.add(check_fn("cache", || async { Ok::<_, String>(()) }));
+        checks.add(check_fn("queue", || async { CheckResult::degraded("backlog") }));
+
+        let (status, body) = ready(&checks).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["checks"][0]["name"], "cache");
+        assert_eq!(body["checks"][0]["status"], "up");
+        assert!(body["checks"][0]["latency_ms"].is_u64());
+        assert_eq!(body["checks"][1]["message"], "backlog");
+
+        checks.add(check_fn("search", || async { Err::<(), _>("connection refused") }));
+        let (status, body) = ready(&checks).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "down");
+        assert_eq!(body["checks"][2]["message"], "connection refused");
+
+        let response = checks
+            .router()
+            .oneshot(Request::get("/health/live").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_draining_reports_not_ready() {
+        let app = crate::App::new().route("/", get(|| async { "ok" }));
+        let readiness = app.readiness();
+        let router = app.into_router();
+        let request = || Request::get("/").body(Body::empty()).unwrap();
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert!(response.headers().get("connection").is_none());
+
+        readiness.start_draining();
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.headers()["connection"], "close");
+
+        let checks = HealthChecks {
+            readiness: readiness.clone(),
+            ..Default::default()
+        };
+        let (status, body) = ready(&checks).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["draining"], true);
+
+        readiness.resume();
+        assert_eq!(ready(&checks).await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,197 @@
+//! Outgoing HTTP calls that carry the current request id
+//!
+//! Requests made through [`HttpClient`] while handling a request send its
+//! [`RequestId`] as `X-Request-Id`, so downstream services log the same id:
+//!
+//! ```rust,ignore
+//! let client = HttpClient::new();
+//! let rates = client.get("https://rates.internal/eur").send().await?;
+//! ```
+//!
+//! Third-party services configured under `[services]` are reached through a
+//! [`ServiceRegistry`], which fails over to a standby endpoint when the
+//! primary is down. [`proxy_routes`] forwards whole paths to an upstream.
+
+pub mod proxy;
+pub mod services;
+
+pub use proxy::{proxy_routes, Proxy};
+pub use services::{Service, ServiceRegistry, ServiceStats};
+
+use reqwest::{IntoUrl, Method, RequestBuilder, Response};
+
+use crate::error::ApiError;
+use crate::middleware::{RequestId, REQUEST_ID_HEADER};
+use crate::startup::RetryPolicy;
+
+/// `reqwest::Client` that forwards the request id
+#[derive(Debug, Clone, Default)]
+pub struct HttpClient {
+    client: reqwest::Client,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap a client built with your own timeouts, TLS settings, etc.
+    pub fn from_client(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        let builder = self.client.request(method, url);
+        match RequestId::current() {
+            Some(id) => builder.header(REQUEST_ID_HEADER, id.0),
+            None => builder,
+        }
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub fn put(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PUT, url)
+    }
+
+    pub fn patch(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PATCH, url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::DELETE, url)
+    }
+
+    /// Send `request`, retrying it as the current route's
+    /// [`RoutePolicy`](crate::policy::RoutePolicy) says
+    ///
+    /// Only idempotent requests with a replayable body are retried, on
+    /// connection errors and responses its retry policy deems
+    /// [transient](crate::startup::is_transient) (502, 503, 504 by default).
+    /// The last response is returned whatever its status.
+    pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let retry = crate::policy::RoutePolicy::current().and_then(|policy| policy.retry().cloned());
+        self.send_with_retry(request, retry).await
+    }
+
+    /// Send `request`, retrying it as `retry` says
+    pub(crate) async fn send_with_retry(&self, request: RequestBuilder, retry: Option<RetryPolicy>) -> reqwest::Result<Response> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let retry = match retry {
+            Some(retry) if services::is_idempotent(request.method()) && request.try_clone().is_some() => retry,
+            _ => return client.execute(request).await,
+        };
+
+        let mut delay = retry.delay;
+        let mut attempt = 1;
+        loop {
+            let result = client.execute(request.try_clone().expect("checked above")).await;
+            let error = match &result {
+                Ok(response) if response.status().is_server_error() => ApiError::custom(
+                    response.status(),
+                    "DOWNSTREAM_ERROR",
+                    format!("{} answered {}", request.url(), response.status()),
+                ),
+                Ok(_) => return result,
+                Err(error) => ApiError::ServiceUnavailable(error.to_string()),
+            };
+            if attempt >= retry.attempts || !(retry.retryable)(&error) {
+                return result;
+            }
+            tracing::warn!(url = %request.url(), attempt, error = %error, retry_in = ?delay, "Downstream call failed, retrying");
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::{body::Body, http::Request, http::HeaderMap, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_forwards_request_id() {
+        // Downstream service echoing the id it received
+        let downstream = axum::Router::new().route(
+            "/echo",
+            get(|headers: HeaderMap| async move {
+                headers.get(REQUEST_ID_HEADER).map(|id| id.to_str().unwrap().to_string()).unwrap_or_default()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/echo", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, downstream).await.unwrap() });
+
+        let client = HttpClient::new();
+        assert_eq!(client.get(&url).send().await.unwrap().text().await.unwrap(), "");
+
+        let router = App::new()
+            .route(
+                "/proxy",
+                get(move || async move { client.get(&url).send().await.unwrap().text().await.unwrap() }),
+            )
+            .with_request_ids()
+            .into_router();
+        let request = Request::get("/proxy").header(REQUEST_ID_HEADER, "req-7").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "req-7");
+    }
+
+    #[tokio::test]
+    async fn test_retries_under_route_policy() {
+        use crate::{policy::RoutePolicy, startup::RetryPolicy};
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::time::Duration;
+
+        let calls = std::sync::Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let flaky = axum::Router::new().route(
+            "/rates",
+            get(move || {
+                let calls = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    match calls % 3 {
+                        0 => (axum::http::StatusCode::OK, "ok"),
+                        _ => (axum::http::StatusCode::SERVICE_UNAVAILABLE, "down"),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/rates", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, flaky).await.unwrap() });
+
+        let client = HttpClient::new();
+        let proxy = {
+            let (client, url) = (client.clone(), url.clone());
+            move || async move { client.send(client.get(&url)).await.unwrap().status().as_u16().to_string() }
+        };
+        let router = App::new()
+            .route("/proxy", get(proxy))
+            .with_route_policy("/proxy", RoutePolicy::new().with_retry(RetryPolicy::new(3, Duration::from_millis(1))))
+            .into_router();
+        let response = router.oneshot(Request::get("/proxy").body(Body::empty()).unwrap()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!((body.as_ref(), calls.load(Ordering::SeqCst)), (&b"200"[..], 3));
+
+        // Outside a route with a policy, a single try
+        assert_eq!(client.send(client.get(&url)).await.unwrap().status(), 503);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,361 @@
+//! Pass-through routes to upstream services
+//!
+//! [`proxy_routes`] forwards everything under a path to another service,
+//! streaming bodies both ways, so an app can sit in front of legacy backends
+//! as a lightweight gateway:
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .mount(proxy_routes(
+//!         "/legacy",
+//!         Proxy::new("http://billing.internal:8080")
+//!             .with_strip_prefix("/legacy")
+//!             .with_removed_request_header("cookie")
+//!             .with_timeout(Duration::from_secs(10))
+//!             .with_retry(RetryPolicy::new(3, Duration::from_millis(100))),
+//!     ))
+//! ```
+//!
+//! `GET /legacy/invoices?page=2` is sent to
+//! `http://billing.internal:8080/invoices?page=2` with the request id and
+//! `X-Forwarded-For`/`X-Forwarded-Host`/`X-Forwarded-Proto` added; the
+//! scheme is the one the client used, as seen by
+//! [`ClientInfo`](crate::client_ip::ClientInfo). Hop-by-hop headers are never
+//! forwarded, and paths with `..` segments are rejected with 400. Upstreams that can't be reached
+//! answer 502, and ones that time out answer 504.
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::any,
+    Router,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::HttpClient;
+use crate::client_ip::ClientInfo;
+use crate::error::ApiError;
+use crate::startup::RetryPolicy;
+
+/// Headers about one connection, not the request
+const HOP_BY_HOP: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+type Rewrite = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Where and how requests are forwarded
+#[derive(Clone)]
+pub struct Proxy {
+    upstream: String,
+    client: HttpClient,
+    rewrite: Option<Rewrite>,
+    removed_request_headers: Vec<HeaderName>,
+    removed_response_headers: Vec<HeaderName>,
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+}
+
+impl std::fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Proxy")
+            .field("upstream", &self.upstream)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Proxy {
+    /// Forward to `upstream`, a base URL such as `http://billing.internal:8080`
+    pub fn new(upstream: impl Into<String>) -> Self {
+        Self {
+            upstream: upstream.into().trim_end_matches('/').to_string(),
+            client: HttpClient::new(),
+            rewrite: None,
+            removed_request_headers: Vec::new(),
+            removed_response_headers: Vec::new(),
+            timeout: None,
+            retry: None,
+        }
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Drop `prefix` from paths before forwarding
+    pub fn with_strip_prefix(self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.with_path_rewrite(move |path| match path.strip_prefix(prefix.as_str()) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("/{}", rest.trim_start_matches('/')),
+            _ => path.to_string(),
+        })
+    }
+
+    /// Map the request path to the upstream path
+    pub fn with_path_rewrite(mut self, rewrite: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.rewrite = Some(Arc::new(rewrite));
+        self
+    }
+
+    /// Don't forward the client's `name` header, e.g. `cookie`
+    pub fn with_removed_request_header(mut self, name: HeaderName) -> Self {
+        self.removed_request_headers.push(name);
+        self
+    }
+
+    /// Don't pass the upstream's `name` header back, e.g. `server`
+    pub fn with_removed_response_header(mut self, name: HeaderName) -> Self {
+        self.removed_response_headers.push(name);
+        self
+    }
+
+    /// Answer 504 when the upstream hasn't finished responding within `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry idempotent requests without a body on connection errors and
+    /// transient statuses
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// The upstream URL for a request to `path` with `query`
+    ///
+    /// Paths that would climb out of the upstream base with `..` are
+    /// rejected.
+    fn url(&self, path: &str, query: Option<&str>) -> Result<String, ApiError> {
+        let path = match &self.rewrite {
+            Some(rewrite) => rewrite(path),
+            None => path.to_string(),
+        };
+        if path.split('/').any(is_parent_segment) {
+            return Err(ApiError::BadRequest("Path must not contain '..' segments".to_string()));
+        }
+        Ok(match query {
+            Some(query) => format!("{}{}?{}", self.upstream, path, query),
+            None => format!("{}{}", self.upstream, path),
+        })
+    }
+
+    /// Forward `request` and stream the upstream's response back
+    pub async fn forward(&self, request: Request) -> Response {
+        match self.try_forward(request).await {
+            Ok(response) => response,
+            Err(error) => error.into_response(),
+        }
+    }
+
+    async fn try_forward(&self, request: Request) -> Result<Response, ApiError> {
+        let (parts, body) = request.into_parts();
+        let url = self.url(parts.uri.path(), parts.uri.query())?;
+
+        let mut headers = filter_headers(&parts.headers, &self.removed_request_headers);
+        headers.remove(header::HOST);
+        if let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+            let forwarded_for = match parts.headers.get(X_FORWARDED_FOR).and_then(|value| value.to_str().ok()) {
+                Some(earlier) => format!("{}, {}", earlier, addr.ip()),
+                None => addr.ip().to_string(),
+            };
+            if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+                headers.insert(X_FORWARDED_FOR, value);
+            }
+        }
+        if let Some(host) = parts.headers.get(header::HOST) {
+            headers.insert(X_FORWARDED_HOST, host.clone());
+        }
+        // Whatever the client claimed is replaced by what the listener saw
+        let proto = ClientInfo::from_extensions(&parts.extensions).map_or("http", |client| client.scheme);
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static(proto));
+
+        let mut upstream = self.client.request(parts.method, &url).headers(headers);
+        if !axum::body::HttpBody::is_end_stream(&body) {
+            upstream = upstream.body(reqwest::Body::wrap_stream(body.into_data_stream()));
+        }
+        if let Some(timeout) = self.timeout {
+            upstream = upstream.timeout(timeout);
+        }
+
+        let response = self.client.send_with_retry(upstream, self.retry.clone()).await.map_err(|error| {
+            tracing::warn!(url, error = %error, "Proxied request failed");
+            if error.is_timeout() {
+                ApiError::custom(StatusCode::GATEWAY_TIMEOUT, "UPSTREAM_TIMEOUT", "Upstream service timed out")
+            } else {
+                ApiError::custom(StatusCode::BAD_GATEWAY, "BAD_GATEWAY", "Upstream service unavailable")
+            }
+        })?;
+
+        let (status, headers) = (response.status(), filter_headers(response.headers(), &self.removed_response_headers));
+        let mut proxied = Response::new(Body::from_stream(response.bytes_stream()));
+        *proxied.status_mut() = status;
+        *proxied.headers_mut() = headers;
+        Ok(proxied)
+    }
+}
+
+/// `..`, including percent-encoded forms upstreams may decode
+fn is_parent_segment(segment: &str) -> bool {
+    matches!(segment.to_ascii_lowercase().as_str(), ".." | ".%2e" | "%2e." | "%2e%2e")
+}
+
+/// `headers` without hop-by-hop ones and `removed`
+fn filter_headers(headers: &HeaderMap, removed: &[HeaderName]) -> HeaderMap {
+    let mut filtered = headers.clone();
+    for name in HOP_BY_HOP.iter().chain(removed) {
+        filtered.remove(name);
+    }
+    filtered
+}
+
+/// Routes forwarding `path` and everything below it through `proxy`
+pub fn proxy_routes(path: &str, proxy: Proxy) -> Router {
+    let proxy = Arc::new(proxy);
+    let handler = move |request: Request| {
+        let proxy = proxy.clone();
+        async move { proxy.forward(request).await }
+    };
+    let path = path.trim_end_matches('/');
+    Router::new()
+        .route(if path.is_empty() { "/" } else { path }, any(handler.clone()))
+        .route(&format!("{}/*rest", path), any(handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Query, routing::get};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tower::ServiceExt;
+
+    async fn upstream() -> (String, Arc<AtomicU32>) {
+        let flaky_calls = Arc::new(AtomicU32::new(0));
+        let calls = flaky_calls.clone();
+        let app = Router::new()
+            .route(
+                "/invoices",
+                get(|Query(query): Query<HashMap<String, String>>, headers: HeaderMap| async move {
+                    let header = |name: &str| headers.get(name).map(|v| v.to_str().unwrap().to_string()).unwrap_or_default();
+                    (
+                        [("server", "legacy/1.0"), ("x-upstream", "billing")],
+                        format!(
+                            "page={} cookie={} host={} proto={}",
+                            query.get("page").cloned().unwrap_or_default(),
+                            header("cookie"),
+                            header(X_FORWARDED_HOST),
+                            header(X_FORWARDED_PROTO)
+                        ),
+                    )
+                }),
+            )
+            .route("/echo", axum::routing::post(|body: Body| async move { Response::new(body) }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "late"
+                }),
+            )
+            .route(
+                "/flaky",
+                get(move || {
+                    let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { if call < 3 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK } }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, flaky_calls)
+    }
+
+    async fn text(response: Response) -> String {
+        String::from_utf8(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_proxy_forwards_and_filters() {
+        let (url, _) = upstream().await;
+        let router = proxy_routes(
+            "/legacy",
+            Proxy::new(url)
+                .with_strip_prefix("/legacy")
+                .with_removed_request_header(header::COOKIE)
+                .with_removed_response_header(header::SERVER),
+        );
+
+        let request = Request::get("/legacy/invoices?page=2")
+            .header(header::COOKIE, "session=secret")
+            .header(header::HOST, "api.example.com")
+            .header(X_FORWARDED_PROTO, "https")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::SERVER).is_none());
+        assert_eq!(response.headers()["x-upstream"], "billing");
+        // The client's own X-Forwarded-Proto is not believed
+        assert_eq!(text(response).await, "page=2 cookie= host=api.example.com proto=http");
+
+        let mut request = Request::get("/legacy/invoices").body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ClientInfo {
+            ip: [203, 0, 113, 7].into(),
+            peer: SocketAddr::from(([10, 0, 0, 2], 443)),
+            scheme: "https",
+        });
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert!(text(response).await.ends_with("proto=https"));
+
+        for path in ["/legacy/../admin", "/legacy/a/%2E%2E/admin", "/legacy/.%2e"] {
+            let response = router.clone().oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+
+        let payload = "x".repeat(256 * 1024);
+        let request = Request::post("/legacy/echo").body(Body::from(payload.clone())).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(text(response).await, payload);
+
+        let response = router.oneshot(Request::get("/legacy/missing").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_timeout_retry_and_unreachable() {
+        let (url, flaky_calls) = upstream().await;
+        let router = proxy_routes(
+            "/",
+            Proxy::new(url)
+                .with_timeout(Duration::from_millis(100))
+                .with_retry(RetryPolicy::new(3, Duration::from_millis(1))),
+        );
+        let get = |uri: &str| router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        assert_eq!(get("/slow").await.unwrap().status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(get("/flaky").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(flaky_calls.load(Ordering::SeqCst), 3);
+
+        let unreachable = proxy_routes("/", Proxy::new("http://127.0.0.1:1"));
+        let response = unreachable.oneshot(Request::get("/x").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+}
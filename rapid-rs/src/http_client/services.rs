@@ -0,0 +1,339 @@
+//! Named external services with blue/green failover
+//!
+//! Each service in the `[services]` config section has a primary endpoint and
+//! optionally a secondary one. After `failure_threshold` consecutive failures
+//! (connection errors or 5xx responses) of the primary, calls go to the
+//! secondary; health checks then probe the primary's `health_path` and switch
+//! back once it answers. Switching endpoints is a config change, not a
+//! redeploy.
+//!
+//! ```rust,ignore
+//! App::new().auto_configure().with_services(ServiceRegistry::from_config(&config.services))
+//!
+//! async fn charge(Dep(services): Dep<ServiceRegistry>, Json(charge): Json<Charge>) -> ApiResult<Receipt> {
+//!     let response = services
+//!         .service("payments")?
+//!         .send(Method::POST, "/charges", |request| request.json(&charge))
+//!         .await?;
+//!     Ok(Json(response.json().await.map_err(|e| ApiError::ServiceUnavailable(e.to_string()))?))
+//! }
+//! ```
+
+use reqwest::{Method, RequestBuilder, Response};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::HttpClient;
+use crate::config::ServiceConfig;
+use crate::error::ApiError;
+
+/// The configured services, registered with [`App::with_services`](crate::App::with_services)
+#[derive(Clone, Default)]
+pub struct ServiceRegistry {
+    client: HttpClient,
+    services: HashMap<String, Service>,
+}
+
+impl ServiceRegistry {
+    pub fn new(client: HttpClient) -> Self {
+        Self {
+            client,
+            services: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(services: &HashMap<String, ServiceConfig>) -> Self {
+        services
+            .iter()
+            .fold(Self::default(), |registry, (name, config)| registry.with_service(name, config.clone()))
+    }
+
+    pub fn with_service(mut self, name: impl Into<String>, config: ServiceConfig) -> Self {
+        let name = name.into();
+        let service = Service {
+            name: name.clone().into(),
+            client: self.client.clone(),
+            state: Arc::new(ServiceState {
+                config,
+                on_secondary: AtomicBool::new(false),
+                consecutive_failures: AtomicU32::new(0),
+                requests: AtomicU64::new(0),
+                failures: AtomicU64::new(0),
+                failovers: AtomicU64::new(0),
+            }),
+        };
+        self.services.insert(name, service);
+        self
+    }
+
+    pub fn service(&self, name: &str) -> Result<Service, ApiError> {
+        self.services
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ApiError::InternalServerError(format!("Service '{}' is not configured", name)))
+    }
+
+    /// Probe every failed-over service's primary and switch back the healthy ones
+    pub async fn check_health(&self) {
+        for service in self.services.values() {
+            service.check_health().await;
+        }
+    }
+
+    /// Run [`check_health`](Self::check_health) every `interval` in the background
+    pub fn spawn_health_checks(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                registry.check_health().await;
+            }
+        })
+    }
+
+    /// Counters for every service, by name
+    pub fn stats(&self) -> HashMap<String, ServiceStats> {
+        self.services
+            .iter()
+            .map(|(name, service)| (name.clone(), service.stats()))
+            .collect()
+    }
+}
+
+struct ServiceState {
+    config: ServiceConfig,
+    on_secondary: AtomicBool,
+    consecutive_failures: AtomicU32,
+    requests: AtomicU64,
+    failures: AtomicU64,
+    failovers: AtomicU64,
+}
+
+/// Counters of one service since startup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceStats {
+    /// The endpoint calls currently go to
+    pub active: String,
+    pub requests: u64,
+    pub failures: u64,
+    pub failovers: u64,
+}
+
+/// One external service, cheap to clone
+#[derive(Clone)]
+pub struct Service {
+    name: Arc<str>,
+    client: HttpClient,
+    state: Arc<ServiceState>,
+}
+
+impl Service {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Base URL of the endpoint calls currently go to
+    pub fn active_endpoint(&self) -> &str {
+        match (&self.state.config.secondary, self.state.on_secondary.load(Ordering::Relaxed)) {
+            (Some(secondary), true) => secondary,
+            _ => &self.state.config.primary,
+        }
+    }
+
+    /// `path` on the active endpoint
+    pub fn url(&self, path: &str) -> String {
+        join(self.active_endpoint(), path)
+    }
+
+    /// Call `path` on the active endpoint, with `build` adding headers and body
+    ///
+    /// Idempotent requests that fail are retried once on the other endpoint.
+    /// A response of the last endpoint tried is returned whatever its status;
+    /// only when no endpoint could be reached is this a `ServiceUnavailable`.
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response, ApiError> {
+        let state = &self.state;
+        let mut endpoints = vec![self.active_endpoint().to_string()];
+        if let Some(secondary) = &state.config.secondary {
+            let other = if endpoints[0] == *secondary { &state.config.primary } else { secondary };
+            if is_idempotent(&method) {
+                endpoints.push(other.clone());
+            }
+        }
+
+        let mut last_error = None;
+        for (attempt, endpoint) in endpoints.iter().enumerate() {
+            let is_last = attempt + 1 == endpoints.len();
+            state.requests.fetch_add(1, Ordering::Relaxed);
+            let result = build(self.client.request(method.clone(), join(endpoint, path))).send().await;
+            let failed = match &result {
+                Ok(response) => response.status().is_server_error(),
+                Err(_) => true,
+            };
+            self.record(endpoint, failed);
+            match result {
+                Ok(response) if !failed || is_last => return Ok(response),
+                Ok(response) => {
+                    tracing::warn!(service = %self.name, endpoint, status = %response.status(), "Service call failed, trying the other endpoint");
+                }
+                Err(error) => {
+                    tracing::warn!(service = %self.name, endpoint, error = %error, "Service call failed");
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(ApiError::ServiceUnavailable(format!(
+            "Service '{}' is unavailable: {}",
+            self.name,
+            last_error.map(|error| error.to_string()).unwrap_or_default()
+        )))
+    }
+
+    /// Count the outcome of a call to `endpoint`, failing over when the primary keeps failing
+    fn record(&self, endpoint: &str, failed: bool) {
+        let state = &self.state;
+        #[cfg(feature = "observability")]
+        crate::metrics::record_counter(
+            "service_requests_total",
+            1,
+            &[
+                ("service", self.name.to_string()),
+                ("endpoint", endpoint.to_string()),
+                ("outcome", if failed { "failure" } else { "success" }.to_string()),
+            ],
+        );
+        if !failed {
+            if endpoint == state.config.primary {
+                state.consecutive_failures.store(0, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        state.failures.fetch_add(1, Ordering::Relaxed);
+        if endpoint != state.config.primary || state.config.secondary.is_none() {
+            return;
+        }
+        let failures = state.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= state.config.failure_threshold && !state.on_secondary.swap(true, Ordering::Relaxed) {
+            state.failovers.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(service = %self.name, failures, "Failing over to the secondary endpoint");
+            #[cfg(feature = "observability")]
+            crate::metrics::record_counter("service_failovers_total", 1, &[("service", self.name.to_string())]);
+        }
+    }
+
+    /// While failed over, switch back if the primary's health path answers with 2xx
+    pub async fn check_health(&self) {
+        let state = &self.state;
+        if !state.on_secondary.load(Ordering::Relaxed) {
+            return;
+        }
+        let url = join(&state.config.primary, &state.config.health_path);
+        let healthy = self
+            .client
+            .get(url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .is_ok_and(|response| response.status().is_success());
+        if healthy {
+            state.consecutive_failures.store(0, Ordering::Relaxed);
+            state.on_secondary.store(false, Ordering::Relaxed);
+            tracing::info!(service = %self.name, "Primary endpoint is healthy again, switching back");
+        }
+    }
+
+    pub fn stats(&self) -> ServiceStats {
+        let state = &self.state;
+        ServiceStats {
+            active: self.active_endpoint().to_string(),
+            requests: state.requests.load(Ordering::Relaxed),
+            failures: state.failures.load(Ordering::Relaxed),
+            failovers: state.failovers.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS)
+}
+
+fn join(base: &str, path: &str) -> String {
+    format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, Router};
+
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_failover_and_recovery() {
+        let up = Arc::new(AtomicBool::new(false));
+        let status = {
+            let up = up.clone();
+            move || {
+                let up = up.clone();
+                async move {
+                    match up.load(Ordering::Relaxed) {
+                        true => (StatusCode::OK, "blue"),
+                        false => (StatusCode::SERVICE_UNAVAILABLE, "down"),
+                    }
+                }
+            }
+        };
+        let blue = serve(Router::new().route("/rates", get(status.clone())).route("/health", get(status))).await;
+        let green = serve(Router::new().route("/rates", get(|| async { "green" }))).await;
+
+        let registry = ServiceRegistry::default().with_service(
+            "rates",
+            ServiceConfig::new(&blue).with_secondary(&green).with_failure_threshold(2),
+        );
+        let rates = registry.service("rates").unwrap();
+        let call = || async { rates.send(Method::GET, "/rates", |request| request).await.unwrap().text().await.unwrap() };
+
+        // Retried on green, but blue stays active until the threshold
+        assert_eq!(call().await, "green");
+        assert_eq!(rates.active_endpoint(), blue);
+        assert_eq!(call().await, "green");
+        assert_eq!(rates.active_endpoint(), green);
+
+        registry.check_health().await;
+        assert_eq!(rates.active_endpoint(), green);
+        up.store(true, Ordering::Relaxed);
+        registry.check_health().await;
+        assert_eq!(call().await, "blue");
+
+        let stats = &registry.stats()["rates"];
+        assert_eq!((stats.requests, stats.failures, stats.failovers), (5, 2, 1));
+        assert!(registry.service("missing").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_service() {
+        let registry = ServiceRegistry::default().with_service("gone", ServiceConfig::new("http://127.0.0.1:9"));
+        let error = registry
+            .service("gone")
+            .unwrap()
+            .send(Method::POST, "/orders", |request| request.body("{}"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ApiError::ServiceUnavailable(_)));
+    }
+}
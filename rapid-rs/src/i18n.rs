@@ -0,0 +1,159 @@
+//! Message translation
+//!
+//! A [`Translator`] maps message keys to text for a locale. [`Catalog`] is
+//! the built-in in-memory implementation; templates interpolate `{param}`
+//! placeholders.
+//!
+//! ```rust,ignore
+//! let catalog = Catalog::new()
+//!     .with_message("en", "validation.length", "Must be at least {min} characters")
+//!     .with_message("fr", "validation.length", "Doit contenir au moins {min} caractères");
+//! ```
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Looks up message templates by locale and key
+pub trait Translator: Send + Sync {
+    /// Template for `key` in `locale`, if there is one
+    fn translate(&self, locale: &str, key: &str) -> Option<String>;
+}
+
+/// Translator handle stored by subsystems
+pub type SharedTranslator = Arc<dyn Translator>;
+
+/// In-memory message catalog
+///
+/// Lookups for a regional locale (`fr-CA`) fall back to its language (`fr`),
+/// then to the fallback locale if one is set.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    messages: HashMap<String, HashMap<String, String>>,
+    fallback_locale: Option<String>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_message(mut self, locale: impl Into<String>, key: impl Into<String>, template: impl Into<String>) -> Self {
+        self.messages
+            .entry(normalize_locale(&locale.into()))
+            .or_default()
+            .insert(key.into(), template.into());
+        self
+    }
+
+    pub fn with_messages<K, T>(mut self, locale: impl Into<String>, messages: impl IntoIterator<Item = (K, T)>) -> Self
+    where
+        K: Into<String>,
+        T: Into<String>,
+    {
+        let entry = self.messages.entry(normalize_locale(&locale.into())).or_default();
+        entry.extend(messages.into_iter().map(|(k, t)| (k.into(), t.into())));
+        self
+    }
+
+    /// Locale used when the requested one has no message for a key
+    pub fn with_fallback_locale(mut self, locale: impl Into<String>) -> Self {
+        self.fallback_locale = Some(normalize_locale(&locale.into()));
+        self
+    }
+
+    fn lookup(&self, locale: &str, key: &str) -> Option<&String> {
+        self.messages.get(locale).and_then(|messages| messages.get(key))
+    }
+}
+
+impl Translator for Catalog {
+    fn translate(&self, locale: &str, key: &str) -> Option<String> {
+        let locale = normalize_locale(locale);
+        let language = locale.split('-').next().unwrap_or(&locale);
+
+        self.lookup(&locale, key)
+            .or_else(|| self.lookup(language, key))
+            .or_else(|| {
+                self.fallback_locale
+                    .as_deref()
+                    .and_then(|fallback| self.lookup(fallback, key))
+            })
+            .cloned()
+    }
+}
+
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Replace `{name}` placeholders with values from `params`
+///
+/// Strings are inserted without quotes; unknown placeholders are left as is.
+pub fn interpolate<'a, K>(template: &str, params: impl IntoIterator<Item = (K, &'a Value)>) -> String
+where
+    K: AsRef<str>,
+{
+    params.into_iter().fold(template.to_string(), |text, (name, value)| {
+        let placeholder = format!("{{{}}}", name.as_ref());
+        if !text.contains(&placeholder) {
+            return text;
+        }
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        text.replace(&placeholder, &value)
+    })
+}
+
+/// Locales from an `Accept-Language` header, most preferred first
+pub fn preferred_locales(accept_language: &str) -> Vec<String> {
+    let mut locales: Vec<(String, f32)> = accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let locale = parts.next()?.trim();
+            if locale.is_empty() || locale == "*" {
+                return None;
+            }
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then(|| (normalize_locale(locale), quality))
+        })
+        .collect();
+
+    // Stable sort keeps header order for equal weights
+    locales.sort_by(|a, b| b.1.total_cmp(&a.1));
+    locales.into_iter().map(|(locale, _)| locale).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_fallbacks() {
+        let catalog = Catalog::new()
+            .with_message("en", "greeting", "Hello {name}")
+            .with_message("fr", "greeting", "Bonjour {name}")
+            .with_fallback_locale("en");
+
+        assert_eq!(catalog.translate("fr-CA", "greeting").unwrap(), "Bonjour {name}");
+        assert_eq!(catalog.translate("de", "greeting").unwrap(), "Hello {name}");
+        assert!(catalog.translate("fr", "missing").is_none());
+
+        let name = Value::from("Ada");
+        assert_eq!(interpolate("Hello {name}", [("name", &name)]), "Hello Ada");
+    }
+
+    #[test]
+    fn test_preferred_locales() {
+        assert_eq!(
+            preferred_locales("fr-CH, fr;q=0.9, en;q=0.8, de;q=0, *;q=0.5"),
+            vec!["fr-ch", "fr", "en"]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! ID generation for users, jobs, workflows, requests and tenants
+//!
+//! Random UUIDv4 is the default. Time-ordered strategies ([`UuidV7`], [`Ulid`],
+//! [`Snowflake`]) keep primary-key indexes append-only, which matters once
+//! tables get large.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .with_id_generator(Arc::new(UuidV7))
+//!     .auto_configure()
+//!     .run()
+//!     .await
+//! ```
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// A strategy for generating unique IDs
+pub trait IdGenerator: Send + Sync + Debug {
+    /// ID for `Uuid`-typed keys such as job and workflow ids
+    fn new_uuid(&self) -> Uuid;
+
+    /// ID for string keys such as user, request and tenant ids
+    fn new_id(&self) -> String {
+        self.new_uuid().to_string()
+    }
+}
+
+/// Generator handle stored by subsystems
+pub type SharedIdGenerator = Arc<dyn IdGenerator>;
+
+static GENERATOR: OnceLock<RwLock<SharedIdGenerator>> = OnceLock::new();
+
+fn global() -> &'static RwLock<SharedIdGenerator> {
+    GENERATOR.get_or_init(|| RwLock::new(Arc::new(UuidV4)))
+}
+
+/// The process-wide generator, UUIDv4 unless replaced
+pub fn generator() -> SharedIdGenerator {
+    global().read().unwrap().clone()
+}
+
+/// Replace the process-wide generator
+///
+/// Usually called through `App::with_id_generator`.
+pub fn set_generator(generator: SharedIdGenerator) {
+    *global().write().unwrap() = generator;
+}
+
+/// New `Uuid` from the process-wide generator
+pub fn new_uuid() -> Uuid {
+    generator().new_uuid()
+}
+
+/// New string ID from the process-wide generator
+pub fn new_id() -> String {
+    generator().new_id()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Random UUIDv4
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV4;
+
+impl IdGenerator for UuidV4 {
+    fn new_uuid(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Time-ordered UUIDv7
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7;
+
+impl IdGenerator for UuidV7 {
+    fn new_uuid(&self) -> Uuid {
+        Uuid::now_v7()
+    }
+}
+
+/// ULID: 48-bit millisecond timestamp and 80 random bits
+///
+/// String IDs use the 26-character Crockford base32 form; `Uuid` keys carry
+/// the same 128 bits.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ulid;
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+impl IdGenerator for Ulid {
+    fn new_uuid(&self) -> Uuid {
+        let random = u128::from_be_bytes(*Uuid::new_v4().as_bytes()) & ((1 << 80) - 1);
+        let timestamp = (unix_millis() as u128 & ((1 << 48) - 1)) << 80;
+        Uuid::from_u128(timestamp | random)
+    }
+
+    fn new_id(&self) -> String {
+        let value = self.new_uuid().as_u128();
+        (0..26)
+            .rev()
+            .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char)
+            .collect()
+    }
+}
+
+/// Twitter-style Snowflake IDs
+///
+/// 41 bits of milliseconds since 2020-01-01, a 10-bit worker id and a 12-bit
+/// per-millisecond sequence. Every instance needs its own worker id. String
+/// IDs are the decimal `u64`; `Uuid` keys put it in the high 64 bits
+/// followed by random bits.
+#[derive(Debug)]
+pub struct Snowflake {
+    worker_id: u64,
+    state: Mutex<(u64, u64)>,
+}
+
+impl Snowflake {
+    /// 2020-01-01T00:00:00Z in Unix milliseconds
+    pub const EPOCH_MILLIS: u64 = 1_577_836_800_000;
+
+    /// Generator for `worker_id` (only the low 10 bits are used)
+    pub fn new(worker_id: u16) -> Self {
+        Self {
+            worker_id: worker_id as u64 & 0x3ff,
+            state: Mutex::new((0, 0)),
+        }
+    }
+
+    pub fn next(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let (last_millis, sequence) = *state;
+
+        let mut millis = unix_millis().saturating_sub(Self::EPOCH_MILLIS).max(last_millis);
+        let sequence = if millis == last_millis {
+            let next = (sequence + 1) & 0xfff;
+            if next == 0 {
+                // Sequence exhausted for this millisecond; wait for the next one
+                while millis <= last_millis {
+                    std::hint::spin_loop();
+                    millis = unix_millis().saturating_sub(Self::EPOCH_MILLIS);
+                }
+            }
+            next
+        } else {
+            0
+        };
+
+        *state = (millis, sequence);
+        (millis << 22) | (self.worker_id << 12) | sequence
+    }
+}
+
+impl IdGenerator for Snowflake {
+    fn new_uuid(&self) -> Uuid {
+        let random = Uuid::new_v4().as_u64_pair().1;
+        Uuid::from_u64_pair(self.next(), random)
+    }
+
+    fn new_id(&self) -> String {
+        self.next().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_ordered_generators() {
+        let v7: Vec<Uuid> = (0..100).map(|_| UuidV7.new_uuid()).collect();
+        assert_eq!(v7[0].get_version_num(), 7);
+        assert!(v7.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let ulid = Ulid.new_id();
+        assert_eq!(ulid.len(), 26);
+        assert!(ulid.bytes().all(|b| CROCKFORD.contains(&b)));
+
+        let snowflake = Snowflake::new(7);
+        let ids: Vec<u64> = (0..5000).map(|_| snowflake.next()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!((ids[0] >> 12) & 0x3ff, 7);
+    }
+}
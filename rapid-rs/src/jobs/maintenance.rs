@@ -0,0 +1,271 @@
+//! Scheduled maintenance tasks
+//!
+//! The housekeeping most deployments need, run on the schedules of the
+//! `[maintenance]` config section:
+//!
+//! ```rust,ignore
+//! let maintenance = Maintenance::new(config.maintenance.clone())
+//!     .with_database(pool.clone())
+//!     .with_job_storage(PostgresJobStorage::new(pool.clone()))
+//!     .with_sessions(sessions.clone())
+//!     .with_lock(cache.clone());
+//!
+//! App::new().auto_configure().with_maintenance(maintenance)
+//! ```
+//!
+//! With several instances, [`with_lock`](Maintenance::with_lock) makes sure
+//! only one of them runs each task.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use super::scheduler::Schedule;
+use super::storage::JobStorage;
+use crate::clock::SharedClock;
+use crate::config::MaintenanceConfig;
+use crate::error::ApiError;
+
+type TaskFn = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<u64, ApiError>> + Send>> + Send + Sync>;
+
+struct Task {
+    name: String,
+    schedule: Schedule,
+    run: TaskFn,
+}
+
+/// Maintenance tasks and their schedules
+pub struct Maintenance {
+    config: MaintenanceConfig,
+    tasks: Vec<Task>,
+    clock: SharedClock,
+    #[cfg(feature = "cache")]
+    lock: Option<Arc<crate::cache::Cache>>,
+}
+
+impl Maintenance {
+    pub fn new(config: MaintenanceConfig) -> Self {
+        Self {
+            config,
+            tasks: Vec::new(),
+            clock: crate::clock::system(),
+            #[cfg(feature = "cache")]
+            lock: None,
+        }
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Run `task` on `schedule`; it returns how many items it processed
+    pub fn with_task<F, Fut>(mut self, name: impl Into<String>, schedule: Schedule, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<u64, ApiError>> + Send + 'static,
+    {
+        self.tasks.push(Task {
+            name: name.into(),
+            schedule,
+            run: Arc::new(move || Box::pin(task())),
+        });
+        self
+    }
+
+    /// Add a built-in task if the config gives it a valid schedule
+    fn with_configured<F, Fut>(self, name: &str, schedule: Option<&String>, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<u64, ApiError>> + Send + 'static,
+    {
+        let Some(expression) = schedule else {
+            return self;
+        };
+        match Schedule::cron(expression) {
+            Ok(schedule) => self.with_task(name, schedule, task),
+            Err(error) => {
+                tracing::warn!(task = name, error = %error, "Invalid maintenance schedule, task disabled");
+                self
+            }
+        }
+    }
+
+    /// `ANALYZE` and `VACUUM` the database as configured
+    pub fn with_database(self, pool: PgPool) -> Self {
+        let analyze_pool = pool.clone();
+        let analyze = self.config.analyze.clone();
+        let vacuum = self.config.vacuum.clone();
+        let statements: Vec<String> = if self.config.vacuum_tables.is_empty() {
+            vec!["VACUUM (ANALYZE)".to_string()]
+        } else {
+            self.config
+                .vacuum_tables
+                .iter()
+                .map(|table| format!("VACUUM (ANALYZE) {}", crate::database::bulk::quote_ident(table)))
+                .collect()
+        };
+
+        self.with_configured("analyze", analyze.as_ref(), move || {
+            let pool = analyze_pool.clone();
+            async move {
+                sqlx::raw_sql("ANALYZE").execute(&pool).await?;
+                Ok(0)
+            }
+        })
+        .with_configured("vacuum", vacuum.as_ref(), move || {
+            let (pool, statements) = (pool.clone(), statements.clone());
+            async move {
+                // VACUUM can't run in a transaction, so one statement at a time
+                for statement in &statements {
+                    sqlx::raw_sql(statement).execute(&pool).await?;
+                }
+                Ok(statements.len() as u64)
+            }
+        })
+    }
+
+    /// Delete completed jobs past the retention period
+    pub fn with_job_storage(self, storage: impl JobStorage) -> Self {
+        let storage = Arc::new(storage);
+        let (schedule, days) = (self.config.job_cleanup.clone(), self.config.job_retention_days);
+        self.with_configured("job-cleanup", schedule.as_ref(), move || {
+            let storage = storage.clone();
+            async move { Ok(storage.cleanup_old_jobs(days).await? as u64) }
+        })
+    }
+
+    /// Purge expired sessions
+    #[cfg(feature = "sessions")]
+    pub fn with_sessions(self, sessions: crate::auth::Sessions) -> Self {
+        let schedule = self.config.session_purge.clone();
+        self.with_configured("session-purge", schedule.as_ref(), move || {
+            let sessions = sessions.clone();
+            async move { sessions.delete_expired().await }
+        })
+    }
+
+    /// Remove stale resumable uploads
+    #[cfg(feature = "file-uploads")]
+    pub fn with_resumable_uploads(self, uploads: Arc<crate::uploads::ResumableUploads>) -> Self {
+        let schedule = self.config.upload_expiry.clone();
+        self.with_configured("upload-expiry", schedule.as_ref(), move || {
+            let uploads = uploads.clone();
+            async move { Ok(uploads.expire_stale().await? as u64) }
+        })
+    }
+
+    /// Take a [lock](crate::cache::CacheLock) on `cache` around each run, so
+    /// instances sharing it don't run the same task at once
+    #[cfg(feature = "cache")]
+    pub fn with_lock(mut self, cache: Arc<crate::cache::Cache>) -> Self {
+        self.lock = Some(cache);
+        self
+    }
+
+    /// Names of the scheduled tasks
+    pub fn task_names(&self) -> Vec<&str> {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+        self.tasks.iter().map(|task| task.name.as_str()).collect()
+    }
+
+    /// Run the task named `name` now, returning how many items it processed
+    pub async fn run_now(&self, name: &str) -> Result<u64, ApiError> {
+        let task = self
+            .tasks
+            .iter()
+            .find(|task| task.name == name)
+            .ok_or_else(|| ApiError::NotFound(format!("Maintenance task '{}' not found", name)))?;
+        (task.run)().await
+    }
+
+    /// Run every task on its schedule in the background
+    ///
+    /// Does nothing when the config disables maintenance.
+    pub fn spawn(self) -> Vec<JoinHandle<()>> {
+        if !self.config.enabled {
+            tracing::info!("Maintenance tasks disabled");
+            return Vec::new();
+        }
+        #[cfg(feature = "cache")]
+        let lock = self.lock;
+        self.tasks
+            .into_iter()
+            .map(|task| {
+                let clock = self.clock.clone();
+                #[cfg(feature = "cache")]
+                let lock = lock.clone();
+                tokio::spawn(async move {
+                    while task.schedule.wait_next(clock.as_ref()).await.is_some() {
+                        #[cfg(feature = "cache")]
+                        let _held = match &lock {
+                            Some(cache) => {
+                                let key = format!("maintenance:{}", task.name);
+                                match cache.try_lock(&key, std::time::Duration::from_secs(3600)).await {
+                                    Ok(Some(held)) => Some(held),
+                                    Ok(None) => continue,
+                                    Err(error) => {
+                                        tracing::warn!(task = %task.name, error = %error, "Failed to lock maintenance task");
+                                        continue;
+                                    }
+                                }
+                            }
+                            None => None,
+                        };
+                        let started = std::time::Instant::now();
+                        match (task.run)().await {
+                            Ok(processed) => tracing::info!(
+                                task = %task.name,
+                                processed,
+                                duration_ms = started.elapsed().as_millis() as u64,
+                                "Maintenance task finished"
+                            ),
+                            Err(error) => tracing::warn!(task = %task.name, error = %error, "Maintenance task failed"),
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::{InMemoryJobStorage, JobMetadata, JobStatus};
+
+    #[tokio::test]
+    async fn test_configured_tasks() {
+        let config = MaintenanceConfig {
+            session_purge: None,
+            upload_expiry: Some("not a cron".to_string()),
+            ..MaintenanceConfig::default()
+        };
+        let clock = crate::clock::ManualClock::frozen();
+        let storage = InMemoryJobStorage::new().with_clock(clock.shared());
+        let done = JobMetadata {
+            status: JobStatus::Completed,
+            completed_at: Some(clock.shared().now() - chrono::Duration::days(31)),
+            ..JobMetadata::default()
+        };
+        storage.save_job(&done, serde_json::json!({})).await.unwrap();
+
+        let maintenance = Maintenance::new(config.clone())
+            .with_job_storage(storage.clone())
+            .with_task("reindex", Schedule::every(60), || async { Ok(3) });
+        assert_eq!(maintenance.task_names(), ["job-cleanup", "reindex"]);
+        assert_eq!(maintenance.run_now("job-cleanup").await.unwrap(), 1);
+        assert_eq!(maintenance.run_now("reindex").await.unwrap(), 3);
+        assert!(maintenance.run_now("vacuum").await.is_err());
+
+        let disabled = Maintenance::new(MaintenanceConfig { enabled: false, ..config })
+            .with_task("reindex", Schedule::every(60), || async { Ok(3) });
+        assert!(disabled.task_names().is_empty());
+        assert!(disabled.spawn().is_empty());
+    }
+}
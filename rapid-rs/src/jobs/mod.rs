@@ -2,21 +2,33 @@
 //!
 //! Provides async task queue with retry logic, scheduling, and monitoring.
 
+pub mod maintenance;
 pub mod queue;
 pub mod worker;
 pub mod scheduler;
 pub mod storage;
+pub mod workflow;
 
-pub use queue::{JobQueue, JobConfig, JobPriority};
-pub use worker::{Job, JobContext, JobResult};
-pub use scheduler::{CronSchedule, Schedule};
+pub use maintenance::Maintenance;
+pub use queue::{
+    job_admin_routes, job_status_routes, JobConfig, JobDetails, JobPriority, JobQueue, JobStatusReport, OverflowPolicy,
+    WorkerCounts,
+};
+pub use worker::{Job, JobContext, JobRegistry, JobResult};
+pub use scheduler::{CatchUp, CronField, CronSchedule, Overlap, RecurringJob, Schedule, ScheduleError, Spread};
 pub use storage::{JobStorage, InMemoryJobStorage};
+pub use workflow::{
+    InMemoryWorkflowStorage, Workflow, WorkflowContext, WorkflowEngine, WorkflowState,
+    WorkflowStatus, WorkflowStep, WorkflowStorage, workflow_routes, WORKFLOW_JOB_TYPE,
+};
 
 #[cfg(feature = "database")]
 pub use storage::PostgresJobStorage;
 
+#[cfg(feature = "database")]
+pub use workflow::PostgresWorkflowStorage;
+
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
 use uuid::Uuid;
 
 /// Job status
@@ -44,12 +56,24 @@ pub struct JobMetadata {
     pub started_at: Option<chrono::DateTime<chrono::Utc>>,
     pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub error: Option<String>,
+    /// Last progress the job reported, see [`JobContext::set_progress`]
+    #[serde(default)]
+    pub progress: Option<JobProgress>,
+}
+
+/// How far a running job has got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// 0 to 100
+    pub percent: u8,
+    pub message: Option<String>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
 impl Default for JobMetadata {
     fn default() -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id: crate::ids::new_uuid(),
             job_type: String::new(),
             priority: JobPriority::Normal,
             status: JobStatus::Pending,
@@ -60,6 +84,7 @@ impl Default for JobMetadata {
             started_at: None,
             completed_at: None,
             error: None,
+            progress: None,
         }
     }
 }
\ No newline at end of file
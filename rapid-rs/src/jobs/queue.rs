@@ -1,12 +1,22 @@
 //! Job queue implementation
 
-use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, Notify, RwLock};
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
-use super::{JobMetadata, JobStatus, JobStorage};
+use super::scheduler::{CatchUp, Overlap, RecurringJob, Schedule};
+use super::{Job, JobContext, JobMetadata, JobProgress, JobRegistry, JobStatus, JobStorage};
+use crate::clock::SharedClock;
 use crate::error::ApiError;
 
 /// Job priority levels
@@ -42,11 +52,60 @@ impl Default for JobConfig {
     }
 }
 
+/// What enqueueing does when a pending-job limit is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Fail with `ApiError::ServiceUnavailable`
+    #[default]
+    Reject,
+    /// Wait up to this long for workers to catch up, then reject
+    Wait(Duration),
+}
+
+/// How often a waiting producer re-checks the pending count
+const OVERFLOW_POLL: Duration = Duration::from_millis(100);
+
 /// Job queue for managing background tasks
+///
+/// Workers run jobs with the handlers registered for their type, within the
+/// job's [`Job::timeout_seconds`](super::Job::timeout_seconds) or the
+/// configured timeout. Failed jobs are retried with exponential backoff and
+/// jitter, and marked [`JobStatus::Dead`] once out of retries; dead jobs can
+/// be listed, inspected and re-enqueued with [`retry_dead`](Self::retry_dead):
+///
+/// ```rust,ignore
+/// let queue = Arc::new(JobQueue::new(storage, JobConfig::default()));
+/// queue.registry().register::<SendEmail>("send_email").await;
+/// queue.start_workers().await;
+///
+/// // Recurring jobs are enqueued by the scheduler as they come due
+/// queue.register_recurring("nightly-cleanup", Schedule::cron("0 3 * * *")?, Cleanup).await?;
+/// queue.start_scheduler();
+///
+/// // Later, to work through a backlog spike
+/// queue.scale_workers(16).await;
+/// ```
+///
+/// Limits on pending jobs stop a runaway producer from growing storage
+/// without bound:
+///
+/// ```rust,ignore
+/// let queue = JobQueue::new(storage, JobConfig::default())
+///     .with_max_pending(10_000)
+///     .with_max_pending_for("send_email", 1_000)
+///     .with_overflow(OverflowPolicy::Wait(Duration::from_secs(5)));
+/// ```
 pub struct JobQueue<S: JobStorage> {
     storage: Arc<S>,
     config: JobConfig,
-    workers: Arc<RwLock<Vec<tokio::task::JoinHandle<()>>>>,
+    clock: SharedClock,
+    registry: Arc<JobRegistry>,
+    workers: Arc<RwLock<Workers>>,
+    recurring: Mutex<Vec<Recurring>>,
+    recurring_changed: Notify,
+    max_pending: Option<usize>,
+    max_pending_by_type: HashMap<String, usize>,
+    overflow: OverflowPolicy,
 }
 
 impl<S: JobStorage> JobQueue<S> {
@@ -55,10 +114,92 @@ impl<S: JobStorage> JobQueue<S> {
         Self {
             storage: Arc::new(storage),
             config,
-            workers: Arc::new(RwLock::new(Vec::new())),
+            clock: crate::clock::system(),
+            registry: Arc::new(JobRegistry::new()),
+            workers: Arc::new(RwLock::new(Workers::default())),
+            recurring: Mutex::new(Vec::new()),
+            recurring_changed: Notify::new(),
+            max_pending: None,
+            max_pending_by_type: HashMap::new(),
+            overflow: OverflowPolicy::Reject,
         }
     }
     
+    /// Timestamp jobs with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Run jobs with the handlers in `registry`
+    pub fn with_registry(mut self, registry: Arc<JobRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+    
+    /// Handlers workers run jobs with
+    pub fn registry(&self) -> &Arc<JobRegistry> {
+        &self.registry
+    }
+    
+    /// Cap the number of pending jobs across all types
+    pub fn with_max_pending(mut self, max: usize) -> Self {
+        self.max_pending = Some(max);
+        self
+    }
+    
+    /// Cap the number of pending jobs of `job_type`
+    pub fn with_max_pending_for(mut self, job_type: impl Into<String>, max: usize) -> Self {
+        self.max_pending_by_type.insert(job_type.into(), max);
+        self
+    }
+    
+    /// What to do when a limit is reached; rejects by default
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+    
+    /// Wait for room under the pending limits, or fail per the overflow policy
+    ///
+    /// The check and the insert are separate steps, so concurrent producers
+    /// can overshoot a limit slightly.
+    async fn reserve(&self, job_type: &str) -> Result<(), ApiError> {
+        let deadline = match self.overflow {
+            OverflowPolicy::Reject => None,
+            OverflowPolicy::Wait(timeout) => chrono::Duration::from_std(timeout)
+                .ok()
+                .and_then(|timeout| self.clock.now().checked_add_signed(timeout))
+                .or(Some(chrono::DateTime::<chrono::Utc>::MAX_UTC)),
+        };
+        
+        loop {
+            let Some(full) = self.full_limit(job_type).await? else {
+                return Ok(());
+            };
+            if deadline.is_none_or(|deadline| self.clock.now() >= deadline) {
+                tracing::warn!(job_type = %job_type, limit = %full, "Job queue full, rejecting job");
+                return Err(ApiError::ServiceUnavailable(format!("Job queue is full ({})", full)));
+            }
+            self.clock.sleep(OVERFLOW_POLL).await;
+        }
+    }
+    
+    /// The first limit that is currently reached, if any
+    async fn full_limit(&self, job_type: &str) -> Result<Option<String>, ApiError> {
+        if let Some(&max) = self.max_pending_by_type.get(job_type) {
+            if self.storage.count_pending(Some(job_type)).await? >= max {
+                return Ok(Some(format!("{} pending {} jobs", max, job_type)));
+            }
+        }
+        if let Some(max) = self.max_pending {
+            if self.storage.count_pending(None).await? >= max {
+                return Ok(Some(format!("{} pending jobs", max)));
+            }
+        }
+        Ok(None)
+    }
+    
     /// Enqueue a job with default priority
     pub async fn enqueue<J: Serialize>(
         &self,
@@ -78,11 +219,15 @@ impl<S: JobStorage> JobQueue<S> {
     ) -> Result<Uuid, ApiError> {
         let payload = serde_json::to_value(job)
             .map_err(|e| ApiError::InternalServerError(format!("Failed to serialize job: {}", e)))?;
+        self.reserve(job_type).await?;
         
-        let mut metadata = JobMetadata::default();
-        metadata.job_type = job_type.to_string();
-        metadata.priority = priority;
-        metadata.max_retries = self.config.max_retries;
+        let metadata = JobMetadata {
+            created_at: self.clock.now(),
+            job_type: job_type.to_string(),
+            priority,
+            max_retries: self.config.max_retries,
+            ..Default::default()
+        };
         
         self.storage.save_job(&metadata, payload).await?;
         
@@ -105,11 +250,15 @@ impl<S: JobStorage> JobQueue<S> {
     ) -> Result<Uuid, ApiError> {
         let payload = serde_json::to_value(job)
             .map_err(|e| ApiError::InternalServerError(format!("Failed to serialize job: {}", e)))?;
+        self.reserve(job_type).await?;
         
-        let mut metadata = JobMetadata::default();
-        metadata.job_type = job_type.to_string();
-        metadata.scheduled_at = Some(scheduled_at);
-        metadata.max_retries = self.config.max_retries;
+        let metadata = JobMetadata {
+            created_at: self.clock.now(),
+            job_type: job_type.to_string(),
+            scheduled_at: Some(scheduled_at),
+            max_retries: self.config.max_retries,
+            ..Default::default()
+        };
         
         self.storage.save_job(&metadata, payload).await?;
         
@@ -153,66 +302,295 @@ impl<S: JobStorage> JobQueue<S> {
         self.storage.get_stats().await
     }
     
-    /// Start background workers
-    pub async fn start_workers(&self) {
-        let mut workers = self.workers.write().await;
+    /// A job's metadata and payload
+    pub async fn inspect(&self, job_id: Uuid) -> Result<JobDetails, ApiError> {
+        Ok(JobDetails {
+            metadata: self.storage.get_job(job_id).await?,
+            payload: self.storage.get_payload(job_id).await?,
+        })
+    }
+    
+    /// Jobs that ran out of retries, most recent first
+    pub async fn dead_jobs(&self, limit: usize) -> Result<Vec<JobMetadata>, ApiError> {
+        self.storage.list_jobs(JobStatus::Dead, limit).await
+    }
+    
+    /// Put a dead job back on the queue with a fresh set of retries
+    ///
+    /// The last error is kept until the job next runs.
+    pub async fn retry_dead(&self, job_id: Uuid) -> Result<(), ApiError> {
+        let JobDetails { mut metadata, payload } = self.inspect(job_id).await?;
+        if metadata.status != JobStatus::Dead {
+            return Err(ApiError::BadRequest(format!(
+                "Cannot retry job with status {:?}",
+                metadata.status
+            )));
+        }
+        
+        metadata.status = JobStatus::Pending;
+        metadata.retry_count = 0;
+        metadata.scheduled_at = None;
+        metadata.started_at = None;
+        metadata.completed_at = None;
+        metadata.progress = None;
+        self.storage.save_job(&metadata, payload).await?;
+        
+        tracing::info!(job_id = %job_id, "Dead job re-enqueued");
+        Ok(())
+    }
+    
+    /// Enqueue `job` on `schedule` once [`start_scheduler`](Self::start_scheduler) runs
+    ///
+    /// Registering `name` again replaces the earlier entry. Use
+    /// [`add_recurring`](Self::add_recurring) for catch-up and overlap policies.
+    pub async fn register_recurring<J: Job>(&self, name: &str, schedule: Schedule, job: J) -> Result<(), ApiError> {
+        self.add_recurring(RecurringJob::new(name, schedule, job)?).await;
+        Ok(())
+    }
+    
+    /// Enqueue a [`RecurringJob`] once [`start_scheduler`](Self::start_scheduler) runs
+    ///
+    /// Each occurrence gets a job id derived from the job's name and time, so
+    /// instances sharing storage and running the same schedule enqueue it
+    /// once. Occurrence times only line up across instances for cron
+    /// schedules and intervals with a start time.
+    pub async fn add_recurring(&self, job: RecurringJob) {
+        let last_run = job.last_run.unwrap_or_else(|| self.clock.now());
+        let mut recurring = self.recurring.lock().await;
+        recurring.retain(|entry| entry.job.name != job.name);
+        tracing::info!(name = %job.name, schedule = %job.schedule.describe(), "Registered recurring job");
+        recurring.push(Recurring { job, last_run, last_job: None });
+        self.recurring_changed.notify_one();
+    }
+    
+    /// Stop enqueueing the recurring job `name`
+    pub async fn remove_recurring(&self, name: &str) {
+        self.recurring.lock().await.retain(|entry| entry.job.name != name);
+        self.recurring_changed.notify_one();
+    }
+    
+    /// Enqueue recurring jobs as they come due, in the background
+    pub fn start_scheduler(self: &Arc<Self>) -> JoinHandle<()> {
+        let queue = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                let next = queue.enqueue_due().await;
+                let changed = queue.recurring_changed.notified();
+                match next {
+                    Some(next) => tokio::select! {
+                        _ = queue.clock.sleep_until(next) => {}
+                        _ = changed => {}
+                    },
+                    None => changed.await,
+                }
+            }
+        })
+    }
+    
+    /// Enqueue the occurrences due by now, returning when the next one is
+    async fn enqueue_due(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let now = self.clock.now();
         
-        for i in 0..self.config.worker_count {
-            let storage = Arc::clone(&self.storage);
-            let config = self.config.clone();
+        // Enqueueing may wait for room in the queue, so only work out what is
+        // due under the lock and leave add/remove_recurring free meanwhile
+        let mut batches = Vec::new();
+        for entry in self.recurring.lock().await.iter_mut() {
+            // Occurrences due by now, each with the one before it
+            let mut due = Vec::new();
+            let mut after = entry.last_run;
+            while let Some(at) = entry.job.schedule.next_run(after).filter(|at| *at <= now) {
+                due.push((after, at));
+                after = at;
+                if due.len() >= MAX_CATCH_UP {
+                    break;
+                }
+            }
+            // Counting from the last occurrence keeps intervals on their cadence
+            entry.last_run = match due.last() {
+                Some(_) if due.len() >= MAX_CATCH_UP => now,
+                Some((_, at)) => *at,
+                None => entry.last_run,
+            };
             
-            let handle = tokio::spawn(async move {
-                tracing::info!("Worker {} started", i);
-                
-                loop {
-                    match storage.fetch_next_job().await {
-                        Ok(Some((mut metadata, payload))) => {
-                            metadata.status = JobStatus::Running;
-                            metadata.started_at = Some(chrono::Utc::now());
-                            
-                            if let Err(e) = storage.save_job(&metadata, payload.clone()).await {
-                                tracing::error!(job_id = %metadata.id, error = %e, "Failed to update job status");
-                                continue;
-                            }
-                            
-                            tracing::info!(
-                                job_id = %metadata.id,
-                                job_type = %metadata.job_type,
-                                "Processing job"
-                            );
-                            
-                            // Job execution would happen here via registered handlers
-                            // For now, mark as completed
-                            metadata.status = JobStatus::Completed;
-                            metadata.completed_at = Some(chrono::Utc::now());
-                            
-                            if let Err(e) = storage.save_job(&metadata, payload).await {
-                                tracing::error!(job_id = %metadata.id, error = %e, "Failed to complete job");
+            let runs = match entry.job.catch_up {
+                CatchUp::Skip => due.last().filter(|(_, at)| now - *at <= chrono::Duration::minutes(1)).copied().into_iter().collect(),
+                CatchUp::Once => due.last().copied().into_iter().collect(),
+                CatchUp::All => due,
+            };
+            let runs: Vec<_> = runs
+                .into_iter()
+                .map(|(previous, at)| {
+                    // Spread delays the run, but the occurrence keeps its nominal time
+                    let run_at = entry
+                        .job
+                        .spread
+                        .as_ref()
+                        .and_then(|spread| entry.job.schedule.next_run_with_spread(previous, spread))
+                        .filter(|run_at| *run_at > now);
+                    (at, run_at)
+                })
+                .collect();
+            if !runs.is_empty() {
+                batches.push((entry.job.clone(), entry.last_job, runs));
+            }
+        }
+        
+        for (job, mut last_job, runs) in batches {
+            for (at, run_at) in runs {
+                if let Some(id) = self.enqueue_occurrence(&job, last_job, at, run_at).await {
+                    last_job = Some(id);
+                }
+            }
+            if let Some(entry) = self.recurring.lock().await.iter_mut().find(|entry| entry.job.name == job.name) {
+                entry.last_job = last_job;
+            }
+        }
+        
+        self.recurring
+            .lock()
+            .await
+            .iter()
+            .filter_map(|entry| entry.job.schedule.next_run(entry.last_run))
+            .min()
+    }
+    
+    /// Enqueue the occurrence of `job` at `at`, returning its job id
+    ///
+    /// `previous` is the job of the occurrence before, for the overlap policy.
+    async fn enqueue_occurrence(
+        &self,
+        job: &RecurringJob,
+        previous: Option<Uuid>,
+        at: chrono::DateTime<chrono::Utc>,
+        run_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Option<Uuid> {
+        if let Some(previous) = previous {
+            let status = self.storage.get_job(previous).await.map(|metadata| metadata.status).ok();
+            if matches!(status, Some(JobStatus::Pending | JobStatus::Running)) {
+                match job.overlap {
+                    Overlap::Skip => {
+                        tracing::info!(name = %job.name, job_id = %previous, "Previous run unfinished, skipping recurring job");
+                        return None;
+                    }
+                    Overlap::Queue => {}
+                    Overlap::Replace => {
+                        if status == Some(JobStatus::Pending) {
+                            if let Err(e) = self.cancel(previous).await {
+                                tracing::warn!(name = %job.name, job_id = %previous, error = %e, "Failed to replace recurring job");
                             }
                         }
-                        Ok(None) => {
-                            // No jobs available, sleep briefly
-                            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                        }
-                        Err(e) => {
-                            tracing::error!(error = %e, "Error fetching job");
-                            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        }
+        
+        let metadata = JobMetadata {
+            id: job.occurrence_id(at),
+            created_at: self.clock.now(),
+            job_type: job.job_type.clone(),
+            priority: job.priority,
+            max_retries: self.config.max_retries,
+            scheduled_at: run_at,
+            ..Default::default()
+        };
+        let inserted = match self.reserve(&job.job_type).await {
+            Ok(()) => self.storage.insert_job(&metadata, job.payload.clone()).await,
+            Err(e) => Err(e),
+        };
+        match inserted {
+            Ok(true) => tracing::debug!(name = %job.name, job_id = %metadata.id, occurrence = %at, "Enqueued recurring job"),
+            Ok(false) => tracing::debug!(name = %job.name, job_id = %metadata.id, occurrence = %at, "Recurring job already enqueued"),
+            Err(e) => {
+                tracing::warn!(name = %job.name, error = %e, "Failed to enqueue recurring job");
+                return None;
+            }
+        }
+        Some(metadata.id)
+    }
+    
+    /// Start `worker_count` background workers
+    pub async fn start_workers(&self) {
+        self.scale_workers(self.config.worker_count).await;
+    }
+    
+    /// Run `count` workers from now on, without a restart
+    ///
+    /// New workers start straight away. Removed ones drain: they finish the
+    /// job they are running, then exit.
+    pub async fn scale_workers(&self, count: usize) -> WorkerCounts {
+        let mut workers = self.workers.write().await;
+        workers.draining.retain(|handle| !handle.is_finished());
+        
+        while workers.active.len() < count {
+            let id = workers.next_id;
+            workers.next_id += 1;
+            let worker = self.spawn_worker(id);
+            workers.active.push(worker);
+        }
+        for worker in workers.active.split_off(count) {
+            let _ = worker.stop.send(true);
+            workers.draining.push(worker.handle);
+        }
+        
+        tracing::info!(workers = count, draining = workers.draining.len(), "Scaled job workers");
+        workers.counts()
+    }
+    
+    /// Current number of running and draining workers
+    pub async fn worker_counts(&self) -> WorkerCounts {
+        let mut workers = self.workers.write().await;
+        workers.draining.retain(|handle| !handle.is_finished());
+        workers.counts()
+    }
+    
+    fn spawn_worker(&self, id: usize) -> Worker {
+        let storage = Arc::clone(&self.storage);
+        let config = self.config.clone();
+        let clock = Arc::clone(&self.clock);
+        let registry = Arc::clone(&self.registry);
+        let (stop, mut stopped) = watch::channel(false);
+        
+        let handle = tokio::spawn(async move {
+            tracing::info!("Worker {} started", id);
+            
+            while !*stopped.borrow() {
+                match storage.fetch_next_job().await {
+                    Ok(Some((mut metadata, payload))) => {
+                        metadata.status = JobStatus::Running;
+                        metadata.started_at = Some(clock.now());
+                        
+                        if let Err(e) = storage.save_job(&metadata, payload.clone()).await {
+                            tracing::error!(job_id = %metadata.id, error = %e, "Failed to update job status");
+                            continue;
                         }
+                        
+                        run_job(&storage, &registry, &config, &clock, metadata, payload).await;
+                    }
+                    Ok(None) => {
+                        // No jobs available, sleep briefly
+                        idle(&mut stopped, Duration::from_secs(1)).await;
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "Error fetching job");
+                        idle(&mut stopped, Duration::from_secs(5)).await;
                     }
                 }
-            });
+            }
             
-            workers.push(handle);
-        }
+            tracing::info!("Worker {} stopped", id);
+        });
         
-        tracing::info!("Started {} workers", self.config.worker_count);
+        Worker { handle, stop }
     }
     
-    /// Stop all workers
+    /// Stop all workers, aborting running jobs
     pub async fn stop_workers(&self) {
         let mut workers = self.workers.write().await;
         
-        for handle in workers.drain(..) {
+        for worker in workers.active.drain(..) {
+            worker.handle.abort();
+        }
+        for handle in workers.draining.drain(..) {
             handle.abort();
         }
         
@@ -220,6 +598,252 @@ impl<S: JobStorage> JobQueue<S> {
     }
 }
 
+/// Sleep for `duration`, or until the worker is told to stop
+async fn idle(stopped: &mut watch::Receiver<bool>, duration: Duration) {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        Ok(()) = stopped.changed() => {}
+    }
+}
+
+/// Most missed occurrences [`CatchUp::All`] enqueues at once
+const MAX_CATCH_UP: usize = 100;
+
+/// A registered recurring job and when it last ran
+struct Recurring {
+    job: RecurringJob,
+    last_run: chrono::DateTime<chrono::Utc>,
+    last_job: Option<Uuid>,
+}
+
+/// A running worker and its stop signal
+struct Worker {
+    handle: JoinHandle<()>,
+    stop: watch::Sender<bool>,
+}
+
+#[derive(Default)]
+struct Workers {
+    active: Vec<Worker>,
+    /// Removed workers finishing their current job
+    draining: Vec<JoinHandle<()>>,
+    next_id: usize,
+}
+
+impl Workers {
+    fn counts(&self) -> WorkerCounts {
+        WorkerCounts {
+            active: self.active.len(),
+            draining: self.draining.len(),
+        }
+    }
+}
+
+/// Number of job workers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerCounts {
+    /// Workers taking new jobs
+    pub active: usize,
+    /// Removed workers still finishing a job
+    pub draining: usize,
+}
+
+/// A job with its payload
+#[derive(Debug, Clone, Serialize)]
+pub struct JobDetails {
+    #[serde(flatten)]
+    pub metadata: JobMetadata,
+    pub payload: serde_json::Value,
+}
+
+/// What a job's submitter may see of it: no payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobStatusReport {
+    pub id: Uuid,
+    pub job_type: String,
+    pub status: JobStatus,
+    pub progress: Option<JobProgress>,
+    /// Last error, kept while the job is retried
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<JobMetadata> for JobStatusReport {
+    fn from(metadata: JobMetadata) -> Self {
+        Self {
+            id: metadata.id,
+            job_type: metadata.job_type,
+            status: metadata.status,
+            progress: metadata.progress,
+            error: metadata.error,
+            created_at: metadata.created_at,
+            started_at: metadata.started_at,
+            completed_at: metadata.completed_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ScaleRequest {
+    workers: usize,
+}
+
+async fn get_workers<S: JobStorage>(State(queue): State<Arc<JobQueue<S>>>) -> Json<WorkerCounts> {
+    Json(queue.worker_counts().await)
+}
+
+async fn scale_workers<S: JobStorage>(
+    State(queue): State<Arc<JobQueue<S>>>,
+    Json(request): Json<ScaleRequest>,
+) -> Result<Json<WorkerCounts>, ApiError> {
+    if request.workers > MAX_WORKERS {
+        return Err(ApiError::BadRequest(format!("At most {} workers can run", MAX_WORKERS)));
+    }
+    Ok(Json(queue.scale_workers(request.workers).await))
+}
+
+#[derive(Debug, Deserialize)]
+struct DeadQuery {
+    limit: Option<usize>,
+}
+
+async fn list_dead<S: JobStorage>(
+    State(queue): State<Arc<JobQueue<S>>>,
+    Query(query): Query<DeadQuery>,
+) -> Result<Json<Vec<JobMetadata>>, ApiError> {
+    Ok(Json(queue.dead_jobs(query.limit.unwrap_or(100).min(1000)).await?))
+}
+
+async fn get_job<S: JobStorage>(
+    State(queue): State<Arc<JobQueue<S>>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<JobDetails>, ApiError> {
+    Ok(Json(queue.inspect(id).await?))
+}
+
+async fn retry_dead<S: JobStorage>(
+    State(queue): State<Arc<JobQueue<S>>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    queue.retry_dead(id).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn get_job_status<S: JobStorage>(
+    State(queue): State<Arc<JobQueue<S>>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<JobStatusReport>, ApiError> {
+    Ok(Json(queue.storage.get_job(id).await?.into()))
+}
+
+/// Most workers the scaling endpoint starts
+const MAX_WORKERS: usize = 1024;
+
+/// Create job worker admin routes
+///
+/// Mounts:
+/// - GET /jobs/workers - Active and draining worker counts
+/// - PUT /jobs/workers - Scale to `{"workers": n}`
+/// - GET /jobs/dead?limit=n - Jobs that ran out of retries
+/// - GET /jobs/:id - A job's status, progress, last error and payload
+/// - POST /jobs/dead/:id/retry - Re-enqueue a dead job
+///
+/// These change how the app runs, so mount them behind admin authentication.
+pub fn job_admin_routes<S: JobStorage>(queue: Arc<JobQueue<S>>) -> Router {
+    Router::new()
+        .route("/jobs/workers", get(get_workers::<S>).put(scale_workers::<S>))
+        .route("/jobs/dead", get(list_dead::<S>))
+        .route("/jobs/dead/:id/retry", post(retry_dead::<S>))
+        .route("/jobs/:id", get(get_job::<S>))
+        .with_state(queue)
+}
+
+/// Create the job status route for the users who submitted jobs
+///
+/// Mounts:
+/// - GET /jobs/:id/status - A job's status, progress and last error, without its payload
+///
+/// Job ids are random, so the id handed back on submission acts as the key;
+/// mount it behind user authentication when ids are shared more widely.
+pub fn job_status_routes<S: JobStorage>(queue: Arc<JobQueue<S>>) -> Router {
+    Router::new()
+        .route("/jobs/:id/status", get(get_job_status::<S>))
+        .with_state(queue)
+}
+
+/// Backoff before retry number `retry`: `base_seconds` doubled per earlier
+/// retry, plus up to half again so jobs that failed together spread out
+fn retry_delay(base_seconds: u64, retry: u32) -> Duration {
+    let backoff = base_seconds
+        .saturating_mul(2u64.saturating_pow(retry.saturating_sub(1)))
+        .saturating_mul(1000);
+    let jitter = rand::Rng::gen_range(&mut rand::thread_rng(), 0..=backoff / 2);
+    Duration::from_millis(backoff.saturating_add(jitter))
+}
+
+/// Run one fetched job and record how it went
+async fn run_job<S: JobStorage>(
+    storage: &Arc<S>,
+    registry: &JobRegistry,
+    config: &JobConfig,
+    clock: &SharedClock,
+    mut metadata: JobMetadata,
+    payload: serde_json::Value,
+) {
+    tracing::info!(
+        job_id = %metadata.id,
+        job_type = %metadata.job_type,
+        "Processing job"
+    );
+    
+    let ctx = JobContext::new(metadata.id, metadata.job_type.clone())
+        .with_retry_count(metadata.retry_count)
+        .with_max_retries(metadata.max_retries)
+        .with_progress_storage(storage.clone(), clock.clone());
+    let timeout = Duration::from_secs(config.job_timeout_seconds);
+    let result = registry
+        .execute_with_timeout(&metadata.job_type, payload.clone(), ctx.clone(), timeout)
+        .await;
+    metadata.progress = ctx.progress().or(metadata.progress);
+    
+    match result {
+        Ok(()) => {
+            metadata.status = JobStatus::Completed;
+            metadata.completed_at = Some(clock.now());
+            metadata.error = None;
+            tracing::info!(job_id = %metadata.id, "Job completed");
+        }
+        Err(e) => {
+            metadata.error = Some(e.to_string());
+            if metadata.retry_count < metadata.max_retries {
+                metadata.retry_count += 1;
+                let delay = retry_delay(config.retry_delay_seconds, metadata.retry_count);
+                metadata.status = JobStatus::Pending;
+                metadata.scheduled_at = chrono::Duration::from_std(delay)
+                    .ok()
+                    .and_then(|delay| clock.now().checked_add_signed(delay))
+                    .or(Some(chrono::DateTime::<chrono::Utc>::MAX_UTC));
+                tracing::warn!(
+                    job_id = %metadata.id,
+                    error = %e,
+                    retry = metadata.retry_count,
+                    "Job failed, retrying"
+                );
+            } else {
+                metadata.status = JobStatus::Dead;
+                metadata.completed_at = Some(clock.now());
+                tracing::error!(job_id = %metadata.id, error = %e, "Job failed, out of retries");
+            }
+        }
+    }
+    
+    if let Err(e) = storage.save_job(&metadata, payload).await {
+        tracing::error!(job_id = %metadata.id, error = %e, "Failed to record job result");
+    }
+}
+
 /// Queue statistics
 #[derive(Debug, Clone, Serialize)]
 pub struct QueueStats {
@@ -233,7 +857,8 @@ pub struct QueueStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::jobs::InMemoryJobStorage;
+    use crate::jobs::{InMemoryJobStorage, Spread};
+    use async_trait::async_trait;
     
     #[tokio::test]
     async fn test_enqueue_job() {
@@ -248,4 +873,328 @@ mod tests {
         let status = queue.get_status(job_id).await.unwrap();
         assert_eq!(status, JobStatus::Pending);
     }
-}
+    
+    #[tokio::test]
+    async fn test_pending_limits() {
+        let storage = InMemoryJobStorage::new();
+        let queue = JobQueue::new(storage.clone(), JobConfig::default())
+            .with_max_pending(3)
+            .with_max_pending_for("email", 1);
+        
+        queue.enqueue(serde_json::json!({}), "email").await.unwrap();
+        let err = queue.enqueue(serde_json::json!({}), "email").await.unwrap_err();
+        assert!(matches!(err, ApiError::ServiceUnavailable(_)));
+        
+        queue.enqueue(serde_json::json!({}), "report").await.unwrap();
+        queue.enqueue(serde_json::json!({}), "report").await.unwrap();
+        assert!(queue.enqueue(serde_json::json!({}), "report").await.is_err());
+        
+        // A waiting producer gets in once a worker takes a job
+        let queue = queue.with_overflow(OverflowPolicy::Wait(Duration::from_secs(5)));
+        let worker = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            storage.fetch_next_job().await.unwrap();
+        });
+        queue.enqueue(serde_json::json!({}), "report").await.unwrap();
+        worker.await.unwrap();
+    }
+    
+    #[derive(Serialize, Deserialize)]
+    struct Flaky {
+        fail_times: u32,
+        sleep_seconds: u64,
+    }
+    
+    #[async_trait]
+    impl crate::jobs::Job for Flaky {
+        async fn execute(&self, ctx: JobContext) -> crate::jobs::JobResult {
+            tokio::time::sleep(Duration::from_secs(self.sleep_seconds)).await;
+            ctx.set_progress(50, format!("attempt {}", ctx.retry_count)).await?;
+            if ctx.retry_count < self.fail_times {
+                return Err(format!("attempt {} failed", ctx.retry_count).into());
+            }
+            Ok(())
+        }
+        
+        fn job_type(&self) -> &str {
+            "flaky"
+        }
+        
+        fn timeout_seconds(&self) -> Option<u64> {
+            (self.sleep_seconds > 0).then_some(1)
+        }
+    }
+    
+    async fn settled(queue: &JobQueue<InMemoryJobStorage>, storage: &InMemoryJobStorage, job_id: Uuid) -> JobMetadata {
+        for _ in 0..200 {
+            let status = queue.get_status(job_id).await.unwrap();
+            if matches!(status, JobStatus::Completed | JobStatus::Dead) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        storage.get_job(job_id).await.unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_workers_run_registered_handlers() {
+        let storage = InMemoryJobStorage::new();
+        let config = JobConfig {
+            max_retries: 2,
+            retry_delay_seconds: 0,
+            worker_count: 2,
+            job_timeout_seconds: 60,
+        };
+        let queue = Arc::new(JobQueue::new(storage.clone(), config));
+        queue.registry().register::<Flaky>("flaky").await;
+        queue.start_workers().await;
+        
+        let retried = queue.enqueue(Flaky { fail_times: 2, sleep_seconds: 0 }, "flaky").await.unwrap();
+        let dead = queue.enqueue(Flaky { fail_times: 5, sleep_seconds: 0 }, "flaky").await.unwrap();
+        let timed_out = queue.enqueue(Flaky { fail_times: 0, sleep_seconds: 30 }, "flaky").await.unwrap();
+        let unknown = queue.enqueue(serde_json::json!({"millis": 0}), "unknown").await.unwrap();
+        
+        let metadata = settled(&queue, &storage, retried).await;
+        assert_eq!(metadata.status, JobStatus::Completed);
+        assert_eq!(metadata.retry_count, 2);
+        assert!(metadata.error.is_none());
+        let progress = metadata.progress.unwrap();
+        assert_eq!((progress.percent, progress.message.as_deref()), (50, Some("attempt 2")));
+        
+        let metadata = settled(&queue, &storage, dead).await;
+        assert_eq!(metadata.status, JobStatus::Dead);
+        assert_eq!(metadata.error.as_deref(), Some("attempt 2 failed"));
+        
+        let metadata = settled(&queue, &storage, unknown).await;
+        assert_eq!(metadata.status, JobStatus::Dead);
+        assert!(metadata.error.unwrap().contains("No handler registered"));
+        
+        let metadata = settled(&queue, &storage, timed_out).await;
+        assert_eq!(metadata.status, JobStatus::Dead);
+        assert_eq!(metadata.error.as_deref(), Some("Job timed out after 1s"));
+        
+        // Dead letters can be inspected and re-enqueued once fixed
+        assert_eq!(queue.dead_jobs(10).await.unwrap().len(), 3);
+        assert_eq!(queue.inspect(dead).await.unwrap().payload["fail_times"], 5);
+        
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+        let router = job_admin_routes(queue.clone());
+        let retry = |id: Uuid| Request::post(format!("/jobs/dead/{}/retry", id)).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(retry(retried)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        
+        queue.registry().register::<Sleepy>("unknown").await;
+        let response = router.clone().oneshot(retry(unknown)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let metadata = settled(&queue, &storage, unknown).await;
+        assert_eq!(metadata.status, JobStatus::Completed);
+        assert_eq!(metadata.retry_count, 0);
+        
+        let response = router.oneshot(Request::get("/jobs/dead").body(Body::empty()).unwrap()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Vec<JobMetadata>>(&body).unwrap().len(), 2);
+        
+        // Submitters see status, progress and error but not the payload
+        let status = Request::get(format!("/jobs/{}/status", dead)).body(Body::empty()).unwrap();
+        let response = job_status_routes(queue.clone()).oneshot(status).await.unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["status"], "Dead");
+        assert_eq!(body["progress"]["percent"], 50);
+        assert_eq!(body["error"], "attempt 2 failed");
+        assert!(body.get("payload").is_none());
+        
+        queue.stop_workers().await;
+    }
+    
+    #[test]
+    fn test_retry_delay_backs_off_with_jitter() {
+        for _ in 0..20 {
+            let first = retry_delay(10, 1);
+            assert!(first >= Duration::from_secs(10) && first <= Duration::from_secs(15));
+            let third = retry_delay(10, 3);
+            assert!(third >= Duration::from_secs(40) && third <= Duration::from_secs(60));
+        }
+        assert_eq!(retry_delay(0, 4), Duration::ZERO);
+        retry_delay(u64::MAX, 100);
+    }
+    
+    #[derive(Serialize, Deserialize)]
+    struct Sleepy {
+        millis: u64,
+    }
+    
+    #[async_trait]
+    impl crate::jobs::Job for Sleepy {
+        async fn execute(&self, _ctx: JobContext) -> crate::jobs::JobResult {
+            tokio::time::sleep(Duration::from_millis(self.millis)).await;
+            Ok(())
+        }
+        
+        fn job_type(&self) -> &str {
+            "sleepy"
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_scaling_drains_removed_workers() {
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+        
+        let storage = InMemoryJobStorage::new();
+        let config = JobConfig {
+            worker_count: 2,
+            ..JobConfig::default()
+        };
+        let queue = Arc::new(JobQueue::new(storage.clone(), config));
+        queue.registry().register::<Sleepy>("sleepy").await;
+        queue.start_workers().await;
+        assert_eq!(queue.worker_counts().await, WorkerCounts { active: 2, draining: 0 });
+        
+        let running = queue.enqueue(Sleepy { millis: 500 }, "sleepy").await.unwrap();
+        while queue.get_status(running).await.unwrap() != JobStatus::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        
+        // The removed worker finishes its job instead of being aborted
+        let counts = queue.scale_workers(0).await;
+        assert_eq!(counts.active, 0);
+        assert!(counts.draining >= 1);
+        assert_eq!(settled(&queue, &storage, running).await.status, JobStatus::Completed);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.worker_counts().await, WorkerCounts { active: 0, draining: 0 });
+        
+        let waiting = queue.enqueue(Sleepy { millis: 0 }, "sleepy").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(queue.get_status(waiting).await.unwrap(), JobStatus::Pending);
+        
+        let router = job_admin_routes(queue.clone());
+        let scale = |workers: usize| {
+            Request::put("/jobs/workers")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::json!({ "workers": workers }).to_string()))
+                .unwrap()
+        };
+        let response = router.clone().oneshot(scale(1)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(settled(&queue, &storage, waiting).await.status, JobStatus::Completed);
+        
+        let response = router.clone().oneshot(Request::get("/jobs/workers").body(Body::empty()).unwrap()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let counts: WorkerCounts = serde_json::from_slice(&body).unwrap();
+        assert_eq!(counts.active, 1);
+        
+        let response = router.oneshot(scale(MAX_WORKERS + 1)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        
+        queue.stop_workers().await;
+    }
+    
+    #[tokio::test]
+    async fn test_recurring_jobs_catch_up_and_overlap() {
+        let clock = crate::clock::ManualClock::frozen();
+        let hour = Duration::from_secs(3600);
+        let scenario = |recurring: RecurringJob| {
+            let storage = InMemoryJobStorage::new().with_clock(clock.shared());
+            let queue = Arc::new(JobQueue::new(storage.clone(), JobConfig::default()).with_clock(clock.shared()));
+            queue.start_scheduler();
+            async move {
+                queue.add_recurring(recurring).await;
+                (queue, storage)
+            }
+        };
+        let hourly = |name: &str| RecurringJob::new(name, Schedule::every(3600), Sleepy { millis: 0 }).unwrap();
+        let zero = Schedule::Interval { seconds: 0, start_at: None };
+        assert!(matches!(RecurringJob::new("zero", zero, Sleepy { millis: 0 }), Err(ApiError::BadRequest(_))));
+        let advance = |by: Duration| {
+            clock.advance(by);
+            tokio::time::sleep(Duration::from_millis(50))
+        };
+        
+        // Defaults: one run for missed occurrences, none while the last is pending
+        let (defaults, defaults_storage) = scenario(hourly("defaults")).await;
+        let (all, all_storage) = scenario(hourly("all").with_catch_up(CatchUp::All).with_overlap(Overlap::Queue)).await;
+        let (_replace, replace_storage) = scenario(hourly("replace").with_catch_up(CatchUp::Skip).with_overlap(Overlap::Replace)).await;
+        
+        advance(hour * 3 + hour / 2).await;
+        assert_eq!(defaults_storage.count_pending(None).await.unwrap(), 1);
+        assert_eq!(all_storage.count_pending(None).await.unwrap(), 3);
+        assert_eq!(replace_storage.count_pending(Some("sleepy")).await.unwrap(), 0);
+        
+        advance(hour / 2).await;
+        assert_eq!(defaults_storage.count_pending(None).await.unwrap(), 1);
+        assert_eq!(all_storage.count_pending(None).await.unwrap(), 4);
+        assert_eq!(replace_storage.count_pending(None).await.unwrap(), 1);
+        
+        advance(hour).await;
+        assert_eq!(replace_storage.count_pending(None).await.unwrap(), 1);
+        assert_eq!(replace_storage.list_jobs(JobStatus::Cancelled, 10).await.unwrap().len(), 1);
+        
+        defaults.remove_recurring("defaults").await;
+        advance(hour * 2).await;
+        assert_eq!(defaults.stats().await.unwrap().pending, 1);
+        assert_eq!(all.stats().await.unwrap().pending, 7);
+    }
+    
+    #[tokio::test]
+    async fn test_recurring_occurrences_enqueued_once_across_instances() {
+        let clock = crate::clock::ManualClock::frozen();
+        let storage = InMemoryJobStorage::new().with_clock(clock.shared());
+        let start = clock.shared().now();
+        for _ in 0..2 {
+            let queue = Arc::new(JobQueue::new(storage.clone(), JobConfig::default()).with_clock(clock.shared()));
+            queue.start_scheduler();
+            let job = RecurringJob::new("report", Schedule::every_starting_at(3600, start), Sleepy { millis: 0 }).unwrap();
+            queue.add_recurring(job.with_overlap(Overlap::Queue)).await;
+        }
+        
+        clock.advance(Duration::from_secs(3600 * 2 + 60));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(storage.count_pending(None).await.unwrap(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_waiting_recurring_job_leaves_registry_unlocked() {
+        let clock = crate::clock::ManualClock::frozen();
+        let storage = InMemoryJobStorage::new().with_clock(clock.shared());
+        let queue = Arc::new(
+            JobQueue::new(storage, JobConfig::default())
+                .with_clock(clock.shared())
+                .with_max_pending(1)
+                .with_overflow(OverflowPolicy::Wait(Duration::from_secs(3600))),
+        );
+        queue.enqueue(Sleepy { millis: 0 }, "sleepy").await.unwrap();
+        let start = clock.shared().now();
+        let job = RecurringJob::new("report", Schedule::every_starting_at(3600, start), Sleepy { millis: 0 }).unwrap();
+        queue.add_recurring(job).await;
+        queue.start_scheduler();
+        
+        // The due occurrence waits for room on the frozen clock
+        clock.advance(Duration::from_secs(3600));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let other = RecurringJob::new("other", Schedule::every(60), Sleepy { millis: 0 }).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), queue.add_recurring(other)).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), queue.remove_recurring("report")).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_recurring_jobs_are_spread() {
+        let clock = crate::clock::ManualClock::frozen();
+        let storage = InMemoryJobStorage::new().with_clock(clock.shared());
+        let queue = Arc::new(JobQueue::new(storage.clone(), JobConfig::default()).with_clock(clock.shared()));
+        queue.start_scheduler();
+        let start = clock.shared().now();
+        let spread = Spread::new().with_stagger(600, "instance-a");
+        let offset = spread.stagger_offset();
+        assert!(offset > 0);
+        let job = RecurringJob::new("report", Schedule::every_starting_at(3600, start), Sleepy { millis: 0 }).unwrap();
+        queue.add_recurring(job.clone().with_spread(spread)).await;
+        
+        clock.advance(Duration::from_secs(3600));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let occurrence = start + chrono::Duration::hours(1);
+        let metadata = storage.get_job(job.occurrence_id(occurrence)).await.unwrap();
+        assert_eq!(metadata.scheduled_at, Some(occurrence + chrono::Duration::seconds(offset)));
+    }
+}
\ No newline at end of file
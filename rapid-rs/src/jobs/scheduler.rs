@@ -1,9 +1,13 @@
 //! Job scheduling with cron support
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
+use super::{Job, JobPriority};
+use crate::clock::Clock;
+use crate::error::ApiError;
+
 /// Cron schedule parser and evaluator
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CronSchedule {
@@ -19,20 +23,145 @@ impl CronSchedule {
     }
     
     fn validate(expr: &str) -> Result<(), ScheduleError> {
-        let parts: Vec<&str> = expr.split_whitespace().collect();
-        if parts.len() != 5 && parts.len() != 6 {
-            return Err(ScheduleError::InvalidFormat(
-                "Cron expression must have 5 or 6 fields".to_string(),
-            ));
+        for (field, raw) in Self::fields(expr)? {
+            parse_field(field, raw)?;
         }
         Ok(())
     }
     
+    /// Split an expression into its fields, paired with the field kind
+    fn fields(expr: &str) -> Result<Vec<(CronField, &str)>, ScheduleError> {
+        let parts: Vec<&str> = expr.split_whitespace().collect();
+        let kinds: &[CronField] = match parts.len() {
+            5 => &CronField::STANDARD,
+            6 => &CronField::WITH_SECONDS,
+            n => {
+                return Err(ScheduleError::InvalidFormat(format!(
+                    "Cron expression must have 5 or 6 fields, got {}",
+                    n
+                )))
+            }
+        };
+        Ok(kinds.iter().copied().zip(parts).collect())
+    }
+    
+    /// The original cron expression
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+    
+    /// Human-readable summary, e.g. "At 09:30, on Monday"
+    pub fn describe(&self) -> String {
+        let fields = match Self::fields(&self.expression) {
+            Ok(fields) => fields,
+            Err(_) => return self.expression.clone(),
+        };
+        let raw = |kind: CronField| {
+            fields
+                .iter()
+                .find(|(f, _)| *f == kind)
+                .map(|(_, raw)| *raw)
+                .unwrap_or("*")
+        };
+        
+        let second = raw(CronField::Second);
+        let minute = raw(CronField::Minute);
+        let hour = raw(CronField::Hour);
+        
+        let mut parts = Vec::new();
+        
+        let is_single = |s: &str| s.parse::<u32>().is_ok();
+        if is_single(minute) && is_single(hour) {
+            let mut time = format!("At {:0>2}:{:0>2}", hour, minute);
+            if second != "*" && second != "0" {
+                time.push_str(&format!(":{:0>2}", second));
+            }
+            parts.push(time);
+        } else {
+            if second != "*" && second != "0" {
+                parts.push(describe_field(CronField::Second, second));
+            }
+            parts.push(describe_field(CronField::Minute, minute));
+            if hour != "*" {
+                parts.push(describe_field(CronField::Hour, hour));
+            }
+        }
+        
+        let day_of_month = raw(CronField::DayOfMonth);
+        let day_of_week = raw(CronField::DayOfWeek);
+        if day_of_month == "*" && day_of_week == "*" {
+            parts.push("every day".to_string());
+        } else {
+            if day_of_month != "*" {
+                parts.push(describe_field(CronField::DayOfMonth, day_of_month));
+            }
+            if day_of_week != "*" {
+                parts.push(describe_field(CronField::DayOfWeek, day_of_week));
+            }
+        }
+        
+        let month = raw(CronField::Month);
+        if month != "*" {
+            parts.push(describe_field(CronField::Month, month));
+        }
+        
+        let description = parts.join(", ");
+        let mut chars = description.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => description,
+        }
+    }
+    
     /// Get the next run time after the given time
+    ///
+    /// `None` when nothing matches within five years, e.g. for February 31st.
     pub fn next_run(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
-        // This is a simplified implementation
-        // In production, use the `cron` crate for full cron parsing
-        Some(after + chrono::Duration::hours(1))
+        let fields = Self::fields(&self.expression).ok()?;
+        let values = |kind: CronField| -> Option<Vec<u32>> {
+            match fields.iter().find(|(f, _)| *f == kind) {
+                Some((_, raw)) => parse_field(kind, raw).ok(),
+                // Five-field expressions run at the top of the minute
+                None => Some(vec![0]),
+            }
+        };
+        let unrestricted = |kind: CronField| fields.iter().any(|(f, raw)| *f == kind && *raw == "*");
+        let (seconds, minutes, hours) = (values(CronField::Second)?, values(CronField::Minute)?, values(CronField::Hour)?);
+        let (days, months, weekdays) = (values(CronField::DayOfMonth)?, values(CronField::Month)?, values(CronField::DayOfWeek)?);
+        let (any_day, any_weekday) = (unrestricted(CronField::DayOfMonth), unrestricted(CronField::DayOfWeek));
+        
+        // Like cron, a restricted day-of-month and day-of-week match either
+        let day_matches = |at: &DateTime<Utc>| {
+            let day = days.contains(&at.day());
+            let weekday = weekdays.contains(&at.weekday().num_days_from_sunday());
+            match (any_day, any_weekday) {
+                (true, true) => true,
+                (true, false) => weekday,
+                (false, true) => day,
+                (false, false) => day || weekday,
+            }
+        };
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|at| Utc.from_utc_datetime(&at));
+        
+        let limit = after + chrono::Duration::days(5 * 366);
+        let mut at = after.with_nanosecond(0)? + chrono::Duration::seconds(1);
+        while at <= limit {
+            if !months.contains(&at.month()) {
+                let (year, month) = if at.month() == 12 { (at.year() + 1, 1) } else { (at.year(), at.month() + 1) };
+                at = midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !day_matches(&at) {
+                at = midnight(at.date_naive().succ_opt()?)?;
+            } else if !hours.contains(&at.hour()) {
+                at = at.with_minute(0)?.with_second(0)? + chrono::Duration::hours(1);
+            } else if !minutes.contains(&at.minute()) {
+                at = at.with_second(0)? + chrono::Duration::minutes(1);
+            } else if !seconds.contains(&at.second()) {
+                at += chrono::Duration::seconds(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
     }
 }
 
@@ -44,6 +173,233 @@ impl FromStr for CronSchedule {
     }
 }
 
+/// Cron expression fields, in expression order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CronField {
+    Second,
+    Minute,
+    Hour,
+    DayOfMonth,
+    Month,
+    DayOfWeek,
+}
+
+impl CronField {
+    const STANDARD: [CronField; 5] = [
+        CronField::Minute,
+        CronField::Hour,
+        CronField::DayOfMonth,
+        CronField::Month,
+        CronField::DayOfWeek,
+    ];
+    
+    const WITH_SECONDS: [CronField; 6] = [
+        CronField::Second,
+        CronField::Minute,
+        CronField::Hour,
+        CronField::DayOfMonth,
+        CronField::Month,
+        CronField::DayOfWeek,
+    ];
+    
+    /// Field name as used in error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Second => "second",
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::DayOfMonth => "day-of-month",
+            Self::Month => "month",
+            Self::DayOfWeek => "day-of-week",
+        }
+    }
+    
+    /// Allowed numeric range (inclusive); day-of-week accepts 7 as Sunday
+    pub fn range(&self) -> (u32, u32) {
+        match self {
+            Self::Second | Self::Minute => (0, 59),
+            Self::Hour => (0, 23),
+            Self::DayOfMonth => (1, 31),
+            Self::Month => (1, 12),
+            Self::DayOfWeek => (0, 7),
+        }
+    }
+    
+    fn names(&self) -> &'static [&'static str] {
+        match self {
+            Self::Month => &MONTH_NAMES,
+            Self::DayOfWeek => &DAY_NAMES,
+            _ => &[],
+        }
+    }
+    
+    fn unit(&self, plural: bool) -> &'static str {
+        match (self, plural) {
+            (Self::Second, false) => "second",
+            (Self::Second, true) => "seconds",
+            (Self::Minute, false) => "minute",
+            (Self::Minute, true) => "minutes",
+            (Self::Hour, false) => "hour",
+            (Self::Hour, true) => "hours",
+            (Self::DayOfMonth, false) => "day",
+            (Self::DayOfMonth, true) => "days",
+            (Self::Month, false) => "month",
+            (Self::Month, true) => "months",
+            (Self::DayOfWeek, false) => "weekday",
+            (Self::DayOfWeek, true) => "weekdays",
+        }
+    }
+}
+
+impl std::fmt::Display for CronField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+const MONTH_LABELS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December",
+];
+
+const DAY_LABELS: [&str; 7] = [
+    "Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday",
+];
+
+/// Parse a single value, accepting month/day names where applicable
+fn parse_value(field: CronField, raw: &str) -> Result<u32, ScheduleError> {
+    let (min, max) = field.range();
+    
+    if let Some(index) = field
+        .names()
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(raw))
+    {
+        // Month names are 1-based, day names are 0-based
+        return Ok(index as u32 + min);
+    }
+    
+    let value: u32 = raw.parse().map_err(|_| ScheduleError::InvalidField {
+        field,
+        value: raw.to_string(),
+        reason: "expected a number".to_string(),
+    })?;
+    
+    if value < min || value > max {
+        return Err(ScheduleError::OutOfRange {
+            field,
+            value: raw.to_string(),
+            min,
+            max,
+        });
+    }
+    
+    Ok(value)
+}
+
+/// Parse a cron field (`*`, `5`, `1-5`, `*/15`, `0-30/5`, `1,15`) into its values
+fn parse_field(field: CronField, raw: &str) -> Result<Vec<u32>, ScheduleError> {
+    let (min, max) = field.range();
+    let mut values = Vec::new();
+    
+    for item in raw.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().ok().filter(|s| *s > 0).ok_or_else(|| {
+                    ScheduleError::InvalidField {
+                        field,
+                        value: item.to_string(),
+                        reason: "step must be a positive number".to_string(),
+                    }
+                })?;
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let (start, end) = (parse_value(field, start)?, parse_value(field, end)?);
+            if start > end {
+                return Err(ScheduleError::InvalidField {
+                    field,
+                    value: item.to_string(),
+                    reason: format!("range start {} is greater than end {}", start, end),
+                });
+            }
+            (start, end)
+        } else {
+            let start = parse_value(field, range)?;
+            // "5/10" means "starting at 5, every 10"
+            (start, if step > 1 { max } else { start })
+        };
+        
+        values.extend((start..=end).step_by(step as usize));
+    }
+    
+    if field == CronField::DayOfWeek {
+        // 7 is an alias for Sunday
+        for value in values.iter_mut() {
+            if *value == 7 {
+                *value = 0;
+            }
+        }
+    }
+    
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+fn value_label(field: CronField, raw: &str) -> String {
+    let value = match parse_value(field, raw) {
+        Ok(value) => value,
+        Err(_) => return raw.to_string(),
+    };
+    match field {
+        CronField::Month => MONTH_LABELS[(value - 1) as usize].to_string(),
+        CronField::DayOfWeek => DAY_LABELS[(value % 7) as usize].to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Describe one (already validated) field
+fn describe_field(field: CronField, raw: &str) -> String {
+    if raw == "*" {
+        return format!("every {}", field.unit(false));
+    }
+    
+    if let Some(step) = raw.strip_prefix("*/") {
+        return format!("every {} {}", step, field.unit(true));
+    }
+    
+    let items: Vec<String> = raw
+        .split(',')
+        .map(|item| match item.split_once('-') {
+            Some((start, end)) if !item.contains('/') => {
+                format!("{} through {}", value_label(field, start), value_label(field, end))
+            }
+            _ => value_label(field, item),
+        })
+        .collect();
+    let list = items.join(", ");
+    
+    match field {
+        CronField::Second | CronField::Minute => format!("at {} {}", field.unit(true), list),
+        CronField::Hour => format!("past hour {}", list),
+        CronField::DayOfMonth => format!("on day {} of the month", list),
+        CronField::Month => format!("in {}", list),
+        CronField::DayOfWeek => format!("on {}", list),
+    }
+}
+
 /// Schedule types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Schedule {
@@ -67,7 +423,12 @@ impl Schedule {
     }
     
     /// Create an interval schedule
+    ///
+    /// # Panics
+    ///
+    /// Panics if `seconds` is zero.
     pub fn every(seconds: u64) -> Self {
+        assert!(seconds > 0, "interval must be at least one second");
         Self::Interval {
             seconds,
             start_at: None,
@@ -75,7 +436,12 @@ impl Schedule {
     }
     
     /// Create an interval schedule with a start time
+    ///
+    /// # Panics
+    ///
+    /// Panics if `seconds` is zero.
     pub fn every_starting_at(seconds: u64, start_at: DateTime<Utc>) -> Self {
+        assert!(seconds > 0, "interval must be at least one second");
         Self::Interval {
             seconds,
             start_at: Some(start_at),
@@ -87,7 +453,27 @@ impl Schedule {
         Ok(Self::Cron(CronSchedule::new(expression)?))
     }
     
+    /// Human-readable summary for admin UIs
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Once(at) => format!("Once at {}", at.format("%Y-%m-%d %H:%M:%S UTC")),
+            Self::Interval { seconds, start_at } => {
+                let mut description = format!("Every {}", describe_interval(*seconds));
+                if let Some(start) = start_at {
+                    description.push_str(&format!(
+                        ", starting at {}",
+                        start.format("%Y-%m-%d %H:%M:%S UTC")
+                    ));
+                }
+                description
+            }
+            Self::Cron(cron) => cron.describe(),
+        }
+    }
+    
     /// Get the next run time after the given time
+    ///
+    /// A zero interval (only possible by deserializing one) never runs.
     pub fn next_run(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
         match self {
             Self::Once(at) => {
@@ -97,6 +483,7 @@ impl Schedule {
                     None
                 }
             }
+            Self::Interval { seconds: 0, .. } => None,
             Self::Interval { seconds, start_at } => {
                 let start = start_at.unwrap_or(after);
                 if after < start {
@@ -110,6 +497,102 @@ impl Schedule {
             Self::Cron(cron) => cron.next_run(after),
         }
     }
+    
+    /// Get the next run time after the given time, with jitter and staggering applied
+    ///
+    /// The spread is applied to the nominal run time, and the result never
+    /// precedes `after`. One-off schedules are not spread.
+    pub fn next_run_with_spread(&self, after: DateTime<Utc>, spread: &Spread) -> Option<DateTime<Utc>> {
+        let next = self.next_run(after)?;
+        if matches!(self, Self::Once(_)) {
+            return Some(next);
+        }
+        Some(spread.apply(next).max(after))
+    }
+    
+    /// Sleep on `clock` until the next run after its current time
+    ///
+    /// Returns the run time, or `None` if the schedule will not run again.
+    pub async fn wait_next(&self, clock: &dyn Clock) -> Option<DateTime<Utc>> {
+        let next = self.next_run(clock.now())?;
+        clock.sleep_until(next).await;
+        Some(next)
+    }
+}
+
+/// Jitter and staggering applied on top of a schedule's run times
+///
+/// Fleets of instances running the same recurring job would otherwise all fire
+/// at exactly the same moment. `jitter` delays every run by a random
+/// `[0, 2 * jitter]` seconds, the same window as `±jitter` but never early,
+/// so no run falls back onto the nominal time; `stagger` adds a fixed
+/// per-instance offset in `[0, stagger)`
+/// derived from a stable instance key, so each instance keeps its own slot.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let spread = Spread::new()
+///     .with_jitter(30)
+///     .with_stagger(300, hostname);
+///
+/// let next = Schedule::every(3600).next_run_with_spread(Utc::now(), &spread);
+/// queue.add_recurring(RecurringJob::new("report", Schedule::every(3600), Report)?.with_spread(spread)).await;
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spread {
+    /// Half the width of the random delay window in seconds
+    pub jitter_seconds: u64,
+    /// Width of the per-instance offset window in seconds
+    pub stagger_seconds: u64,
+    /// Key used to derive the per-instance offset (e.g. hostname or pod name)
+    pub instance_key: Option<String>,
+}
+
+impl Spread {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Delay every run by a random `[0, 2 * seconds]`
+    pub fn with_jitter(mut self, seconds: u64) -> Self {
+        self.jitter_seconds = seconds;
+        self
+    }
+    
+    /// Offset every run by a stable amount in `[0, seconds)` derived from `instance_key`
+    pub fn with_stagger(mut self, seconds: u64, instance_key: impl Into<String>) -> Self {
+        self.stagger_seconds = seconds;
+        self.instance_key = Some(instance_key.into());
+        self
+    }
+    
+    /// The fixed per-instance offset in seconds
+    pub fn stagger_offset(&self) -> i64 {
+        match (&self.instance_key, self.stagger_seconds) {
+            (Some(key), window) if window > 0 => (fnv1a(key.as_bytes()) % window) as i64,
+            _ => 0,
+        }
+    }
+    
+    /// Apply stagger and a freshly sampled jitter to a run time
+    pub fn apply(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let jitter = if self.jitter_seconds > 0 {
+            let width = self.jitter_seconds.saturating_mul(2).min(i64::MAX as u64) as i64;
+            rand::Rng::gen_range(&mut rand::thread_rng(), 0..=width)
+        } else {
+            0
+        };
+        
+        at + chrono::Duration::seconds(self.stagger_offset() + jitter)
+    }
+}
+
+/// FNV-1a hash; stable across processes and builds, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
 }
 
 /// Schedule error types
@@ -120,6 +603,49 @@ pub enum ScheduleError {
     
     #[error("Invalid field value: {0}")]
     InvalidValue(String),
+    
+    #[error("{field} field '{value}' out of range {min}-{max}")]
+    OutOfRange {
+        field: CronField,
+        value: String,
+        min: u32,
+        max: u32,
+    },
+    
+    #[error("{field} field '{value}' is invalid: {reason}")]
+    InvalidField {
+        field: CronField,
+        value: String,
+        reason: String,
+    },
+}
+
+impl ScheduleError {
+    /// The cron field the error refers to, if any
+    pub fn field(&self) -> Option<CronField> {
+        match self {
+            Self::OutOfRange { field, .. } | Self::InvalidField { field, .. } => Some(*field),
+            _ => None,
+        }
+    }
+}
+
+fn describe_interval(seconds: u64) -> String {
+    let (amount, unit) = if seconds.is_multiple_of(86400) {
+        (seconds / 86400, "day")
+    } else if seconds.is_multiple_of(3600) {
+        (seconds / 3600, "hour")
+    } else if seconds.is_multiple_of(60) {
+        (seconds / 60, "minute")
+    } else {
+        (seconds, "second")
+    };
+    
+    if amount == 1 {
+        unit.to_string()
+    } else {
+        format!("{} {}s", amount, unit)
+    }
 }
 
 /// Common schedule helpers
@@ -157,6 +683,111 @@ pub mod schedules {
     }
 }
 
+/// What a recurring job does about occurrences the scheduler missed, e.g.
+/// while the process was suspended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CatchUp {
+    /// Drop occurrences missed by more than a minute
+    Skip,
+    /// Run once for any number of missed occurrences
+    #[default]
+    Once,
+    /// Run every missed occurrence, up to 100
+    All,
+}
+
+/// What a recurring job does when its previous run hasn't finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlap {
+    /// Don't enqueue this occurrence
+    #[default]
+    Skip,
+    /// Enqueue it anyway
+    Queue,
+    /// Cancel the previous run if it hasn't started, and enqueue this one
+    Replace,
+}
+
+/// A job enqueued on a schedule, see [`JobQueue::register_recurring`](super::JobQueue::register_recurring)
+#[derive(Debug, Clone)]
+pub struct RecurringJob {
+    pub(crate) name: String,
+    pub(crate) schedule: Schedule,
+    pub(crate) job_type: String,
+    pub(crate) payload: serde_json::Value,
+    pub(crate) priority: JobPriority,
+    pub(crate) catch_up: CatchUp,
+    pub(crate) overlap: Overlap,
+    pub(crate) spread: Option<Spread>,
+    pub(crate) last_run: Option<DateTime<Utc>>,
+}
+
+impl RecurringJob {
+    /// Enqueue `job` on `schedule`; `name` identifies the recurring entry
+    pub fn new<J: Job>(name: impl Into<String>, schedule: Schedule, job: J) -> Result<Self, ApiError> {
+        let name = name.into();
+        if matches!(schedule, Schedule::Interval { seconds: 0, .. }) {
+            return Err(ApiError::BadRequest(format!("Recurring job '{}' has a zero interval", name)));
+        }
+        let payload = serde_json::to_value(&job)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to serialize job: {}", e)))?;
+        Ok(Self {
+            name,
+            schedule,
+            job_type: job.job_type().to_string(),
+            payload,
+            priority: JobPriority::Normal,
+            catch_up: CatchUp::default(),
+            overlap: Overlap::default(),
+            spread: None,
+            last_run: None,
+        })
+    }
+    
+    pub fn with_priority(mut self, priority: JobPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+    
+    /// Runs once for missed occurrences by default
+    pub fn with_catch_up(mut self, catch_up: CatchUp) -> Self {
+        self.catch_up = catch_up;
+        self
+    }
+    
+    /// Skips occurrences while the previous run is pending or running by default
+    pub fn with_overlap(mut self, overlap: Overlap) -> Self {
+        self.overlap = overlap;
+        self
+    }
+    
+    /// Delay each run by `spread`, so instances don't all start it at once
+    pub fn with_spread(mut self, spread: Spread) -> Self {
+        self.spread = Some(spread);
+        self
+    }
+    
+    /// Treat occurrences after `at` as due, e.g. the last run recorded before
+    /// a restart; defaults to when the job is registered
+    pub fn with_last_run(mut self, at: DateTime<Utc>) -> Self {
+        self.last_run = Some(at);
+        self
+    }
+    
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// Job id for the occurrence at `at`, the same on every instance
+    pub(crate) fn occurrence_id(&self, at: DateTime<Utc>) -> uuid::Uuid {
+        uuid::Uuid::from_u64_pair(fnv1a(self.name.as_bytes()), at.timestamp_millis() as u64)
+    }
+    
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,10 +811,109 @@ mod tests {
         assert!(next - now <= chrono::Duration::seconds(60));
     }
     
+    #[test]
+    #[should_panic(expected = "interval must be at least one second")]
+    fn test_zero_interval_rejected() {
+        Schedule::every(0);
+    }
+    
+    #[test]
+    fn test_deserialized_zero_interval_never_runs() {
+        let schedule: Schedule = serde_json::from_value(serde_json::json!({"Interval": {"seconds": 0, "start_at": null}})).unwrap();
+        assert_eq!(schedule.next_run(Utc::now()), None);
+    }
+    
     #[test]
     fn test_cron_schedule() {
         let schedule = Schedule::cron("0 0 * * *").unwrap();
         let next = schedule.next_run(Utc::now());
         assert!(next.is_some());
     }
+    
+    #[test]
+    fn test_cron_next_run() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let next = |expression: &str, after: &str| CronSchedule::new(expression).unwrap().next_run(at(after));
+        
+        assert_eq!(next("0 3 * * *", "2026-01-01T05:00:00Z"), Some(at("2026-01-02T03:00:00Z")));
+        assert_eq!(next("0 3 * * *", "2026-01-01T02:59:59.5Z"), Some(at("2026-01-01T03:00:00Z")));
+        assert_eq!(next("*/15 * * * *", "2026-01-01T10:07:30Z"), Some(at("2026-01-01T10:15:00Z")));
+        assert_eq!(next("*/15 * * * *", "2026-01-01T10:15:00Z"), Some(at("2026-01-01T10:30:00Z")));
+        assert_eq!(next("30 * * * * *", "2026-01-01T10:00:00Z"), Some(at("2026-01-01T10:00:30Z")));
+        assert_eq!(next("0 9 * * MON", "2026-01-02T12:00:00Z"), Some(at("2026-01-05T09:00:00Z")));
+        assert_eq!(next("0 0 1 * MON", "2026-01-02T00:00:00Z"), Some(at("2026-01-05T00:00:00Z")));
+        assert_eq!(next("0 0 1 JAN *", "2026-01-02T00:00:00Z"), Some(at("2027-01-01T00:00:00Z")));
+        assert_eq!(next("0 0 29 2 *", "2026-03-01T00:00:00Z"), Some(at("2028-02-29T00:00:00Z")));
+        assert_eq!(next("0 0 31 2 *", "2026-01-01T00:00:00Z"), None);
+    }
+    
+    #[test]
+    fn test_cron_validation_errors() {
+        let err = CronSchedule::new("61 * * * *").unwrap_err();
+        assert_eq!(err.to_string(), "minute field '61' out of range 0-59");
+        assert_eq!(err.field(), Some(CronField::Minute));
+        
+        let err = CronSchedule::new("0 0 * 13 *").unwrap_err();
+        assert_eq!(err.field(), Some(CronField::Month));
+        
+        let err = CronSchedule::new("*/0 * * * *").unwrap_err();
+        assert!(matches!(err, ScheduleError::InvalidField { .. }));
+        
+        assert!(CronSchedule::new("0 9-17/2 * JAN-MAR MON,FRI").is_ok());
+        assert!(CronSchedule::new("* * *").is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_wait_next_follows_clock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let start = clock.now();
+        let schedule = Schedule::every_starting_at(60, start);
+        
+        let waiter = {
+            let clock = clock.clone();
+            tokio::spawn(async move { schedule.wait_next(&clock).await })
+        };
+        tokio::task::yield_now().await;
+        
+        clock.advance(std::time::Duration::from_secs(60));
+        let fired = tokio::time::timeout(std::time::Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fired, Some(start + chrono::Duration::seconds(60)));
+    }
+    
+    #[test]
+    fn test_spread() {
+        let now = Utc::now();
+        let schedule = Schedule::every_starting_at(3600, now);
+        let nominal = schedule.next_run(now).unwrap();
+        
+        let jitter = Spread::new().with_jitter(30);
+        for _ in 0..20 {
+            let next = schedule.next_run_with_spread(now, &jitter).unwrap();
+            assert!((0..=60).contains(&(next - nominal).num_seconds()));
+        }
+        
+        let a = Spread::new().with_stagger(300, "instance-a");
+        let b = Spread::new().with_stagger(300, "instance-a");
+        assert_eq!(a.stagger_offset(), b.stagger_offset());
+        assert!((0..300).contains(&a.stagger_offset()));
+        assert_eq!(Spread::new().stagger_offset(), 0);
+    }
+    
+    #[test]
+    fn test_describe() {
+        assert_eq!(Schedule::cron("0 0 * * *").unwrap().describe(), "At 00:00, every day");
+        assert_eq!(
+            Schedule::cron("30 9 * * 1").unwrap().describe(),
+            "At 09:30, on Monday"
+        );
+        assert_eq!(
+            Schedule::cron("*/15 * * * *").unwrap().describe(),
+            "Every 15 minutes, every day"
+        );
+        assert_eq!(Schedule::every(300).describe(), "Every 5 minutes");
+        assert_eq!(Schedule::every(3600).describe(), "Every hour");
+    }
 }
@@ -7,7 +7,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use super::{JobMetadata, JobStatus};
+use super::{JobMetadata, JobProgress, JobStatus};
+use crate::clock::SharedClock;
 use crate::error::ApiError;
 use crate::jobs::queue::QueueStats;
 
@@ -17,15 +18,65 @@ pub trait JobStorage: Send + Sync + 'static {
     /// Save a job with its metadata
     async fn save_job(&self, metadata: &JobMetadata, payload: Value) -> Result<(), ApiError>;
     
+    /// Save a new job unless one with its id exists, returning whether it was saved
+    ///
+    /// The default checks, then saves, so two callers can both insert;
+    /// storage shared between instances should override it atomically.
+    async fn insert_job(&self, metadata: &JobMetadata, payload: Value) -> Result<bool, ApiError> {
+        match self.get_job(metadata.id).await {
+            Ok(_) => Ok(false),
+            Err(ApiError::NotFound(_)) => self.save_job(metadata, payload).await.map(|()| true),
+            Err(e) => Err(e),
+        }
+    }
+    
     /// Get job metadata by ID
     async fn get_job(&self, job_id: Uuid) -> Result<JobMetadata, ApiError>;
     
+    /// Get a job's payload by ID
+    async fn get_payload(&self, job_id: Uuid) -> Result<Value, ApiError> {
+        Err(ApiError::InternalServerError(format!(
+            "Job storage cannot read the payload of job {}",
+            job_id
+        )))
+    }
+    
+    /// Jobs with `status`, most recently finished first
+    async fn list_jobs(&self, status: JobStatus, _limit: usize) -> Result<Vec<JobMetadata>, ApiError> {
+        Err(ApiError::InternalServerError(format!(
+            "Job storage cannot list {:?} jobs",
+            status
+        )))
+    }
+    
+    /// Record the progress a running job reported
+    ///
+    /// The default keeps nothing, so progress only shows once the job's
+    /// result is saved.
+    async fn set_progress(&self, _job_id: Uuid, _progress: &JobProgress) -> Result<(), ApiError> {
+        Ok(())
+    }
+    
     /// Fetch the next pending job
     async fn fetch_next_job(&self) -> Result<Option<(JobMetadata, Value)>, ApiError>;
     
     /// Get queue statistics
     async fn get_stats(&self) -> Result<QueueStats, ApiError>;
     
+    /// Number of pending jobs, of `job_type` only when given
+    ///
+    /// The default counts all jobs from [`get_stats`](Self::get_stats) and
+    /// errors for a single type, so per-type queue limits need an override.
+    async fn count_pending(&self, job_type: Option<&str>) -> Result<usize, ApiError> {
+        match job_type {
+            None => Ok(self.get_stats().await?.pending),
+            Some(job_type) => Err(ApiError::InternalServerError(format!(
+                "Job storage cannot count pending '{}' jobs",
+                job_type
+            ))),
+        }
+    }
+    
     /// Clean up old completed jobs
     async fn cleanup_old_jobs(&self, older_than_days: u32) -> Result<usize, ApiError>;
 }
@@ -34,14 +85,22 @@ pub trait JobStorage: Send + Sync + 'static {
 #[derive(Clone)]
 pub struct InMemoryJobStorage {
     jobs: Arc<RwLock<HashMap<Uuid, (JobMetadata, Value)>>>,
+    clock: SharedClock,
 }
 
 impl InMemoryJobStorage {
     pub fn new() -> Self {
         Self {
             jobs: Arc::new(RwLock::new(HashMap::new())),
+            clock: crate::clock::system(),
         }
     }
+    
+    /// Decide which scheduled jobs are due, and stamp start times, using `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
 }
 
 impl Default for InMemoryJobStorage {
@@ -58,6 +117,17 @@ impl JobStorage for InMemoryJobStorage {
         Ok(())
     }
     
+    async fn insert_job(&self, metadata: &JobMetadata, payload: Value) -> Result<bool, ApiError> {
+        let mut jobs = self.jobs.write().await;
+        match jobs.entry(metadata.id) {
+            std::collections::hash_map::Entry::Occupied(_) => Ok(false),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert((metadata.clone(), payload));
+                Ok(true)
+            }
+        }
+    }
+    
     async fn get_job(&self, job_id: Uuid) -> Result<JobMetadata, ApiError> {
         let jobs = self.jobs.read().await;
         jobs.get(&job_id)
@@ -65,20 +135,49 @@ impl JobStorage for InMemoryJobStorage {
             .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))
     }
     
+    async fn get_payload(&self, job_id: Uuid) -> Result<Value, ApiError> {
+        let jobs = self.jobs.read().await;
+        jobs.get(&job_id)
+            .map(|(_, payload)| payload.clone())
+            .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))
+    }
+    
+    async fn list_jobs(&self, status: JobStatus, limit: usize) -> Result<Vec<JobMetadata>, ApiError> {
+        let jobs = self.jobs.read().await;
+        let mut listed: Vec<_> = jobs
+            .values()
+            .filter(|(metadata, _)| metadata.status == status)
+            .map(|(metadata, _)| metadata.clone())
+            .collect();
+        listed.sort_by(|a, b| b.completed_at.cmp(&a.completed_at).then(b.created_at.cmp(&a.created_at)));
+        listed.truncate(limit);
+        Ok(listed)
+    }
+    
+    async fn set_progress(&self, job_id: Uuid, progress: &JobProgress) -> Result<(), ApiError> {
+        let mut jobs = self.jobs.write().await;
+        let (metadata, _) = jobs
+            .get_mut(&job_id)
+            .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))?;
+        metadata.progress = Some(progress.clone());
+        Ok(())
+    }
+    
     async fn fetch_next_job(&self) -> Result<Option<(JobMetadata, Value)>, ApiError> {
         let mut jobs = self.jobs.write().await;
+        let now = self.clock.now();
         
         // Find highest priority pending job - collect IDs and metadata, not references
         let mut pending_jobs: Vec<_> = jobs
             .iter()
             .filter(|(_, (metadata, _))| {
                 metadata.status == JobStatus::Pending
-                    && metadata.scheduled_at.map_or(true, |t| t <= chrono::Utc::now())
+                    && metadata.scheduled_at.is_none_or(|t| t <= now)
             })
             .map(|(id, (metadata, _))| (*id, metadata.priority))
             .collect();
         
-        pending_jobs.sort_by(|a, b| b.1.cmp(&a.1));
+        pending_jobs.sort_by_key(|&(_, priority)| std::cmp::Reverse(priority));
         
         if let Some((job_id, _)) = pending_jobs.first() {
             // Now we can safely get mutable reference
@@ -87,7 +186,7 @@ impl JobStorage for InMemoryJobStorage {
                 
                 // Update status to running
                 metadata.status = JobStatus::Running;
-                metadata.started_at = Some(chrono::Utc::now());
+                metadata.started_at = Some(now);
                 
                 Ok(result)
             } else {
@@ -123,15 +222,26 @@ impl JobStorage for InMemoryJobStorage {
         Ok(stats)
     }
     
+    async fn count_pending(&self, job_type: Option<&str>) -> Result<usize, ApiError> {
+        let jobs = self.jobs.read().await;
+        Ok(jobs
+            .values()
+            .filter(|(metadata, _)| {
+                metadata.status == JobStatus::Pending
+                    && job_type.is_none_or(|job_type| metadata.job_type == job_type)
+            })
+            .count())
+    }
+    
     async fn cleanup_old_jobs(&self, older_than_days: u32) -> Result<usize, ApiError> {
         let mut jobs = self.jobs.write().await;
-        let cutoff = chrono::Utc::now() - chrono::Duration::days(older_than_days as i64);
+        let cutoff = self.clock.now() - chrono::Duration::days(older_than_days as i64);
         
         let to_remove: Vec<Uuid> = jobs
             .iter()
             .filter(|(_, (metadata, _))| {
                 metadata.status == JobStatus::Completed
-                    && metadata.completed_at.map_or(false, |t| t < cutoff)
+                    && metadata.completed_at.is_some_and(|t| t < cutoff)
             })
             .map(|(id, _)| *id)
             .collect();
@@ -174,9 +284,12 @@ impl PostgresJobStorage {
                 scheduled_at TIMESTAMPTZ,
                 started_at TIMESTAMPTZ,
                 completed_at TIMESTAMPTZ,
-                error TEXT
+                error TEXT,
+                progress JSONB
             );
             
+            ALTER TABLE jobs ADD COLUMN IF NOT EXISTS progress JSONB;
+            
             CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status);
             CREATE INDEX IF NOT EXISTS idx_jobs_priority ON jobs(priority DESC);
             CREATE INDEX IF NOT EXISTS idx_jobs_scheduled ON jobs(scheduled_at);
@@ -189,6 +302,63 @@ impl PostgresJobStorage {
     }
 }
 
+/// Job columns other than the payload, in [`JobRow`] order
+#[cfg(feature = "database")]
+const JOB_COLUMNS: &str =
+    "id, job_type, priority, status, retry_count, max_retries, created_at, scheduled_at, started_at, completed_at, error, progress";
+
+#[cfg(feature = "database")]
+type JobRow = (
+    Uuid,
+    String,
+    i32,
+    String,
+    i32,
+    i32,
+    chrono::DateTime<chrono::Utc>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<String>,
+    Option<sqlx::types::Json<JobProgress>>,
+);
+
+#[cfg(feature = "database")]
+fn job_from_row(row: JobRow) -> JobMetadata {
+    let status = match row.3.as_str() {
+        "Pending" => JobStatus::Pending,
+        "Running" => JobStatus::Running,
+        "Completed" => JobStatus::Completed,
+        "Failed" => JobStatus::Failed,
+        "Dead" => JobStatus::Dead,
+        "Cancelled" => JobStatus::Cancelled,
+        _ => JobStatus::Pending,
+    };
+    
+    let priority = match row.2 {
+        0 => crate::jobs::JobPriority::Low,
+        1 => crate::jobs::JobPriority::Normal,
+        2 => crate::jobs::JobPriority::High,
+        3 => crate::jobs::JobPriority::Critical,
+        _ => crate::jobs::JobPriority::Normal,
+    };
+    
+    JobMetadata {
+        id: row.0,
+        job_type: row.1,
+        priority,
+        status,
+        retry_count: row.4 as u32,
+        max_retries: row.5 as u32,
+        created_at: row.6,
+        scheduled_at: row.7,
+        started_at: row.8,
+        completed_at: row.9,
+        error: row.10,
+        progress: row.11.map(|progress| progress.0),
+    }
+}
+
 #[cfg(feature = "database")]
 #[async_trait]
 impl JobStorage for PostgresJobStorage {
@@ -197,15 +367,17 @@ impl JobStorage for PostgresJobStorage {
             r#"
             INSERT INTO jobs (
                 id, job_type, payload, priority, status, retry_count, max_retries,
-                created_at, scheduled_at, started_at, completed_at, error
+                created_at, scheduled_at, started_at, completed_at, error, progress
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
             ON CONFLICT (id) DO UPDATE SET
                 status = $5,
                 retry_count = $6,
+                scheduled_at = $9,
                 started_at = $10,
                 completed_at = $11,
-                error = $12
+                error = $12,
+                progress = $13
             "#,
         )
         .bind(metadata.id)
@@ -220,56 +392,87 @@ impl JobStorage for PostgresJobStorage {
         .bind(metadata.started_at)
         .bind(metadata.completed_at)
         .bind(&metadata.error)
+        .bind(metadata.progress.as_ref().map(sqlx::types::Json))
         .execute(&self.pool)
         .await?;
         
         Ok(())
     }
     
-    async fn get_job(&self, job_id: Uuid) -> Result<JobMetadata, ApiError> {
-        let row = sqlx::query_as::<_, (Uuid, String, i32, String, i32, i32, chrono::DateTime<chrono::Utc>, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, Option<String>)>(
-            "SELECT id, job_type, priority, status, retry_count, max_retries, created_at, scheduled_at, started_at, completed_at, error FROM jobs WHERE id = $1"
+    async fn insert_job(&self, metadata: &JobMetadata, payload: Value) -> Result<bool, ApiError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO jobs (
+                id, job_type, payload, priority, status, retry_count, max_retries,
+                created_at, scheduled_at, started_at, completed_at, error, progress
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (id) DO NOTHING
+            "#,
         )
-        .bind(job_id)
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))?;
-        
-        let status = match row.3.as_str() {
-            "Pending" => JobStatus::Pending,
-            "Running" => JobStatus::Running,
-            "Completed" => JobStatus::Completed,
-            "Failed" => JobStatus::Failed,
-            "Dead" => JobStatus::Dead,
-            "Cancelled" => JobStatus::Cancelled,
-            _ => JobStatus::Pending,
-        };
+        .bind(metadata.id)
+        .bind(&metadata.job_type)
+        .bind(&payload)
+        .bind(metadata.priority as i32)
+        .bind(format!("{:?}", metadata.status))
+        .bind(metadata.retry_count as i32)
+        .bind(metadata.max_retries as i32)
+        .bind(metadata.created_at)
+        .bind(metadata.scheduled_at)
+        .bind(metadata.started_at)
+        .bind(metadata.completed_at)
+        .bind(&metadata.error)
+        .bind(metadata.progress.as_ref().map(sqlx::types::Json))
+        .execute(&self.pool)
+        .await?;
         
-        let priority = match row.2 {
-            0 => crate::jobs::JobPriority::Low,
-            1 => crate::jobs::JobPriority::Normal,
-            2 => crate::jobs::JobPriority::High,
-            3 => crate::jobs::JobPriority::Critical,
-            _ => crate::jobs::JobPriority::Normal,
-        };
+        Ok(result.rows_affected() == 1)
+    }
+    
+    async fn get_job(&self, job_id: Uuid) -> Result<JobMetadata, ApiError> {
+        sqlx::query_as::<_, JobRow>(&format!("SELECT {} FROM jobs WHERE id = $1", JOB_COLUMNS))
+            .bind(job_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(job_from_row)
+            .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))
+    }
+    
+    async fn get_payload(&self, job_id: Uuid) -> Result<Value, ApiError> {
+        sqlx::query_scalar("SELECT payload FROM jobs WHERE id = $1")
+            .bind(job_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))
+    }
+    
+    async fn list_jobs(&self, status: JobStatus, limit: usize) -> Result<Vec<JobMetadata>, ApiError> {
+        let rows = sqlx::query_as::<_, JobRow>(&format!(
+            "SELECT {} FROM jobs WHERE status = $1 ORDER BY completed_at DESC NULLS LAST, created_at DESC LIMIT $2",
+            JOB_COLUMNS
+        ))
+        .bind(format!("{:?}", status))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
         
-        Ok(JobMetadata {
-            id: row.0,
-            job_type: row.1,
-            priority,
-            status,
-            retry_count: row.4 as u32,
-            max_retries: row.5 as u32,
-            created_at: row.6,
-            scheduled_at: row.7,
-            started_at: row.8,
-            completed_at: row.9,
-            error: row.10,
-        })
+        Ok(rows.into_iter().map(job_from_row).collect())
+    }
+    
+    async fn set_progress(&self, job_id: Uuid, progress: &JobProgress) -> Result<(), ApiError> {
+        let result = sqlx::query("UPDATE jobs SET progress = $2 WHERE id = $1")
+            .bind(job_id)
+            .bind(sqlx::types::Json(progress))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(ApiError::NotFound(format!("Job {} not found", job_id)));
+        }
+        Ok(())
     }
     
     async fn fetch_next_job(&self) -> Result<Option<(JobMetadata, Value)>, ApiError> {
-        let row = sqlx::query_as::<_, (Uuid, String, Value, i32, String, i32, i32, chrono::DateTime<chrono::Utc>, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, Option<String>)>(
+        let row = sqlx::query_as::<_, (Uuid, String, Value, i32, String, i32, i32, chrono::DateTime<chrono::Utc>, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, Option<String>, Option<sqlx::types::Json<JobProgress>>)>(
             r#"
             UPDATE jobs
             SET status = 'Running', started_at = NOW()
@@ -281,7 +484,7 @@ impl JobStorage for PostgresJobStorage {
                 LIMIT 1
                 FOR UPDATE SKIP LOCKED
             )
-            RETURNING id, job_type, payload, priority, status, retry_count, max_retries, created_at, scheduled_at, started_at, completed_at, error
+            RETURNING id, job_type, payload, priority, status, retry_count, max_retries, created_at, scheduled_at, started_at, completed_at, error, progress
             "#
         )
         .fetch_optional(&self.pool)
@@ -318,6 +521,7 @@ impl JobStorage for PostgresJobStorage {
                 started_at: row.9,
                 completed_at: row.10,
                 error: row.11,
+                progress: row.12.map(|progress| progress.0),
             };
             
             Ok(Some((metadata, row.2)))
@@ -350,6 +554,17 @@ impl JobStorage for PostgresJobStorage {
         })
     }
     
+    async fn count_pending(&self, job_type: Option<&str>) -> Result<usize, ApiError> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM jobs WHERE status = 'Pending' AND ($1::TEXT IS NULL OR job_type = $1)",
+        )
+        .bind(job_type)
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(count as usize)
+    }
+    
     async fn cleanup_old_jobs(&self, older_than_days: u32) -> Result<usize, ApiError> {
         let result = sqlx::query(
             r#"
@@ -369,15 +584,18 @@ impl JobStorage for PostgresJobStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::Clock;
     use crate::jobs::JobPriority;
     
     #[tokio::test]
     async fn test_in_memory_storage() {
         let storage = InMemoryJobStorage::new();
         
-        let mut metadata = JobMetadata::default();
-        metadata.job_type = "test_job".to_string();
-        metadata.priority = JobPriority::High;
+        let metadata = JobMetadata {
+            job_type: "test_job".to_string(),
+            priority: JobPriority::High,
+            ..Default::default()
+        };
         
         let payload = serde_json::json!({"test": "data"});
         
@@ -387,4 +605,22 @@ mod tests {
         assert_eq!(retrieved.job_type, "test_job");
         assert_eq!(retrieved.priority, JobPriority::High);
     }
+    
+    #[tokio::test]
+    async fn test_scheduled_jobs_wait_for_clock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let storage = InMemoryJobStorage::new().with_clock(clock.shared());
+        
+        let metadata = JobMetadata {
+            scheduled_at: Some(clock.now() + chrono::Duration::minutes(5)),
+            ..Default::default()
+        };
+        storage.save_job(&metadata, serde_json::json!({})).await.unwrap();
+        
+        assert!(storage.fetch_next_job().await.unwrap().is_none());
+        
+        clock.advance(std::time::Duration::from_secs(300));
+        let (fetched, _) = storage.fetch_next_job().await.unwrap().unwrap();
+        assert_eq!(fetched.id, metadata.id);
+    }
 }
@@ -3,16 +3,37 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 
+use super::{JobProgress, JobStorage};
+use crate::clock::SharedClock;
+use crate::error::ApiError;
+
 /// Job execution context
 #[derive(Debug, Clone)]
 pub struct JobContext {
     pub job_id: Uuid,
     pub job_type: String,
     pub retry_count: u32,
+    /// Retries the queue allows before the job is dead
+    pub max_retries: u32,
     pub metadata: HashMap<String, String>,
+    progress: Progress,
+}
+
+/// Where [`JobContext::set_progress`] goes; shared by clones of the context
+#[derive(Clone, Default)]
+struct Progress {
+    last: Arc<Mutex<Option<JobProgress>>>,
+    sink: Option<(Arc<dyn JobStorage>, SharedClock)>,
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress").field("last", &self.last).finish_non_exhaustive()
+    }
 }
 
 impl JobContext {
@@ -21,19 +42,57 @@ impl JobContext {
             job_id,
             job_type,
             retry_count: 0,
+            max_retries: 0,
             metadata: HashMap::new(),
+            progress: Progress::default(),
         }
     }
     
+    /// Persist progress to `storage` as the job reports it
+    pub(crate) fn with_progress_storage(mut self, storage: Arc<dyn JobStorage>, clock: SharedClock) -> Self {
+        self.progress.sink = Some((storage, clock));
+        self
+    }
+    
     pub fn with_retry_count(mut self, count: u32) -> Self {
         self.retry_count = count;
         self
     }
     
+    pub fn with_max_retries(mut self, max: u32) -> Self {
+        self.max_retries = max;
+        self
+    }
+    
     pub fn add_metadata(mut self, key: String, value: String) -> Self {
         self.metadata.insert(key, value);
         self
     }
+    
+    /// Report how far the job has got, shown by `GET /jobs/:id/status`
+    ///
+    /// `percent` is capped at 100.
+    pub async fn set_progress(&self, percent: u8, message: impl Into<String>) -> Result<(), ApiError> {
+        let updated_at = match &self.progress.sink {
+            Some((_, clock)) => clock.now(),
+            None => chrono::Utc::now(),
+        };
+        let progress = JobProgress {
+            percent: percent.min(100),
+            message: Some(message.into()),
+            updated_at,
+        };
+        *self.progress.last.lock().unwrap() = Some(progress.clone());
+        match &self.progress.sink {
+            Some((storage, _)) => storage.set_progress(self.job_id, &progress).await,
+            None => Ok(()),
+        }
+    }
+    
+    /// The progress last reported with [`set_progress`](Self::set_progress)
+    pub fn progress(&self) -> Option<JobProgress> {
+        self.progress.last.lock().unwrap().clone()
+    }
 }
 
 /// Job execution result
@@ -94,17 +153,44 @@ impl JobRegistry {
         tracing::info!(job_type = %job_type, "Registered job handler");
     }
     
-    /// Execute a job by type
+    /// Register a handler that isn't a typed [`Job`]
+    pub(crate) async fn register_handler(&self, job_type: &str, handler: Box<dyn JobHandler>) {
+        self.handlers.write().await.insert(job_type.to_string(), handler);
+        tracing::info!(job_type = %job_type, "Registered job handler");
+    }
+    
+    /// Execute a job by type, within the job's own timeout if it sets one
     pub async fn execute(
         &self,
         job_type: &str,
         payload: serde_json::Value,
         ctx: JobContext,
+    ) -> JobResult {
+        self.run(job_type, payload, ctx, None).await
+    }
+    
+    /// Execute a job by type, within `default_timeout` unless the job sets its own
+    pub async fn execute_with_timeout(
+        &self,
+        job_type: &str,
+        payload: serde_json::Value,
+        ctx: JobContext,
+        default_timeout: Duration,
+    ) -> JobResult {
+        self.run(job_type, payload, ctx, Some(default_timeout)).await
+    }
+    
+    async fn run(
+        &self,
+        job_type: &str,
+        payload: serde_json::Value,
+        ctx: JobContext,
+        default_timeout: Option<Duration>,
     ) -> JobResult {
         let handlers = self.handlers.read().await;
         
         if let Some(handler) = handlers.get(job_type) {
-            handler.handle(payload, ctx).await
+            handler.handle(payload, ctx, default_timeout).await
         } else {
             Err(format!("No handler registered for job type: {}", job_type).into())
         }
@@ -119,8 +205,8 @@ impl Default for JobRegistry {
 
 /// Internal trait for type-erased job handling
 #[async_trait]
-trait JobHandler: Send + Sync {
-    async fn handle(&self, payload: serde_json::Value, ctx: JobContext) -> JobResult;
+pub(crate) trait JobHandler: Send + Sync {
+    async fn handle(&self, payload: serde_json::Value, ctx: JobContext, default_timeout: Option<Duration>) -> JobResult;
 }
 
 /// Typed job handler wrapper
@@ -138,13 +224,19 @@ impl<J: Job> TypedJobHandler<J> {
 
 #[async_trait]
 impl<J: Job + 'static> JobHandler for TypedJobHandler<J> {
-    async fn handle(&self, payload: serde_json::Value, ctx: JobContext) -> JobResult {
+    async fn handle(&self, payload: serde_json::Value, ctx: JobContext, default_timeout: Option<Duration>) -> JobResult {
         let job: J = serde_json::from_value(payload)
             .map_err(|e| format!("Failed to deserialize job: {}", e))?;
         
         job.before_execute(&ctx).await?;
         
-        let result = job.execute(ctx.clone()).await;
+        let timeout = job.timeout_seconds().map(Duration::from_secs).or(default_timeout);
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, job.execute(ctx.clone()))
+                .await
+                .unwrap_or_else(|_| Err(format!("Job timed out after {}s", timeout.as_secs_f64()).into())),
+            None => job.execute(ctx.clone()).await,
+        };
         
         match &result {
             Ok(_) => {
@@ -0,0 +1,895 @@
+//! Workflow (saga) orchestration on top of the job system
+//!
+//! A workflow is an ordered list of steps. Each step may define a compensation
+//! action that undoes its effect; when a step fails, the compensations of all
+//! previously completed steps run in reverse order. Workflow state is persisted
+//! after every step so interrupted workflows can be resumed after a crash.
+//!
+//! [`WorkflowEngine::spawn`] runs an instance as a job on a [`JobQueue`], so
+//! a failed step is retried by the queue (resuming from the last persisted
+//! step) and only compensated once the job is out of retries. Such instances
+//! belong to the queue: [`WorkflowEngine::resume_interrupted`] leaves them to
+//! the job's own retry.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use rapid_rs::jobs::workflow::*;
+//!
+//! struct ChargePayment;
+//!
+//! #[async_trait]
+//! impl WorkflowStep for ChargePayment {
+//!     fn name(&self) -> &str { "charge_payment" }
+//!
+//!     async fn execute(&self, ctx: &mut WorkflowContext) -> JobResult {
+//!         ctx.set("payment_id", "pay_123");
+//!         Ok(())
+//!     }
+//!
+//!     async fn compensate(&self, ctx: &mut WorkflowContext) -> JobResult {
+//!         // refund ctx.get::<String>("payment_id")
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let engine = Arc::new(WorkflowEngine::new(InMemoryWorkflowStorage::new()));
+//! engine.register(Workflow::new("order").step(ReserveInventory).step(ChargePayment)).await?;
+//! engine.register_jobs(queue.registry()).await;
+//! queue.start_workers().await;
+//!
+//! let id = engine.spawn(&queue, "order", serde_json::json!({"order_id": 42})).await?;
+//! ```
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::worker::{JobContext, JobHandler, JobRegistry, JobResult};
+use super::{JobQueue, JobStorage};
+use crate::clock::SharedClock;
+use crate::error::ApiError;
+
+/// Workflow status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WorkflowStatus {
+    /// Steps are being executed
+    Running,
+    /// All steps completed successfully
+    Completed,
+    /// A step failed and compensations are running
+    Compensating,
+    /// All compensations completed after a failure
+    Compensated,
+    /// A compensation failed; manual intervention required
+    Failed,
+}
+
+impl WorkflowStatus {
+    /// Whether the workflow still has work left to do
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Running | Self::Compensating)
+    }
+}
+
+/// Persisted workflow state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowState {
+    pub id: Uuid,
+    pub workflow: String,
+    pub status: WorkflowStatus,
+    /// Index of the next step to execute
+    pub current_step: usize,
+    /// Names of steps that completed successfully, in order
+    pub completed_steps: Vec<String>,
+    /// Shared data passed between steps
+    pub data: serde_json::Value,
+    pub error: Option<String>,
+    /// Run by a queue job (see [`WorkflowEngine::spawn`]), which resumes it itself
+    #[serde(default)]
+    pub queued: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl WorkflowState {
+    fn new(workflow: &str, data: serde_json::Value, now: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            id: crate::ids::new_uuid(),
+            workflow: workflow.to_string(),
+            status: WorkflowStatus::Running,
+            current_step: 0,
+            completed_steps: Vec::new(),
+            data,
+            error: None,
+            queued: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Execution context handed to each workflow step
+#[derive(Debug, Clone)]
+pub struct WorkflowContext {
+    pub workflow_id: Uuid,
+    pub data: serde_json::Value,
+}
+
+impl WorkflowContext {
+    /// Read a typed value from the shared workflow data
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.data
+            .get(key)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Store a value in the shared workflow data
+    pub fn set<T: Serialize>(&mut self, key: &str, value: T) {
+        if !self.data.is_object() {
+            self.data = serde_json::Value::Object(serde_json::Map::new());
+        }
+        if let (Some(map), Ok(value)) = (self.data.as_object_mut(), serde_json::to_value(value)) {
+            map.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// A single step of a workflow
+#[async_trait]
+pub trait WorkflowStep: Send + Sync {
+    /// Step name (must be unique within a workflow)
+    fn name(&self) -> &str;
+
+    /// Perform the step
+    async fn execute(&self, ctx: &mut WorkflowContext) -> JobResult;
+
+    /// Undo the step after a later step failed
+    async fn compensate(&self, _ctx: &mut WorkflowContext) -> JobResult {
+        Ok(())
+    }
+}
+
+/// Workflow definition
+pub struct Workflow {
+    name: String,
+    steps: Vec<Arc<dyn WorkflowStep>>,
+}
+
+impl Workflow {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Append a step
+    pub fn step(mut self, step: impl WorkflowStep + 'static) -> Self {
+        self.steps.push(Arc::new(step));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn step_names(&self) -> Vec<String> {
+        self.steps.iter().map(|s| s.name().to_string()).collect()
+    }
+}
+
+/// Trait for workflow state storage backends
+#[async_trait]
+pub trait WorkflowStorage: Send + Sync + 'static {
+    /// Insert or update workflow state
+    async fn save_state(&self, state: &WorkflowState) -> Result<(), ApiError>;
+
+    /// Get workflow state by ID
+    async fn get_state(&self, id: Uuid) -> Result<WorkflowState, ApiError>;
+
+    /// List all workflow states
+    async fn list_states(&self) -> Result<Vec<WorkflowState>, ApiError>;
+
+    /// List workflows that were interrupted before finishing
+    async fn list_active(&self) -> Result<Vec<WorkflowState>, ApiError> {
+        Ok(self
+            .list_states()
+            .await?
+            .into_iter()
+            .filter(|s| s.status.is_active())
+            .collect())
+    }
+}
+
+/// In-memory workflow storage (for development/testing)
+#[derive(Clone, Default)]
+pub struct InMemoryWorkflowStorage {
+    states: Arc<RwLock<HashMap<Uuid, WorkflowState>>>,
+}
+
+impl InMemoryWorkflowStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WorkflowStorage for InMemoryWorkflowStorage {
+    async fn save_state(&self, state: &WorkflowState) -> Result<(), ApiError> {
+        self.states.write().await.insert(state.id, state.clone());
+        Ok(())
+    }
+
+    async fn get_state(&self, id: Uuid) -> Result<WorkflowState, ApiError> {
+        self.states
+            .read()
+            .await
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("Workflow {} not found", id)))
+    }
+
+    async fn list_states(&self) -> Result<Vec<WorkflowState>, ApiError> {
+        Ok(self.states.read().await.values().cloned().collect())
+    }
+}
+
+/// PostgreSQL workflow storage
+#[cfg(feature = "database")]
+pub struct PostgresWorkflowStorage {
+    pool: sqlx::PgPool,
+}
+
+#[cfg(feature = "database")]
+impl PostgresWorkflowStorage {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize the workflows table
+    pub async fn init(&self) -> Result<(), ApiError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS workflows (
+                id UUID PRIMARY KEY,
+                workflow VARCHAR(255) NOT NULL,
+                status VARCHAR(50) NOT NULL,
+                state JSONB NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_workflows_status ON workflows(status);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "database")]
+#[async_trait]
+impl WorkflowStorage for PostgresWorkflowStorage {
+    async fn save_state(&self, state: &WorkflowState) -> Result<(), ApiError> {
+        let json = serde_json::to_value(state)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to serialize workflow: {}", e)))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO workflows (id, workflow, status, state, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (id) DO UPDATE SET
+                status = $3,
+                state = $4,
+                updated_at = $5
+            "#,
+        )
+        .bind(state.id)
+        .bind(&state.workflow)
+        .bind(format!("{:?}", state.status))
+        .bind(json)
+        .bind(state.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_state(&self, id: Uuid) -> Result<WorkflowState, ApiError> {
+        let row = sqlx::query_as::<_, (serde_json::Value,)>("SELECT state FROM workflows WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound(format!("Workflow {} not found", id)))?;
+
+        serde_json::from_value(row.0)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to parse workflow: {}", e)))
+    }
+
+    async fn list_states(&self) -> Result<Vec<WorkflowState>, ApiError> {
+        let rows = sqlx::query_as::<_, (serde_json::Value,)>(
+            "SELECT state FROM workflows ORDER BY updated_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                serde_json::from_value(row.0).map_err(|e| {
+                    ApiError::InternalServerError(format!("Failed to parse workflow: {}", e))
+                })
+            })
+            .collect()
+    }
+
+    async fn list_active(&self) -> Result<Vec<WorkflowState>, ApiError> {
+        let rows = sqlx::query_as::<_, (serde_json::Value,)>(
+            "SELECT state FROM workflows WHERE status IN ('Running', 'Compensating')",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                serde_json::from_value(row.0).map_err(|e| {
+                    ApiError::InternalServerError(format!("Failed to parse workflow: {}", e))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Workflow engine - registers workflow definitions and drives their execution
+pub struct WorkflowEngine<S: WorkflowStorage> {
+    storage: Arc<S>,
+    workflows: RwLock<HashMap<String, Arc<Workflow>>>,
+    clock: SharedClock,
+}
+
+impl<S: WorkflowStorage> WorkflowEngine<S> {
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage: Arc::new(storage),
+            workflows: RwLock::new(HashMap::new()),
+            clock: crate::clock::system(),
+        }
+    }
+
+    /// Timestamp workflow state with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Register a workflow definition
+    ///
+    /// Fails if two steps share a name, since completed steps are recorded
+    /// (and compensated) by name.
+    pub async fn register(&self, workflow: Workflow) -> Result<(), ApiError> {
+        let mut names = std::collections::HashSet::new();
+        if let Some(step) = workflow.steps.iter().find(|step| !names.insert(step.name())) {
+            return Err(ApiError::BadRequest(format!(
+                "Workflow '{}' has more than one step named '{}'",
+                workflow.name,
+                step.name()
+            )));
+        }
+        tracing::info!(workflow = %workflow.name, steps = workflow.steps.len(), "Registered workflow");
+        self.workflows
+            .write()
+            .await
+            .insert(workflow.name.clone(), Arc::new(workflow));
+        Ok(())
+    }
+
+    /// Run the workflow jobs [`spawn`](Self::spawn) enqueues with this engine
+    ///
+    /// Register with the queue's registry before starting its workers, so
+    /// jobs left over from before a restart are picked up too.
+    pub async fn register_jobs(self: &Arc<Self>, registry: &JobRegistry) {
+        registry
+            .register_handler(WORKFLOW_JOB_TYPE, Box::new(WorkflowJobHandler(Arc::clone(self))))
+            .await;
+    }
+
+    /// Start a new workflow instance and run it to completion
+    ///
+    /// Returns the workflow ID. Use [`WorkflowEngine::spawn`] to run in the background.
+    pub async fn start(&self, workflow: &str, data: serde_json::Value) -> Result<Uuid, ApiError> {
+        let definition = self.definition(workflow).await?;
+        let state = WorkflowState::new(workflow, data, self.clock.now());
+        let id = state.id;
+
+        self.storage.save_state(&state).await?;
+        tracing::info!(workflow_id = %id, workflow = %workflow, "Workflow started");
+
+        self.drive(&definition, state, false).await?;
+        Ok(id)
+    }
+
+    /// Start a new workflow instance as a job on `queue`
+    ///
+    /// The queue's workers must run the engine's handler, see
+    /// [`register_jobs`](Self::register_jobs). A failed step is retried with
+    /// the job, so steps should be safe to run more than once.
+    pub async fn spawn<Q: JobStorage>(
+        &self,
+        queue: &JobQueue<Q>,
+        workflow: &str,
+        data: serde_json::Value,
+    ) -> Result<Uuid, ApiError> {
+        self.definition(workflow).await?;
+        let mut state = WorkflowState::new(workflow, data, self.clock.now());
+        state.queued = true;
+        let id = state.id;
+
+        self.storage.save_state(&state).await?;
+        queue.enqueue(WorkflowJob { workflow_id: id }, WORKFLOW_JOB_TYPE).await?;
+        tracing::info!(workflow_id = %id, workflow = %workflow, "Workflow started");
+
+        Ok(id)
+    }
+
+    /// Resume all workflows that were interrupted (e.g. by a crash)
+    ///
+    /// Call this once on startup after registering workflow definitions.
+    /// Instances started with [`spawn`](Self::spawn) are skipped, since their
+    /// job is retried by the queue. A workflow that fails to resume is logged
+    /// and does not stop the others.
+    pub async fn resume_interrupted(&self) -> Result<usize, ApiError> {
+        let active = self.storage.list_active().await?;
+        let mut resumed = 0;
+
+        for state in active.into_iter().filter(|state| !state.queued) {
+            let definition = match self.definition(&state.workflow).await {
+                Ok(definition) => definition,
+                Err(_) => {
+                    tracing::warn!(workflow_id = %state.id, workflow = %state.workflow, "No definition registered, skipping resume");
+                    continue;
+                }
+            };
+
+            let id = state.id;
+            tracing::info!(workflow_id = %id, status = ?state.status, "Resuming workflow");
+            match self.drive(&definition, state, false).await {
+                Ok(()) => resumed += 1,
+                Err(e) => tracing::error!(workflow_id = %id, error = %e, "Failed to resume workflow"),
+            }
+        }
+
+        Ok(resumed)
+    }
+
+    /// Get workflow state
+    pub async fn status(&self, id: Uuid) -> Result<WorkflowState, ApiError> {
+        self.storage.get_state(id).await
+    }
+
+    /// List all workflow states
+    pub async fn list(&self) -> Result<Vec<WorkflowState>, ApiError> {
+        self.storage.list_states().await
+    }
+
+    async fn definition(&self, workflow: &str) -> Result<Arc<Workflow>, ApiError> {
+        self.workflows
+            .read()
+            .await
+            .get(workflow)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("Workflow '{}' is not registered", workflow)))
+    }
+
+    async fn persist(&self, state: &mut WorkflowState, ctx: &WorkflowContext) -> Result<(), ApiError> {
+        state.data = ctx.data.clone();
+        state.updated_at = self.clock.now();
+        self.storage.save_state(state).await
+    }
+
+    /// Compensate an instance whose run was cut short, recording `error`
+    async fn abandon(&self, workflow: &Workflow, id: Uuid, error: &str) -> Result<(), ApiError> {
+        let mut state = self.storage.get_state(id).await?;
+        if state.status == WorkflowStatus::Running {
+            tracing::warn!(workflow_id = %id, error = %error, "Workflow out of time, compensating");
+            state.status = WorkflowStatus::Compensating;
+            state.error = Some(error.to_string());
+            state.updated_at = self.clock.now();
+            self.storage.save_state(&state).await?;
+        }
+        self.drive(workflow, state, false).await
+    }
+
+    /// Run remaining steps (or compensations) from the persisted position
+    ///
+    /// With `retry`, a failed step is returned as an error instead of being
+    /// compensated, for the job to run it again.
+    async fn drive(&self, workflow: &Workflow, mut state: WorkflowState, retry: bool) -> Result<(), ApiError> {
+        let mut ctx = WorkflowContext {
+            workflow_id: state.id,
+            data: state.data.clone(),
+        };
+
+        if state.status == WorkflowStatus::Running {
+            while state.current_step < workflow.steps.len() {
+                let step = &workflow.steps[state.current_step];
+
+                match step.execute(&mut ctx).await {
+                    Ok(()) => {
+                        tracing::debug!(workflow_id = %state.id, step = %step.name(), "Workflow step completed");
+                        state.completed_steps.push(step.name().to_string());
+                        state.current_step += 1;
+                        self.persist(&mut state, &ctx).await?;
+                    }
+                    Err(e) => {
+                        let error = format!("Step '{}' failed: {}", step.name(), e);
+                        state.error = Some(error.clone());
+                        if retry {
+                            tracing::warn!(workflow_id = %state.id, step = %step.name(), error = %e, "Workflow step failed, retrying");
+                            self.persist(&mut state, &ctx).await?;
+                            return Err(ApiError::InternalServerError(error));
+                        }
+                        tracing::warn!(workflow_id = %state.id, step = %step.name(), error = %e, "Workflow step failed, compensating");
+                        state.status = WorkflowStatus::Compensating;
+                        self.persist(&mut state, &ctx).await?;
+                        break;
+                    }
+                }
+            }
+
+            if state.status == WorkflowStatus::Running {
+                state.status = WorkflowStatus::Completed;
+                state.error = None;
+                self.persist(&mut state, &ctx).await?;
+                tracing::info!(workflow_id = %state.id, "Workflow completed");
+                return Ok(());
+            }
+        }
+
+        if state.status == WorkflowStatus::Compensating {
+            // Compensate completed steps in reverse order, popping each as it is undone
+            while let Some(name) = state.completed_steps.last().cloned() {
+                let Some(step) = workflow.steps.iter().find(|s| s.name() == name) else {
+                    state.completed_steps.pop();
+                    continue;
+                };
+
+                if let Err(e) = step.compensate(&mut ctx).await {
+                    tracing::error!(workflow_id = %state.id, step = %name, error = %e, "Compensation failed");
+                    state.status = WorkflowStatus::Failed;
+                    state.error = Some(format!("Compensation of '{}' failed: {}", name, e));
+                    self.persist(&mut state, &ctx).await?;
+                    return Ok(());
+                }
+
+                state.completed_steps.pop();
+                self.persist(&mut state, &ctx).await?;
+            }
+
+            state.status = WorkflowStatus::Compensated;
+            self.persist(&mut state, &ctx).await?;
+            tracing::info!(workflow_id = %state.id, "Workflow compensated");
+        }
+
+        Ok(())
+    }
+}
+
+/// Job type [`WorkflowEngine::spawn`] enqueues workflow instances under
+pub const WORKFLOW_JOB_TYPE: &str = "workflow";
+
+#[derive(Serialize, Deserialize)]
+struct WorkflowJob {
+    workflow_id: Uuid,
+}
+
+/// Drives a spawned workflow from its persisted state
+struct WorkflowJobHandler<S: WorkflowStorage>(Arc<WorkflowEngine<S>>);
+
+#[async_trait]
+impl<S: WorkflowStorage> JobHandler for WorkflowJobHandler<S> {
+    async fn handle(&self, payload: serde_json::Value, ctx: JobContext, default_timeout: Option<Duration>) -> JobResult {
+        let job: WorkflowJob = serde_json::from_value(payload)
+            .map_err(|e| format!("Failed to deserialize job: {}", e))?;
+        let engine = &self.0;
+        let state = engine.storage.get_state(job.workflow_id).await?;
+        if !state.status.is_active() {
+            return Ok(());
+        }
+        let definition = engine.definition(&state.workflow).await?;
+        // A timed out attempt resumes from the last step it persisted
+        let retry = ctx.retry_count < ctx.max_retries;
+        let drive = engine.drive(&definition, state, retry);
+        let Some(timeout) = default_timeout else {
+            drive.await?;
+            return Ok(());
+        };
+        match tokio::time::timeout(timeout, drive).await {
+            Ok(result) => Ok(result?),
+            Err(_) => {
+                let error = format!("Job timed out after {}s", timeout.as_secs_f64());
+                // Out of attempts: undo the steps the cut-short run completed
+                if !retry {
+                    engine.abandon(&definition, job.workflow_id, &error).await?;
+                }
+                Err(error.into())
+            }
+        }
+    }
+}
+
+async fn get_workflow<S: WorkflowStorage>(
+    State(engine): State<Arc<WorkflowEngine<S>>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<WorkflowState>, ApiError> {
+    Ok(Json(engine.status(id).await?))
+}
+
+async fn list_workflows<S: WorkflowStorage>(
+    State(engine): State<Arc<WorkflowEngine<S>>>,
+) -> Result<Json<Vec<WorkflowState>>, ApiError> {
+    Ok(Json(engine.list().await?))
+}
+
+/// Create workflow status routes
+///
+/// Mounts:
+/// - GET /workflows - List workflows
+/// - GET /workflows/:id - Get workflow status
+pub fn workflow_routes<S: WorkflowStorage>(engine: Arc<WorkflowEngine<S>>) -> Router {
+    Router::new()
+        .route("/workflows", get(list_workflows::<S>))
+        .route("/workflows/:id", get(get_workflow::<S>))
+        .with_state(engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Reserve(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl WorkflowStep for Reserve {
+        fn name(&self) -> &str {
+            "reserve"
+        }
+
+        async fn execute(&self, ctx: &mut WorkflowContext) -> JobResult {
+            ctx.set("reserved", true);
+            Ok(())
+        }
+
+        async fn compensate(&self, ctx: &mut WorkflowContext) -> JobResult {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ctx.set("reserved", false);
+            Ok(())
+        }
+    }
+
+    struct Charge {
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl WorkflowStep for Charge {
+        fn name(&self) -> &str {
+            "charge"
+        }
+
+        async fn execute(&self, _ctx: &mut WorkflowContext) -> JobResult {
+            if self.fail {
+                Err("card declined".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workflow_completes() {
+        let compensations = Arc::new(AtomicUsize::new(0));
+        let engine = WorkflowEngine::new(InMemoryWorkflowStorage::new());
+        engine
+            .register(
+                Workflow::new("order")
+                    .step(Reserve(compensations.clone()))
+                    .step(Charge { fail: false }),
+            )
+            .await
+            .unwrap();
+
+        let id = engine.start("order", serde_json::json!({})).await.unwrap();
+        let state = engine.status(id).await.unwrap();
+
+        assert_eq!(state.status, WorkflowStatus::Completed);
+        assert_eq!(state.completed_steps, vec!["reserve", "charge"]);
+        assert_eq!(state.data["reserved"], true);
+        assert_eq!(compensations.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_workflow_compensates_on_failure() {
+        let compensations = Arc::new(AtomicUsize::new(0));
+        let engine = WorkflowEngine::new(InMemoryWorkflowStorage::new());
+        engine
+            .register(
+                Workflow::new("order")
+                    .step(Reserve(compensations.clone()))
+                    .step(Charge { fail: true }),
+            )
+            .await
+            .unwrap();
+
+        let id = engine.start("order", serde_json::json!({})).await.unwrap();
+        let state = engine.status(id).await.unwrap();
+
+        assert_eq!(state.status, WorkflowStatus::Compensated);
+        assert!(state.completed_steps.is_empty());
+        assert_eq!(state.data["reserved"], false);
+        assert_eq!(compensations.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_step_names_rejected() {
+        let engine = WorkflowEngine::new(InMemoryWorkflowStorage::new());
+        let workflow = Workflow::new("order")
+            .step(Charge { fail: false })
+            .step(Charge { fail: false });
+        assert!(matches!(engine.register(workflow).await, Err(ApiError::BadRequest(_))));
+        assert!(engine.start("order", serde_json::json!({})).await.is_err());
+    }
+
+    struct Flaky(Arc<AtomicUsize>, usize);
+
+    #[async_trait]
+    impl WorkflowStep for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn execute(&self, _ctx: &mut WorkflowContext) -> JobResult {
+            if self.0.fetch_add(1, Ordering::SeqCst) < self.1 {
+                return Err("gateway timeout".into());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spawned_workflow_retries_through_queue() {
+        use crate::jobs::{InMemoryJobStorage, JobConfig};
+
+        let config = JobConfig {
+            max_retries: 2,
+            retry_delay_seconds: 0,
+            worker_count: 1,
+            job_timeout_seconds: 60,
+        };
+        let queue = JobQueue::new(InMemoryJobStorage::new(), config);
+        let engine = Arc::new(WorkflowEngine::new(InMemoryWorkflowStorage::new()));
+        let compensations = Arc::new(AtomicUsize::new(0));
+        let (recovers, never) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        engine
+            .register(Workflow::new("recovers").step(Reserve(compensations.clone())).step(Flaky(recovers.clone(), 2)))
+            .await
+            .unwrap();
+        engine
+            .register(Workflow::new("never").step(Reserve(compensations.clone())).step(Flaky(never.clone(), 10)))
+            .await
+            .unwrap();
+        engine.register_jobs(queue.registry()).await;
+        queue.start_workers().await;
+
+        let recovered = engine.spawn(&queue, "recovers", serde_json::json!({})).await.unwrap();
+        let failed = engine.spawn(&queue, "never", serde_json::json!({})).await.unwrap();
+        for _ in 0..200 {
+            let states = [engine.status(recovered).await.unwrap(), engine.status(failed).await.unwrap()];
+            if states.iter().all(|state| !state.status.is_active()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        // Each retry resumes at the failed step rather than starting over
+        let state = engine.status(recovered).await.unwrap();
+        assert_eq!(state.status, WorkflowStatus::Completed);
+        assert!(state.error.is_none());
+        assert_eq!(recovers.load(Ordering::SeqCst), 3);
+
+        // Compensation waits until the job is out of retries
+        let state = engine.status(failed).await.unwrap();
+        assert_eq!(state.status, WorkflowStatus::Compensated);
+        assert_eq!(never.load(Ordering::SeqCst), 3);
+        assert_eq!(compensations.load(Ordering::SeqCst), 1);
+
+        let stats = queue.stats().await.unwrap();
+        assert_eq!((stats.completed, stats.dead), (2, 0));
+        queue.stop_workers().await;
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_workflow() {
+        let storage = InMemoryWorkflowStorage::new();
+
+        // Simulate a crash after the first step was persisted
+        let mut state = WorkflowState::new("order", serde_json::json!({"reserved": true}), chrono::Utc::now());
+        state.current_step = 1;
+        state.completed_steps = vec!["reserve".to_string()];
+        storage.save_state(&state).await.unwrap();
+
+        let engine = WorkflowEngine::new(storage);
+        engine
+            .register(
+                Workflow::new("order")
+                    .step(Reserve(Arc::new(AtomicUsize::new(0))))
+                    .step(Charge { fail: false }),
+            )
+            .await
+            .unwrap();
+
+        // Spawned instances are left to their queue job
+        let mut queued = WorkflowState::new("order", serde_json::json!({}), chrono::Utc::now());
+        queued.queued = true;
+        engine.storage.save_state(&queued).await.unwrap();
+
+        assert_eq!(engine.resume_interrupted().await.unwrap(), 1);
+        let resumed = engine.status(state.id).await.unwrap();
+        assert_eq!(resumed.status, WorkflowStatus::Completed);
+        assert_eq!(engine.status(queued.id).await.unwrap().status, WorkflowStatus::Running);
+    }
+
+    struct Hang;
+
+    #[async_trait]
+    impl WorkflowStep for Hang {
+        fn name(&self) -> &str {
+            "hang"
+        }
+
+        async fn execute(&self, _ctx: &mut WorkflowContext) -> JobResult {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_final_attempt_timeout_compensates() {
+        let compensations = Arc::new(AtomicUsize::new(0));
+        let engine = Arc::new(WorkflowEngine::new(InMemoryWorkflowStorage::new()));
+        engine
+            .register(Workflow::new("order").step(Reserve(compensations.clone())).step(Hang))
+            .await
+            .unwrap();
+        let mut state = WorkflowState::new("order", serde_json::json!({}), chrono::Utc::now());
+        state.queued = true;
+        engine.storage.save_state(&state).await.unwrap();
+
+        let handler = WorkflowJobHandler(engine.clone());
+        let payload = serde_json::json!({"workflow_id": state.id});
+        let mut ctx = JobContext::new(Uuid::new_v4(), WORKFLOW_JOB_TYPE.to_string());
+        ctx.max_retries = 1;
+
+        // An attempt with retries left keeps the workflow running
+        let timeout = Some(Duration::from_millis(20));
+        assert!(handler.handle(payload.clone(), ctx.clone(), timeout).await.is_err());
+        assert_eq!(engine.status(state.id).await.unwrap().status, WorkflowStatus::Running);
+
+        ctx.retry_count = 1;
+        assert!(handler.handle(payload, ctx, timeout).await.is_err());
+        let state = engine.status(state.id).await.unwrap();
+        assert_eq!(state.status, WorkflowStatus::Compensated);
+        assert!(state.error.unwrap().contains("timed out"));
+        assert_eq!(compensations.load(Ordering::SeqCst), 1);
+    }
+}
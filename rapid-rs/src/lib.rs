@@ -3,12 +3,45 @@
 //! Zero-config, batteries-included web framework for Rust.
 //! FastAPI meets Spring Boot, powered by Axum.
 
+// Lets `#[derive(FromEnv)]` refer to `::rapid_rs` inside this crate too
+extern crate self as rapid_rs;
+
+pub mod analytics;
 pub mod app;
+pub mod client_ip;
+pub mod clock;
+pub mod conditional;
 pub mod config;
 pub mod database;
+pub mod dependencies;
+pub mod deprecation;
+pub mod dev;
+pub mod docs_ui;
+pub mod env;
 pub mod error;
+pub mod error_catalog;
 pub mod extractors;
+pub mod health;
+pub mod i18n;
+pub mod ids;
+pub(crate) mod limits;
+pub mod middleware;
+pub mod openapi;
+pub mod pagination;
+pub mod plugin;
+pub mod policy;
 pub mod prelude;
+pub mod query;
+pub mod replay;
+#[cfg(any(feature = "cache", feature = "sessions"))]
+pub mod replication;
+pub mod reporting;
+pub mod sitemap;
+pub mod startup;
+pub mod validation;
+pub mod versioning;
+pub(crate) mod listener;
+pub(crate) mod shutdown;
 
 // Phase 2 features
 #[cfg(feature = "auth")]
@@ -39,6 +72,9 @@ pub mod feature_flags;
 #[cfg(feature = "multi-tenancy")]
 pub mod multi_tenancy;
 
+#[cfg(feature = "tls")]
+pub mod tls;
+
 // Phase 4 features
 #[cfg(feature = "graphql")]
 pub mod graphql;
@@ -52,6 +88,28 @@ pub mod uploads;
 #[cfg(feature = "admin")]
 pub mod admin;
 
+#[cfg(feature = "streaming")]
+pub mod streaming;
+
+#[cfg(feature = "events")]
+pub mod events;
+
+#[cfg(feature = "http-client")]
+pub mod http_client;
+
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+
+#[cfg(feature = "lambda")]
+pub mod serverless;
+
+#[cfg(feature = "chaos")]
+pub mod chaos;
+
 pub use app::App;
-pub use error::{ApiError, ApiResult};
-pub use extractors::ValidatedJson;
\ No newline at end of file
+pub use dependencies::Dep;
+pub use env::FromEnv;
+pub use error::{ApiError, ApiResult, WithDetails};
+pub use extractors::{ValidatedForm, ValidatedJson, ValidatedQuery, ValidationConfig};
+pub use openapi::{api_handler, RouteDoc};
+pub use pagination::{Paginated, Pagination, Sort, Sortable};
\ No newline at end of file
@@ -0,0 +1,169 @@
+//! Request timeout and body size limits
+//!
+//! Set app-wide with [`App::with_request_timeout`](crate::App::with_request_timeout)
+//! and [`App::with_max_body_size`](crate::App::with_max_body_size), or the
+//! `server.request_timeout_seconds` and `server.max_body_size_bytes` config
+//! keys, and override them for single routes by their path pattern:
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .route("/reports", get(reports))
+//!     .route("/imports", post(import))
+//!     .with_request_timeout(Duration::from_secs(10))
+//!     .with_max_body_size(1024 * 1024)
+//!     .with_route_timeout("/reports", Duration::from_secs(60))
+//!     .with_route_max_body_size("/imports", 100 * 1024 * 1024)
+//! ```
+//!
+//! Slow requests fail with `408 REQUEST_TIMEOUT` and oversized bodies with
+//! `413 PAYLOAD_TOO_LARGE`, in the usual error JSON.
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{ApiError, RenderedError};
+
+/// axum's own default, kept for routes without a configured limit
+const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RequestLimits {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) max_body_size: Option<usize>,
+    pub(crate) route_timeouts: HashMap<String, Duration>,
+    pub(crate) route_body_sizes: HashMap<String, usize>,
+}
+
+fn route(request: &Request) -> Option<&str> {
+    request.extensions().get::<MatchedPath>().map(MatchedPath::as_str)
+}
+
+impl RequestLimits {
+    pub(crate) fn has_timeouts(&self) -> bool {
+        self.timeout.is_some() || !self.route_timeouts.is_empty()
+    }
+
+    pub(crate) fn has_body_sizes(&self) -> bool {
+        self.max_body_size.is_some() || !self.route_body_sizes.is_empty()
+    }
+
+    fn timeout_for(&self, request: &Request) -> Option<Duration> {
+        route(request)
+            .and_then(|route| self.route_timeouts.get(route).copied())
+            .or(self.timeout)
+    }
+
+    fn body_size_for(&self, request: &Request) -> usize {
+        route(request)
+            .and_then(|route| self.route_body_sizes.get(route).copied())
+            .or(self.max_body_size)
+            .unwrap_or(DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+pub(crate) fn payload_too_large(limit: usize) -> ApiError {
+    ApiError::custom(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "PAYLOAD_TOO_LARGE",
+        format!("Request body exceeds {} bytes", limit),
+    )
+}
+
+/// Apply the route's timeout, see [`enforce_deadline`](crate::database::deadline)
+pub(crate) async fn enforce_timeout(limits: Arc<RequestLimits>, request: Request, next: Next) -> Response {
+    match limits.timeout_for(&request) {
+        Some(timeout) => crate::database::deadline::enforce_deadline(timeout, request, next).await,
+        None => next.run(request).await,
+    }
+}
+
+/// Reject bodies over the route's limit, up front when `Content-Length` says so
+pub(crate) async fn enforce_body_size(limits: Arc<RequestLimits>, request: Request, next: Next) -> Response {
+    let limit = limits.body_size_for(&request);
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > limit as u64) {
+        return payload_too_large(limit).into_response();
+    }
+
+    let request = request.map(|body| Body::new(http_body_util::Limited::new(body, limit)));
+    let response = next.run(request).await;
+
+    // axum's own rejection when a streamed body runs over is plain text
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && response.extensions().get::<RenderedError>().is_none() {
+        return payload_too_large(limit).into_response();
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::App;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::post};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_body_size_limits() {
+        let router = App::new()
+            .route("/small", post(|body: String| async move { body.len().to_string() }))
+            .route("/large", post(|body: String| async move { body.len().to_string() }))
+            .with_max_body_size(10)
+            .with_route_max_body_size("/large", 100)
+            .into_router();
+        let send = |uri: &str, body: String, declared: bool| {
+            let mut request = Request::post(uri);
+            if declared {
+                request = request.header("content-length", body.len());
+            }
+            router.clone().oneshot(request.body(Body::from(body)).unwrap())
+        };
+
+        assert_eq!(send("/small", "short".into(), true).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("/large", "x".repeat(50), true).await.unwrap().status(), StatusCode::OK);
+
+        // Rejected from Content-Length, and while reading when it's missing
+        for declared in [true, false] {
+            let response = send("/small", "x".repeat(50), declared).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+        }
+        let response = send("/large", "x".repeat(120), false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_route_timeout_override() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            "done"
+        };
+        let router = App::new()
+            .route("/slow", axum::routing::get(slow))
+            .route("/report", axum::routing::get(slow))
+            .with_request_timeout(Duration::from_millis(50))
+            .with_route_timeout("/report", Duration::from_secs(5))
+            .into_router();
+        let get = |uri: &str| router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let response = get("/slow").await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "REQUEST_TIMEOUT");
+        assert_eq!(get("/report").await.unwrap().status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,244 @@
+//! Connection handling for `App::run`
+//!
+//! Protections that HTTP middleware can't provide because they apply before
+//! a request exists: per-IP and total connection limits, the accept queue
+//! length, and a deadline for clients to finish sending request headers.
+
+use axum::{extract::ConnectInfo, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::{conn::auto::Builder, graceful::{GracefulShutdown, Watcher}},
+    service::TowerToHyperService,
+};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tower::ServiceExt;
+
+use crate::client_ip::ListenerScheme;
+use crate::config::ServerConfig;
+
+/// Bind `addr` with an accept queue of `backlog` connections
+pub(crate) fn bind(addr: SocketAddr, backlog: u32) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(backlog)
+}
+
+/// Connection limits from [`ServerConfig`], shared by every listener
+#[derive(Clone)]
+pub(crate) struct ConnectionLimits {
+    per_ip: Option<usize>,
+    total: Option<Arc<Semaphore>>,
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    header_read_timeout: Duration,
+}
+
+impl ConnectionLimits {
+    pub(crate) fn new(config: &ServerConfig) -> Self {
+        Self {
+            per_ip: config.max_connections_per_ip,
+            total: config.max_connections.map(|max| Arc::new(Semaphore::new(max))),
+            open: Arc::default(),
+            header_read_timeout: Duration::from_secs(config.header_read_timeout_seconds),
+        }
+    }
+
+    /// Next connection within the limits
+    ///
+    /// Waits without accepting while the server is at capacity, so clients
+    /// queue in the kernel backlog. Connections from an IP that already has
+    /// its maximum open are closed straight away.
+    pub(crate) async fn accept(&self, listener: &TcpListener) -> (TcpStream, SocketAddr, ConnectionPermit) {
+        loop {
+            let slot = match &self.total {
+                Some(total) => Some(total.clone().acquire_owned().await.expect("semaphore is never closed")),
+                None => None,
+            };
+
+            let (stream, addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    accept_failed(e).await;
+                    continue;
+                }
+            };
+
+            match self.track(addr.ip()) {
+                Some(client) => return (stream, addr, ConnectionPermit { _slot: slot, _client: client }),
+                None => tracing::debug!(client = %addr, "Too many connections from client, closing"),
+            }
+        }
+    }
+
+    fn track(&self, ip: IpAddr) -> Option<ClientSlot> {
+        let mut open = self.open.lock().unwrap();
+        let count = open.entry(ip).or_default();
+        if self.per_ip.is_some_and(|max| *count >= max) {
+            return None;
+        }
+        *count += 1;
+        Some(ClientSlot {
+            ip,
+            open: self.open.clone(),
+        })
+    }
+
+    /// Connection builder enforcing the header read timeout
+    pub(crate) fn builder(&self) -> Builder<TokioExecutor> {
+        let mut builder = Builder::new(TokioExecutor::new());
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .header_read_timeout(self.header_read_timeout);
+        builder
+    }
+}
+
+/// Held for as long as a connection is open
+pub(crate) struct ConnectionPermit {
+    _slot: Option<OwnedSemaphorePermit>,
+    _client: ClientSlot,
+}
+
+struct ClientSlot {
+    ip: IpAddr,
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        let mut open = self.open.lock().unwrap();
+        if let Some(count) = open.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Back off after accept errors, e.g. running out of file descriptors
+async fn accept_failed(e: io::Error) {
+    if matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionRefused
+    ) {
+        return;
+    }
+    tracing::warn!(error = %e, "Failed to accept connection");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+/// Serve `router` on one accepted connection until it closes
+///
+/// Requests carry `ConnectInfo<SocketAddr>` for the client address and the
+/// listener's `scheme` for [`ClientInfo`](crate::client_ip::ClientInfo).
+pub(crate) async fn serve_connection<S>(
+    io: S,
+    addr: SocketAddr,
+    scheme: &'static str,
+    router: Router,
+    builder: &Builder<TokioExecutor>,
+    watcher: Watcher,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = router.map_request(move |mut request: axum::extract::Request<hyper::body::Incoming>| {
+        request.extensions_mut().insert(ConnectInfo(addr));
+        request.extensions_mut().insert(ListenerScheme(scheme));
+        request
+    });
+
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(io), TowerToHyperService::new(service));
+    if let Err(e) = watcher.watch(connection).await {
+        tracing::debug!(client = %addr, error = %e, "Connection closed with error");
+    }
+}
+
+/// Serve plain HTTP until `shutdown` turns true, then drain
+pub(crate) async fn serve(
+    listener: TcpListener,
+    router: Router,
+    limits: ConnectionLimits,
+    mut shutdown: watch::Receiver<bool>,
+) -> io::Result<()> {
+    let graceful = GracefulShutdown::new();
+
+    loop {
+        let (stream, addr, permit) = tokio::select! {
+            conn = limits.accept(&listener) => conn,
+            _ = shutdown.wait_for(|stopping| *stopping) => break,
+        };
+
+        let builder = limits.builder();
+        let router = router.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            serve_connection(stream, addr, "http", router, &builder, watcher).await;
+            drop(permit);
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn closes_within(stream: &mut TcpStream, wait: Duration) -> bool {
+        let mut buf = Vec::new();
+        tokio::time::timeout(wait, stream.read_to_end(&mut buf))
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_connection_limits() {
+        let config = ServerConfig {
+            max_connections_per_ip: Some(1),
+            header_read_timeout_seconds: 1,
+            ..crate::config::AppConfig::default().server
+        };
+        let listener = bind("127.0.0.1:0".parse().unwrap(), config.accept_backlog).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new().route("/", axum::routing::get(|| async { "ok" }));
+        let (stop, stopped) = watch::channel(false);
+        let server = tokio::spawn(serve(listener, router, ConnectionLimits::new(&config), stopped));
+
+        // A slow client sends half its headers and holds the connection
+        let mut slow = TcpStream::connect(addr).await.unwrap();
+        slow.write_all(b"GET / HTTP/1.1\r\nHost: loc").await.unwrap();
+
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        assert!(closes_within(&mut second, Duration::from_millis(500)).await);
+
+        // The header timeout frees the slot for the next connection
+        assert!(closes_within(&mut slow, Duration::from_secs(5)).await);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        stop.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+    }
+}
@@ -108,50 +108,25 @@ pub fn record_request(method: &str, path: &str, status_code: u16, duration: Dura
     }
 }
 
+/// Label pairs as `metrics` labels
+#[cfg(feature = "observability")]
+fn to_labels(labels: &[(&'static str, String)]) -> Vec<metrics::Label> {
+    labels.iter().map(|(key, value)| metrics::Label::new(*key, value.clone())).collect()
+}
+
 #[cfg(feature = "observability")]
 pub fn record_counter(name: &'static str, value: u64, labels: &[(&'static str, String)]) {
-    use metrics::counter;
-    
-    if labels.is_empty() {
-        counter!(name).increment(value);
-    } else {
-        // Build labels dynamically
-        let mut c = counter!(name);
-        for (key, val) in labels {
-            c = counter!(name, *key => val.clone());
-        }
-        c.increment(value);
-    }
+    metrics::counter!(name, to_labels(labels)).increment(value);
 }
 
 #[cfg(feature = "observability")]
 pub fn record_gauge(name: &'static str, value: f64, labels: &[(&'static str, String)]) {
-    use metrics::gauge;
-    
-    if labels.is_empty() {
-        gauge!(name).set(value);
-    } else {
-        let mut g = gauge!(name);
-        for (key, val) in labels {
-            g = gauge!(name, *key => val.clone());
-        }
-        g.set(value);
-    }
+    metrics::gauge!(name, to_labels(labels)).set(value);
 }
 
 #[cfg(feature = "observability")]
 pub fn record_histogram(name: &'static str, value: f64, labels: &[(&'static str, String)]) {
-    use metrics::histogram;
-    
-    if labels.is_empty() {
-        histogram!(name).record(value);
-    } else {
-        let mut h = histogram!(name);
-        for (key, val) in labels {
-            h = histogram!(name, *key => val.clone());
-        }
-        h.record(value);
-    }
+    metrics::histogram!(name, to_labels(labels)).record(value);
 }
 
 #[cfg(feature = "observability")]
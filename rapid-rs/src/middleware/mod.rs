@@ -1,3 +1,3 @@
 pub mod request_id;
 
-pub use request_id::RequestIdLayer;
+pub use request_id::{RequestId, RequestIdLayer, REQUEST_ID_HEADER};
@@ -1,11 +1,75 @@
+//! Request ids, taken from `X-Request-Id` or generated
+//!
+//! [`RequestIdLayer`] (installed by [`App::auto_configure`](crate::App::auto_configure)
+//! or [`App::with_request_ids`](crate::App::with_request_ids)) runs each
+//! request in a `request` span carrying the id, echoes it in the response's
+//! `X-Request-Id` header and adds it to error bodies as `request_id`.
+//! Handlers get it with the [`RequestId`] extractor:
+//!
+//! ```rust,ignore
+//! async fn create_order(request_id: RequestId, Json(order): Json<NewOrder>) -> ApiResult<Order> {
+//!     tracing::info!(%request_id, "Creating order");
+//!     ...
+//! }
+//! ```
+//!
+//! The id is also readable anywhere in the request's task with
+//! [`RequestId::current`], but not from tasks it spawns.
+
 use axum::{
-    extract::Request,
-    http::{header, HeaderValue},
-    middleware::Next,
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderValue},
     response::Response,
 };
+use std::fmt;
 use tower::{Layer, Service};
-use uuid::Uuid;
+use tracing::Instrument;
+
+use crate::error::ApiError;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// The id of the request being handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The id of the request this task is handling, if any
+    pub fn current() -> Option<RequestId> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestId
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .ok_or_else(|| ApiError::InternalServerError("RequestIdLayer is not installed".to_string()))
+    }
+}
 
 /// Layer that adds request IDs to all requests
 #[derive(Clone)]
@@ -58,27 +122,62 @@ where
         // Generate or extract request ID
         let request_id = req
             .headers()
-            .get("x-request-id")
+            .get(REQUEST_ID_HEADER)
             .and_then(|v| v.to_str().ok())
+            .filter(|id| !id.is_empty())
             .map(|s| s.to_string())
-            .unwrap_or_else(|| Uuid::new_v4().to_string());
+            .unwrap_or_else(crate::ids::new_id);
+        let header_value = HeaderValue::from_str(&request_id).ok();
 
-        // Store in extensions for handlers to access
-        req.extensions_mut().insert(request_id.clone());
+        // Store in extensions for handlers, and the header for inner layers
+        if let Some(header_value) = &header_value {
+            req.headers_mut().insert(REQUEST_ID_HEADER, header_value.clone());
+        }
+        req.extensions_mut().insert(RequestId(request_id.clone()));
 
-        let future = self.inner.call(req);
+        let span = tracing::info_span!("request", request_id = %request_id);
+        let future = CURRENT.scope(RequestId(request_id), self.inner.call(req).instrument(span));
 
         Box::pin(async move {
             let mut response = future.await?;
-            
+
             // Add request ID to response headers
-            if let Ok(header_value) = HeaderValue::from_str(&request_id) {
-                response
-                    .headers_mut()
-                    .insert("x-request-id", header_value);
+            if let Some(header_value) = header_value {
+                response.headers_mut().insert(REQUEST_ID_HEADER, header_value);
             }
 
             Ok(response)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::{body::Body, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_request_id_propagation() {
+        let router = App::new()
+            .route("/id", get(|id: RequestId| async move { format!("{}|{:?}", id, RequestId::current()) }))
+            .route("/fail", get(|| async { Err::<(), _>(ApiError::NotFound("nope".to_string())) }))
+            .with_request_ids()
+            .into_router();
+
+        let request = Request::get("/id").header(REQUEST_ID_HEADER, "req-42").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-42");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "req-42|Some(RequestId(\"req-42\"))");
+
+        let response = router.oneshot(Request::get("/fail").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["request_id"], generated.as_str());
+        assert!(RequestId::current().is_none());
+    }
+}
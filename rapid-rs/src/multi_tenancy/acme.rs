@@ -0,0 +1,278 @@
+//! Automatic TLS certificates for tenant custom domains
+//!
+//! Each custom domain gets its own Let's Encrypt certificate, issued and
+//! renewed over the `tls-alpn-01` challenge on the HTTPS port. The set of
+//! domains is re-read from the [`TenantResolver`] periodically, so newly added
+//! tenant domains are picked up without a restart.
+//!
+//! ```rust,ignore
+//! let resolver = Arc::new(PostgresTenantResolver::new(pool));
+//! let certificates = TenantCertificates::new(resolver.clone(), AcmeSettings::new()
+//!     .with_contact("ops@example.com")
+//!     .with_cache_dir("./acme-cache")
+//!     .production());
+//!
+//! App::new()
+//!     .auto_configure()
+//!     .with_tenant_certificates(certificates)
+//!     .run()
+//!     .await?;
+//! ```
+
+use futures::StreamExt;
+use rustls_acme::{caches::DirCache, AcmeConfig, ResolvesServerCertAcme};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+
+use super::TenantResolver;
+use crate::error::ApiError;
+
+/// ACME account and issuance settings
+#[derive(Debug, Clone)]
+pub struct AcmeSettings {
+    /// Contact addresses for the ACME account (`mailto:` is added if missing)
+    pub contacts: Vec<String>,
+    /// Directory for account keys and certificates; without it every restart re-issues
+    pub cache_dir: Option<PathBuf>,
+    /// Use the Let's Encrypt production directory instead of staging
+    pub production: bool,
+    /// Domains served in addition to tenant custom domains
+    pub domains: Vec<String>,
+    /// How often the tenant domain list is refreshed
+    pub refresh_interval: Duration,
+    /// Port of the HTTPS listener
+    pub https_port: u16,
+}
+
+impl AcmeSettings {
+    /// Staging directory, no cache, refreshed every 5 minutes on port 443
+    pub fn new() -> Self {
+        Self {
+            contacts: Vec::new(),
+            cache_dir: None,
+            production: false,
+            domains: Vec::new(),
+            refresh_interval: Duration::from_secs(300),
+            https_port: 443,
+        }
+    }
+
+    pub fn with_contact(mut self, contact: impl Into<String>) -> Self {
+        let contact = contact.into();
+        self.contacts.push(if contact.contains(':') {
+            contact
+        } else {
+            format!("mailto:{}", contact)
+        });
+        self
+    }
+
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Issue trusted certificates (rate limited by Let's Encrypt)
+    pub fn production(mut self) -> Self {
+        self.production = true;
+        self
+    }
+
+    /// Also obtain a certificate for `domain` (e.g. the app's own domain)
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domains.push(domain.into());
+        self
+    }
+
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    pub fn with_https_port(mut self, port: u16) -> Self {
+        self.https_port = port;
+        self
+    }
+}
+
+impl Default for AcmeSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct DomainCertificate {
+    resolver: Arc<ResolvesServerCertAcme>,
+    task: JoinHandle<()>,
+}
+
+/// Picks the certificate for a connection by its SNI name
+#[derive(Default)]
+struct SniResolver {
+    domains: RwLock<HashMap<String, Arc<ResolvesServerCertAcme>>>,
+}
+
+impl std::fmt::Debug for SniResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SniResolver").finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let domain = client_hello.server_name()?.to_ascii_lowercase();
+        let resolver = self.domains.read().ok()?.get(&domain).cloned()?;
+        resolver.resolve(client_hello)
+    }
+}
+
+/// Issues and renews certificates for every tenant custom domain
+#[derive(Clone)]
+pub struct TenantCertificates {
+    resolver: Arc<dyn TenantResolver>,
+    settings: AcmeSettings,
+    sni: Arc<SniResolver>,
+    certificates: Arc<tokio::sync::Mutex<HashMap<String, DomainCertificate>>>,
+}
+
+impl TenantCertificates {
+    pub fn new<R: TenantResolver + 'static>(resolver: Arc<R>, settings: AcmeSettings) -> Self {
+        Self {
+            resolver,
+            settings,
+            sni: Arc::new(SniResolver::default()),
+            certificates: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn settings(&self) -> &AcmeSettings {
+        &self.settings
+    }
+
+    /// rustls config serving the managed certificates and answering challenges
+    pub fn rustls_config(&self) -> Arc<ServerConfig> {
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(self.sni.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec(), crate::tls::ACME_TLS_ALPN.to_vec()];
+        Arc::new(config)
+    }
+
+    /// Domains that should currently have certificates
+    pub async fn desired_domains(&self) -> Result<BTreeSet<String>, ApiError> {
+        let tenant_domains = self.resolver.list_custom_domains().await?;
+
+        Ok(self
+            .settings
+            .domains
+            .iter()
+            .chain(tenant_domains.iter())
+            .map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect())
+    }
+
+    /// Start issuing certificates for new domains and drop removed ones
+    pub async fn refresh(&self) -> Result<(), ApiError> {
+        let desired = self.desired_domains().await?;
+        let mut certificates = self.certificates.lock().await;
+
+        certificates.retain(|domain, certificate| {
+            let keep = desired.contains(domain);
+            if !keep {
+                tracing::info!(domain = %domain, "Removing certificate for custom domain");
+                certificate.task.abort();
+            }
+            keep
+        });
+
+        for domain in desired {
+            if !certificates.contains_key(&domain) {
+                tracing::info!(domain = %domain, "Managing certificate for custom domain");
+                certificates.insert(domain.clone(), self.start_domain(&domain));
+            }
+        }
+
+        let resolvers = certificates
+            .iter()
+            .map(|(domain, certificate)| (domain.clone(), certificate.resolver.clone()))
+            .collect();
+        if let Ok(mut domains) = self.sni.domains.write() {
+            *domains = resolvers;
+        }
+
+        Ok(())
+    }
+
+    fn start_domain(&self, domain: &str) -> DomainCertificate {
+        let mut state = AcmeConfig::new([domain])
+            .contact(&self.settings.contacts)
+            .cache_option(self.settings.cache_dir.clone().map(DirCache::new))
+            .directory_lets_encrypt(self.settings.production)
+            .state();
+        let resolver = state.resolver();
+
+        // Polling the state drives issuance and renewal
+        let domain = domain.to_string();
+        let task = tokio::spawn(async move {
+            while let Some(event) = state.next().await {
+                match event {
+                    Ok(ok) => tracing::info!(domain = %domain, event = ?ok, "ACME certificate event"),
+                    Err(err) => tracing::warn!(domain = %domain, error = %err, "ACME certificate error"),
+                }
+            }
+        });
+
+        DomainCertificate { resolver, task }
+    }
+
+    /// Refresh now and then every `refresh_interval` in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let certificates = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(certificates.settings.refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = certificates.refresh().await {
+                    tracing::error!(error = %e, "Failed to refresh tenant custom domains");
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multi_tenancy::{InMemoryTenantResolver, TenantConfig, TenantId};
+
+    #[tokio::test]
+    async fn test_desired_domains() {
+        let resolver = InMemoryTenantResolver::new();
+        resolver
+            .add_tenant(TenantConfig::new(TenantId::new("acme"), "Acme".to_string()).with_custom_domain("App.Acme.com"))
+            .await
+            .unwrap();
+
+        let mut inactive = TenantConfig::new(TenantId::new("old"), "Old".to_string()).with_custom_domain("old.com");
+        inactive.is_active = false;
+        resolver.add_tenant(inactive).await.unwrap();
+
+        let settings = AcmeSettings::new()
+            .with_contact("ops@example.com")
+            .with_domain("example.com.");
+        assert_eq!(settings.contacts, vec!["mailto:ops@example.com".to_string()]);
+
+        let certificates = TenantCertificates::new(Arc::new(resolver), settings);
+        let domains: Vec<String> = certificates.desired_domains().await.unwrap().into_iter().collect();
+        assert_eq!(domains, vec!["app.acme.com".to_string(), "example.com".to_string()]);
+    }
+}
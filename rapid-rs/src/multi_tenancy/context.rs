@@ -6,6 +6,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use super::{TenantId, TenantConfig};
+#[cfg(feature = "database")]
+use super::{TenantLimits, TenantPlan};
 use crate::error::ApiError;
 
 /// Tenant information in request context
@@ -54,6 +56,10 @@ impl TenantContext {
     pub fn get_metadata(&self, key: &str) -> Option<&String> {
         self.info.metadata.get(key)
     }
+    
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.info.metadata
+    }
 }
 
 /// Trait for resolving tenant from request
@@ -65,6 +71,16 @@ pub trait TenantResolver: Send + Sync {
     /// Resolve tenant ID from header
     async fn resolve_from_header(&self, header_value: &str) -> Result<TenantId, ApiError>;
     
+    /// Resolve tenant ID from a custom domain (e.g. `api.acme.com`)
+    async fn resolve_from_domain(&self, domain: &str) -> Result<TenantId, ApiError> {
+        Err(ApiError::NotFound(format!("Tenant not found for domain: {}", domain)))
+    }
+    
+    /// All custom domains served by active tenants
+    async fn list_custom_domains(&self) -> Result<Vec<String>, ApiError> {
+        Ok(Vec::new())
+    }
+    
     /// Get tenant configuration
     async fn get_tenant_config(&self, tenant_id: &TenantId) -> Result<TenantConfig, ApiError>;
 }
@@ -73,6 +89,7 @@ pub trait TenantResolver: Send + Sync {
 pub struct InMemoryTenantResolver {
     tenants: Arc<RwLock<HashMap<TenantId, TenantConfig>>>,
     subdomain_map: Arc<RwLock<HashMap<String, TenantId>>>,
+    domain_map: Arc<RwLock<HashMap<String, TenantId>>>,
 }
 
 impl InMemoryTenantResolver {
@@ -80,6 +97,7 @@ impl InMemoryTenantResolver {
         Self {
             tenants: Arc::new(RwLock::new(HashMap::new())),
             subdomain_map: Arc::new(RwLock::new(HashMap::new())),
+            domain_map: Arc::new(RwLock::new(HashMap::new())),
         }
     }
     
@@ -93,6 +111,11 @@ impl InMemoryTenantResolver {
             subdomain_map.insert(subdomain.clone(), config.id.clone());
         }
         
+        let mut domain_map = self.domain_map.write().await;
+        for domain in &config.custom_domains {
+            domain_map.insert(domain.to_ascii_lowercase(), config.id.clone());
+        }
+        
         tenants.insert(config.id.clone(), config);
         
         Ok(())
@@ -108,6 +131,11 @@ impl InMemoryTenantResolver {
                 let mut subdomain_map = self.subdomain_map.write().await;
                 subdomain_map.remove(&subdomain);
             }
+            
+            let mut domain_map = self.domain_map.write().await;
+            for domain in &config.custom_domains {
+                domain_map.remove(&domain.to_ascii_lowercase());
+            }
         }
         
         Ok(())
@@ -142,6 +170,26 @@ impl TenantResolver for InMemoryTenantResolver {
         Ok(TenantId::new(header_value))
     }
     
+    async fn resolve_from_domain(&self, domain: &str) -> Result<TenantId, ApiError> {
+        let domain_map = self.domain_map.read().await;
+        
+        domain_map
+            .get(&domain.to_ascii_lowercase())
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("Tenant not found for domain: {}", domain)))
+    }
+    
+    async fn list_custom_domains(&self) -> Result<Vec<String>, ApiError> {
+        let tenants = self.tenants.read().await;
+        let domain_map = self.domain_map.read().await;
+        
+        Ok(domain_map
+            .iter()
+            .filter(|(_, tenant_id)| tenants.get(*tenant_id).is_some_and(|tenant| tenant.is_active))
+            .map(|(domain, _)| domain.clone())
+            .collect())
+    }
+    
     async fn get_tenant_config(&self, tenant_id: &TenantId) -> Result<TenantConfig, ApiError> {
         let tenants = self.tenants.read().await;
         
@@ -179,8 +227,13 @@ impl PostgresTenantResolver {
                 is_active BOOLEAN NOT NULL DEFAULT TRUE
             );
             
+            ALTER TABLE tenants ADD COLUMN IF NOT EXISTS plan TEXT NOT NULL DEFAULT 'Free';
+            ALTER TABLE tenants ADD COLUMN IF NOT EXISTS limits JSONB;
+            ALTER TABLE tenants ADD COLUMN IF NOT EXISTS custom_domains JSONB NOT NULL DEFAULT '[]';
+            
             CREATE INDEX IF NOT EXISTS idx_tenants_subdomain ON tenants(subdomain);
             CREATE INDEX IF NOT EXISTS idx_tenants_active ON tenants(is_active);
+            CREATE INDEX IF NOT EXISTS idx_tenants_custom_domains ON tenants USING GIN (custom_domains);
             "#,
         )
         .execute(&self.pool)
@@ -188,6 +241,18 @@ impl PostgresTenantResolver {
         
         Ok(())
     }
+    
+    /// List all active tenants
+    pub async fn list_tenants(&self) -> Result<Vec<TenantConfig>, ApiError> {
+        let rows = sqlx::query_as::<_, TenantRow>(&format!(
+            "SELECT {} FROM tenants WHERE is_active = TRUE ORDER BY id",
+            TENANT_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        
+        rows.into_iter().map(tenant_from_row).collect()
+    }
 }
 
 #[cfg(feature = "database")]
@@ -209,34 +274,80 @@ impl TenantResolver for PostgresTenantResolver {
         Ok(TenantId::new(header_value))
     }
     
-    async fn get_tenant_config(&self, tenant_id: &TenantId) -> Result<TenantConfig, ApiError> {
-        let row = sqlx::query_as::<_, (String, String, Option<String>, Option<String>, serde_json::Value, serde_json::Value, chrono::DateTime<chrono::Utc>, bool)>(
-            "SELECT id, name, subdomain, database_url, features, metadata, created_at, is_active FROM tenants WHERE id = $1"
+    async fn resolve_from_domain(&self, domain: &str) -> Result<TenantId, ApiError> {
+        let row = sqlx::query_as::<_, (String,)>(
+            "SELECT id FROM tenants WHERE custom_domains ? $1 AND is_active = TRUE"
         )
-        .bind(tenant_id.as_str())
+        .bind(domain.to_ascii_lowercase())
         .fetch_optional(&self.pool)
         .await?
-        .ok_or_else(|| ApiError::NotFound(format!("Tenant not found: {}", tenant_id)))?;
+        .ok_or_else(|| ApiError::NotFound(format!("Tenant not found for domain: {}", domain)))?;
         
-        let features: Vec<String> = serde_json::from_value(row.4)
-            .map_err(|e| ApiError::InternalServerError(format!("Failed to parse features: {}", e)))?;
+        Ok(TenantId::new(row.0))
+    }
+    
+    async fn list_custom_domains(&self) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query_as::<_, (String,)>(
+            "SELECT DISTINCT jsonb_array_elements_text(custom_domains) FROM tenants WHERE is_active = TRUE"
+        )
+        .fetch_all(&self.pool)
+        .await?;
         
-        let metadata: HashMap<String, String> = serde_json::from_value(row.5)
-            .map_err(|e| ApiError::InternalServerError(format!("Failed to parse metadata: {}", e)))?;
+        Ok(rows.into_iter().map(|row| row.0).collect())
+    }
+    
+    async fn get_tenant_config(&self, tenant_id: &TenantId) -> Result<TenantConfig, ApiError> {
+        let row = sqlx::query_as::<_, TenantRow>(&format!("SELECT {} FROM tenants WHERE id = $1", TENANT_COLUMNS))
+            .bind(tenant_id.as_str())
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound(format!("Tenant not found: {}", tenant_id)))?;
         
-        Ok(TenantConfig {
-            id: TenantId::new(row.0),
-            name: row.1,
-            subdomain: row.2,
-            database_url: row.3,
-            features,
-            metadata,
-            created_at: row.6,
-            is_active: row.7,
-        })
+        tenant_from_row(row)
     }
 }
 
+#[cfg(feature = "database")]
+type TenantRow = (String, String, Option<String>, Option<String>, serde_json::Value, serde_json::Value, chrono::DateTime<chrono::Utc>, bool, String, Option<serde_json::Value>, serde_json::Value);
+
+#[cfg(feature = "database")]
+const TENANT_COLUMNS: &str = "id, name, subdomain, database_url, features, metadata, created_at, is_active, plan, limits, custom_domains";
+
+#[cfg(feature = "database")]
+fn tenant_from_row(row: TenantRow) -> Result<TenantConfig, ApiError> {
+    let features: Vec<String> = serde_json::from_value(row.4)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to parse features: {}", e)))?;
+    
+    let metadata: HashMap<String, String> = serde_json::from_value(row.5)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to parse metadata: {}", e)))?;
+    
+    let plan: TenantPlan = serde_json::from_value(serde_json::Value::String(row.8))
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to parse plan: {}", e)))?;
+    
+    let limits = match row.9 {
+        Some(limits) => serde_json::from_value(limits)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to parse limits: {}", e)))?,
+        None => TenantLimits::for_plan(plan),
+    };
+    
+    let custom_domains: Vec<String> = serde_json::from_value(row.10)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to parse custom domains: {}", e)))?;
+    
+    Ok(TenantConfig {
+        id: TenantId::new(row.0),
+        name: row.1,
+        subdomain: row.2,
+        database_url: row.3,
+        features,
+        metadata,
+        created_at: row.6,
+        is_active: row.7,
+        plan,
+        limits,
+        custom_domains,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
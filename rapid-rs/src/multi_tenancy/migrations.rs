@@ -0,0 +1,321 @@
+//! Running schema migrations across tenants
+//!
+//! Supports both schema-per-tenant (one Postgres schema per tenant in a shared
+//! database) and database-per-tenant (`TenantConfig::database_url`) layouts.
+//! Each tenant is migrated independently, so one failure does not stop the
+//! others.
+//!
+//! ```rust,ignore
+//! let migrator = TenantMigrator::new(MigrationConfig::new())
+//!     .schema_per_tenant(pool.clone())
+//!     .with_concurrency(8)
+//!     .on_progress(|p| println!("[{}/{}] {} {:?}", p.completed, p.total, p.tenant_id, p.status));
+//!
+//! let report = migrator.run(&resolver.list_tenants().await?).await;
+//! if !report.is_success() { /* ... */ }
+//! ```
+
+use serde::Serialize;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use super::{TenantConfig, TenantId};
+use crate::database::MigrationConfig;
+use crate::error::ApiError;
+
+/// Where each tenant's tables live
+#[derive(Clone)]
+pub enum TenantMigrationTarget {
+    /// One schema per tenant in a shared database, named `{prefix}{tenant_id}`
+    Schema { pool: PgPool, prefix: String },
+    /// Each tenant's own database from `TenantConfig::database_url`
+    Database,
+}
+
+/// Migration state of a single tenant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum TenantMigrationStatus {
+    Started,
+    Succeeded,
+    Skipped(String),
+    Failed(String),
+}
+
+/// Progress update passed to [`TenantMigrator::on_progress`]
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantMigrationProgress {
+    pub tenant_id: TenantId,
+    pub status: TenantMigrationStatus,
+    /// Tenants finished so far, including this one if it just finished
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Outcome of a run across tenants
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TenantMigrationReport {
+    pub succeeded: Vec<TenantId>,
+    pub skipped: Vec<(TenantId, String)>,
+    pub failed: Vec<(TenantId, String)>,
+}
+
+impl TenantMigrationReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+type ProgressCallback = Arc<dyn Fn(&TenantMigrationProgress) + Send + Sync>;
+
+/// Runs migrations for many tenants with bounded concurrency
+#[derive(Clone)]
+pub struct TenantMigrator {
+    config: MigrationConfig,
+    target: TenantMigrationTarget,
+    concurrency: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl TenantMigrator {
+    /// Database-per-tenant migrator running 4 tenants at a time
+    pub fn new(config: MigrationConfig) -> Self {
+        Self {
+            config,
+            target: TenantMigrationTarget::Database,
+            concurrency: 4,
+            progress: None,
+        }
+    }
+
+    /// Migrate `tenant_{id}` schemas in the shared database behind `pool`
+    pub fn schema_per_tenant(mut self, pool: PgPool) -> Self {
+        self.target = TenantMigrationTarget::Schema {
+            pool,
+            prefix: "tenant_".to_string(),
+        };
+        self
+    }
+
+    pub fn with_target(mut self, target: TenantMigrationTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Maximum tenants migrated at the same time
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Called whenever a tenant starts or finishes
+    pub fn on_progress(mut self, callback: impl Fn(&TenantMigrationProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Schema name used for a tenant
+    ///
+    /// Hyphens become `$` so that `acme-corp` and `acme_corp` get different
+    /// schemas. Uppercase ids are rejected, since Postgres folds unquoted
+    /// names to lowercase, as are names over Postgres' 63-byte limit.
+    pub fn schema_name(prefix: &str, tenant_id: &TenantId) -> Result<String, ApiError> {
+        let id = tenant_id.as_str();
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-') {
+            return Err(ApiError::BadRequest(format!(
+                "Tenant id '{}' cannot be used as a schema name",
+                id
+            )));
+        }
+        let schema = format!("{}{}", prefix, id.replace('-', "$"));
+        if schema.len() > 63 {
+            return Err(ApiError::BadRequest(format!(
+                "Schema name for tenant '{}' is longer than 63 bytes",
+                id
+            )));
+        }
+        Ok(schema)
+    }
+
+    /// Migrate the given tenants
+    pub async fn run(&self, tenants: &[TenantConfig]) -> TenantMigrationReport {
+        let total = tenants.len();
+        let completed = Arc::new(AtomicUsize::new(0));
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let mut tasks = tokio::task::JoinSet::new();
+
+        for tenant in tenants.iter().cloned() {
+            let migrator = self.clone();
+            let completed = completed.clone();
+            let semaphore = semaphore.clone();
+
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                migrator.report(&tenant.id, TenantMigrationStatus::Started, completed.load(Ordering::Relaxed), total);
+
+                let status = migrator.migrate_tenant(&tenant).await;
+                let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                migrator.report(&tenant.id, status.clone(), done, total);
+
+                (tenant.id, status)
+            });
+        }
+
+        let mut report = TenantMigrationReport::default();
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((tenant_id, TenantMigrationStatus::Succeeded)) => report.succeeded.push(tenant_id),
+                Ok((tenant_id, TenantMigrationStatus::Skipped(reason))) => report.skipped.push((tenant_id, reason)),
+                Ok((tenant_id, TenantMigrationStatus::Failed(error))) => report.failed.push((tenant_id, error)),
+                Ok((_, TenantMigrationStatus::Started)) => {}
+                Err(e) => tracing::error!(error = %e, "Tenant migration task panicked"),
+            }
+        }
+
+        tracing::info!(
+            succeeded = report.succeeded.len(),
+            skipped = report.skipped.len(),
+            failed = report.failed.len(),
+            "Tenant migrations finished"
+        );
+
+        report
+    }
+
+    /// Migrate only tenants whose ID is in `ids`
+    pub async fn run_subset(&self, tenants: &[TenantConfig], ids: &[TenantId]) -> TenantMigrationReport {
+        let selected: Vec<TenantConfig> = tenants
+            .iter()
+            .filter(|tenant| ids.contains(&tenant.id))
+            .cloned()
+            .collect();
+        self.run(&selected).await
+    }
+
+    fn report(&self, tenant_id: &TenantId, status: TenantMigrationStatus, completed: usize, total: usize) {
+        match &status {
+            TenantMigrationStatus::Failed(error) => {
+                tracing::error!(tenant_id = %tenant_id, error = %error, "Tenant migration failed")
+            }
+            status => tracing::info!(tenant_id = %tenant_id, status = ?status, "Tenant migration"),
+        }
+
+        if let Some(callback) = &self.progress {
+            callback(&TenantMigrationProgress {
+                tenant_id: tenant_id.clone(),
+                status,
+                completed,
+                total,
+            });
+        }
+    }
+
+    async fn migrate_tenant(&self, tenant: &TenantConfig) -> TenantMigrationStatus {
+        let result = match &self.target {
+            TenantMigrationTarget::Schema { pool, prefix } => self.migrate_schema(pool, prefix, &tenant.id).await,
+            TenantMigrationTarget::Database => match &tenant.database_url {
+                Some(url) => self.migrate_database(url).await,
+                None => return TenantMigrationStatus::Skipped("no database_url configured".to_string()),
+            },
+        };
+
+        match result {
+            Ok(()) => TenantMigrationStatus::Succeeded,
+            Err(e) => TenantMigrationStatus::Failed(e.to_string()),
+        }
+    }
+
+    async fn load_migrator(&self) -> Result<sqlx::migrate::Migrator, ApiError> {
+        sqlx::migrate::Migrator::new(Path::new(&self.config.migrations_path))
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to load migrations: {}", e)))
+    }
+
+    async fn migrate_schema(&self, pool: &PgPool, prefix: &str, tenant_id: &TenantId) -> Result<(), ApiError> {
+        let schema = Self::schema_name(prefix, tenant_id)?;
+        let migrator = self.load_migrator().await?;
+
+        sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS \"{}\"", schema))
+            .execute(pool)
+            .await?;
+
+        // Dedicated connection whose search_path points at the tenant schema,
+        // so the shared pool never sees a tenant-specific session
+        let options = pool.connect_options().as_ref().clone().options([("search_path", schema.as_str())]);
+        let tenant_pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        let result = migrator
+            .run(&tenant_pool)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Migration failed: {}", e)));
+        tenant_pool.close().await;
+
+        result
+    }
+
+    async fn migrate_database(&self, database_url: &str) -> Result<(), ApiError> {
+        if self.config.create_db_if_missing {
+            crate::database::ensure_database_exists(database_url).await?;
+        }
+
+        let migrator = self.load_migrator().await?;
+        let pool = PgPool::connect(database_url)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to connect to tenant database: {}", e)))?;
+
+        let result = migrator
+            .run(&pool)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Migration failed: {}", e)));
+        pool.close().await;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_schema_name() {
+        assert_eq!(
+            TenantMigrator::schema_name("tenant_", &TenantId::new("acme-corp")).unwrap(),
+            "tenant_acme$corp"
+        );
+        assert_eq!(
+            TenantMigrator::schema_name("tenant_", &TenantId::new("acme_corp")).unwrap(),
+            "tenant_acme_corp"
+        );
+        assert!(TenantMigrator::schema_name("tenant_", &TenantId::new("Acme")).is_err());
+        assert!(TenantMigrator::schema_name("tenant_", &TenantId::new("a".repeat(60))).is_err());
+        assert!(TenantMigrator::schema_name("tenant_", &TenantId::new("a\"; DROP")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tenants_without_database_are_skipped() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let migrator = TenantMigrator::new(MigrationConfig::new())
+            .with_concurrency(2)
+            .on_progress(move |progress| seen.lock().unwrap().push(progress.status.clone()));
+
+        let tenants = vec![
+            TenantConfig::new(TenantId::new("a"), "A".to_string()),
+            TenantConfig::new(TenantId::new("b"), "B".to_string()),
+        ];
+        let report = migrator.run_subset(&tenants, &[TenantId::new("a")]).await;
+
+        assert!(report.is_success());
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(events.lock().unwrap().len(), 2);
+    }
+}
@@ -28,7 +28,9 @@
 //!     resolver.add_tenant(tenant).await.unwrap();
 //!     
 //!     // Setup middleware
-//!     let config = Arc::new(TenantMiddlewareConfig::new(resolver));
+//!     let config = Arc::new(
+//!         TenantMiddlewareConfig::new(resolver).with_base_domain("example.com")
+//!     );
 //!     
 //!     let app = Router::new()
 //!         .route("/", get(my_handler))
@@ -48,13 +50,29 @@
 
 pub mod context;
 pub mod middleware;
+pub mod usage;
+pub mod settings;
+
+#[cfg(feature = "database")]
+pub mod migrations;
+
+#[cfg(feature = "acme")]
+pub mod acme;
 
 pub use context::{TenantContext, TenantInfo, TenantResolver, InMemoryTenantResolver};
 pub use middleware::{tenant_middleware, TenantExtractor, TenantMiddlewareConfig};
+pub use settings::{TenantDefaults, TenantSettings, TenantSettingsSection};
+pub use usage::{tenant_usage_routes, TenantUsageReport, TenantUsageTracker, UsageBucket, UsageTotals};
 
 #[cfg(feature = "database")]
 pub use context::PostgresTenantResolver;
 
+#[cfg(feature = "acme")]
+pub use acme::{AcmeSettings, TenantCertificates};
+
+#[cfg(feature = "database")]
+pub use migrations::{TenantMigrationProgress, TenantMigrationReport, TenantMigrationStatus, TenantMigrationTarget, TenantMigrator};
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -70,6 +88,11 @@ impl TenantId {
         Self(id.into())
     }
     
+    /// Generate a fresh ID with the app's [`IdGenerator`](crate::ids::IdGenerator)
+    pub fn generate() -> Self {
+        Self(crate::ids::new_id())
+    }
+    
     /// Get the tenant ID as a string slice
     pub fn as_str(&self) -> &str {
         &self.0
@@ -114,6 +137,10 @@ pub struct TenantConfig {
     /// Subdomain for this tenant (e.g., "acme" for acme.example.com)
     pub subdomain: Option<String>,
     
+    /// Custom domains served for this tenant (e.g., "app.acme.com")
+    #[serde(default)]
+    pub custom_domains: Vec<String>,
+    
     /// Optional dedicated database URL for this tenant
     pub database_url: Option<String>,
     
@@ -128,6 +155,14 @@ pub struct TenantConfig {
     
     /// Whether the tenant is currently active
     pub is_active: bool,
+    
+    /// Subscription plan
+    #[serde(default)]
+    pub plan: TenantPlan,
+    
+    /// Resource limits, defaulting to the plan's limits
+    #[serde(default)]
+    pub limits: TenantLimits,
 }
 
 impl TenantConfig {
@@ -137,11 +172,14 @@ impl TenantConfig {
             id,
             name,
             subdomain: None,
+            custom_domains: Vec::new(),
             database_url: None,
             features: Vec::new(),
             metadata: std::collections::HashMap::new(),
             created_at: chrono::Utc::now(),
             is_active: true,
+            plan: TenantPlan::default(),
+            limits: TenantLimits::default(),
         }
     }
     
@@ -151,6 +189,12 @@ impl TenantConfig {
         self
     }
     
+    /// Serve this tenant on a custom domain
+    pub fn with_custom_domain(mut self, domain: impl Into<String>) -> Self {
+        self.custom_domains.push(domain.into().to_ascii_lowercase());
+        self
+    }
+    
     /// Set a dedicated database URL for this tenant
     pub fn with_database(mut self, url: String) -> Self {
         self.database_url = Some(url);
@@ -169,6 +213,19 @@ impl TenantConfig {
         self
     }
     
+    /// Set the plan, applying its default limits
+    pub fn with_plan(mut self, plan: TenantPlan) -> Self {
+        self.plan = plan;
+        self.limits = TenantLimits::for_plan(plan);
+        self
+    }
+    
+    /// Override the resource limits
+    pub fn with_limits(mut self, limits: TenantLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+    
     /// Check if a feature is enabled for this tenant
     pub fn has_feature(&self, feature: &str) -> bool {
         self.features.iter().any(|f| f == feature)
@@ -184,28 +241,24 @@ impl TenantConfig {
 /// Tenant isolation strategy
 ///
 /// Defines how data is isolated between tenants.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum IsolationStrategy {
     /// Each tenant has its own separate database
     Database,
     
     /// All tenants share a database with tenant_id column for filtering
+    #[default]
     Schema,
     
     /// Hybrid approach combining database and schema isolation
     Hybrid,
 }
 
-impl Default for IsolationStrategy {
-    fn default() -> Self {
-        Self::Schema
-    }
-}
-
 /// Tenant plan/tier for subscription management
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TenantPlan {
     /// Free tier with limited features
+    #[default]
     Free,
     
     /// Basic paid plan
@@ -221,12 +274,6 @@ pub enum TenantPlan {
     Custom,
 }
 
-impl Default for TenantPlan {
-    fn default() -> Self {
-        Self::Free
-    }
-}
-
 /// Tenant limits for resource management
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TenantLimits {
@@ -0,0 +1,225 @@
+//! Typed per-tenant configuration overrides
+//!
+//! A settings section is a plain struct whose defaults come from
+//! `[tenant_defaults.<section>]` in the app config. Individual fields can be
+//! overridden per tenant with `TenantConfig::metadata` entries named
+//! `<section>.<field>`.
+//!
+//! ```rust,ignore
+//! #[derive(Clone, Default, Serialize, Deserialize)]
+//! struct Branding {
+//!     logo_url: String,
+//!     max_upload_mb: u32,
+//! }
+//!
+//! impl TenantSettingsSection for Branding {
+//!     const SECTION: &'static str = "branding";
+//! }
+//!
+//! // tenant metadata: {"branding.logo_url": "https://acme.test/logo.png"}
+//! async fn logo(TenantSettings(branding): TenantSettings<Branding>) -> String {
+//!     branding.logo_url
+//! }
+//!
+//! let defaults = TenantDefaults::<Branding>::from_app_config(&AppConfig::load()?)?;
+//! router.layer(axum::Extension(defaults));
+//! ```
+
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+use super::TenantContext;
+use crate::config::AppConfig;
+use crate::error::ApiError;
+
+/// A group of settings that tenants may override
+pub trait TenantSettingsSection: Serialize + DeserializeOwned + Default + Clone + Send + Sync + 'static {
+    /// Config section name and metadata key prefix
+    const SECTION: &'static str;
+}
+
+/// App-wide defaults for a settings section
+#[derive(Debug, Clone)]
+pub struct TenantDefaults<T>(pub T);
+
+impl<T: TenantSettingsSection> TenantDefaults<T> {
+    /// Read `[tenant_defaults.<section>]`, falling back to `T::default()` per field
+    pub fn from_app_config(config: &AppConfig) -> Result<Self, ApiError> {
+        let mut value = to_object::<T>(&T::default())?;
+
+        if let Some(serde_json::Value::Object(section)) = config.tenant_defaults.get(T::SECTION) {
+            for (field, configured) in section {
+                if value.contains_key(field) {
+                    value.insert(field.clone(), configured.clone());
+                }
+            }
+        }
+
+        from_object(value, T::SECTION).map(Self)
+    }
+}
+
+/// Resolved settings for the current tenant
+///
+/// As an extractor, uses the request's [`TenantContext`] (if any) and the
+/// [`TenantDefaults<T>`] extension (or `T::default()`).
+#[derive(Debug, Clone)]
+pub struct TenantSettings<T>(pub T);
+
+impl<T: TenantSettingsSection> TenantSettings<T> {
+    /// Apply `<section>.<field>` metadata overrides on top of `defaults`
+    ///
+    /// Only fields that exist on `T` can be overridden; other keys are
+    /// ignored. Values are parsed as JSON when the field is not a string, so
+    /// `"branding.max_upload_mb": "50"` sets a number; a field that defaults
+    /// to `null` takes the raw text when it isn't valid JSON. An override
+    /// that doesn't fit its field is logged and skipped.
+    pub fn resolve(defaults: &T, metadata: &HashMap<String, String>) -> Result<T, ApiError> {
+        let mut value = to_object(defaults)?;
+        let prefix = format!("{}.", T::SECTION);
+
+        for (key, raw) in metadata {
+            let Some(field) = key.strip_prefix(&prefix) else {
+                continue;
+            };
+            let Some(current) = value.get(field) else {
+                tracing::warn!(key = %key, "Ignoring tenant override for unknown setting");
+                continue;
+            };
+
+            let previous = current.clone();
+            let parsed = match current {
+                serde_json::Value::String(_) => Ok(serde_json::Value::String(raw.clone())),
+                serde_json::Value::Null => {
+                    Ok(serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.clone())))
+                }
+                _ => serde_json::from_str(raw),
+            };
+            let error = match parsed {
+                Ok(parsed) => {
+                    value.insert(field.to_string(), parsed);
+                    from_object::<T>(value.clone(), T::SECTION).err().map(|e| e.to_string())
+                }
+                Err(e) => Some(e.to_string()),
+            };
+            if let Some(error) = error {
+                tracing::warn!(key = %key, error = %error, "Ignoring invalid tenant override");
+                value.insert(field.to_string(), previous);
+            }
+        }
+
+        from_object(value, T::SECTION)
+    }
+}
+
+fn to_object<T: Serialize>(settings: &T) -> Result<serde_json::Map<String, serde_json::Value>, ApiError> {
+    match serde_json::to_value(settings) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err(ApiError::InternalServerError(
+            "Tenant settings must serialize to an object".to_string(),
+        )),
+        Err(e) => Err(ApiError::InternalServerError(format!("Failed to serialize settings: {}", e))),
+    }
+}
+
+fn from_object<T: DeserializeOwned>(
+    value: serde_json::Map<String, serde_json::Value>,
+    section: &str,
+) -> Result<T, ApiError> {
+    serde_json::from_value(serde_json::Value::Object(value))
+        .map_err(|e| ApiError::InternalServerError(format!("Invalid '{}' settings: {}", section, e)))
+}
+
+impl TenantContext {
+    /// Settings section for this tenant, with overrides applied
+    pub fn settings<T: TenantSettingsSection>(&self, defaults: &T) -> Result<T, ApiError> {
+        TenantSettings::resolve(defaults, self.metadata())
+    }
+}
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for TenantSettings<T>
+where
+    S: Send + Sync,
+    T: TenantSettingsSection,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let defaults = parts
+            .extensions
+            .get::<TenantDefaults<T>>()
+            .map(|defaults| defaults.0.clone())
+            .unwrap_or_default();
+
+        match parts.extensions.get::<TenantContext>() {
+            Some(tenant) => tenant.settings(&defaults).map(Self),
+            None => Ok(Self(defaults)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+    struct Branding {
+        logo_url: String,
+        max_upload_mb: u32,
+        dark_mode: bool,
+        tagline: Option<String>,
+    }
+
+    impl TenantSettingsSection for Branding {
+        const SECTION: &'static str = "branding";
+    }
+
+    #[test]
+    fn test_defaults_from_app_config() {
+        let mut config = AppConfig::default();
+        config.tenant_defaults.insert(
+            "branding".to_string(),
+            serde_json::json!({"logo_url": "/logo.png", "unknown": 1}),
+        );
+
+        let TenantDefaults(defaults) = TenantDefaults::<Branding>::from_app_config(&config).unwrap();
+        assert_eq!(defaults.logo_url, "/logo.png");
+        assert_eq!(defaults.max_upload_mb, 0);
+    }
+
+    #[test]
+    fn test_resolve_overrides() {
+        let defaults = Branding {
+            logo_url: "/logo.png".to_string(),
+            max_upload_mb: 10,
+            dark_mode: false,
+            tagline: None,
+        };
+        let metadata = HashMap::from([
+            ("branding.logo_url".to_string(), "https://acme.test/logo.png".to_string()),
+            ("branding.max_upload_mb".to_string(), "50".to_string()),
+            ("branding.unknown".to_string(), "x".to_string()),
+            ("industry".to_string(), "retail".to_string()),
+        ]);
+
+        let settings = TenantSettings::resolve(&defaults, &metadata).unwrap();
+        assert_eq!(settings.logo_url, "https://acme.test/logo.png");
+        assert_eq!(settings.max_upload_mb, 50);
+        assert!(!settings.dark_mode);
+
+        // Bad overrides keep the default instead of failing the request
+        let invalid = HashMap::from([
+            ("branding.dark_mode".to_string(), "maybe".to_string()),
+            ("branding.max_upload_mb".to_string(), "\"lots\"".to_string()),
+            ("branding.tagline".to_string(), "Fresh daily".to_string()),
+        ]);
+        let settings = TenantSettings::resolve(&defaults, &invalid).unwrap();
+        assert!(!settings.dark_mode);
+        assert_eq!(settings.max_upload_mb, 10);
+        assert_eq!(settings.tagline.as_deref(), Some("Fresh daily"));
+    }
+}
@@ -0,0 +1,323 @@
+//! Per-tenant usage tracking and reporting
+//!
+//! Usage is aggregated into hourly buckets so reports can be produced for
+//! arbitrary time ranges (billing periods, support investigations).
+//!
+//! ```rust,ignore
+//! let tracker = TenantUsageTracker::new();
+//! let config = TenantMiddlewareConfig::new(resolver.clone()).with_usage_tracker(tracker.clone());
+//!
+//! App::new()
+//!     .mount(tenant_usage_routes(Arc::new(resolver), tracker))
+//!     .run()
+//!     .await?;
+//! ```
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use super::{TenantId, TenantLimits, TenantPlan, TenantResolver};
+use crate::error::ApiError;
+
+/// Usage recorded for one tenant during one hour
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageBucket {
+    pub start: DateTime<Utc>,
+    pub requests: u64,
+    pub errors: u64,
+    pub jobs: u64,
+    /// Highest concurrent websocket connections seen during the hour
+    pub peak_websocket_connections: u64,
+}
+
+/// Usage summed over a report's time range
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub errors: u64,
+    pub jobs: u64,
+    pub peak_websocket_connections: u64,
+    /// Busiest hour in the range, comparable to `max_api_requests_per_hour`
+    pub peak_requests_per_hour: u64,
+}
+
+/// Usage report returned by `GET /admin/tenants/:id/usage`
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantUsageReport {
+    pub tenant_id: TenantId,
+    pub plan: TenantPlan,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub totals: UsageTotals,
+    /// Current values, independent of the time range
+    pub storage_bytes: u64,
+    pub websocket_connections: u64,
+    pub limits: TenantLimits,
+    /// Names of limits exceeded during the range
+    pub exceeded_limits: Vec<String>,
+    pub buckets: Vec<UsageBucket>,
+}
+
+#[derive(Debug, Default)]
+struct TenantUsage {
+    buckets: BTreeMap<DateTime<Utc>, UsageBucket>,
+    storage_bytes: u64,
+    websocket_connections: u64,
+}
+
+impl TenantUsage {
+    fn bucket(&mut self, now: DateTime<Utc>) -> &mut UsageBucket {
+        let start = hour_start(now);
+        self.buckets.entry(start).or_insert_with(|| UsageBucket {
+            start,
+            ..UsageBucket::default()
+        })
+    }
+}
+
+fn hour_start(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.duration_trunc(Duration::hours(1)).unwrap_or(time)
+}
+
+/// Collects per-tenant usage in memory
+///
+/// Cloning is cheap; clones share the same counters.
+#[derive(Clone)]
+pub struct TenantUsageTracker {
+    usage: Arc<RwLock<HashMap<TenantId, TenantUsage>>>,
+    retention: Duration,
+}
+
+impl TenantUsageTracker {
+    /// Tracker keeping 90 days of hourly buckets
+    pub fn new() -> Self {
+        Self {
+            usage: Arc::new(RwLock::new(HashMap::new())),
+            retention: Duration::days(90),
+        }
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Record a handled request; called by the tenant middleware
+    pub async fn record_request(&self, tenant_id: &TenantId, is_error: bool) {
+        self.update(tenant_id, |usage, now| {
+            let bucket = usage.bucket(now);
+            bucket.requests += 1;
+            if is_error {
+                bucket.errors += 1;
+            }
+        })
+        .await;
+    }
+
+    /// Record a background job run on behalf of the tenant
+    pub async fn record_job(&self, tenant_id: &TenantId) {
+        self.update(tenant_id, |usage, now| usage.bucket(now).jobs += 1).await;
+    }
+
+    /// Set the tenant's current storage footprint
+    pub async fn set_storage_bytes(&self, tenant_id: &TenantId, bytes: u64) {
+        self.update(tenant_id, |usage, _| usage.storage_bytes = bytes).await;
+    }
+
+    /// Set the tenant's current websocket connection count
+    ///
+    /// With the `websocket` feature this is `ConnectionStore::by_tenant(..).len()`.
+    pub async fn set_websocket_connections(&self, tenant_id: &TenantId, connections: u64) {
+        self.update(tenant_id, |usage, now| {
+            usage.websocket_connections = connections;
+            let bucket = usage.bucket(now);
+            bucket.peak_websocket_connections = bucket.peak_websocket_connections.max(connections);
+        })
+        .await;
+    }
+
+    async fn update(&self, tenant_id: &TenantId, apply: impl FnOnce(&mut TenantUsage, DateTime<Utc>)) {
+        let now = Utc::now();
+        let cutoff = hour_start(now - self.retention);
+
+        let mut usage = self.usage.write().await;
+        let tenant = usage.entry(tenant_id.clone()).or_default();
+        apply(tenant, now);
+
+        // Buckets are ordered, so expired ones are always at the front
+        while let Some(entry) = tenant.buckets.first_entry() {
+            if *entry.key() >= cutoff {
+                break;
+            }
+            entry.remove();
+        }
+    }
+
+    /// Build a usage report for `[from, to)`
+    ///
+    /// An empty or reversed range has no buckets.
+    pub async fn report(
+        &self,
+        tenant_id: &TenantId,
+        plan: TenantPlan,
+        limits: TenantLimits,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> TenantUsageReport {
+        let usage = self.usage.read().await;
+        let tenant = usage.get(tenant_id);
+
+        let start = hour_start(from);
+        let buckets: Vec<UsageBucket> = tenant
+            .filter(|_| start < to)
+            .map(|tenant| {
+                tenant
+                    .buckets
+                    .range(start..to)
+                    .map(|(_, bucket)| bucket.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let totals = buckets.iter().fold(UsageTotals::default(), |mut totals, bucket| {
+            totals.requests += bucket.requests;
+            totals.errors += bucket.errors;
+            totals.jobs += bucket.jobs;
+            totals.peak_websocket_connections = totals.peak_websocket_connections.max(bucket.peak_websocket_connections);
+            totals.peak_requests_per_hour = totals.peak_requests_per_hour.max(bucket.requests);
+            totals
+        });
+
+        let storage_bytes = tenant.map_or(0, |tenant| tenant.storage_bytes);
+
+        let mut exceeded_limits = Vec::new();
+        if limits
+            .max_api_requests_per_hour
+            .is_some_and(|max| totals.peak_requests_per_hour > u64::from(max))
+        {
+            exceeded_limits.push("max_api_requests_per_hour".to_string());
+        }
+        if limits.max_storage_bytes.is_some_and(|max| storage_bytes > max) {
+            exceeded_limits.push("max_storage_bytes".to_string());
+        }
+
+        TenantUsageReport {
+            tenant_id: tenant_id.clone(),
+            plan,
+            from,
+            to,
+            totals,
+            storage_bytes,
+            websocket_connections: tenant.map_or(0, |tenant| tenant.websocket_connections),
+            limits,
+            exceeded_limits,
+            buckets,
+        }
+    }
+}
+
+impl Default for TenantUsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time range for usage reports; defaults to the last 24 hours
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+struct UsageState<R> {
+    resolver: Arc<R>,
+    tracker: TenantUsageTracker,
+}
+
+impl<R> Clone for UsageState<R> {
+    fn clone(&self) -> Self {
+        Self {
+            resolver: self.resolver.clone(),
+            tracker: self.tracker.clone(),
+        }
+    }
+}
+
+async fn get_tenant_usage<R: TenantResolver>(
+    State(state): State<UsageState<R>>,
+    Path(id): Path<String>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<TenantUsageReport>, ApiError> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::hours(24));
+    if from >= to {
+        return Err(ApiError::BadRequest("'from' must be before 'to'".to_string()));
+    }
+
+    let tenant_id = TenantId::new(id);
+    let config = state.resolver.get_tenant_config(&tenant_id).await?;
+
+    Ok(Json(
+        state
+            .tracker
+            .report(&tenant_id, config.plan, config.limits, from, to)
+            .await,
+    ))
+}
+
+/// Admin route `GET /admin/tenants/:id/usage?from=&to=` (RFC 3339 timestamps)
+///
+/// These routes are unauthenticated; mount them behind your admin auth.
+pub fn tenant_usage_routes<R: TenantResolver + 'static>(resolver: Arc<R>, tracker: TenantUsageTracker) -> Router {
+    Router::new()
+        .route("/admin/tenants/:id/usage", get(get_tenant_usage::<R>))
+        .with_state(UsageState { resolver, tracker })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_usage_report() {
+        let tracker = TenantUsageTracker::new();
+        let acme = TenantId::new("acme");
+
+        tracker.record_request(&acme, false).await;
+        tracker.record_request(&acme, true).await;
+        tracker.record_job(&acme).await;
+        tracker.set_websocket_connections(&acme, 3).await;
+        tracker.set_storage_bytes(&acme, 2_000_000_000).await;
+        tracker.record_request(&TenantId::new("other"), false).await;
+
+        let now = Utc::now();
+        let report = tracker
+            .report(&acme, TenantPlan::Free, TenantLimits::default(), now - Duration::hours(1), now + Duration::hours(1))
+            .await;
+
+        assert_eq!(report.totals.requests, 2);
+        assert_eq!(report.totals.errors, 1);
+        assert_eq!(report.totals.jobs, 1);
+        assert_eq!(report.totals.peak_websocket_connections, 3);
+        assert_eq!(report.buckets.len(), 1);
+        assert_eq!(report.exceeded_limits, vec!["max_storage_bytes".to_string()]);
+
+        // Ranges outside the recorded hour are empty
+        let report = tracker
+            .report(&acme, TenantPlan::Free, TenantLimits::default(), now - Duration::days(3), now - Duration::days(2))
+            .await;
+        assert_eq!(report.totals.requests, 0);
+        let report = tracker
+            .report(&acme, TenantPlan::Free, TenantLimits::default(), now + Duration::hours(1), now - Duration::hours(1))
+            .await;
+        assert!(report.buckets.is_empty());
+    }
+}
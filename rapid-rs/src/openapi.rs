@@ -0,0 +1,851 @@
+//! OpenAPI document built from registered routes
+//!
+//! Routes are documented either with utoipa (`#[utoipa::path]` handlers or a
+//! `#[derive(OpenApi)]` doc) or with a [`RouteDoc`] next to the route itself.
+//! `App::auto_configure()` serves the result as OpenAPI 3.1 at
+//! `/openapi.json`, with Swagger UI at `/docs`.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .auto_configure()
+//!     .route_with_doc(
+//!         "/users/:id",
+//!         get(get_user),
+//!         RouteDoc::get()
+//!             .summary("Fetch a user")
+//!             .tag("users")
+//!             .response::<User>(200, "The user")
+//!             .response_empty(404, "No such user"),
+//!     )
+//! ```
+//!
+//! Request types deriving [`SchemaConstraints`] next to `Validate` publish
+//! their `length`, `range`, `email`, `url` and `regex` rules as JSON Schema
+//! keywords when documented with [`RouteDoc::validated_request`].
+//!
+//! Types implementing [`ApiExample`], by hand or with
+//! `#[derive(ApiExample)]`, publish their example payloads once registered
+//! with [`RouteDoc::with_examples`] or [`ApiDocs::add_examples`]. The same
+//! payloads serve as test fixtures through `testing::example`, which fails
+//! when an example no longer deserializes, so the documented examples stay
+//! valid:
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize, ToSchema, ApiExample)]
+//! #[api_example(name = "minimal", json = r#"{"email": "ada@example.com"}"#)]
+//! struct CreateUser { email: String, name: Option<String> }
+//!
+//! RouteDoc::post().request::<CreateUser>().with_examples::<CreateUser>()
+//! ```
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use utoipa::openapi::{
+    path::{OperationBuilder, Parameter, ParameterBuilder, ParameterIn, PathItem, PathItemType},
+    request_body::RequestBodyBuilder,
+    ComponentsBuilder, ContentBuilder, InfoBuilder, OpenApi, OpenApiBuilder, Ref, RefOr, Required,
+    ResponseBuilder, Schema,
+};
+use utoipa::ToSchema;
+
+use crate::error_catalog::ErrorCatalog;
+
+pub use rapid_rs_macros::{api_handler, ApiExample, SchemaConstraints};
+
+/// Example payloads of a type, for the docs and as test fixtures
+///
+/// Derivable from `#[api_example(name = "...", json = "...")]` attributes;
+/// see the [module docs](self).
+pub trait ApiExample {
+    fn examples() -> Vec<Example>;
+}
+
+/// A named example payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    pub name: String,
+    pub summary: Option<String>,
+    pub value: Value,
+}
+
+impl Example {
+    /// `value` serialized to JSON
+    ///
+    /// # Panics
+    ///
+    /// If `value` can't be serialized.
+    pub fn new(name: impl Into<String>, value: impl serde::Serialize) -> Self {
+        let name = name.into();
+        let value = serde_json::to_value(value)
+            .unwrap_or_else(|e| panic!("Example `{}` can't be serialized: {}", name, e));
+        Self {
+            name,
+            summary: None,
+            value,
+        }
+    }
+
+    /// An example written as JSON text
+    ///
+    /// # Panics
+    ///
+    /// If `json` is not valid JSON.
+    pub fn from_json(name: impl Into<String>, json: &str) -> Self {
+        let name = name.into();
+        let value = serde_json::from_str(json)
+            .unwrap_or_else(|e| panic!("Example `{}` is not valid JSON: {}", name, e));
+        Self {
+            name,
+            summary: None,
+            value,
+        }
+    }
+
+    pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+}
+
+/// Validation rules of a type, as JSON Schema keywords
+///
+/// Usually derived; see the [module docs](self).
+pub trait SchemaConstraints {
+    fn schema_constraints() -> Vec<FieldConstraint>;
+}
+
+/// A validator rule expressed in JSON Schema
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    /// `minLength`/`maxLength`, or `minItems`/`maxItems` for arrays
+    Length { min: Option<u64>, max: Option<u64> },
+    Range {
+        min: Option<f64>,
+        max: Option<f64>,
+        exclusive_min: Option<f64>,
+        exclusive_max: Option<f64>,
+    },
+    Format(&'static str),
+    Pattern(String),
+}
+
+/// A [`Constraint`] on one property
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldConstraint {
+    /// JSON property name
+    pub field: String,
+    pub constraint: Constraint,
+}
+
+impl FieldConstraint {
+    pub fn new(field: impl Into<String>, constraint: Constraint) -> Self {
+        Self {
+            field: field.into(),
+            constraint,
+        }
+    }
+
+    /// Add the keywords to a property schema; `$ref` properties are left alone
+    fn apply(&self, property: &mut serde_json::Map<String, Value>) {
+        if property.contains_key("$ref") {
+            return;
+        }
+        let is_array = property.get("type").and_then(Value::as_str) == Some("array");
+        let mut set = |keyword: &str, value: Value| {
+            property.insert(keyword.to_string(), value);
+        };
+
+        match &self.constraint {
+            Constraint::Length { min, max } => {
+                let (min_keyword, max_keyword) = if is_array {
+                    ("minItems", "maxItems")
+                } else {
+                    ("minLength", "maxLength")
+                };
+                if let Some(min) = min {
+                    set(min_keyword, json!(min));
+                }
+                if let Some(max) = max {
+                    set(max_keyword, json!(max));
+                }
+            }
+            Constraint::Range {
+                min,
+                max,
+                exclusive_min,
+                exclusive_max,
+            } => {
+                for (keyword, value) in [
+                    ("minimum", min),
+                    ("maximum", max),
+                    ("exclusiveMinimum", exclusive_min),
+                    ("exclusiveMaximum", exclusive_max),
+                ] {
+                    if let Some(value) = value {
+                        set(keyword, json!(value));
+                    }
+                }
+            }
+            Constraint::Format(format) => set("format", json!(format)),
+            Constraint::Pattern(pattern) => set("pattern", json!(pattern)),
+        }
+    }
+}
+
+/// The app's OpenAPI document
+#[derive(Clone)]
+pub struct ApiDocs {
+    openapi: OpenApi,
+    /// Constraints by component schema name, applied when rendering
+    constraints: HashMap<String, Vec<FieldConstraint>>,
+    /// Examples by component schema name, applied when rendering
+    examples: HashMap<String, Vec<Example>>,
+    errors: ErrorCatalog,
+}
+
+impl Default for ApiDocs {
+    fn default() -> Self {
+        Self::new("rapid-rs API", "0.1.0")
+    }
+}
+
+impl ApiDocs {
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            openapi: OpenApiBuilder::new()
+                .info(InfoBuilder::new().title(title).version(version).build())
+                .build(),
+            constraints: HashMap::new(),
+            examples: HashMap::new(),
+            errors: ErrorCatalog::builtin(),
+        }
+    }
+
+    pub fn set_info(&mut self, title: impl Into<String>, version: impl Into<String>) {
+        self.openapi.info.title = title.into();
+        self.openapi.info.version = version.into();
+    }
+
+    /// Merge a document, e.g. from `#[derive(OpenApi)]`
+    pub fn merge(&mut self, openapi: OpenApi) {
+        self.openapi.merge(openapi);
+    }
+
+    /// Add a `#[utoipa::path]` handler
+    pub fn add_path<P: utoipa::Path>(&mut self) {
+        let mut openapi = OpenApi::new(self.openapi.info.clone(), utoipa::openapi::Paths::new());
+        openapi.paths.paths.insert(P::path(), P::path_item(None));
+        self.openapi.merge(openapi);
+    }
+
+    /// Register a schema under `components.schemas`
+    pub fn add_schema<T: ToSchema<'static>>(&mut self) {
+        let (name, schema) = T::schema();
+        self.add_component(name.to_string(), schema);
+    }
+
+    /// Register a schema along with its validation constraints
+    pub fn add_validated_schema<T: ToSchema<'static> + SchemaConstraints>(&mut self) {
+        self.add_schema::<T>();
+        let (name, _) = T::schema();
+        self.constraints.insert(name.to_string(), T::schema_constraints());
+    }
+
+    /// Publish the examples of `T` wherever its schema is used
+    pub fn add_examples<T: ToSchema<'static> + ApiExample>(&mut self) {
+        let (name, _) = T::schema();
+        self.examples.insert(name.to_string(), T::examples());
+    }
+
+    /// Examples registered for the component schema `name`
+    pub fn examples(&self, name: &str) -> &[Example] {
+        self.examples.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Document the route at `path` (axum syntax, e.g. `/users/:id`)
+    pub fn add_route(&mut self, path: &str, doc: RouteDoc) {
+        let (path, params) = openapi_path(path);
+        let RouteDoc {
+            method,
+            mut operation,
+            schemas,
+            declared_params,
+            constraints,
+            examples,
+            deprecation,
+            #[cfg(feature = "auth")]
+            auth,
+        } = doc;
+
+        for name in params.into_iter().filter(|name| !declared_params.contains(name)) {
+            operation = operation.parameter(path_parameter(&name, None));
+        }
+        for (name, schema) in schemas {
+            self.add_component(name, schema);
+        }
+        self.constraints.extend(constraints);
+        self.examples.extend(examples);
+        #[cfg(feature = "auth")]
+        for scheme in auth.iter().flat_map(|auth| auth.schemes()) {
+            self.openapi.merge(
+                OpenApiBuilder::new()
+                    .components(Some(
+                        ComponentsBuilder::new()
+                            .security_scheme(scheme.scheme_name(), scheme.security_scheme())
+                            .build(),
+                    ))
+                    .build(),
+            );
+        }
+
+        let mut operation = operation.build();
+        if let Some(deprecation) = deprecation {
+            operation.deprecated = Some(utoipa::openapi::Deprecated::True);
+            operation.description = Some(match operation.description.take() {
+                Some(description) => format!("{}\n\n{}", description, deprecation.describe()),
+                None => deprecation.describe(),
+            });
+        }
+
+        let mut openapi = OpenApi::new(self.openapi.info.clone(), utoipa::openapi::Paths::new());
+        openapi
+            .paths
+            .paths
+            .insert(path, PathItem::new(method, operation));
+        self.openapi.merge(openapi);
+    }
+
+    fn add_component(&mut self, name: String, schema: RefOr<Schema>) {
+        self.openapi.merge(
+            OpenApiBuilder::new()
+                .components(Some(ComponentsBuilder::new().schema(name, schema).build()))
+                .build(),
+        );
+    }
+
+    /// Add a domain error code to the [error catalog](crate::error_catalog)
+    pub fn add_error_code(&mut self, status: axum::http::StatusCode, code: impl Into<String>, description: impl Into<String>) {
+        self.errors.add(status, code, description);
+    }
+
+    pub fn error_catalog(&self) -> &ErrorCatalog {
+        &self.errors
+    }
+
+    /// The document as generated by utoipa (OpenAPI 3.0)
+    pub fn openapi(&self) -> &OpenApi {
+        &self.openapi
+    }
+
+    /// The document as OpenAPI 3.1 JSON
+    pub fn to_json(&self) -> Value {
+        let mut value = serde_json::to_value(&self.openapi).unwrap_or_else(|_| json!({}));
+        for (name, constraints) in &self.constraints {
+            let pointer = format!("/components/schemas/{}/properties", name);
+            let Some(Value::Object(properties)) = value.pointer_mut(&pointer) else {
+                continue;
+            };
+            for constraint in constraints {
+                if let Some(Value::Object(property)) = properties.get_mut(&constraint.field) {
+                    constraint.apply(property);
+                }
+            }
+        }
+        upgrade_to_3_1(&mut value);
+        // After the upgrade, which would rewrite `nullable` keys in payloads
+        for (name, examples) in &self.examples {
+            apply_examples(&mut value, name, examples);
+        }
+        self.errors.apply(&mut value);
+        value["openapi"] = json!("3.1.0");
+        value
+    }
+}
+
+/// Documentation for one route
+///
+/// Request and response types are added to `components.schemas` and
+/// referenced from the operation. Path parameters (`:id`) are documented as
+/// strings unless declared with [`RouteDoc::path_param`].
+pub struct RouteDoc {
+    method: PathItemType,
+    operation: OperationBuilder,
+    schemas: Vec<(String, RefOr<Schema>)>,
+    declared_params: Vec<String>,
+    constraints: Vec<(String, Vec<FieldConstraint>)>,
+    examples: Vec<(String, Vec<Example>)>,
+    deprecation: Option<crate::deprecation::Deprecation>,
+    #[cfg(feature = "auth")]
+    auth: Option<crate::auth::RouteAuth>,
+}
+
+impl RouteDoc {
+    pub fn new(method: PathItemType) -> Self {
+        Self {
+            method,
+            operation: OperationBuilder::new(),
+            schemas: Vec::new(),
+            declared_params: Vec::new(),
+            constraints: Vec::new(),
+            examples: Vec::new(),
+            deprecation: None,
+            #[cfg(feature = "auth")]
+            auth: None,
+        }
+    }
+
+    pub fn get() -> Self {
+        Self::new(PathItemType::Get)
+    }
+
+    pub fn post() -> Self {
+        Self::new(PathItemType::Post)
+    }
+
+    pub fn put() -> Self {
+        Self::new(PathItemType::Put)
+    }
+
+    pub fn patch() -> Self {
+        Self::new(PathItemType::Patch)
+    }
+
+    pub fn delete() -> Self {
+        Self::new(PathItemType::Delete)
+    }
+
+    pub fn summary(mut self, summary: impl Into<String>) -> Self {
+        self.operation = self.operation.summary(Some(summary.into()));
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.operation = self.operation.description(Some(description.into()));
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.operation = self.operation.tag(tag);
+        self
+    }
+
+    pub fn operation_id(mut self, operation_id: impl Into<String>) -> Self {
+        self.operation = self.operation.operation_id(Some(operation_id.into()));
+        self
+    }
+
+    /// Describe a path parameter
+    pub fn path_param(mut self, name: impl Into<String>, description: impl Into<String>) -> Self {
+        let name = name.into();
+        self.operation = self
+            .operation
+            .parameter(path_parameter(&name, Some(description.into())));
+        self.declared_params.push(name);
+        self
+    }
+
+    /// JSON request body of type `T`
+    pub fn request<T: ToSchema<'static>>(self) -> Self {
+        self.request_as::<T>(&["application/json"])
+    }
+
+    /// Request body of type `T`, sent as JSON or protobuf
+    #[cfg(feature = "protobuf")]
+    pub fn protobuf_request<T: ToSchema<'static>>(self) -> Self {
+        self.request_as::<T>(&["application/json", crate::protobuf::PROTOBUF_CONTENT_TYPE])
+    }
+
+    fn request_as<T: ToSchema<'static>>(mut self, content_types: &[&str]) -> Self {
+        let schema = self.schema_ref::<T>();
+        let body = content_types.iter().fold(RequestBodyBuilder::new(), |body, content_type| {
+            body.content(*content_type, ContentBuilder::new().schema(schema.clone()).build())
+        });
+        self.operation = self.operation.request_body(Some(body.required(Some(Required::True)).build()));
+        self
+    }
+
+    /// JSON request body of type `T`, publishing its validation rules
+    pub fn validated_request<T: ToSchema<'static> + SchemaConstraints>(mut self) -> Self {
+        let (name, _) = T::schema();
+        self.constraints.push((name.to_string(), T::schema_constraints()));
+        self.request::<T>()
+    }
+
+    /// Publish the examples of `T`, a request or response type of this or
+    /// any other route
+    pub fn with_examples<T: ToSchema<'static> + ApiExample>(mut self) -> Self {
+        let (name, _) = T::schema();
+        self.examples.push((name.to_string(), T::examples()));
+        self
+    }
+
+    /// JSON response of type `T` for `status`
+    pub fn response<T: ToSchema<'static>>(self, status: u16, description: impl Into<String>) -> Self {
+        self.response_as::<T>(status, description, &["application/json"])
+    }
+
+    /// Response of type `T` for `status`, as JSON or protobuf depending on `Accept`
+    #[cfg(feature = "protobuf")]
+    pub fn protobuf_response<T: ToSchema<'static>>(self, status: u16, description: impl Into<String>) -> Self {
+        self.response_as::<T>(status, description, &["application/json", crate::protobuf::PROTOBUF_CONTENT_TYPE])
+    }
+
+    fn response_as<T: ToSchema<'static>>(mut self, status: u16, description: impl Into<String>, content_types: &[&str]) -> Self {
+        let schema = self.schema_ref::<T>();
+        let response = content_types.iter().fold(ResponseBuilder::new().description(description), |response, content_type| {
+            response.content(*content_type, ContentBuilder::new().schema(schema.clone()).build())
+        });
+        self.operation = self.operation.response(status.to_string(), response.build());
+        self
+    }
+
+    /// Response without a body for `status`
+    pub fn response_empty(mut self, status: u16, description: impl Into<String>) -> Self {
+        self.operation = self.operation.response(
+            status.to_string(),
+            ResponseBuilder::new().description(description).build(),
+        );
+        self
+    }
+
+    /// Error response returned as the `ApiError` variant named `variant`
+    ///
+    /// # Panics
+    ///
+    /// If `variant` is not an `ApiError` variant.
+    pub fn api_error(mut self, variant: &str) -> Self {
+        let (status, description) = crate::error::ApiError::documented_status(variant)
+            .unwrap_or_else(|| panic!("`{}` is not an ApiError variant", variant));
+        let schema = self.schema_ref::<crate::error::ErrorResponse>();
+        self.operation = self.operation.response(
+            status.as_u16().to_string(),
+            ResponseBuilder::new()
+                .description(description)
+                .content("application/json", ContentBuilder::new().schema(schema).build())
+                .build(),
+        );
+        self
+    }
+
+    /// Mark the route deprecated
+    ///
+    /// Flagged in the docs, and with [`App::route_with_doc`](crate::App::route_with_doc)
+    /// its responses carry `Deprecation`/`Sunset` headers; see
+    /// [`deprecation`](crate::deprecation).
+    pub fn deprecated(mut self, deprecation: crate::deprecation::Deprecation) -> Self {
+        self.deprecation = Some(deprecation);
+        self
+    }
+
+    pub(crate) fn route_deprecation(&self) -> Option<&crate::deprecation::Deprecation> {
+        self.deprecation.as_ref()
+    }
+
+    /// Authentication the route requires
+    ///
+    /// Published as the operation's `security` along with its 401/403
+    /// responses, and enforced when added with
+    /// [`App::route_with_doc`](crate::App::route_with_doc).
+    #[cfg(feature = "auth")]
+    pub fn auth(mut self, auth: crate::auth::RouteAuth) -> Self {
+        self.operation = self.operation.securities(Some(auth.security()));
+        if !auth.is_public() {
+            self = self.api_error("Unauthorized");
+        }
+        if auth.has_permissions() {
+            self = self.api_error("Forbidden");
+        }
+        self.auth = Some(auth);
+        self
+    }
+
+    /// The authentication set with [`RouteDoc::auth`]
+    #[cfg(feature = "auth")]
+    pub(crate) fn route_auth(&self) -> Option<&crate::auth::RouteAuth> {
+        self.auth.as_ref()
+    }
+
+    fn schema_ref<T: ToSchema<'static>>(&mut self) -> RefOr<Schema> {
+        let (name, schema) = T::schema();
+        self.schemas.push((name.to_string(), schema));
+        RefOr::Ref(Ref::from_schema_name(name))
+    }
+}
+
+fn path_parameter(name: &str, description: Option<String>) -> Parameter {
+    ParameterBuilder::new()
+        .name(name)
+        .parameter_in(ParameterIn::Path)
+        .required(Required::True)
+        .description(description)
+        .schema(Some(
+            utoipa::openapi::ObjectBuilder::new().schema_type(utoipa::openapi::SchemaType::String),
+        ))
+        .build()
+}
+
+/// Convert axum `:param` / `*rest` segments to `{param}`, returning the names
+fn openapi_path(path: &str) -> (String, Vec<String>) {
+    let mut params = Vec::new();
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+            Some(name) if !name.is_empty() => {
+                params.push(name.to_string());
+                format!("{{{}}}", name)
+            }
+            _ => segment.to_string(),
+        })
+        .collect();
+    (segments.join("/"), params)
+}
+
+/// Add `examples` to the schema `name` and named examples to every media
+/// type referencing it
+fn apply_examples(value: &mut Value, name: &str, examples: &[Example]) {
+    if examples.is_empty() {
+        return;
+    }
+    if let Some(Value::Object(schema)) = value.pointer_mut(&format!("/components/schemas/{}", name)) {
+        let values = examples.iter().map(|example| example.value.clone()).collect();
+        schema.insert("examples".to_string(), Value::Array(values));
+    }
+
+    let reference = format!("#/components/schemas/{}", name);
+    let named: serde_json::Map<String, Value> = examples
+        .iter()
+        .map(|example| {
+            let mut object = json!({ "value": example.value });
+            if let Some(summary) = &example.summary {
+                object["summary"] = json!(summary);
+            }
+            (example.name.clone(), object)
+        })
+        .collect();
+    let Some(Value::Object(paths)) = value.get_mut("paths") else {
+        return;
+    };
+    let operations = paths
+        .values_mut()
+        .filter_map(Value::as_object_mut)
+        .flat_map(|item| item.values_mut())
+        .filter_map(Value::as_object_mut);
+    for operation in operations {
+        let mut contents = Vec::new();
+        for (key, field) in operation.iter_mut() {
+            match key.as_str() {
+                "requestBody" => contents.extend(field.get_mut("content")),
+                "responses" => contents.extend(
+                    field
+                        .as_object_mut()
+                        .into_iter()
+                        .flat_map(|responses| responses.values_mut())
+                        .filter_map(|response| response.get_mut("content")),
+                ),
+                _ => {}
+            }
+        }
+        for content in contents.into_iter().filter_map(Value::as_object_mut) {
+            for media in content.values_mut().filter_map(Value::as_object_mut) {
+                if media.get("schema").and_then(|schema| schema.get("$ref")).and_then(Value::as_str) == Some(&reference) {
+                    media.insert("examples".to_string(), Value::Object(named.clone()));
+                }
+            }
+        }
+    }
+}
+
+/// Replace OpenAPI 3.0 `nullable` with 3.1 `null` type unions
+fn upgrade_to_3_1(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for child in object.values_mut() {
+                upgrade_to_3_1(child);
+            }
+
+            match object.remove("nullable") {
+                Some(Value::Bool(true)) => {}
+                Some(Value::Bool(false)) | None => return,
+                Some(other) => {
+                    // Not the schema keyword (e.g. a property named "nullable")
+                    object.insert("nullable".to_string(), other);
+                    return;
+                }
+            }
+
+            match object.get_mut("type") {
+                Some(Value::String(ty)) => {
+                    let ty = std::mem::take(ty);
+                    object.insert("type".to_string(), json!([ty, "null"]));
+                }
+                _ => {
+                    let schema = Value::Object(std::mem::take(object));
+                    object.insert("oneOf".to_string(), json!([schema, { "type": "null" }]));
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(upgrade_to_3_1),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize, ToSchema)]
+    #[allow(dead_code)]
+    struct User {
+        id: String,
+        nickname: Option<String>,
+    }
+
+    #[derive(serde::Deserialize, ToSchema, validator::Validate, SchemaConstraints)]
+    #[allow(dead_code)]
+    struct CreateUser {
+        #[validate(email, length(max = 254))]
+        email: String,
+        #[serde(rename = "displayName")]
+        #[validate(length(min = 2, max = 50, message = "Name must be 2-50 characters"))]
+        display_name: String,
+        #[validate(range(min = 13, exclusive_max = 150))]
+        age: u8,
+        #[validate(length(min = 1))]
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_validation_constraints() {
+        let mut docs = ApiDocs::default();
+        docs.add_route("/users", RouteDoc::post().validated_request::<CreateUser>());
+
+        let json = docs.to_json();
+        let properties = &json["components"]["schemas"]["CreateUser"]["properties"];
+        assert_eq!(properties["email"]["format"], "email");
+        assert_eq!(properties["email"]["maxLength"], 254);
+        assert_eq!(properties["displayName"]["minLength"], 2);
+        assert_eq!(properties["displayName"]["maxLength"], 50);
+        assert_eq!(properties["age"]["minimum"], 13.0);
+        assert_eq!(properties["age"]["exclusiveMaximum"], 150.0);
+        assert_eq!(properties["tags"]["minItems"], 1);
+    }
+
+    /// Fetch a user
+    ///
+    /// Looks the user up by id.
+    #[api_handler(get, tag = "users", response(200, User, "The user"), errors(NotFound, Unauthorized))]
+    async fn get_user() -> crate::ApiResult<()> {
+        Err(crate::ApiError::Unauthorized)
+    }
+
+    #[test]
+    fn test_api_handler() {
+        let _router: axum::Router = axum::Router::new().route("/users/:id", axum::routing::get(get_user));
+        let mut docs = ApiDocs::default();
+        docs.add_route("/users/:id", get_user_doc());
+
+        let json = docs.to_json();
+        let get = &json["paths"]["/users/{id}"]["get"];
+        assert_eq!(get["operationId"], "get_user");
+        assert_eq!(get["summary"], "Fetch a user");
+        assert_eq!(get["description"], "Looks the user up by id.");
+        assert_eq!(get["tags"][0], "users");
+        assert!(get["responses"]["200"].is_object());
+        assert_eq!(
+            get["responses"]["404"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ErrorResponse"
+        );
+        assert!(get["responses"]["401"].is_object());
+    }
+
+    #[test]
+    fn test_route_docs() {
+        let mut docs = ApiDocs::default();
+        docs.add_route(
+            "/users/:id",
+            RouteDoc::get()
+                .summary("Fetch a user")
+                .response::<User>(200, "The user")
+                .response_empty(404, "No such user"),
+        );
+        docs.add_route("/users", RouteDoc::post().request::<User>().response::<User>(201, "Created"));
+
+        let json = docs.to_json();
+        assert_eq!(json["openapi"], "3.1.0");
+
+        let get = &json["paths"]["/users/{id}"]["get"];
+        assert_eq!(get["summary"], "Fetch a user");
+        assert_eq!(get["parameters"][0]["name"], "id");
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/User"
+        );
+        assert!(json["paths"]["/users"]["post"]["requestBody"].is_object());
+
+        let nickname = &json["components"]["schemas"]["User"]["properties"]["nickname"];
+        assert_eq!(nickname["type"], json!(["string", "null"]));
+        assert!(nickname.get("nullable").is_none());
+    }
+
+    #[derive(serde::Deserialize, ToSchema, ApiExample)]
+    #[api_example(name = "minimal", json = r#"{"email": "ada@example.com", "nullable": true}"#)]
+    #[api_example(name = "full", summary = "Every field", json = r#"{"email": "ada@example.com", "nickname": "ada"}"#)]
+    #[allow(dead_code)]
+    struct Signup {
+        email: String,
+        nickname: Option<String>,
+    }
+
+    #[test]
+    fn test_examples() {
+        let mut docs = ApiDocs::default();
+        docs.add_route(
+            "/signup",
+            RouteDoc::post()
+                .request::<Signup>()
+                .response::<User>(201, "Created")
+                .with_examples::<Signup>(),
+        );
+        assert_eq!(docs.examples("Signup").len(), 2);
+
+        let json = docs.to_json();
+        let schema = &json["components"]["schemas"]["Signup"];
+        assert_eq!(schema["examples"][0], json!({"email": "ada@example.com", "nullable": true}));
+        let media = &json["paths"]["/signup"]["post"]["requestBody"]["content"]["application/json"];
+        assert_eq!(media["examples"]["full"]["summary"], "Every field");
+        assert_eq!(media["examples"]["full"]["value"]["nickname"], "ada");
+        assert!(json["paths"]["/signup"]["post"]["responses"]["201"]["content"]["application/json"]
+            .get("examples")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_app_serves_spec() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let router = crate::App::new()
+            .with_api_docs()
+            .with_api_info("Users", "1.2.0")
+            .route_with_doc(
+                "/users",
+                axum::routing::get(|| async { "[]" }),
+                RouteDoc::get().response::<User>(200, "Users"),
+            )
+            .into_router();
+
+        let response = router
+            .oneshot(Request::builder().uri("/openapi.json").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["openapi"], "3.1.0");
+        assert_eq!(json["info"]["title"], "Users");
+        assert!(json["paths"]["/users"]["get"].is_object());
+        assert!(json["components"]["schemas"]["User"].is_object());
+    }
+}
@@ -0,0 +1,275 @@
+//! Cursor (keyset) pagination
+//!
+//! Offset pagination slows down the deeper the page; keyset pagination
+//! continues from the last row seen instead. [`Cursor<K>`] reads
+//! `?cursor=&limit=`, where the cursor is an opaque, signed encoding of the
+//! key `K` of the last item on the previous page, so clients can't forge
+//! positions.
+//!
+//! ```rust,ignore
+//! async fn list_events(
+//!     State(pool): State<PgPool>,
+//!     cursor: Cursor<(DateTime<Utc>, String)>,
+//! ) -> ApiResult<CursorPage<Event>> {
+//!     let columns = ["created_at", "id"];
+//!     let mut query = QueryBuilder::new("SELECT * FROM events WHERE ");
+//!     cursor.push_after(&mut query, &columns, SortDirection::Desc);
+//!     cursor.push_order_limit(&mut query, &columns, SortDirection::Desc);
+//!
+//!     let events: Vec<Event> = query.build_query_as().fetch_all(&pool).await?;
+//!     Ok(Json(cursor.page(events, |event| (event.created_at, event.id.clone()))))
+//! }
+//!
+//! Router::new()
+//!     .route("/events", get(list_events))
+//!     .layer(Extension(CursorCodec::new(std::env::var("CURSOR_SECRET")?)))
+//! ```
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::Sha256;
+use sqlx::{Postgres, QueryBuilder};
+use std::sync::Arc;
+
+use super::{parse_number, query_param, request_uri, PaginationConfig, SortDirection};
+use crate::error::ApiError;
+
+/// Encodes keys as opaque cursors signed with HMAC-SHA256
+#[derive(Clone)]
+pub struct CursorCodec {
+    key: Arc<[u8]>,
+}
+
+impl std::fmt::Debug for CursorCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CursorCodec").finish_non_exhaustive()
+    }
+}
+
+impl CursorCodec {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: Arc::from(secret.as_ref()),
+        }
+    }
+
+    pub fn encode<K: Serialize>(&self, key: &K) -> String {
+        let payload = serde_json::to_vec(key).expect("cursor keys serialize to JSON");
+        let signature = self.mac(&payload).finalize().into_bytes();
+        format!("{}.{}", URL_SAFE_NO_PAD.encode(&payload), URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// Key of a cursor made by [`encode`](Self::encode)
+    pub fn decode<K: DeserializeOwned>(&self, cursor: &str) -> Result<K, ApiError> {
+        let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+        let (payload, signature) = cursor.split_once('.').ok_or_else(invalid)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        self.mac(&payload).verify_slice(&signature).map_err(|_| invalid())?;
+
+        serde_json::from_slice(&payload).map_err(|_| invalid())
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(payload);
+        mac
+    }
+}
+
+/// Key columns a page is ordered by, as a tuple of their values
+///
+/// Implemented for tuples of up to four values, e.g. `(DateTime<Utc>, Uuid)`
+/// for `ORDER BY created_at, id`; use `(i64,)` for a single column.
+pub trait KeysetKey: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {
+    fn push_binds(&self, query: &mut QueryBuilder<'_, Postgres>);
+}
+
+macro_rules! impl_keyset_key {
+    ($($name:ident: $index:tt),+) => {
+        impl<$($name),+> KeysetKey for ($($name,)+)
+        where
+            $($name: Serialize + DeserializeOwned + Clone + Send + Sync + 'static
+                + for<'q> sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres>),+
+        {
+            fn push_binds(&self, query: &mut QueryBuilder<'_, Postgres>) {
+                let mut values = query.separated(", ");
+                $(values.push_bind(self.$index.clone());)+
+            }
+        }
+    };
+}
+
+impl_keyset_key!(A: 0);
+impl_keyset_key!(A: 0, B: 1);
+impl_keyset_key!(A: 0, B: 1, C: 2);
+impl_keyset_key!(A: 0, B: 1, C: 2, D: 3);
+
+/// The position a keyset-paginated request continues from
+///
+/// Needs a [`CursorCodec`] as an `Extension`. `limit` is bounded by
+/// [`PaginationConfig`] like [`Pagination::per_page`](super::Pagination).
+#[derive(Debug, Clone)]
+pub struct Cursor<K> {
+    /// Key of the last item already seen, `None` on the first page
+    pub after: Option<K>,
+    pub limit: u32,
+    codec: CursorCodec,
+}
+
+impl<K: KeysetKey> Cursor<K> {
+    /// Append `(columns) > (after)` (`<` when descending), or `TRUE` on the first page
+    ///
+    /// All key columns must be sorted in the same direction.
+    pub fn push_after(&self, query: &mut QueryBuilder<'_, Postgres>, columns: &[&str], direction: SortDirection) {
+        let Some(after) = &self.after else {
+            query.push("TRUE");
+            return;
+        };
+        let operator = match direction {
+            SortDirection::Asc => ">",
+            SortDirection::Desc => "<",
+        };
+        query.push(format!("({}) {} (", columns.join(", "), operator));
+        after.push_binds(query);
+        query.push(")");
+    }
+
+    /// Append ` ORDER BY .. LIMIT ..`, fetching one extra row to tell if there are more
+    pub fn push_order_limit(&self, query: &mut QueryBuilder<'_, Postgres>, columns: &[&str], direction: SortDirection) {
+        let order: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{} {}", column, direction.as_sql()))
+            .collect();
+        query
+            .push(format!(" ORDER BY {} LIMIT ", order.join(", ")))
+            .push_bind(i64::from(self.limit) + 1);
+    }
+
+    /// Wrap rows fetched with [`push_order_limit`](Self::push_order_limit)
+    ///
+    /// `key` extracts the key columns of an item.
+    pub fn page<T>(&self, mut items: Vec<T>, key: impl Fn(&T) -> K) -> CursorPage<T> {
+        let has_more = items.len() > self.limit as usize;
+        items.truncate(self.limit as usize);
+        let next_cursor = has_more
+            .then(|| items.last().map(|item| self.codec.encode(&key(item))))
+            .flatten();
+
+        CursorPage {
+            items,
+            next_cursor,
+            has_more,
+        }
+    }
+}
+
+#[async_trait]
+impl<K, S> FromRequestParts<S> for Cursor<K>
+where
+    K: KeysetKey,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let codec = parts
+            .extensions
+            .get::<CursorCodec>()
+            .cloned()
+            .ok_or_else(|| ApiError::InternalServerError("CursorCodec extension missing".to_string()))?;
+        let config = parts.extensions.get::<PaginationConfig>().copied().unwrap_or_default();
+        let uri = request_uri(parts);
+
+        let after = query_param(&uri, "cursor")
+            .filter(|cursor| !cursor.is_empty())
+            .map(|cursor| codec.decode(&cursor))
+            .transpose()?;
+        let limit = parse_number(&uri, "limit")?
+            .unwrap_or(config.default_per_page)
+            .clamp(1, config.max_per_page.max(1));
+
+        Ok(Self { after, limit, codec })
+    }
+}
+
+/// One page of a keyset-paginated list
+#[derive(Debug, Clone, Serialize)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    /// Pass as `?cursor=` for the next page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get, Extension, Json, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_codec_round_trip() {
+        let codec = CursorCodec::new("secret");
+        let cursor = codec.encode(&(42_i64, "abc".to_string()));
+        assert_eq!(codec.decode::<(i64, String)>(&cursor).unwrap(), (42, "abc".to_string()));
+
+        let (payload, signature) = cursor.split_once('.').unwrap();
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(b"[1,\"abc\"]"), signature);
+        assert!(codec.decode::<(i64, String)>(&forged).is_err());
+        assert!(CursorCodec::new("other").decode::<(i64, String)>(&cursor).is_err());
+        assert!(codec.decode::<(i64, String)>(payload).is_err());
+    }
+
+    #[test]
+    fn test_push_after() {
+        let cursor = Cursor {
+            after: Some((7_i64,)),
+            limit: 10,
+            codec: CursorCodec::new("secret"),
+        };
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM items WHERE ");
+        cursor.push_after(&mut query, &["id"], SortDirection::Desc);
+        cursor.push_order_limit(&mut query, &["id"], SortDirection::Desc);
+        assert_eq!(query.sql(), "SELECT * FROM items WHERE (id) < ($1) ORDER BY id DESC LIMIT $2");
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pages() {
+        // Items 1..=25, paged by id; the handler stands in for the query
+        async fn list(cursor: Cursor<(i64,)>) -> Json<CursorPage<i64>> {
+            let after = cursor.after.map_or(0, |(id,)| id);
+            let rows = (after + 1..=25).take(cursor.limit as usize + 1).collect();
+            Json(cursor.page(rows, |id| (*id,)))
+        }
+        let router = Router::new()
+            .route("/items", get(list))
+            .layer(Extension(CursorCodec::new("secret")))
+            .layer(Extension(PaginationConfig::new().with_default_per_page(10)));
+
+        let mut seen = Vec::new();
+        let mut uri = "/items".to_string();
+        loop {
+            let response = router.clone().oneshot(Request::get(&uri).body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            seen.extend(page["items"].as_array().unwrap().iter().map(|id| id.as_i64().unwrap()));
+            match page["next_cursor"].as_str() {
+                Some(cursor) => uri = format!("/items?cursor={}", cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, (1..=25).collect::<Vec<_>>());
+
+        let response = router.oneshot(Request::get("/items?cursor=bogus").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,383 @@
+//! Offset pagination and sorting for list endpoints
+//!
+//! [`Pagination`] reads `?page=&per_page=`, [`Sort<T>`] reads
+//! `?sort=-created_at,name` against an allowlist, and [`Paginated<T>`] wraps a
+//! page of results with totals and links to neighbouring pages. For large
+//! tables, see keyset pagination in `pagination::cursor` (`cursor-pagination` feature).
+//!
+//! ```rust,ignore
+//! struct UserSort;
+//!
+//! impl Sortable for UserSort {
+//!     const FIELDS: &'static [&'static str] = &["name", "created_at"];
+//!     const DEFAULT: &'static str = "-created_at";
+//! }
+//!
+//! async fn list_users(
+//!     State(pool): State<PgPool>,
+//!     page: Pagination,
+//!     sort: Sort<UserSort>,
+//! ) -> ApiResult<Paginated<User>> {
+//!     let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(&pool).await?;
+//!     let sql = format!("SELECT * FROM users {} {}", sort.order_by(), page.limit_offset());
+//!     let users = sqlx::query_as(&sql).fetch_all(&pool).await?;
+//!     Ok(Json(page.paginate(users, total as u64)))
+//! }
+//! ```
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri},
+    http::{request::Parts, Uri},
+};
+use serde::Serialize;
+use std::marker::PhantomData;
+
+use crate::error::ApiError;
+
+#[cfg(feature = "cursor-pagination")]
+pub mod cursor;
+
+#[cfg(feature = "cursor-pagination")]
+pub use cursor::{Cursor, CursorCodec, CursorPage, KeysetKey};
+
+/// Page size bounds for [`Pagination`], read from a request extension
+#[derive(Debug, Clone, Copy)]
+pub struct PaginationConfig {
+    /// Page size when the request doesn't ask for one (default: 20)
+    pub default_per_page: u32,
+    /// Largest page size a request may ask for (default: 100)
+    pub max_per_page: u32,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            default_per_page: 20,
+            max_per_page: 100,
+        }
+    }
+}
+
+impl PaginationConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_default_per_page(mut self, per_page: u32) -> Self {
+        self.default_per_page = per_page;
+        self
+    }
+
+    pub fn with_max_per_page(mut self, per_page: u32) -> Self {
+        self.max_per_page = per_page;
+        self
+    }
+}
+
+/// The page a list request asked for
+///
+/// Pages start at 1; `per_page` is clamped to [`PaginationConfig`]'s bounds.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+    uri: Uri,
+}
+
+impl Pagination {
+    pub fn new(page: u32, per_page: u32) -> Self {
+        Self {
+            page: page.max(1),
+            per_page: per_page.max(1),
+            uri: Uri::from_static("/"),
+        }
+    }
+
+    /// Rows to skip
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+
+    /// Rows to return
+    pub fn limit(&self) -> u64 {
+        u64::from(self.per_page)
+    }
+
+    /// `LIMIT .. OFFSET ..` clause for this page
+    pub fn limit_offset(&self) -> String {
+        format!("LIMIT {} OFFSET {}", self.limit(), self.offset())
+    }
+
+    /// Append a bound `LIMIT .. OFFSET ..` clause to `query`
+    pub fn push_limit_offset<'args, DB>(&self, query: &mut sqlx::QueryBuilder<'args, DB>)
+    where
+        DB: sqlx::Database,
+        i64: sqlx::Encode<'args, DB> + sqlx::Type<DB>,
+    {
+        query
+            .push(" LIMIT ")
+            .push_bind(self.limit() as i64)
+            .push(" OFFSET ")
+            .push_bind(self.offset() as i64);
+    }
+
+    /// Wrap one page of `items` out of `total`
+    pub fn paginate<T>(&self, items: Vec<T>, total: u64) -> Paginated<T> {
+        let total_pages = total.div_ceil(u64::from(self.per_page)).max(1) as u32;
+        let link = |page: u32| self.link(page);
+
+        Paginated {
+            items,
+            page: self.page,
+            per_page: self.per_page,
+            total,
+            total_pages,
+            links: PageLinks {
+                current: link(self.page),
+                first: link(1),
+                last: link(total_pages),
+                next: (self.page < total_pages).then(|| link(self.page + 1)),
+                prev: (self.page > 1).then(|| link((self.page - 1).min(total_pages))),
+            },
+        }
+    }
+
+    /// The request's URI with `page` and `per_page` set
+    fn link(&self, page: u32) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in form_urlencoded::parse(self.uri.query().unwrap_or_default().as_bytes()) {
+            if key != "page" && key != "per_page" {
+                query.append_pair(&key, &value);
+            }
+        }
+        query
+            .append_pair("page", &page.to_string())
+            .append_pair("per_page", &self.per_page.to_string());
+        format!("{}?{}", self.uri.path(), query.finish())
+    }
+}
+
+/// Value of the query parameter `name`, if present
+fn query_param(uri: &Uri, name: &str) -> Option<String> {
+    form_urlencoded::parse(uri.query()?.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// The full request URI, even inside nested routers
+fn request_uri(parts: &Parts) -> Uri {
+    parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or_else(|| parts.uri.clone(), |OriginalUri(uri)| uri.clone())
+}
+
+fn parse_number(uri: &Uri, name: &str) -> Result<Option<u32>, ApiError> {
+    query_param(uri, name)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse::<u32>()
+                .map_err(|_| ApiError::BadRequest(format!("'{}' must be a positive integer", name)))
+        })
+        .transpose()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Pagination
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts.extensions.get::<PaginationConfig>().copied().unwrap_or_default();
+        let uri = request_uri(parts);
+
+        let page = parse_number(&uri, "page")?.unwrap_or(1);
+        if page == 0 {
+            return Err(ApiError::BadRequest("'page' starts at 1".to_string()));
+        }
+        let per_page = parse_number(&uri, "per_page")?
+            .unwrap_or(config.default_per_page)
+            .clamp(1, config.max_per_page.max(1));
+
+        Ok(Self { page, per_page, uri })
+    }
+}
+
+/// Links to neighbouring pages, relative to the server root
+#[derive(Debug, Clone, Serialize)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub current: String,
+    pub first: String,
+    pub last: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+}
+
+/// One page of a list response
+#[derive(Debug, Clone, Serialize)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    /// Items across all pages
+    pub total: u64,
+    pub total_pages: u32,
+    pub links: PageLinks,
+}
+
+/// Fields a list may be sorted by
+pub trait Sortable: Send + Sync + 'static {
+    /// Allowed sort fields; they are used verbatim as column names
+    const FIELDS: &'static [&'static str];
+    /// Sort when the request has none, e.g. `-created_at` (empty = unsorted)
+    const DEFAULT: &'static str = "";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+/// The order a list request asked for
+///
+/// `?sort=-created_at,name` sorts by `created_at` descending, then `name`.
+/// Fields outside `T::FIELDS` are rejected.
+#[derive(Debug, Clone)]
+pub struct Sort<T> {
+    pub fields: Vec<(&'static str, SortDirection)>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Sortable> Sort<T> {
+    /// Parse a `sort` parameter against `T::FIELDS`
+    pub fn parse(value: &str) -> Result<Self, ApiError> {
+        let fields = value
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(|field| {
+                let (name, direction) = match field.strip_prefix('-') {
+                    Some(name) => (name, SortDirection::Desc),
+                    None => (field.strip_prefix('+').unwrap_or(field), SortDirection::Asc),
+                };
+                let name = T::FIELDS.iter().find(|allowed| **allowed == name).ok_or_else(|| {
+                    ApiError::BadRequest(format!(
+                        "Cannot sort by '{}'; allowed fields: {}",
+                        name,
+                        T::FIELDS.join(", ")
+                    ))
+                })?;
+                Ok((*name, direction))
+            })
+            .collect::<Result<_, ApiError>>()?;
+
+        Ok(Self {
+            fields,
+            _marker: PhantomData,
+        })
+    }
+
+    /// `ORDER BY ..` clause, or an empty string when unsorted
+    pub fn order_by(&self) -> String {
+        if self.fields.is_empty() {
+            return String::new();
+        }
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(field, direction)| format!("{} {}", field, direction.as_sql()))
+            .collect();
+        format!("ORDER BY {}", fields.join(", "))
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Sort<T>
+where
+    T: Sortable,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let sort = query_param(&request_uri(parts), "sort").unwrap_or_else(|| T::DEFAULT.to_string());
+        Self::parse(&sort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get, Extension, Json, Router};
+    use tower::ServiceExt;
+
+    struct UserSort;
+
+    impl Sortable for UserSort {
+        const FIELDS: &'static [&'static str] = &["name", "created_at"];
+        const DEFAULT: &'static str = "-created_at";
+    }
+
+    async fn list(page: Pagination, sort: Sort<UserSort>) -> Json<Paginated<String>> {
+        let items = vec![format!("{} {}", sort.order_by(), page.limit_offset())];
+        Json(page.paginate(items, 45))
+    }
+
+    async fn get_json(router: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_pagination_and_sort() {
+        let router = Router::new()
+            .route("/users", get(list))
+            .layer(Extension(PaginationConfig::new().with_default_per_page(10).with_max_per_page(20)));
+
+        let (status, body) = get_json(&router, "/users").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["items"][0], "ORDER BY created_at DESC LIMIT 10 OFFSET 0");
+        assert_eq!(body["total_pages"], 5);
+        assert_eq!(body["links"]["next"], "/users?page=2&per_page=10");
+        assert!(body["links"].get("prev").is_none());
+
+        let (_, body) = get_json(&router, "/users?q=ada&page=2&per_page=50&sort=name,-created_at").await;
+        assert_eq!(body["items"][0], "ORDER BY name ASC, created_at DESC LIMIT 20 OFFSET 20");
+        assert_eq!(body["links"]["self"], "/users?q=ada&sort=name%2C-created_at&page=2&per_page=20");
+        assert_eq!(body["links"]["last"], "/users?q=ada&sort=name%2C-created_at&page=3&per_page=20");
+        assert_eq!(body["links"]["prev"], "/users?q=ada&sort=name%2C-created_at&page=1&per_page=20");
+
+        let (status, _) = get_json(&router, "/users?page=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = get_json(&router, "/users?sort=password").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["message"].as_str().unwrap().contains("allowed fields: name, created_at"));
+    }
+
+    #[test]
+    fn test_paginate_past_the_end() {
+        let page = Pagination::new(7, 10).paginate(Vec::<u32>::new(), 0);
+        assert_eq!(page.total_pages, 1);
+        assert!(page.links.next.is_none());
+        assert_eq!(page.links.prev.as_deref(), Some("/?page=1&per_page=10"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Per-route resilience policies
+//!
+//! A [`RoutePolicy`] bundles what would otherwise be repeated in every
+//! handler body: a timeout, retries of idempotent downstream calls made with
+//! [`HttpClient::send`](crate::http_client::HttpClient::send), and a fallback
+//! response served when the handler fails or times out:
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .route("/quotes/:symbol", get(quote))
+//!     .with_route_policy(
+//!         "/quotes/:symbol",
+//!         RoutePolicy::new()
+//!             .with_timeout(Duration::from_secs(2))
+//!             .with_retry(RetryPolicy::new(3, Duration::from_millis(50)))
+//!             .with_fallback((StatusCode::OK, Json(json!({ "quote": null, "stale": true })))),
+//!     )
+//!
+//! async fn quote(Dep(client): Dep<HttpClient>, Path(symbol): Path<String>) -> ApiResult<Quote> {
+//!     // Retried up to 3 times on connection errors, 502, 503 and 504
+//!     let response = client
+//!         .send(client.get(format!("{QUOTES}/{symbol}")))
+//!         .await
+//!         .map_err(|e| ApiError::ServiceUnavailable(e.to_string()))?;
+//!     ...
+//! }
+//! ```
+//!
+//! The timeout is the route timeout of [`limits`](crate::App::with_route_timeout),
+//! so database work is cancelled with it too.
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::startup::RetryPolicy;
+
+/// Header set on fallback responses
+pub const X_FALLBACK: &str = "x-fallback";
+
+tokio::task_local! {
+    static CURRENT: Arc<RoutePolicy>;
+}
+
+type Fallback = Arc<dyn Fn() -> Response + Send + Sync>;
+
+/// Timeout, retries and fallback for one route, see the [module docs](self)
+#[derive(Clone, Default)]
+pub struct RoutePolicy {
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    fallback: Option<Fallback>,
+}
+
+impl RoutePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the request with 408 after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry idempotent downstream calls made while handling the request
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Serve `response` instead of timeouts and 5xx errors
+    pub fn with_fallback<R>(self, response: R) -> Self
+    where
+        R: IntoResponse + Clone + Send + Sync + 'static,
+    {
+        self.with_fallback_fn(move || response.clone().into_response())
+    }
+
+    /// Build the fallback response on demand, e.g. from a local cache
+    pub fn with_fallback_fn(mut self, fallback: impl Fn() -> Response + Send + Sync + 'static) -> Self {
+        self.fallback = Some(Arc::new(fallback));
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
+
+    /// The policy of the route this task is handling, if any
+    pub fn current() -> Option<Arc<RoutePolicy>> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+}
+
+impl std::fmt::Debug for RoutePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoutePolicy")
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry.is_some())
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+/// Run the request under its route's policy
+pub(crate) async fn apply(policies: Arc<HashMap<String, Arc<RoutePolicy>>>, request: Request, next: Next) -> Response {
+    let policy = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| policies.get(route.as_str()))
+        .cloned();
+    let Some(policy) = policy else {
+        return next.run(request).await;
+    };
+
+    let response = CURRENT.scope(policy.clone(), next.run(request)).await;
+    let failed = response.status().is_server_error() || response.status() == StatusCode::REQUEST_TIMEOUT;
+    match &policy.fallback {
+        Some(fallback) if failed => {
+            tracing::warn!(status = %response.status(), "Serving fallback response");
+            let mut fallback = fallback();
+            fallback.headers_mut().insert(X_FALLBACK, HeaderValue::from_static("true"));
+            fallback
+        }
+        _ => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ApiError, App};
+    use axum::{body::Body, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_timeout_and_fallback() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            "done"
+        };
+        let router = App::new()
+            .route("/slow", get(slow))
+            .route("/broken", get(|| async { ApiError::ServiceUnavailable("down".to_string()) }))
+            .route("/plain", get(|| async { ApiError::ServiceUnavailable("down".to_string()) }))
+            .with_route_policy(
+                "/slow",
+                RoutePolicy::new().with_timeout(Duration::from_millis(20)),
+            )
+            .with_route_policy("/broken", RoutePolicy::new().with_fallback("cached"))
+            .into_router();
+        let get = |uri: &str| router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        assert_eq!(get("/slow").await.unwrap().status(), StatusCode::REQUEST_TIMEOUT);
+        let response = get("/broken").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[X_FALLBACK], "true");
+        assert_eq!(get("/plain").await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -4,8 +4,14 @@
 
 pub use crate::{
     app::App,
-    error::{ApiError, ApiResult},
-    extractors::ValidatedJson,
+    client_ip::ClientInfo,
+    conditional::{Conditional, ETag},
+    dependencies::Dep,
+    error::{ApiError, ApiResult, WithDetails},
+    extractors::{ValidatedForm, ValidatedJson, ValidatedQuery},
+    middleware::RequestId,
+    openapi::{api_handler, RouteDoc},
+    pagination::{Paginated, Pagination, Sort, Sortable},
 };
 
 // Re-export commonly used types from dependencies
@@ -0,0 +1,292 @@
+//! Protocol Buffers request and response bodies
+//!
+//! [`Protobuf`] reads and writes `application/x-protobuf` bodies with
+//! [prost](https://docs.rs/prost) messages. Endpoints serving both internal
+//! protobuf consumers and external JSON ones use [`Negotiated`] for the
+//! request and [`BodyFormat`] for the response:
+//!
+//! ```rust,ignore
+//! #[derive(Clone, PartialEq, prost::Message, Serialize, Deserialize, ToSchema)]
+//! struct Order {
+//!     #[prost(string, tag = "1")]
+//!     id: String,
+//!     #[prost(uint32, tag = "2")]
+//!     quantity: u32,
+//! }
+//!
+//! async fn create_order(format: BodyFormat, Negotiated(order): Negotiated<Order>) -> Response {
+//!     format.respond(StatusCode::CREATED, save(order).await)
+//! }
+//!
+//! App::new().route_with_doc(
+//!     "/orders",
+//!     post(create_order),
+//!     RouteDoc::post().protobuf_request::<Order>().protobuf_response::<Order>(201, "Created order"),
+//! )
+//! ```
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::ApiError;
+
+/// Content type of protobuf bodies
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Other names clients use for protobuf bodies
+const PROTOBUF_ALIASES: [&str; 2] = ["application/protobuf", "application/vnd.google.protobuf"];
+
+fn is_protobuf(media_type: &str) -> bool {
+    media_type.eq_ignore_ascii_case(PROTOBUF_CONTENT_TYPE)
+        || PROTOBUF_ALIASES.iter().any(|alias| media_type.eq_ignore_ascii_case(alias))
+}
+
+fn is_json(media_type: &str) -> bool {
+    let media_type = media_type.to_ascii_lowercase();
+    media_type == "application/json" || (media_type.starts_with("application/") && media_type.ends_with("+json"))
+}
+
+fn content_type(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .unwrap_or_default()
+        .trim()
+}
+
+fn unsupported_media_type(expected: &str) -> ApiError {
+    ApiError::custom(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "UNSUPPORTED_MEDIA_TYPE",
+        format!("Expected request with `Content-Type: {}`", expected),
+    )
+}
+
+fn decode<T: Message + Default>(body: Bytes) -> Result<T, ApiError> {
+    T::decode(body).map_err(|error| {
+        tracing::error!("Protobuf decoding failed: {}", error);
+        ApiError::custom(StatusCode::BAD_REQUEST, "INVALID_PROTOBUF", format!("Invalid protobuf payload: {}", error))
+    })
+}
+
+fn encode<T: Message>(status: StatusCode, message: &T) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, HeaderValue::from_static(PROTOBUF_CONTENT_TYPE))],
+        message.encode_to_vec(),
+    )
+        .into_response()
+}
+
+/// Protobuf request body, or protobuf response
+///
+/// Requests must be sent with `Content-Type: application/x-protobuf` (or
+/// `application/protobuf`); other bodies are rejected with 415, and ones that
+/// don't decode with 400 `INVALID_PROTOBUF`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Protobuf<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Protobuf<T>
+where
+    T: Message + Default,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_protobuf(content_type(req.headers())) {
+            return Err(unsupported_media_type(PROTOBUF_CONTENT_TYPE).into_response());
+        }
+        let body = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        Ok(Protobuf(decode(body).map_err(IntoResponse::into_response)?))
+    }
+}
+
+impl<T: Message> IntoResponse for Protobuf<T> {
+    fn into_response(self) -> Response {
+        encode(StatusCode::OK, &self.0)
+    }
+}
+
+/// Request body read as protobuf or JSON, following its `Content-Type`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Negotiated<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Negotiated<T>
+where
+    T: Message + Default + DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = content_type(req.headers()).to_string();
+        if is_protobuf(&content_type) {
+            let Protobuf(value) = Protobuf::from_request(req, state).await?;
+            Ok(Negotiated(value))
+        } else if is_json(&content_type) {
+            let Json(value) = Json::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+            Ok(Negotiated(value))
+        } else {
+            Err(unsupported_media_type(&format!("application/json` or `{}", PROTOBUF_CONTENT_TYPE)).into_response())
+        }
+    }
+}
+
+/// Response format the client asked for with `Accept`
+///
+/// Protobuf when the client ranks a protobuf media type above JSON, JSON
+/// otherwise (including without `Accept`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+impl BodyFormat {
+    /// Format preferred by an `Accept` header value
+    pub fn from_accept(accept: &str) -> Self {
+        let (mut json, mut protobuf) = (None::<f32>, None::<f32>);
+        for range in accept.split(',') {
+            let mut parts = range.split(';');
+            let media_type = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            let slot = if is_protobuf(media_type) {
+                &mut protobuf
+            } else if is_json(media_type) || media_type == "*/*" || media_type == "application/*" {
+                &mut json
+            } else {
+                continue;
+            };
+            *slot = Some(slot.map_or(quality, |current| current.max(quality)));
+        }
+        match (protobuf, json) {
+            (Some(protobuf), json) if protobuf > 0.0 && protobuf > json.unwrap_or(0.0) => BodyFormat::Protobuf,
+            _ => BodyFormat::Json,
+        }
+    }
+
+    /// `value` encoded in this format with `status`
+    pub fn respond<T: Message + Serialize>(self, status: StatusCode, value: T) -> Response {
+        match self {
+            BodyFormat::Json => (status, Json(value)).into_response(),
+            BodyFormat::Protobuf => encode(status, &value),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for BodyFormat {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map(BodyFormat::from_accept)
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Clone, PartialEq, Message, Serialize, Deserialize, utoipa::ToSchema)]
+    struct Order {
+        #[prost(string, tag = "1")]
+        id: String,
+        #[prost(uint32, tag = "2")]
+        quantity: u32,
+    }
+
+    fn order() -> Order {
+        Order {
+            id: "o-1".to_string(),
+            quantity: 3,
+        }
+    }
+
+    #[test]
+    fn test_accept_negotiation() {
+        assert_eq!(BodyFormat::from_accept("application/x-protobuf"), BodyFormat::Protobuf);
+        assert_eq!(BodyFormat::from_accept("application/json, application/protobuf;q=0.5"), BodyFormat::Json);
+        assert_eq!(BodyFormat::from_accept("application/json;q=0.5, application/x-protobuf"), BodyFormat::Protobuf);
+        assert_eq!(BodyFormat::from_accept("application/x-protobuf;q=0, */*"), BodyFormat::Json);
+        assert_eq!(BodyFormat::from_accept("text/html"), BodyFormat::Json);
+    }
+
+    #[tokio::test]
+    async fn test_protobuf_and_json_bodies() {
+        let router = Router::new()
+            .route("/strict", post(|Protobuf(order): Protobuf<Order>| async move { Protobuf(order) }))
+            .route(
+                "/orders",
+                post(|format: BodyFormat, Negotiated(order): Negotiated<Order>| async move {
+                    format.respond(StatusCode::CREATED, order)
+                }),
+            );
+        let send = |uri: &str, content_type: &str, accept: &str, body: Vec<u8>| {
+            router.clone().oneshot(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::ACCEPT, accept)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let body = |response: Response| async { axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap() };
+
+        let response = send("/strict", PROTOBUF_CONTENT_TYPE, "*/*", order().encode_to_vec()).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROTOBUF_CONTENT_TYPE);
+        assert_eq!(Order::decode(body(response).await).unwrap(), order());
+
+        let json = serde_json::to_vec(&order()).unwrap();
+        let response = send("/strict", "application/json", "*/*", json.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let response = send("/strict", PROTOBUF_CONTENT_TYPE, "*/*", vec![0xff, 0xff]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // JSON in, protobuf out, and the other way round
+        let response = send("/orders", "application/json", PROTOBUF_CONTENT_TYPE, json).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(Order::decode(body(response).await).unwrap(), order());
+
+        let response = send("/orders", "application/protobuf", "application/json", order().encode_to_vec()).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(serde_json::from_slice::<Order>(&body(response).await).unwrap(), order());
+    }
+
+    #[test]
+    fn test_route_doc_lists_both_formats() {
+        let mut docs = crate::openapi::ApiDocs::default();
+        docs.add_route(
+            "/orders",
+            crate::RouteDoc::post().protobuf_request::<Order>().protobuf_response::<Order>(201, "Created"),
+        );
+        let operation = &docs.to_json()["paths"]["/orders"]["post"];
+        for content in [&operation["requestBody"]["content"], &operation["responses"]["201"]["content"]] {
+            assert_eq!(content["application/json"], content[PROTOBUF_CONTENT_TYPE]);
+            assert_eq!(content[PROTOBUF_CONTENT_TYPE]["schema"]["$ref"], "#/components/schemas/Order");
+        }
+    }
+}
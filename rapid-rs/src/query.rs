@@ -0,0 +1,459 @@
+//! Query strings with arrays and nested objects
+//!
+//! `serde_urlencoded` only understands flat `key=value` pairs, so the
+//! formats frontends commonly send fail to deserialize. With the default
+//! [`QueryMode::Nested`], [`ValidatedQuery`](crate::ValidatedQuery) accepts:
+//!
+//! - repeated keys: `?tag=a&tag=b`
+//! - bracketed arrays: `?tag[]=a&tag[]=b` or `?tag[0]=a&tag[1]=b`
+//! - nested maps: `?filter[status]=active&filter[owner][id]=7`
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize, Validate)]
+//! struct Search {
+//!     #[validate(length(max = 5))]
+//!     tag: Vec<String>,
+//!     filter: HashMap<String, String>,
+//! }
+//!
+//! async fn search(ValidatedQuery(search): ValidatedQuery<Search>) -> ApiResult<Vec<Item>> { ... }
+//! ```
+//!
+//! Pick another mode with [`ValidationConfig::with_query_mode`](crate::ValidationConfig::with_query_mode).
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use std::fmt;
+
+/// How query strings are turned into values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueryMode {
+    /// Plain `serde_urlencoded`: one value per key, no nesting
+    Flat,
+    /// Repeated keys and `key[]` build arrays, `key[name]` builds maps
+    #[default]
+    Nested,
+    /// Like `Nested`, and a single `tag=a,b` also fills an array
+    NestedWithCommas,
+}
+
+/// Why a query string didn't fit its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError(String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl de::Error for QueryError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        QueryError(msg.to_string())
+    }
+}
+
+/// Deserialize `query` (without the leading `?`), reporting the path of the
+/// offending value
+pub fn from_query<T: DeserializeOwned>(query: &str, mode: QueryMode) -> Result<T, serde_path_to_error::Error<QueryError>> {
+    if mode == QueryMode::Flat {
+        let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+        return serde_path_to_error::deserialize(deserializer).map_err(|error| {
+            let path = error.path().clone();
+            serde_path_to_error::Error::new(path, QueryError(error.into_inner().to_string()))
+        });
+    }
+
+    let deserializer = NodeDeserializer {
+        node: parse(query),
+        commas: mode == QueryMode::NestedWithCommas,
+    };
+    serde_path_to_error::deserialize(deserializer)
+}
+
+/// The query as JSON, for async validation rules
+pub(crate) fn to_json(query: &str, mode: QueryMode) -> serde_json::Value {
+    if mode == QueryMode::Flat {
+        let pairs: Vec<_> = form_urlencoded::parse(query.as_bytes()).collect();
+        return crate::extractors::form_json(pairs.iter().map(|(key, value)| (key.as_ref(), value.as_ref())));
+    }
+    parse(query).into_json()
+}
+
+fn parse(query: &str) -> Node {
+    let mut root = Node::Map(Vec::new());
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        root.insert(&segments(&key), value.into_owned());
+    }
+    root
+}
+
+/// `filter[owner][id]` as `["filter", "owner", "id"]`; `tag[]` ends in `""`
+fn segments(key: &str) -> Vec<&str> {
+    let Some((head, mut rest)) = key.split_once('[') else {
+        return vec![key];
+    };
+    let mut segments = vec![head];
+    loop {
+        match rest.split_once(']') {
+            Some((segment, after)) => {
+                segments.push(segment);
+                match after.strip_prefix('[') {
+                    Some(next) => rest = next,
+                    // Keep anything after the last bracket as a plain key
+                    None => return segments,
+                }
+            }
+            None => return vec![key],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Value(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn insert(&mut self, path: &[&str], value: String) {
+        let Some((first, rest)) = path.split_first() else {
+            self.push(Node::Value(value));
+            return;
+        };
+
+        if first.is_empty() {
+            // `tag[]`: append, nesting further if the key continues
+            let mut node = Node::Map(Vec::new());
+            if rest.is_empty() {
+                node = Node::Value(value);
+            } else {
+                node.insert(rest, value);
+            }
+            self.push(node);
+            return;
+        }
+
+        if let Node::Value(_) = self {
+            *self = Node::Map(Vec::new());
+        }
+        let entries = match self {
+            Node::Map(entries) => entries,
+            Node::List(items) => {
+                // `tag=a&tag[x]=b`: hang the map off the last item
+                if !matches!(items.last(), Some(Node::Map(_))) {
+                    items.push(Node::Map(Vec::new()));
+                }
+                return items.last_mut().unwrap().insert(path, value);
+            }
+            Node::Value(_) => unreachable!(),
+        };
+
+        match entries.iter_mut().find(|(key, _)| key == first) {
+            Some((_, node)) if rest.is_empty() => node.push(Node::Value(value)),
+            Some((_, node)) => node.insert(rest, value),
+            None => {
+                let node = if rest.is_empty() {
+                    Node::Value(value)
+                } else if rest[0].is_empty() {
+                    let mut node = Node::List(Vec::new());
+                    node.insert(rest, value);
+                    node
+                } else {
+                    let mut node = Node::Map(Vec::new());
+                    node.insert(rest, value);
+                    node
+                };
+                entries.push((first.to_string(), node));
+            }
+        }
+    }
+
+    /// Add `node` as another value for this key
+    fn push(&mut self, node: Node) {
+        match self {
+            Node::List(items) => items.push(node),
+            existing => {
+                let previous = std::mem::replace(existing, Node::List(Vec::new()));
+                *existing = Node::List(vec![previous, node]);
+            }
+        }
+    }
+
+    fn into_json(self) -> serde_json::Value {
+        match self {
+            Node::Value(value) => serde_json::Value::String(value),
+            Node::List(items) => items.into_iter().map(Node::into_json).collect(),
+            Node::Map(entries) => entries
+                .into_iter()
+                .map(|(key, node)| (key, node.into_json()))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Node::Value(value) => Unexpected::Str(value),
+            Node::List(_) => Unexpected::Seq,
+            Node::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+struct NodeDeserializer {
+    node: Node,
+    commas: bool,
+}
+
+impl NodeDeserializer {
+    fn child(&self, node: Node) -> Self {
+        Self { node, commas: self.commas }
+    }
+
+    fn invalid_type(&self, expected: &dyn de::Expected) -> QueryError {
+        de::Error::invalid_type(self.node.unexpected(), expected)
+    }
+
+    /// The items of a list, also from `a[0]=x&a[1]=y` or a single value
+    fn into_items(self) -> Vec<Node> {
+        match self.node {
+            Node::List(items) => items,
+            Node::Map(entries) if entries.iter().all(|(key, _)| key.parse::<usize>().is_ok()) => {
+                let mut entries = entries;
+                entries.sort_by_key(|(key, _)| key.parse::<usize>().unwrap_or_default());
+                entries.into_iter().map(|(_, node)| node).collect()
+            }
+            Node::Value(value) if self.commas => {
+                value.split(',').map(|item| Node::Value(item.to_string())).collect()
+            }
+            node => vec![node],
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, QueryError> for NodeDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+            match &self.node {
+                Node::Value(value) => match value.trim().parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(value), &visitor)),
+                },
+                _ => Err(self.invalid_type(&visitor)),
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for NodeDeserializer {
+    type Error = QueryError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.node {
+            Node::Value(value) => visitor.visit_string(value),
+            Node::List(_) => self.deserialize_seq(visitor),
+            Node::Map(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.node {
+            Node::Value(value) => visitor.visit_string(value),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_string(visitor)
+    }
+
+    /// `?page=` counts as no page
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match &self.node {
+            Node::Value(value) if value.is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        let commas = self.commas;
+        let items = self.into_items().into_iter().map(|node| NodeDeserializer { node, commas });
+        let mut seq = de::value::SeqDeserializer::new(items);
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.node {
+            Node::Map(ref entries) => {
+                let entries: Vec<_> = entries.iter().map(|(key, node)| (key.clone(), self.child(node.clone()))).collect();
+                let mut map = de::value::MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        match self.node {
+            Node::Value(value) => visitor.visit_enum(IntoDeserializer::<QueryError>::into_deserializer(value)),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        #[serde(default)]
+        tag: Vec<String>,
+        #[serde(default)]
+        filter: HashMap<String, String>,
+        page: Option<u32>,
+    }
+
+    fn search(query: &str, mode: QueryMode) -> Search {
+        from_query(query, mode).unwrap()
+    }
+
+    #[test]
+    fn test_arrays_and_nested_maps() {
+        let tags = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(search("tag=a&tag=b", QueryMode::Nested).tag, tags);
+        assert_eq!(search("tag[]=a&tag[]=b", QueryMode::Nested).tag, tags);
+        assert_eq!(search("tag%5B1%5D=b&tag%5B0%5D=a", QueryMode::Nested).tag, tags);
+        assert_eq!(search("tag=a", QueryMode::Nested).tag, ["a"]);
+        assert_eq!(search("tag=a,b", QueryMode::Nested).tag, ["a,b"]);
+        assert_eq!(search("tag=a,b", QueryMode::NestedWithCommas).tag, tags);
+
+        let parsed = search("filter[status]=active&filter[owner]=me&page=2", QueryMode::Nested);
+        assert_eq!(parsed.filter["status"], "active");
+        assert_eq!(parsed.filter["owner"], "me");
+        assert_eq!(parsed.page, Some(2));
+        assert_eq!(search("page=", QueryMode::Nested).page, None);
+    }
+
+    #[test]
+    fn test_deep_objects() {
+        #[derive(Debug, Deserialize)]
+        struct Owner {
+            id: u64,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Filter {
+            owner: Owner,
+            statuses: Vec<String>,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Query {
+            filter: Filter,
+        }
+
+        let query: Query =
+            from_query("filter[owner][id]=7&filter[statuses][]=open&filter[statuses][]=closed", QueryMode::Nested)
+                .unwrap();
+        assert_eq!(query.filter.owner.id, 7);
+        assert_eq!(query.filter.statuses, ["open", "closed"]);
+
+        let error = from_query::<Query>("filter[owner][id]=seven&filter[statuses]=x", QueryMode::Nested).unwrap_err();
+        assert_eq!(error.path().to_string(), "filter.owner.id");
+    }
+
+    #[test]
+    fn test_flat_mode() {
+        assert_eq!(search("page=3", QueryMode::Flat).page, Some(3));
+        // serde_urlencoded has no sequences
+        assert!(from_query::<Search>("tag=a", QueryMode::Flat).is_err());
+    }
+}
@@ -2,21 +2,26 @@
 
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Utc};
 use governor::{
-    clock::DefaultClock,
+    clock::Clock,
+    middleware::StateInformationMiddleware,
+    nanos::Nanos,
     state::{InMemoryState, NotKeyed},
     Quota, RateLimiter as GovernorRateLimiter,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::num::NonZeroU32;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
 
+use crate::clock::SharedClock;
+
 /// Rate limit configuration
 #[derive(Debug, Clone)]
 pub struct RateLimitConfig {
@@ -40,26 +45,233 @@ impl Default for RateLimitConfig {
     }
 }
 
+/// Feeds a [`SharedClock`] to governor as time elapsed since creation
+#[derive(Clone)]
+struct LimiterClock {
+    clock: SharedClock,
+    origin: DateTime<Utc>,
+}
+
+impl governor::clock::Clock for LimiterClock {
+    type Instant = Nanos;
+    
+    fn now(&self) -> Nanos {
+        Nanos::from((self.clock.now() - self.origin).to_std().unwrap_or_default())
+    }
+}
+
+/// How much of the budget requests to matching endpoints consume
+#[derive(Debug, Clone)]
+struct EndpointCost {
+    method: Option<Method>,
+    path: String,
+    cost: u32,
+}
+
+impl EndpointCost {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.method.as_ref().is_none_or(|m| m == method) && path_matches(&self.path, path)
+    }
+}
+
+/// Whether `path` matches a route pattern with `:param` and `*rest` segments
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern = pattern.trim_matches('/').split('/');
+    let mut path = path.trim_matches('/').split('/');
+    loop {
+        match (pattern.next(), path.next()) {
+            (Some(p), Some(_)) if p.starts_with('*') => return true,
+            (Some(p), Some(s)) if p.starts_with(':') && !s.is_empty() => {}
+            (Some(p), Some(s)) if p == s => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Outcome of charging a request against the budget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// Budget when full
+    pub limit: u32,
+    /// Budget left after this request
+    pub remaining: u32,
+    /// What this request cost
+    pub cost: u32,
+    /// Set when the request was rejected
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitStatus {
+    pub fn is_allowed(&self) -> bool {
+        self.retry_after.is_none()
+    }
+
+    /// `X-RateLimit-*` headers, plus `Retry-After` when rejected
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-cost", HeaderValue::from(self.cost));
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(axum::http::header::RETRY_AFTER, HeaderValue::from(retry_after_seconds(retry_after)));
+        }
+        headers
+    }
+}
+
+fn retry_after_seconds(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
 /// Rate limiter
+///
+/// Every request costs 1 unless an endpoint declares a higher cost, so
+/// expensive endpoints use up a client's budget faster:
+///
+/// ```rust,ignore
+/// let limiter = RateLimiter::new(RateLimitConfig::per_minute(100))
+///     .with_cost("/search", 5)
+///     .with_method_cost(Method::POST, "/reports/:id/export", 20);
+/// ```
 #[derive(Clone)]
 pub struct RateLimiter {
-    limiter: Arc<GovernorRateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
+    limiter: Arc<GovernorRateLimiter<NotKeyed, InMemoryState, LimiterClock, StateInformationMiddleware>>,
+    clock: LimiterClock,
+    burst_size: u32,
+    replenish: Duration,
+    costs: Arc<Vec<EndpointCost>>,
+    last: Arc<Mutex<BucketSnapshot>>,
+}
+
+/// Budget left at a point in time, for carrying a limiter across restarts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketSnapshot {
+    pub remaining: u32,
+    pub taken_at: DateTime<Utc>,
 }
 
 impl RateLimiter {
     pub fn new(config: RateLimitConfig) -> Self {
+        Self::with_clock(config, crate::clock::system())
+    }
+    
+    /// Rate limiter whose budget refills according to `clock`
+    pub fn with_clock(config: RateLimitConfig, clock: SharedClock) -> Self {
         let quota = Quota::with_period(config.period)
             .unwrap()
             .allow_burst(NonZeroU32::new(config.burst_size).unwrap());
+        let clock = LimiterClock {
+            origin: clock.now(),
+            clock,
+        };
         
         Self {
-            limiter: Arc::new(GovernorRateLimiter::direct(quota)),
+            limiter: Arc::new(
+                GovernorRateLimiter::direct_with_clock(quota, &clock).with_middleware::<StateInformationMiddleware>(),
+            ),
+            last: Arc::new(Mutex::new(BucketSnapshot {
+                remaining: config.burst_size,
+                taken_at: clock.clock.now(),
+            })),
+            clock,
+            burst_size: config.burst_size,
+            replenish: config.period,
+            costs: Arc::new(Vec::new()),
         }
     }
     
+    /// Charge `cost` for requests to `path`, with any method
+    ///
+    /// `path` is a route pattern like `/users/:id` or `/files/*rest`. The
+    /// first matching declaration wins; costs above the burst size use up the
+    /// whole budget.
+    pub fn with_cost(self, path: impl Into<String>, cost: u32) -> Self {
+        self.push_cost(None, path.into(), cost)
+    }
+    
+    /// Charge `cost` for `method` requests to `path`
+    pub fn with_method_cost(self, method: Method, path: impl Into<String>, cost: u32) -> Self {
+        self.push_cost(Some(method), path.into(), cost)
+    }
+    
+    fn push_cost(mut self, method: Option<Method>, path: String, cost: u32) -> Self {
+        Arc::make_mut(&mut self.costs).push(EndpointCost { method, path, cost });
+        self
+    }
+    
+    /// Declared cost of a request, 1 by default
+    pub fn cost_of(&self, method: &Method, path: &str) -> u32 {
+        self.costs
+            .iter()
+            .find(|endpoint| endpoint.matches(method, path))
+            .map_or(1, |endpoint| endpoint.cost)
+    }
+    
     /// Check if request is allowed
     pub fn check(&self) -> bool {
-        self.limiter.check().is_ok()
+        self.check_cost(1).is_allowed()
+    }
+    
+    /// Take `cost` from the budget if it's all available
+    pub fn check_cost(&self, cost: u32) -> RateLimitStatus {
+        let cost = cost.clamp(1, self.burst_size);
+        let n = NonZeroU32::new(cost).expect("cost is at least 1");
+        let mut status = RateLimitStatus {
+            limit: self.burst_size,
+            remaining: 0,
+            cost,
+            retry_after: None,
+        };
+        match self.limiter.check_n(n).expect("cost is within the burst size") {
+            Ok(snapshot) => {
+                status.remaining = snapshot.remaining_burst_capacity();
+                self.record(status.remaining);
+            }
+            Err(not_until) => status.retry_after = Some(not_until.wait_time_from(self.clock.now())),
+        }
+        status
+    }
+    
+    fn record(&self, remaining: u32) {
+        *self.last.lock().unwrap() = BucketSnapshot {
+            remaining,
+            taken_at: self.clock.clock.now(),
+        };
+    }
+    
+    /// Budget left as of the last allowed request
+    pub fn snapshot(&self) -> BucketSnapshot {
+        self.last.lock().unwrap().clone()
+    }
+    
+    /// Pick up from a snapshot taken by a previous instance
+    ///
+    /// Budget that would have refilled since the snapshot was taken is
+    /// credited back. Call on a fresh limiter, before it serves requests.
+    pub fn restore(&self, snapshot: &BucketSnapshot) {
+        let elapsed = (self.clock.clock.now() - snapshot.taken_at).to_std().unwrap_or_default();
+        let refilled = elapsed.as_nanos() / self.replenish.as_nanos().max(1);
+        let remaining = (u128::from(snapshot.remaining) + refilled).min(u128::from(self.burst_size)) as u32;
+        
+        if let Some(used) = NonZeroU32::new(self.burst_size - remaining) {
+            let _ = self.limiter.check_n(used);
+        }
+        self.record(remaining);
+    }
+    
+    /// How long an empty budget takes to refill completely
+    pub fn refill_time(&self) -> Duration {
+        self.replenish * self.burst_size
+    }
+    
+    /// The latest snapshot, without keeping the limiter alive
+    pub(crate) fn weak_snapshot(&self) -> Weak<Mutex<BucketSnapshot>> {
+        Arc::downgrade(&self.last)
+    }
+    
+    pub(crate) fn clock(&self) -> SharedClock {
+        self.clock.clock.clone()
     }
 }
 
@@ -71,21 +283,32 @@ struct RateLimitError {
 }
 
 /// Rate limiting middleware
+///
+/// Charges each request its endpoint's cost and reports the budget in
+/// `X-RateLimit-*` headers.
 pub async fn rate_limit_middleware(
     State(limiter): State<RateLimiter>,
     request: Request,
     next: Next,
 ) -> Response {
-    if limiter.check() {
-        next.run(request).await
-    } else {
-        let error = RateLimitError {
-            code: "RATE_LIMIT_EXCEEDED".to_string(),
-            message: "Too many requests. Please try again later.".to_string(),
-            retry_after_seconds: 60,
-        };
-        
-        (StatusCode::TOO_MANY_REQUESTS, Json(error)).into_response()
+    let cost = limiter.cost_of(request.method(), request.uri().path());
+    let status = limiter.check_cost(cost);
+    
+    match status.retry_after {
+        None => {
+            let mut response = next.run(request).await;
+            response.headers_mut().extend(status.headers());
+            response
+        }
+        Some(retry_after) => {
+            let error = RateLimitError {
+                code: "RATE_LIMIT_EXCEEDED".to_string(),
+                message: "Too many requests. Please try again later.".to_string(),
+                retry_after_seconds: retry_after_seconds(retry_after),
+            };
+            
+            (StatusCode::TOO_MANY_REQUESTS, status.headers(), Json(error)).into_response()
+        }
     }
 }
 
@@ -110,4 +333,83 @@ mod tests {
         // Third should fail
         assert!(!limiter.check());
     }
+    
+    #[test]
+    fn test_rate_limiter_refills_with_clock() {
+        let clock = crate::clock::ManualClock::frozen();
+        let config = RateLimitConfig {
+            requests_per_period: 1,
+            period: Duration::from_secs(10),
+            burst_size: 1,
+        };
+        let limiter = RateLimiter::with_clock(config, clock.shared());
+        
+        assert!(limiter.check());
+        assert!(!limiter.check());
+        
+        clock.advance(Duration::from_secs(10));
+        assert!(limiter.check());
+    }
+    
+    #[test]
+    fn test_snapshot_restore() {
+        let clock = crate::clock::ManualClock::frozen();
+        let config = RateLimitConfig {
+            requests_per_period: 10,
+            period: Duration::from_secs(1),
+            burst_size: 10,
+        };
+        let limiter = RateLimiter::with_clock(config.clone(), clock.shared());
+        assert_eq!(limiter.check_cost(8).remaining, 2);
+        let snapshot = limiter.snapshot();
+        assert_eq!(snapshot.remaining, 2);
+        
+        // A restarted instance keeps the spent budget, less what refilled meanwhile
+        clock.advance(Duration::from_secs(3));
+        let restarted = RateLimiter::with_clock(config, clock.shared());
+        restarted.restore(&snapshot);
+        assert_eq!(restarted.snapshot().remaining, 5);
+        assert!(restarted.check_cost(5).is_allowed());
+        assert!(!restarted.check());
+    }
+    
+    #[tokio::test]
+    async fn test_endpoint_costs() {
+        use axum::{body::Body, routing::get, Router};
+        use tower::ServiceExt;
+        
+        let clock = crate::clock::ManualClock::frozen();
+        let config = RateLimitConfig {
+            requests_per_period: 10,
+            period: Duration::from_secs(1),
+            burst_size: 10,
+        };
+        let limiter = RateLimiter::with_clock(config, clock.shared())
+            .with_cost("/search", 5)
+            .with_method_cost(Method::DELETE, "/users/:id", 3);
+        assert_eq!(limiter.cost_of(&Method::GET, "/search"), 5);
+        assert_eq!(limiter.cost_of(&Method::GET, "/users/7"), 1);
+        assert_eq!(limiter.cost_of(&Method::DELETE, "/users/7"), 3);
+        
+        let app = Router::new()
+            .route("/search", get(|| async { "results" }))
+            .route("/users/:id", get(|| async { "user" }))
+            .layer(axum::middleware::from_fn_with_state(limiter, rate_limit_middleware));
+        let call = |uri: &'static str| app.clone().oneshot(axum::http::Request::get(uri).body(Body::empty()).unwrap());
+        
+        let response = call("/search").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-ratelimit-cost"], "5");
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "5");
+        
+        call("/search").await.unwrap();
+        let response = call("/users/1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "1");
+        
+        clock.advance(Duration::from_secs(1));
+        let response = call("/users/1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+    }
 }
\ No newline at end of file
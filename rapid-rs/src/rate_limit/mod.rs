@@ -1,8 +1,10 @@
 //! Rate limiting middleware
 
 pub mod middleware;
+pub mod persistence;
 
-pub use middleware::{RateLimiter, RateLimitConfig, rate_limit_middleware};
+pub use middleware::{BucketSnapshot, RateLimiter, RateLimitConfig, RateLimitStatus, rate_limit_middleware};
+pub use persistence::{FileLimiterStore, LimiterStore};
 
 use std::time::Duration;
 
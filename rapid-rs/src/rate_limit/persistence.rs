@@ -0,0 +1,160 @@
+//! Rate limiter state that survives restarts
+//!
+//! In-memory budgets reset on every deploy, letting every client send a full
+//! burst at once. Persisting snapshots keeps spent budget spent:
+//!
+//! ```rust,ignore
+//! let store = Arc::new(FileLimiterStore::new("/var/lib/api/rate-limits"));
+//! let limiter = RateLimiter::new(RateLimitConfig::per_minute(100))
+//!     .persisted(store.clone(), "api", Duration::from_secs(5))
+//!     .await?;
+//!
+//! App::new()
+//!     .on_shutdown({
+//!         let limiter = limiter.clone();
+//!         move || async move { limiter.save_to(store.as_ref(), "api").await.ok(); }
+//!     })
+//! ```
+
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::middleware::{BucketSnapshot, RateLimiter};
+use crate::error::ApiError;
+
+/// Where limiter snapshots are kept between runs
+#[async_trait]
+pub trait LimiterStore: Send + Sync {
+    async fn load(&self, key: &str) -> Result<Option<BucketSnapshot>, ApiError>;
+
+    /// `ttl` is how long until the snapshot no longer matters because the
+    /// budget would have refilled
+    async fn save(&self, key: &str, snapshot: &BucketSnapshot, ttl: Duration) -> Result<(), ApiError>;
+}
+
+/// Snapshots as JSON files in a directory, one per key
+#[derive(Debug, Clone)]
+pub struct FileLimiterStore {
+    dir: PathBuf,
+}
+
+impl FileLimiterStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+#[async_trait]
+impl LimiterStore for FileLimiterStore {
+    async fn load(&self, key: &str) -> Result<Option<BucketSnapshot>, ApiError> {
+        match tokio::fs::read(self.path(key)).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ApiError::InternalServerError(format!("Failed to read rate limit snapshot: {}", e))),
+        }
+    }
+
+    async fn save(&self, key: &str, snapshot: &BucketSnapshot, _ttl: Duration) -> Result<(), ApiError> {
+        let write = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            // Write then rename, so a crash never leaves a torn file
+            let tmp = self.dir.join(format!(".{}.json.tmp", key));
+            tokio::fs::write(&tmp, serde_json::to_vec(snapshot)?).await?;
+            tokio::fs::rename(&tmp, self.path(key)).await
+        };
+        write
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to write rate limit snapshot: {}", e)))
+    }
+}
+
+/// Snapshots in the app cache; use the Redis backend to share them across
+/// instances and deploys
+#[cfg(feature = "cache")]
+#[async_trait]
+impl LimiterStore for crate::cache::Cache {
+    async fn load(&self, key: &str) -> Result<Option<BucketSnapshot>, ApiError> {
+        self.get(&format!("rate_limit:{}", key)).await
+    }
+
+    async fn save(&self, key: &str, snapshot: &BucketSnapshot, ttl: Duration) -> Result<(), ApiError> {
+        self.set(&format!("rate_limit:{}", key), snapshot, ttl).await
+    }
+}
+
+impl RateLimiter {
+    /// Restore from `store`, then save a snapshot every `interval`
+    ///
+    /// Saving stops once every clone of the limiter is dropped.
+    pub async fn persisted(
+        self,
+        store: Arc<dyn LimiterStore>,
+        key: impl Into<String>,
+        interval: Duration,
+    ) -> Result<Self, ApiError> {
+        let key = key.into();
+        if let Some(snapshot) = store.load(&key).await? {
+            self.restore(&snapshot);
+            tracing::debug!(key = %key, remaining = snapshot.remaining, "Restored rate limiter");
+        }
+
+        let last = self.weak_snapshot();
+        let clock = self.clock();
+        let ttl = self.refill_time();
+        tokio::spawn(async move {
+            loop {
+                clock.sleep(interval).await;
+                let Some(last) = last.upgrade() else { break };
+                let snapshot = last.lock().unwrap().clone();
+                if let Err(e) = store.save(&key, &snapshot, ttl).await {
+                    tracing::warn!(key = %key, error = %e, "Failed to save rate limiter snapshot");
+                }
+            }
+        });
+
+        Ok(self)
+    }
+
+    /// Save the current snapshot, e.g. from a shutdown hook
+    pub async fn save_to(&self, store: &dyn LimiterStore, key: &str) -> Result<(), ApiError> {
+        store.save(key, &self.snapshot(), self.refill_time()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::RateLimitConfig;
+
+    #[tokio::test]
+    async fn test_file_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-limits-{}", uuid::Uuid::new_v4()));
+        let store: Arc<dyn LimiterStore> = Arc::new(FileLimiterStore::new(&dir));
+        let config = RateLimitConfig {
+            requests_per_period: 10,
+            period: Duration::from_secs(60),
+            burst_size: 10,
+        };
+
+        let limiter = RateLimiter::new(config.clone())
+            .persisted(store.clone(), "api", Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert!(limiter.check_cost(7).is_allowed());
+        limiter.save_to(store.as_ref(), "api").await.unwrap();
+
+        let restarted = RateLimiter::new(config)
+            .persisted(store, "api", Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(restarted.snapshot().remaining, 3);
+        assert!(!restarted.check_cost(4).is_allowed());
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
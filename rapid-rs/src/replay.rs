@@ -0,0 +1,459 @@
+//! Capturing failed requests to replay them elsewhere
+//!
+//! With [`App::with_request_capture`](crate::App::with_request_capture), requests
+//! that match a [`RequestCapture`] filter (by default: answered with a 5xx)
+//! are recorded into a [`CaptureStore`], with credentials and sensitive body
+//! fields redacted. [`routes`] lists them, and with the `http-client` feature
+//! `POST /captures/:id/replay` or `rapid replay` sends one to a dev instance
+//! to reproduce the bug:
+//!
+//! ```rust,ignore
+//! let captures = Arc::new(MemoryCaptureStore::new(200));
+//!
+//! App::new()
+//!     .auto_configure()
+//!     .with_request_capture(RequestCapture::new(captures.clone()).with_route("/orders"))
+//!     .mount(Router::new().nest("/admin", replay::routes(captures)).route_layer(admin_only))
+//! ```
+//!
+//! ```text
+//! rapid replay https://api.example.com/admin/captures --id 01J... --target http://localhost:3000
+//! ```
+
+use axum::{
+    async_trait,
+    body::{Body, HttpBody},
+    extract::{MatchedPath, Path, Request, State},
+    http::{header, HeaderName},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::error::ApiError;
+use crate::middleware::RequestId;
+
+/// Value sent in place of redacted headers and fields
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A recorded request and the status it got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedRequest {
+    pub id: String,
+    pub captured_at: DateTime<Utc>,
+    pub method: String,
+    /// Path and query
+    pub uri: String,
+    /// Route pattern, e.g. `/orders/:id`
+    pub route: Option<String>,
+    pub headers: Vec<(String, String)>,
+    /// `None` for empty, binary or oversized bodies
+    pub body: Option<String>,
+    pub status: u16,
+    pub request_id: Option<String>,
+}
+
+/// Where captured requests are kept
+#[async_trait]
+pub trait CaptureStore: Send + Sync + 'static {
+    async fn save(&self, capture: CapturedRequest) -> Result<(), ApiError>;
+
+    /// Most recent first
+    async fn list(&self, limit: usize) -> Result<Vec<CapturedRequest>, ApiError>;
+
+    async fn get(&self, id: &str) -> Result<Option<CapturedRequest>, ApiError>;
+}
+
+/// Keeps the last `capacity` captures in memory
+pub struct MemoryCaptureStore {
+    capacity: usize,
+    captures: Mutex<VecDeque<CapturedRequest>>,
+}
+
+impl MemoryCaptureStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            captures: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl CaptureStore for MemoryCaptureStore {
+    async fn save(&self, capture: CapturedRequest) -> Result<(), ApiError> {
+        let mut captures = self.captures.lock().unwrap();
+        if captures.len() == self.capacity {
+            captures.pop_back();
+        }
+        captures.push_front(capture);
+        Ok(())
+    }
+
+    async fn list(&self, limit: usize) -> Result<Vec<CapturedRequest>, ApiError> {
+        Ok(self.captures.lock().unwrap().iter().take(limit).cloned().collect())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<CapturedRequest>, ApiError> {
+        Ok(self.captures.lock().unwrap().iter().find(|capture| capture.id == id).cloned())
+    }
+}
+
+/// Which requests are captured and what is redacted
+#[derive(Clone)]
+pub struct RequestCapture {
+    store: Arc<dyn CaptureStore>,
+    routes: Vec<String>,
+    min_status: u16,
+    redacted_headers: Vec<HeaderName>,
+    redacted_fields: Vec<String>,
+    max_body_size: usize,
+}
+
+impl RequestCapture {
+    /// Capture every request answered with a 5xx
+    pub fn new(store: Arc<dyn CaptureStore>) -> Self {
+        Self {
+            store,
+            routes: Vec::new(),
+            min_status: 500,
+            redacted_headers: vec![
+                header::AUTHORIZATION,
+                header::PROXY_AUTHORIZATION,
+                header::COOKIE,
+                HeaderName::from_static("x-api-key"),
+            ],
+            redacted_fields: vec!["password".to_string(), "secret".to_string(), "token".to_string()],
+            max_body_size: 64 * 1024,
+        }
+    }
+
+    /// Only capture requests whose path starts with `prefix` (repeatable)
+    pub fn with_route(mut self, prefix: impl Into<String>) -> Self {
+        self.routes.push(prefix.into());
+        self
+    }
+
+    /// Capture responses with at least this status (default 500)
+    pub fn with_min_status(mut self, status: u16) -> Self {
+        self.min_status = status;
+        self
+    }
+
+    pub fn with_redacted_header(mut self, name: HeaderName) -> Self {
+        self.redacted_headers.push(name);
+        self
+    }
+
+    /// Redact JSON and form fields whose name contains `pattern`
+    /// (`password`, `secret` and `token` by default)
+    pub fn with_redacted_field(mut self, pattern: impl Into<String>) -> Self {
+        self.redacted_fields.push(pattern.into().to_lowercase());
+        self
+    }
+
+    /// Bodies up to this size are kept (default 64 KiB)
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    fn matches_route(&self, path: &str) -> bool {
+        self.routes.is_empty() || self.routes.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    fn is_sensitive(&self, field: &str) -> bool {
+        let field = field.to_lowercase();
+        self.redacted_fields.iter().any(|pattern| field.contains(pattern.as_str()))
+    }
+
+    fn redact_json(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    if self.is_sensitive(key) {
+                        *value = serde_json::Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_json(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.redact_json(item)),
+            _ => {}
+        }
+    }
+
+    /// The body with sensitive fields replaced, if it can be kept
+    fn sanitize_body(&self, content_type: &str, bytes: &[u8]) -> Option<String> {
+        if bytes.is_empty() || bytes.len() > self.max_body_size {
+            return None;
+        }
+        if content_type.contains("json") {
+            let mut value: serde_json::Value = serde_json::from_slice(bytes).ok()?;
+            self.redact_json(&mut value);
+            return Some(value.to_string());
+        }
+        if content_type.starts_with("application/x-www-form-urlencoded") {
+            let mut form = form_urlencoded::Serializer::new(String::new());
+            for (key, value) in form_urlencoded::parse(bytes) {
+                form.append_pair(&key, if self.is_sensitive(&key) { REDACTED } else { &value });
+            }
+            return Some(form.finish());
+        }
+        // Other text could hold anything; leave it out
+        None
+    }
+}
+
+/// Record requests matching `capture`'s filter, see the [module docs](self)
+pub(crate) async fn capture(capture: Arc<RequestCapture>, request: Request, next: Next) -> Response {
+    if !capture.matches_route(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    // Larger bodies stream through untouched and are left out of the capture
+    let fits = body
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= capture.max_body_size as u64);
+    let (body, bytes) = if fits {
+        match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(bytes) => (Body::from(bytes.clone()), bytes),
+            Err(error) => return ApiError::BadRequest(format!("Failed to read request body: {}", error)).into_response(),
+        }
+    } else {
+        (body, Default::default())
+    };
+    let request = Request::from_parts(parts.clone(), body);
+    let response = next.run(request).await;
+    if response.status().as_u16() < capture.min_status {
+        return response;
+    }
+
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let captured = CapturedRequest {
+        id: crate::ids::new_id(),
+        captured_at: Utc::now(),
+        method: parts.method.to_string(),
+        uri: parts.uri.path_and_query().map(|uri| uri.to_string()).unwrap_or_default(),
+        route: parts.extensions.get::<MatchedPath>().map(|route| route.as_str().to_string()),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = match capture.redacted_headers.contains(name) {
+                    true => REDACTED,
+                    false => value.to_str().ok()?,
+                };
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect(),
+        body: capture.sanitize_body(&content_type, &bytes),
+        status: response.status().as_u16(),
+        request_id: parts
+            .extensions
+            .get::<RequestId>()
+            .map(|id| id.0.clone())
+            .or_else(|| RequestId::current().map(|id| id.0)),
+    };
+    tracing::info!(capture_id = %captured.id, method = %captured.method, uri = %captured.uri, "Captured request for replay");
+    if let Err(error) = capture.store.save(captured).await {
+        tracing::warn!(error = %error, "Failed to store captured request");
+    }
+    response
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    limit: Option<usize>,
+}
+
+async fn list_captures(
+    State(store): State<Arc<dyn CaptureStore>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> Result<Json<Vec<CapturedRequest>>, ApiError> {
+    Ok(Json(store.list(query.limit.unwrap_or(50)).await?))
+}
+
+async fn get_capture(
+    State(store): State<Arc<dyn CaptureStore>>,
+    Path(id): Path<String>,
+) -> Result<Json<CapturedRequest>, ApiError> {
+    store
+        .get(&id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Capture {}", id)))
+}
+
+/// `GET /captures`, `GET /captures/:id` and, with `http-client`,
+/// `POST /captures/:id/replay`; protect them like any admin endpoint
+pub fn routes(store: Arc<dyn CaptureStore>) -> Router {
+    let router = Router::new()
+        .route("/captures", get(list_captures))
+        .route("/captures/:id", get(get_capture));
+    #[cfg(feature = "http-client")]
+    let router = router.route("/captures/:id/replay", axum::routing::post(replay_capture));
+    router.with_state(store)
+}
+
+/// Outcome of sending a capture again
+#[cfg(feature = "http-client")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayResult {
+    pub id: String,
+    pub target: String,
+    pub original_status: u16,
+    pub status: u16,
+    pub body: String,
+}
+
+/// Send `capture` to the instance at `target`, e.g. `http://localhost:3000`
+///
+/// Redacted and connection-specific headers are left out; add credentials
+/// valid on the target with `extra_headers`.
+#[cfg(feature = "http-client")]
+pub async fn replay(
+    client: &crate::http_client::HttpClient,
+    capture: &CapturedRequest,
+    target: &str,
+    extra_headers: &[(String, String)],
+) -> Result<ReplayResult, ApiError> {
+    let method = reqwest::Method::from_bytes(capture.method.as_bytes())
+        .map_err(|_| ApiError::BadRequest(format!("Invalid method {}", capture.method)))?;
+    let url = format!("{}{}", target.trim_end_matches('/'), capture.uri);
+    let skipped = [header::HOST, header::CONTENT_LENGTH, header::CONNECTION, header::TRANSFER_ENCODING];
+
+    let mut request = client.request(method, &url);
+    for (name, value) in &capture.headers {
+        if value != REDACTED && !skipped.iter().any(|skipped| skipped.as_str() == name) {
+            request = request.header(name, value);
+        }
+    }
+    for (name, value) in extra_headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = &capture.body {
+        request = request.body(body.clone());
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ApiError::ServiceUnavailable(format!("Replay to {} failed: {}", target, e)))?;
+    let status = response.status().as_u16();
+    let body = response.text().await.unwrap_or_default();
+    Ok(ReplayResult {
+        id: capture.id.clone(),
+        target: target.to_string(),
+        original_status: capture.status,
+        status,
+        body,
+    })
+}
+
+#[cfg(feature = "http-client")]
+#[derive(Deserialize)]
+struct ReplayRequest {
+    target: String,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "http-client")]
+async fn replay_capture(
+    State(store): State<Arc<dyn CaptureStore>>,
+    Path(id): Path<String>,
+    Json(replay_request): Json<ReplayRequest>,
+) -> Result<Json<ReplayResult>, ApiError> {
+    let capture = store
+        .get(&id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Capture {}", id)))?;
+    let client = crate::http_client::HttpClient::new();
+    Ok(Json(replay(&client, &capture, &replay_request.target, &replay_request.headers).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::{http::StatusCode, routing::post};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_captures_sanitized_failures() {
+        let store = Arc::new(MemoryCaptureStore::new(10));
+        let router = App::new()
+            .route("/orders/:id", post(|| async { ApiError::InternalServerError("boom".to_string()) }))
+            .route("/ok", post(|| async { "fine" }))
+            .with_request_capture(RequestCapture::new(store.clone()))
+            .mount(routes(store.clone()))
+            .into_router();
+
+        let order = Request::post("/orders/7?dry_run=true")
+            .header("content-type", "application/json")
+            .header("authorization", "Bearer secret")
+            .body(Body::from(r#"{"sku": "A1", "card": {"token": "tok_123"}}"#))
+            .unwrap();
+        let response = router.clone().oneshot(order).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        router.clone().oneshot(Request::post("/ok").body(Body::empty()).unwrap()).await.unwrap();
+
+        let captures = store.list(10).await.unwrap();
+        assert_eq!(captures.len(), 1);
+        let capture = &captures[0];
+        assert_eq!((capture.method.as_str(), capture.uri.as_str()), ("POST", "/orders/7?dry_run=true"));
+        assert_eq!(capture.route.as_deref(), Some("/orders/:id"));
+        assert_eq!(capture.status, 500);
+        assert!(capture.headers.contains(&("authorization".to_string(), REDACTED.to_string())));
+        let body: serde_json::Value = serde_json::from_str(capture.body.as_deref().unwrap()).unwrap();
+        assert_eq!(body, serde_json::json!({ "sku": "A1", "card": { "token": REDACTED } }));
+
+        let response = router
+            .oneshot(Request::get(format!("/captures/{}", capture.id)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "http-client")]
+    #[tokio::test]
+    async fn test_replay() {
+        let target = Router::new().route(
+            "/orders/:id",
+            post(|headers: axum::http::HeaderMap, body: String| async move {
+                format!("{} {}", headers.contains_key("authorization"), body)
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, target).await.unwrap() });
+
+        let capture = CapturedRequest {
+            id: "c1".to_string(),
+            captured_at: Utc::now(),
+            method: "POST".to_string(),
+            uri: "/orders/7".to_string(),
+            route: None,
+            headers: vec![("authorization".to_string(), REDACTED.to_string())],
+            body: Some("{}".to_string()),
+            status: 500,
+            request_id: None,
+        };
+        let client = crate::http_client::HttpClient::new();
+        let result = replay(&client, &capture, &url, &[]).await.unwrap();
+        assert_eq!((result.status, result.body.as_str()), (200, "false {}"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Cross-region replication for sessions and the cache
+//!
+//! In an active-passive multi-region deployment, the active region writes
+//! sessions and cache entries through to a store in the passive region, so
+//! that region can take over without logging everyone out or starting with
+//! a cold cache. See [`ReplicatedSessionStore`](crate::auth::sessions::ReplicatedSessionStore)
+//! and [`Cache::with_replica`](crate::cache::Cache::with_replica).
+//!
+//! A failing replica never fails a request: its errors are logged (and
+//! counted as `replication_errors_total` with `observability`).
+
+use std::future::Future;
+
+use crate::error::ApiError;
+
+/// When writes reach the replica
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplicationMode {
+    /// Before the write returns, adding the cross-region round trip
+    #[default]
+    WriteThrough,
+    /// In a background task; writes of the last moments before a region
+    /// failure may be lost
+    Background,
+}
+
+/// Which copy wins when both regions hold the same record
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The local copy; the replica is only read when it is missing
+    #[default]
+    PrimaryWins,
+    /// The most recently renewed copy, e.g. the session expiring last
+    LatestWins,
+}
+
+/// Replicate one write to `target` as `mode` says, logging failures
+pub(crate) async fn replicate<F>(mode: ReplicationMode, target: &'static str, operation: &'static str, write: F)
+where
+    F: Future<Output = Result<(), ApiError>> + Send + 'static,
+{
+    let write = async move {
+        if let Err(error) = write.await {
+            tracing::warn!(target_store = target, operation, error = %error, "Replication failed");
+            #[cfg(feature = "observability")]
+            crate::metrics::record_counter(
+                "replication_errors_total",
+                1,
+                &[("store", target.to_string()), ("operation", operation.to_string())],
+            );
+        }
+    };
+    match mode {
+        ReplicationMode::WriteThrough => write.await,
+        ReplicationMode::Background => {
+            tokio::spawn(write);
+        }
+    }
+}
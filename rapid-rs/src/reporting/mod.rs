@@ -0,0 +1,224 @@
+//! Error reporting hooks
+//!
+//! [`App::on_error`](crate::App::on_error) registers observers that see every
+//! [`ApiError`] a route returns and every handler panic, together with an
+//! [`ErrorContext`] describing the request. With the `sentry` feature,
+//! [`sentry::report`] sends server errors to Sentry.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .on_error(|error, context| {
+//!         if error.status_code().is_server_error() {
+//!             alerts.notify(format!("{} on {:?}: {}", context.method, context.route, error));
+//!         }
+//!     })
+//!     .on_error(rapid_rs::reporting::sentry::report)
+//! ```
+
+#[cfg(feature = "sentry")]
+pub mod sentry;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{Extensions, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+
+use crate::error::{ApiError, RenderedError};
+
+/// Called with every error and panic, see [`App::on_error`](crate::App::on_error)
+pub(crate) type ErrorObserver = Arc<dyn Fn(&ApiError, &ErrorContext) + Send + Sync>;
+
+/// The request an error was returned for
+#[derive(Debug, Clone, Default)]
+pub struct ErrorContext {
+    pub request_id: Option<String>,
+    pub method: Method,
+    pub path: String,
+    /// Route pattern, e.g. `/users/:id`
+    pub route: Option<String>,
+    pub tenant: Option<String>,
+    /// Subject of the authenticated user
+    pub user: Option<String>,
+    /// The handler panicked rather than returning an error
+    pub panicked: bool,
+}
+
+/// Tenant and user found while handling a request
+///
+/// Tenant and auth middleware usually run inside the reporting layer, so they
+/// record what they resolve here instead of in the observer's copy of the request.
+#[derive(Clone, Default)]
+pub(crate) struct ReportScope(Arc<Mutex<(Option<String>, Option<String>)>>);
+
+impl ReportScope {
+    /// The scope of `request`, shared with outer layers that created one
+    pub(crate) fn attach(request: &mut Request) -> Self {
+        let scope = request.extensions().get::<ReportScope>().cloned().unwrap_or_default();
+        request.extensions_mut().insert(scope.clone());
+        scope
+    }
+
+    /// Tenant and user recorded so far
+    pub(crate) fn get(&self) -> (Option<String>, Option<String>) {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Attach the authenticated user to errors reported for this request
+pub(crate) fn record_user(extensions: &Extensions, user: &str) {
+    if let Some(scope) = extensions.get::<ReportScope>() {
+        scope.0.lock().unwrap().1 = Some(user.to_string());
+    }
+}
+
+/// Attach the resolved tenant to errors reported for this request
+#[cfg(feature = "multi-tenancy")]
+pub(crate) fn record_tenant(extensions: &Extensions, tenant: &str) {
+    if let Some(scope) = extensions.get::<ReportScope>() {
+        scope.0.lock().unwrap().0 = Some(tenant.to_string());
+    }
+}
+
+impl ErrorContext {
+    fn from_request(request: &Request) -> Self {
+        let extensions = request.extensions();
+        let request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .or_else(|| extensions.get::<crate::middleware::RequestId>().map(|id| id.0.clone()));
+
+        #[cfg(feature = "multi-tenancy")]
+        let tenant = extensions
+            .get::<crate::multi_tenancy::TenantContext>()
+            .map(|context| context.tenant_id().as_str().to_string());
+        #[cfg(not(feature = "multi-tenancy"))]
+        let tenant = None;
+
+        #[cfg(feature = "auth")]
+        let user = extensions.get::<crate::auth::Claims>().map(|claims| claims.sub.clone());
+        #[cfg(not(feature = "auth"))]
+        let user = None;
+
+        Self {
+            request_id,
+            method: request.method().clone(),
+            path: request.uri().path().to_string(),
+            route: extensions.get::<MatchedPath>().map(|path| path.as_str().to_string()),
+            tenant,
+            user,
+            panicked: false,
+        }
+    }
+}
+
+/// Run the handler, catching panics, and pass any error to `observers`
+pub(crate) async fn observe_errors(observers: Arc<[ErrorObserver]>, mut request: Request, next: Next) -> Response {
+    let scope = ReportScope::attach(&mut request);
+    let mut context = ErrorContext::from_request(&request);
+
+    let mut future = Box::pin(next.run(request));
+    let outcome = std::future::poll_fn(|cx| {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    })
+    .await;
+
+    let (response, error) = match outcome {
+        Ok(response) => match response.extensions().get::<RenderedError>() {
+            Some(RenderedError(error)) => {
+                let error = error.clone();
+                (response, error)
+            }
+            None => return response,
+        },
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "handler panicked".to_string());
+            context.panicked = true;
+            let response = ApiError::InternalServerError("Unexpected error".to_string()).into_response();
+            (response, Arc::new(ApiError::InternalServerError(message)))
+        }
+    };
+
+    let (tenant, user) = scope.get();
+    context.tenant = context.tenant.or(tenant);
+    context.user = context.user.or(user);
+    if let Some(request_id) = response.headers().get("x-request-id").and_then(|value| value.to_str().ok()) {
+        context.request_id = Some(request_id.to_string());
+    }
+
+    for observer in observers.iter() {
+        observer(&error, &context);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{App, ApiError};
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    use super::ErrorContext;
+
+    #[tokio::test]
+    async fn test_on_error_sees_errors_and_panics() {
+        let seen: Arc<Mutex<Vec<(String, ErrorContext)>>> = Arc::default();
+        let sink = seen.clone();
+
+        async fn boom() -> &'static str {
+            panic!("kaboom")
+        }
+        let app = App::new()
+            .route(
+                "/users/:id",
+                get(|request: axum::extract::Request| async move {
+                    super::record_user(request.extensions(), "user-1");
+                    Err::<&str, _>(ApiError::NotFound("No such user".to_string()))
+                }),
+            )
+            .route("/boom", get(boom))
+            .route("/ok", get(|| async { "ok" }))
+            .on_error(move |error, context| sink.lock().unwrap().push((error.to_string(), context.clone())));
+        let router = app.into_router();
+
+        let request = Request::get("/users/7").header("x-request-id", "req-1").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = router.clone().oneshot(Request::get("/boom").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("kaboom"));
+
+        let response = router.oneshot(Request::get("/ok").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        let (error, context) = &seen[0];
+        assert_eq!(error, "Not found: No such user");
+        assert_eq!(context.route.as_deref(), Some("/users/:id"));
+        assert_eq!(context.path, "/users/7");
+        assert_eq!(context.request_id.as_deref(), Some("req-1"));
+        assert_eq!(context.user.as_deref(), Some("user-1"));
+        assert!(!context.panicked);
+
+        let (error, context) = &seen[1];
+        assert_eq!(error, "Internal server error: kaboom");
+        assert!(context.panicked);
+    }
+}
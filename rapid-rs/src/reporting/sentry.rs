@@ -0,0 +1,48 @@
+//! Sentry integration
+//!
+//! Initialise the Sentry client as usual and register [`report`]:
+//!
+//! ```rust,ignore
+//! let _guard = sentry::init(std::env::var("SENTRY_DSN")?);
+//!
+//! App::new()
+//!     .on_error(rapid_rs::reporting::sentry::report)
+//!     .run()
+//!     .await
+//! ```
+
+use super::ErrorContext;
+use crate::error::ApiError;
+
+/// Send server errors and panics to Sentry, ignoring client errors
+pub fn report(error: &ApiError, context: &ErrorContext) {
+    if context.panicked || error.status_code().is_server_error() {
+        capture(error, context);
+    }
+}
+
+/// Send any error to Sentry, tagged with the request it came from
+pub fn capture(error: &ApiError, context: &ErrorContext) {
+    sentry::with_scope(
+        |scope| {
+            let route = context.route.as_deref().unwrap_or(&context.path);
+            scope.set_transaction(Some(&format!("{} {}", context.method, route)));
+            scope.set_tag("error_code", error.error_code());
+            scope.set_tag("status", error.status_code().as_u16());
+            if let Some(request_id) = &context.request_id {
+                scope.set_tag("request_id", request_id);
+            }
+            if let Some(tenant) = &context.tenant {
+                scope.set_tag("tenant", tenant);
+            }
+            if context.panicked {
+                scope.set_tag("panic", true);
+            }
+            scope.set_user(context.user.as_ref().map(|user| sentry::User {
+                id: Some(user.clone()),
+                ..Default::default()
+            }));
+        },
+        || sentry::capture_error(error),
+    );
+}
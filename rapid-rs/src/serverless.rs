@@ -0,0 +1,349 @@
+//! Running an app on AWS Lambda and other serverless runtimes
+//!
+//! [`Serverless`] wraps an [`App`] so each invocation is routed through the
+//! same routes, layers and extractors as `App::run` would serve:
+//!
+//! ```rust,ignore
+//! #[tokio::main]
+//! async fn main() -> Result<(), lambda_runtime::Error> {
+//!     Serverless::lazy(|| async {
+//!         App::new().auto_configure().mount(routes())
+//!     })
+//!     .run()
+//!     .await
+//! }
+//! ```
+//!
+//! The app is built and its startup steps run on the first invocation, then
+//! reused while the execution environment stays warm, so cold starts only
+//! pay for what the first request needs. API Gateway REST (payload 1.0),
+//! HTTP API (payload 2.0) and ALB events are understood;
+//! [`handle`](Serverless::handle) serves plain HTTP requests for other
+//! function platforms.
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, Response},
+    Router,
+};
+use base64::Engine;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use tokio::sync::OnceCell;
+use tower::ServiceExt;
+
+use crate::error::ApiError;
+use crate::App;
+
+type Build = Box<dyn FnOnce() -> BoxFuture<'static, App> + Send>;
+
+/// An app served one invocation at a time
+pub struct Serverless {
+    build: Mutex<Option<Build>>,
+    router: OnceCell<Router>,
+}
+
+impl Serverless {
+    /// Serve `app`, running its startup steps on the first invocation
+    pub fn new(app: App) -> Self {
+        Self::lazy(|| async { app })
+    }
+
+    /// Serve the app `build` returns, built on the first invocation
+    pub fn lazy<F, Fut>(build: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = App> + Send + 'static,
+    {
+        Self {
+            build: Mutex::new(Some(Box::new(move || Box::pin(build())))),
+            router: OnceCell::new(),
+        }
+    }
+
+    async fn router(&self) -> Result<Router, ApiError> {
+        self.router
+            .get_or_try_init(|| async {
+                let build = self.build.lock().unwrap().take().ok_or_else(|| {
+                    ApiError::ServiceUnavailable("The app failed to start".to_string())
+                })?;
+                build().await.into_prepared_router().await.map_err(|error| {
+                    tracing::error!(error = %error, "App failed to start");
+                    ApiError::ServiceUnavailable("The app failed to start".to_string())
+                })
+            })
+            .await
+            .cloned()
+    }
+
+    /// Route one HTTP request, for platforms that hand over plain requests
+    pub async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        match self.router().await {
+            Ok(router) => match router.oneshot(request).await {
+                Ok(response) => response,
+                Err(infallible) => match infallible {},
+            },
+            Err(error) => axum::response::IntoResponse::into_response(error),
+        }
+    }
+
+    /// Route an API Gateway or ALB event, answering in the same payload format
+    pub async fn handle_event(&self, event: serde_json::Value) -> Result<serde_json::Value, ApiError> {
+        let event: GatewayEvent = serde_json::from_value(event)
+            .map_err(|error| ApiError::BadRequest(format!("Unsupported Lambda event: {}", error)))?;
+        let version_2 = event.version.as_deref() == Some("2.0");
+        let response = self.handle(event.into_request()?).await;
+        let response = GatewayResponse::from_response(response, version_2).await?;
+        serde_json::to_value(response).map_err(|error| ApiError::InternalServerError(error.to_string()))
+    }
+
+    /// Serve invocations from the Lambda runtime API until the environment shuts down
+    pub async fn run(self) -> Result<(), lambda_runtime::Error> {
+        let serverless = &self;
+        lambda_runtime::run(lambda_runtime::service_fn(
+            move |event: lambda_runtime::LambdaEvent<serde_json::Value>| async move {
+                serverless
+                    .handle_event(event.payload)
+                    .await
+                    .map_err(|error| lambda_runtime::Error::from(error.to_string()))
+            },
+        ))
+        .await
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct GatewayEvent {
+    version: Option<String>,
+    // Payload 2.0
+    raw_path: Option<String>,
+    raw_query_string: Option<String>,
+    cookies: Option<Vec<String>>,
+    // Payload 1.0 and ALB
+    http_method: Option<String>,
+    path: Option<String>,
+    multi_value_headers: Option<HashMap<String, Vec<String>>>,
+    query_string_parameters: Option<HashMap<String, String>>,
+    multi_value_query_string_parameters: Option<HashMap<String, Vec<String>>>,
+    headers: Option<HashMap<String, String>>,
+    request_context: Option<RequestContext>,
+    body: Option<String>,
+    is_base64_encoded: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct RequestContext {
+    http: Option<HttpContext>,
+    identity: Option<Identity>,
+    /// Set for ALB events
+    elb: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct HttpContext {
+    method: Option<String>,
+    source_ip: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Identity {
+    source_ip: Option<String>,
+}
+
+impl GatewayEvent {
+    fn into_request(self) -> Result<Request<Body>, ApiError> {
+        let invalid = |what: &str| ApiError::BadRequest(format!("Invalid {} in Lambda event", what));
+        let context = self.request_context.unwrap_or_default();
+        let http = context.http.unwrap_or_default();
+
+        let method = http.method.or(self.http_method).unwrap_or_else(|| "GET".to_string());
+        let path = self.raw_path.or(self.path).unwrap_or_else(|| "/".to_string());
+        let query = match self.raw_query_string {
+            Some(query) => query,
+            None => {
+                let pairs: Vec<(String, String)> =
+                    match (self.multi_value_query_string_parameters, self.query_string_parameters) {
+                        (Some(parameters), _) => parameters
+                            .into_iter()
+                            .flat_map(|(name, values)| values.into_iter().map(move |value| (name.clone(), value)))
+                            .collect(),
+                        (None, Some(parameters)) => parameters.into_iter().collect(),
+                        (None, None) => Vec::new(),
+                    };
+                // ALB passes parameters still percent-encoded, API Gateway decoded
+                if context.elb.is_some() {
+                    pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&")
+                } else {
+                    form_urlencoded::Serializer::new(String::new()).extend_pairs(pairs).finish()
+                }
+            }
+        };
+        let uri = if query.is_empty() { path } else { format!("{}?{}", path, query) };
+
+        let mut request = Request::builder()
+            .method(Method::from_bytes(method.as_bytes()).map_err(|_| invalid("method"))?)
+            .uri(uri)
+            .body(match self.body {
+                Some(body) if self.is_base64_encoded => Body::from(
+                    base64::engine::general_purpose::STANDARD
+                        .decode(body)
+                        .map_err(|_| invalid("body"))?,
+                ),
+                Some(body) => Body::from(body),
+                None => Body::empty(),
+            })
+            .map_err(|_| invalid("path"))?;
+
+        let headers = request.headers_mut();
+        let single = self.headers.unwrap_or_default().into_iter().map(|(name, value)| (name, vec![value]));
+        let values = match self.multi_value_headers {
+            Some(multi) => multi,
+            None => single.collect(),
+        };
+        for (name, values) in values {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid("header"))?;
+            for value in values {
+                headers.append(&name, HeaderValue::from_str(&value).map_err(|_| invalid("header"))?);
+            }
+        }
+        if let Some(cookies) = self.cookies.filter(|cookies| !cookies.is_empty()) {
+            let cookies = HeaderValue::from_str(&cookies.join("; ")).map_err(|_| invalid("cookie"))?;
+            headers.insert(header::COOKIE, cookies);
+        }
+
+        let source_ip = http.source_ip.or(context.identity.and_then(|identity| identity.source_ip));
+        if let Some(ip) = source_ip.and_then(|ip| ip.parse::<IpAddr>().ok()) {
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip, 0)));
+        }
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GatewayResponse {
+    status_code: u16,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    multi_value_headers: Option<HashMap<String, Vec<String>>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cookies: Vec<String>,
+    body: String,
+    is_base64_encoded: bool,
+}
+
+impl GatewayResponse {
+    async fn from_response(response: Response<Body>, version_2: bool) -> Result<Self, ApiError> {
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(|error| ApiError::InternalServerError(format!("Failed to read response body: {}", error)))?;
+        let (body, is_base64_encoded) = match String::from_utf8(body.to_vec()) {
+            Ok(text) => (text, false),
+            Err(_) => (base64::engine::general_purpose::STANDARD.encode(&body), true),
+        };
+
+        let mut response = GatewayResponse {
+            status_code: parts.status.as_u16(),
+            headers: HashMap::new(),
+            multi_value_headers: None,
+            cookies: Vec::new(),
+            body,
+            is_base64_encoded,
+        };
+        if version_2 {
+            // Payload 2.0 has no repeated headers; cookies get their own field
+            response.cookies = header_values(&parts.headers, &header::SET_COOKIE);
+            for name in parts.headers.keys().filter(|name| **name != header::SET_COOKIE) {
+                response.headers.insert(name.to_string(), header_values(&parts.headers, name).join(", "));
+            }
+        } else {
+            let multi = parts
+                .headers
+                .keys()
+                .map(|name| (name.to_string(), header_values(&parts.headers, name)))
+                .collect();
+            response.multi_value_headers = Some(multi);
+        }
+        Ok(response)
+    }
+}
+
+fn header_values(headers: &HeaderMap, name: &HeaderName) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Query, routing::post};
+    use serde_json::json;
+
+    fn app() -> App {
+        App::new().route(
+            "/orders/:id",
+            post(
+                |Query(query): Query<HashMap<String, String>>, ConnectInfo(addr): ConnectInfo<SocketAddr>, body: String| async move {
+                    (
+                        axum::response::AppendHeaders([(header::SET_COOKIE, "a=1"), (header::SET_COOKIE, "b=2")]),
+                        format!("{} {} {}", query["expand"], addr.ip(), body),
+                    )
+                },
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_http_api_event() {
+        let serverless = Serverless::lazy(|| async { app() });
+        let event = json!({
+            "version": "2.0",
+            "rawPath": "/orders/1",
+            "rawQueryString": "expand=items",
+            "headers": {"content-type": "text/plain"},
+            "requestContext": {"http": {"method": "POST", "sourceIp": "203.0.113.7"}},
+            "body": "aGVsbG8=",
+            "isBase64Encoded": true
+        });
+        let response = serverless.handle_event(event).await.unwrap();
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["body"], "items 203.0.113.7 hello");
+        assert_eq!(response["cookies"], json!(["a=1", "b=2"]));
+
+        let response = serverless.handle_event(json!({"version": "2.0", "rawPath": "/missing"})).await.unwrap();
+        assert_eq!(response["statusCode"], 404);
+    }
+
+    #[tokio::test]
+    async fn test_rest_api_event() {
+        let serverless = Serverless::new(app());
+        let event = json!({
+            "httpMethod": "POST",
+            "path": "/orders/1",
+            "multiValueQueryStringParameters": {"expand": ["items"]},
+            "headers": null,
+            "requestContext": {"identity": {"sourceIp": "198.51.100.1"}},
+            "body": "hi",
+            "isBase64Encoded": false
+        });
+        let response = serverless.handle_event(event).await.unwrap();
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["body"], "items 198.51.100.1 hi");
+        assert_eq!(response["multiValueHeaders"]["set-cookie"], json!(["a=1", "b=2"]));
+    }
+}
@@ -0,0 +1,75 @@
+//! Graceful shutdown support for `App::run`
+
+use std::future::Future;
+use std::pin::Pin;
+
+/// Async cleanup registered with `App::on_shutdown`
+pub(crate) type ShutdownHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+pub(crate) fn hook<F, Fut>(f: F) -> ShutdownHook
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Box::new(move || Box::pin(f()))
+}
+
+/// Resolve on Ctrl+C (SIGINT) or, on Unix, SIGTERM
+pub(crate) async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Run hooks in registration order
+pub(crate) async fn run_hooks(hooks: Vec<ShutdownHook>) {
+    let count = hooks.len();
+    for (i, hook) in hooks.into_iter().enumerate() {
+        tracing::debug!(hook = i + 1, of = count, "Running shutdown hook");
+        hook().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_hooks_run_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let hooks = (1..=3)
+            .map(|n| {
+                let calls = calls.clone();
+                hook(move || async move { calls.lock().unwrap().push(n) })
+            })
+            .collect();
+
+        run_hooks(hooks).await;
+        assert_eq!(*calls.lock().unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,432 @@
+//! `sitemap.xml` and `robots.txt`
+//!
+//! [`App::with_sitemap`](crate::App::with_sitemap) serves `/sitemap.xml`
+//! listing the documented `GET` routes without path parameters, the paths
+//! added here, and whatever a provider returns for dynamic pages;
+//! [`App::with_robots`](crate::App::with_robots) serves `/robots.txt`
+//! pointing crawlers at it:
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .with_sitemap(
+//!         Sitemap::new("https://example.com")
+//!             .with_path("/")
+//!             .excluding("/account")
+//!             .with_provider(|| async {
+//!                 let posts = load_posts().await?;
+//!                 Ok(posts
+//!                     .into_iter()
+//!                     .map(|post| SitemapEntry::new(format!("/posts/{}", post.slug)).with_last_modified(post.updated_at))
+//!                     .collect())
+//!             }),
+//!     )
+//!     .with_robots(Robots::new().disallow("/admin"))
+//! ```
+//!
+//! Both are sent with `Cache-Control: public, max-age=3600` unless
+//! configured otherwise.
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::openapi::PathItemType;
+
+use crate::error::ApiError;
+use crate::openapi::ApiDocs;
+
+/// Most URLs one sitemap file may list
+const MAX_URLS: usize = 50_000;
+
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+type Provider = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<Vec<SitemapEntry>, ApiError>> + Send>> + Send + Sync>;
+
+/// How often a page is expected to change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeFrequency {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl ChangeFrequency {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChangeFrequency::Always => "always",
+            ChangeFrequency::Hourly => "hourly",
+            ChangeFrequency::Daily => "daily",
+            ChangeFrequency::Weekly => "weekly",
+            ChangeFrequency::Monthly => "monthly",
+            ChangeFrequency::Yearly => "yearly",
+            ChangeFrequency::Never => "never",
+        }
+    }
+}
+
+/// One `<url>` in the sitemap
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapEntry {
+    /// Path below the base URL, or an absolute URL
+    pub location: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub change_frequency: Option<ChangeFrequency>,
+    pub priority: Option<f32>,
+}
+
+impl SitemapEntry {
+    pub fn new(location: impl Into<String>) -> Self {
+        Self {
+            location: location.into(),
+            last_modified: None,
+            change_frequency: None,
+            priority: None,
+        }
+    }
+
+    pub fn with_last_modified(mut self, at: DateTime<Utc>) -> Self {
+        self.last_modified = Some(at);
+        self
+    }
+
+    pub fn with_change_frequency(mut self, frequency: ChangeFrequency) -> Self {
+        self.change_frequency = Some(frequency);
+        self
+    }
+
+    /// Relative importance between 0.0 and 1.0
+    pub fn with_priority(mut self, priority: f32) -> Self {
+        self.priority = Some(priority.clamp(0.0, 1.0));
+        self
+    }
+}
+
+/// Pages listed in `/sitemap.xml`
+#[derive(Clone)]
+pub struct Sitemap {
+    base_url: String,
+    entries: Vec<SitemapEntry>,
+    excluded: Vec<String>,
+    provider: Option<Provider>,
+    max_age: Duration,
+}
+
+impl std::fmt::Debug for Sitemap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sitemap")
+            .field("base_url", &self.base_url)
+            .field("entries", &self.entries)
+            .field("excluded", &self.excluded)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sitemap {
+    /// Sitemap for the site at `base_url`, such as `https://example.com`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            entries: Vec::new(),
+            excluded: Vec::new(),
+            provider: None,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// List `path`, e.g. a page served by a route that isn't documented
+    pub fn with_path(self, path: impl Into<String>) -> Self {
+        self.with_entry(SitemapEntry::new(path))
+    }
+
+    pub fn with_entry(mut self, entry: SitemapEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    /// Leave documented routes under `prefix` out
+    pub fn excluding(mut self, prefix: impl Into<String>) -> Self {
+        self.excluded.push(prefix.into());
+        self
+    }
+
+    /// Add the entries `provider` returns on every request, e.g. one per post
+    pub fn with_provider<F, Fut>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<SitemapEntry>, ApiError>> + Send + 'static,
+    {
+        self.provider = Some(Arc::new(move || Box::pin(provider())));
+        self
+    }
+
+    /// How long clients and CDNs may cache the sitemap
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Absolute URL of `/sitemap.xml`
+    pub fn url(&self) -> String {
+        format!("{}/sitemap.xml", self.base_url)
+    }
+
+    /// Also list the documented `GET` routes without path parameters
+    pub(crate) fn with_documented_routes(mut self, docs: &ApiDocs) -> Self {
+        let documented: Vec<_> = docs
+            .openapi()
+            .paths
+            .paths
+            .iter()
+            .filter(|(path, item)| item.operations.contains_key(&PathItemType::Get) && !path.contains('{'))
+            .filter(|(path, _)| !self.excluded.iter().any(|prefix| path.starts_with(prefix.as_str())))
+            .filter(|(path, _)| !self.entries.iter().any(|entry| &entry.location == *path))
+            .map(|(path, _)| SitemapEntry::new(path.clone()))
+            .collect();
+        self.entries.extend(documented);
+        self
+    }
+
+    /// The sitemap document
+    pub async fn render(&self) -> Result<String, ApiError> {
+        let mut entries = self.entries.clone();
+        if let Some(provider) = &self.provider {
+            entries.extend(provider().await?);
+        }
+        if entries.len() > MAX_URLS {
+            tracing::warn!(urls = entries.len(), "Sitemap has more than {} URLs, truncating", MAX_URLS);
+            entries.truncate(MAX_URLS);
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for entry in &entries {
+            let location = if entry.location.contains("://") {
+                entry.location.clone()
+            } else {
+                format!("{}/{}", self.base_url, entry.location.trim_start_matches('/'))
+            };
+            xml.push_str("  <url>\n");
+            xml.push_str(&format!("    <loc>{}</loc>\n", escape(&location)));
+            if let Some(at) = entry.last_modified {
+                xml.push_str(&format!("    <lastmod>{}</lastmod>\n", at.format("%Y-%m-%dT%H:%M:%SZ")));
+            }
+            if let Some(frequency) = entry.change_frequency {
+                xml.push_str(&format!("    <changefreq>{}</changefreq>\n", frequency.as_str()));
+            }
+            if let Some(priority) = entry.priority {
+                xml.push_str(&format!("    <priority>{:.1}</priority>\n", priority));
+            }
+            xml.push_str("  </url>\n");
+        }
+        xml.push_str("</urlset>\n");
+        Ok(xml)
+    }
+}
+
+/// Crawler rules served as `/robots.txt`
+#[derive(Debug, Clone)]
+pub struct Robots {
+    groups: Vec<(String, Vec<String>)>,
+    sitemaps: Vec<String>,
+    max_age: Duration,
+}
+
+impl Robots {
+    /// Allow everything to every crawler until rules are added
+    pub fn new() -> Self {
+        Self {
+            groups: vec![("*".to_string(), Vec::new())],
+            sitemaps: Vec::new(),
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Keep crawlers out of `path`
+    pub fn disallow(self, path: impl Into<String>) -> Self {
+        self.rule(format!("Disallow: {}", path.into()))
+    }
+
+    /// Let crawlers into `path` below a disallowed one
+    pub fn allow(self, path: impl Into<String>) -> Self {
+        self.rule(format!("Allow: {}", path.into()))
+    }
+
+    /// Following rules apply to `user_agent` only, e.g. `GPTBot`
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.groups.push((user_agent.into(), Vec::new()));
+        self
+    }
+
+    /// Point crawlers at a sitemap; the app's own is added automatically
+    pub fn with_sitemap(mut self, url: impl Into<String>) -> Self {
+        self.sitemaps.push(url.into());
+        self
+    }
+
+    /// How long clients and CDNs may cache the file
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    fn rule(mut self, rule: String) -> Self {
+        if let Some((_, rules)) = self.groups.last_mut() {
+            rules.push(rule);
+        }
+        self
+    }
+
+    /// The robots.txt document
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (user_agent, rules) in &self.groups {
+            if rules.is_empty() && user_agent != "*" {
+                continue;
+            }
+            text.push_str(&format!("User-agent: {}\n", user_agent));
+            if rules.is_empty() {
+                text.push_str("Disallow:\n");
+            }
+            for rule in rules {
+                text.push_str(rule);
+                text.push('\n');
+            }
+            text.push('\n');
+        }
+        for sitemap in &self.sitemaps {
+            text.push_str(&format!("Sitemap: {}\n", sitemap));
+        }
+        text
+    }
+}
+
+impl Default for Robots {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn cached(content_type: &'static str, max_age: Duration, body: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_str(&format!("public, max-age={}", max_age.as_secs())).unwrap(),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// `/sitemap.xml` and `/robots.txt`, for whichever is configured
+pub(crate) fn router(sitemap: Option<Sitemap>, robots: Option<Robots>, docs: &ApiDocs) -> Router {
+    let mut router = Router::new();
+    let sitemap_url = sitemap.as_ref().map(Sitemap::url);
+
+    if let Some(sitemap) = sitemap {
+        let sitemap = Arc::new(sitemap.with_documented_routes(docs));
+        router = router.route(
+            "/sitemap.xml",
+            get(move || {
+                let sitemap = sitemap.clone();
+                async move {
+                    match sitemap.render().await {
+                        Ok(xml) => cached("application/xml; charset=utf-8", sitemap.max_age, xml),
+                        Err(error) => {
+                            tracing::error!(error = %error, "Failed to build sitemap");
+                            (StatusCode::SERVICE_UNAVAILABLE, [(header::RETRY_AFTER, "60")]).into_response()
+                        }
+                    }
+                }
+            }),
+        );
+    }
+
+    if let Some(mut robots) = robots {
+        if let Some(url) = sitemap_url.filter(|url| !robots.sitemaps.contains(url)) {
+            robots.sitemaps.push(url);
+        }
+        let (text, max_age) = (robots.render(), robots.max_age);
+        router = router.route(
+            "/robots.txt",
+            get(move || async move { cached("text/plain; charset=utf-8", max_age, text) }),
+        );
+    }
+
+    router
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{App, RouteDoc};
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn fetch(router: &Router, uri: &str) -> (StatusCode, Option<HeaderValue>, String) {
+        let response = router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let (status, cache_control) = (response.status(), response.headers().get(header::CACHE_CONTROL).cloned());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, cache_control, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_and_robots() {
+        let updated = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().with_timezone(&Utc);
+        let router = App::new()
+            .route_with_doc("/about", get(|| async { "about" }), RouteDoc::get())
+            .route_with_doc("/posts/:slug", get(|| async { "post" }), RouteDoc::get())
+            .route_with_doc("/contact", axum::routing::post(|| async { "sent" }), RouteDoc::post())
+            .route_with_doc("/account/settings", get(|| async { "settings" }), RouteDoc::get())
+            .with_sitemap(
+                Sitemap::new("https://example.com/")
+                    .with_path("/")
+                    .excluding("/account")
+                    .with_provider(move || async move {
+                        Ok(vec![SitemapEntry::new("/posts/a&b")
+                            .with_last_modified(updated)
+                            .with_change_frequency(ChangeFrequency::Weekly)
+                            .with_priority(0.8)])
+                    }),
+            )
+            .with_robots(Robots::new().disallow("/admin").user_agent("GPTBot").disallow("/"))
+            .into_router();
+
+        let (status, cache_control, xml) = fetch(&router, "/sitemap.xml").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(cache_control.unwrap(), "public, max-age=3600");
+        for loc in ["https://example.com/", "https://example.com/about", "https://example.com/posts/a&amp;b"] {
+            assert!(xml.contains(&format!("<loc>{}</loc>", loc)), "{} missing from {}", loc, xml);
+        }
+        assert!(!xml.contains("contact") && !xml.contains("account") && !xml.contains("{slug}"));
+        assert!(xml.contains("<lastmod>2026-01-02T03:04:05Z</lastmod>"));
+        assert!(xml.contains("<changefreq>weekly</changefreq>") && xml.contains("<priority>0.8</priority>"));
+
+        let (_, _, text) = fetch(&router, "/robots.txt").await;
+        assert_eq!(
+            text,
+            "User-agent: *\nDisallow: /admin\n\nUser-agent: GPTBot\nDisallow: /\n\nSitemap: https://example.com/sitemap.xml\n"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Ordered startup of subsystems
+//!
+//! Subsystems that need something before they can start (jobs need the
+//! database, cache warmers need the cache) register an [`Init`] step naming
+//! what they run [`after`](Init::after). [`App::run`](crate::App::run) boots
+//! the steps in dependency order before accepting requests, runs steps whose
+//! dependencies are all met in parallel, retries transient failures and logs
+//! how long each step took:
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .with_init(
+//!         Init::new("database", |mut deps| async move {
+//!             deps.insert(PgPool::connect(&url).await?);
+//!             Ok(deps)
+//!         })
+//!         .with_retry(RetryPolicy::new(5, Duration::from_millis(200))),
+//!     )
+//!     .with_init(Init::new("jobs", start_workers).after("database"))
+//!     .with_init(Init::new("cache", connect_cache))
+//!     .with_init(Init::new("cache-warmer", warm_cache).after("cache").after("database"))
+//! ```
+//!
+//! Each step receives the dependencies registered so far, including what
+//! earlier steps added, and returns them with its own additions, which
+//! handlers can then extract with [`Dep`](crate::Dep).
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::dependencies::Dependencies;
+use crate::error::ApiError;
+
+type InitFn = Arc<dyn Fn(Dependencies) -> Pin<Box<dyn Future<Output = Result<Dependencies, ApiError>> + Send>> + Send + Sync>;
+
+/// One startup step
+#[derive(Clone)]
+pub struct Init {
+    name: String,
+    after: Vec<String>,
+    retry: RetryPolicy,
+    init: InitFn,
+}
+
+impl Init {
+    pub fn new<F, Fut>(name: impl Into<String>, init: F) -> Self
+    where
+        F: Fn(Dependencies) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Dependencies, ApiError>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            after: Vec::new(),
+            retry: RetryPolicy::none(),
+            init: Arc::new(move |deps| Box::pin(init(deps))),
+        }
+    }
+
+    /// Start only once the step named `name` has finished
+    pub fn after(mut self, name: impl Into<String>) -> Self {
+        self.after.push(name.into());
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl std::fmt::Debug for Init {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Init").field("name", &self.name).field("after", &self.after).finish()
+    }
+}
+
+/// How often a failing step is retried
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub(crate) attempts: u32,
+    pub(crate) delay: Duration,
+    pub(crate) retryable: fn(&ApiError) -> bool,
+}
+
+impl RetryPolicy {
+    /// Fail on the first error
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+
+    /// Up to `attempts` tries in total, waiting `delay` after the first
+    /// failure and twice as long after each further one
+    ///
+    /// Only [transient](is_transient) errors are retried.
+    pub fn new(attempts: u32, delay: Duration) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            delay,
+            retryable: is_transient,
+        }
+    }
+
+    /// Decide which errors are worth another try
+    pub fn retry_if(mut self, retryable: fn(&ApiError) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+}
+
+/// Errors likely to go away on their own: unavailable services and database
+/// connection problems
+pub fn is_transient(error: &ApiError) -> bool {
+    match error {
+        ApiError::ServiceUnavailable(_) => true,
+        ApiError::DatabaseError(error) => matches!(
+            error,
+            sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::Tls(_)
+        ),
+        ApiError::Custom { status, .. } => {
+            matches!(status.as_u16(), 502..=504)
+        }
+        _ => false,
+    }
+}
+
+/// Why the app could not start
+#[derive(Debug, thiserror::Error)]
+pub enum StartupError {
+    #[error("Startup step '{step}' runs after '{dependency}', which is not registered")]
+    UnknownDependency { step: String, dependency: String },
+
+    #[error("Startup steps depend on each other in a cycle: {}", .0.join(", "))]
+    Cycle(Vec<String>),
+
+    #[error("Startup step '{step}' failed: {source}")]
+    Failed { step: String, source: ApiError },
+}
+
+/// Group `steps` into stages; the steps of a stage only depend on earlier stages
+fn stages(steps: Vec<Init>) -> Result<Vec<Vec<Init>>, StartupError> {
+    let names: HashSet<String> = steps.iter().map(|step| step.name.clone()).collect();
+    for step in &steps {
+        if let Some(dependency) = step.after.iter().find(|dependency| !names.contains(*dependency)) {
+            return Err(StartupError::UnknownDependency {
+                step: step.name.clone(),
+                dependency: dependency.clone(),
+            });
+        }
+    }
+
+    let mut done = HashSet::new();
+    let mut pending = steps;
+    let mut stages = Vec::new();
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|step| step.after.iter().all(|dependency| done.contains(dependency)));
+        if ready.is_empty() {
+            return Err(StartupError::Cycle(waiting.into_iter().map(|step| step.name).collect()));
+        }
+        done.extend(ready.iter().map(|step| step.name.clone()));
+        stages.push(ready);
+        pending = waiting;
+    }
+    Ok(stages)
+}
+
+async fn run_step(step: Init, dependencies: Dependencies) -> Result<Dependencies, StartupError> {
+    let started = Instant::now();
+    let mut delay = step.retry.delay;
+    let mut attempt = 1;
+    loop {
+        match (step.init)(dependencies.clone()).await {
+            Ok(dependencies) => {
+                tracing::info!(
+                    step = %step.name,
+                    attempts = attempt,
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "✅ Started {}",
+                    step.name
+                );
+                return Ok(dependencies);
+            }
+            Err(error) if attempt < step.retry.attempts && (step.retry.retryable)(&error) => {
+                tracing::warn!(step = %step.name, attempt, error = %error, retry_in = ?delay, "Startup step failed, retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(error) => {
+                tracing::error!(step = %step.name, attempt, error = %error, "Startup step failed");
+                return Err(StartupError::Failed { step: step.name, source: error });
+            }
+        }
+    }
+}
+
+/// Run `steps` in dependency order, adding what they provide to `dependencies`
+pub(crate) async fn boot(steps: Vec<Init>, dependencies: &mut Dependencies) -> Result<(), StartupError> {
+    if steps.is_empty() {
+        return Ok(());
+    }
+    let started = Instant::now();
+    let count = steps.len();
+
+    for stage in stages(steps)? {
+        let mut tasks = tokio::task::JoinSet::new();
+        let mut order = HashMap::new();
+        for step in stage {
+            let name = step.name.clone();
+            let id = tasks.spawn(run_step(step, dependencies.clone())).id();
+            order.insert(id, name);
+        }
+        while let Some(result) = tasks.join_next_with_id().await {
+            match result {
+                Ok((_, provided)) => dependencies.merge(&provided?),
+                Err(error) => {
+                    let step = order.remove(&error.id()).unwrap_or_default();
+                    return Err(StartupError::Failed {
+                        step,
+                        source: ApiError::InternalServerError(format!("Startup step panicked: {}", error)),
+                    });
+                }
+            }
+        }
+    }
+
+    tracing::info!(steps = count, elapsed_ms = started.elapsed().as_millis() as u64, "✅ Startup complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn recording(name: &'static str, log: Arc<Mutex<Vec<&'static str>>>) -> Init {
+        Init::new(name, move |deps| {
+            let log = log.clone();
+            async move {
+                log.lock().unwrap().push(name);
+                Ok(deps)
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_boot_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let steps = vec![
+            recording("warmer", log.clone()).after("cache").after("database"),
+            recording("jobs", log.clone()).after("database"),
+            Init::new("database", |mut deps| async move {
+                deps.insert(42u32);
+                Ok(deps)
+            }),
+            recording("cache", log.clone()),
+        ];
+
+        let stages: Vec<Vec<String>> = stages(steps.clone())
+            .unwrap()
+            .into_iter()
+            .map(|stage| stage.into_iter().map(|step| step.name).collect())
+            .collect();
+        assert_eq!(stages, [vec!["database", "cache"], vec!["warmer", "jobs"]]);
+
+        let mut dependencies = Dependencies::new();
+        boot(steps, &mut dependencies).await.unwrap();
+        assert_eq!(*dependencies.get::<u32>().unwrap(), 42);
+        assert_eq!(log.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_graphs() {
+        let step = |name: &str| Init::new(name, |deps| async { Ok(deps) });
+
+        let error = boot(vec![step("jobs").after("database")], &mut Dependencies::new()).await.unwrap_err();
+        assert!(matches!(error, StartupError::UnknownDependency { .. }));
+
+        let cycle = vec![step("a").after("b"), step("b").after("a"), step("c")];
+        let error = boot(cycle, &mut Dependencies::new()).await.unwrap_err();
+        assert!(matches!(error, StartupError::Cycle(names) if names == ["a", "b"]));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let attempts = Arc::new(Mutex::new(0));
+        let counter = attempts.clone();
+        let flaky = Init::new("flaky", move |deps| {
+            let counter = counter.clone();
+            async move {
+                let mut attempts = counter.lock().unwrap();
+                *attempts += 1;
+                match *attempts {
+                    1 | 2 => Err(ApiError::ServiceUnavailable("not yet".to_string())),
+                    _ => Ok(deps),
+                }
+            }
+        })
+        .with_retry(RetryPolicy::new(3, Duration::from_millis(1)));
+        boot(vec![flaky], &mut Dependencies::new()).await.unwrap();
+        assert_eq!(*attempts.lock().unwrap(), 3);
+
+        let broken = Init::new("broken", |_| async { Err(ApiError::BadRequest("bad config".to_string())) })
+            .with_retry(RetryPolicy::new(3, Duration::from_millis(1)));
+        let error = boot(vec![broken], &mut Dependencies::new()).await.unwrap_err();
+        assert!(matches!(error, StartupError::Failed { step, .. } if step == "broken"));
+    }
+}
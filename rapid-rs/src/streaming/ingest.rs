@@ -0,0 +1,580 @@
+//! Row-by-row NDJSON and CSV request bodies
+//!
+//! [`NdjsonRows`] and [`CsvRows`] read the body as it arrives, so a
+//! multi-gigabyte import never has to fit in memory. Rows that fail to parse
+//! or validate are skipped and recorded in an [`IngestReport`]:
+//!
+//! ```rust,ignore
+//! async fn import(State(pool): State<PgPool>, mut rows: CsvRows<Product>) -> Result<IngestReport, ApiError> {
+//!     let mut batch = Vec::new();
+//!     while let Some(product) = rows.next().await? {
+//!         batch.push(product);
+//!         if batch.len() == 1000 {
+//!             BulkInsert::new("products").execute(&mut *pool.acquire().await?, &batch).await?;
+//!             batch.clear();
+//!         }
+//!     }
+//!     // ...write the rest
+//!     Ok(rows.into_report())
+//! }
+//! ```
+//!
+//! These extractors read the raw body, bypassing axum's default body limit;
+//! put a limit in front of import routes if they need one.
+
+use axum::{
+    async_trait,
+    body::BodyDataStream,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use futures::StreamExt;
+use serde::de::{self, DeserializeOwned, IntoDeserializer};
+use serde::Serialize;
+use std::marker::PhantomData;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::error::ApiError;
+
+/// Limits for [`NdjsonRows`] and [`CsvRows`]
+///
+/// Read from request extensions, e.g. `.layer(Extension(IngestConfig::new()))`.
+#[derive(Debug, Clone)]
+pub struct IngestConfig {
+    /// Longest accepted row, in bytes
+    pub max_row_bytes: usize,
+    /// Row errors kept in the report; later ones are only counted
+    pub max_errors: usize,
+    pub csv_delimiter: u8,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_row_bytes: 1024 * 1024,
+            max_errors: 100,
+            csv_delimiter: b',',
+        }
+    }
+}
+
+impl IngestConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_row_bytes(mut self, max: usize) -> Self {
+        self.max_row_bytes = max;
+        self
+    }
+
+    pub fn with_max_errors(mut self, max: usize) -> Self {
+        self.max_errors = max;
+        self
+    }
+
+    pub fn with_csv_delimiter(mut self, delimiter: u8) -> Self {
+        self.csv_delimiter = delimiter;
+        self
+    }
+}
+
+/// Why one row was rejected
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RowError {
+    /// 1-based line the row starts on
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub code: String,
+    pub message: String,
+}
+
+/// Outcome of an import
+///
+/// As a response it is `200 OK` when every row was accepted and
+/// `422 Unprocessable Entity` otherwise.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct IngestReport {
+    pub accepted: usize,
+    pub rejected: usize,
+    /// The first rejected rows' errors, up to the configured maximum
+    pub errors: Vec<RowError>,
+}
+
+impl IntoResponse for IngestReport {
+    fn into_response(self) -> Response {
+        let status = if self.rejected == 0 {
+            StatusCode::OK
+        } else {
+            StatusCode::UNPROCESSABLE_ENTITY
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Splits the body into lines without buffering more than one row
+struct LineReader {
+    body: BodyDataStream,
+    buf: Vec<u8>,
+    /// Bytes of `buf` already searched for a newline
+    searched: usize,
+    done: bool,
+    line: usize,
+    max_bytes: usize,
+}
+
+impl LineReader {
+    fn new(request: Request, max_bytes: usize) -> Self {
+        Self {
+            body: request.into_body().into_data_stream(),
+            buf: Vec::new(),
+            searched: 0,
+            done: false,
+            line: 0,
+            max_bytes,
+        }
+    }
+
+    /// Next line without its terminator
+    async fn next_line(&mut self) -> Result<Option<Vec<u8>>, ApiError> {
+        loop {
+            if let Some(end) = self.buf[self.searched..].iter().position(|&b| b == b'\n') {
+                let end = self.searched + end;
+                let mut line: Vec<u8> = self.buf.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                self.searched = 0;
+                self.line += 1;
+                return Ok(Some(line));
+            }
+            self.searched = self.buf.len();
+
+            if self.buf.len() > self.max_bytes {
+                return Err(ApiError::BadRequest(format!(
+                    "Line {} is longer than {} bytes",
+                    self.line + 1,
+                    self.max_bytes
+                )));
+            }
+            if self.done {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                self.line += 1;
+                self.searched = 0;
+                return Ok(Some(std::mem::take(&mut self.buf)));
+            }
+
+            match self.body.next().await {
+                Some(Ok(chunk)) => self.buf.extend_from_slice(&chunk),
+                Some(Err(e)) => return Err(ApiError::BadRequest(format!("Failed to read request body: {}", e))),
+                None => self.done = true,
+            }
+        }
+    }
+}
+
+/// Row counting and error collection shared by both formats
+struct Rows<T> {
+    lines: LineReader,
+    report: IngestReport,
+    max_errors: usize,
+    _row: PhantomData<fn() -> T>,
+}
+
+impl<T: Validate> Rows<T> {
+    fn new(request: Request) -> (Self, IngestConfig) {
+        let config = request.extensions().get::<IngestConfig>().cloned().unwrap_or_default();
+        let rows = Self {
+            lines: LineReader::new(request, config.max_row_bytes),
+            report: IngestReport::default(),
+            max_errors: config.max_errors,
+            _row: PhantomData,
+        };
+        (rows, config)
+    }
+
+    /// Validate a parsed row, recording why it was rejected
+    fn accept(&mut self, line: usize, parsed: Result<T, String>) -> Option<T> {
+        let errors = match parsed {
+            Ok(row) => match row.validate() {
+                Ok(()) => {
+                    self.report.accepted += 1;
+                    return Some(row);
+                }
+                Err(errors) => crate::validation::flatten_errors(&errors)
+                    .into_iter()
+                    .map(|(field, error)| RowError {
+                        line,
+                        field: Some(field),
+                        code: error.code.to_string(),
+                        message: error
+                            .message
+                            .as_ref()
+                            .map(|message| message.to_string())
+                            .unwrap_or_else(|| "Validation failed".to_string()),
+                    })
+                    .collect(),
+            },
+            Err(message) => vec![RowError {
+                line,
+                field: None,
+                code: "invalid_row".to_string(),
+                message,
+            }],
+        };
+
+        self.report.rejected += 1;
+        let room = self.max_errors.saturating_sub(self.report.errors.len());
+        self.report.errors.extend(errors.into_iter().take(room));
+        None
+    }
+}
+
+/// Newline-delimited JSON body, one `T` per line
+///
+/// Blank lines are ignored.
+pub struct NdjsonRows<T> {
+    rows: Rows<T>,
+}
+
+impl<T: DeserializeOwned + Validate> NdjsonRows<T> {
+    /// Next valid row, or `None` at the end of the body
+    ///
+    /// Fails only if the body can't be read; bad rows are skipped and
+    /// reported.
+    pub async fn next(&mut self) -> Result<Option<T>, ApiError> {
+        while let Some(line) = self.rows.lines.next_line().await? {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let parsed = serde_json::from_slice(&line).map_err(|e| e.to_string());
+            let number = self.rows.lines.line;
+            if let Some(row) = self.rows.accept(number, parsed) {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn report(&self) -> &IngestReport {
+        &self.rows.report
+    }
+
+    pub fn into_report(self) -> IngestReport {
+        self.rows.report
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for NdjsonRows<T>
+where
+    T: Validate + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let (rows, _) = Rows::new(req);
+        Ok(Self { rows })
+    }
+}
+
+/// CSV body with a header row, one `T` per record
+///
+/// Columns map to fields by header name. Quoted fields may contain
+/// delimiters, doubled quotes and newlines; empty fields deserialize as
+/// `None` for `Option` fields.
+pub struct CsvRows<T> {
+    rows: Rows<T>,
+    headers: Vec<String>,
+    delimiter: u8,
+}
+
+impl<T: DeserializeOwned + Validate> CsvRows<T> {
+    /// Next valid row, or `None` at the end of the body
+    ///
+    /// Fails only if the body can't be read; bad rows are skipped and
+    /// reported.
+    pub async fn next(&mut self) -> Result<Option<T>, ApiError> {
+        while let Some((line, fields)) = read_record(&mut self.rows.lines, self.delimiter).await? {
+            if fields.len() == 1 && fields[0].is_empty() {
+                continue;
+            }
+            let parsed = if fields.len() != self.headers.len() {
+                Err(format!("Expected {} fields, found {}", self.headers.len(), fields.len()))
+            } else {
+                T::deserialize(RecordDeserializer {
+                    headers: &self.headers,
+                    fields: &fields,
+                })
+                .map_err(|e: de::value::Error| e.to_string())
+            };
+            if let Some(row) = self.rows.accept(line, parsed) {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub fn report(&self) -> &IngestReport {
+        &self.rows.report
+    }
+
+    pub fn into_report(self) -> IngestReport {
+        self.rows.report
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for CsvRows<T>
+where
+    T: Validate + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let (mut rows, config) = Rows::new(req);
+        let (_, headers) = read_record(&mut rows.lines, config.csv_delimiter)
+            .await?
+            .ok_or_else(|| ApiError::BadRequest("CSV body has no header row".to_string()))?;
+        let headers = headers.into_iter().map(|header| header.trim().to_string()).collect();
+
+        Ok(Self {
+            rows,
+            headers,
+            delimiter: config.csv_delimiter,
+        })
+    }
+}
+
+/// Next CSV record and the line it starts on
+async fn read_record(lines: &mut LineReader, delimiter: u8) -> Result<Option<(usize, Vec<String>)>, ApiError> {
+    let Some(mut record) = lines.next_line().await? else {
+        return Ok(None);
+    };
+    let start = lines.line;
+
+    // An odd number of quotes means a quoted field continues on the next line
+    while record.iter().filter(|&&b| b == b'"').count() % 2 == 1 {
+        let Some(next) = lines.next_line().await? else {
+            return Err(ApiError::BadRequest(format!("Unterminated quoted field starting on line {}", start)));
+        };
+        if record.len() + next.len() > lines.max_bytes {
+            return Err(ApiError::BadRequest(format!(
+                "Record on line {} is longer than {} bytes",
+                start, lines.max_bytes
+            )));
+        }
+        record.push(b'\n');
+        record.extend_from_slice(&next);
+    }
+
+    let record = String::from_utf8(record)
+        .map_err(|_| ApiError::BadRequest(format!("Line {} is not valid UTF-8", start)))?;
+    Ok(Some((start, split_record(&record, delimiter as char))))
+}
+
+fn split_record(record: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Deserializes one CSV record as a map from header to field
+struct RecordDeserializer<'a> {
+    headers: &'a [String],
+    fields: &'a [String],
+}
+
+impl<'de, 'a> de::Deserializer<'de> for RecordDeserializer<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let entries = self
+            .headers
+            .iter()
+            .map(|header| header.as_str())
+            .zip(self.fields.iter().map(|field| FieldDeserializer(field)));
+        visitor.visit_map(de::value::MapDeserializer::new(entries))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// One CSV field, parsed into whatever type the row asks for
+struct FieldDeserializer<'a>(&'a str);
+
+impl<'de, 'a> IntoDeserializer<'de, de::value::Error> for FieldDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_field {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let value = self.0.trim();
+                match value.parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(value), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldDeserializer<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    parse_field! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Product {
+        #[validate(length(min = 1, message = "SKU is required"))]
+        sku: String,
+        price: i64,
+        note: Option<String>,
+    }
+
+    async fn import(router: Router, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let response = router.oneshot(Request::post("/import").body(Body::from(body)).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_csv_rows() {
+        let router = Router::new().route(
+            "/import",
+            post(|mut rows: CsvRows<Product>| async move {
+                let mut products = Vec::new();
+                while let Some(product) = rows.next().await? {
+                    products.push((product.price, product.note));
+                }
+                assert_eq!(products, [(100, Some("a, \"quoted\"\nnote".to_string())), (7, None)]);
+                Ok::<_, ApiError>(rows.into_report())
+            }),
+        );
+
+        let body = "sku,price,note\r\n\
+                    A1,100,\"a, \"\"quoted\"\"\nnote\"\r\n\
+                    ,5,\n\
+                    B2,ten,\n\
+                    C3,7,\n";
+        let (status, report) = import(router, body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(report["accepted"], 2);
+        assert_eq!(report["rejected"], 2);
+        assert_eq!(report["errors"][0]["line"], 4);
+        assert_eq!(report["errors"][0]["field"], "sku");
+        assert_eq!(report["errors"][1]["line"], 5);
+        assert_eq!(report["errors"][1]["code"], "invalid_row");
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_rows() {
+        let router = Router::new().route(
+            "/import",
+            post(|mut rows: NdjsonRows<Product>| async move {
+                while rows.next().await?.is_some() {}
+                Ok::<_, ApiError>(rows.into_report())
+            }),
+        );
+
+        let (status, report) = import(router.clone(), "{\"sku\":\"A\",\"price\":1}\n\n{\"sku\":\"B\",\"price\":2}").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["accepted"], 2);
+
+        let (status, report) = import(router, "{\"sku\":\"A\",\"price\":1}\nnot json\n").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(report["errors"][0]["line"], 2);
+    }
+}
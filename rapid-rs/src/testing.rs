@@ -1,7 +1,8 @@
 //! Testing utilities for rapid-rs applications
 //!
 //! Provides helpers for testing API endpoints, database interactions,
-//! and authentication flows.
+//! and authentication flows, with request fixtures taken from the examples
+//! published in the OpenAPI spec (see [`example`]).
 
 use axum::{
     body::Body,
@@ -9,8 +10,11 @@ use axum::{
     Router,
 };
 use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
 use tower::ServiceExt;
 
+use crate::{dependencies::Dependencies, openapi::ApiExample, App};
+
 /// Test client for making requests to your API
 pub struct TestClient {
     app: Router,
@@ -188,6 +192,144 @@ impl TestResponse {
     }
 }
 
+/// The example `name` of `T`, as a fixture
+///
+/// Examples come from [`ApiExample`], the same ones published in the
+/// OpenAPI spec, so a test using one also checks that it still fits `T`.
+///
+/// ```rust,ignore
+/// client.post("/users", &example::<CreateUser>("minimal")).await.assert_status(StatusCode::CREATED);
+/// ```
+///
+/// # Panics
+///
+/// If `T` has no such example or it doesn't deserialize into `T`.
+pub fn example<T: ApiExample + DeserializeOwned>(name: &str) -> T {
+    examples::<T>()
+        .into_iter()
+        .find_map(|(example, value)| (example == name).then_some(value))
+        .unwrap_or_else(|| panic!("{} has no example named `{}`", std::any::type_name::<T>(), name))
+}
+
+/// Every example of `T`, by name
+///
+/// # Panics
+///
+/// If an example doesn't deserialize into `T`.
+pub fn examples<T: ApiExample + DeserializeOwned>() -> Vec<(String, T)> {
+    T::examples()
+        .into_iter()
+        .map(|example| {
+            let value = serde_json::from_value(example.value).unwrap_or_else(|e| {
+                panic!("Example `{}` of {} is invalid: {}", example.name, std::any::type_name::<T>(), e)
+            });
+            (example.name, value)
+        })
+        .collect()
+}
+
+/// Assert every example of `T` deserializes and passes its validation rules
+pub fn assert_examples_valid<T: ApiExample + DeserializeOwned + validator::Validate>() {
+    for (name, value) in examples::<T>() {
+        if let Err(errors) = value.validate() {
+            panic!("Example `{}` of {} fails validation: {}", name, std::any::type_name::<T>(), errors);
+        }
+    }
+}
+
+/// An [`App`] under test with dependency overrides
+///
+/// Overrides replace dependencies registered with `App::provide` for this
+/// test only, like FastAPI's `dependency_overrides`.
+///
+/// ```rust,ignore
+/// let mailer = Arc::new(CaptureEmailProvider::new());
+/// let client = TestApp::new(build_app())
+///     .override_arc::<dyn EmailProvider>(mailer.clone())
+///     .client();
+///
+/// client.post("/signup", &body).await.assert_status(StatusCode::CREATED);
+/// assert_eq!(mailer.sent().len(), 1);
+/// ```
+pub struct TestApp {
+    router: Router,
+    dependencies: Dependencies,
+    overrides: Dependencies,
+}
+
+impl TestApp {
+    pub fn new(app: App) -> Self {
+        let (router, dependencies) = app.into_parts();
+        Self {
+            router,
+            dependencies,
+            overrides: Dependencies::new(),
+        }
+    }
+    
+    /// Replace the dependency of type `T`
+    pub fn override_dependency<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.overrides.insert(value);
+        self
+    }
+    
+    /// Replace a shared dependency, e.g. `Arc<dyn EmailProvider>`
+    pub fn override_arc<T: ?Sized + Send + Sync + 'static>(mut self, value: Arc<T>) -> Self {
+        self.overrides.insert_arc(value);
+        self
+    }
+    
+    /// Drop all overrides, restoring the app's own dependencies
+    pub fn clear_overrides(mut self) -> Self {
+        self.overrides = Dependencies::new();
+        self
+    }
+    
+    /// Dependencies the app sees, overrides applied
+    pub fn dependencies(&self) -> Dependencies {
+        let mut dependencies = self.dependencies.clone();
+        dependencies.merge(&self.overrides);
+        dependencies
+    }
+    
+    /// Client sending requests to the app with the current overrides
+    pub fn client(&self) -> TestClient {
+        TestClient::new(self.router.clone().layer(axum::Extension(self.dependencies())))
+    }
+}
+
+/// Email provider that records messages instead of sending them
+#[cfg(feature = "notifications")]
+#[derive(Default)]
+pub struct CaptureEmailProvider {
+    sent: std::sync::Mutex<Vec<crate::notifications::EmailMessage>>,
+}
+
+#[cfg(feature = "notifications")]
+impl CaptureEmailProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Messages sent so far
+    pub fn sent(&self) -> Vec<crate::notifications::EmailMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+    
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+}
+
+#[cfg(feature = "notifications")]
+#[async_trait::async_trait]
+impl crate::notifications::EmailProvider for CaptureEmailProvider {
+    async fn send(&self, message: crate::notifications::EmailMessage) -> Result<(), crate::error::ApiError> {
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+}
+
 /// Database test utilities
 #[cfg(feature = "db-tests")]
 pub mod db {
@@ -253,4 +395,50 @@ mod tests {
         let json: serde_json::Value = response.json();
         assert_eq!(json["test"], "data");
     }
+    
+    #[tokio::test]
+    async fn test_app_dependency_overrides() {
+        use crate::dependencies::Dep;
+        
+        async fn greeting(Dep(greeting): Dep<String>) -> String {
+            greeting.to_string()
+        }
+        
+        let app = || App::new().provide("hello".to_string()).route("/greeting", get(greeting));
+        
+        TestApp::new(app()).client().get("/greeting").await.assert_text_contains("hello");
+        
+        let overridden = TestApp::new(app()).override_dependency("bonjour".to_string());
+        overridden.client().get("/greeting").await.assert_text_contains("bonjour");
+        overridden.clear_overrides().client().get("/greeting").await.assert_text_contains("hello");
+        
+        let missing = TestApp::new(App::new().route("/greeting", get(greeting)));
+        missing.client().get("/greeting").await.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    
+    #[derive(serde::Serialize, serde::Deserialize, validator::Validate)]
+    struct Signup {
+        #[validate(email)]
+        email: String,
+    }
+    
+    impl ApiExample for Signup {
+        fn examples() -> Vec<crate::openapi::Example> {
+            vec![
+                crate::openapi::Example::new("valid", Signup { email: "ada@example.com".to_string() }),
+                crate::openapi::Example::from_json("invalid", r#"{"email": "not an email"}"#),
+            ]
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_example_fixtures() {
+        let client = TestClient::new(Router::new().route("/echo", axum::routing::post(echo)));
+        let response = client.post("/echo", &example::<Signup>("valid")).await;
+        assert_eq!(response.json::<serde_json::Value>()["email"], "ada@example.com");
+        
+        assert_eq!(examples::<Signup>().len(), 2);
+        let invalid = std::panic::catch_unwind(assert_examples_valid::<Signup>).unwrap_err();
+        assert!(invalid.downcast_ref::<String>().unwrap().contains("Example `invalid`"));
+    }
 }
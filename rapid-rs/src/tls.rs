@@ -0,0 +1,317 @@
+//! HTTPS served directly by the app
+//!
+//! [`App::with_tls`](crate::App::with_tls) terminates TLS in-process, so no
+//! reverse proxy is needed. HTTP/2 is negotiated over ALPN, and certificates
+//! loaded from PEM files are re-read on `SIGHUP`.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .auto_configure()
+//!     .with_tls("/etc/ssl/api.pem", "/etc/ssl/api.key")
+//!     .run()
+//!     .await
+//! ```
+
+use axum::Router;
+use hyper_util::server::graceful::GracefulShutdown;
+use rustls_pki_types::pem::PemObject;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio_rustls::rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+use tokio_rustls::TlsAcceptor;
+
+use crate::listener::{serve_connection, ConnectionLimits};
+
+pub use tokio_rustls::rustls;
+
+/// ALPN protocol used by ACME `tls-alpn-01` validation connections
+#[cfg(feature = "acme")]
+pub(crate) const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// Time allowed for a client to finish the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Certificates and settings for the HTTPS listener
+#[derive(Clone)]
+pub struct TlsConfig {
+    server_config: Arc<ServerConfig>,
+    files: Option<Arc<PemFiles>>,
+}
+
+impl TlsConfig {
+    /// Serve a PEM certificate chain and private key
+    ///
+    /// The files are read again on [`TlsConfig::reload`], which `App::run`
+    /// calls on `SIGHUP`.
+    pub fn from_pem_files(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> io::Result<Self> {
+        let files = Arc::new(PemFiles::load(cert_path.as_ref(), key_path.as_ref())?);
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(files.clone());
+
+        Ok(Self {
+            server_config: Arc::new(with_default_alpn(config)),
+            files: Some(files),
+        })
+    }
+
+    /// Use a prebuilt rustls config
+    ///
+    /// ALPN defaults to HTTP/2 and HTTP/1.1 when the config sets none.
+    pub fn from_rustls(config: ServerConfig) -> Self {
+        Self {
+            server_config: Arc::new(with_default_alpn(config)),
+            files: None,
+        }
+    }
+
+    /// Re-read certificate files; a no-op for configs built with `from_rustls`
+    ///
+    /// On error the previous certificate stays in use.
+    pub fn reload(&self) -> io::Result<()> {
+        match &self.files {
+            Some(files) => files.reload(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn server_config(&self) -> Arc<ServerConfig> {
+        self.server_config.clone()
+    }
+
+    /// Reload certificates whenever the process receives `SIGHUP`
+    pub(crate) fn reload_on_sighup(&self) {
+        #[cfg(unix)]
+        if self.files.is_some() {
+            let config = self.clone();
+            tokio::spawn(async move {
+                let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to listen for SIGHUP");
+                        return;
+                    }
+                };
+                while hangup.recv().await.is_some() {
+                    match config.reload() {
+                        Ok(()) => tracing::info!("🔄 TLS certificates reloaded"),
+                        Err(e) => tracing::error!(error = %e, "Failed to reload TLS certificates"),
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl std::fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("alpn_protocols", &self.server_config.alpn_protocols)
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+fn with_default_alpn(mut config: ServerConfig) -> ServerConfig {
+    if config.alpn_protocols.is_empty() {
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    }
+    config
+}
+
+/// Certificate loaded from PEM files, swapped in place on reload
+struct PemFiles {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl PemFiles {
+    fn load(cert_path: &Path, key_path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            current: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+        })
+    }
+
+    fn reload(&self) -> io::Result<()> {
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for PemFiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PemFiles")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish()
+    }
+}
+
+impl ResolvesServerCert for PemFiles {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> io::Result<CertifiedKey> {
+    let invalid = |path: &Path, e: &dyn std::fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(cert_path, &e))?;
+    if certs.is_empty() {
+        return Err(invalid(cert_path, &"no certificates found"));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid(key_path, &e))?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key).map_err(|e| invalid(key_path, &e))?;
+
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// Accept TLS connections on `listener` and serve `router` over them
+///
+/// Requests carry `ConnectInfo<SocketAddr>` like the plain HTTP listener, and
+/// the same connection `limits` apply. Once `shutdown` turns true the listener
+/// stops accepting and open connections are closed gracefully; the future
+/// resolves when they are done. Connections negotiating `acme-tls/1` only
+/// exist to answer a certificate challenge and are closed once the handshake
+/// completes.
+pub(crate) async fn serve_tls(
+    listener: TcpListener,
+    router: Router,
+    config: Arc<ServerConfig>,
+    limits: ConnectionLimits,
+    mut shutdown: watch::Receiver<bool>,
+) -> io::Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+    let graceful = GracefulShutdown::new();
+
+    loop {
+        let (stream, addr, permit) = tokio::select! {
+            conn = limits.accept(&listener) => conn,
+            _ = shutdown.wait_for(|stopping| *stopping) => break,
+        };
+
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+        let builder = limits.builder();
+        let watcher = graceful.watcher();
+
+        tokio::spawn(async move {
+            let _permit = permit;
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    tracing::debug!(client = %addr, error = %e, "TLS handshake failed");
+                    return;
+                }
+                Err(_) => {
+                    tracing::debug!(client = %addr, "TLS handshake timed out");
+                    return;
+                }
+            };
+
+            #[cfg(feature = "acme")]
+            if stream.get_ref().1.alpn_protocol() == Some(ACME_TLS_ALPN) {
+                return;
+            }
+
+            serve_connection(stream, addr, "https", router, &builder, watcher).await;
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// How `App::with_tls*` was configured; PEM files load when the app runs
+pub(crate) enum TlsSource {
+    PemFiles { cert: PathBuf, key: PathBuf },
+    Config(TlsConfig),
+}
+
+impl TlsSource {
+    pub(crate) fn into_config(self) -> io::Result<TlsConfig> {
+        match self {
+            Self::PemFiles { cert, key } => TlsConfig::from_pem_files(cert, key),
+            Self::Config(config) => Ok(config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::{pki_types::ServerName, ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    #[tokio::test]
+    async fn test_serves_https_with_alpn() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("rapid-rs-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cert.pem"), cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(dir.join("key.pem"), cert.serialize_private_key_pem()).unwrap();
+
+        let tls = TlsConfig::from_pem_files(dir.join("cert.pem"), dir.join("key.pem")).unwrap();
+        tls.reload().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new().route("/", axum::routing::get(|| async { "secure" }));
+        let (stop, stopped) = watch::channel(false);
+        let limits = ConnectionLimits::new(&crate::config::AppConfig::default().server);
+        let server = tokio::spawn(serve_tls(listener, router, tls.server_config(), limits, stopped));
+
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(cert.serialize_der().unwrap())).unwrap();
+        let connect = |alpn: &[u8]| {
+            let mut client = ClientConfig::builder()
+                .with_root_certificates(roots.clone())
+                .with_no_client_auth();
+            client.alpn_protocols = vec![alpn.to_vec()];
+            let connector = TlsConnector::from(Arc::new(client));
+            async move {
+                let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+                let server_name = ServerName::try_from("localhost").unwrap();
+                connector.connect(server_name, tcp).await.unwrap()
+            }
+        };
+
+        let mut stream = connect(b"http/1.1").await;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("secure"));
+
+        let stream = connect(b"h2").await;
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+        drop(stream);
+
+        stop.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
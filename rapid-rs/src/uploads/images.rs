@@ -0,0 +1,1003 @@
+//! Image uploads: validation, metadata stripping and resized variants
+//!
+//! [`ImagePipeline`] checks that an upload really is an image (by its bytes,
+//! not its declared type) within the configured dimensions, strips EXIF and
+//! similar metadata, stores it, and derives the configured variants such as
+//! thumbnails. Variants are stored next to the original as
+//! `<stem>_<variant>.<ext>`, so their URLs follow from the original's name.
+//!
+//! Variants are decoded and resized by [`DefaultImageResizer`], built on the
+//! `image` crate; apps wanting another codec plug in their own
+//! [`ImageResizer`].
+//!
+//! ```rust,ignore
+//! let pipeline = Arc::new(
+//!     ImagePipeline::new(service, ImageConfig::new()
+//!         .with_max_dimensions(8000, 8000)
+//!         .with_variant(ImageVariant::new("thumb", 200, 200).cover())
+//!         .with_variant(ImageVariant::new("large", 1600, 1600))),
+//! );
+//!
+//! let processed = pipeline.upload("cat.jpg", &bytes).await?;
+//! let thumb_url = pipeline.variant_url(&processed.original.stored_name, "thumb").await;
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{FileUploadService, UploadedFile};
+use crate::error::ApiError;
+
+/// Image formats recognised from file contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    /// Detect the format from the leading bytes
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// Format and size of a validated image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageInfo {
+    /// Read the format and dimensions from the image headers
+    pub fn read(data: &[u8]) -> Option<Self> {
+        let format = ImageFormat::sniff(data)?;
+        let (width, height) = match format {
+            ImageFormat::Jpeg => jpeg_dimensions(data)?,
+            ImageFormat::Png => png_dimensions(data)?,
+            ImageFormat::Gif => (u16_le(data, 6)? as u32, u16_le(data, 8)? as u32),
+            ImageFormat::Webp => webp_dimensions(data)?,
+        };
+        Some(Self { format, width, height })
+    }
+}
+
+/// How a variant fits its box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale down to fit inside, keeping the aspect ratio
+    #[default]
+    Contain,
+    /// Scale and crop to fill the box exactly
+    Cover,
+}
+
+/// A derived size of every uploaded image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageVariant {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub fit: Fit,
+}
+
+impl ImageVariant {
+    pub fn new(name: impl Into<String>, width: u32, height: u32) -> Self {
+        Self {
+            name: name.into(),
+            width,
+            height,
+            fit: Fit::Contain,
+        }
+    }
+
+    pub fn cover(mut self) -> Self {
+        self.fit = Fit::Cover;
+        self
+    }
+}
+
+/// Image upload rules and variants
+#[derive(Debug, Clone)]
+pub struct ImageConfig {
+    /// Accepted formats (default: all of [`ImageFormat`])
+    pub formats: Vec<ImageFormat>,
+    pub min_width: u32,
+    pub min_height: u32,
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Remove EXIF, XMP and text metadata (default: true)
+    pub strip_metadata: bool,
+    pub variants: Vec<ImageVariant>,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            formats: vec![ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Gif, ImageFormat::Webp],
+            min_width: 1,
+            min_height: 1,
+            max_width: 10_000,
+            max_height: 10_000,
+            strip_metadata: true,
+            variants: Vec::new(),
+        }
+    }
+}
+
+impl ImageConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_formats(mut self, formats: Vec<ImageFormat>) -> Self {
+        self.formats = formats;
+        self
+    }
+
+    pub fn with_min_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.min_width = width;
+        self.min_height = height;
+        self
+    }
+
+    pub fn with_max_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.max_width = width;
+        self.max_height = height;
+        self
+    }
+
+    pub fn with_strip_metadata(mut self, strip: bool) -> Self {
+        self.strip_metadata = strip;
+        self
+    }
+
+    pub fn with_variant(mut self, variant: ImageVariant) -> Self {
+        self.variants.push(variant);
+        self
+    }
+}
+
+/// Decodes and resizes images for variants
+///
+/// Gets the original bytes, metadata included, so EXIF orientation can be
+/// applied. Returns the encoded variant, in `format` or another format.
+pub trait ImageResizer: Send + Sync + 'static {
+    fn resize(&self, data: &[u8], info: &ImageInfo, variant: &ImageVariant) -> Result<Vec<u8>, ApiError>;
+}
+
+/// [`ImageResizer`] built on the `image` crate
+///
+/// Applies EXIF orientation, never upscales, and encodes variants in the
+/// original's format. GIFs keep only their first frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultImageResizer;
+
+impl ImageResizer for DefaultImageResizer {
+    fn resize(&self, data: &[u8], info: &ImageInfo, variant: &ImageVariant) -> Result<Vec<u8>, ApiError> {
+        use image::{imageops::FilterType, DynamicImage, ImageDecoder, ImageReader};
+
+        let format = match info.format {
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Gif => image::ImageFormat::Gif,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+        };
+        let decode_error = |e: image::ImageError| ApiError::BadRequest(format!("Failed to decode image: {}", e));
+
+        let mut decoder = ImageReader::with_format(std::io::Cursor::new(data), format)
+            .into_decoder()
+            .map_err(decode_error)?;
+        let orientation = decoder.orientation().map_err(decode_error)?;
+        let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+        image.apply_orientation(orientation);
+
+        let (width, height) = (variant.width.min(image.width()), variant.height.min(image.height()));
+        let resized = match variant.fit {
+            Fit::Contain if image.width() <= variant.width && image.height() <= variant.height => image,
+            Fit::Contain => image.resize(width, height, FilterType::Lanczos3),
+            Fit::Cover => image.resize_to_fill(width, height, FilterType::Lanczos3),
+        };
+        // JPEG has no alpha channel
+        let resized = match info.format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(resized.to_rgb8()),
+            _ => resized,
+        };
+
+        let mut out = std::io::Cursor::new(Vec::new());
+        resized
+            .write_to(&mut out, format)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to encode image variant: {}", e)))?;
+        Ok(out.into_inner())
+    }
+}
+
+/// An image and its variants after upload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessedImage {
+    pub info: ImageInfo,
+    pub original: UploadedFile,
+    /// Empty when variants are generated in the background
+    pub variants: Vec<UploadedFile>,
+}
+
+/// Validates, stores and resizes image uploads
+pub struct ImagePipeline {
+    service: Arc<FileUploadService>,
+    config: ImageConfig,
+    resizer: Arc<dyn ImageResizer>,
+}
+
+impl ImagePipeline {
+    pub fn new(service: Arc<FileUploadService>, config: ImageConfig) -> Self {
+        Self {
+            service,
+            config,
+            resizer: Arc::new(DefaultImageResizer),
+        }
+    }
+
+    /// Resize variants with `resizer` instead of [`DefaultImageResizer`]
+    pub fn with_resizer(mut self, resizer: impl ImageResizer) -> Self {
+        self.resizer = Arc::new(resizer);
+        self
+    }
+
+    pub fn config(&self) -> &ImageConfig {
+        &self.config
+    }
+
+    /// Check that `data` is an accepted image within the size limits
+    pub fn validate(&self, data: &[u8]) -> Result<ImageInfo, ApiError> {
+        let info = ImageInfo::read(data)
+            .ok_or_else(|| ApiError::BadRequest("File is not a supported image".to_string()))?;
+        let config = &self.config;
+
+        if !config.formats.contains(&info.format) {
+            return Err(ApiError::BadRequest(format!(
+                "Image type '{}' is not allowed",
+                info.format.mime_type()
+            )));
+        }
+        if info.width < config.min_width || info.height < config.min_height {
+            return Err(ApiError::BadRequest(format!(
+                "Image is {}x{}, smaller than the minimum {}x{}",
+                info.width, info.height, config.min_width, config.min_height
+            )));
+        }
+        if info.width > config.max_width || info.height > config.max_height {
+            return Err(ApiError::BadRequest(format!(
+                "Image is {}x{}, larger than the maximum {}x{}",
+                info.width, info.height, config.max_width, config.max_height
+            )));
+        }
+        Ok(info)
+    }
+
+    /// Validate and store an image, then generate its variants
+    pub async fn upload(&self, filename: &str, data: &[u8]) -> Result<ProcessedImage, ApiError> {
+        let (info, original) = self.store_original(filename, data).await?;
+        let variants = self.generate_variants(&original, &info, data).await?;
+        Ok(ProcessedImage { info, original, variants })
+    }
+
+    /// Validate and store an image, generating variants in a background job
+    ///
+    /// The job runs [`generate_variants_for`](Self::generate_variants_for)
+    /// on the pipeline registered with [`register_jobs`](Self::register_jobs).
+    #[cfg(feature = "jobs")]
+    pub async fn upload_deferred<S: crate::jobs::JobStorage + 'static>(
+        &self,
+        queue: &crate::jobs::JobQueue<S>,
+        filename: &str,
+        data: &[u8],
+    ) -> Result<ProcessedImage, ApiError> {
+        let (info, original) = self.store_original(filename, data).await?;
+        if !self.config.variants.is_empty() {
+            let job = ImageVariantsJob {
+                stored_name: original.stored_name.clone(),
+            };
+            queue.enqueue(job, IMAGE_VARIANTS_JOB).await?;
+        }
+        Ok(ProcessedImage {
+            info,
+            original,
+            variants: Vec::new(),
+        })
+    }
+
+    async fn store_original(&self, filename: &str, data: &[u8]) -> Result<(ImageInfo, UploadedFile), ApiError> {
+        let info = self.validate(data)?;
+        let stripped;
+        let data = if self.config.strip_metadata {
+            stripped = strip_metadata(info.format, data);
+            &stripped
+        } else {
+            data
+        };
+
+        let original = self.service.save(filename, info.format.mime_type(), data).await?;
+        Ok((info, original))
+    }
+
+    /// Generate and store every configured variant of a stored original
+    pub async fn generate_variants(
+        &self,
+        original: &UploadedFile,
+        info: &ImageInfo,
+        data: &[u8],
+    ) -> Result<Vec<UploadedFile>, ApiError> {
+        if self.config.variants.is_empty() {
+            return Ok(Vec::new());
+        }
+        let resizer = self.resizer.clone();
+        let mut variants = Vec::with_capacity(self.config.variants.len());
+        for variant in &self.config.variants {
+            let resized = {
+                let (resizer, data, info, variant) = (resizer.clone(), data.to_vec(), *info, variant.clone());
+                tokio::task::spawn_blocking(move || resizer.resize(&data, &info, &variant))
+                    .await
+                    .map_err(|e| ApiError::InternalServerError(format!("Image resize panicked: {}", e)))??
+            };
+            // Resizers may keep metadata; detect what they produced
+            let format = ImageFormat::sniff(&resized).unwrap_or(info.format);
+            let resized = strip_metadata(format, &resized);
+
+            let stored_name = variant_name(&original.stored_name, &variant.name, format);
+            let mut file = self.service.storage.save_named(&stored_name, format.mime_type(), &resized).await?;
+            file.original_name = original.original_name.clone();
+            variants.push(file);
+        }
+
+        tracing::debug!(stored_name = %original.stored_name, count = variants.len(), "Generated image variants");
+        Ok(variants)
+    }
+
+    /// Generate the variants of an already stored image, reading it back from storage
+    pub async fn generate_variants_for(&self, stored_name: &str) -> Result<Vec<UploadedFile>, ApiError> {
+        let data = self.service.storage.read(stored_name).await?;
+        let info = ImageInfo::read(&data)
+            .ok_or_else(|| ApiError::BadRequest(format!("'{}' is not a supported image", stored_name)))?;
+        let original = UploadedFile::new(
+            stored_name.to_string(),
+            stored_name.to_string(),
+            info.format.mime_type().to_string(),
+            data.len(),
+            self.service.storage.url(stored_name).await,
+        );
+        self.generate_variants(&original, &info, &data).await
+    }
+
+    /// URL of a variant of the original stored as `stored_name`
+    ///
+    /// Variants are stored in the format the resizer produced; this assumes
+    /// it kept the original's format.
+    pub async fn variant_url(&self, stored_name: &str, variant: &str) -> String {
+        let format = std::path::Path::new(stored_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Gif, ImageFormat::Webp]
+                .into_iter()
+                .find(|format| format.extension() == ext))
+            .unwrap_or(ImageFormat::Jpeg);
+        self.service.storage.url(&variant_name(stored_name, variant, format)).await
+    }
+
+    /// Run the variant jobs [`upload_deferred`](Self::upload_deferred)
+    /// enqueues on this pipeline
+    ///
+    /// Register with the queue's registry before starting its workers.
+    #[cfg(feature = "jobs")]
+    pub async fn register_jobs(self: &Arc<Self>, registry: &crate::jobs::JobRegistry) {
+        registry
+            .register_handler(IMAGE_VARIANTS_JOB, Box::new(ImageVariantsHandler(self.clone())))
+            .await;
+    }
+}
+
+/// Stored name of a variant: `<stem>_<variant>.<ext>`
+pub fn variant_name(stored_name: &str, variant: &str, format: ImageFormat) -> String {
+    let stem = stored_name.rsplit_once('.').map_or(stored_name, |(stem, _)| stem);
+    format!("{}_{}.{}", stem, variant, format.extension())
+}
+
+/// Job type of [`ImageVariantsJob`]
+#[cfg(feature = "jobs")]
+pub const IMAGE_VARIANTS_JOB: &str = "image_variants";
+
+/// Payload of the background job generating the variants of a stored image
+#[cfg(feature = "jobs")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageVariantsJob {
+    pub stored_name: String,
+}
+
+/// Runs [`ImageVariantsJob`]s on the pipeline that registered it
+#[cfg(feature = "jobs")]
+struct ImageVariantsHandler(Arc<ImagePipeline>);
+
+#[cfg(feature = "jobs")]
+#[async_trait::async_trait]
+impl crate::jobs::worker::JobHandler for ImageVariantsHandler {
+    async fn handle(
+        &self,
+        payload: serde_json::Value,
+        _ctx: crate::jobs::JobContext,
+        default_timeout: Option<std::time::Duration>,
+    ) -> crate::jobs::JobResult {
+        let job: ImageVariantsJob = serde_json::from_value(payload)
+            .map_err(|e| format!("Failed to deserialize job: {}", e))?;
+        let generate = self.0.generate_variants_for(&job.stored_name);
+        match default_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, generate)
+                .await
+                .map_err(|_| format!("Job timed out after {}s", timeout.as_secs_f64()))??,
+            None => generate.await?,
+        };
+        Ok(())
+    }
+}
+
+/// Remove EXIF, XMP and text metadata, keeping what affects rendering
+///
+/// JPEG loses its APP1 (EXIF/XMP), APP13 (IPTC) and comment segments, PNG its
+/// `eXIf`, text and `tIME` chunks, WebP its `EXIF` and `XMP ` chunks. An EXIF
+/// Orientation other than upright survives as a minimal EXIF block holding
+/// only that tag, so viewers and the resizer still turn the image the right
+/// way. GIFs and anything unparseable come back unchanged.
+pub fn strip_metadata(format: ImageFormat, data: &[u8]) -> Vec<u8> {
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(data),
+        ImageFormat::Png => strip_png(data),
+        ImageFormat::Webp => strip_webp(data),
+        ImageFormat::Gif => None,
+    };
+    stripped.unwrap_or_else(|| data.to_vec())
+}
+
+fn u16_le(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u16_be(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u32_le(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u24_le(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// The Orientation tag of a TIFF-structured EXIF block, if not upright
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let tiff = tiff.strip_prefix(b"Exif\0\0").unwrap_or(tiff);
+    let u16_at = |at| match tiff.get(..2)? {
+        b"II" => u16_le(tiff, at),
+        b"MM" => u16_be(tiff, at),
+        _ => None,
+    };
+    let u32_at = |at| match tiff.get(..2)? {
+        b"II" => u32_le(tiff, at),
+        b"MM" => u32_be(tiff, at),
+        _ => None,
+    };
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (2..=8).contains(orientation))
+}
+
+/// A little-endian TIFF block holding only the Orientation tag
+fn orientation_exif(orientation: u16) -> Vec<u8> {
+    let mut tiff = b"II*\0\x08\0\0\0\x01\0".to_vec();
+    tiff.extend_from_slice(&ORIENTATION_TAG.to_le_bytes());
+    // SHORT, one value, padded to four bytes; then no next IFD
+    tiff.extend_from_slice(&[3, 0, 1, 0, 0, 0]);
+    tiff.extend_from_slice(&orientation.to_le_bytes());
+    tiff.extend_from_slice(&[0; 6]);
+    tiff
+}
+
+/// CRC-32 as PNG chunks use it
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+/// A JPEG segment: `(marker, start, end)` including the marker
+type Segment = (u8, usize, usize);
+
+/// JPEG segments before the scan data, and where the scan data starts
+fn jpeg_segments(data: &[u8]) -> Option<(Vec<Segment>, usize)> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Fill byte
+            0xFF => pos += 1,
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                segments.push((marker, pos, pos + 2));
+                pos += 2;
+            }
+            // End of image before any scan
+            0xD9 => return Some((segments, pos)),
+            _ => {
+                let end = pos + 2 + u16_be(data, pos + 2)? as usize;
+                if end > data.len() {
+                    return None;
+                }
+                segments.push((marker, pos, end));
+                pos = end;
+                // Start of scan: the rest is entropy-coded data
+                if marker == 0xDA {
+                    return Some((segments, pos));
+                }
+            }
+        }
+    }
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let (segments, _) = jpeg_segments(data)?;
+    segments
+        .iter()
+        .find(|(marker, _, _)| matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC))
+        .and_then(|(_, start, _)| Some((u16_be(data, start + 7)? as u32, u16_be(data, start + 5)? as u32)))
+}
+
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    for (marker, start, end) in segments {
+        if marker == 0xE1 {
+            let body = &data[start + 4..end];
+            if let Some(orientation) = exif_orientation(body).filter(|_| body.starts_with(b"Exif\0\0")) {
+                let tiff = orientation_exif(orientation);
+                out.extend_from_slice(&[0xFF, 0xE1]);
+                out.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+                out.extend_from_slice(b"Exif\0\0");
+                out.extend_from_slice(&tiff);
+            }
+        } else if !matches!(marker, 0xED | 0xFE) {
+            out.extend_from_slice(&data[start..end]);
+        }
+    }
+    out.extend_from_slice(&data[scan..]);
+    Some(out)
+}
+
+/// PNG chunks after the signature: `(type, start, end)` including length and CRC
+fn png_chunks(data: &[u8]) -> Option<Vec<([u8; 4], usize, usize)>> {
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < data.len() {
+        let length = u32_be(data, pos)? as usize;
+        let kind: [u8; 4] = data.get(pos + 4..pos + 8)?.try_into().ok()?;
+        let end = pos.checked_add(12)?.checked_add(length)?;
+        if end > data.len() {
+            return None;
+        }
+        chunks.push((kind, pos, end));
+        pos = end;
+    }
+    Some(chunks)
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((u32_be(data, 16)?, u32_be(data, 20)?))
+}
+
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..8]);
+    for (kind, start, end) in png_chunks(data)? {
+        if &kind == b"eXIf" {
+            if let Some(orientation) = exif_orientation(&data[start + 8..end - 4]) {
+                let mut chunk = b"eXIf".to_vec();
+                chunk.extend_from_slice(&orientation_exif(orientation));
+                out.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+                out.extend_from_slice(&chunk);
+                out.extend_from_slice(&crc32(&chunk).to_be_bytes());
+            }
+        } else if !matches!(&kind, b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            out.extend_from_slice(&data[start..end]);
+        }
+    }
+    Some(out)
+}
+
+/// WebP chunks after the RIFF header: `(fourcc, start, end)` including padding
+fn webp_chunks(data: &[u8]) -> Option<Vec<([u8; 4], usize, usize)>> {
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos < data.len() {
+        let kind: [u8; 4] = data.get(pos..pos + 4)?.try_into().ok()?;
+        let size = u32_le(data, pos + 4)? as usize;
+        let end = (pos + 8).checked_add(size + size % 2)?.min(data.len());
+        chunks.push((kind, pos, end));
+        pos = end;
+    }
+    Some(chunks)
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let (kind, start, _) = *webp_chunks(data)?.first()?;
+    let body = start + 8;
+    match &kind {
+        b"VP8 " => {
+            if data.get(body + 3..body + 6)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            Some((
+                (u16_le(data, body + 6)? & 0x3FFF) as u32,
+                (u16_le(data, body + 8)? & 0x3FFF) as u32,
+            ))
+        }
+        b"VP8L" => {
+            if *data.get(body)? != 0x2F {
+                return None;
+            }
+            let bits = u32_le(data, body + 1)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((u24_le(data, body + 4)? + 1, u24_le(data, body + 7)? + 1)),
+        _ => None,
+    }
+}
+
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    const EXIF_FLAG: u8 = 0x08;
+    const XMP_FLAG: u8 = 0x04;
+
+    let chunks = webp_chunks(data)?;
+    let orientation = chunks
+        .iter()
+        .find(|(kind, _, _)| kind == b"EXIF")
+        .and_then(|&(_, start, end)| exif_orientation(&data[start + 8..end]));
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..12]);
+    for (kind, start, end) in chunks {
+        match &kind {
+            b"EXIF" => {
+                if let Some(orientation) = orientation {
+                    let tiff = orientation_exif(orientation);
+                    out.extend_from_slice(b"EXIF");
+                    out.extend_from_slice(&(tiff.len() as u32).to_le_bytes());
+                    out.extend_from_slice(&tiff);
+                }
+            }
+            b"XMP " => {}
+            b"VP8X" => {
+                let flags = out.len() + 8;
+                out.extend_from_slice(&data[start..end]);
+                if let Some(flags) = out.get_mut(flags) {
+                    *flags &= !XMP_FLAG;
+                    if orientation.is_none() {
+                        *flags &= !EXIF_FLAG;
+                    }
+                }
+            }
+            _ => out.extend_from_slice(&data[start..end]),
+        }
+    }
+    let riff_size = u32::try_from(out.len() - 8).ok()?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uploads::UploadConfig;
+
+    fn png(width: u32, height: u32, extra: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut chunk = |kind: &[u8; 4], body: &[u8]| {
+            data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            data.extend_from_slice(kind);
+            data.extend_from_slice(body);
+            data.extend_from_slice(&[0; 4]);
+        };
+        let mut ihdr = width.to_be_bytes().to_vec();
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        chunk(b"IHDR", &ihdr);
+        for (kind, body) in extra {
+            chunk(kind, body);
+        }
+        chunk(b"IDAT", &[1, 2, 3]);
+        chunk(b"IEND", &[]);
+        data
+    }
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        data.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, b'J', b'F']);
+        data.extend_from_slice(&[0xFF, 0xE1, 0x00, 0x08, b'E', b'x', b'i', b'f', 0, 0]);
+        data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 8]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[1, 1, 0x11, 0]);
+        data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0xAB, 0xFF, 0x00, 0xCD, 0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn test_image_info() {
+        assert_eq!(
+            ImageInfo::read(&png(640, 480, &[])),
+            Some(ImageInfo { format: ImageFormat::Png, width: 640, height: 480 })
+        );
+        let info = ImageInfo::read(&jpeg(1024, 768)).unwrap();
+        assert_eq!((info.format, info.width, info.height), (ImageFormat::Jpeg, 1024, 768));
+        assert_eq!(ImageInfo::read(b"GIF89a\x20\x00\x10\x00").map(|i| (i.width, i.height)), Some((32, 16)));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x08\0\0\0".to_vec();
+        webp.extend_from_slice(&[199, 0, 0, 99, 0, 0]);
+        assert_eq!(ImageInfo::read(&webp).map(|i| (i.width, i.height)), Some((200, 100)));
+
+        assert_eq!(ImageInfo::read(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), None);
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let stripped = strip_metadata(ImageFormat::Jpeg, &jpeg(10, 10));
+        assert!(!stripped.windows(4).any(|w| w == b"Exif"));
+        assert!(stripped.starts_with(&[0xFF, 0xD8, 0xFF, 0xE0]));
+        assert!(stripped.ends_with(&[0xAB, 0xFF, 0x00, 0xCD, 0xFF, 0xD9]));
+        assert_eq!(ImageInfo::read(&stripped).map(|i| i.width), Some(10));
+
+        let original = png(10, 10, &[(b"eXIf", b"MM\0*"), (b"tEXt", b"Author\0Ada")]);
+        let stripped = strip_metadata(ImageFormat::Png, &original);
+        assert_eq!(stripped, png(10, 10, &[]));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x0c\0\0\0\x09\0\0\x09\0\0EXIF\x03\0\0\0abc\0".to_vec();
+        let size = (webp.len() - 8) as u32;
+        webp[4..8].copy_from_slice(&size.to_le_bytes());
+        let stripped = strip_metadata(ImageFormat::Webp, &webp);
+        assert_eq!(stripped.len(), 30);
+        assert_eq!(stripped[20], 0);
+        assert_eq!(u32_le(&stripped, 4), Some(22));
+    }
+
+    struct Crop;
+
+    impl ImageResizer for Crop {
+        fn resize(&self, _data: &[u8], _info: &ImageInfo, variant: &ImageVariant) -> Result<Vec<u8>, ApiError> {
+            Ok(png(variant.width, variant.height, &[(b"tEXt", b"resized")]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-images-{}", uuid::Uuid::new_v4()));
+        let service = Arc::new(FileUploadService::new(UploadConfig::new().with_upload_dir(dir.to_string_lossy())));
+        let config = ImageConfig::new()
+            .with_max_dimensions(2000, 2000)
+            .with_variant(ImageVariant::new("thumb", 64, 64).cover());
+        let pipeline = ImagePipeline::new(service, config).with_resizer(Crop);
+
+        assert!(pipeline.upload("huge.png", &png(4000, 10, &[])).await.is_err());
+        assert!(pipeline.upload("fake.png", b"not an image").await.is_err());
+
+        let processed = pipeline
+            .upload("cat.png", &png(800, 600, &[(b"eXIf", b"MM\0*")]))
+            .await
+            .unwrap();
+        assert_eq!(processed.original.content_type, "image/png");
+        let stored = tokio::fs::read(dir.join(&processed.original.stored_name)).await.unwrap();
+        assert_eq!(stored, png(800, 600, &[]));
+
+        let thumb = &processed.variants[0];
+        assert_eq!(thumb.stored_name, variant_name(&processed.original.stored_name, "thumb", ImageFormat::Png));
+        assert_eq!(pipeline.variant_url(&processed.original.stored_name, "thumb").await, thumb.url);
+        let stored = tokio::fs::read(dir.join(&thumb.stored_name)).await.unwrap();
+        assert_eq!(stored, png(64, 64, &[]));
+
+        let regenerated = pipeline.generate_variants_for(&processed.original.stored_name).await.unwrap();
+        assert_eq!(regenerated[0].stored_name, thumb.stored_name);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    fn encoded(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::new(width, height));
+        let mut out = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_default_resizer() {
+        let resize = |data: &[u8], variant: ImageVariant| {
+            let info = ImageInfo::read(data).unwrap();
+            let resized = DefaultImageResizer.resize(data, &info, &variant).unwrap();
+            let out = ImageInfo::read(&resized).unwrap();
+            (out.format, out.width, out.height)
+        };
+
+        let png = encoded(400, 200, image::ImageFormat::Png);
+        assert_eq!(resize(&png, ImageVariant::new("thumb", 100, 100)), (ImageFormat::Png, 100, 50));
+        assert_eq!(resize(&png, ImageVariant::new("thumb", 100, 100).cover()), (ImageFormat::Png, 100, 100));
+        assert_eq!(resize(&png, ImageVariant::new("large", 1600, 1600)), (ImageFormat::Png, 400, 200));
+
+        let jpeg = encoded(300, 300, image::ImageFormat::Jpeg);
+        assert_eq!(resize(&jpeg, ImageVariant::new("thumb", 64, 64)), (ImageFormat::Jpeg, 64, 64));
+
+        let info = ImageInfo::read(&png).unwrap();
+        assert!(DefaultImageResizer.resize(&png[..40], &info, &ImageVariant::new("thumb", 10, 10)).is_err());
+    }
+
+    /// Big-endian EXIF with a camera make and `orientation`
+    fn exif(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"MM\0*\0\0\0\x08\0\x02".to_vec();
+        tiff.extend_from_slice(&[0x01, 0x0F, 0, 2, 0, 0, 0, 4, b'A', b'c', b'm', 0]);
+        tiff.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0; 6]);
+        tiff
+    }
+
+    fn with_app1(jpeg: &[u8], tiff: &[u8]) -> Vec<u8> {
+        let mut data = jpeg[..2].to_vec();
+        data.extend_from_slice(&[0xFF, 0xE1]);
+        data.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend_from_slice(tiff);
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    fn decoded_orientation(data: &[u8]) -> image::metadata::Orientation {
+        use image::ImageDecoder;
+        let reader = image::ImageReader::new(std::io::Cursor::new(data)).with_guessed_format().unwrap();
+        reader.into_decoder().unwrap().orientation().unwrap()
+    }
+
+    #[test]
+    fn test_strip_metadata_keeps_orientation() {
+        use image::metadata::Orientation;
+
+        let jpeg = with_app1(&encoded(8, 4, image::ImageFormat::Jpeg), &exif(6));
+        let stripped = strip_metadata(ImageFormat::Jpeg, &jpeg);
+        assert!(!stripped.windows(3).any(|w| w == b"Acm"));
+        assert_eq!(decoded_orientation(&stripped), Orientation::Rotate90);
+        let upright = strip_metadata(ImageFormat::Jpeg, &with_app1(&encoded(8, 4, image::ImageFormat::Jpeg), &exif(1)));
+        assert!(!upright.windows(4).any(|w| w == b"Exif"));
+
+        let png = encoded(8, 4, image::ImageFormat::Png);
+        let mut chunk = b"eXIf".to_vec();
+        chunk.extend_from_slice(&exif(3));
+        let mut tagged = png[..33].to_vec();
+        tagged.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+        tagged.extend_from_slice(&chunk);
+        tagged.extend_from_slice(&crc32(&chunk).to_be_bytes());
+        tagged.extend_from_slice(&png[33..]);
+        let stripped = strip_metadata(ImageFormat::Png, &tagged);
+        assert!(!stripped.windows(3).any(|w| w == b"Acm"));
+        assert_eq!(decoded_orientation(&stripped), Orientation::Rotate180);
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x0c\0\0\0\x09\0\0\x09\0\0EXIF".to_vec();
+        webp.extend_from_slice(&(exif(8).len() as u32).to_le_bytes());
+        webp.extend_from_slice(&exif(8));
+        let stripped = strip_metadata(ImageFormat::Webp, &webp);
+        assert_eq!(stripped[20], 0x08);
+        assert_eq!(exif_orientation(&stripped[38..]), Some(8));
+        assert!(!stripped.windows(3).any(|w| w == b"Acm"));
+    }
+
+    #[tokio::test]
+    async fn test_variants_follow_orientation_of_stripped_original() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-images-{}", uuid::Uuid::new_v4()));
+        let service = Arc::new(FileUploadService::new(UploadConfig::new().with_upload_dir(dir.to_string_lossy())));
+        let config = ImageConfig::new().with_variant(ImageVariant::new("thumb", 32, 32));
+        let pipeline = ImagePipeline::new(service, config);
+
+        // Stored sideways: 64x32 pixels meant to be shown 32x64
+        let jpeg = with_app1(&encoded(64, 32, image::ImageFormat::Jpeg), &exif(6));
+        let processed = pipeline.upload("portrait.jpg", &jpeg).await.unwrap();
+        let regenerated = pipeline.generate_variants_for(&processed.original.stored_name).await.unwrap();
+        for thumb in processed.variants.iter().chain(&regenerated) {
+            let info = ImageInfo::read(&tokio::fs::read(dir.join(&thumb.stored_name)).await.unwrap()).unwrap();
+            assert_eq!((info.width, info.height), (16, 32));
+        }
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[cfg(feature = "jobs")]
+    #[tokio::test]
+    async fn test_deferred_variants_run_on_registered_pipeline() {
+        use crate::jobs::{InMemoryJobStorage, JobConfig, JobQueue};
+
+        let dir = std::env::temp_dir().join(format!("rapid-rs-images-{}", uuid::Uuid::new_v4()));
+        let service = Arc::new(FileUploadService::new(UploadConfig::new().with_upload_dir(dir.to_string_lossy())));
+        let config = ImageConfig::new().with_variant(ImageVariant::new("thumb", 32, 32));
+        let pipeline = Arc::new(ImagePipeline::new(service, config));
+
+        let queue = JobQueue::new(InMemoryJobStorage::new(), JobConfig::default());
+        pipeline.register_jobs(queue.registry()).await;
+        queue.start_workers().await;
+
+        let processed = pipeline
+            .upload_deferred(&queue, "cat.png", &encoded(128, 64, image::ImageFormat::Png))
+            .await
+            .unwrap();
+        assert!(processed.variants.is_empty());
+
+        let thumb = dir.join(variant_name(&processed.original.stored_name, "thumb", ImageFormat::Png));
+        for _ in 0..100 {
+            if thumb.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let info = ImageInfo::read(&tokio::fs::read(&thumb).await.unwrap()).unwrap();
+        assert_eq!((info.width, info.height), (32, 16));
+        assert_eq!(queue.stats().await.unwrap().dead, 0);
+
+        queue.stop_workers().await;
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -23,9 +23,19 @@
 //! ```
 
 pub mod handler;
+#[cfg(feature = "images")]
+pub mod images;
+pub mod multipart;
+pub mod resumable;
+pub mod signed;
 pub mod storage;
 
 pub use handler::upload_routes;
+#[cfg(feature = "images")]
+pub use images::{DefaultImageResizer, ImageConfig, ImageFormat, ImageInfo, ImagePipeline, ImageResizer, ImageVariant, ProcessedImage};
+pub use multipart::{FormFile, MultipartConfig, TypedMultipart};
+pub use resumable::{resumable_upload_routes, ResumableUpload, ResumableUploads};
+pub use signed::{signed_file_routes, SignedRequest, UrlSigner};
 pub use storage::{LocalStorage, StorageBackend, UploadStorage};
 
 use serde::{Deserialize, Serialize};
@@ -172,10 +182,50 @@ impl FileUploadService {
         self.storage.save(filename, content_type, data).await
     }
 
+    /// Save a file already on disk, such as a completed chunked upload
+    ///
+    /// Checked like [`save`](Self::save). The file may be moved into storage.
+    pub async fn save_file(
+        &self,
+        filename: &str,
+        content_type: &str,
+        path: &std::path::Path,
+    ) -> Result<UploadedFile, crate::error::ApiError> {
+        let size = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| crate::error::ApiError::InternalServerError(format!("Failed to read file: {}", e)))?
+            .len();
+        if size > self.config.max_file_size as u64 {
+            return Err(crate::error::ApiError::BadRequest(format!(
+                "File size {} exceeds maximum allowed size {}",
+                size,
+                self.config.max_file_size
+            )));
+        }
+
+        if !self.config.is_allowed(content_type) {
+            return Err(crate::error::ApiError::BadRequest(format!(
+                "Content type '{}' is not allowed",
+                content_type
+            )));
+        }
+
+        self.storage.save_file(filename, content_type, path).await
+    }
+
     /// Delete a file by stored name
     pub async fn delete(&self, stored_name: &str) -> Result<(), crate::error::ApiError> {
         self.storage.delete(stored_name).await
     }
+
+    /// Time-limited link to a private file
+    pub async fn signed_url(
+        &self,
+        stored_name: &str,
+        ttl: std::time::Duration,
+    ) -> Result<String, crate::error::ApiError> {
+        self.storage.signed_url(stored_name, ttl).await
+    }
 }
 
 #[cfg(test)]
@@ -212,6 +262,26 @@ mod tests {
         assert!(!config.is_allowed("text/plain"));
     }
 
+    #[tokio::test]
+    async fn test_save_file_moves_into_storage() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-uploads-{}", Uuid::new_v4()));
+        let service = FileUploadService::new(UploadConfig::new().with_max_size(8).with_upload_dir(dir.to_string_lossy()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let source = dir.join("incoming");
+
+        tokio::fs::write(&source, b"too large").await.unwrap();
+        assert!(service.save_file("big.txt", "text/plain", &source).await.is_err());
+
+        tokio::fs::write(&source, b"notes").await.unwrap();
+        let file = service.save_file("notes.txt", "text/plain", &source).await.unwrap();
+        assert_eq!((file.original_name.as_str(), file.size), ("notes.txt", 5));
+        assert!(file.stored_name.ends_with(".txt"));
+        assert_eq!(tokio::fs::read(dir.join(&file.stored_name)).await.unwrap(), b"notes");
+        assert!(!source.exists());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
     #[test]
     fn test_is_allowed_empty_means_all() {
         let config = UploadConfig::new();
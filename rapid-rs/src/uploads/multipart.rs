@@ -0,0 +1,539 @@
+//! Typed multipart forms with upload limits
+//!
+//! [`TypedMultipart<T>`] streams file parts to temporary files while enforcing
+//! the size limits and content-type allowlists of a [`MultipartConfig`]
+//! extension, then deserializes and validates `T` like
+//! [`ValidatedJson`](crate::ValidatedJson). File fields are [`FormFile`]s;
+//! their temp files are removed once the last clone is dropped.
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize, Validate)]
+//! struct Avatar {
+//!     #[validate(length(max = 200))]
+//!     caption: String,
+//!     image: FormFile,
+//! }
+//!
+//! async fn upload_avatar(
+//!     State(service): State<Arc<FileUploadService>>,
+//!     TypedMultipart(form): TypedMultipart<Avatar>,
+//! ) -> Result<Json<UploadedFile>, ApiError> {
+//!     Ok(Json(form.image.persist(&service).await?))
+//! }
+//!
+//! let limits = MultipartConfig::new()
+//!     .with_field_limit("image", 2 * 1024 * 1024)
+//!     .with_field_types("image", vec!["image/png", "image/jpeg"]);
+//!
+//! Router::new()
+//!     .route("/avatar", post(upload_avatar))
+//!     .layer(Extension(limits))
+//! ```
+//!
+//! These extractors read the body themselves, so axum's `DefaultBodyLimit`
+//! does not apply; [`MultipartConfig::max_total_size`] caps the whole request.
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{FileUploadService, UploadedFile};
+use crate::error::ApiError;
+use crate::extractors::{
+    form_json, form_value_error, invalid_form, validation_failed, RequestValidation, ValidationFieldError,
+};
+
+/// Limits for multipart extractors, read from a request extension
+#[derive(Debug, Clone)]
+pub struct MultipartConfig {
+    /// Maximum size of a file part (default: 10 MB)
+    pub max_file_size: usize,
+    /// Maximum size of a text part (default: 64 KiB)
+    pub max_text_size: usize,
+    /// Maximum size of the whole body (default: 50 MB)
+    pub max_total_size: usize,
+    /// Maximum number of parts (default: 100)
+    pub max_fields: usize,
+    /// Allowed MIME types of file parts (empty = allow all)
+    pub allowed_types: Vec<String>,
+    /// Size limits for individual fields
+    pub field_limits: HashMap<String, usize>,
+    /// Allowed MIME types for individual fields, replacing `allowed_types`
+    pub field_types: HashMap<String, Vec<String>>,
+    /// Where file parts are buffered (default: the system temp dir)
+    pub temp_dir: PathBuf,
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: 10 * 1024 * 1024,
+            max_text_size: 64 * 1024,
+            max_total_size: 50 * 1024 * 1024,
+            max_fields: 100,
+            allowed_types: Vec::new(),
+            field_limits: HashMap::new(),
+            field_types: HashMap::new(),
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+impl MultipartConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_file_size(mut self, bytes: usize) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    pub fn with_max_text_size(mut self, bytes: usize) -> Self {
+        self.max_text_size = bytes;
+        self
+    }
+
+    pub fn with_max_total_size(mut self, bytes: usize) -> Self {
+        self.max_total_size = bytes;
+        self
+    }
+
+    pub fn with_max_fields(mut self, max: usize) -> Self {
+        self.max_fields = max;
+        self
+    }
+
+    /// MIME types accepted for file parts; `image/*` matches any image type
+    pub fn with_allowed_types(mut self, types: Vec<impl Into<String>>) -> Self {
+        self.allowed_types = types.into_iter().map(|t| t.into()).collect();
+        self
+    }
+
+    pub fn with_field_limit(mut self, field: impl Into<String>, bytes: usize) -> Self {
+        self.field_limits.insert(field.into(), bytes);
+        self
+    }
+
+    pub fn with_field_types(mut self, field: impl Into<String>, types: Vec<impl Into<String>>) -> Self {
+        self.field_types
+            .insert(field.into(), types.into_iter().map(|t| t.into()).collect());
+        self
+    }
+
+    pub fn with_temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = dir.into();
+        self
+    }
+
+    fn limit_for(&self, field: &str, default: usize) -> usize {
+        self.field_limits.get(field).copied().unwrap_or(default)
+    }
+
+    /// Check if a file part of `field` may have this MIME type
+    pub fn is_allowed(&self, field: &str, content_type: &str) -> bool {
+        let allowed = self.field_types.get(field).unwrap_or(&self.allowed_types);
+        if allowed.is_empty() {
+            return true;
+        }
+        let (kind, _) = content_type.split_once('/').unwrap_or((content_type, ""));
+        allowed.iter().any(|t| {
+            t == "*/*"
+                || t.eq_ignore_ascii_case(content_type)
+                || t.strip_suffix("/*").is_some_and(|prefix| prefix.eq_ignore_ascii_case(kind))
+        })
+    }
+}
+
+/// Removes the file when dropped
+#[derive(Debug)]
+struct TempPath(PathBuf);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A file part buffered to a temporary file
+///
+/// Also an extractor for the first file in a multipart body.
+#[derive(Debug, Clone)]
+pub struct FormFile {
+    /// Form field the file was sent as
+    pub field: String,
+    /// Name the client gave the file
+    pub file_name: Option<String>,
+    /// Declared MIME type
+    pub content_type: String,
+    /// Size in bytes
+    pub size: usize,
+    temp: Arc<TempPath>,
+}
+
+impl FormFile {
+    /// Temporary file holding the contents
+    pub fn path(&self) -> &Path {
+        &self.temp.0
+    }
+
+    pub async fn bytes(&self) -> Result<Vec<u8>, ApiError> {
+        tokio::fs::read(self.path())
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to read upload: {}", e)))
+    }
+
+    /// Store the file with `service`, which applies its own upload rules
+    pub async fn persist(&self, service: &FileUploadService) -> Result<UploadedFile, ApiError> {
+        let data = self.bytes().await?;
+        service
+            .save(self.file_name.as_deref().unwrap_or("unnamed"), &self.content_type, &data)
+            .await
+    }
+}
+
+thread_local! {
+    // Files of the form being deserialized, by the token standing in for them
+    static PENDING_FILES: RefCell<HashMap<String, FormFile>> = RefCell::new(HashMap::new());
+}
+
+fn with_pending_files<R>(files: HashMap<String, FormFile>, f: impl FnOnce() -> R) -> R {
+    PENDING_FILES.with(|pending| *pending.borrow_mut() = files);
+    let result = f();
+    PENDING_FILES.with(|pending| pending.borrow_mut().clear());
+    result
+}
+
+impl<'de> Deserialize<'de> for FormFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = String::deserialize(deserializer)?;
+        PENDING_FILES
+            .with(|pending| pending.borrow().get(&token).cloned())
+            .ok_or_else(|| serde::de::Error::custom("expected a file upload"))
+    }
+}
+
+#[derive(Default)]
+struct FormParts {
+    text: Vec<(String, String)>,
+    files: Vec<FormFile>,
+}
+
+fn limit_error(field: &str, code: &str, message: String) -> Response {
+    validation_failed(vec![ValidationFieldError::new(field, code, message)])
+}
+
+fn multipart_error(error: multer::Error) -> Response {
+    match error {
+        multer::Error::StreamSizeExceeded { limit } => limit_error(
+            "",
+            "request_too_large",
+            format!("Request body exceeds {} bytes", limit),
+        ),
+        error => invalid_form(vec![ValidationFieldError::new("", "multipart", error.to_string())]),
+    }
+}
+
+fn io_error(error: std::io::Error) -> Response {
+    ApiError::InternalServerError(format!("Failed to buffer upload: {}", error)).into_response()
+}
+
+/// Read every part, streaming files to disk and enforcing `config`'s limits
+async fn read_parts(req: Request, config: &MultipartConfig) -> Result<FormParts, Response> {
+    let boundary = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| multer::parse_boundary(value).ok())
+        .ok_or_else(|| {
+            invalid_form(vec![ValidationFieldError::new(
+                "",
+                "content_type",
+                "Expected request with `Content-Type: multipart/form-data`",
+            )])
+        })?;
+
+    let constraints = multer::Constraints::new()
+        .size_limit(multer::SizeLimit::new().whole_stream(config.max_total_size as u64));
+    let mut multipart = multer::Multipart::with_constraints(req.into_body().into_data_stream(), boundary, constraints);
+
+    let mut parts = FormParts::default();
+    let mut count = 0;
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        count += 1;
+        if count > config.max_fields {
+            return Err(limit_error(
+                "",
+                "too_many_fields",
+                format!("At most {} fields are allowed", config.max_fields),
+            ));
+        }
+        let name = field.name().unwrap_or_default().to_string();
+
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            let limit = config.limit_for(&name, config.max_text_size);
+            let mut value = Vec::new();
+            while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                if value.len() + chunk.len() > limit {
+                    return Err(limit_error(&name, "field_too_large", format!("Field exceeds {} bytes", limit)));
+                }
+                value.extend_from_slice(&chunk);
+            }
+            let value = String::from_utf8(value).map_err(|_| {
+                invalid_form(vec![ValidationFieldError::new(name.clone(), "invalid_utf8", "Text fields must be UTF-8")])
+            })?;
+            parts.text.push((name, value));
+            continue;
+        };
+
+        let content_type = field
+            .content_type()
+            .map_or_else(|| "application/octet-stream".to_string(), |mime| mime.essence_str().to_string());
+        if !config.is_allowed(&name, &content_type) {
+            return Err(limit_error(
+                &name,
+                "content_type",
+                format!("Content type '{}' is not allowed", content_type),
+            ));
+        }
+
+        let limit = config.limit_for(&name, config.max_file_size);
+        tokio::fs::create_dir_all(&config.temp_dir).await.map_err(io_error)?;
+        let temp = TempPath(config.temp_dir.join(format!("rapid-rs-upload-{}", Uuid::new_v4())));
+        let mut out = tokio::fs::File::create(&temp.0).await.map_err(io_error)?;
+        let mut size = 0;
+        while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+            size += chunk.len();
+            if size > limit {
+                return Err(limit_error(&name, "file_too_large", format!("File exceeds {} bytes", limit)));
+            }
+            out.write_all(&chunk).await.map_err(io_error)?;
+        }
+        out.flush().await.map_err(io_error)?;
+
+        // Browsers send an empty, unnamed part for a file input left blank
+        if file_name.is_empty() && size == 0 {
+            continue;
+        }
+        parts.files.push(FormFile {
+            field: name,
+            file_name: Some(file_name).filter(|name| !name.is_empty()),
+            content_type,
+            size,
+            temp: Arc::new(temp),
+        });
+    }
+
+    Ok(parts)
+}
+
+fn config_of(req: &Request) -> MultipartConfig {
+    req.extensions().get::<MultipartConfig>().cloned().unwrap_or_default()
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for FormFile
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let config = config_of(&req);
+        let parts = read_parts(req, &config).await?;
+        parts.files.into_iter().next().ok_or_else(|| {
+            invalid_form(vec![ValidationFieldError::new("", "missing_file", "No file provided")])
+        })
+    }
+}
+
+/// Extractor that deserializes and validates `multipart/form-data` bodies
+///
+/// Text parts are parsed like [`ValidatedForm`](crate::ValidatedForm) fields;
+/// file parts deserialize into [`FormFile`] (or `Option<FormFile>`) fields.
+/// Each field may appear once.
+pub struct TypedMultipart<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for TypedMultipart<T>
+where
+    T: DeserializeOwned + Validate + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let validation = RequestValidation::new(req.headers(), req.extensions());
+        let config = config_of(&req);
+        let parts = read_parts(req, &config).await?;
+
+        // Files stand in the form as unguessable tokens `FormFile` looks up
+        let mut pending = HashMap::new();
+        let encoded = {
+            let mut encoded = form_urlencoded::Serializer::new(String::new());
+            encoded.extend_pairs(&parts.text);
+            for file in parts.files {
+                let token = format!("rapid-rs-file:{}", Uuid::new_v4());
+                encoded.append_pair(&file.field, &token);
+                pending.insert(token, file);
+            }
+            encoded.finish()
+        };
+
+        let value = with_pending_files(pending, || {
+            let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(encoded.as_bytes()));
+            serde_path_to_error::deserialize::<_, T>(deserializer)
+        })
+        .map_err(form_value_error)?;
+
+        let value = validation
+            .check(value, || {
+                form_json(parts.text.iter().map(|(key, value)| (key.as_str(), value.as_str())))
+            })
+            .await?;
+
+        Ok(TypedMultipart(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::post, Extension, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize, Validate)]
+    struct Avatar {
+        #[validate(length(min = 1))]
+        caption: String,
+        image: FormFile,
+        thumbnail: Option<FormFile>,
+    }
+
+    const BOUNDARY: &str = "rapid-rs-boundary";
+
+    /// Field name, file name and content type for files, and contents
+    type Part<'a> = (&'a str, Option<(&'a str, &'a str)>, &'a str);
+
+    fn multipart(parts: &[Part]) -> axum::http::Request<Body> {
+        let mut body = String::new();
+        for (name, file, value) in parts {
+            body.push_str(&format!("--{}\r\n", BOUNDARY));
+            match file {
+                Some((file_name, content_type)) => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                    name, file_name, content_type
+                )),
+                None => body.push_str(&format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name)),
+            }
+            body.push_str(value);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+
+        axum::http::Request::post("/avatar")
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn router(dir: &Path) -> Router {
+        let config = MultipartConfig::new()
+            .with_temp_dir(dir)
+            .with_field_limit("image", 16)
+            .with_field_types("image", vec!["image/*"]);
+        Router::new()
+            .route(
+                "/avatar",
+                post(|TypedMultipart(form): TypedMultipart<Avatar>| async move {
+                    assert!(form.thumbnail.is_none());
+                    let data = form.image.bytes().await.unwrap();
+                    format!("{}:{}:{}", form.caption, form.image.file_name.unwrap(), String::from_utf8(data).unwrap())
+                }),
+            )
+            .layer(Extension(config))
+    }
+
+    async fn first_error(response: Response) -> Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body["errors"][0].clone()
+    }
+
+    #[tokio::test]
+    async fn test_typed_multipart() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-multipart-{}", Uuid::new_v4()));
+        let router = router(&dir);
+
+        let request = multipart(&[
+            ("caption", None, "Me"),
+            ("image", Some(("me.png", "image/png")), "png-bytes"),
+            ("thumbnail", Some(("", "application/octet-stream")), ""),
+        ]);
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"Me:me.png:png-bytes");
+
+        let request = multipart(&[("caption", None, ""), ("image", Some(("me.png", "image/png")), "png")]);
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(first_error(response).await["field"], "caption");
+
+        let request = multipart(&[("caption", None, "Me")]);
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(first_error(response).await["code"], "missing_field");
+
+        // Temp files go away with the form
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_limits() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-multipart-{}", Uuid::new_v4()));
+        let router = router(&dir);
+
+        let request = multipart(&[
+            ("caption", None, "Me"),
+            ("image", Some(("me.png", "image/png")), "far too many png bytes"),
+        ]);
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = first_error(response).await;
+        assert_eq!(error["field"], "image");
+        assert_eq!(error["code"], "file_too_large");
+
+        let request = multipart(&[("caption", None, "Me"), ("image", Some(("me.exe", "application/x-msdownload")), "MZ")]);
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(first_error(response).await["code"], "content_type");
+
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let config = MultipartConfig::new()
+            .with_allowed_types(vec!["application/pdf"])
+            .with_field_types("avatar", vec!["image/*"]);
+        assert!(config.is_allowed("avatar", "image/webp"));
+        assert!(!config.is_allowed("avatar", "application/pdf"));
+        assert!(config.is_allowed("resume", "application/pdf"));
+        assert!(!config.is_allowed("resume", "image/png"));
+    }
+}
@@ -0,0 +1,597 @@
+//! Resumable uploads (tus-style)
+//!
+//! Large files are sent in chunks that can be resumed after a dropped
+//! connection, following the core of the [tus](https://tus.io) protocol:
+//!
+//! - `POST /upload/resumable` with `Upload-Length` (and optionally
+//!   `Upload-Metadata: filename <base64>,filetype <base64>`) creates an upload
+//!   and answers with its `Location`
+//! - `PATCH <location>` with `Content-Type: application/offset+octet-stream`
+//!   and `Upload-Offset` appends bytes; the upload is handed to the storage
+//!   backend once all bytes have arrived
+//! - `HEAD <location>` reports `Upload-Offset`, where a client resumes from;
+//!   `GET` also returns the upload as JSON, including the stored file
+//! - `DELETE <location>` cancels the upload
+//!
+//! Partial data lives on local disk until the upload completes. Uploads not
+//! touched for the configured TTL are removed by
+//! [`expire_stale`](ResumableUploads::expire_stale).
+//!
+//! ```rust,ignore
+//! let service = Arc::new(FileUploadService::new(UploadConfig::new().with_max_size(2 << 30)));
+//! let resumable = Arc::new(ResumableUploads::new(service.clone()));
+//! resumable.clone().spawn_expiry(Schedule::hourly());
+//!
+//! App::new()
+//!     .mount(upload_routes(service))
+//!     .mount(resumable_upload_routes(resumable))
+//! ```
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Path, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use super::{FileUploadService, UploadedFile};
+use crate::clock::SharedClock;
+use crate::error::ApiError;
+
+const TUS_VERSION: &str = "1.0.0";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_EXPIRES: HeaderName = HeaderName::from_static("upload-expires");
+
+/// State of a resumable upload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumableUpload {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    /// Total size in bytes
+    pub length: u64,
+    /// Bytes received so far
+    pub offset: u64,
+    pub created_at: DateTime<Utc>,
+    /// Removed after this unless touched again
+    pub expires_at: DateTime<Utc>,
+    /// The stored file, once complete
+    pub file: Option<UploadedFile>,
+}
+
+impl ResumableUpload {
+    pub fn is_complete(&self) -> bool {
+        self.file.is_some()
+    }
+}
+
+/// Resumable upload sessions, finished into a [`FileUploadService`]
+pub struct ResumableUploads {
+    service: Arc<FileUploadService>,
+    dir: PathBuf,
+    ttl: Duration,
+    clock: SharedClock,
+    /// Uploads a `PATCH` is currently writing to
+    busy: Mutex<HashSet<String>>,
+}
+
+impl ResumableUploads {
+    /// Keeps partial uploads in `.resumable` under the service's upload directory
+    pub fn new(service: Arc<FileUploadService>) -> Self {
+        let dir = PathBuf::from(&service.config.upload_dir).join(".resumable");
+        Self {
+            service,
+            dir,
+            ttl: Duration::from_secs(24 * 3600),
+            clock: crate::clock::system(),
+            busy: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Directory for partial uploads
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// How long an upload may sit idle before it expires (default 24 hours)
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Start an upload of `length` bytes
+    pub async fn create(
+        &self,
+        filename: &str,
+        content_type: &str,
+        length: u64,
+    ) -> Result<ResumableUpload, ApiError> {
+        let max_size = self.service.config.max_file_size;
+        if length > max_size as u64 {
+            return Err(ApiError::BadRequest(format!(
+                "File size {} exceeds maximum allowed size {}",
+                length, max_size
+            )));
+        }
+        if !self.service.config.is_allowed(content_type) {
+            return Err(ApiError::BadRequest(format!(
+                "Content type '{}' is not allowed",
+                content_type
+            )));
+        }
+
+        fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| io_error("create upload dir", e))?;
+
+        let now = self.clock.now();
+        let upload = ResumableUpload {
+            id: Uuid::new_v4().to_string(),
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            length,
+            offset: 0,
+            created_at: now,
+            expires_at: self.expiry(now),
+            file: None,
+        };
+        fs::write(self.data_path(&upload.id), b"")
+            .await
+            .map_err(|e| io_error("create upload", e))?;
+        self.save(&upload).await?;
+
+        if length == 0 {
+            return self.finish(upload).await;
+        }
+        Ok(upload)
+    }
+
+    /// Current state of an upload
+    pub async fn get(&self, id: &str) -> Result<ResumableUpload, ApiError> {
+        let not_found = || ApiError::NotFound("Upload not found".to_string());
+        // Only our own IDs, so paths can't escape the directory
+        Uuid::parse_str(id).map_err(|_| not_found())?;
+
+        let json = match fs::read(self.meta_path(id)).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(not_found()),
+            Err(e) => return Err(io_error("read upload", e)),
+        };
+        let upload: ResumableUpload = serde_json::from_slice(&json)
+            .map_err(|e| ApiError::InternalServerError(format!("Corrupt upload state: {}", e)))?;
+
+        if upload.expires_at <= self.clock.now() {
+            return Err(not_found());
+        }
+        Ok(upload)
+    }
+
+    /// Append `chunks` at `offset`, which must be the upload's current offset
+    ///
+    /// Bytes received before the stream fails are kept, so the client can
+    /// resume from the new offset.
+    pub async fn append<S, E>(&self, id: &str, offset: u64, mut chunks: S) -> Result<ResumableUpload, ApiError>
+    where
+        S: futures::Stream<Item = Result<axum::body::Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        let _busy = self.lock(id)?;
+        let mut upload = self.get(id).await?;
+        if upload.is_complete() {
+            return Err(ApiError::BadRequest("Upload is already complete".to_string()));
+        }
+        if offset != upload.offset {
+            return Err(ApiError::BadRequest(format!(
+                "Upload-Offset {} does not match current offset {}",
+                offset, upload.offset
+            )));
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.data_path(id))
+            .await
+            .map_err(|e| io_error("open upload", e))?;
+
+        let mut failure = None;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    failure = Some(ApiError::BadRequest(format!("Upload interrupted: {}", e)));
+                    break;
+                }
+            };
+            if upload.offset + chunk.len() as u64 > upload.length {
+                failure = Some(ApiError::BadRequest("Chunk exceeds Upload-Length".to_string()));
+                break;
+            }
+            if let Err(e) = file.write_all(&chunk).await {
+                failure = Some(io_error("write upload", e));
+                break;
+            }
+            upload.offset += chunk.len() as u64;
+        }
+        file.flush().await.map_err(|e| io_error("write upload", e))?;
+        // A failed write may have left part of a chunk behind
+        file.set_len(upload.offset).await.map_err(|e| io_error("write upload", e))?;
+        drop(file);
+
+        upload.expires_at = self.expiry(self.clock.now());
+        self.save(&upload).await?;
+        if let Some(error) = failure {
+            return Err(error);
+        }
+
+        if upload.offset == upload.length {
+            return self.finish(upload).await;
+        }
+        Ok(upload)
+    }
+
+    /// Cancel an upload, discarding what was received
+    pub async fn cancel(&self, id: &str) -> Result<(), ApiError> {
+        let _busy = self.lock(id)?;
+        self.get(id).await?;
+        self.remove(id).await
+    }
+
+    /// Remove uploads past their expiry, returning how many were removed
+    ///
+    /// Completed uploads only lose their resumable state; the stored file stays.
+    pub async fn expire_stale(&self) -> Result<usize, ApiError> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(io_error("read upload dir", e)),
+        };
+
+        let now = self.clock.now();
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await.map_err(|e| io_error("read upload dir", e))? {
+            let path = entry.path();
+            let Some(id) = path.file_name().and_then(|name| name.to_str()?.strip_suffix(".json")) else {
+                continue;
+            };
+            let expired = match fs::read(&path).await {
+                Ok(json) => serde_json::from_slice::<ResumableUpload>(&json).is_ok_and(|upload| upload.expires_at <= now),
+                Err(_) => false,
+            };
+            if expired && !self.busy.lock().unwrap().contains(id) {
+                self.remove(id).await?;
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            tracing::info!(removed, "Expired stale resumable uploads");
+        }
+        Ok(removed)
+    }
+
+    /// Run [`expire_stale`](Self::expire_stale) on `schedule` in the background
+    #[cfg(feature = "jobs")]
+    pub fn spawn_expiry(self: Arc<Self>, schedule: crate::jobs::scheduler::Schedule) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            while schedule.wait_next(self.clock.as_ref()).await.is_some() {
+                if let Err(e) = self.expire_stale().await {
+                    tracing::warn!(error = %e, "Failed to expire resumable uploads");
+                }
+            }
+        })
+    }
+
+    /// Hand the complete data to the storage backend
+    async fn finish(&self, mut upload: ResumableUpload) -> Result<ResumableUpload, ApiError> {
+        let file = self
+            .service
+            .save_file(&upload.filename, &upload.content_type, &self.data_path(&upload.id))
+            .await?;
+
+        tracing::info!(upload_id = %upload.id, stored_name = %file.stored_name, size = file.size, "Resumable upload completed");
+        upload.file = Some(file);
+        self.save(&upload).await?;
+        remove_if_exists(&self.data_path(&upload.id)).await?;
+        Ok(upload)
+    }
+
+    fn lock(&self, id: &str) -> Result<BusyGuard<'_>, ApiError> {
+        if !self.busy.lock().unwrap().insert(id.to_string()) {
+            return Err(ApiError::BadRequest("Upload is busy with another request".to_string()));
+        }
+        Ok(BusyGuard { busy: &self.busy, id: id.to_string() })
+    }
+
+    async fn save(&self, upload: &ResumableUpload) -> Result<(), ApiError> {
+        let json = serde_json::to_vec(upload)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to serialize upload: {}", e)))?;
+        // Write then rename, so a crash never leaves half a state file
+        let temp = self.dir.join(format!("{}.json.tmp", upload.id));
+        fs::write(&temp, json).await.map_err(|e| io_error("save upload", e))?;
+        fs::rename(&temp, self.meta_path(&upload.id))
+            .await
+            .map_err(|e| io_error("save upload", e))
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), ApiError> {
+        remove_if_exists(&self.data_path(id)).await?;
+        remove_if_exists(&self.meta_path(id)).await
+    }
+
+    fn expiry(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let ttl = chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX);
+        now.checked_add_signed(ttl).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    fn data_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.part", id))
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+struct BusyGuard<'a> {
+    busy: &'a Mutex<HashSet<String>>,
+    id: String,
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.busy.lock().unwrap().remove(&self.id);
+    }
+}
+
+async fn remove_if_exists(path: &std::path::Path) -> Result<(), ApiError> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(io_error("delete upload", e)),
+        _ => Ok(()),
+    }
+}
+
+fn io_error(action: &str, e: std::io::Error) -> ApiError {
+    ApiError::InternalServerError(format!("Failed to {}: {}", action, e))
+}
+
+/// `filename` and `filetype` from an `Upload-Metadata` header
+fn parse_metadata(value: &str) -> Result<(Option<String>, Option<String>), ApiError> {
+    let mut filename = None;
+    let mut filetype = None;
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (key, encoded) = pair.split_once(' ').unwrap_or((pair, ""));
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| ApiError::BadRequest(format!("Invalid Upload-Metadata value for '{}'", key)))?;
+        match key {
+            "filename" => filename = Some(decoded),
+            "filetype" | "content_type" => filetype = Some(decoded),
+            _ => {}
+        }
+    }
+    Ok((filename, filetype))
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Result<u64, ApiError> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok()?.parse().ok())
+        .ok_or_else(|| ApiError::BadRequest(format!("Missing or invalid {} header", name)))
+}
+
+fn offset_headers(upload: &ResumableUpload) -> [(HeaderName, HeaderValue); 4] {
+    [
+        (TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION)),
+        (UPLOAD_OFFSET, HeaderValue::from(upload.offset)),
+        (UPLOAD_LENGTH, HeaderValue::from(upload.length)),
+        (
+            UPLOAD_EXPIRES,
+            HeaderValue::from_str(&upload.expires_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+                .unwrap_or(HeaderValue::from_static("")),
+        ),
+    ]
+}
+
+/// Create a resumable upload
+pub async fn create_upload(
+    State(uploads): State<Arc<ResumableUploads>>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let length = header_u64(&headers, &UPLOAD_LENGTH)?;
+    let (filename, filetype) = match headers.get(&UPLOAD_METADATA).and_then(|value| value.to_str().ok()) {
+        Some(value) => parse_metadata(value)?,
+        None => (None, None),
+    };
+    let filename = filename.unwrap_or_else(|| "unnamed".to_string());
+    let content_type = filetype.unwrap_or_else(|| "application/octet-stream".to_string());
+
+    let upload = uploads.create(&filename, &content_type, length).await?;
+    let location = format!("{}/{}", uri.path().trim_end_matches('/'), upload.id);
+
+    Ok((
+        StatusCode::CREATED,
+        offset_headers(&upload),
+        [(header::LOCATION, location)],
+        Json(upload),
+    )
+        .into_response())
+}
+
+/// State of an upload; `HEAD` gives just the offset headers
+pub async fn get_upload(
+    State(uploads): State<Arc<ResumableUploads>>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let upload = uploads.get(&id).await?;
+    Ok((
+        offset_headers(&upload),
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(upload),
+    )
+        .into_response())
+}
+
+/// Append a chunk
+pub async fn patch_upload(
+    State(uploads): State<Arc<ResumableUploads>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, ApiError> {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    if content_type != Some(OFFSET_CONTENT_TYPE) {
+        return Err(ApiError::BadRequest(format!("Content-Type must be {}", OFFSET_CONTENT_TYPE)));
+    }
+    let offset = header_u64(&headers, &UPLOAD_OFFSET)?;
+
+    let upload = uploads.append(&id, offset, body.into_data_stream()).await?;
+    Ok((StatusCode::NO_CONTENT, offset_headers(&upload)).into_response())
+}
+
+/// Cancel an upload
+pub async fn delete_upload(
+    State(uploads): State<Arc<ResumableUploads>>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    uploads.cancel(&id).await?;
+    Ok((StatusCode::NO_CONTENT, [(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION))]).into_response())
+}
+
+/// Create resumable upload routes
+///
+/// Mounts:
+/// - POST /upload/resumable - Start an upload
+/// - GET, HEAD, PATCH, DELETE /upload/resumable/:id - Inspect, append to or cancel it
+pub fn resumable_upload_routes(uploads: Arc<ResumableUploads>) -> Router {
+    Router::new()
+        .route("/upload/resumable", post(create_upload))
+        .route(
+            "/upload/resumable/:id",
+            get(get_upload).patch(patch_upload).delete(delete_upload),
+        )
+        .with_state(uploads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uploads::UploadConfig;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn uploads(dir: &std::path::Path, clock: SharedClock) -> Arc<ResumableUploads> {
+        let config = UploadConfig::new().with_upload_dir(dir.to_string_lossy());
+        let service = Arc::new(FileUploadService::new(config));
+        Arc::new(ResumableUploads::new(service).with_clock(clock))
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("rapid-rs-resumable-{}", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_interruption() {
+        let dir = temp_dir();
+        let uploads = uploads(&dir, crate::clock::system());
+        let router = resumable_upload_routes(uploads.clone());
+
+        let metadata = format!(
+            "filename {},filetype {}",
+            base64::engine::general_purpose::STANDARD.encode("notes.txt"),
+            base64::engine::general_purpose::STANDARD.encode("text/plain"),
+        );
+        let request = Request::post("/upload/resumable")
+            .header("upload-length", "11")
+            .header("upload-metadata", metadata)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let id = location.rsplit('/').next().unwrap().to_string();
+
+        // The connection drops after the first chunk
+        let chunks = futures::stream::iter(vec![
+            Ok(axum::body::Bytes::from_static(b"hello")),
+            Err("connection reset"),
+        ]);
+        assert!(uploads.append(&id, 0, chunks).await.is_err());
+
+        let response = router
+            .clone()
+            .oneshot(Request::head(&location).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["upload-offset"], "5");
+
+        let patch = |offset: &str, body: &'static str| {
+            Request::patch(&location)
+                .header("content-type", OFFSET_CONTENT_TYPE)
+                .header("upload-offset", offset)
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let response = router.clone().oneshot(patch("0", " world")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = router.clone().oneshot(patch("5", " world")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()["upload-offset"], "11");
+
+        let upload = uploads.get(&id).await.unwrap();
+        let file = upload.file.expect("upload should be stored");
+        assert_eq!(file.original_name, "notes.txt");
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(file.size, 11);
+        assert_eq!(fs::read(dir.join(&file.stored_name)).await.unwrap(), b"hello world");
+        assert!(!uploads.data_path(&id).exists());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expire_stale() {
+        let dir = temp_dir();
+        let clock = crate::clock::ManualClock::frozen();
+        let uploads = uploads(&dir, clock.shared());
+
+        let stale = uploads.create("a.bin", "application/octet-stream", 10).await.unwrap();
+        clock.advance(Duration::from_secs(23 * 3600));
+        let fresh = uploads.create("b.bin", "application/octet-stream", 10).await.unwrap();
+        clock.advance(Duration::from_secs(2 * 3600));
+
+        assert!(matches!(uploads.get(&stale.id).await, Err(ApiError::NotFound(_))));
+        assert_eq!(uploads.expire_stale().await.unwrap(), 1);
+        assert!(!uploads.data_path(&stale.id).exists());
+        assert!(uploads.get(&fresh.id).await.is_ok());
+        assert!(uploads.create("c.bin", "application/octet-stream", 11 << 20).await.is_err());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
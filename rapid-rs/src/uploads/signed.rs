@@ -0,0 +1,270 @@
+//! Time-limited signed URLs for private files
+//!
+//! A [`UrlSigner`] appends `expires` and `signature` query parameters to a
+//! path; the [`SignedRequest`] extractor accepts a request only if they
+//! match and haven't expired. Storage backends hand out signed links through
+//! [`UploadStorage::signed_url`](super::UploadStorage::signed_url) — local
+//! storage signs links to [`signed_file_routes`], object stores can return
+//! their own presigned URLs so bytes never pass through the app.
+//!
+//! ```rust,ignore
+//! let signer = UrlSigner::new(std::env::var("FILE_URL_SECRET")?);
+//! let storage = LocalStorage::new("./private").with_signer(signer.clone());
+//! let service = Arc::new(FileUploadService::with_storage(config, StorageBackend::Local(storage)));
+//!
+//! let link = service.signed_url(&file.stored_name, Duration::from_secs(600)).await?;
+//!
+//! App::new().mount(signed_file_routes(service, signer))
+//! ```
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, Path, State},
+    http::{header, request::Parts},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::FileUploadService;
+use crate::clock::SharedClock;
+use crate::error::ApiError;
+
+/// Signs and verifies URL paths with HMAC-SHA256
+#[derive(Clone)]
+pub struct UrlSigner {
+    key: Arc<[u8]>,
+    prefix: String,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlSigner").field("prefix", &self.prefix).finish_non_exhaustive()
+    }
+}
+
+impl UrlSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: Arc::from(secret.as_ref()),
+            prefix: "/files".to_string(),
+            clock: crate::clock::system(),
+        }
+    }
+
+    /// Path [`signed_file_routes`] serve files under (default `/files`)
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// `path` with a signature valid for `ttl`
+    pub fn sign(&self, path: &str, ttl: Duration) -> String {
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        let expires = self.clock.now().checked_add_signed(ttl).unwrap_or(DateTime::<Utc>::MAX_UTC);
+        self.sign_until(path, expires)
+    }
+
+    /// `path` with a signature valid until `expires`
+    pub fn sign_until(&self, path: &str, expires: DateTime<Utc>) -> String {
+        let expires = expires.timestamp();
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(path, expires).finalize().into_bytes());
+        let separator = if path.contains('?') { '&' } else { '?' };
+        format!("{}{}expires={}&signature={}", path, separator, expires, signature)
+    }
+
+    /// Signed link to a stored file under the prefix
+    pub fn file_url(&self, stored_name: &str, ttl: Duration) -> String {
+        self.sign(&format!("{}/{}", self.prefix, stored_name), ttl)
+    }
+
+    /// Check a signature made by [`sign`](Self::sign) for `path`
+    pub fn verify(&self, path: &str, expires: i64, signature: &str) -> Result<(), ApiError> {
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ApiError::Forbidden)?;
+        self.mac(path, expires)
+            .verify_slice(&signature)
+            .map_err(|_| ApiError::Forbidden)?;
+
+        if expires <= self.clock.now().timestamp() {
+            return Err(ApiError::Forbidden);
+        }
+        Ok(())
+    }
+
+    fn mac(&self, path: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(path.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+}
+
+/// A request whose URL carries a valid signature
+///
+/// Needs the [`UrlSigner`] as an `Extension`. The signature covers the path;
+/// other query parameters are not signed.
+#[derive(Debug, Clone)]
+pub struct SignedRequest {
+    pub path: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SignedRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let signer = parts
+            .extensions
+            .get::<UrlSigner>()
+            .cloned()
+            .ok_or_else(|| ApiError::InternalServerError("UrlSigner extension missing".to_string()))?;
+        // Nested routers see a shortened path; signatures cover the full one
+        let uri = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map_or_else(|| parts.uri.clone(), |OriginalUri(uri)| uri.clone());
+
+        let query = uri.query().map(query_pairs).unwrap_or_default();
+        let expires = query
+            .get("expires")
+            .and_then(|expires| expires.parse::<i64>().ok())
+            .ok_or(ApiError::Forbidden)?;
+        let signature = query.get("signature").ok_or(ApiError::Forbidden)?;
+
+        signer.verify(uri.path(), expires, signature)?;
+        Ok(Self {
+            path: uri.path().to_string(),
+            expires_at: DateTime::from_timestamp(expires, 0).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        })
+    }
+}
+
+/// `key=value` pairs of a query string; the values we read need no decoding
+fn query_pairs(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn mime_from_extension(stored_name: &str) -> &'static str {
+    let extension = stored_name.rsplit_once('.').map_or("", |(_, extension)| extension);
+    match extension.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serve a file to a holder of a signed link
+pub async fn signed_file_handler(
+    _signed: SignedRequest,
+    State(service): State<Arc<FileUploadService>>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let data = service.storage.read(&name).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime_from_extension(&name)),
+            (header::CACHE_CONTROL, "private, no-store"),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+/// Create signed download routes
+///
+/// Mounts:
+/// - GET {prefix}/:name - Download a file with a signed link
+pub fn signed_file_routes(service: Arc<FileUploadService>, signer: UrlSigner) -> Router {
+    Router::new()
+        .route(&format!("{}/:name", signer.prefix()), get(signed_file_handler))
+        .with_state(service)
+        .layer(Extension(signer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uploads::{LocalStorage, StorageBackend, UploadConfig};
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let clock = crate::clock::ManualClock::frozen();
+        let signer = UrlSigner::new("secret").with_clock(clock.shared());
+        let url = signer.sign("/files/report.pdf", Duration::from_secs(60));
+        let (path, query) = url.split_once('?').unwrap();
+        let query = query_pairs(query);
+        let expires: i64 = query["expires"].parse().unwrap();
+
+        assert!(signer.verify(path, expires, &query["signature"]).is_ok());
+        assert!(signer.verify("/files/other.pdf", expires, &query["signature"]).is_err());
+        assert!(signer.verify(path, expires + 3600, &query["signature"]).is_err());
+        assert!(UrlSigner::new("other").verify(path, expires, &query["signature"]).is_err());
+
+        clock.advance(Duration::from_secs(61));
+        assert!(signer.verify(path, expires, &query["signature"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signed_download() {
+        let dir = std::env::temp_dir().join(format!("rapid-rs-signed-{}", uuid::Uuid::new_v4()));
+        let signer = UrlSigner::new("secret");
+        let storage = LocalStorage::new(dir.to_string_lossy()).with_signer(signer.clone());
+        let service = Arc::new(FileUploadService::with_storage(UploadConfig::new(), StorageBackend::Local(storage)));
+        let file = service.save("notes.txt", "text/plain", b"private").await.unwrap();
+
+        let link = service.signed_url(&file.stored_name, Duration::from_secs(60)).await.unwrap();
+        let router = signed_file_routes(service, signer);
+
+        let response = router.clone().oneshot(Request::get(&link).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"private");
+
+        let unsigned = format!("/files/{}", file.stored_name);
+        let response = router.clone().oneshot(Request::get(&unsigned).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let tampered = link.replace(&file.stored_name, "other.txt");
+        let response = router.oneshot(Request::get(&tampered).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1,9 +1,11 @@
 //! File storage backends
 
 use std::path::Path;
+use std::time::Duration;
 use tokio::fs;
 use uuid::Uuid;
 use crate::error::ApiError;
+use super::signed::UrlSigner;
 use super::UploadedFile;
 
 /// Storage trait for file backends
@@ -12,6 +14,33 @@ pub trait UploadStorage: Send + Sync {
     async fn save(&self, filename: &str, content_type: &str, data: &[u8]) -> Result<UploadedFile, ApiError>;
     async fn delete(&self, stored_name: &str) -> Result<(), ApiError>;
     async fn url(&self, stored_name: &str) -> String;
+
+    /// Save under a caller-chosen name, e.g. for derived files like image variants
+    async fn save_named(&self, _stored_name: &str, _content_type: &str, _data: &[u8]) -> Result<UploadedFile, ApiError> {
+        Err(ApiError::InternalServerError("Storage backend does not support named saves".to_string()))
+    }
+
+    /// Store the file at `path` without the caller holding it in memory
+    ///
+    /// The file may be moved into storage, so don't use `path` afterwards.
+    /// Backends that can't stream fall back to reading it and calling
+    /// [`save`](Self::save).
+    async fn save_file(&self, filename: &str, content_type: &str, path: &Path) -> Result<UploadedFile, ApiError> {
+        let data = fs::read(path)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to read file: {}", e)))?;
+        self.save(filename, content_type, &data).await
+    }
+
+    /// Read a stored file back
+    async fn read(&self, _stored_name: &str) -> Result<Vec<u8>, ApiError> {
+        Err(ApiError::InternalServerError("Storage backend does not support reads".to_string()))
+    }
+
+    /// Link to a private file that works for `ttl`, e.g. a presigned object store URL
+    async fn signed_url(&self, _stored_name: &str, _ttl: Duration) -> Result<String, ApiError> {
+        Err(ApiError::InternalServerError("Storage backend does not support signed URLs".to_string()))
+    }
 }
 
 /// Storage backend enum
@@ -31,12 +60,43 @@ impl StorageBackend {
             StorageBackend::Local(s) => s.delete(stored_name).await,
         }
     }
+
+    pub async fn save_named(&self, stored_name: &str, content_type: &str, data: &[u8]) -> Result<UploadedFile, ApiError> {
+        match self {
+            StorageBackend::Local(s) => s.save_named(stored_name, content_type, data).await,
+        }
+    }
+
+    pub async fn save_file(&self, filename: &str, content_type: &str, path: &Path) -> Result<UploadedFile, ApiError> {
+        match self {
+            StorageBackend::Local(s) => s.save_file(filename, content_type, path).await,
+        }
+    }
+
+    pub async fn read(&self, stored_name: &str) -> Result<Vec<u8>, ApiError> {
+        match self {
+            StorageBackend::Local(s) => s.read(stored_name).await,
+        }
+    }
+
+    pub async fn url(&self, stored_name: &str) -> String {
+        match self {
+            StorageBackend::Local(s) => s.url(stored_name).await,
+        }
+    }
+
+    pub async fn signed_url(&self, stored_name: &str, ttl: Duration) -> Result<String, ApiError> {
+        match self {
+            StorageBackend::Local(s) => s.signed_url(stored_name, ttl).await,
+        }
+    }
 }
 
 /// Local filesystem storage
 pub struct LocalStorage {
     base_dir: String,
     base_url: String,
+    signer: Option<UrlSigner>,
 }
 
 impl LocalStorage {
@@ -45,6 +105,7 @@ impl LocalStorage {
         Self {
             base_url: "/uploads".to_string(),
             base_dir,
+            signer: None,
         }
     }
 
@@ -53,6 +114,20 @@ impl LocalStorage {
         self
     }
 
+    /// Sign links to files served by `signed_file_routes`
+    pub fn with_signer(mut self, signer: UrlSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Path of a stored file; names can't point outside the base directory
+    fn path(&self, stored_name: &str) -> Result<std::path::PathBuf, ApiError> {
+        if stored_name.is_empty() || stored_name.contains(['/', '\\']) || stored_name.starts_with('.') {
+            return Err(ApiError::BadRequest(format!("Invalid file name '{}'", stored_name)));
+        }
+        Ok(Path::new(&self.base_dir).join(stored_name))
+    }
+
     fn extension_from_mime(content_type: &str) -> &str {
         match content_type {
             "image/jpeg" => ".jpg",
@@ -73,14 +148,21 @@ impl LocalStorage {
 #[async_trait::async_trait]
 impl UploadStorage for LocalStorage {
     async fn save(&self, filename: &str, content_type: &str, data: &[u8]) -> Result<UploadedFile, ApiError> {
-        // Create upload dir if it doesn't exist
-        fs::create_dir_all(&self.base_dir).await
-            .map_err(|e| ApiError::InternalServerError(format!("Failed to create upload dir: {}", e)))?;
-
         // Generate unique stored filename
         let ext = Self::extension_from_mime(content_type);
         let stored_name = format!("{}{}", Uuid::new_v4(), ext);
-        let file_path = Path::new(&self.base_dir).join(&stored_name);
+
+        let mut file = self.save_named(&stored_name, content_type, data).await?;
+        file.original_name = filename.to_string();
+        Ok(file)
+    }
+
+    async fn save_named(&self, stored_name: &str, content_type: &str, data: &[u8]) -> Result<UploadedFile, ApiError> {
+        let file_path = self.path(stored_name)?;
+
+        // Create upload dir if it doesn't exist
+        fs::create_dir_all(&self.base_dir).await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to create upload dir: {}", e)))?;
 
         // Write file
         fs::write(&file_path, data).await
@@ -89,14 +171,47 @@ impl UploadStorage for LocalStorage {
         let url = format!("{}/{}", self.base_url, stored_name);
 
         Ok(UploadedFile::new(
-            filename.to_string(),
-            stored_name,
+            stored_name.to_string(),
+            stored_name.to_string(),
             content_type.to_string(),
             data.len(),
             url,
         ))
     }
 
+    async fn save_file(&self, filename: &str, content_type: &str, path: &Path) -> Result<UploadedFile, ApiError> {
+        let stored_name = format!("{}{}", Uuid::new_v4(), Self::extension_from_mime(content_type));
+        let file_path = self.path(&stored_name)?;
+        let write_error = |e: std::io::Error| ApiError::InternalServerError(format!("Failed to write file: {}", e));
+
+        fs::create_dir_all(&self.base_dir).await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to create upload dir: {}", e)))?;
+
+        // Renaming fails across filesystems; copy then
+        if fs::rename(path, &file_path).await.is_err() {
+            fs::copy(path, &file_path).await.map_err(write_error)?;
+        }
+        let size = fs::metadata(&file_path).await.map_err(write_error)?.len() as usize;
+
+        Ok(UploadedFile::new(
+            filename.to_string(),
+            stored_name.clone(),
+            content_type.to_string(),
+            size,
+            format!("{}/{}", self.base_url, stored_name),
+        ))
+    }
+
+    async fn read(&self, stored_name: &str) -> Result<Vec<u8>, ApiError> {
+        match fs::read(self.path(stored_name)?).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(ApiError::NotFound(format!("File '{}' not found", stored_name)))
+            }
+            Err(e) => Err(ApiError::InternalServerError(format!("Failed to read file: {}", e))),
+        }
+    }
+
     async fn delete(&self, stored_name: &str) -> Result<(), ApiError> {
         let file_path = Path::new(&self.base_dir).join(stored_name);
         if file_path.exists() {
@@ -109,4 +224,13 @@ impl UploadStorage for LocalStorage {
     async fn url(&self, stored_name: &str) -> String {
         format!("{}/{}", self.base_url, stored_name)
     }
+
+    async fn signed_url(&self, stored_name: &str, ttl: Duration) -> Result<String, ApiError> {
+        self.path(stored_name)?;
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or_else(|| ApiError::InternalServerError("No URL signer configured for local storage".to_string()))?;
+        Ok(signer.file_url(stored_name, ttl))
+    }
 }
@@ -0,0 +1,402 @@
+//! Nested and cross-field validation helpers
+//!
+//! [`ValidatedJson`](crate::ValidatedJson) reports errors from nested structs
+//! and collections (`#[validate(nested)]`) with their full path, e.g.
+//! `items[2].price`. Schema-level validators can point their errors at the
+//! fields involved with [`schema_error`]:
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize, Validate)]
+//! #[validate(schema(function = "check_dates"))]
+//! struct Booking {
+//!     starts_on: NaiveDate,
+//!     ends_on: NaiveDate,
+//! }
+//!
+//! fn check_dates(booking: &Booking) -> Result<(), ValidationError> {
+//!     if booking.ends_on < booking.starts_on {
+//!         return Err(schema_error("date_order", &["ends_on"], "Must not be before starts_on"));
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Rules that need I/O, such as checking an email is not taken, are async
+//! validators registered by name in a [`ValidatorRegistry`] and referenced
+//! with `#[validate_async(...)]`. They run after the synchronous rules pass:
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize, Validate, AsyncValidate)]
+//! struct Signup {
+//!     #[validate(email)]
+//!     #[validate_async(unique_email)]
+//!     email: String,
+//! }
+//!
+//! let validators = ValidatorRegistry::new()
+//!     .with_validator("unique_email", |email: Value, deps: Dependencies| async move {
+//!         let store = deps.get::<dyn UserStore>().expect("UserStore registered");
+//!         match store.find_by_email(email.as_str().unwrap_or_default()).await {
+//!             Ok(None) => Ok(()),
+//!             _ => Err(ValidationError::new("unique_email").with_message("Email is already registered".into())),
+//!         }
+//!     })
+//!     .with_rules_for::<Signup>();
+//!
+//! App::new().with_validators(validators)
+//! ```
+
+use serde_json::Value;
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::dependencies::Dependencies;
+
+pub use rapid_rs_macros::AsyncValidate;
+
+/// Key under which validator stores schema-level errors
+const SCHEMA_FIELD: &str = "__all__";
+
+/// Param listing the fields a schema-level error applies to
+const FIELDS_PARAM: &str = "fields";
+
+/// Error for a schema-level validator, reported against `fields`
+///
+/// With no fields the error is reported against the struct itself.
+pub fn schema_error(
+    code: &'static str,
+    fields: &[&str],
+    message: impl Into<Cow<'static, str>>,
+) -> ValidationError {
+    let mut error = ValidationError::new(code).with_message(message.into());
+    if !fields.is_empty() {
+        error.add_param(Cow::Borrowed(FIELDS_PARAM), &fields);
+    }
+    error
+}
+
+/// Every error in `errors` with the path of the field it belongs to
+///
+/// Paths use dots for struct fields and brackets for list indices
+/// (`items[2].price`); errors on the top-level struct have an empty path.
+/// Sorted by path so responses are stable.
+pub fn flatten_errors(errors: &ValidationErrors) -> Vec<(String, &ValidationError)> {
+    let mut flat = Vec::new();
+    collect(errors, "", &mut flat);
+    flat.sort_by(|a, b| a.0.cmp(&b.0));
+    flat
+}
+
+fn collect<'a>(errors: &'a ValidationErrors, prefix: &str, flat: &mut Vec<(String, &'a ValidationError)>) {
+    for (field, kind) in errors.errors() {
+        match kind {
+            ValidationErrorsKind::Field(field_errors) if *field == SCHEMA_FIELD => {
+                for error in field_errors {
+                    match schema_fields(error) {
+                        Some(fields) => {
+                            flat.extend(fields.into_iter().map(|field| (join(prefix, &field), error)))
+                        }
+                        None => flat.push((prefix.to_string(), error)),
+                    }
+                }
+            }
+            ValidationErrorsKind::Field(field_errors) => {
+                let path = join(prefix, field);
+                flat.extend(field_errors.iter().map(|error| (path.clone(), error)));
+            }
+            ValidationErrorsKind::Struct(nested) => collect(nested, &join(prefix, field), flat),
+            ValidationErrorsKind::List(items) => {
+                let path = join(prefix, field);
+                for (index, nested) in items {
+                    collect(nested, &format!("{}[{}]", path, index), flat);
+                }
+            }
+        }
+    }
+}
+
+fn schema_fields(error: &ValidationError) -> Option<Vec<String>> {
+    let fields = error.params.get(FIELDS_PARAM)?.as_array()?;
+    let fields: Vec<String> = fields
+        .iter()
+        .filter_map(|field| field.as_str().map(str::to_string))
+        .collect();
+    (!fields.is_empty()).then_some(fields)
+}
+
+fn join(prefix: &str, field: &str) -> String {
+    if prefix.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", prefix, field)
+    }
+}
+
+/// Types with fields checked by async validators
+///
+/// Usually derived with `#[validate_async(name, ...)]` on fields; add
+/// `field = "jsonName"` when the JSON name differs from the field name.
+pub trait AsyncValidate {
+    fn async_rules() -> Vec<AsyncRule>;
+}
+
+/// Run the validator `validator` on the value at `field`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsyncRule {
+    /// Dotted path into the JSON body, e.g. `address.country`
+    pub field: String,
+    pub validator: String,
+}
+
+impl AsyncRule {
+    pub fn new(field: impl Into<String>, validator: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            validator: validator.into(),
+        }
+    }
+}
+
+type ValidatorFuture = Pin<Box<dyn Future<Output = Result<(), ValidationError>> + Send>>;
+type BoxedValidator = Arc<dyn Fn(Value, Dependencies) -> ValidatorFuture + Send + Sync>;
+
+/// Named async validators and the payload types that use them
+///
+/// Register with `App::with_validators`. Validators receive the field's
+/// JSON value (`Null` when absent) and the app's dependencies.
+///
+/// Deriving [`AsyncValidate`] is not enough on its own: each payload type
+/// must be added with [`with_rules_for`](Self::with_rules_for).
+/// `App::with_validators` panics if a validator is used by no registered
+/// type, or a registered type names a validator that doesn't exist.
+#[derive(Clone, Default)]
+pub struct ValidatorRegistry {
+    validators: HashMap<String, BoxedValidator>,
+    rules: HashMap<TypeId, Vec<AsyncRule>>,
+}
+
+impl ValidatorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_validator<F, Fut>(mut self, name: impl Into<String>, validator: F) -> Self
+    where
+        F: Fn(Value, Dependencies) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), ValidationError>> + Send + 'static,
+    {
+        self.validators
+            .insert(name.into(), Arc::new(move |value, deps| Box::pin(validator(value, deps))));
+        self
+    }
+
+    /// Check `T` payloads in [`ValidatedJson`](crate::ValidatedJson) against its rules
+    pub fn with_rules_for<T: AsyncValidate + 'static>(self) -> Self {
+        self.with_rules(TypeId::of::<T>(), T::async_rules())
+    }
+
+    /// Rules for `type_id`, for types without the derive
+    pub fn with_rules(mut self, type_id: TypeId, rules: Vec<AsyncRule>) -> Self {
+        self.rules.entry(type_id).or_default().extend(rules);
+        self
+    }
+
+    /// Fail if a validator is never used or a rule names a missing one
+    ///
+    /// An unused validator almost always means a payload type derives
+    /// [`AsyncValidate`] but was never added with `with_rules_for`, so its
+    /// rules would silently not run.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        let rules: Vec<&AsyncRule> = self.rules.values().flatten().collect();
+        if let Some(rule) = rules.iter().find(|rule| !self.validators.contains_key(&rule.validator)) {
+            return Err(format!(
+                "Async validator '{}' used by field '{}' is not registered",
+                rule.validator, rule.field
+            ));
+        }
+        let mut unused: Vec<&str> = self
+            .validators
+            .keys()
+            .filter(|name| !rules.iter().any(|rule| &rule.validator == *name))
+            .map(String::as_str)
+            .collect();
+        if !unused.is_empty() {
+            unused.sort_unstable();
+            return Err(format!(
+                "Async validators {:?} are not used by any payload type; add the types that derive \
+                 AsyncValidate with ValidatorRegistry::with_rules_for",
+                unused
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn has_rules(&self, type_id: TypeId) -> bool {
+        self.rules.get(&type_id).is_some_and(|rules| !rules.is_empty())
+    }
+
+    /// Run the rules for `type_id` against `body` in declaration order
+    ///
+    /// Returns the failures by field path, or `Err` naming a validator that
+    /// was referenced but never registered.
+    pub(crate) async fn run(
+        &self,
+        type_id: TypeId,
+        body: &Value,
+        deps: &Dependencies,
+    ) -> Result<Vec<(String, ValidationError)>, String> {
+        let mut failures = Vec::new();
+        for rule in self.rules.get(&type_id).into_iter().flatten() {
+            let validator = self
+                .validators
+                .get(&rule.validator)
+                .ok_or_else(|| rule.validator.clone())?;
+            let pointer = format!("/{}", rule.field.replace('.', "/"));
+            let value = body.pointer(&pointer).cloned().unwrap_or(Value::Null);
+            if let Err(error) = validator(value, deps.clone()).await {
+                failures.push((rule.field.clone(), error));
+            }
+        }
+        Ok(failures)
+    }
+}
+
+/// `path` without list indices, e.g. `items.price` for `items[2].price`
+pub(crate) fn without_indices(path: &str) -> String {
+    let mut key = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            c if !in_index => key.push(c),
+            _ => {}
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    #[validate(schema(function = "check_totals"))]
+    struct Order {
+        #[validate(length(min = 1))]
+        reference: String,
+        #[validate(nested)]
+        items: Vec<Item>,
+        total: f64,
+    }
+
+    #[derive(Validate)]
+    struct Item {
+        #[validate(range(min = 0.0))]
+        price: f64,
+    }
+
+    fn check_totals(order: &Order) -> Result<(), ValidationError> {
+        let sum: f64 = order.items.iter().map(|item| item.price).sum();
+        if (sum - order.total).abs() > f64::EPSILON {
+            return Err(schema_error("total_mismatch", &["total"], "Total must equal the item prices"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let order = Order {
+            reference: String::new(),
+            items: vec![Item { price: 1.0 }, Item { price: 2.0 }, Item { price: -1.0 }],
+            total: 2.0,
+        };
+        let errors = order.validate().unwrap_err();
+        let paths: Vec<String> = flatten_errors(&errors).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, ["items[2].price", "reference"]);
+        assert_eq!(without_indices("items[2].price"), "items.price");
+    }
+
+    #[derive(AsyncValidate)]
+    #[allow(dead_code)]
+    struct Signup {
+        #[validate_async(unique_email)]
+        email: String,
+        #[validate_async(country_code, field = "address.country")]
+        country: String,
+    }
+
+    #[tokio::test]
+    async fn test_async_rules() {
+        assert_eq!(
+            Signup::async_rules(),
+            [
+                AsyncRule::new("email", "unique_email"),
+                AsyncRule::new("address.country", "country_code")
+            ]
+        );
+
+        let registry = ValidatorRegistry::new()
+            .with_validator("unique_email", |email: Value, deps: Dependencies| async move {
+                let taken = deps.get::<Vec<String>>().unwrap();
+                match taken.iter().any(|t| email == t.as_str()) {
+                    true => Err(ValidationError::new("unique_email")),
+                    false => Ok(()),
+                }
+            })
+            .with_rules_for::<Signup>();
+
+        let mut deps = Dependencies::new();
+        deps.insert(vec!["taken@example.com".to_string()]);
+        let body = serde_json::json!({"email": "taken@example.com", "address": {"country": "NZ"}});
+
+        let err = registry.run(TypeId::of::<Signup>(), &body, &deps).await.unwrap_err();
+        assert_eq!(err, "country_code");
+
+        let registry = registry.with_validator("country_code", |code: Value, _| async move {
+            match code.as_str().is_some_and(|code| code.len() == 2) {
+                true => Ok(()),
+                false => Err(ValidationError::new("country_code")),
+            }
+        });
+        let failures = registry.run(TypeId::of::<Signup>(), &body, &deps).await.unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "email");
+    }
+
+    #[test]
+    fn test_registry_check() {
+        let validator = |_: Value, _: Dependencies| async { Ok(()) };
+        let registry = ValidatorRegistry::new()
+            .with_validator("unique_email", validator)
+            .with_validator("country_code", validator);
+        // Deriving AsyncValidate without with_rules_for is caught
+        assert!(registry.check().unwrap_err().contains("with_rules_for"));
+        assert!(registry.clone().with_rules_for::<Signup>().check().is_ok());
+
+        let missing = ValidatorRegistry::new()
+            .with_validator("unique_email", validator)
+            .with_rules_for::<Signup>();
+        assert!(missing.check().unwrap_err().contains("'country_code'"));
+    }
+
+    #[test]
+    fn test_schema_errors_target_fields() {
+        let order = Order {
+            reference: "A-1".to_string(),
+            items: vec![Item { price: 1.0 }],
+            total: 5.0,
+        };
+        let errors = order.validate().unwrap_err();
+        let flat = flatten_errors(&errors);
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat[0].0, "total");
+        assert_eq!(flat[0].1.code, "total_mismatch");
+    }
+}
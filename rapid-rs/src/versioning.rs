@@ -0,0 +1,288 @@
+//! Serving several versions of an API side by side
+//!
+//! Mount one router per version with [`App::mount_versioned`](crate::App::mount_versioned)
+//! and pick how clients choose between them with [`Versioning`]:
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .mount_versioned(1, routes_v1())
+//!     .mount_versioned(2, routes_v2())
+//!     .with_versioning(
+//!         Versioning::url_prefix()
+//!             .deprecate(1, Deprecation::new().sunset(NaiveDate::from_ymd_opt(2025, 12, 31).unwrap())),
+//!     )
+//! ```
+//!
+//! - [`Versioning::url_prefix`] (the default) serves version 1 under `/v1`
+//! - [`Versioning::header`] reads the version from a header (`Api-Version: 2`)
+//! - [`Versioning::media_type`] reads it from `Accept: application/vnd.<vendor>.v2+json`
+//!
+//! With the header and media type strategies, requests that don't ask for a
+//! version get the default one (the latest, unless set), and unknown versions
+//! fail with `400 UNSUPPORTED_API_VERSION`. Responses carry an `Api-Version`
+//! header, and those of deprecated versions the headers described in
+//! [`deprecation`](crate::deprecation). Handlers can ask for [`ApiVersion`].
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use crate::deprecation::Deprecation;
+use crate::error::{ApiError, WithDetails};
+
+/// Header the version is reported in, and read from by default
+pub const API_VERSION_HEADER: &str = "api-version";
+
+/// How clients ask for a version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionStrategy {
+    /// `/v1/users`
+    UrlPrefix,
+    /// A header holding `2` or `v2`
+    Header(HeaderName),
+    /// `Accept: application/vnd.<vendor>.v2+json`
+    MediaType(String),
+}
+
+/// How versions are selected and which are deprecated
+#[derive(Debug, Clone)]
+pub struct Versioning {
+    strategy: VersionStrategy,
+    default_version: Option<u32>,
+    deprecations: HashMap<u32, Deprecation>,
+}
+
+impl Default for Versioning {
+    fn default() -> Self {
+        Self::url_prefix()
+    }
+}
+
+impl Versioning {
+    pub fn url_prefix() -> Self {
+        Self::new(VersionStrategy::UrlPrefix)
+    }
+
+    /// Read the version from `name`, e.g. `"api-version"`
+    pub fn header(name: &'static str) -> Self {
+        Self::new(VersionStrategy::Header(HeaderName::from_static(name)))
+    }
+
+    /// Read the version from `Accept: application/vnd.<vendor>.v<n>+json`
+    pub fn media_type(vendor: impl Into<String>) -> Self {
+        Self::new(VersionStrategy::MediaType(vendor.into()))
+    }
+
+    fn new(strategy: VersionStrategy) -> Self {
+        Self {
+            strategy,
+            default_version: None,
+            deprecations: HashMap::new(),
+        }
+    }
+
+    /// Version served when a request doesn't ask for one (default: the latest)
+    pub fn default_version(mut self, version: u32) -> Self {
+        self.default_version = Some(version);
+        self
+    }
+
+    /// Mark every endpoint of `version` deprecated
+    pub fn deprecate(mut self, version: u32, deprecation: Deprecation) -> Self {
+        self.deprecations.insert(version, deprecation);
+        self
+    }
+
+    pub fn strategy(&self) -> &VersionStrategy {
+        &self.strategy
+    }
+
+    /// The version a request with `headers` asks for, if it names one
+    fn requested(&self, headers: &axum::http::HeaderMap) -> Result<Option<u32>, ()> {
+        match &self.strategy {
+            VersionStrategy::UrlPrefix => Ok(None),
+            VersionStrategy::Header(name) => match headers.get(name) {
+                Some(value) => {
+                    let value = value.to_str().map_err(|_| ())?.trim();
+                    value.trim_start_matches(['v', 'V']).parse().map(Some).map_err(|_| ())
+                }
+                None => Ok(None),
+            },
+            VersionStrategy::MediaType(vendor) => {
+                let prefix = format!("application/vnd.{}.v", vendor);
+                let accept = headers.get_all(header::ACCEPT).iter().filter_map(|value| value.to_str().ok());
+                for media_type in accept.flat_map(|value| value.split(',')) {
+                    let essence = media_type.split(';').next().unwrap_or_default().trim();
+                    if let Some(rest) = essence.strip_prefix(&prefix) {
+                        let digits = rest.split('+').next().unwrap_or_default();
+                        return digits.parse().map(Some).map_err(|_| ());
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The API version a request is served by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiVersion(pub u32);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiVersion
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .ok_or_else(|| ApiError::InternalServerError("Route is not mounted with App::mount_versioned".to_string()))
+    }
+}
+
+/// `router` with the version's extension, response header and deprecation
+fn prepare(version: u32, router: Router, versioning: &Versioning) -> Router {
+    let router = match versioning.deprecations.get(&version) {
+        Some(deprecation) => {
+            let deprecation = Arc::new(deprecation.clone());
+            let label: Arc<str> = format!("v{}", version).into();
+            router.layer(axum::middleware::from_fn(move |request, next| {
+                crate::deprecation::mark_deprecated(deprecation.clone(), label.clone(), request, next)
+            }))
+        }
+        None => router,
+    };
+    router
+        .layer(axum::middleware::map_response(move |mut response: Response| async move {
+            response.headers_mut().insert(API_VERSION_HEADER, HeaderValue::from(version));
+            response
+        }))
+        .layer(axum::Extension(ApiVersion(version)))
+}
+
+fn unsupported(supported: &[u32]) -> Response {
+    ApiError::custom(StatusCode::BAD_REQUEST, "UNSUPPORTED_API_VERSION", "Unsupported API version")
+        .with_details(serde_json::json!({ "supported": supported }))
+        .into_response()
+}
+
+/// Add the versioned routers to `router`
+pub(crate) fn install(router: Router, versioning: Versioning, versions: BTreeMap<u32, Router>) -> Router {
+    if versions.is_empty() {
+        return router;
+    }
+
+    if versioning.strategy == VersionStrategy::UrlPrefix {
+        return versions.into_iter().fold(router, |router, (version, versioned)| {
+            router.nest(&format!("/v{}", version), prepare(version, versioned, &versioning))
+        });
+    }
+
+    let supported: Vec<u32> = versions.keys().copied().collect();
+    let default = versioning.default_version.or(supported.last().copied());
+    let versions: Arc<BTreeMap<u32, Router>> = Arc::new(
+        versions
+            .into_iter()
+            .map(|(version, versioned)| (version, prepare(version, versioned, &versioning)))
+            .collect(),
+    );
+    let versioning = Arc::new(versioning);
+
+    // Unversioned routes match first; everything else is dispatched by version
+    router.fallback_service(tower::service_fn(move |request: Request| {
+        let versions = versions.clone();
+        let versioning = versioning.clone();
+        let supported = supported.clone();
+        async move {
+            let version = match versioning.requested(request.headers()) {
+                Ok(requested) => requested.or(default),
+                Err(()) => None,
+            };
+            match version.and_then(|version| versions.get(&version)) {
+                Some(versioned) => versioned.clone().oneshot(request).await,
+                None => Ok(unsupported(&supported)),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use axum::{body::Body, routing::get};
+
+    fn app(versioning: Versioning) -> Router {
+        let v1 = Router::new().route("/users", get(|ApiVersion(version): ApiVersion| async move { format!("v{} users", version) }));
+        let v2 = Router::new().route("/users", get(|| async { "v2 users" }));
+        App::new()
+            .route("/status", get(|| async { "ok" }))
+            .mount_versioned(1, v1)
+            .mount_versioned(2, v2)
+            .with_versioning(versioning.deprecate(1, Deprecation::new().with_successor("/v2/users")))
+            .into_router()
+    }
+
+    async fn call(router: &Router, request: axum::http::request::Builder) -> (StatusCode, String, Response) {
+        let response = router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap(), Response::from_parts(parts, Body::empty()))
+    }
+
+    #[tokio::test]
+    async fn test_url_prefix_versions() {
+        let router = app(Versioning::url_prefix());
+
+        let (status, body, response) = call(&router, Request::get("/v1/users")).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "v1 users"));
+        assert_eq!(response.headers()[API_VERSION_HEADER], "1");
+        assert_eq!(response.headers()["deprecation"], "true");
+
+        let (status, body, response) = call(&router, Request::get("/v2/users")).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "v2 users"));
+        assert!(response.headers().get("deprecation").is_none());
+
+        assert_eq!(call(&router, Request::get("/users")).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_header_versions() {
+        let router = app(Versioning::header(API_VERSION_HEADER));
+
+        let (_, body, response) = call(&router, Request::get("/users").header(API_VERSION_HEADER, "v1")).await;
+        assert_eq!(body, "v1 users");
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(call(&router, Request::get("/users")).await.1, "v2 users");
+        assert_eq!(call(&router, Request::get("/status")).await.1, "ok");
+
+        let (status, body, _) = call(&router, Request::get("/users").header(API_VERSION_HEADER, "7")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["code"], "UNSUPPORTED_API_VERSION");
+        assert_eq!(body["details"]["supported"], serde_json::json!([1, 2]));
+    }
+
+    #[tokio::test]
+    async fn test_media_type_versions() {
+        let router = app(Versioning::media_type("acme").default_version(1));
+
+        let accept = "text/html, application/vnd.acme.v2+json; q=0.9";
+        assert_eq!(call(&router, Request::get("/users").header(header::ACCEPT, accept)).await.1, "v2 users");
+        assert_eq!(call(&router, Request::get("/users")).await.1, "v1 users");
+        let (status, ..) = call(&router, Request::get("/missing").header(header::ACCEPT, "application/vnd.acme.v2+json")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,139 @@
+//! Long polling for clients that can't hold a WebSocket or SSE stream
+//!
+//! A poll waits on a [`Realtime`] topic and answers with the next event
+//! published to it, or `204 No Content` once the wait times out:
+//!
+//! ```rust,ignore
+//! let realtime = Realtime::new();
+//! App::new().mount(LongPoll::new(realtime.clone()).routes());
+//!
+//! // GET /poll/orders?timeout=20 returns as soon as this is published
+//! realtime.publish("orders", json!({"id": 42})).await?;
+//! ```
+//!
+//! Events published between two polls aren't seen by either, so clients
+//! should re-poll straight away and refetch state after reconnecting.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::time::Duration;
+
+use super::{realtime::Realtime, room::RoomKey, server::Connecting, RealtimeEvent};
+
+/// Waits for events on [`Realtime`] topics
+#[derive(Clone)]
+pub struct LongPoll {
+    realtime: Realtime,
+    timeout: Duration,
+    max_timeout: Duration,
+}
+
+impl LongPoll {
+    /// Polls wait 30 seconds by default, and at most 60
+    pub fn new(realtime: Realtime) -> Self {
+        Self {
+            realtime,
+            timeout: Duration::from_secs(30),
+            max_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// How long a poll waits when the client doesn't say
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Longest wait a client may ask for with `?timeout=`
+    pub fn with_max_timeout(mut self, max_timeout: Duration) -> Self {
+        self.max_timeout = max_timeout;
+        self
+    }
+
+    /// The next event published to `key` within `timeout`
+    pub async fn wait(&self, key: impl Into<RoomKey>, timeout: Duration) -> Option<RealtimeEvent> {
+        let mut events = Box::pin(self.realtime.subscribe(key).await);
+        tokio::time::timeout(timeout, events.next()).await.ok().flatten()
+    }
+
+    /// Poll route (`GET /poll/:topic?timeout=<seconds>`)
+    ///
+    /// With multi-tenancy, clients only see their tenant's events. Polls are
+    /// checked with the [`Realtime`] authorizer, if one is set.
+    pub fn routes(&self) -> Router {
+        Router::new()
+            .route("/poll/:topic", get(poll_handler))
+            .with_state(self.clone())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PollQuery {
+    /// Seconds to wait
+    timeout: Option<u64>,
+}
+
+async fn poll_handler(
+    State(poll): State<LongPoll>,
+    Path(topic): Path<String>,
+    Query(query): Query<PollQuery>,
+    Connecting(conn): Connecting,
+) -> Response {
+    if let Err(rejection) = poll.realtime.authorize(&conn, &topic).await {
+        return rejection.into_response();
+    }
+    let key = RoomKey::for_connection(&conn, topic);
+
+    let timeout = query
+        .timeout
+        .map(Duration::from_secs)
+        .unwrap_or(poll.timeout)
+        .min(poll.max_timeout);
+    match poll.wait(key, timeout).await {
+        Some(event) => Json(event).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_poll_returns_event_or_times_out() {
+        let realtime = Realtime::new();
+        let router = LongPoll::new(realtime.clone())
+            .with_max_timeout(Duration::from_secs(5))
+            .routes();
+
+        let poll = tokio::spawn(
+            router
+                .clone()
+                .oneshot(Request::get("/poll/orders?timeout=30").body(Body::empty()).unwrap()),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        realtime.publish("orders", serde_json::json!({"id": 42})).await.unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(1), poll).await.unwrap().unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let event: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(event["topic"], "orders");
+        assert_eq!(event["payload"]["id"], 42);
+
+        let response = router
+            .oneshot(Request::get("/poll/orders?timeout=0").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -138,7 +138,7 @@ impl Message {
 }
 
 /// Broadcast options
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct BroadcastOptions {
     /// Exclude these connections from broadcast
     pub exclude: Vec<ConnectionId>,
@@ -148,16 +148,9 @@ pub struct BroadcastOptions {
     
     /// Room to broadcast to
     pub room: Option<String>,
-}
-
-impl Default for BroadcastOptions {
-    fn default() -> Self {
-        Self {
-            exclude: Vec::new(),
-            only: None,
-            room: None,
-        }
-    }
+    
+    /// Restrict the broadcast to one tenant; also scopes `room` to that tenant
+    pub tenant: Option<String>,
 }
 
 impl BroadcastOptions {
@@ -179,6 +172,11 @@ impl BroadcastOptions {
         self.room = Some(room.into());
         self
     }
+    
+    pub fn for_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
 }
 
 #[cfg(test)]
@@ -4,11 +4,21 @@ pub mod server;
 pub mod handler;
 pub mod room;
 pub mod message;
+pub mod store;
+pub mod queue;
+pub mod session;
+pub mod realtime;
+pub mod long_poll;
 
 pub use server::{WebSocketServer, WebSocketConfig};
 pub use handler::{WebSocketHandler, ConnectionId};
-pub use room::{RoomManager, Room};
+pub use room::{JoinRejection, Room, RoomAuthorizer, RoomKey, RoomManager};
 pub use message::{Message, MessageType, BroadcastOptions};
+pub use store::{connection_routes, ConnectionStore};
+pub use queue::{OverflowPolicy, SendOutcome, SendQueue};
+pub use session::{ResumeConfig, SessionStore};
+pub use realtime::{LocalPubSub, PubSubBackend, Realtime, RealtimeEvent};
+pub use long_poll::LongPoll;
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -18,6 +28,8 @@ use uuid::Uuid;
 pub struct ConnectionInfo {
     pub id: Uuid,
     pub user_id: Option<String>,
+    #[serde(default)]
+    pub tenant_id: Option<String>,
     pub connected_at: chrono::DateTime<chrono::Utc>,
     pub remote_addr: Option<String>,
     pub metadata: std::collections::HashMap<String, String>,
@@ -28,6 +40,7 @@ impl ConnectionInfo {
         Self {
             id,
             user_id: None,
+            tenant_id: None,
             connected_at: chrono::Utc::now(),
             remote_addr: None,
             metadata: std::collections::HashMap::new(),
@@ -0,0 +1,209 @@
+//! Bounded outbound queues for WebSocket connections
+//!
+//! Every connection gets its own queue, so a slow client only fills its own
+//! buffer instead of growing memory for everyone during broadcasts.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+use super::Message;
+
+/// What to do when a connection's send queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued message to make room
+    #[default]
+    DropOldest,
+    /// Discard the message being sent
+    DropNewest,
+    /// Close the connection
+    Disconnect,
+}
+
+impl OverflowPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::DropOldest => "drop_oldest",
+            OverflowPolicy::DropNewest => "drop_newest",
+            OverflowPolicy::Disconnect => "disconnect",
+        }
+    }
+}
+
+/// Outcome of queueing a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendOutcome {
+    Queued,
+    /// Queue was full; a message was dropped according to the policy
+    Dropped,
+    /// Queue was full and the connection is being closed
+    Disconnected,
+    /// Connection is already closed
+    Closed,
+}
+
+/// Bounded per-connection queue of outbound messages
+#[derive(Debug)]
+pub struct SendQueue {
+    messages: Mutex<VecDeque<Message>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    notify: Notify,
+    closed: AtomicBool,
+}
+
+impl SendQueue {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            policy,
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Queue a message without waiting
+    pub fn push(&self, message: Message) -> SendOutcome {
+        if self.is_closed() {
+            return SendOutcome::Closed;
+        }
+
+        let (outcome, depth) = {
+            let mut messages = self.messages.lock().unwrap();
+
+            let outcome = if messages.len() < self.capacity {
+                messages.push_back(message);
+                SendOutcome::Queued
+            } else {
+                match self.policy {
+                    OverflowPolicy::DropOldest => {
+                        messages.pop_front();
+                        messages.push_back(message);
+                        SendOutcome::Dropped
+                    }
+                    OverflowPolicy::DropNewest => SendOutcome::Dropped,
+                    OverflowPolicy::Disconnect => {
+                        self.closed.store(true, Ordering::Release);
+                        SendOutcome::Disconnected
+                    }
+                }
+            };
+
+            (outcome, messages.len())
+        };
+
+        self.record_metrics(outcome, depth);
+        self.notify.notify_one();
+        outcome
+    }
+
+    /// Wait for the next message; `None` once the queue is closed
+    pub async fn pop(&self) -> Option<Message> {
+        loop {
+            if self.is_closed() {
+                return None;
+            }
+            if let Some(message) = self.messages.lock().unwrap().pop_front() {
+                return Some(message);
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Take all messages still waiting to be sent
+    pub fn drain(&self) -> Vec<Message> {
+        self.messages.lock().unwrap().drain(..).collect()
+    }
+
+    /// Number of messages waiting to be sent
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Stop accepting messages and wake the writer
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    fn record_metrics(&self, outcome: SendOutcome, depth: usize) {
+        if outcome != SendOutcome::Queued {
+            tracing::warn!(
+                policy = self.policy.as_str(),
+                capacity = self.capacity,
+                "WebSocket send queue full"
+            );
+        }
+
+        #[cfg(feature = "observability")]
+        {
+            crate::metrics::record_histogram("websocket_send_queue_depth", depth as f64, &[]);
+            if outcome != SendOutcome::Queued {
+                crate::metrics::record_counter(
+                    "websocket_send_queue_overflow_total",
+                    1,
+                    &[("policy", self.policy.as_str().to_string())],
+                );
+            }
+        }
+
+        #[cfg(not(feature = "observability"))]
+        let _ = depth;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(message: &Message) -> String {
+        match &message.message_type {
+            super::super::MessageType::Text { content } => content.clone(),
+            _ => String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let queue = SendQueue::new(2, OverflowPolicy::DropOldest);
+        assert_eq!(queue.push(Message::text("a")), SendOutcome::Queued);
+        assert_eq!(queue.push(Message::text("b")), SendOutcome::Queued);
+        assert_eq!(queue.push(Message::text("c")), SendOutcome::Dropped);
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(content(&queue.pop().await.unwrap()), "b");
+        assert_eq!(content(&queue.pop().await.unwrap()), "c");
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest() {
+        let queue = SendQueue::new(1, OverflowPolicy::DropNewest);
+        queue.push(Message::text("a"));
+        assert_eq!(queue.push(Message::text("b")), SendOutcome::Dropped);
+        assert_eq!(content(&queue.pop().await.unwrap()), "a");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_on_overflow() {
+        let queue = SendQueue::new(1, OverflowPolicy::Disconnect);
+        queue.push(Message::text("a"));
+        assert_eq!(queue.push(Message::text("b")), SendOutcome::Disconnected);
+        assert!(queue.pop().await.is_none());
+        assert_eq!(queue.push(Message::text("c")), SendOutcome::Closed);
+    }
+}
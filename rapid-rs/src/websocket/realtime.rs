@@ -0,0 +1,453 @@
+//! Unified realtime publishing over WebSockets and Server-Sent Events
+//!
+//! [`Realtime::publish`] fans an event out to every local subscriber of a
+//! topic, whatever transport it uses, and optionally to a [`PubSubBackend`]
+//! so other instances deliver it to their own clients. [`LongPoll`](super::LongPoll)
+//! serves the same topics to clients that can only poll.
+//!
+//! ```rust,ignore
+//! let server = WebSocketServer::new();
+//! let realtime = Realtime::new().with_websocket(server.clone());
+//!
+//! App::new()
+//!     .mount(server.routes())
+//!     .mount(realtime.sse_routes())
+//!     .run()
+//!     .await?;
+//!
+//! realtime.publish("orders", json!({"id": 42})).await?;
+//! ```
+//!
+//! Private topics take the same [`RoomAuthorizer`] as websocket rooms, via
+//! [`Realtime::with_authorizer`].
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use futures::stream::{BoxStream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::borrow::{Borrow, BorrowMut};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use super::{
+    message::BroadcastOptions,
+    room::{JoinRejection, RoomAuthorizer, RoomKey},
+    server::{Connecting, WebSocketServer},
+    ConnectionInfo, Message,
+};
+use crate::error::ApiError;
+
+/// Buffered events per SSE topic before slow subscribers start skipping
+const SSE_CHANNEL_CAPACITY: usize = 256;
+
+/// An event published to a topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RealtimeEvent {
+    pub id: Uuid,
+    pub topic: String,
+    pub tenant_id: Option<String>,
+    pub payload: serde_json::Value,
+    /// Instance that published the event
+    pub origin: Uuid,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl RealtimeEvent {
+    fn new(origin: Uuid, key: RoomKey, payload: serde_json::Value) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            topic: key.room_id,
+            tenant_id: key.tenant_id,
+            payload,
+            origin,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    fn key(&self) -> RoomKey {
+        RoomKey {
+            tenant_id: self.tenant_id.clone(),
+            room_id: self.topic.clone(),
+        }
+    }
+}
+
+/// Distributed pub/sub used to reach clients connected to other instances
+#[async_trait]
+pub trait PubSubBackend: Send + Sync {
+    /// Publish an event to all instances
+    async fn publish(&self, event: &RealtimeEvent) -> Result<(), ApiError>;
+
+    /// Stream of events published by any instance, including this one
+    async fn subscribe(&self) -> Result<BoxStream<'static, RealtimeEvent>, ApiError>;
+}
+
+/// In-process [`PubSubBackend`], for tests and single-node deployments
+#[derive(Clone)]
+pub struct LocalPubSub {
+    sender: broadcast::Sender<RealtimeEvent>,
+}
+
+impl LocalPubSub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(SSE_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl Default for LocalPubSub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl PubSubBackend for LocalPubSub {
+    async fn publish(&self, event: &RealtimeEvent) -> Result<(), ApiError> {
+        let _ = self.sender.send(event.clone());
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, RealtimeEvent>, ApiError> {
+        Ok(receiver_stream(self.sender.subscribe()).boxed())
+    }
+}
+
+fn receiver_stream<R>(receiver: R) -> impl Stream<Item = RealtimeEvent>
+where
+    R: BorrowMut<broadcast::Receiver<RealtimeEvent>> + Send + 'static,
+{
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.borrow_mut().recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Realtime subscriber lagged, events skipped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+type Channels = Arc<RwLock<HashMap<RoomKey, broadcast::Sender<RealtimeEvent>>>>;
+
+/// A subscription to a topic; the topic's channel goes with its last subscriber
+struct Subscriber {
+    receiver: Option<broadcast::Receiver<RealtimeEvent>>,
+    key: RoomKey,
+    channels: Channels,
+}
+
+impl Borrow<broadcast::Receiver<RealtimeEvent>> for Subscriber {
+    fn borrow(&self) -> &broadcast::Receiver<RealtimeEvent> {
+        self.receiver.as_ref().expect("receiver is only taken on drop")
+    }
+}
+
+impl BorrowMut<broadcast::Receiver<RealtimeEvent>> for Subscriber {
+    fn borrow_mut(&mut self) -> &mut broadcast::Receiver<RealtimeEvent> {
+        self.receiver.as_mut().expect("receiver is only taken on drop")
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        // Subscribing happens under the same lock, so no receiver can appear in between
+        let mut channels = self.channels.write().unwrap();
+        self.receiver.take();
+        if channels.get(&self.key).is_some_and(|sender| sender.receiver_count() == 0) {
+            channels.remove(&self.key);
+        }
+    }
+}
+
+/// Transport-agnostic publisher for websocket rooms and SSE streams
+///
+/// Topics map to websocket rooms of the same name. Cloning is cheap.
+#[derive(Clone)]
+pub struct Realtime {
+    instance_id: Uuid,
+    websocket: Option<WebSocketServer>,
+    sse: Channels,
+    backend: Option<Arc<dyn PubSubBackend>>,
+    authorizer: Option<Arc<dyn RoomAuthorizer>>,
+}
+
+impl Realtime {
+    pub fn new() -> Self {
+        Self {
+            instance_id: Uuid::new_v4(),
+            websocket: None,
+            sse: Arc::new(RwLock::new(HashMap::new())),
+            backend: None,
+            authorizer: None,
+        }
+    }
+
+    /// Deliver events to members of the websocket room named after the topic
+    pub fn with_websocket(mut self, server: WebSocketServer) -> Self {
+        self.websocket = Some(server);
+        self
+    }
+
+    /// Forward events to other instances; call [`Realtime::listen`] to receive theirs
+    pub fn with_backend(mut self, backend: impl PubSubBackend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Check SSE and long-poll subscriptions with `authorizer`
+    pub fn with_authorizer(mut self, authorizer: impl RoomAuthorizer + 'static) -> Self {
+        self.authorizer = Some(Arc::new(authorizer));
+        self
+    }
+
+    /// Run the authorization hook for a client subscribing to `topic`
+    pub async fn authorize(&self, conn: &ConnectionInfo, topic: &str) -> Result<(), JoinRejection> {
+        match &self.authorizer {
+            Some(authorizer) => authorizer.authorize_join(conn, topic).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Publish `payload` to everyone subscribed to `topic`
+    ///
+    /// Returns the number of local subscribers reached.
+    pub async fn publish(&self, topic: &str, payload: serde_json::Value) -> Result<usize, ApiError> {
+        self.publish_to(RoomKey::new(topic), payload).await
+    }
+
+    /// Publish to a topic within a tenant's namespace
+    pub async fn publish_to_tenant(
+        &self,
+        tenant_id: &str,
+        topic: &str,
+        payload: serde_json::Value,
+    ) -> Result<usize, ApiError> {
+        self.publish_to(RoomKey::for_tenant(tenant_id, topic), payload).await
+    }
+
+    async fn publish_to(&self, key: RoomKey, payload: serde_json::Value) -> Result<usize, ApiError> {
+        let event = RealtimeEvent::new(self.instance_id, key, payload);
+        let delivered = self.deliver(&event).await;
+
+        if let Some(backend) = &self.backend {
+            backend.publish(&event).await?;
+        }
+
+        Ok(delivered)
+    }
+
+    /// Deliver an event to local websocket and SSE subscribers
+    async fn deliver(&self, event: &RealtimeEvent) -> usize {
+        let mut delivered = 0;
+
+        if let Some(server) = &self.websocket {
+            let message = Message::json(event.payload.clone())
+                .in_room(event.topic.clone())
+                .with_metadata("topic".to_string(), event.topic.clone())
+                .with_metadata("event_id".to_string(), event.id.to_string());
+
+            let mut options = BroadcastOptions::new().in_room(event.topic.clone());
+            options.tenant = event.tenant_id.clone();
+
+            delivered += server.broadcast(message, &options).await;
+        }
+
+        if let Some(sender) = self.sse.read().unwrap().get(&event.key()) {
+            delivered += sender.send(event.clone()).unwrap_or(0);
+        }
+
+        delivered
+    }
+
+    /// Deliver events published by other instances through the backend
+    pub async fn listen(&self) -> Result<tokio::task::JoinHandle<()>, ApiError> {
+        let Some(backend) = &self.backend else {
+            return Err(ApiError::InternalServerError(
+                "Realtime has no pub/sub backend configured".to_string(),
+            ));
+        };
+
+        let mut events = backend.subscribe().await?;
+        let realtime = self.clone();
+
+        Ok(tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                if event.origin != realtime.instance_id {
+                    realtime.deliver(&event).await;
+                }
+            }
+            tracing::warn!("Realtime backend subscription ended");
+        }))
+    }
+
+    /// Subscribe to a topic's events in-process
+    ///
+    /// This doesn't run the authorization hook; see [`Realtime::authorize`].
+    pub async fn subscribe(&self, key: impl Into<RoomKey>) -> impl Stream<Item = RealtimeEvent> {
+        let key = key.into();
+        let receiver = self
+            .sse
+            .write()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| broadcast::channel(SSE_CHANNEL_CAPACITY).0)
+            .subscribe();
+        receiver_stream(Subscriber {
+            receiver: Some(receiver),
+            key,
+            channels: self.sse.clone(),
+        })
+    }
+
+    /// Number of topics with live in-process subscribers
+    pub fn subscribed_topics(&self) -> usize {
+        self.sse.read().unwrap().len()
+    }
+
+    /// SSE routes (`GET /realtime/:topic`)
+    ///
+    /// With multi-tenancy, subscribers only see their tenant's events.
+    /// Subscriptions are checked with the authorizer, if one is set.
+    pub fn sse_routes(&self) -> Router {
+        Router::new()
+            .route("/realtime/:topic", get(sse_handler))
+            .with_state(self.clone())
+    }
+}
+
+impl Default for Realtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn sse_handler(
+    State(realtime): State<Realtime>,
+    Path(topic): Path<String>,
+    Connecting(conn): Connecting,
+) -> Response {
+    if let Err(rejection) = realtime.authorize(&conn, &topic).await {
+        tracing::info!(topic = %topic, code = %rejection.code, "SSE subscription rejected");
+        return rejection.into_response();
+    }
+
+    let events = realtime.subscribe(RoomKey::for_connection(&conn, topic)).await.map(|event| {
+        let data = serde_json::to_string(&event.payload).unwrap_or_default();
+        Ok::<_, Infallible>(Event::default()
+            .id(event.id.to_string())
+            .event(event.topic)
+            .data(data))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::{queue::OverflowPolicy, ConnectionInfo, SendQueue};
+
+    #[tokio::test]
+    async fn test_publish_reaches_websocket_and_sse() {
+        let server = WebSocketServer::new();
+        let conn = ConnectionInfo::new(Uuid::new_v4());
+        server
+            .connections()
+            .insert(conn.clone(), Arc::new(SendQueue::new(8, OverflowPolicy::DropOldest)))
+            .await;
+        server.room_manager().join_unchecked("orders", conn.id).await;
+
+        let realtime = Realtime::new().with_websocket(server.clone());
+        let mut sse = Box::pin(realtime.subscribe("orders").await);
+
+        let delivered = realtime.publish("orders", serde_json::json!({"id": 1})).await.unwrap();
+        assert_eq!(delivered, 2);
+        assert_eq!(server.connections().queue_depth(conn.id).await, Some(1));
+        assert_eq!(sse.next().await.unwrap().payload["id"], 1);
+
+        // Tenant topics are separate from unscoped ones
+        let delivered = realtime
+            .publish_to_tenant("acme", "orders", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(delivered, 0);
+    }
+
+    #[tokio::test]
+    async fn test_topics_pruned_with_last_subscriber() {
+        let realtime = Realtime::new();
+        let first = realtime.subscribe("orders").await;
+        let second = realtime.subscribe("orders").await;
+        assert_eq!(realtime.subscribed_topics(), 1);
+
+        drop(first);
+        assert_eq!(realtime.subscribed_topics(), 1);
+        drop(second);
+        assert_eq!(realtime.subscribed_topics(), 0);
+    }
+
+    struct SignedInOnly;
+
+    #[async_trait]
+    impl RoomAuthorizer for SignedInOnly {
+        async fn authorize_join(&self, conn: &ConnectionInfo, _topic: &str) -> Result<(), JoinRejection> {
+            match conn.user_id {
+                Some(_) => Ok(()),
+                None => Err(JoinRejection::unauthenticated()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_subscriptions_are_authorized() {
+        use axum::{body::Body, http::{Request, StatusCode}};
+        use tower::ServiceExt;
+
+        let realtime = Realtime::new().with_authorizer(SignedInOnly);
+        let response = realtime
+            .sse_routes()
+            .oneshot(Request::get("/realtime/orders").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(realtime.subscribed_topics(), 0);
+
+        let response = super::super::LongPoll::new(realtime)
+            .routes()
+            .oneshot(Request::get("/poll/orders?timeout=0").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_backend_fan_out() {
+        let backend = LocalPubSub::new();
+        let publisher = Realtime::new().with_backend(backend.clone());
+        let receiver = Realtime::new().with_backend(backend);
+        receiver.listen().await.unwrap();
+
+        let mut events = Box::pin(receiver.subscribe("news").await);
+        publisher.publish("news", serde_json::json!("hello")).await.unwrap();
+
+        let event = tokio::time::timeout(std::time::Duration::from_secs(1), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.payload, "hello");
+    }
+}
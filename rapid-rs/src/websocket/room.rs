@@ -1,73 +1,241 @@
 //! WebSocket room management for group messaging
 
+use async_trait::async_trait;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock as SyncRwLock};
 use tokio::sync::RwLock;
-use uuid::Uuid;
 
-use super::handler::ConnectionId;
+use super::{handler::ConnectionId, ConnectionInfo, Message};
+
+/// Reason a connection was refused entry to a room
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JoinRejection {
+    pub code: String,
+    pub reason: String,
+}
+
+impl JoinRejection {
+    pub fn new(code: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            reason: reason.into(),
+        }
+    }
+    
+    /// Connection is not allowed in the room
+    pub fn forbidden(reason: impl Into<String>) -> Self {
+        Self::new("room_forbidden", reason)
+    }
+    
+    /// Connection must authenticate before joining
+    pub fn unauthenticated() -> Self {
+        Self::new("room_unauthenticated", "Authentication required to join this room")
+    }
+    
+    /// Error message sent to the rejected client
+    pub fn to_message(&self, room_id: &str) -> Message {
+        Message::error(self.code.clone(), self.reason.clone()).in_room(room_id)
+    }
+}
+
+/// `401` for [`JoinRejection::unauthenticated`], `403` otherwise
+impl IntoResponse for JoinRejection {
+    fn into_response(self) -> Response {
+        let status = match self.code.as_str() {
+            "room_unauthenticated" => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::FORBIDDEN,
+        };
+        crate::error::ApiError::custom(status, self.code, self.reason).into_response()
+    }
+}
+
+impl std::fmt::Display for JoinRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.reason)
+    }
+}
+
+/// Hook deciding whether a connection may join a room
+///
+/// Use this for private rooms: check `conn.user_id` / `conn.tenant_id` or look
+/// up membership before the join happens.
+#[async_trait]
+pub trait RoomAuthorizer: Send + Sync {
+    async fn authorize_join(&self, conn: &ConnectionInfo, room_id: &str) -> Result<(), JoinRejection>;
+}
+
+/// Identifies a room, scoped to a tenant when multi-tenancy is in use
+///
+/// Rooms with the same name in different tenants are distinct, so a
+/// broadcast to tenant A's "general" never reaches tenant B. Plain `&str`
+/// converts to an unscoped key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoomKey {
+    pub tenant_id: Option<String>,
+    pub room_id: String,
+}
+
+impl RoomKey {
+    pub fn new(room_id: impl Into<String>) -> Self {
+        Self {
+            tenant_id: None,
+            room_id: room_id.into(),
+        }
+    }
+    
+    pub fn for_tenant(tenant_id: impl Into<String>, room_id: impl Into<String>) -> Self {
+        Self {
+            tenant_id: Some(tenant_id.into()),
+            room_id: room_id.into(),
+        }
+    }
+    
+    /// Key for `room_id` in the connection's tenant
+    pub fn for_connection(conn: &ConnectionInfo, room_id: impl Into<String>) -> Self {
+        Self {
+            tenant_id: conn.tenant_id.clone(),
+            room_id: room_id.into(),
+        }
+    }
+}
+
+impl From<&str> for RoomKey {
+    fn from(room_id: &str) -> Self {
+        Self::new(room_id)
+    }
+}
+
+impl From<String> for RoomKey {
+    fn from(room_id: String) -> Self {
+        Self::new(room_id)
+    }
+}
+
+impl From<&String> for RoomKey {
+    fn from(room_id: &String) -> Self {
+        Self::new(room_id.clone())
+    }
+}
 
 /// Room manager for organizing connections into groups
 pub struct RoomManager {
-    rooms: Arc<RwLock<HashMap<String, Room>>>,
+    rooms: Arc<RwLock<HashMap<RoomKey, Room>>>,
+    authorizer: Option<Arc<dyn RoomAuthorizer>>,
 }
 
 impl RoomManager {
     pub fn new() -> Self {
         Self {
             rooms: Arc::new(RwLock::new(HashMap::new())),
+            authorizer: None,
+        }
+    }
+    
+    /// Check joins made through [`RoomManager::join`] with `authorizer`
+    pub fn with_authorizer(mut self, authorizer: impl RoomAuthorizer + 'static) -> Self {
+        self.authorizer = Some(Arc::new(authorizer));
+        self
+    }
+    
+    /// Run the join authorization hook without joining
+    pub async fn authorize_join(&self, conn: &ConnectionInfo, room_id: &str) -> Result<(), JoinRejection> {
+        match &self.authorizer {
+            Some(authorizer) => authorizer.authorize_join(conn, room_id).await,
+            None => Ok(()),
+        }
+    }
+    
+    /// Authorize and join a room
+    ///
+    /// The room is scoped to the connection's tenant, if it has one.
+    pub async fn join(&self, conn: &ConnectionInfo, room_id: &str) -> Result<(), JoinRejection> {
+        if let Err(rejection) = self.authorize_join(conn, room_id).await {
+            tracing::info!(
+                room_id = %room_id,
+                connection_id = %conn.id,
+                code = %rejection.code,
+                "Room join rejected"
+            );
+            return Err(rejection);
         }
+        
+        self.join_unchecked(RoomKey::for_connection(conn, room_id), conn.id).await;
+        Ok(())
+    }
+    
+    /// Join a room by connection ID alone
+    ///
+    /// Runs the authorization hook as [`join`](Self::join) does, but for an
+    /// anonymous connection without a tenant, so authorizers that check the
+    /// user reject it. Rejections are logged and otherwise ignored.
+    #[deprecated(note = "use `RoomManager::join` with the connection's `ConnectionInfo`")]
+    pub async fn join_room(&self, room_id: &str, conn_id: ConnectionId) {
+        let _ = self.join(&ConnectionInfo::new(conn_id), room_id).await;
     }
     
     /// Create a new room
-    pub async fn create_room(&self, room_id: &str) -> Room {
+    pub async fn create_room(&self, room: impl Into<RoomKey>) -> Room {
+        let key = room.into();
         let mut rooms = self.rooms.write().await;
-        let room = Room::new(room_id.to_string());
-        rooms.insert(room_id.to_string(), room.clone());
-        tracing::info!(room_id = %room_id, "Room created");
-        room
+        let room = rooms
+            .entry(key.clone())
+            .or_insert_with(|| Room::for_key(&key));
+        tracing::info!(room_id = %key.room_id, tenant_id = ?key.tenant_id, "Room created");
+        room.clone()
     }
     
     /// Get a room by ID
-    pub async fn get_room(&self, room_id: &str) -> Option<Room> {
+    pub async fn get_room(&self, room: impl Into<RoomKey>) -> Option<Room> {
         let rooms = self.rooms.read().await;
-        rooms.get(room_id).cloned()
+        rooms.get(&room.into()).cloned()
     }
     
-    /// Join a room
-    pub async fn join_room(&self, room_id: &str, conn_id: ConnectionId) {
+    /// Join a room without running the authorization hook
+    ///
+    /// Outside the crate joins go through [`join`](Self::join), which also
+    /// scopes the room to the connection's tenant.
+    pub(crate) async fn join_unchecked(&self, room: impl Into<RoomKey>, conn_id: ConnectionId) {
+        let key = room.into();
         let mut rooms = self.rooms.write().await;
         
-        let room = rooms
-            .entry(room_id.to_string())
-            .or_insert_with(|| Room::new(room_id.to_string()));
-        
-        room.add_connection(conn_id);
+        rooms
+            .entry(key.clone())
+            .or_insert_with(|| Room::for_key(&key))
+            .add_connection(conn_id)
+            .await;
         
         tracing::info!(
-            room_id = %room_id,
+            room_id = %key.room_id,
+            tenant_id = ?key.tenant_id,
             connection_id = %conn_id,
             "Connection joined room"
         );
     }
     
     /// Leave a room
-    pub async fn leave_room(&self, room_id: &str, conn_id: ConnectionId) {
+    pub async fn leave_room(&self, room: impl Into<RoomKey>, conn_id: ConnectionId) {
+        let key = room.into();
         let mut rooms = self.rooms.write().await;
         
-        if let Some(room) = rooms.get_mut(room_id) {
+        if let Some(room) = rooms.get(&key) {
             room.remove_connection(conn_id);
             
             tracing::info!(
-                room_id = %room_id,
+                room_id = %key.room_id,
+                tenant_id = ?key.tenant_id,
                 connection_id = %conn_id,
                 "Connection left room"
             );
             
             // Remove empty rooms
             if room.is_empty() {
-                rooms.remove(room_id);
-                tracing::info!(room_id = %room_id, "Empty room removed");
+                rooms.remove(&key);
+                tracing::info!(room_id = %key.room_id, "Empty room removed");
             }
         }
     }
@@ -75,43 +243,44 @@ impl RoomManager {
     /// Remove connection from all rooms
     pub async fn remove_from_all_rooms(&self, conn_id: ConnectionId) {
         let mut rooms = self.rooms.write().await;
-        let room_ids: Vec<String> = rooms.keys().cloned().collect();
-        
-        for room_id in room_ids {
-            if let Some(room) = rooms.get_mut(&room_id) {
-                room.remove_connection(conn_id);
-                
-                // Remove empty rooms
-                if room.is_empty() {
-                    rooms.remove(&room_id);
-                }
-            }
-        }
+        
+        rooms.retain(|_, room| {
+            room.remove_connection(conn_id);
+            !room.is_empty()
+        });
     }
     
     /// Get all connections in a room
-    pub async fn get_room_connections(&self, room_id: &str) -> Vec<ConnectionId> {
+    pub async fn get_room_connections(&self, room: impl Into<RoomKey>) -> Vec<ConnectionId> {
         let rooms = self.rooms.read().await;
         rooms
-            .get(room_id)
-            .map(|room| room.connections().to_vec())
+            .get(&room.into())
+            .map(Room::connections)
             .unwrap_or_default()
     }
     
     /// List all rooms
     pub async fn list_rooms(&self) -> Vec<RoomInfo> {
+        self.list_rooms_where(|_| true).await
+    }
+    
+    /// List the rooms belonging to a tenant
+    pub async fn list_tenant_rooms(&self, tenant_id: &str) -> Vec<RoomInfo> {
+        self.list_rooms_where(|room| room.tenant_id.as_deref() == Some(tenant_id))
+            .await
+    }
+    
+    async fn list_rooms_where(&self, predicate: impl Fn(&Room) -> bool) -> Vec<RoomInfo> {
         let rooms = self.rooms.read().await;
-        let mut result = Vec::new();
-        
-        for room in rooms.values() {
-            let conn_count = room.connections.read().await.len();
-            result.push(RoomInfo {
+        rooms
+            .values()
+            .filter(|room| predicate(room))
+            .map(|room| RoomInfo {
                 id: room.id.clone(),
-                connection_count: conn_count,
-            });
-        }
-        
-        result
+                tenant_id: room.tenant_id.clone(),
+                connection_count: room.connection_count(),
+            })
+            .collect()
     }
 }
 
@@ -122,43 +291,55 @@ impl Default for RoomManager {
 }
 
 /// Individual room
+///
+/// Clones share the same connection set. It sits behind a synchronous lock
+/// that is never held across an await, so reading it is safe on the runtime.
 #[derive(Debug, Clone)]
 pub struct Room {
     pub id: String,
-    connections: Arc<RwLock<HashSet<ConnectionId>>>,
+    pub tenant_id: Option<String>,
+    connections: Arc<SyncRwLock<HashSet<ConnectionId>>>,
 }
 
 impl Room {
     pub fn new(id: String) -> Self {
         Self {
             id,
-            connections: Arc::new(RwLock::new(HashSet::new())),
+            tenant_id: None,
+            connections: Arc::new(SyncRwLock::new(HashSet::new())),
+        }
+    }
+    
+    fn for_key(key: &RoomKey) -> Self {
+        Self {
+            id: key.room_id.clone(),
+            tenant_id: key.tenant_id.clone(),
+            connections: Arc::new(SyncRwLock::new(HashSet::new())),
         }
     }
     
     pub async fn add_connection(&self, conn_id: ConnectionId) {
-        let mut connections = self.connections.write().await;
-        connections.insert(conn_id);
+        self.connections.write().unwrap().insert(conn_id);
     }
     
     pub fn remove_connection(&self, conn_id: ConnectionId) {
-        let mut connections = self.connections.blocking_write();
-        connections.remove(&conn_id);
+        self.connections.write().unwrap().remove(&conn_id);
+    }
+    
+    pub fn contains(&self, conn_id: ConnectionId) -> bool {
+        self.connections.read().unwrap().contains(&conn_id)
     }
     
     pub fn is_empty(&self) -> bool {
-        let connections = self.connections.blocking_read();
-        connections.is_empty()
+        self.connections.read().unwrap().is_empty()
     }
     
     pub fn connections(&self) -> Vec<ConnectionId> {
-        let connections = self.connections.blocking_read();
-        connections.iter().copied().collect()
+        self.connections.read().unwrap().iter().copied().collect()
     }
     
     pub fn connection_count(&self) -> usize {
-        let connections = self.connections.blocking_read();
-        connections.len()
+        self.connections.read().unwrap().len()
     }
 }
 
@@ -166,14 +347,17 @@ impl Room {
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct RoomInfo {
     pub id: String,
+    pub tenant_id: Option<String>,
     pub connection_count: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use uuid::Uuid;
     
     #[tokio::test]
+    #[allow(deprecated)]
     async fn test_room_management() {
         let manager = RoomManager::new();
         let conn_id = Uuid::new_v4();
@@ -188,5 +372,60 @@ mod tests {
         
         let connections = manager.get_room_connections("test_room").await;
         assert_eq!(connections.len(), 0);
+        
+        // Rooms handed out share their connections with the manager
+        let room = manager.create_room("lobby").await;
+        room.add_connection(conn_id).await;
+        assert!(manager.get_room("lobby").await.unwrap().contains(conn_id));
+    }
+    
+    struct MembersOnly;
+    
+    #[async_trait]
+    impl RoomAuthorizer for MembersOnly {
+        async fn authorize_join(&self, conn: &ConnectionInfo, room_id: &str) -> Result<(), JoinRejection> {
+            match conn.user_id.as_deref() {
+                None => Err(JoinRejection::unauthenticated()),
+                Some("alice") => Ok(()),
+                Some(_) => Err(JoinRejection::forbidden(format!("Not a member of {}", room_id))),
+            }
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_authorize_join() {
+        let manager = RoomManager::new().with_authorizer(MembersOnly);
+        
+        let mut alice = ConnectionInfo::new(Uuid::new_v4());
+        alice.user_id = Some("alice".to_string());
+        assert!(manager.join(&alice, "private").await.is_ok());
+        
+        let anonymous = ConnectionInfo::new(Uuid::new_v4());
+        let rejection = manager.join(&anonymous, "private").await.unwrap_err();
+        assert_eq!(rejection.code, "room_unauthenticated");
+        #[allow(deprecated)]
+        manager.join_room("private", Uuid::new_v4()).await;
+        
+        assert_eq!(manager.get_room_connections("private").await, vec![alice.id]);
+    }
+    
+    #[tokio::test]
+    async fn test_tenant_rooms_are_isolated() {
+        let manager = RoomManager::new();
+        
+        let mut a = ConnectionInfo::new(Uuid::new_v4());
+        a.tenant_id = Some("tenant-a".to_string());
+        let mut b = ConnectionInfo::new(Uuid::new_v4());
+        b.tenant_id = Some("tenant-b".to_string());
+        
+        manager.join(&a, "general").await.unwrap();
+        manager.join(&b, "general").await.unwrap();
+        
+        assert_eq!(
+            manager.get_room_connections(RoomKey::for_tenant("tenant-a", "general")).await,
+            vec![a.id]
+        );
+        assert!(manager.get_room_connections("general").await.is_empty());
+        assert_eq!(manager.list_tenant_rooms("tenant-b").await.len(), 1);
     }
 }
\ No newline at end of file
@@ -1,20 +1,36 @@
 //! WebSocket server implementation
 
 use axum::{
+    async_trait,
     extract::{
         ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
-        State,
+        FromRequestParts, Query, State,
     },
-    response::IntoResponse,
+    http::request::Parts,
+    response::Response,
     routing::get,
     Router,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use super::{handler::WebSocketHandler, room::RoomManager, ConnectionInfo, Message};
+use super::{
+    handler::{ConnectionId, WebSocketHandler},
+    message::BroadcastOptions,
+    queue::{OverflowPolicy, SendOutcome, SendQueue},
+    room::{JoinRejection, RoomAuthorizer, RoomKey, RoomManager},
+    session::{ResumeConfig, SessionStore},
+    store::ConnectionStore,
+    ConnectionInfo, Message,
+};
+
+/// How long a closing connection's writer gets to send its last frame and the close
+const WRITER_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// WebSocket server configuration
 #[derive(Debug, Clone)]
@@ -22,6 +38,11 @@ pub struct WebSocketConfig {
     pub max_message_size: usize,
     pub ping_interval_secs: u64,
     pub timeout_secs: u64,
+    /// Outbound messages buffered per connection before `overflow_policy` applies
+    pub send_queue_capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+    /// Resume tokens for reconnecting clients (disabled by default)
+    pub resume: ResumeConfig,
 }
 
 impl Default for WebSocketConfig {
@@ -30,15 +51,24 @@ impl Default for WebSocketConfig {
             max_message_size: 64 * 1024,
             ping_interval_secs: 30,
             timeout_secs: 60,
+            send_queue_capacity: 256,
+            overflow_policy: OverflowPolicy::DropOldest,
+            resume: ResumeConfig::default(),
         }
     }
 }
 
 /// WebSocket server
+///
+/// Clones share connections, rooms and the handler.
+#[derive(Clone)]
 pub struct WebSocketServer {
     config: WebSocketConfig,
     handler: Arc<RwLock<Option<Arc<dyn WebSocketHandler>>>>,
     room_manager: Arc<RoomManager>,
+    connections: ConnectionStore,
+    sessions: SessionStore,
+    active_connections: Arc<AtomicUsize>,
 }
 
 impl WebSocketServer {
@@ -47,10 +77,30 @@ impl WebSocketServer {
     }
     
     pub fn with_config(config: WebSocketConfig) -> Self {
+        let sessions = SessionStore::new();
         Self {
             config,
             handler: Arc::new(RwLock::new(None)),
             room_manager: Arc::new(RoomManager::new()),
+            connections: ConnectionStore::new().with_sessions(sessions.clone()),
+            sessions,
+            active_connections: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+    
+    /// Set the handler while building the server
+    pub fn with_handler(self, handler: impl WebSocketHandler + 'static) -> Self {
+        Self {
+            handler: Arc::new(RwLock::new(Some(Arc::new(handler)))),
+            ..self
+        }
+    }
+    
+    /// Require rooms joined via [`WebSocketServer::join_room`] to pass `authorizer`
+    pub fn with_room_authorizer(self, authorizer: impl RoomAuthorizer + 'static) -> Self {
+        Self {
+            room_manager: Arc::new(RoomManager::new().with_authorizer(authorizer)),
+            ..self
         }
     }
     
@@ -62,11 +112,104 @@ impl WebSocketServer {
         self.room_manager.clone()
     }
     
+    /// Registry of live connections, for lookups and force-disconnects
+    ///
+    /// Mount [`super::connection_routes`] with this store to expose it to admins.
+    pub fn connections(&self) -> ConnectionStore {
+        self.connections.clone()
+    }
+    
+    /// Join a room on behalf of a connection, running the authorization hook
+    ///
+    /// On rejection the client is sent an error message carrying the
+    /// rejection code and reason.
+    pub async fn join_room(&self, conn_id: ConnectionId, room_id: &str) -> Result<(), JoinRejection> {
+        let conn = self
+            .connections
+            .get(conn_id)
+            .await
+            .ok_or_else(|| JoinRejection::new("connection_not_found", "Connection is not open"))?;
+        
+        let result = self.room_manager.join(&conn, room_id).await;
+        if let Err(rejection) = &result {
+            self.connections.send(conn_id, rejection.to_message(room_id)).await;
+        }
+        result
+    }
+    
+    /// Queue a message for a single connection
+    ///
+    /// With resume enabled, messages for a recently disconnected client are
+    /// buffered and replayed when it reconnects.
+    pub async fn send(&self, conn_id: ConnectionId, message: Message) -> SendOutcome {
+        let resume = &self.config.resume;
+        if !resume.enabled {
+            return self.connections.send(conn_id, message).await;
+        }
+        
+        match self.connections.send(conn_id, message.clone()).await {
+            SendOutcome::Closed if self.sessions.buffer(conn_id, message, resume.buffer_size).await => {
+                SendOutcome::Queued
+            }
+            outcome => outcome,
+        }
+    }
+    
+    /// Queue a message for all connections selected by `options`
+    ///
+    /// Rooms are looked up in `options.tenant`'s namespace, so tenants never
+    /// receive each other's broadcasts even when room names match. Returns the
+    /// number of connections that accepted the message without overflowing.
+    pub async fn broadcast(&self, message: Message, options: &BroadcastOptions) -> usize {
+        let mut targets = match (&options.room, &options.tenant) {
+            (Some(room), tenant) => {
+                let key = RoomKey {
+                    tenant_id: tenant.clone(),
+                    room_id: room.clone(),
+                };
+                self.room_manager.get_room_connections(key).await
+            }
+            (None, Some(tenant)) => self.connections.by_tenant(tenant).await.into_iter().map(|info| info.id).collect(),
+            (None, None) => self.connections.list().await.into_iter().map(|info| info.id).collect(),
+        };
+        
+        if let Some(only) = &options.only {
+            targets.retain(|id| only.contains(id));
+        }
+        targets.retain(|id| !options.exclude.contains(id));
+        
+        if !self.config.resume.enabled {
+            return self.connections.send_many(&targets, &message).await;
+        }
+        
+        let mut delivered = 0;
+        for id in targets {
+            if self.send(id, message.clone()).await == SendOutcome::Queued {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+    
+    /// Number of currently open connections
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+    
+    /// WebSocket routes (`GET /ws`)
+    ///
+    /// The remote address is the client as resolved through the app's
+    /// trusted proxies, see [`ClientInfo`](crate::client_ip::ClientInfo). It
+    /// is only recorded when the app is served with
+    /// `into_make_service_with_connect_info::<SocketAddr>()`, which `App::run` does.
     pub fn routes(&self) -> Router {
         let state = WebSocketServerState {
             config: self.config.clone(),
             handler: self.handler.clone(),
             room_manager: self.room_manager.clone(),
+            connections: self.connections.clone(),
+            sessions: self.sessions.clone(),
+            active_connections: self.active_connections.clone(),
         };
         
         Router::new()
@@ -86,20 +229,121 @@ struct WebSocketServerState {
     config: WebSocketConfig,
     handler: Arc<RwLock<Option<Arc<dyn WebSocketHandler>>>>,
     room_manager: Arc<RoomManager>,
+    connections: ConnectionStore,
+    sessions: SessionStore,
+    active_connections: Arc<AtomicUsize>,
+}
+
+/// The [`ConnectionInfo`] of a client opening a websocket or realtime stream
+///
+/// Carries the client address, tenant and, with a valid bearer token, the
+/// user. A token that fails to verify rejects the request.
+pub(super) struct Connecting(pub ConnectionInfo);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Connecting {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let mut conn_info = ConnectionInfo::new(Uuid::new_v4());
+        let client = crate::client_ip::ClientInfo::from_request_parts(parts, state).await.ok();
+        conn_info.remote_addr = client.map(|client| match client.is_forwarded() {
+            true => client.ip.to_string(),
+            false => client.peer.to_string(),
+        });
+        
+        #[cfg(feature = "multi-tenancy")]
+        if let Some(tenant) = parts.extensions.get::<crate::multi_tenancy::TenantContext>() {
+            conn_info.tenant_id = Some(tenant.tenant_id().as_str().to_string());
+        }
+        
+        // Browsers cannot set headers on websocket upgrades or EventSource, so also accept `?token=`
+        let params = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+            .map(|Query(params)| params)
+            .unwrap_or_default();
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .or_else(|| params.get("token").map(String::as_str));
+        
+        #[cfg(feature = "auth")]
+        if let Some(token) = token {
+            let config = parts
+                .extensions
+                .get::<crate::auth::AuthConfig>()
+                .cloned()
+                .unwrap_or_else(crate::auth::AuthConfig::from_env);
+            
+            match crate::auth::jwt::verify_access_token(token, &config) {
+                Ok(claims) => conn_info.user_id = Some(claims.sub),
+                Err(e) => return Err(axum::response::IntoResponse::into_response(e)),
+            }
+        }
+        
+        #[cfg(not(feature = "auth"))]
+        let _ = token;
+        
+        Ok(Connecting(conn_info))
+    }
 }
 
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<WebSocketServerState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    Query(params): Query<HashMap<String, String>>,
+    Connecting(conn_info): Connecting,
+) -> Response {
+    let resume_token = params.get("resume_token").cloned();
+    
+    ws.max_message_size(state.config.max_message_size)
+        .on_upgrade(move |socket| handle_socket(socket, state, conn_info, resume_token))
+}
+
+fn record_connection_gauge(active: usize) {
+    #[cfg(feature = "observability")]
+    crate::metrics::record_gauge("websocket_connections_active", active as f64, &[]);
+    
+    #[cfg(not(feature = "observability"))]
+    let _ = active;
 }
 
-async fn handle_socket(socket: WebSocket, state: WebSocketServerState) {
-    let connection_id = Uuid::new_v4();
-    let conn_info = ConnectionInfo::new(connection_id);
+async fn handle_socket(
+    socket: WebSocket,
+    state: WebSocketServerState,
+    mut conn_info: ConnectionInfo,
+    resume_token: Option<String>,
+) {
+    let resume = state.config.resume.clone();
+    let mut replay = Vec::new();
+    let mut resumed_token = None;
     
-    tracing::info!(connection_id = %connection_id, "WebSocket connection established");
+    // Take over the previous connection ID so room memberships carry over
+    if let (true, Some(token)) = (resume.enabled, resume_token) {
+        match state
+            .sessions
+            .resume(&token, conn_info.user_id.as_deref(), resume.window())
+            .await
+        {
+            Some((conn_id, missed)) => {
+                conn_info.id = conn_id;
+                replay = missed;
+                resumed_token = Some(token);
+            }
+            None => tracing::debug!("Resume token rejected, starting a new session"),
+        }
+    }
+    
+    let connection_id = conn_info.id;
+    
+    tracing::info!(
+        connection_id = %connection_id,
+        remote_addr = ?conn_info.remote_addr,
+        user_id = ?conn_info.user_id,
+        resumed = resumed_token.is_some(),
+        "WebSocket connection established"
+    );
     
     if let Some(handler) = state.handler.read().await.as_ref() {
         if let Err(e) = handler.on_connect(connection_id, &conn_info).await {
@@ -108,9 +352,67 @@ async fn handle_socket(socket: WebSocket, state: WebSocketServerState) {
         }
     }
     
+    let queue = Arc::new(SendQueue::new(
+        state.config.send_queue_capacity,
+        state.config.overflow_policy,
+    ));
+    
+    if resume.enabled {
+        let resumed = resumed_token.is_some();
+        let token = match resumed_token {
+            Some(token) => token,
+            None => state.sessions.open(connection_id, conn_info.user_id.clone()).await,
+        };
+        queue.push(
+            Message::system("session")
+                .with_metadata("resume_token".to_string(), token)
+                .with_metadata("resumed".to_string(), resumed.to_string()),
+        );
+        for message in replay {
+            queue.push(message);
+        }
+    }
+    
+    state.connections.insert(conn_info, queue.clone()).await;
+    let active = state.active_connections.fetch_add(1, Ordering::Relaxed) + 1;
+    record_connection_gauge(active);
+    #[cfg(feature = "observability")]
+    crate::metrics::record_counter("websocket_connections_total", 1, &[]);
+    
     let (mut sender, mut receiver) = socket.split();
     
-    while let Some(msg) = receiver.next().await {
+    // Writer drains the bounded queue; it exits when the queue is closed by a
+    // force-disconnect or overflow, or when the client stops accepting frames
+    let writer_queue = queue.clone();
+    let mut writer = tokio::spawn(async move {
+        while let Some(message) = writer_queue.pop().await {
+            let text = match message.to_json() {
+                Ok(text) => text,
+                Err(e) => {
+                    tracing::error!(connection_id = %connection_id, error = %e, "Failed to serialize message");
+                    continue;
+                }
+            };
+            if sender.send(WsMessage::Text(text)).await.is_err() {
+                return;
+            }
+        }
+        let _ = sender.send(WsMessage::Close(None)).await;
+    });
+    
+    let mut writer_done = false;
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = &mut writer => {
+                writer_done = true;
+                break;
+            }
+        };
+        
         match msg {
             Ok(WsMessage::Text(text)) => {
                 tracing::debug!(connection_id = %connection_id, "Received text: {}", text);
@@ -138,13 +440,8 @@ async fn handle_socket(socket: WebSocket, state: WebSocketServerState) {
                     }
                 }
             }
-            Ok(WsMessage::Ping(data)) => {
-                if let Err(e) = sender.send(WsMessage::Pong(data)).await {
-                    tracing::error!(connection_id = %connection_id, error = %e, "Failed to send pong");
-                    break;
-                }
-            }
-            Ok(WsMessage::Pong(_)) => {}
+            // Pings are answered by the transport
+            Ok(WsMessage::Ping(_)) | Ok(WsMessage::Pong(_)) => {}
             Ok(WsMessage::Close(_)) => {
                 tracing::info!(connection_id = %connection_id, "WebSocket close received");
                 break;
@@ -160,6 +457,34 @@ async fn handle_socket(socket: WebSocket, state: WebSocketServerState) {
         let _ = handler.on_disconnect(connection_id).await;
     }
     
+    queue.close();
+    if !writer_done && tokio::time::timeout(WRITER_CLOSE_TIMEOUT, &mut writer).await.is_err() {
+        writer.abort();
+    }
+    state.connections.remove(connection_id).await;
+    
+    // Keep rooms until the resume window passes without a reconnect, unless
+    // the session was revoked by a force-disconnect
+    let detached = resume.enabled
+        && state
+            .sessions
+            .detach(connection_id, queue.drain(), resume.buffer_size)
+            .await;
+    if detached {
+        let sessions = state.sessions.clone();
+        let room_manager = state.room_manager.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(resume.window()).await;
+            if sessions.expire(connection_id, resume.window()).await {
+                room_manager.remove_from_all_rooms(connection_id).await;
+            }
+        });
+    } else {
+        state.room_manager.remove_from_all_rooms(connection_id).await;
+    }
+    let active = state.active_connections.fetch_sub(1, Ordering::Relaxed) - 1;
+    record_connection_gauge(active);
+    
     tracing::info!(connection_id = %connection_id, "WebSocket connection closed");
 }
 
@@ -171,6 +496,7 @@ mod tests {
     fn test_websocket_config() {
         let config = WebSocketConfig::default();
         assert_eq!(config.max_message_size, 64 * 1024);
+        assert_eq!(config.overflow_policy, OverflowPolicy::DropOldest);
     }
     
     #[tokio::test]
@@ -178,4 +504,45 @@ mod tests {
         let server = WebSocketServer::new();
         let _routes = server.routes();
     }
+    
+    #[tokio::test]
+    async fn test_with_handler() {
+        let server = WebSocketServer::new().with_handler(crate::websocket::handler::EchoHandler);
+        assert!(server.handler.read().await.is_some());
+        assert_eq!(server.active_connections(), 0);
+    }
+    
+    #[tokio::test]
+    async fn test_broadcast_to_room() {
+        let server = WebSocketServer::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        for id in [a, b] {
+            let queue = Arc::new(SendQueue::new(4, OverflowPolicy::DropOldest));
+            server.connections.insert(ConnectionInfo::new(id), queue).await;
+        }
+        server.room_manager.join_unchecked("general", a).await;
+        
+        let options = BroadcastOptions::new().in_room("general");
+        assert_eq!(server.broadcast(Message::text("hi"), &options).await, 1);
+        assert_eq!(server.connections.queue_depth(b).await, Some(0));
+        
+        let options = BroadcastOptions::new().exclude(vec![a]);
+        assert_eq!(server.broadcast(Message::text("hi"), &options).await, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_send_buffers_for_detached_session() {
+        let server = WebSocketServer::with_config(WebSocketConfig {
+            resume: ResumeConfig::enabled(),
+            ..WebSocketConfig::default()
+        });
+        let conn_id = Uuid::new_v4();
+        let token = server.sessions.open(conn_id, None).await;
+        server.sessions.detach(conn_id, Vec::new(), 10).await;
+        
+        assert_eq!(server.send(conn_id, Message::text("missed")).await, SendOutcome::Queued);
+        
+        let (_, replay) = server.sessions.resume(&token, None, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(replay.len(), 1);
+    }
 }
\ No newline at end of file
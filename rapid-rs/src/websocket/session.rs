@@ -0,0 +1,238 @@
+//! Resumable sessions for reconnecting clients
+//!
+//! On connect the client receives a resume token. If it reconnects with
+//! `?resume_token=...` within the resume window it takes over its previous
+//! connection ID, keeping room memberships, and messages sent while it was
+//! away are replayed before live traffic.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{handler::ConnectionId, Message};
+
+/// Reconnection settings
+#[derive(Debug, Clone)]
+pub struct ResumeConfig {
+    pub enabled: bool,
+    /// How long a disconnected session can be resumed
+    pub window_secs: u64,
+    /// Messages buffered per disconnected session; oldest are dropped first
+    pub buffer_size: usize,
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 120,
+            buffer_size: 100,
+        }
+    }
+}
+
+impl ResumeConfig {
+    /// Enabled with default window and buffer
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_window(mut self, window_secs: u64) -> Self {
+        self.window_secs = window_secs;
+        self
+    }
+
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+}
+
+#[derive(Debug)]
+struct Session {
+    conn_id: ConnectionId,
+    user_id: Option<String>,
+    detached_at: Option<Instant>,
+    buffer: VecDeque<Message>,
+}
+
+#[derive(Debug, Default)]
+struct Sessions {
+    by_token: HashMap<String, Session>,
+    tokens: HashMap<ConnectionId, String>,
+}
+
+/// Resume tokens and missed-message buffers
+#[derive(Clone, Default)]
+pub struct SessionStore {
+    inner: Arc<RwLock<Sessions>>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a session for a new connection and return its resume token
+    pub async fn open(&self, conn_id: ConnectionId, user_id: Option<String>) -> String {
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let mut inner = self.inner.write().await;
+        inner.by_token.insert(
+            token.clone(),
+            Session {
+                conn_id,
+                user_id,
+                detached_at: None,
+                buffer: VecDeque::new(),
+            },
+        );
+        inner.tokens.insert(conn_id, token.clone());
+        token
+    }
+
+    /// Reattach a disconnected session
+    ///
+    /// Returns the original connection ID and the messages missed while
+    /// disconnected. Fails if the token is unknown, still attached, expired,
+    /// or belongs to a different user.
+    pub async fn resume(
+        &self,
+        token: &str,
+        user_id: Option<&str>,
+        window: Duration,
+    ) -> Option<(ConnectionId, Vec<Message>)> {
+        let mut inner = self.inner.write().await;
+        let session = inner.by_token.get_mut(token)?;
+
+        let detached_at = session.detached_at?;
+        if detached_at.elapsed() > window || session.user_id.as_deref() != user_id {
+            return None;
+        }
+
+        session.detached_at = None;
+        Some((session.conn_id, session.buffer.drain(..).collect()))
+    }
+
+    /// Resume token of an attached or detached session
+    pub async fn token(&self, conn_id: ConnectionId) -> Option<String> {
+        self.inner.read().await.tokens.get(&conn_id).cloned()
+    }
+
+    /// Mark a session disconnected, keeping messages that were never delivered
+    ///
+    /// Returns `false` if there is no session to resume, e.g. it was revoked.
+    pub async fn detach(&self, conn_id: ConnectionId, undelivered: Vec<Message>, buffer_size: usize) -> bool {
+        let mut inner = self.inner.write().await;
+        let Some(token) = inner.tokens.get(&conn_id).cloned() else {
+            return false;
+        };
+        match inner.by_token.get_mut(&token) {
+            Some(session) => {
+                session.detached_at = Some(Instant::now());
+                session.buffer.extend(undelivered);
+                while session.buffer.len() > buffer_size {
+                    session.buffer.pop_front();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Buffer a message for a disconnected session; `false` if there is none
+    pub async fn buffer(&self, conn_id: ConnectionId, message: Message, buffer_size: usize) -> bool {
+        let mut inner = self.inner.write().await;
+        let Some(token) = inner.tokens.get(&conn_id).cloned() else {
+            return false;
+        };
+        match inner.by_token.get_mut(&token) {
+            Some(session) if session.detached_at.is_some() => {
+                session.buffer.push_back(message);
+                if session.buffer.len() > buffer_size {
+                    session.buffer.pop_front();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drop the session so its token can no longer be resumed
+    ///
+    /// Returns `true` when there was a session.
+    pub async fn revoke(&self, conn_id: ConnectionId) -> bool {
+        let mut inner = self.inner.write().await;
+        match inner.tokens.remove(&conn_id) {
+            Some(token) => inner.by_token.remove(&token).is_some(),
+            None => false,
+        }
+    }
+
+    /// Drop the session if it is still disconnected after `window`
+    ///
+    /// Returns `true` when the session was removed.
+    pub async fn expire(&self, conn_id: ConnectionId, window: Duration) -> bool {
+        let mut inner = self.inner.write().await;
+        let Some(token) = inner.tokens.get(&conn_id).cloned() else {
+            return false;
+        };
+        let expired = inner
+            .by_token
+            .get(&token)
+            .and_then(|session| session.detached_at)
+            .is_some_and(|detached_at| detached_at.elapsed() >= window);
+
+        if expired {
+            inner.by_token.remove(&token);
+            inner.tokens.remove(&conn_id);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resume_replays_missed_messages() {
+        let store = SessionStore::new();
+        let conn_id = Uuid::new_v4();
+        let token = store.open(conn_id, Some("alice".to_string())).await;
+        let window = Duration::from_secs(60);
+
+        // Attached sessions cannot be taken over
+        assert!(store.resume(&token, Some("alice"), window).await.is_none());
+
+        store.detach(conn_id, vec![Message::text("pending")], 10).await;
+        assert!(store.buffer(conn_id, Message::text("missed"), 10).await);
+
+        assert!(store.resume(&token, Some("mallory"), window).await.is_none());
+        let (resumed, replay) = store.resume(&token, Some("alice"), window).await.unwrap();
+        assert_eq!(resumed, conn_id);
+        assert_eq!(replay.len(), 2);
+
+        assert!(!store.buffer(conn_id, Message::text("live"), 10).await);
+    }
+
+    #[tokio::test]
+    async fn test_expire() {
+        let store = SessionStore::new();
+        let conn_id = Uuid::new_v4();
+        let token = store.open(conn_id, None).await;
+
+        assert!(!store.expire(conn_id, Duration::ZERO).await);
+        store.detach(conn_id, Vec::new(), 10).await;
+        assert!(store.expire(conn_id, Duration::ZERO).await);
+        assert!(store.resume(&token, None, Duration::from_secs(60)).await.is_none());
+    }
+}
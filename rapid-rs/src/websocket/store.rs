@@ -0,0 +1,291 @@
+//! Registry of live WebSocket connections
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use super::{
+    handler::ConnectionId,
+    queue::{SendOutcome, SendQueue},
+    session::SessionStore,
+    ConnectionInfo, Message,
+};
+use crate::error::ApiError;
+
+struct ConnectionEntry {
+    info: ConnectionInfo,
+    queue: Arc<SendQueue>,
+}
+
+/// Store holding [`ConnectionInfo`] for every open connection
+///
+/// Cloning is cheap; clones share the same registry.
+#[derive(Clone, Default)]
+pub struct ConnectionStore {
+    connections: Arc<RwLock<HashMap<ConnectionId, ConnectionEntry>>>,
+    /// Resumable sessions revoked on force-disconnect
+    sessions: Option<SessionStore>,
+}
+
+impl ConnectionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Revoke connections' resume tokens in `sessions` when force-disconnecting
+    pub(crate) fn with_sessions(mut self, sessions: SessionStore) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Register a connection together with its outbound queue
+    pub(crate) async fn insert(&self, info: ConnectionInfo, queue: Arc<SendQueue>) {
+        self.connections
+            .write()
+            .await
+            .insert(info.id, ConnectionEntry { info, queue });
+    }
+
+    pub(crate) async fn remove(&self, conn_id: ConnectionId) -> Option<ConnectionInfo> {
+        self.connections.write().await.remove(&conn_id).map(|entry| entry.info)
+    }
+
+    /// Get a connection by ID
+    pub async fn get(&self, conn_id: ConnectionId) -> Option<ConnectionInfo> {
+        self.connections.read().await.get(&conn_id).map(|entry| entry.info.clone())
+    }
+
+    /// List all live connections
+    pub async fn list(&self) -> Vec<ConnectionInfo> {
+        self.find(|_| true).await
+    }
+
+    /// Connections authenticated as `user_id`
+    pub async fn by_user(&self, user_id: &str) -> Vec<ConnectionInfo> {
+        self.find(|info| info.user_id.as_deref() == Some(user_id)).await
+    }
+
+    /// Connections belonging to `tenant_id`
+    pub async fn by_tenant(&self, tenant_id: &str) -> Vec<ConnectionInfo> {
+        self.find(|info| info.tenant_id.as_deref() == Some(tenant_id)).await
+    }
+
+    /// Connections whose metadata has `key` set to `value`
+    pub async fn by_metadata(&self, key: &str, value: &str) -> Vec<ConnectionInfo> {
+        self.find(|info| info.metadata.get(key).map(String::as_str) == Some(value)).await
+    }
+
+    /// Connections matching an arbitrary predicate
+    pub async fn find(&self, predicate: impl Fn(&ConnectionInfo) -> bool) -> Vec<ConnectionInfo> {
+        self.connections
+            .read()
+            .await
+            .values()
+            .filter(|entry| predicate(&entry.info))
+            .map(|entry| entry.info.clone())
+            .collect()
+    }
+
+    /// Update a connection's metadata tag
+    pub async fn set_metadata(&self, conn_id: ConnectionId, key: impl Into<String>, value: impl Into<String>) -> bool {
+        match self.connections.write().await.get_mut(&conn_id) {
+            Some(entry) => {
+                entry.info.metadata.insert(key.into(), value.into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queue a message for one connection
+    pub async fn send(&self, conn_id: ConnectionId, message: Message) -> SendOutcome {
+        match self.connections.read().await.get(&conn_id) {
+            Some(entry) => entry.queue.push(message),
+            None => SendOutcome::Closed,
+        }
+    }
+
+    /// Queue a message for every connection in `conn_ids`, returning how many accepted it
+    pub async fn send_many(&self, conn_ids: &[ConnectionId], message: &Message) -> usize {
+        let connections = self.connections.read().await;
+        conn_ids
+            .iter()
+            .filter_map(|id| connections.get(id))
+            .filter(|entry| entry.queue.push(message.clone()) == SendOutcome::Queued)
+            .count()
+    }
+
+    /// Number of messages waiting in a connection's send queue
+    pub async fn queue_depth(&self, conn_id: ConnectionId) -> Option<usize> {
+        self.connections.read().await.get(&conn_id).map(|entry| entry.queue.len())
+    }
+
+    /// Number of live connections
+    pub async fn len(&self) -> usize {
+        self.connections.read().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.connections.read().await.is_empty()
+    }
+
+    /// Ask a connection to close; returns `false` if it is not open
+    ///
+    /// Its resume token is revoked, so the client can't reconnect into the
+    /// same session.
+    pub async fn disconnect(&self, conn_id: ConnectionId) -> bool {
+        match self.connections.read().await.get(&conn_id) {
+            Some(entry) => entry.queue.close(),
+            None => return false,
+        }
+        if let Some(sessions) = &self.sessions {
+            sessions.revoke(conn_id).await;
+        }
+        tracing::info!(connection_id = %conn_id, "WebSocket connection force-disconnected");
+        true
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ConnectionFilter {
+    user_id: Option<String>,
+    tenant_id: Option<String>,
+    /// `key:value` metadata tag
+    tag: Option<String>,
+}
+
+async fn list_connections(
+    State(store): State<ConnectionStore>,
+    Query(filter): Query<ConnectionFilter>,
+) -> Json<Vec<ConnectionInfo>> {
+    let tag = filter
+        .tag
+        .as_deref()
+        .map(|tag| tag.split_once(':').unwrap_or((tag, "")));
+
+    Json(
+        store
+            .find(|info| {
+                filter.user_id.as_ref().is_none_or(|id| info.user_id.as_ref() == Some(id))
+                    && filter.tenant_id.as_ref().is_none_or(|id| info.tenant_id.as_ref() == Some(id))
+                    && tag.is_none_or(|(key, value)| {
+                        info.metadata.get(key).map(String::as_str) == Some(value)
+                    })
+            })
+            .await,
+    )
+}
+
+async fn get_connection(
+    State(store): State<ConnectionStore>,
+    Path(id): Path<ConnectionId>,
+) -> Result<Json<ConnectionInfo>, ApiError> {
+    store
+        .get(id)
+        .await
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Connection {} not found", id)))
+}
+
+async fn disconnect_connection(
+    State(store): State<ConnectionStore>,
+    Path(id): Path<ConnectionId>,
+) -> Result<StatusCode, ApiError> {
+    if store.disconnect(id).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("Connection {} not found", id)))
+    }
+}
+
+/// Admin routes for inspecting and closing connections
+///
+/// - `GET /ws/connections?user_id=&tenant_id=&tag=key:value`
+/// - `GET /ws/connections/:id`
+/// - `DELETE /ws/connections/:id` - force-disconnect
+///
+/// These routes are unauthenticated; mount them behind your admin auth.
+pub fn connection_routes(store: ConnectionStore) -> Router {
+    Router::new()
+        .route("/ws/connections", get(list_connections))
+        .route("/ws/connections/:id", get(get_connection))
+        .route("/ws/connections/:id", delete(disconnect_connection))
+        .with_state(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::queue::OverflowPolicy;
+    use uuid::Uuid;
+
+    fn queue() -> Arc<SendQueue> {
+        Arc::new(SendQueue::new(8, OverflowPolicy::DropOldest))
+    }
+
+    #[tokio::test]
+    async fn test_connection_lookup() {
+        let store = ConnectionStore::new();
+
+        let mut alice = ConnectionInfo::new(Uuid::new_v4());
+        alice.user_id = Some("alice".to_string());
+        alice.tenant_id = Some("acme".to_string());
+        alice.metadata.insert("client".to_string(), "ios".to_string());
+        store.insert(alice.clone(), queue()).await;
+        store.insert(ConnectionInfo::new(Uuid::new_v4()), queue()).await;
+
+        assert_eq!(store.len().await, 2);
+        assert_eq!(store.by_user("alice").await.len(), 1);
+        assert_eq!(store.by_tenant("acme").await[0].id, alice.id);
+        assert_eq!(store.by_metadata("client", "ios").await.len(), 1);
+        assert!(store.by_metadata("client", "web").await.is_empty());
+
+        store.remove(alice.id).await;
+        assert!(store.get(alice.id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_force_disconnect() {
+        let store = ConnectionStore::new();
+        let info = ConnectionInfo::new(Uuid::new_v4());
+        let queue = queue();
+        store.insert(info.clone(), queue.clone()).await;
+
+        assert!(store.disconnect(info.id).await);
+        assert!(queue.is_closed());
+
+        assert!(!store.disconnect(Uuid::new_v4()).await);
+    }
+
+    #[tokio::test]
+    async fn test_force_disconnect_revokes_resume_token() {
+        let sessions = SessionStore::new();
+        let store = ConnectionStore::new().with_sessions(sessions.clone());
+        let info = ConnectionInfo::new(Uuid::new_v4());
+        let token = sessions.open(info.id, None).await;
+        store.insert(info.clone(), queue()).await;
+
+        assert!(store.disconnect(info.id).await);
+        assert!(!sessions.detach(info.id, Vec::new(), 8).await);
+        let window = std::time::Duration::from_secs(60);
+        assert!(sessions.resume(&token, None, window).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let store = ConnectionStore::new();
+        let info = ConnectionInfo::new(Uuid::new_v4());
+        store.insert(info.clone(), queue()).await;
+
+        assert_eq!(store.send(info.id, Message::text("hi")).await, SendOutcome::Queued);
+        assert_eq!(store.queue_depth(info.id).await, Some(1));
+        assert_eq!(store.send(Uuid::new_v4(), Message::text("hi")).await, SendOutcome::Closed);
+    }
+}